@@ -0,0 +1,47 @@
+//! Compares walking with plain walkdir traversals, on a generated tree,
+//! or on the directory given, as in
+//! `cargo bench --features bench -- /mnt/nas/photos`.
+use mediawalker::bench::{best_of, measure, traverse};
+use mediawalker::testing::generate_tree;
+use mediawalker::MediaWalker;
+use std::env;
+use std::path::PathBuf;
+
+/// The generated tree: a library of a few thousand files, some not media.
+const SPEC: &str = "photos/2023/img{}.jpg 2000
+                    photos/2024/img{}.png 1000
+                    music/album/track{}.mp3 1000
+                    video/clip{}.mp4 500
+                    documents/note{}.txt 500";
+
+/// How many times each is run, the fastest counting.
+const RUNS: usize = 5;
+
+fn main() {
+    // Cargo passes `--bench` along with the arguments given after `--`.
+    let given = env::args().skip(1).find(|arg| !arg.starts_with("--"));
+    let generated = match given {
+        Some(_) => None,
+        None => Some(generate_tree(SPEC).expect("generating the tree")),
+    };
+    let root = match &generated {
+        Some(tree) => tree.path().to_path_buf(),
+        None => PathBuf::from(given.unwrap()),
+    };
+    let threads = std::thread::available_parallelism().map_or(4, |n| n.get());
+
+    println!("walking {}", root.display());
+    println!(
+        "walkdir                {}",
+        best_of(RUNS, || traverse(&root))
+    );
+    println!(
+        "mediawalker            {}",
+        best_of(RUNS, || measure(MediaWalker::new(&root)))
+    );
+    println!(
+        "mediawalker, {:>2} threads {}",
+        threads,
+        best_of(RUNS, || measure(MediaWalker::new(&root).threads(threads)))
+    );
+}
@@ -0,0 +1,106 @@
+//! Looks up the songs of a directory on AcoustID during the walk, filling
+//! in their MusicBrainz recording IDs and titles.
+//!
+//!     ACOUSTID_KEY=... cargo run --example acoustid -- ~/Music
+//!
+//! Needs Chromaprint's `fpcalc` and `curl`, and an application key from
+//! <https://acoustid.org/new-application>.
+use mediawalker::{Enricher, Kind, MediaWalkResult, MediaWalker};
+use std::env;
+use std::process::Command;
+
+/// Looks songs up by their fingerprints.
+struct AcoustId {
+    key: String,
+}
+
+impl AcoustId {
+    /// Runs a command, returning what it printed if it succeeded.
+    fn run(command: &mut Command) -> Option<String> {
+        let output = command.output().ok()?;
+        output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+impl Enricher for AcoustId {
+    fn enrich(&self, walk_result: &mut MediaWalkResult) {
+        if walk_result.kind != Some(Kind::Audio) {
+            return;
+        }
+        let Some(fingerprint) =
+            Self::run(Command::new("fpcalc").arg("-json").arg(&walk_result.path))
+        else {
+            return;
+        };
+        let (Some(duration), Some(fingerprint)) = (
+            value_after(&fingerprint, 0, "duration"),
+            value_after(&fingerprint, 0, "fingerprint"),
+        ) else {
+            return;
+        };
+        let duration = duration.parse::<f64>().unwrap_or_default().round();
+        let Some(response) = Self::run(
+            Command::new("curl")
+                .args(["--silent", "--fail"])
+                .args(["--data", &format!("client={}", self.key)])
+                .args(["--data", "meta=recordings"])
+                .args(["--data", &format!("duration={}", duration)])
+                .args(["--data-urlencode", &format!("fingerprint={}", fingerprint)])
+                .arg("https://api.acoustid.org/v2/lookup"),
+        ) else {
+            return;
+        };
+        // The best match comes first.
+        let annotations = &mut walk_result.annotations;
+        if let Some(id) = value_after(&response, 0, "id") {
+            annotations.insert("acoustid_id".to_string(), id);
+        }
+        if let Some(recordings) = response.find("\"recordings\"") {
+            if let Some(id) = value_after(&response, recordings, "id") {
+                annotations.insert("musicbrainz_recording_id".to_string(), id);
+            }
+            if let Some(title) = value_after(&response, recordings, "title") {
+                annotations.insert("title".to_string(), title);
+            }
+        }
+    }
+}
+
+/// The first value of a key in a JSON document after `from`, enough for
+/// the flat strings and numbers looked for here.
+fn value_after(json: &str, from: usize, key: &str) -> Option<String> {
+    let quoted = format!("\"{}\"", key);
+    let start = from + json[from..].find(&quoted)? + quoted.len();
+    let rest = json[start..].trim_start().strip_prefix(':')?.trim_start();
+    if let Some(string) = rest.strip_prefix('"') {
+        let end = string.find('"')?;
+        return Some(string[..end].to_string());
+    }
+    let end = rest.find([',', '}', ']']).unwrap_or(rest.len());
+    Some(rest[..end].trim().to_string())
+}
+
+fn main() {
+    let (Some(root), Ok(key)) = (env::args().nth(1), env::var("ACOUSTID_KEY")) else {
+        eprintln!("usage: ACOUSTID_KEY=... acoustid <directory>");
+        std::process::exit(2);
+    };
+    let walker = MediaWalker::new(root)
+        .kinds(&[Kind::Audio])
+        // Lookups are slow: make several at a time.
+        .stage_concurrency(Kind::Audio, 4)
+        .enricher(AcoustId { key });
+    for walk_result in walker.start() {
+        let title = walk_result.annotations.get("title");
+        let recording = walk_result.annotations.get("musicbrainz_recording_id");
+        match (title, recording) {
+            (Some(title), Some(recording)) => {
+                println!("{}: {} ({})", walk_result.path, title, recording)
+            }
+            _ => println!("{}: not found", walk_result.path),
+        }
+    }
+}
@@ -0,0 +1,71 @@
+/*
+ * The C API of mediawalker, built as a shared library with
+ * `cargo rustc --release --lib --features capi --crate-type cdylib`.
+ *
+ *     mediawalker_walk *walk = mediawalker_start("/home/me/Music");
+ *     mediawalker_result *result;
+ *     while ((result = mediawalker_next(walk)) != NULL) {
+ *         if (mediawalker_result_status(result) == 1)
+ *             printf("%s: %s\n", mediawalker_result_path(result),
+ *                    mediawalker_result_mime(result));
+ *         mediawalker_result_free(result);
+ *     }
+ *     mediawalker_free(walk);
+ */
+#ifndef MEDIAWALKER_H
+#define MEDIAWALKER_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct MediawalkerWalk mediawalker_walk;
+typedef struct MediawalkerResult mediawalker_result;
+
+#define MEDIAWALKER_KIND_UNKNOWN (-1)
+#define MEDIAWALKER_KIND_AUDIO 0
+#define MEDIAWALKER_KIND_IMAGE 1
+#define MEDIAWALKER_KIND_VIDEO 2
+#define MEDIAWALKER_KIND_COMPANION 3
+#define MEDIAWALKER_KIND_APPLICATION 4
+#define MEDIAWALKER_KIND_ARCHIVE 5
+#define MEDIAWALKER_KIND_DOCUMENT 6
+#define MEDIAWALKER_KIND_EBOOK 7
+#define MEDIAWALKER_KIND_FONT 8
+#define MEDIAWALKER_KIND_TEXT 9
+
+/* Starts walking a UTF-8 path. Returns NULL if it is not valid UTF-8. */
+mediawalker_walk *mediawalker_start(const char *root);
+/*
+ * Starts walking a UTF-8 path for the kind_count MEDIAWALKER_KIND_* codes
+ * at kinds, or the default kinds if kinds is NULL, on threads threads, or
+ * the default count if 0. Returns NULL if the path is not valid UTF-8 or
+ * a kind is unknown.
+ */
+mediawalker_walk *mediawalker_start_with(const char *root, const int *kinds,
+                                         size_t kind_count, size_t threads);
+/* Waits for the next result. Returns NULL once the walk is over. */
+mediawalker_result *mediawalker_next(mediawalker_walk *walk);
+/* Stops a walk, from any thread. */
+void mediawalker_cancel(const mediawalker_walk *walk);
+/* Stops and frees a walk. */
+void mediawalker_free(mediawalker_walk *walk);
+
+/* The strings are valid until the result is freed. */
+const char *mediawalker_result_path(const mediawalker_result *result);
+const char *mediawalker_result_mime(const mediawalker_result *result);
+/* One of MEDIAWALKER_KIND_*. */
+int mediawalker_result_kind(const mediawalker_result *result);
+/* 1 if a media type was found, 0 if not, -1 on error. */
+int mediawalker_result_status(const mediawalker_result *result);
+/* The error message, or NULL if the file could be inspected. */
+const char *mediawalker_result_error(const mediawalker_result *result);
+void mediawalker_result_free(mediawalker_result *result);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
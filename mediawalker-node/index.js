@@ -0,0 +1,119 @@
+'use strict';
+// Node.js bindings of mediawalker. Build the native module with
+//
+//     cargo rustc --release --lib --features node --crate-type cdylib
+//
+// and copy the library to mediawalker.node next to this file, or point
+// MEDIAWALKER_NODE at it. Then
+//
+//     const { walk } = require('./mediawalker-node');
+//     for await (const result of walk('/data/photos', { kinds: ['image'] })) {
+//       console.log(result.path, result.mime);
+//     }
+//
+// or, as events,
+//
+//     walk('/data/photos')
+//       .on('result', (result) => console.log(result.path))
+//       .on('end', () => console.log('done'));
+const { EventEmitter } = require('events');
+const path = require('path');
+
+let native = null;
+
+// Loads the native module once, whatever the extension of its file.
+function load() {
+  if (native === null) {
+    const module = { exports: {} };
+    const file = process.env.MEDIAWALKER_NODE || path.join(__dirname, 'mediawalker.node');
+    process.dlopen(module, file);
+    native = module.exports;
+  }
+  return native;
+}
+
+// A running walk. Each result is an object with the `path`, `mime` type,
+// `kind` name or null, `status` and `error` of a file. The status is
+// 'found' if a media type was found, 'unknown' if not, and 'error' if the
+// file could not be inspected, with the message in `error`.
+//
+// Iterate over it asynchronously, or listen to its 'result' events and
+// its 'end' event, but not both.
+class Walk extends EventEmitter {
+  // `options.kinds` names the kinds to report instead of the default ones,
+  // like ['audio', 'image'], and `options.threads` is the number of
+  // threads inspecting files.
+  constructor(root, options = {}) {
+    super();
+    this._walk = load().start(String(root), options.kinds, options.threads);
+    this._last = Promise.resolve();
+    this._ended = false;
+    this._emitting = false;
+    this.on('newListener', (event) => {
+      if (event === 'result' && !this._emitting) {
+        this._emitting = true;
+        process.nextTick(() => this._emit());
+      }
+    });
+  }
+
+  // Stops the walk. Results already on their way may still be reported.
+  cancel() {
+    native.cancel(this._walk);
+  }
+
+  // The next result, or null once the walk is over. Calls are queued, so
+  // that each gets a result of its own.
+  _next() {
+    const next = this._last.then(() => {
+      if (this._ended) {
+        return null;
+      }
+      return new Promise((resolve) => {
+        native.next(this._walk, (result) => {
+          if (result === null) {
+            this._ended = true;
+          }
+          resolve(result);
+        });
+      });
+    });
+    this._last = next;
+    return next;
+  }
+
+  async _emit() {
+    for (;;) {
+      const result = await this._next();
+      if (result === null) {
+        break;
+      }
+      this.emit('result', result);
+    }
+    this.emit('end');
+  }
+
+  [Symbol.asyncIterator]() {
+    return {
+      next: async () => {
+        const result = await this._next();
+        return result === null ? { done: true, value: undefined } : { done: false, value: result };
+      },
+      // Breaking out of a loop stops the walk.
+      return: async () => {
+        this.cancel();
+        return { done: true, value: undefined };
+      },
+      [Symbol.asyncIterator]() {
+        return this;
+      },
+    };
+  }
+}
+
+// Starts walking `root`, see `Walk`.
+function walk(root, options) {
+  return new Walk(root, options);
+}
+
+module.exports = { walk, Walk };
@@ -0,0 +1,131 @@
+"""Python bindings of mediawalker, over its C API.
+
+Build the shared library with
+
+    cargo rustc --release --lib --features capi --crate-type cdylib
+
+and put it next to this module, or point ``MEDIAWALKER_LIB`` at it. Then
+
+    import mediawalker
+    for result in mediawalker.walk("/data/photos", kinds=["image"], threads=4):
+        print(result["path"], result["mime"])
+"""
+import ctypes
+import os
+import sys
+
+__all__ = ["walk", "KINDS"]
+
+#: The kinds, by their ``MEDIAWALKER_KIND_*`` codes in ``mediawalker.h``.
+KINDS = [
+    "audio",
+    "image",
+    "video",
+    "companion",
+    "application",
+    "archive",
+    "document",
+    "ebook",
+    "font",
+    "text",
+]
+
+_lib = None
+
+
+def _library():
+    """Loads the shared library once, from ``MEDIAWALKER_LIB`` or next to
+    this module."""
+    global _lib
+    if _lib is not None:
+        return _lib
+    path = os.environ.get("MEDIAWALKER_LIB")
+    if path is None:
+        name = {"darwin": "libmediawalker.dylib", "win32": "mediawalker.dll"}.get(
+            sys.platform, "libmediawalker.so"
+        )
+        path = os.path.join(os.path.dirname(os.path.abspath(__file__)), name)
+    lib = ctypes.CDLL(path)
+    walk_p = ctypes.c_void_p
+    result_p = ctypes.c_void_p
+    lib.mediawalker_start_with.argtypes = [
+        ctypes.c_char_p,
+        ctypes.POINTER(ctypes.c_int),
+        ctypes.c_size_t,
+        ctypes.c_size_t,
+    ]
+    lib.mediawalker_start_with.restype = walk_p
+    lib.mediawalker_next.argtypes = [walk_p]
+    lib.mediawalker_next.restype = result_p
+    lib.mediawalker_free.argtypes = [walk_p]
+    lib.mediawalker_free.restype = None
+    for name in ("path", "mime", "error"):
+        function = getattr(lib, "mediawalker_result_" + name)
+        function.argtypes = [result_p]
+        function.restype = ctypes.c_char_p
+    for name in ("kind", "status"):
+        function = getattr(lib, "mediawalker_result_" + name)
+        function.argtypes = [result_p]
+        function.restype = ctypes.c_int
+    lib.mediawalker_result_free.argtypes = [result_p]
+    lib.mediawalker_result_free.restype = None
+    _lib = lib
+    return lib
+
+
+def walk(path, kinds=None, threads=None):
+    """Walks ``path``, yielding a dict per file found, with its ``path``,
+    ``mime`` type, ``kind`` name or None, ``status`` and ``error``. The
+    status is ``"found"`` if a media type was found, ``"unknown"`` if not,
+    and ``"error"`` if the file could not be inspected, with the message
+    in ``error``.
+
+    ``kinds`` is a list of kind names to report instead of the default
+    ones, like ``["audio", "image"]``, and ``threads`` the number of
+    threads inspecting files. The walk stops when the generator is closed
+    or collected.
+    """
+    lib = _library()
+    if kinds is None:
+        codes, count = None, 0
+    else:
+        try:
+            indices = [KINDS.index(kind.lower()) for kind in kinds]
+        except ValueError:
+            raise ValueError("unknown kinds: %r" % (kinds,)) from None
+        codes = (ctypes.c_int * len(indices))(*indices)
+        count = len(indices)
+    if threads is not None and threads < 1:
+        raise ValueError("threads must be at least 1")
+    handle = lib.mediawalker_start_with(
+        os.fsencode(path), codes, count, threads or 0
+    )
+    if not handle:
+        raise ValueError("not a UTF-8 path: %r" % (path,))
+    try:
+        while True:
+            result = lib.mediawalker_next(handle)
+            if not result:
+                return
+            try:
+                yield _result(lib, result)
+            finally:
+                lib.mediawalker_result_free(result)
+    finally:
+        lib.mediawalker_free(handle)
+
+
+_STATUSES = {1: "found", 0: "unknown", -1: "error"}
+
+
+def _result(lib, result):
+    """A result as a dict."""
+    kind = lib.mediawalker_result_kind(result)
+    error = lib.mediawalker_result_error(result)
+    return {
+        "path": os.fsdecode(lib.mediawalker_result_path(result)),
+        "mime": lib.mediawalker_result_mime(result).decode(),
+        "kind": KINDS[kind] if 0 <= kind < len(KINDS) else None,
+        "status": _STATUSES[lib.mediawalker_result_status(result)],
+        "error": error.decode() if error is not None else None,
+    }
@@ -0,0 +1,99 @@
+//! Telling from their metadata the files the walk may not read, see
+//! [`MediaWalker::check_permissions`](crate::MediaWalker::check_permissions).
+use std::fs::Metadata;
+
+#[cfg(unix)]
+extern "C" {
+    fn geteuid() -> u32;
+    fn getegid() -> u32;
+    fn getgroups(size: i32, list: *mut u32) -> i32;
+}
+
+/// Whether the permission bits of a file let the process read it. Where
+/// they are not to be relied on, as for the superuser, or off Unix, files
+/// are taken to be readable, and get opened.
+pub(crate) fn may_read(metadata: &Metadata) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        // SAFETY: These only read the credentials of the process.
+        let (uid, gid) = unsafe { (geteuid(), getegid()) };
+        if uid == 0 {
+            return true;
+        }
+        let mut groups = vec![0; 64];
+        // SAFETY: The list holds as many groups as it is said to.
+        let count = unsafe { getgroups(groups.len() as i32, groups.as_mut_ptr()) };
+        // More groups than fit: some could grant the read.
+        if count < 0 {
+            return true;
+        }
+        groups.truncate(count as usize);
+        groups.push(gid);
+        allows_read(
+            metadata.mode(),
+            metadata.uid(),
+            metadata.gid(),
+            uid,
+            &groups,
+        )
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        true
+    }
+}
+
+/// Whether the mode of a file, owned by `owner` and `group`, lets a user
+/// in `groups` read it. As in the system's own check, only the bits of
+/// the owner apply to them, then only those of the group.
+#[cfg(unix)]
+fn allows_read(mode: u32, owner: u32, group: u32, uid: u32, groups: &[u32]) -> bool {
+    if owner == uid {
+        mode & 0o400 != 0
+    } else if groups.contains(&group) {
+        mode & 0o040 != 0
+    } else {
+        mode & 0o004 != 0
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::testutil::TempTree;
+    use crate::{MediaWalker, SkipReason, WalkEvent};
+    use std::fs::{self, Permissions};
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn it_tells_unreadable_files() {
+        assert!(allows_read(0o400, 1000, 1000, 1000, &[1000]));
+        assert!(!allows_read(0o044, 1000, 1000, 1000, &[1000]));
+        assert!(allows_read(0o640, 1000, 100, 1001, &[100]));
+        assert!(!allows_read(0o604, 1000, 100, 1001, &[100]));
+        assert!(allows_read(0o604, 1000, 100, 1001, &[1001]));
+        assert!(!allows_read(0o640, 1000, 100, 1001, &[1001]));
+
+        let tree = TempTree::new("access");
+        tree.sample("sample_640x426.jpg", "mine.jpg");
+        let theirs = tree.sample("sample_640x426.jpg", "theirs.jpg");
+        fs::set_permissions(&theirs, Permissions::from_mode(0o000)).unwrap();
+        let mut handle = MediaWalker::new(tree.path())
+            .check_permissions(true)
+            .start();
+        let events: Vec<_> = handle.events().collect();
+        let skipped = events.iter().any(|event| {
+            matches!(event, WalkEvent::Skipped { path, reason: SkipReason::NoPermission }
+                if path == &theirs)
+        });
+        let found = events
+            .iter()
+            .filter(|event| matches!(event, WalkEvent::Found(_)))
+            .count();
+        // The superuser reads the file whatever its mode.
+        let root = unsafe { geteuid() } == 0;
+        assert_eq!((skipped, found), if root { (false, 2) } else { (true, 1) });
+    }
+}
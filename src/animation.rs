@@ -0,0 +1,191 @@
+//! Telling animated images from still ones by their containers, see
+//! [`MediaWalker::detect_animation`](crate::MediaWalker::detect_animation).
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Whether a GIF, PNG, WebP, AVIF or HEIF image is animated, by its MIME type.
+/// `None` for other types, and for files that cannot be parsed.
+pub(crate) fn is_animated(path: &Path, mime: &str) -> Option<bool> {
+    let mut file = BufReader::new(File::open(path).ok()?);
+    match mime {
+        "image/gif" => gif(&mut file),
+        "image/png" => apng(&mut file),
+        "image/webp" => webp(&mut file),
+        "image/avif" => avif(&mut file),
+        "image/heif-sequence" => Ok(true),
+        _ => return None,
+    }
+    .ok()
+}
+
+/// Whether a GIF has more than one frame, reading up to the second.
+fn gif<R: Read + Seek>(reader: &mut R) -> io::Result<bool> {
+    let mut header = [0; 13];
+    reader.read_exact(&mut header)?;
+    if !header.starts_with(b"GIF8") {
+        return Err(io::ErrorKind::InvalidData.into());
+    }
+    skip_color_table(reader, header[10])?;
+    let mut frames = 0;
+    loop {
+        let mut introducer = [0; 1];
+        reader.read_exact(&mut introducer)?;
+        match introducer[0] {
+            // An extension: a label, then data sub-blocks.
+            0x21 => {
+                reader.seek(SeekFrom::Current(1))?;
+                skip_sub_blocks(reader)?;
+            }
+            // An image: a descriptor, a local color table, the minimum
+            // code size, then the compressed data in sub-blocks.
+            0x2c => {
+                frames += 1;
+                if frames > 1 {
+                    return Ok(true);
+                }
+                let mut descriptor = [0; 9];
+                reader.read_exact(&mut descriptor)?;
+                skip_color_table(reader, descriptor[8])?;
+                reader.seek(SeekFrom::Current(1))?;
+                skip_sub_blocks(reader)?;
+            }
+            // The trailer.
+            0x3b => return Ok(false),
+            _ => return Err(io::ErrorKind::InvalidData.into()),
+        }
+    }
+}
+
+/// Skips the color table a GIF's packed fields tell of, if any.
+fn skip_color_table<R: Read + Seek>(reader: &mut R, packed: u8) -> io::Result<()> {
+    if packed & 0x80 != 0 {
+        let len = 3 << ((packed & 0x07) + 1);
+        reader.seek(SeekFrom::Current(len))?;
+    }
+    Ok(())
+}
+
+/// Skips GIF data sub-blocks, up to the empty one that ends them.
+fn skip_sub_blocks<R: Read + Seek>(reader: &mut R) -> io::Result<()> {
+    loop {
+        let mut len = [0; 1];
+        reader.read_exact(&mut len)?;
+        if len[0] == 0 {
+            return Ok(());
+        }
+        reader.seek(SeekFrom::Current(i64::from(len[0])))?;
+    }
+}
+
+/// Whether a PNG is an APNG: one with an animation control chunk, for
+/// more than one frame, before the image data.
+fn apng<R: Read + Seek>(reader: &mut R) -> io::Result<bool> {
+    let mut signature = [0; 8];
+    reader.read_exact(&mut signature)?;
+    if &signature != b"\x89PNG\r\n\x1a\n" {
+        return Err(io::ErrorKind::InvalidData.into());
+    }
+    loop {
+        let mut chunk = [0; 8];
+        reader.read_exact(&mut chunk)?;
+        let len = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        match &chunk[4..] {
+            b"acTL" => {
+                let mut frames = [0; 4];
+                reader.read_exact(&mut frames)?;
+                return Ok(u32::from_be_bytes(frames) > 1);
+            }
+            b"IDAT" | b"IEND" => return Ok(false),
+            _ => reader.seek(SeekFrom::Current(i64::from(len) + 4))?,
+        };
+    }
+}
+
+/// Whether a WebP is animated, as its extended header tells.
+fn webp<R: Read>(reader: &mut R) -> io::Result<bool> {
+    let mut header = [0; 21];
+    reader.read_exact(&mut header)?;
+    if &header[..4] != b"RIFF" || &header[8..12] != b"WEBP" {
+        return Err(io::ErrorKind::InvalidData.into());
+    }
+    // Simple files, lossy or lossless, are never animated.
+    Ok(&header[12..16] == b"VP8X" && header[20] & 0x02 != 0)
+}
+
+/// Whether an AVIF is an image sequence, as its brands tell.
+fn avif<R: Read>(reader: &mut R) -> io::Result<bool> {
+    let mut header = [0; 8];
+    reader.read_exact(&mut header)?;
+    if &header[4..] != b"ftyp" {
+        return Err(io::ErrorKind::InvalidData.into());
+    }
+    let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+    let mut brands = vec![0; (len as usize).saturating_sub(8).min(1024)];
+    reader.read_exact(&mut brands)?;
+    // The major brand, the minor version, then the compatible brands.
+    Ok(brands
+        .chunks_exact(4)
+        .enumerate()
+        .any(|(i, brand)| i != 1 && brand == b"avis"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// A GIF of `frames` 1x1 frames, with a global color table.
+    fn gif_of(frames: usize) -> Vec<u8> {
+        let mut bytes = b"GIF89a\x01\0\x01\0\x80\0\0".to_vec();
+        bytes.extend([0; 6]);
+        // A looping extension.
+        bytes.extend(b"\x21\xff\x0bNETSCAPE2.0\x03\x01\0\0\0");
+        for _ in 0..frames {
+            bytes.extend(b"\x21\xf9\x04\0\x0a\0\0\0");
+            bytes.extend(b"\x2c\0\0\0\0\x01\0\x01\0\0\x02\x02\x44\x01\0");
+        }
+        bytes.push(0x3b);
+        bytes
+    }
+
+    #[test]
+    fn it_detects_animation() {
+        assert!(!gif(&mut Cursor::new(gif_of(1))).unwrap());
+        assert!(gif(&mut Cursor::new(gif_of(3))).unwrap());
+
+        let png = |chunks: &[(&[u8; 4], &[u8])]| {
+            let mut bytes = b"\x89PNG\r\n\x1a\n".to_vec();
+            for (kind, data) in chunks {
+                bytes.extend((data.len() as u32).to_be_bytes());
+                bytes.extend(*kind);
+                bytes.extend(*data);
+                bytes.extend([0; 4]);
+            }
+            apng(&mut Cursor::new(bytes)).unwrap()
+        };
+        let ihdr: &[u8] = &[0; 13];
+        assert!(!png(&[(b"IHDR", ihdr), (b"IDAT", b"")]));
+        let actl: &[u8] = &[0, 0, 0, 4, 0, 0, 0, 0];
+        assert!(png(&[(b"IHDR", ihdr), (b"acTL", actl), (b"IDAT", b"")]));
+
+        let webp_of = |chunk: &[u8; 4], flags: u8| {
+            let mut bytes = b"RIFF\0\0\0\0WEBP".to_vec();
+            bytes.extend(chunk);
+            bytes.extend([10, 0, 0, 0, flags]);
+            webp(&mut Cursor::new(bytes)).unwrap()
+        };
+        assert!(webp_of(b"VP8X", 0x12));
+        assert!(!webp_of(b"VP8X", 0x10));
+        assert!(!webp_of(b"VP8L", 0x02));
+
+        let avif_of = |brands: &[u8]| {
+            let mut bytes = ((brands.len() + 8) as u32).to_be_bytes().to_vec();
+            bytes.extend(b"ftyp");
+            bytes.extend(brands);
+            avif(&mut Cursor::new(bytes)).unwrap()
+        };
+        assert!(avif_of(b"avis\0\0\0\0avifavismif1"));
+        assert!(!avif_of(b"avif\0\0\0\0avifmif1miaf"));
+    }
+}
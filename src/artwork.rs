@@ -0,0 +1,338 @@
+//! Album art of songs, see
+//! [`MediaWalker::artwork`](crate::MediaWalker::artwork): pictures
+//! embedded in ID3v2 tags and FLAC files, or the cover images next to
+//! the songs, like `cover.jpg` or `folder.png`.
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// The names of cover images, without their extensions, in the order
+/// they are preferred.
+const COVER_NAMES: &[&str] = &["cover", "folder", "front", "album", "albumart"];
+
+/// The extensions of cover images.
+const COVER_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp"];
+
+/// An ID3v2 picture type: the front cover.
+const FRONT_COVER: u8 = 3;
+
+/// How much of a picture frame is read for its MIME type and
+/// description at most.
+const MAX_PICTURE_HEADER: u64 = 4096;
+
+/// Where the album art of a song, or of a directory of songs, is, see
+/// [`MediaWalkResult::artwork`](crate::MediaWalkResult::artwork).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArtworkRef {
+    /// The image at this path, next to the songs.
+    File(String),
+    /// The image embedded in the song.
+    Embedded {
+        /// The image's MIME type, as the tag has it, like `image/jpeg`.
+        mime: String,
+        /// Where the image starts in the song, in bytes.
+        offset: u64,
+        /// The size of the image, in bytes.
+        len: u64,
+    },
+}
+
+impl ArtworkRef {
+    /// Reads the image, embedded in `song` or not.
+    pub fn read<P: AsRef<Path>>(&self, song: P) -> io::Result<Vec<u8>> {
+        match self {
+            ArtworkRef::File(path) => fs::read(path),
+            ArtworkRef::Embedded { offset, len, .. } => {
+                let mut file = File::open(song)?;
+                file.seek(SeekFrom::Start(*offset))?;
+                let mut image = vec![];
+                file.take(*len).read_to_end(&mut image)?;
+                if image.len() as u64 != *len {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                Ok(image)
+            }
+        }
+    }
+}
+
+/// The picture embedded in a song, preferring the front cover if there
+/// are several.
+pub(crate) fn embedded(path: &Path, mime: &str) -> Option<ArtworkRef> {
+    let mut file = BufReader::new(File::open(path).ok()?);
+    match mime {
+        "audio/x-flac" | "audio/flac" => flac_picture(&mut file).ok()?,
+        _ => id3_picture(&mut file).ok()?,
+    }
+}
+
+/// The cover image in a directory, if there is one, by the preferred
+/// names of [`COVER_NAMES`], ignoring case. `open` is the directory to
+/// list, and `dir` the directory as reported.
+pub(crate) fn in_dir(open: &Path, dir: &Path) -> Option<ArtworkRef> {
+    let mut best: Option<(usize, String)> = None;
+    for entry in fs::read_dir(open).ok()?.flatten() {
+        let name = entry.file_name();
+        let Some(name) = name.to_str() else {
+            continue;
+        };
+        let lower = name.to_lowercase();
+        let Some((stem, extension)) = lower.rsplit_once('.') else {
+            continue;
+        };
+        if !COVER_EXTENSIONS.contains(&extension) {
+            continue;
+        }
+        let Some(rank) = COVER_NAMES.iter().position(|cover| *cover == stem) else {
+            continue;
+        };
+        if best.as_ref().is_none_or(|(best, _)| rank < *best) && entry.path().is_file() {
+            best = Some((rank, name.to_string()));
+        }
+    }
+    best.map(|(_, name)| ArtworkRef::File(dir.join(name).to_string_lossy().into_owned()))
+}
+
+/// The picture of an ID3v2.3 or ID3v2.4 tag at the start of a file, from
+/// its `APIC` frames.
+fn id3_picture<R: Read + Seek>(file: &mut R) -> io::Result<Option<ArtworkRef>> {
+    let mut header = [0; 10];
+    file.read_exact(&mut header)?;
+    let version = header[3];
+    // Unsynchronised tags do not keep the pictures as they are.
+    if &header[..3] != b"ID3" || !(3..=4).contains(&version) || header[5] & 0x80 != 0 {
+        return Ok(None);
+    }
+    let end = 10 + syncsafe(&header[6..10]);
+    let mut at = 10;
+    if header[5] & 0x40 != 0 {
+        let mut size = [0; 4];
+        file.read_exact(&mut size)?;
+        at += match version {
+            3 => 4 + u32::from_be_bytes(size) as u64,
+            _ => syncsafe(&size),
+        };
+    }
+    let mut found = None;
+    while at + 10 <= end {
+        file.seek(SeekFrom::Start(at))?;
+        let mut frame = [0; 10];
+        file.read_exact(&mut frame)?;
+        if frame[0] == 0 {
+            break;
+        }
+        let size = match version {
+            3 => u32::from_be_bytes([frame[4], frame[5], frame[6], frame[7]]) as u64,
+            _ => syncsafe(&frame[4..8]),
+        };
+        let start = at + 10;
+        at = start + size;
+        // Compressed, encrypted or unsynchronised frames are left alone.
+        let altered = match version {
+            3 => frame[9] & 0xc0 != 0,
+            _ => frame[9] & 0x0f != 0,
+        };
+        if &frame[..4] != b"APIC" || altered || at > end {
+            continue;
+        }
+        let mut body = vec![];
+        file.by_ref()
+            .take(size.min(MAX_PICTURE_HEADER))
+            .read_to_end(&mut body)?;
+        let Some((mime, picture_type, skipped)) = apic_header(&body) else {
+            continue;
+        };
+        let picture = ArtworkRef::Embedded {
+            mime,
+            offset: start + skipped as u64,
+            len: size - skipped as u64,
+        };
+        if picture_type == FRONT_COVER {
+            return Ok(Some(picture));
+        }
+        found = found.or(Some(picture));
+    }
+    Ok(found)
+}
+
+/// The MIME type and picture type of an `APIC` frame, and where its
+/// picture starts.
+fn apic_header(body: &[u8]) -> Option<(String, u8, usize)> {
+    let encoding = *body.first()?;
+    let mime_len = body[1..].iter().position(|&b| b == 0)?;
+    let mime = String::from_utf8_lossy(&body[1..1 + mime_len]).into_owned();
+    let mut at = 1 + mime_len + 1;
+    let picture_type = *body.get(at)?;
+    at += 1;
+    // The description ends in a null character of the encoding.
+    at += match encoding {
+        1 | 2 => {
+            let pairs = body.get(at..)?.chunks_exact(2);
+            2 * pairs.take_while(|pair| pair != &[0, 0]).count() + 2
+        }
+        _ => body.get(at..)?.iter().position(|&b| b == 0)? + 1,
+    };
+    let mime = match mime.to_ascii_lowercase().as_str() {
+        // Version 2.2 image formats, which some taggers still write.
+        "jpg" | "jpeg" => "image/jpeg".to_string(),
+        "png" => "image/png".to_string(),
+        _ => mime,
+    };
+    (at <= body.len()).then_some((mime, picture_type, at))
+}
+
+/// A 28-bit integer of 7 bits a byte, as ID3v2 sizes are.
+fn syncsafe(bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .fold(0, |size, &byte| size << 7 | (byte & 0x7f) as u64)
+}
+
+/// The picture of a FLAC file, from its `PICTURE` metadata blocks.
+fn flac_picture<R: Read + Seek>(file: &mut R) -> io::Result<Option<ArtworkRef>> {
+    let mut magic = [0; 4];
+    file.read_exact(&mut magic)?;
+    if &magic != b"fLaC" {
+        return Ok(None);
+    }
+    let mut at = 4;
+    let mut found = None;
+    loop {
+        file.seek(SeekFrom::Start(at))?;
+        let mut header = [0; 4];
+        file.read_exact(&mut header)?;
+        let last = header[0] & 0x80 != 0;
+        let size = u32::from_be_bytes([0, header[1], header[2], header[3]]) as u64;
+        let start = at + 4;
+        at = start + size;
+        if header[0] & 0x7f == 6 {
+            let read_u32 = |file: &mut R| -> io::Result<u32> {
+                let mut word = [0; 4];
+                file.read_exact(&mut word)?;
+                Ok(u32::from_be_bytes(word))
+            };
+            let picture_type = read_u32(file)?;
+            let mime_len = read_u32(file)?;
+            let mut mime = vec![0; mime_len.min(256) as usize];
+            file.read_exact(&mut mime)?;
+            let description_len = read_u32(file)? as i64;
+            // The width, height, color depth and number of colors.
+            file.seek(SeekFrom::Current(description_len + 16))?;
+            let len = read_u32(file)? as u64;
+            let offset = start + 4 + 4 + mime_len as u64 + 4 + description_len as u64 + 16 + 4;
+            if offset + len <= at {
+                let picture = ArtworkRef::Embedded {
+                    mime: String::from_utf8_lossy(&mime).into_owned(),
+                    offset,
+                    len,
+                };
+                if picture_type == FRONT_COVER as u32 {
+                    return Ok(Some(picture));
+                }
+                found = found.or(Some(picture));
+            }
+        }
+        if last {
+            return Ok(found);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempTree;
+    use crate::MediaWalker;
+    use std::io::Cursor;
+
+    /// An ID3v2.3 tag with a picture of each type, and some audio.
+    fn id3(pictures: &[(u8, &[u8])]) -> Vec<u8> {
+        let mut frames = vec![];
+        frames.extend(b"TIT2\0\0\0\x06\0\0\0Title");
+        for (picture_type, image) in pictures {
+            let mut body = b"\x01image/png\0".to_vec();
+            body.push(*picture_type);
+            // "Cover", in UTF-16 with a byte order mark.
+            body.extend(b"\xff\xfeC\0o\0v\0e\0r\0\0\0");
+            body.extend(*image);
+            frames.extend(b"APIC");
+            frames.extend((body.len() as u32).to_be_bytes());
+            frames.extend([0, 0]);
+            frames.extend(body);
+        }
+        frames.extend([0; 16]);
+        let size = frames.len() as u32;
+        let mut tag = b"ID3\x03\0\0".to_vec();
+        tag.extend((0..4).rev().map(|i| (size >> (7 * i)) as u8 & 0x7f));
+        tag.extend(frames);
+        tag.extend(b"\xff\xfb\x90\x00 audio");
+        tag
+    }
+
+    fn flac(picture_type: u32, image: &[u8]) -> Vec<u8> {
+        let mut picture = vec![];
+        picture.extend(picture_type.to_be_bytes());
+        picture.extend(10u32.to_be_bytes());
+        picture.extend(b"image/jpeg");
+        picture.extend(4u32.to_be_bytes());
+        picture.extend(b"Back");
+        picture.extend([0; 16]);
+        picture.extend((image.len() as u32).to_be_bytes());
+        picture.extend(image);
+        let mut file = b"fLaC".to_vec();
+        file.extend([0, 0, 0, 34]);
+        file.extend([0; 34]);
+        file.push(0x80 | 6);
+        file.extend(&(picture.len() as u32).to_be_bytes()[1..]);
+        file.extend(picture);
+        file
+    }
+
+    #[test]
+    fn it_finds_embedded_pictures() {
+        let tag = id3(&[(0, b"other"), (FRONT_COVER, b"front")]);
+        let picture = id3_picture(&mut Cursor::new(&tag)).unwrap().unwrap();
+        let ArtworkRef::Embedded { mime, offset, len } = &picture else {
+            panic!("{:?}", picture);
+        };
+        assert_eq!(mime, "image/png");
+        assert_eq!(&tag[*offset as usize..(offset + len) as usize], b"front");
+
+        let tag = id3(&[(0, b"other")]);
+        assert!(id3_picture(&mut Cursor::new(&tag)).unwrap().is_some());
+        assert_eq!(id3_picture(&mut Cursor::new(id3(&[]))).unwrap(), None);
+
+        let tree = TempTree::new("artwork-flac");
+        let song = tree.file("song.flac", &flac(4, b"\xff\xd8 back"));
+        let picture = embedded(&song, "audio/x-flac").unwrap();
+        assert!(matches!(&picture, ArtworkRef::Embedded { mime, .. } if mime == "image/jpeg"));
+        assert_eq!(picture.read(&song).unwrap(), b"\xff\xd8 back");
+    }
+
+    #[test]
+    fn it_finds_cover_images() {
+        let tree = TempTree::new("artwork-dir");
+        tree.file("album/Folder.PNG", b"png");
+        tree.file("album/cover.jpg", b"jpg");
+        tree.file("album/back.jpg", b"jpg");
+        tree.file("other/front.txt", b"text");
+        let album = tree.path().join("album");
+        assert_eq!(
+            in_dir(&album, Path::new("music/album")),
+            Some(ArtworkRef::File("music/album/cover.jpg".to_string()))
+        );
+        assert_eq!(in_dir(&tree.path().join("other"), Path::new("other")), None);
+
+        let song = tree.sample("sample3.mp3", "album/01.mp3");
+        let cover = tree.path().join("album/cover.jpg");
+        let results: Vec<_> = MediaWalker::new(tree.path())
+            .artwork(true)
+            .start()
+            .collect();
+        let song = results.iter().find(|r| Path::new(&r.path) == song).unwrap();
+        assert_eq!(
+            song.artwork,
+            Some(ArtworkRef::File(cover.to_string_lossy().into_owned()))
+        );
+    }
+}
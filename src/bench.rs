@@ -0,0 +1,96 @@
+//! Measuring walks against plain directory traversals, on generated
+//! trees or the storage at hand, as `cargo bench --features bench` does,
+//! see `benches/walk.rs`.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use mediawalker::bench::{best_of, measure, traverse};
+//! use mediawalker::MediaWalker;
+//! let root = "/mnt/nas/photos";
+//! println!("walkdir      {}", best_of(3, || traverse(root)));
+//! println!("mediawalker  {}", best_of(3, || measure(MediaWalker::new(root).threads(8))));
+//! ```
+use crate::MediaWalker;
+use std::fmt;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use walkdir::WalkDir;
+
+/// How many files a run went through, and how long it took.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Measurement {
+    /// The files found.
+    pub files: u64,
+    /// How long the run took.
+    pub elapsed: Duration,
+}
+
+impl Measurement {
+    /// The files found per second.
+    pub fn files_per_sec(&self) -> f64 {
+        self.files as f64 / self.elapsed.as_secs_f64().max(f64::MIN_POSITIVE)
+    }
+}
+
+impl fmt::Display for Measurement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} files in {:.1} ms, {:.0} files/s",
+            self.files,
+            self.elapsed.as_secs_f64() * 1000.0,
+            self.files_per_sec()
+        )
+    }
+}
+
+/// Traverses a tree with walkdir alone, telling files apart by the type
+/// of their entries: the most a walk could go at, not reading any file.
+pub fn traverse<P: AsRef<Path>>(root: P) -> Measurement {
+    let started = Instant::now();
+    let files = WalkDir::new(root)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+        .count();
+    Measurement {
+        files: files as u64,
+        elapsed: started.elapsed(),
+    }
+}
+
+/// Runs a walk to its end, counting its results, whatever they are.
+pub fn measure(walker: MediaWalker) -> Measurement {
+    let started = Instant::now();
+    let files = walker.start().count();
+    Measurement {
+        files: files as u64,
+        elapsed: started.elapsed(),
+    }
+}
+
+/// The fastest of a few runs, the others being slowed down by whatever
+/// else the machine was doing. The first run also warms up the caches
+/// of the filesystem.
+pub fn best_of(runs: usize, mut run: impl FnMut() -> Measurement) -> Measurement {
+    (0..runs.max(1))
+        .map(|_| run())
+        .min_by_key(|measurement| measurement.elapsed)
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::generate_tree;
+
+    #[test]
+    fn it_measures_walks_and_traversals() {
+        let tree = generate_tree("a/img{}.jpg 20\nb/song{}.mp3 10\nnotes.txt").unwrap();
+        let traversed = best_of(2, || traverse(tree.path()));
+        let walked = best_of(2, || measure(MediaWalker::new(tree.path())));
+        assert_eq!((traversed.files, walked.files), (31, 31));
+        assert!(traversed.to_string().starts_with("31 files in "));
+    }
+}
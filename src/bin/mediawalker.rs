@@ -0,0 +1,275 @@
+//! The `mediawalker` command, with the subcommands of the features it
+//! is built with.
+use mediawalker::{SkipReason, WalkConfig, WalkEvent};
+use std::env;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+const USAGE: &str = "usage: mediawalker scan --root PATH [--config FILE] [--filter EXPRESSION]
+                       [--skip-inaccessible | --require-all]
+       mediawalker serve --root PATH [--listen ADDRESS]
+       mediawalker grpc --root PATH [--listen ADDRESS]
+       mediawalker dbus
+       mediawalker daemon --config FILE [--interval INTERVAL]";
+
+/// The options that take no value.
+const FLAGS: [&str; 2] = ["skip-inaccessible", "require-all"];
+
+/// What a scan does with the paths it may not read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Inaccessible {
+    /// Prints an error for each.
+    Report,
+    /// Skips the files it may not read without opening them, and sums up
+    /// the paths at the end.
+    Skip,
+    /// Sums up the paths at the end, and fails if there are any.
+    Require,
+}
+
+/// The options given after the subcommand, with their values, and the
+/// flags.
+#[derive(Debug, Default)]
+struct Args {
+    options: Vec<(String, String)>,
+    flags: Vec<String>,
+}
+
+impl Args {
+    /// Parses the arguments after the subcommand, `None` if one is not an
+    /// option or lacks its value.
+    fn parse(args: impl IntoIterator<Item = String>) -> Option<Args> {
+        let mut parsed = Args::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.strip_prefix("--") {
+                Some(name) if FLAGS.contains(&name) => parsed.flags.push(name.to_string()),
+                Some(name) => parsed.options.push((name.to_string(), args.next()?)),
+                None => return None,
+            }
+        }
+        Some(parsed)
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.flags.iter().any(|flag| flag == name)
+    }
+
+    /// The value of an option, the last one if it was given more than
+    /// once.
+    fn option(&self, name: &str) -> Option<String> {
+        self.options
+            .iter()
+            .rev()
+            .find(|(option, _)| option == name)
+            .map(|(_, value)| value.clone())
+    }
+
+    /// What to do with the paths that may not be read, `None` if told
+    /// both to skip them and to require them.
+    fn inaccessible(&self) -> Option<Inaccessible> {
+        match (self.flag("skip-inaccessible"), self.flag("require-all")) {
+            (false, false) => Some(Inaccessible::Report),
+            (true, false) => Some(Inaccessible::Skip),
+            (false, true) => Some(Inaccessible::Require),
+            (true, true) => None,
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let mut args = env::args().skip(1);
+    let command = args.next().unwrap_or_default();
+    let Some(args) = Args::parse(args) else {
+        return usage();
+    };
+    let option = |name: &str| args.option(name);
+    let result: Result<(), String> = match command.as_str() {
+        "scan" => {
+            let (Some(root), Some(inaccessible)) = (option("root"), args.inaccessible()) else {
+                return usage();
+            };
+            scan(root, option("config"), option("filter"), inaccessible)
+        }
+        #[cfg(feature = "serve")]
+        "serve" => {
+            let Some(root) = option("root") else {
+                return usage();
+            };
+            let listen = option("listen").unwrap_or(":8080".to_string());
+            mediawalker::serve::serve(&root, &listen).map_err(|err| err.to_string())
+        }
+        #[cfg(feature = "grpc")]
+        "grpc" => {
+            let Some(root) = option("root") else {
+                return usage();
+            };
+            let listen = option("listen").unwrap_or(":50051".to_string());
+            mediawalker::grpc::serve(&root, &listen).map_err(|err| err.to_string())
+        }
+        #[cfg(all(feature = "dbus", target_os = "linux"))]
+        "dbus" => mediawalker::dbus::serve().map_err(|err| err.to_string()),
+        #[cfg(feature = "daemon")]
+        "daemon" => {
+            let Some(config) = option("config") else {
+                return usage();
+            };
+            let interval = option("interval").unwrap_or("6h".to_string());
+            let Some(interval) = mediawalker::daemon::parse_interval(&interval) else {
+                return usage();
+            };
+            mediawalker::WalkConfig::from_file(&config)
+                .and_then(|config| {
+                    mediawalker::daemon::run(&config, interval, |event| {
+                        eprintln!("{}", event);
+                        std::ops::ControlFlow::Continue(())
+                    })
+                })
+                .map_err(|err| err.to_string())
+        }
+        _ => return usage(),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("mediawalker: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Prints the path and the MIME type of each result of a walk, as
+/// configured by a file and a filter expression, and the errors to
+/// stderr. Unless they are only reported, the paths that could not be
+/// read are summed up at the end, for them to be scanned again with
+/// elevated privileges.
+fn scan(
+    root: String,
+    config: Option<String>,
+    filter: Option<String>,
+    inaccessible: Inaccessible,
+) -> Result<(), String> {
+    let mut config = match config {
+        Some(config) => WalkConfig::from_file(&config).map_err(|err| err.to_string())?,
+        None => WalkConfig::default(),
+    };
+    config.root = Some(root.into());
+    let mut walker = config.walker().map_err(|err| err.to_string())?;
+    if let Some(filter) = filter {
+        walker = walker.filter(filter.parse().map_err(|err| format!("--filter: {}", err))?);
+    }
+    if inaccessible == Inaccessible::Skip {
+        walker = walker.check_permissions(true);
+    }
+    let mut out = io::stdout().lock();
+    let mut denied = vec![];
+    let mut handle = walker.start();
+    for event in handle.events() {
+        let walk_result = match event {
+            WalkEvent::Found(walk_result) => walk_result,
+            WalkEvent::Skipped {
+                path,
+                reason: SkipReason::NoPermission,
+            } => {
+                denied.push(path);
+                continue;
+            }
+            // Asked for with `throughput` in the config.
+            WalkEvent::Throughput {
+                files_per_sec,
+                mb_per_sec,
+                eta,
+            } => {
+                let eta = eta.map_or(String::new(), |eta| {
+                    format!(", {:.1}s to go", eta.as_secs_f64())
+                });
+                eprintln!(
+                    "mediawalker: {:.0} files/s, {:.1} MB/s{}",
+                    files_per_sec, mb_per_sec, eta
+                );
+                continue;
+            }
+            _ => continue,
+        };
+        match &walk_result.result {
+            Ok(_) => writeln!(out, "{}\t{}", walk_result.path, walk_result.mime)
+                .map_err(|err| err.to_string())?,
+            Err(err)
+                if inaccessible != Inaccessible::Report
+                    && err.kind() == io::ErrorKind::PermissionDenied =>
+            {
+                denied.push(PathBuf::from(walk_result.path))
+            }
+            Err(err) => eprintln!("mediawalker: {}: {}", walk_result.path, err),
+        }
+    }
+    let denied = subtrees(denied);
+    if denied.is_empty() || inaccessible == Inaccessible::Report {
+        return Ok(());
+    }
+    eprintln!("mediawalker: could not read, to be scanned with elevated privileges:");
+    for path in &denied {
+        eprintln!("  {}", path.display());
+    }
+    match inaccessible {
+        Inaccessible::Require => Err(format!("{} paths could not be read", denied.len())),
+        _ => Ok(()),
+    }
+}
+
+/// Sorts paths, leaving out the ones under others.
+fn subtrees(mut paths: Vec<PathBuf>) -> Vec<PathBuf> {
+    paths.sort();
+    let mut subtrees: Vec<PathBuf> = vec![];
+    for path in paths {
+        if !subtrees.last().is_some_and(|last| path.starts_with(last)) {
+            subtrees.push(path);
+        }
+    }
+    subtrees
+}
+
+fn usage() -> ExitCode {
+    eprintln!("{}", USAGE);
+    ExitCode::from(2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Option<Args> {
+        Args::parse(args.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn it_parses_options_and_flags() {
+        let args = parse("--root /a --config one.toml --config two.toml").unwrap();
+        assert_eq!(args.option("root").as_deref(), Some("/a"));
+        assert_eq!(args.option("config").as_deref(), Some("two.toml"));
+        assert_eq!(args.option("filter"), None);
+        assert_eq!(args.inaccessible(), Some(Inaccessible::Report));
+        assert!(parse("--root").is_none());
+        assert!(parse("/a").is_none());
+
+        let args = parse("--skip-inaccessible --root /a").unwrap();
+        assert_eq!(args.option("root").as_deref(), Some("/a"));
+        assert_eq!(args.inaccessible(), Some(Inaccessible::Skip));
+        let args = parse("--root /a --require-all").unwrap();
+        assert_eq!(args.inaccessible(), Some(Inaccessible::Require));
+        let args = parse("--root /a --skip-inaccessible --require-all").unwrap();
+        assert_eq!(args.inaccessible(), None);
+    }
+
+    #[test]
+    fn it_sums_up_paths_by_subtree() {
+        let paths = ["/a/b/c", "/b", "/a/b", "/a b", "/a/bc", "/a/b"];
+        let paths = paths.into_iter().map(PathBuf::from).collect();
+        assert_eq!(
+            subtrees(paths),
+            ["/a/b", "/a/bc", "/a b", "/b"].map(PathBuf::from)
+        );
+        assert!(subtrees(vec![]).is_empty());
+    }
+}
@@ -0,0 +1,176 @@
+//! Sniffing ISO-BMFF files by their brands, ahead of infer, which misses
+//! some of the HEIF brands phones write.
+use infer::MatcherType;
+use std::fmt;
+
+/// The brands of HEIF still images, and of HEIF image sequences, like
+/// Live Photo bursts.
+const HEIF_IMAGES: &[&[u8; 4]] = &[b"heic", b"heix", b"heim", b"heis"];
+const HEIF_SEQUENCES: &[&[u8; 4]] = &[b"hevc", b"hevx", b"hevm", b"hevs"];
+
+/// The structural brands of HEIF, telling nothing of the codec.
+const HEIF_STRUCTURES: &[&[u8; 4]] = &[b"mif1", b"mif2", b"msf1"];
+
+/// The brands of AVIF still images and image sequences.
+const AVIF: &[&[u8; 4]] = &[b"avif", b"avis"];
+
+/// The four-character brand of an ISO-BMFF file, like `heic`, see
+/// [`MediaWalkResult::brand`](crate::MediaWalkResult::brand).
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Brand(pub [u8; 4]);
+
+impl Brand {
+    /// The brand as text, without the padding of short ones like `qt  `.
+    pub fn name(&self) -> &str {
+        std::str::from_utf8(&self.0).unwrap_or_default().trim_end()
+    }
+}
+
+impl fmt::Display for Brand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl fmt::Debug for Brand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Brand({:?})", self.name())
+    }
+}
+
+/// A sniffed file type.
+#[derive(Clone, Copy)]
+pub(crate) struct Sniffed {
+    /// The file type.
+    pub(crate) info: infer::Type,
+    /// The major brand of an ISO-BMFF file, like `heic` or `isom`.
+    pub(crate) brand: Option<Brand>,
+}
+
+/// Figures out the type of a file from its header.
+pub(crate) fn sniff(header: &[u8]) -> Option<Sniffed> {
+    let Some((major, compatible)) = ftyp(header) else {
+        return infer::get(header).map(|info| Sniffed { info, brand: None });
+    };
+    let brand = Some(Brand(major));
+    let info = match image_mime(&major, &compatible) {
+        Some((mime, extension)) => infer::Type::new(MatcherType::Image, mime, extension, |_| true),
+        None => infer::get(header)?,
+    };
+    Some(Sniffed { info, brand })
+}
+
+/// The MIME type and extension of a HEIF or AVIF image, by its brands:
+/// the major brand if it tells, else the compatible ones.
+fn image_mime(major: &[u8; 4], compatible: &[[u8; 4]]) -> Option<(&'static str, &'static str)> {
+    let is = |brands: &[&[u8; 4]], brand: &[u8; 4]| brands.contains(&brand);
+    let classify = |brand: &[u8; 4]| {
+        if is(AVIF, brand) {
+            Some(("image/avif", "avif"))
+        } else if is(HEIF_IMAGES, brand) {
+            Some(("image/heif", "heif"))
+        } else if is(HEIF_SEQUENCES, brand) {
+            Some(("image/heif-sequence", "heifs"))
+        } else {
+            None
+        }
+    };
+    if let Some(mime) = classify(major) {
+        return Some(mime);
+    }
+    // Videos stay videos even if compatible with HEIF, as infer has it.
+    if !is(HEIF_STRUCTURES, major) {
+        return None;
+    }
+    compatible.iter().find_map(classify).or(match major {
+        b"msf1" => Some(("image/heif-sequence", "heifs")),
+        _ => Some(("image/heif", "heif")),
+    })
+}
+
+/// The major and compatible brands of an ISO-BMFF file, as far as the
+/// header goes.
+fn ftyp(header: &[u8]) -> Option<([u8; 4], Vec<[u8; 4]>)> {
+    if header.len() < 16 || &header[4..8] != b"ftyp" {
+        return None;
+    }
+    let len = u32::from_be_bytes(header[..4].try_into().ok()?) as usize;
+    let major = header[8..12].try_into().ok()?;
+    // The minor version comes between the major and compatible brands.
+    let compatible = header
+        .get(16..len.clamp(16, header.len()))?
+        .chunks_exact(4)
+        .map(|brand| brand.try_into().unwrap())
+        .collect();
+    Some((major, compatible))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The header of an ISO-BMFF file of the given brands.
+    fn header(major: &[u8; 4], compatible: &[&[u8; 4]]) -> Vec<u8> {
+        let mut bytes = ((16 + 4 * compatible.len()) as u32).to_be_bytes().to_vec();
+        bytes.extend(b"ftyp");
+        bytes.extend(major);
+        bytes.extend([0; 4]);
+        for brand in compatible {
+            bytes.extend(*brand);
+        }
+        // The start of a meta box.
+        bytes.extend(b"\0\0\0\x20meta");
+        bytes
+    }
+
+    #[test]
+    fn it_sniffs_brands() {
+        let mime = |major, compatible| {
+            let sniffed = sniff(&header(major, compatible)).unwrap();
+            let brand = sniffed.brand.unwrap();
+            (sniffed.info.mime_type(), brand.name().to_string())
+        };
+        // Recent iPhones write heix for 10-bit pictures.
+        assert_eq!(
+            mime(b"heix", &[b"mif1", b"heix"]),
+            ("image/heif", "heix".into())
+        );
+        assert_eq!(
+            mime(b"heic", &[b"mif1", b"heic"]),
+            ("image/heif", "heic".into())
+        );
+        assert_eq!(
+            mime(b"mif1", &[b"mif1", b"heic"]),
+            ("image/heif", "mif1".into())
+        );
+        assert_eq!(
+            mime(b"mif1", &[b"mif1", b"miaf"]),
+            ("image/heif", "mif1".into())
+        );
+        assert_eq!(
+            mime(b"msf1", &[b"msf1", b"hevc"]),
+            ("image/heif-sequence", "msf1".into())
+        );
+        assert_eq!(
+            mime(b"avif", &[b"mif1", b"miaf"]),
+            ("image/avif", "avif".into())
+        );
+        assert_eq!(
+            mime(b"avis", &[b"msf1", b"avis"]),
+            ("image/avif", "avis".into())
+        );
+        assert_eq!(
+            mime(b"isom", &[b"isom", b"mp41"]),
+            ("video/mp4", "isom".into())
+        );
+        assert_eq!(
+            mime(b"isom", &[b"isom", b"heic"]),
+            ("video/mp4", "isom".into())
+        );
+        assert_eq!(mime(b"qt  ", &[b"qt  "]), ("video/quicktime", "qt".into()));
+
+        let jpeg = sniff(&[0xff, 0xd8, 0xff, 0xe0, 0, 0x10]).unwrap();
+        assert_eq!(jpeg.info.mime_type(), "image/jpeg");
+        assert_eq!(jpeg.brand, None);
+    }
+}
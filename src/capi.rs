@@ -0,0 +1,333 @@
+//! A C API for embedding the walker, behind the `capi` feature. See
+//! `include/mediawalker.h` for the declarations.
+use crate::{Kind, MediaWalkResult, MediaWalker, WalkHandle};
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A running walk, as handed to C.
+pub struct MediawalkerWalk {
+    handle: WalkHandle,
+    /// Shared with the handle, so that the walk can be cancelled while
+    /// another thread waits on the handle.
+    stopped: Arc<AtomicBool>,
+}
+
+/// A single result, as handed to C.
+pub struct MediawalkerResult {
+    path: CString,
+    mime: CString,
+    kind: c_int,
+    status: c_int,
+    error: Option<CString>,
+}
+
+impl MediawalkerResult {
+    fn new(walk_result: MediaWalkResult) -> Self {
+        let c_string = |s: &str| CString::new(s.replace('\0', "")).unwrap_or_default();
+        let (status, error) = match &walk_result.result {
+            Ok(true) => (1, None),
+            Ok(false) => (0, None),
+            Err(err) => (-1, Some(c_string(&err.to_string()))),
+        };
+        MediawalkerResult {
+            path: c_string(&walk_result.path),
+            mime: c_string(&walk_result.mime),
+            kind: walk_result.kind.map_or(-1, kind_code),
+            status,
+            error,
+        }
+    }
+}
+
+/// The `MEDIAWALKER_KIND_*` code of a kind. These are fixed by the
+/// header, whatever the order of the kinds.
+fn kind_code(kind: Kind) -> c_int {
+    match kind {
+        Kind::Audio => 0,
+        Kind::Image => 1,
+        Kind::Video => 2,
+        Kind::Companion => 3,
+        Kind::Application => 4,
+        Kind::Archive => 5,
+        Kind::Document => 6,
+        Kind::Ebook => 7,
+        Kind::Font => 8,
+        Kind::Text => 9,
+    }
+}
+
+/// Starts walking `root`, a NUL-terminated UTF-8 path. Returns null if
+/// the root is not valid UTF-8. Free the walk with
+/// [`mediawalker_free`].
+///
+/// # Safety
+///
+/// `root` must be a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn mediawalker_start(root: *const c_char) -> *mut MediawalkerWalk {
+    if root.is_null() {
+        return ptr::null_mut();
+    }
+    let Ok(root) = CStr::from_ptr(root).to_str() else {
+        return ptr::null_mut();
+    };
+    walk(MediaWalker::new(root))
+}
+
+/// Starts walking `root` like [`mediawalker_start`], reporting only the
+/// `kind_count` kinds at `kinds`, `MEDIAWALKER_KIND_*` codes, and
+/// inspecting files on `threads` threads. `kinds` may be null for the
+/// default kinds, and `threads` 0 for the default count. Returns null if
+/// the root is not valid UTF-8, or a kind code is unknown.
+///
+/// # Safety
+///
+/// `root` must be a valid NUL-terminated string, and `kinds` null or
+/// valid for `kind_count` ints.
+#[no_mangle]
+pub unsafe extern "C" fn mediawalker_start_with(
+    root: *const c_char,
+    kinds: *const c_int,
+    kind_count: usize,
+    threads: usize,
+) -> *mut MediawalkerWalk {
+    if root.is_null() {
+        return ptr::null_mut();
+    }
+    let Ok(root) = CStr::from_ptr(root).to_str() else {
+        return ptr::null_mut();
+    };
+    let mut walker = MediaWalker::new(root);
+    if !kinds.is_null() {
+        let codes = std::slice::from_raw_parts(kinds, kind_count);
+        let kinds: Option<Vec<_>> = codes
+            .iter()
+            .map(|code| Kind::ALL.into_iter().find(|kind| kind_code(*kind) == *code))
+            .collect();
+        let Some(kinds) = kinds else {
+            return ptr::null_mut();
+        };
+        walker = walker.kinds(&kinds);
+    }
+    if threads > 0 {
+        walker = walker.threads(threads);
+    }
+    walk(walker)
+}
+
+/// Starts a walk, boxed for C.
+fn walk(walker: MediaWalker) -> *mut MediawalkerWalk {
+    let handle = walker.start();
+    let stopped = handle.stop_flag();
+    Box::into_raw(Box::new(MediawalkerWalk { handle, stopped }))
+}
+
+/// Waits for the next result of a walk. Returns null once the walk is
+/// over. Free the result with [`mediawalker_result_free`].
+///
+/// # Safety
+///
+/// `walk` must come from [`mediawalker_start`], and not be freed.
+#[no_mangle]
+pub unsafe extern "C" fn mediawalker_next(walk: *mut MediawalkerWalk) -> *mut MediawalkerResult {
+    if walk.is_null() {
+        return ptr::null_mut();
+    }
+    // Borrow just the handle, as the walk may be cancelled meanwhile.
+    let handle = &mut (*walk).handle;
+    match handle.next() {
+        Some(walk_result) => Box::into_raw(Box::new(MediawalkerResult::new(walk_result))),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Stops a walk. [`mediawalker_next`] returns null soon after. Can be
+/// called from any thread.
+///
+/// # Safety
+///
+/// `walk` must come from [`mediawalker_start`], and not be freed.
+#[no_mangle]
+pub unsafe extern "C" fn mediawalker_cancel(walk: *const MediawalkerWalk) {
+    if !walk.is_null() {
+        (*walk).stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Stops and frees a walk.
+///
+/// # Safety
+///
+/// `walk` must come from [`mediawalker_start`], or be null, and not be
+/// used after.
+#[no_mangle]
+pub unsafe extern "C" fn mediawalker_free(walk: *mut MediawalkerWalk) {
+    if !walk.is_null() {
+        let walk = Box::from_raw(walk);
+        walk.handle.stop();
+    }
+}
+
+/// The path of a result, valid until the result is freed.
+///
+/// # Safety
+///
+/// `result` must come from [`mediawalker_next`], and not be freed.
+#[no_mangle]
+pub unsafe extern "C" fn mediawalker_result_path(
+    result: *const MediawalkerResult,
+) -> *const c_char {
+    result.as_ref().map_or(ptr::null(), |r| r.path.as_ptr())
+}
+
+/// The MIME type of a result, empty if it is not known, valid until the
+/// result is freed.
+///
+/// # Safety
+///
+/// `result` must come from [`mediawalker_next`], and not be freed.
+#[no_mangle]
+pub unsafe extern "C" fn mediawalker_result_mime(
+    result: *const MediawalkerResult,
+) -> *const c_char {
+    result.as_ref().map_or(ptr::null(), |r| r.mime.as_ptr())
+}
+
+/// The kind of a result: `MEDIAWALKER_KIND_AUDIO` and so on, or -1 if
+/// it is not known.
+///
+/// # Safety
+///
+/// `result` must come from [`mediawalker_next`], and not be freed.
+#[no_mangle]
+pub unsafe extern "C" fn mediawalker_result_kind(result: *const MediawalkerResult) -> c_int {
+    result.as_ref().map_or(-1, |r| r.kind)
+}
+
+/// The status of a result: 1 if a media type was found, 0 if not, and
+/// -1 if the file could not be inspected.
+///
+/// # Safety
+///
+/// `result` must come from [`mediawalker_next`], and not be freed.
+#[no_mangle]
+pub unsafe extern "C" fn mediawalker_result_status(result: *const MediawalkerResult) -> c_int {
+    result.as_ref().map_or(-1, |r| r.status)
+}
+
+/// The error message of a failed result, null for other results. Valid
+/// until the result is freed.
+///
+/// # Safety
+///
+/// `result` must come from [`mediawalker_next`], and not be freed.
+#[no_mangle]
+pub unsafe extern "C" fn mediawalker_result_error(
+    result: *const MediawalkerResult,
+) -> *const c_char {
+    result
+        .as_ref()
+        .and_then(|r| r.error.as_ref())
+        .map_or(ptr::null(), |error| error.as_ptr())
+}
+
+/// Frees a result.
+///
+/// # Safety
+///
+/// `result` must come from [`mediawalker_next`], or be null, and not be
+/// used after.
+#[no_mangle]
+pub unsafe extern "C" fn mediawalker_result_free(result: *mut MediawalkerResult) {
+    if !result.is_null() {
+        drop(Box::from_raw(result));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_walks_through_the_c_api() {
+        let root = CString::new(concat!(env!("CARGO_MANIFEST_DIR"), "/resources/test")).unwrap();
+        let mut found = vec![];
+        unsafe {
+            let walk = mediawalker_start(root.as_ptr());
+            assert!(!walk.is_null());
+            loop {
+                let result = mediawalker_next(walk);
+                if result.is_null() {
+                    break;
+                }
+                let path = CStr::from_ptr(mediawalker_result_path(result));
+                let mime = CStr::from_ptr(mediawalker_result_mime(result));
+                found.push((
+                    path.to_str().unwrap().to_string(),
+                    mime.to_str().unwrap().to_string(),
+                    mediawalker_result_kind(result),
+                    mediawalker_result_status(result),
+                ));
+                assert!(mediawalker_result_error(result).is_null());
+                mediawalker_result_free(result);
+            }
+            mediawalker_free(walk);
+        }
+        assert_eq!(found.len(), 9);
+        assert!(found
+            .iter()
+            .any(|(path, mime, kind, status)| path.ends_with("sample3.mp3")
+                && mime == "audio/mpeg"
+                && *kind == 0
+                && *status == 1));
+    }
+
+    #[test]
+    fn it_walks_with_options_through_the_c_api() {
+        let root = CString::new(concat!(env!("CARGO_MANIFEST_DIR"), "/resources/test")).unwrap();
+        let mut kinds = vec![];
+        unsafe {
+            let codes = [kind_code(Kind::Audio), kind_code(Kind::Image)];
+            let walk = mediawalker_start_with(root.as_ptr(), codes.as_ptr(), codes.len(), 2);
+            assert!(!walk.is_null());
+            loop {
+                let result = mediawalker_next(walk);
+                if result.is_null() {
+                    break;
+                }
+                if mediawalker_result_status(result) == 1 {
+                    kinds.push(mediawalker_result_kind(result));
+                }
+                mediawalker_result_free(result);
+            }
+            mediawalker_free(walk);
+
+            let unknown = [42];
+            let walk = mediawalker_start_with(root.as_ptr(), unknown.as_ptr(), 1, 0);
+            assert!(walk.is_null());
+        }
+        assert!(!kinds.is_empty());
+        assert!(kinds.iter().all(|kind| *kind == 0 || *kind == 1));
+    }
+
+    #[test]
+    fn it_matches_the_kind_codes_of_the_header() {
+        let header = include_str!("../include/mediawalker.h");
+        for kind in Kind::ALL {
+            let define = format!(
+                "#define MEDIAWALKER_KIND_{} {}\n",
+                kind.name().to_uppercase(),
+                kind_code(kind)
+            );
+            assert!(
+                header.contains(&define),
+                "{} is not {}",
+                kind,
+                define.trim()
+            );
+        }
+    }
+}
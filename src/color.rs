@@ -0,0 +1,419 @@
+//! Color information of images and videos, read from their headers, see
+//! [`MediaWalker::color_info`](crate::MediaWalker::color_info).
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// The largest piece of a file read to parse: an ICC profile, say.
+const MAX_READ: u64 = 1024 * 1024;
+
+/// How deep ISO media boxes are looked into.
+const MAX_BOX_DEPTH: usize = 8;
+
+/// The transfer characteristics of HDR video: SMPTE ST 2084 (PQ) and
+/// ARIB STD-B67 (HLG).
+const TRANSFER_PQ: u16 = 16;
+const TRANSFER_HLG: u16 = 18;
+
+/// How an image or a video renders color, as told by its headers
+/// without decoding it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ColorInfo {
+    /// The bits per color channel, if told.
+    pub bit_depth: Option<u8>,
+    /// Whether the content is HDR: PQ or HLG video, Dolby Vision, or a
+    /// JPEG with an Ultra HDR gain map.
+    pub hdr: bool,
+    /// The description of the embedded ICC profile, like `Display P3`.
+    /// For PNGs, the name the file gives its profile.
+    pub icc_profile_name: Option<String>,
+}
+
+/// Reads the color information of a JPEG, PNG, HEIF, AVIF or MP4 file,
+/// by its MIME type.
+pub(crate) fn read(path: &Path, mime: &str) -> Option<ColorInfo> {
+    let mut file = File::open(path).ok()?;
+    match mime {
+        "image/jpeg" => jpeg(&mut file),
+        "image/png" => png(&mut file),
+        "image/heif"
+        | "image/heif-sequence"
+        | "image/avif"
+        | "video/mp4"
+        | "video/quicktime"
+        | "video/x-m4v" => isobmff(&mut file),
+        _ => return None,
+    }
+    .ok()
+}
+
+/// Reads the segments of a JPEG up to the image data.
+fn jpeg<R: Read + Seek>(reader: &mut R) -> io::Result<ColorInfo> {
+    let mut info = ColorInfo::default();
+    let mut icc_chunks = vec![];
+    let mut marker = [0; 2];
+    reader.read_exact(&mut marker)?;
+    if marker != [0xff, 0xd8] {
+        return Err(io::ErrorKind::InvalidData.into());
+    }
+    loop {
+        reader.read_exact(&mut marker)?;
+        if marker[0] != 0xff {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+        match marker[1] {
+            // Padding, and markers without a length.
+            0xff | 0x01 | 0xd0..=0xd7 => continue,
+            // The image data starts, or the image ends.
+            0xda | 0xd9 => break,
+            _ => {}
+        }
+        let len = u64::from(read_u16(reader)?).saturating_sub(2);
+        let wanted = matches!(marker[1], 0xc0..=0xcf if !matches!(marker[1], 0xc4 | 0xc8 | 0xcc))
+            || matches!(marker[1], 0xe1 | 0xe2);
+        if !wanted || len > MAX_READ {
+            reader.seek(SeekFrom::Current(len as i64))?;
+            continue;
+        }
+        let mut data = vec![0; len as usize];
+        reader.read_exact(&mut data)?;
+        match marker[1] {
+            0xe1 => {
+                if let Some(xmp) = data.strip_prefix(b"http://ns.adobe.com/xap/1.0/\0") {
+                    info.hdr |= contains(xmp, b"hdrgm:Version");
+                }
+            }
+            0xe2 => {
+                if let Some(chunk) = data.strip_prefix(b"ICC_PROFILE\0") {
+                    if chunk.len() > 2 {
+                        icc_chunks.push((chunk[0], chunk[2..].to_vec()));
+                    }
+                }
+            }
+            // A start of frame, with the sample precision first.
+            _ => {
+                if let Some(&depth) = data.first() {
+                    info.bit_depth.get_or_insert(depth);
+                }
+            }
+        }
+    }
+    if !icc_chunks.is_empty() {
+        icc_chunks.sort_by_key(|(sequence, _)| *sequence);
+        let icc: Vec<u8> = icc_chunks
+            .into_iter()
+            .flat_map(|(_, chunk)| chunk)
+            .collect();
+        info.icc_profile_name = icc_description(&icc);
+    }
+    Ok(info)
+}
+
+/// Reads the chunks of a PNG up to the image data.
+fn png<R: Read + Seek>(reader: &mut R) -> io::Result<ColorInfo> {
+    let mut info = ColorInfo::default();
+    let mut signature = [0; 8];
+    reader.read_exact(&mut signature)?;
+    if &signature != b"\x89PNG\r\n\x1a\n" {
+        return Err(io::ErrorKind::InvalidData.into());
+    }
+    loop {
+        let len = u64::from(read_u32(reader)?);
+        let mut kind = [0; 4];
+        reader.read_exact(&mut kind)?;
+        if matches!(&kind, b"IDAT" | b"IEND") {
+            break;
+        }
+        if !matches!(&kind, b"IHDR" | b"iCCP" | b"cICP") || len > MAX_READ {
+            reader.seek(SeekFrom::Current(len as i64 + 4))?;
+            continue;
+        }
+        let mut data = vec![0; len as usize + 4];
+        reader.read_exact(&mut data)?;
+        match &kind {
+            b"IHDR" => info.bit_depth = data.get(8).copied(),
+            b"iCCP" => {
+                let name = data.split(|&b| b == 0).next().unwrap_or_default();
+                // Latin-1, as PNG keywords are.
+                info.icc_profile_name = Some(name.iter().map(|&b| char::from(b)).collect());
+            }
+            _ => {
+                let transfer = data.get(1).copied().map(u16::from);
+                info.hdr |= matches!(transfer, Some(TRANSFER_PQ | TRANSFER_HLG));
+            }
+        }
+    }
+    Ok(info)
+}
+
+/// Reads the boxes of an ISO base media file: HEIF and AVIF images, and
+/// MP4 and QuickTime videos.
+fn isobmff<R: Read + Seek>(reader: &mut R) -> io::Result<ColorInfo> {
+    let mut info = ColorInfo::default();
+    let end = reader.seek(SeekFrom::End(0))?;
+    boxes(reader, 0, end, 0, &mut info)?;
+    Ok(info)
+}
+
+/// Reads the boxes between two offsets, looking into the ones that hold
+/// the color and codec properties.
+fn boxes<R: Read + Seek>(
+    reader: &mut R,
+    mut offset: u64,
+    end: u64,
+    depth: usize,
+    info: &mut ColorInfo,
+) -> io::Result<()> {
+    while offset + 8 <= end {
+        reader.seek(SeekFrom::Start(offset))?;
+        let mut size = u64::from(read_u32(reader)?);
+        let mut kind = [0; 4];
+        reader.read_exact(&mut kind)?;
+        let mut header = 8;
+        if size == 1 {
+            size = read_u64(reader)?;
+            header = 16;
+        } else if size == 0 {
+            size = end - offset;
+        }
+        if size < header {
+            break;
+        }
+        let body = offset + header;
+        let box_end = offset.saturating_add(size).min(end);
+        // Where the child boxes start: after the fields of full boxes,
+        // sample descriptions, and visual sample entries.
+        let children = match &kind {
+            b"moov" | b"trak" | b"mdia" | b"minf" | b"stbl" | b"iprp" | b"ipco" => Some(body),
+            b"meta" => Some(body + 4),
+            b"stsd" => Some(body + 8),
+            b"avc1" | b"avc3" | b"hvc1" | b"hev1" | b"av01" | b"dvh1" | b"dvhe" | b"vp09" => {
+                Some(body + 78)
+            }
+            _ => None,
+        };
+        match children {
+            Some(start) if depth < MAX_BOX_DEPTH => boxes(reader, start, box_end, depth + 1, info)?,
+            Some(_) => {}
+            None if matches!(
+                &kind,
+                b"colr" | b"pixi" | b"avcC" | b"hvcC" | b"av1C" | b"dvcC" | b"dvvC"
+            ) && box_end - body <= MAX_READ =>
+            {
+                let mut data = vec![0; (box_end - body) as usize];
+                reader.read_exact(&mut data)?;
+                property(&kind, &data, info);
+            }
+            None => {}
+        }
+        offset += size;
+    }
+    Ok(())
+}
+
+/// Takes the color information out of a box.
+fn property(kind: &[u8; 4], data: &[u8], info: &mut ColorInfo) {
+    let byte = |at: usize| data.get(at).copied();
+    match kind {
+        b"colr" => match data.get(..4) {
+            Some(b"nclx") => {
+                let transfer = be(data, 6, 2).map(|t| t as u16);
+                info.hdr |= matches!(transfer, Some(TRANSFER_PQ | TRANSFER_HLG));
+            }
+            Some(b"prof" | b"rICC") if info.icc_profile_name.is_none() => {
+                info.icc_profile_name = icc_description(&data[4..]);
+            }
+            _ => {}
+        },
+        // A full box, then the channel count and the bits of each.
+        b"pixi" => {
+            if let Some(bits) = byte(5) {
+                info.bit_depth.get_or_insert(bits);
+            }
+        }
+        b"hvcC" => {
+            if let Some(luma) = byte(17) {
+                info.bit_depth.get_or_insert((luma & 0x7) + 8);
+            }
+        }
+        b"av1C" => {
+            if let Some(flags) = byte(2) {
+                let bits = match (flags & 0x40 != 0, flags & 0x20 != 0) {
+                    (true, true) => 12,
+                    (true, false) => 10,
+                    _ => 8,
+                };
+                info.bit_depth.get_or_insert(bits);
+            }
+        }
+        b"avcC" => {
+            if let Some(bits) = avc_bit_depth(data) {
+                info.bit_depth.get_or_insert(bits);
+            }
+        }
+        // Dolby Vision.
+        _ => info.hdr = true,
+    }
+}
+
+/// The bit depth of an H.264 configuration: 8 unless the high profiles'
+/// extension after the parameter sets tells otherwise.
+fn avc_bit_depth(data: &[u8]) -> Option<u8> {
+    let profile = *data.get(1)?;
+    if !matches!(profile, 100 | 110 | 122 | 144) {
+        return Some(8);
+    }
+    let mut at = 6;
+    let sps_count = data.get(5)? & 0x1f;
+    for _ in 0..sps_count {
+        at += 2 + be(data, at, 2)?;
+    }
+    let pps_count = *data.get(at)?;
+    at += 1;
+    for _ in 0..pps_count {
+        at += 2 + be(data, at, 2)?;
+    }
+    // Older files leave the extension out.
+    Some(data.get(at + 1).map_or(8, |luma| (luma & 0x7) + 8))
+}
+
+/// The description of an ICC profile, from its `desc` tag.
+fn icc_description(icc: &[u8]) -> Option<String> {
+    let tags = be(icc, 128, 4)?;
+    for i in 0..tags.min(256) {
+        let entry = 132 + i * 12;
+        if icc.get(entry..entry + 4)? != b"desc" {
+            continue;
+        }
+        let (offset, len) = (be(icc, entry + 4, 4)?, be(icc, entry + 8, 4)?);
+        let tag = icc.get(offset..offset.checked_add(len)?)?;
+        let text = match tag.get(..4)? {
+            // ICC v2: a count, then ASCII.
+            b"desc" => {
+                let count = be(tag, 8, 4)?;
+                let ascii = tag.get(12..12 + count)?;
+                ascii.iter().map(|&b| char::from(b)).collect::<String>()
+            }
+            // ICC v4: records of UTF-16 text, taking the first.
+            b"mluc" => {
+                let (len, offset) = (be(tag, 20, 4)?, be(tag, 24, 4)?);
+                let units: Vec<u16> = tag
+                    .get(offset..offset.checked_add(len)?)?
+                    .chunks_exact(2)
+                    .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                    .collect();
+                String::from_utf16_lossy(&units)
+            }
+            _ => return None,
+        };
+        let text = text.trim_end_matches('\0').trim();
+        return (!text.is_empty()).then(|| text.to_string());
+    }
+    None
+}
+
+/// A big-endian number of `len` bytes at an offset.
+fn be(data: &[u8], at: usize, len: usize) -> Option<usize> {
+    let bytes = data.get(at..at.checked_add(len)?)?;
+    Some(bytes.iter().fold(0, |n, &b| n << 8 | usize::from(b)))
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack
+        .windows(needle.len())
+        .any(|window| window == needle)
+}
+
+fn read_u16<R: Read>(reader: &mut R) -> io::Result<u16> {
+    let mut bytes = [0; 2];
+    reader.read_exact(&mut bytes)?;
+    Ok(u16::from_be_bytes(bytes))
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_be_bytes(bytes))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_be_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// An ICC v4 profile with nothing but a description.
+    fn icc_profile(description: &str) -> Vec<u8> {
+        let text: Vec<u8> = description
+            .encode_utf16()
+            .flat_map(|unit| unit.to_be_bytes())
+            .collect();
+        let mut mluc = b"mluc\0\0\0\0".to_vec();
+        for n in [1, 12, u32::from_be_bytes(*b"enUS"), text.len() as u32, 28] {
+            mluc.extend(n.to_be_bytes());
+        }
+        mluc.extend(&text);
+        let mut icc = vec![0; 128];
+        icc.extend(1u32.to_be_bytes());
+        icc.extend(b"desc");
+        icc.extend(144u32.to_be_bytes());
+        icc.extend((mluc.len() as u32).to_be_bytes());
+        icc.extend(mluc);
+        icc
+    }
+
+    #[test]
+    fn it_reads_color_info() {
+        let sample = |name: &str, mime: &str| {
+            let path = format!("{}/resources/test/{}", env!("CARGO_MANIFEST_DIR"), name);
+            read(Path::new(&path), mime).unwrap()
+        };
+        let sdr = ColorInfo {
+            bit_depth: Some(8),
+            hdr: false,
+            icc_profile_name: None,
+        };
+        assert_eq!(sample("sample_640x426.jpg", "image/jpeg"), sdr);
+        assert_eq!(sample("sample1.heic", "image/heif"), sdr);
+        assert_eq!(sample("sample_640x360.mp4", "video/mp4"), sdr);
+
+        let icc = icc_profile("Display P3");
+        let mut bytes = b"\xff\xd8".to_vec();
+        let mut app2 = b"ICC_PROFILE\0\x01\x01".to_vec();
+        app2.extend(&icc);
+        bytes.extend(b"\xff\xe2");
+        bytes.extend((app2.len() as u16 + 2).to_be_bytes());
+        bytes.extend(app2);
+        let xmp = b"http://ns.adobe.com/xap/1.0/\0<x hdrgm:Version=\"1.0\"/>";
+        bytes.extend(b"\xff\xe1");
+        bytes.extend((xmp.len() as u16 + 2).to_be_bytes());
+        bytes.extend(xmp);
+        bytes.extend(b"\xff\xc0\0\x0b\x0c\0\x10\0\x10\x01\x01\x11\0\xff\xda");
+        let info = jpeg(&mut Cursor::new(bytes)).unwrap();
+        assert_eq!(info.bit_depth, Some(12));
+        assert!(info.hdr);
+        assert_eq!(info.icc_profile_name.as_deref(), Some("Display P3"));
+
+        let mut bytes = b"\x89PNG\r\n\x1a\n".to_vec();
+        for (kind, data) in [
+            (b"IHDR", &b"\0\0\0\x10\0\0\0\x10\x10\x02\0\0\0"[..]),
+            (b"iCCP", b"Rec. 2100 PQ\0\0"),
+            (b"cICP", b"\x09\x10\0\x01"),
+            (b"IDAT", b""),
+        ] {
+            bytes.extend((data.len() as u32).to_be_bytes());
+            bytes.extend(kind);
+            bytes.extend(data);
+            bytes.extend([0; 4]);
+        }
+        let info = png(&mut Cursor::new(bytes)).unwrap();
+        assert_eq!(info.bit_depth, Some(16));
+        assert!(info.hdr);
+        assert_eq!(info.icc_profile_name.as_deref(), Some("Rec. 2100 PQ"));
+    }
+}
@@ -0,0 +1,198 @@
+use crate::{Kind, MediaWalkResult};
+use std::io;
+use std::sync::Arc;
+
+/// A found file, as kept in a [`ResultArena`]: indexes into the arena
+/// instead of strings of its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompactResult {
+    path_start: usize,
+    path_len: u32,
+    mime: u16,
+    /// The file's category, `None` if the media type could not be found.
+    pub kind: Option<Kind>,
+    outcome: Outcome,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Media,
+    Unknown,
+    /// An index into the arena's errors.
+    Failed(u32),
+}
+
+/// A compact collection of results, for keeping millions of them
+/// around: the paths share a single string and the MIME types a short
+/// list, so that a result takes no allocations of its own.
+///
+/// Only the path, MIME type, kind and outcome of a file are kept. The
+/// rest of a [`MediaWalkResult`] is left out: anything asked for of the
+/// walk, like IDs, thumbnails, signatures, annotations or color
+/// information, and its path issues and resolved paths.
+///
+/// # Examples
+///
+/// ```
+/// use mediawalker::{MediaWalker, ResultArena};
+/// let arena: ResultArena = MediaWalker::new(env!("CARGO_MANIFEST_DIR")).start().collect();
+/// for found in arena.iter() {
+///     println!("{}: {}", arena.path(found), arena.mime(found));
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct ResultArena {
+    paths: String,
+    mimes: Vec<Arc<str>>,
+    errors: Vec<io::Error>,
+    results: Vec<CompactResult>,
+}
+
+impl ResultArena {
+    /// Creates an empty arena.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a result to the arena.
+    pub fn push(&mut self, walk_result: MediaWalkResult) {
+        let path_start = self.paths.len();
+        self.paths.push_str(&walk_result.path);
+        let mime = match self
+            .mimes
+            .iter()
+            .position(|mime| Arc::ptr_eq(mime, &walk_result.mime) || *mime == walk_result.mime)
+        {
+            Some(index) => index,
+            None => {
+                self.mimes.push(walk_result.mime);
+                self.mimes.len() - 1
+            }
+        };
+        let outcome = match walk_result.result {
+            Ok(true) => Outcome::Media,
+            Ok(false) => Outcome::Unknown,
+            Err(err) => {
+                self.errors.push(err);
+                Outcome::Failed(self.errors.len() as u32 - 1)
+            }
+        };
+        self.results.push(CompactResult {
+            path_start,
+            path_len: walk_result.path.len() as u32,
+            mime: mime as u16,
+            kind: walk_result.kind,
+            outcome,
+        });
+    }
+
+    /// The results, in the order they were added.
+    pub fn results(&self) -> &[CompactResult] {
+        &self.results
+    }
+
+    /// Returns an iterator over the results.
+    pub fn iter(&self) -> std::slice::Iter<'_, CompactResult> {
+        self.results.iter()
+    }
+
+    /// How many results there are.
+    pub fn len(&self) -> usize {
+        self.results.len()
+    }
+
+    /// Whether there are no results.
+    pub fn is_empty(&self) -> bool {
+        self.results.is_empty()
+    }
+
+    /// The path of a result.
+    pub fn path(&self, found: &CompactResult) -> &str {
+        &self.paths[found.path_start..found.path_start + found.path_len as usize]
+    }
+
+    /// The MIME type of a result.
+    pub fn mime(&self, found: &CompactResult) -> &str {
+        &self.mimes[found.mime as usize]
+    }
+
+    /// The outcome of a result, as in [`MediaWalkResult::result`].
+    pub fn result(&self, found: &CompactResult) -> Result<bool, &io::Error> {
+        match found.outcome {
+            Outcome::Media => Ok(true),
+            Outcome::Unknown => Ok(false),
+            Outcome::Failed(index) => Err(&self.errors[index as usize]),
+        }
+    }
+
+    /// Makes a full result out of a compact one, with only what the
+    /// arena keeps. An error is copied by its kind and message.
+    pub fn to_result(&self, found: &CompactResult) -> MediaWalkResult {
+        let mut walk_result = MediaWalkResult::new(self.path(found).to_string());
+        walk_result.mime = Arc::clone(&self.mimes[found.mime as usize]);
+        walk_result.kind = found.kind;
+        walk_result.result = self
+            .result(found)
+            .map_err(|err| io::Error::new(err.kind(), err.to_string()));
+        walk_result
+    }
+
+    /// Turns the arena back into full results.
+    pub fn into_results(self) -> Vec<MediaWalkResult> {
+        self.results
+            .iter()
+            .map(|found| self.to_result(found))
+            .collect()
+    }
+}
+
+impl Extend<MediaWalkResult> for ResultArena {
+    fn extend<I: IntoIterator<Item = MediaWalkResult>>(&mut self, iter: I) {
+        for walk_result in iter {
+            self.push(walk_result);
+        }
+    }
+}
+
+impl FromIterator<MediaWalkResult> for ResultArena {
+    fn from_iter<I: IntoIterator<Item = MediaWalkResult>>(iter: I) -> Self {
+        let mut arena = ResultArena::new();
+        arena.extend(iter);
+        arena
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MediaWalker;
+
+    #[test]
+    fn it_keeps_results_in_an_arena() {
+        let root = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/test");
+        let expected: Vec<_> = MediaWalker::new(root).start().collect();
+        let arena: ResultArena = MediaWalker::new(root).start().collect();
+        assert_eq!(arena.len(), expected.len());
+        assert!(arena.mimes.len() < arena.len());
+        for (found, walk_result) in arena.iter().zip(&expected) {
+            assert_eq!(arena.path(found), walk_result.path);
+            assert_eq!(arena.mime(found), &*walk_result.mime);
+            assert_eq!(found.kind, walk_result.kind);
+            assert_eq!(
+                arena.result(found).ok(),
+                walk_result.result.as_ref().ok().copied()
+            );
+        }
+
+        let mut arena = ResultArena::new();
+        let mut failed = MediaWalkResult::new("gone.jpg".to_string());
+        failed.result = Err(io::Error::new(io::ErrorKind::NotFound, "gone"));
+        arena.push(failed);
+        let results = arena.into_results();
+        assert_eq!(results[0].path, "gone.jpg");
+        assert_eq!(
+            results[0].result.as_ref().unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+}
@@ -0,0 +1,1126 @@
+use crate::{
+    DirEmit, ErrorPolicy, Filter, IdStrategy, Kind, LockedFiles, MediaWalker, Normalization,
+    Preset, Priority,
+};
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// The prefix of the environment variables read by [`WalkConfig::from_env`].
+const ENV_PREFIX: &str = "MEDIAWALKER_";
+
+/// The error of a key that names no option.
+const UNKNOWN_KEY: &str = "unknown key";
+
+/// Walk options read from a configuration file, so scan profiles (say,
+/// "photos only") can be kept outside the code. Every builder option of
+/// [`MediaWalker`] has a key of the same name.
+///
+/// Both TOML and JSON are understood. As configuration files are flat,
+/// only a subset of them is: top-level keys with string, integer,
+/// boolean or array values.
+///
+/// # Examples
+///
+/// ```
+/// use mediawalker::{Kind, WalkConfig};
+/// let config = WalkConfig::from_toml(
+///     r#"
+///     root = "/mnt/nas/photos"
+///     kinds = ["image"] # Photos only, please.
+///     error_policy = "ignore"
+///     "#,
+/// )
+/// .unwrap();
+/// assert_eq!(config.kinds, Some(vec![Kind::Image]));
+/// let walker = config.walker().unwrap();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WalkConfig {
+    /// The directory to walk.
+    pub root: Option<PathBuf>,
+    /// A bundle of options, applied before the others.
+    pub preset: Option<Preset>,
+    /// The kinds to report, replacing the defaults.
+    pub kinds: Option<Vec<Kind>>,
+    /// The kinds to report in addition to the others.
+    pub extra_kinds: Vec<Kind>,
+    /// Whether to report companion files.
+    pub companions: Option<bool>,
+    /// What to do with errors.
+    pub error_policy: Option<ErrorPolicy>,
+    /// How many threads inspect the found files.
+    pub threads: Option<usize>,
+    /// Glob patterns of paths to skip.
+    pub excludes: Vec<String>,
+    /// How long to pause after each found file, e.g. `"10ms"`.
+    pub throttle: Option<Duration>,
+    /// How long results may wait in the walk's buffers, e.g. `"50ms"`.
+    pub target_latency: Option<Duration>,
+    /// The percent of time waiting for IO over which to slow down.
+    pub io_backoff: Option<u32>,
+    /// Whether to pause while on battery.
+    pub pause_on_battery: Option<bool>,
+    /// The log to write the events of the walk to.
+    pub record: Option<PathBuf>,
+    /// How often to report the speed of the walk, e.g. `"1s"`.
+    pub throughput: Option<Duration>,
+    /// How many files the walk is expected to go through.
+    pub expected_files: Option<u64>,
+    /// The file extensions to inspect.
+    pub extensions: Vec<String>,
+    /// The smallest size of files to inspect, e.g. `"10kB"`.
+    pub min_size: Option<u64>,
+    /// The largest size of files to inspect, e.g. `"4GB"`.
+    pub max_size: Option<u64>,
+    /// Whether to walk into Windows junctions.
+    pub follow_reparse_points: Option<bool>,
+    /// Whether to stop at macOS bundles.
+    pub treat_bundles_as_files: Option<bool>,
+    /// Whether to report the bundles stopped at.
+    pub report_bundles: Option<bool>,
+    /// Whether to seed walks from the Spotlight index on macOS.
+    pub spotlight: Option<bool>,
+    /// Whether to sniff files through memory maps.
+    pub mmap: Option<bool>,
+    /// How many directories below the root files must be.
+    pub min_depth: Option<usize>,
+    /// How many symbolic links to follow at most to resolve one.
+    pub max_symlink_depth: Option<usize>,
+    /// The order to walk each directory in, e.g. `"newest-first"`.
+    pub priority: Option<Priority>,
+    /// Glob patterns of directories to walk first.
+    pub prioritize_dirs: Vec<String>,
+    /// The seed to shuffle the results with.
+    pub shuffle: Option<u64>,
+    /// Whether to look up cached thumbnails for images.
+    pub thumbnails: Option<bool>,
+    /// Whether to walk into trash directories.
+    pub include_trash: Option<bool>,
+    /// The names of the photo-workflow cache directories to skip,
+    /// replacing the defaults.
+    pub workflow_caches: Option<Vec<String>>,
+    /// How to compute stable IDs, e.g. `"inode"`.
+    pub ids: Option<IdStrategy>,
+    /// Whether to report and flag suspicious files.
+    pub flag_suspicious: Option<bool>,
+    /// Whether to check the paths of the found files.
+    pub check_paths: Option<bool>,
+    /// The Unicode form to normalize paths to, `"nfc"` or `"nfd"`.
+    pub normalize_paths: Option<Normalization>,
+    /// Whether to report each file once, however many paths lead to it.
+    pub dedupe_paths: Option<bool>,
+    /// The kinds to finish in stages of their own, with their thread
+    /// counts, written like `"video:2"`.
+    pub stage_concurrency: Vec<(Kind, usize)>,
+    /// How many threads hash the files for their content IDs apart from
+    /// their results, see [`MediaWalker::hash_concurrency`].
+    pub hash_concurrency: Option<usize>,
+    /// Whether to send the results before their slow details, see
+    /// [`MediaWalker::progressive`].
+    pub progressive: Option<bool>,
+    /// Which directories to report, `"never"` or `"with_matches"`, see
+    /// [`MediaWalker::emit_dirs`].
+    pub emit_dirs: Option<DirEmit>,
+    /// How many threads walk the directory tree.
+    pub traversal_threads: Option<usize>,
+    /// How much memory the queues of the walk may take, e.g. `"64MB"`.
+    pub memory_budget: Option<u64>,
+    /// Whether to report symbolic links whose targets are gone.
+    pub report_dangling_symlinks: Option<bool>,
+    /// Whether to compute perceptual signatures of videos.
+    pub video_signatures: Option<bool>,
+    /// Whether to read the color information of images and videos.
+    pub color_info: Option<bool>,
+    /// Whether to tell animated images from still ones.
+    pub detect_animation: Option<bool>,
+    /// Whether to find the videos embedded in motion photos.
+    pub motion_photos: Option<bool>,
+    /// Whether to report the files whose paths are not UTF-8, converted
+    /// lossily.
+    pub lossy_paths: Option<bool>,
+    /// Whether to skip the files the walk may not read, as told by their
+    /// permissions.
+    pub check_permissions: Option<bool>,
+    /// What to do with encrypted files, `"ignore"`, `"report"` or
+    /// `"skip"`, see [`MediaWalker::locked_files`].
+    pub locked_files: Option<LockedFiles>,
+    /// Whether to find the album art of songs.
+    pub artwork: Option<bool>,
+    /// Whether to look for the media embedded in documents.
+    #[cfg(feature = "documents")]
+    pub embedded_media: Option<bool>,
+    /// When the daemon scans every file, e.g. `"0 3 * * *"` for nightly,
+    /// see [`daemon::run`](crate::daemon::run).
+    #[cfg(feature = "daemon")]
+    pub schedule: Option<crate::daemon::Schedule>,
+    /// The SQLite database the daemon keeps an index of the files in,
+    /// see [`index`](crate::index).
+    #[cfg(feature = "daemon")]
+    pub index: Option<PathBuf>,
+    /// Whether to read the EXIF of images.
+    pub exif: Option<bool>,
+    /// Whether to tag likely screenshots and document scans.
+    pub media_hints: Option<bool>,
+    /// The expression to keep results by, e.g.
+    /// `"kind == video && size > 100MB"`.
+    pub filter: Option<Filter>,
+}
+
+/// An error reading a [`WalkConfig`].
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The file could not be read.
+    Io(io::Error),
+    /// The file is not valid TOML or JSON, or uses unsupported syntax.
+    Syntax {
+        /// The line of the error, starting from 1.
+        line: usize,
+        /// What went wrong.
+        message: String,
+    },
+    /// A key is unknown, or its value is not valid for it.
+    Invalid {
+        /// The offending key.
+        key: String,
+        /// What went wrong.
+        message: String,
+    },
+    /// A walker was asked for, but no root was configured.
+    MissingRoot,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(err) => write!(f, "cannot read configuration: {}", err),
+            ConfigError::Syntax { line, message } => write!(f, "line {}: {}", line, message),
+            ConfigError::Invalid { key, message } => write!(f, "{}: {}", key, message),
+            ConfigError::MissingRoot => f.write_str("no root configured"),
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConfigError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ConfigError {
+    fn from(err: io::Error) -> Self {
+        ConfigError::Io(err)
+    }
+}
+
+impl WalkConfig {
+    /// Reads a configuration file: JSON if its extension is `.json`,
+    /// TOML otherwise.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        let is_json = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        if is_json {
+            WalkConfig::from_json(&text)
+        } else {
+            WalkConfig::from_toml(&text)
+        }
+    }
+
+    /// Parses a TOML configuration.
+    pub fn from_toml(text: &str) -> Result<Self, ConfigError> {
+        WalkConfig::from_entries(Parser::new(text, true).toml()?, false)
+    }
+
+    /// Parses a JSON configuration.
+    pub fn from_json(text: &str) -> Result<Self, ConfigError> {
+        WalkConfig::from_entries(Parser::new(text, false).json()?, false)
+    }
+
+    /// Parses a JSON configuration setting only the given keys.
+    pub(crate) fn from_json_keys(text: &str, allowed: &[&str]) -> Result<Self, ConfigError> {
+        let entries = Parser::new(text, false).json()?;
+        if let Some((key, _)) = entries
+            .iter()
+            .find(|(key, _)| !allowed.contains(&key.as_str()))
+        {
+            return Err(ConfigError::Invalid {
+                key: key.clone(),
+                message: "not allowed here".to_string(),
+            });
+        }
+        WalkConfig::from_entries(entries, false)
+    }
+
+    /// Reads the configuration from `MEDIAWALKER_*` environment
+    /// variables, named after the keys: `MEDIAWALKER_THREADS=4`,
+    /// `MEDIAWALKER_KINDS=image,video`, `MEDIAWALKER_EXCLUDES=*.tmp,cache`,
+    /// `MEDIAWALKER_THROTTLE=5ms` and so on. Lists are comma-separated.
+    /// Variables that name no key, like those of a newer version, are
+    /// skipped.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        WalkConfig::from_vars(env::vars())
+    }
+
+    fn from_vars<I: IntoIterator<Item = (String, String)>>(vars: I) -> Result<Self, ConfigError> {
+        WalkConfig::from_entries(
+            vars.into_iter()
+                .filter_map(|(name, value)| {
+                    let key = name.strip_prefix(ENV_PREFIX)?.to_ascii_lowercase();
+                    Some((key, Value::Raw(value)))
+                })
+                .collect(),
+            true,
+        )
+    }
+
+    /// Layers another configuration over this one: the options set in
+    /// `overlay` win, and its lists are appended to these.
+    pub fn overlay(mut self, overlay: WalkConfig) -> Self {
+        self.root = overlay.root.or(self.root);
+        self.preset = overlay.preset.or(self.preset);
+        self.kinds = overlay.kinds.or(self.kinds);
+        self.extra_kinds.extend(overlay.extra_kinds);
+        self.companions = overlay.companions.or(self.companions);
+        self.error_policy = overlay.error_policy.or(self.error_policy);
+        self.threads = overlay.threads.or(self.threads);
+        self.excludes.extend(overlay.excludes);
+        self.throttle = overlay.throttle.or(self.throttle);
+        self.target_latency = overlay.target_latency.or(self.target_latency);
+        self.io_backoff = overlay.io_backoff.or(self.io_backoff);
+        self.pause_on_battery = overlay.pause_on_battery.or(self.pause_on_battery);
+        self.record = overlay.record.or(self.record);
+        self.throughput = overlay.throughput.or(self.throughput);
+        self.expected_files = overlay.expected_files.or(self.expected_files);
+        self.extensions.extend(overlay.extensions);
+        self.min_size = overlay.min_size.or(self.min_size);
+        self.max_size = overlay.max_size.or(self.max_size);
+        self.follow_reparse_points = overlay.follow_reparse_points.or(self.follow_reparse_points);
+        self.treat_bundles_as_files = overlay
+            .treat_bundles_as_files
+            .or(self.treat_bundles_as_files);
+        self.report_bundles = overlay.report_bundles.or(self.report_bundles);
+        self.spotlight = overlay.spotlight.or(self.spotlight);
+        self.mmap = overlay.mmap.or(self.mmap);
+        self.min_depth = overlay.min_depth.or(self.min_depth);
+        self.max_symlink_depth = overlay.max_symlink_depth.or(self.max_symlink_depth);
+        self.priority = overlay.priority.or(self.priority);
+        self.prioritize_dirs.extend(overlay.prioritize_dirs);
+        self.shuffle = overlay.shuffle.or(self.shuffle);
+        self.thumbnails = overlay.thumbnails.or(self.thumbnails);
+        self.include_trash = overlay.include_trash.or(self.include_trash);
+        self.workflow_caches = overlay.workflow_caches.or(self.workflow_caches);
+        self.ids = overlay.ids.or(self.ids);
+        self.flag_suspicious = overlay.flag_suspicious.or(self.flag_suspicious);
+        self.check_paths = overlay.check_paths.or(self.check_paths);
+        self.normalize_paths = overlay.normalize_paths.or(self.normalize_paths);
+        self.dedupe_paths = overlay.dedupe_paths.or(self.dedupe_paths);
+        self.stage_concurrency.extend(overlay.stage_concurrency);
+        self.hash_concurrency = overlay.hash_concurrency.or(self.hash_concurrency);
+        self.progressive = overlay.progressive.or(self.progressive);
+        self.emit_dirs = overlay.emit_dirs.or(self.emit_dirs);
+        self.traversal_threads = overlay.traversal_threads.or(self.traversal_threads);
+        self.memory_budget = overlay.memory_budget.or(self.memory_budget);
+        self.report_dangling_symlinks = overlay
+            .report_dangling_symlinks
+            .or(self.report_dangling_symlinks);
+        self.video_signatures = overlay.video_signatures.or(self.video_signatures);
+        self.color_info = overlay.color_info.or(self.color_info);
+        self.detect_animation = overlay.detect_animation.or(self.detect_animation);
+        self.motion_photos = overlay.motion_photos.or(self.motion_photos);
+        self.lossy_paths = overlay.lossy_paths.or(self.lossy_paths);
+        self.check_permissions = overlay.check_permissions.or(self.check_permissions);
+        self.locked_files = overlay.locked_files.or(self.locked_files);
+        self.artwork = overlay.artwork.or(self.artwork);
+        #[cfg(feature = "documents")]
+        {
+            self.embedded_media = overlay.embedded_media.or(self.embedded_media);
+        }
+        #[cfg(feature = "daemon")]
+        {
+            self.schedule = overlay.schedule.or(self.schedule);
+            self.index = overlay.index.or(self.index);
+        }
+        self.exif = overlay.exif.or(self.exif);
+        self.media_hints = overlay.media_hints.or(self.media_hints);
+        self.filter = overlay.filter.or(self.filter);
+        self
+    }
+
+    fn from_entries(
+        entries: Vec<(String, Value)>,
+        skip_unknown: bool,
+    ) -> Result<Self, ConfigError> {
+        let mut config = WalkConfig::default();
+        for (key, value) in entries {
+            match config.set(&key, value) {
+                Err(message) if skip_unknown && message == UNKNOWN_KEY => {}
+                result => result.map_err(|message| ConfigError::Invalid { key, message })?,
+            }
+        }
+        Ok(config)
+    }
+
+    /// Sets a single option.
+    pub(crate) fn set(&mut self, key: &str, value: Value) -> Result<(), String> {
+        match key {
+            "root" => self.root = Some(PathBuf::from(value.into_string()?)),
+            "preset" => self.preset = Some(value.into_string()?.parse()?),
+            "kinds" => self.kinds = Some(value.parse_list()?),
+            "extra_kinds" => self.extra_kinds = value.parse_list()?,
+            "companions" => self.companions = Some(value.into_bool()?),
+            "error_policy" => self.error_policy = Some(value.into_string()?.parse()?),
+            "threads" => self.threads = Some(value.into_count()?),
+            "excludes" => self.excludes = value.into_strings()?,
+            "throttle" => self.throttle = Some(value.into_duration()?),
+            "target_latency" => self.target_latency = Some(value.into_duration()?),
+            "pause_on_battery" => self.pause_on_battery = Some(value.into_bool()?),
+            "record" => self.record = Some(PathBuf::from(value.into_string()?)),
+            "throughput" => self.throughput = Some(value.into_duration()?),
+            "expected_files" => self.expected_files = Some(value.into_count()? as u64),
+            "io_backoff" => self.io_backoff = Some(value.into_count()?.min(100) as u32),
+            "extensions" => self.extensions = value.into_strings()?,
+            "min_size" => self.min_size = Some(value.into_size()?),
+            "max_size" => self.max_size = Some(value.into_size()?),
+            "follow_reparse_points" => self.follow_reparse_points = Some(value.into_bool()?),
+            "treat_bundles_as_files" => self.treat_bundles_as_files = Some(value.into_bool()?),
+            "report_bundles" => self.report_bundles = Some(value.into_bool()?),
+            "spotlight" => self.spotlight = Some(value.into_bool()?),
+            "mmap" => self.mmap = Some(value.into_bool()?),
+            "min_depth" => self.min_depth = Some(value.into_count()?),
+            "max_symlink_depth" => self.max_symlink_depth = Some(value.into_count()?),
+            "priority" => self.priority = Some(value.into_string()?.parse()?),
+            "prioritize_dirs" => self.prioritize_dirs = value.into_strings()?,
+            "shuffle" => {
+                let seed = value.into_int()?;
+                let seed = u64::try_from(seed)
+                    .map_err(|_| format!("expected a non-negative seed, not {}", seed))?;
+                self.shuffle = Some(seed);
+            }
+            "thumbnails" => self.thumbnails = Some(value.into_bool()?),
+            "include_trash" => self.include_trash = Some(value.into_bool()?),
+            "workflow_caches" => self.workflow_caches = Some(value.into_strings()?),
+            "ids" => self.ids = Some(value.into_string()?.parse()?),
+            "flag_suspicious" => self.flag_suspicious = Some(value.into_bool()?),
+            "check_paths" => self.check_paths = Some(value.into_bool()?),
+            "normalize_paths" => self.normalize_paths = Some(value.into_string()?.parse()?),
+            "dedupe_paths" => self.dedupe_paths = Some(value.into_bool()?),
+            "hash_concurrency" => self.hash_concurrency = Some(value.into_count()?),
+            "progressive" => self.progressive = Some(value.into_bool()?),
+            "emit_dirs" => self.emit_dirs = Some(value.into_string()?.parse()?),
+            "traversal_threads" => self.traversal_threads = Some(value.into_count()?),
+            "memory_budget" => self.memory_budget = Some(value.into_size()?),
+            "report_dangling_symlinks" => self.report_dangling_symlinks = Some(value.into_bool()?),
+            "video_signatures" => self.video_signatures = Some(value.into_bool()?),
+            "color_info" => self.color_info = Some(value.into_bool()?),
+            "detect_animation" => self.detect_animation = Some(value.into_bool()?),
+            "motion_photos" => self.motion_photos = Some(value.into_bool()?),
+            "artwork" => self.artwork = Some(value.into_bool()?),
+            "lossy_paths" => self.lossy_paths = Some(value.into_bool()?),
+            "check_permissions" => self.check_permissions = Some(value.into_bool()?),
+            "locked_files" => self.locked_files = Some(value.into_string()?.parse()?),
+            #[cfg(feature = "documents")]
+            "embedded_media" => self.embedded_media = Some(value.into_bool()?),
+            #[cfg(feature = "daemon")]
+            "schedule" => self.schedule = Some(value.into_string()?.parse()?),
+            #[cfg(feature = "daemon")]
+            "index" => self.index = Some(PathBuf::from(value.into_string()?)),
+            "exif" => self.exif = Some(value.into_bool()?),
+            "media_hints" => self.media_hints = Some(value.into_bool()?),
+            "filter" => self.filter = Some(value.into_string()?.parse()?),
+            "stage_concurrency" => {
+                self.stage_concurrency = value
+                    .into_strings()?
+                    .iter()
+                    .map(|stage| {
+                        let (kind, threads) = stage
+                            .split_once(':')
+                            .ok_or_else(|| format!("expected kind:threads, not '{}'", stage))?;
+                        let threads = threads
+                            .trim()
+                            .parse()
+                            .map_err(|_| format!("expected a thread count, not '{}'", threads))?;
+                        Ok((kind.trim().parse()?, threads))
+                    })
+                    .collect::<Result<_, String>>()?
+            }
+            _ => return Err(UNKNOWN_KEY.to_string()),
+        }
+        Ok(())
+    }
+
+    /// Applies the configured options, except the root, to a walker.
+    pub fn apply(&self, mut walker: MediaWalker) -> MediaWalker {
+        if let Some(preset) = self.preset {
+            walker = walker.preset(preset);
+        }
+        if let Some(kinds) = &self.kinds {
+            walker = walker.kinds(kinds);
+        }
+        walker = walker.extra_kinds(&self.extra_kinds);
+        if let Some(companions) = self.companions {
+            walker = walker.companions(companions);
+        }
+        if let Some(policy) = self.error_policy {
+            walker = walker.error_policy(policy);
+        }
+        if let Some(threads) = self.threads {
+            walker = walker.threads(threads);
+        }
+        for pattern in &self.excludes {
+            walker = walker.exclude(pattern);
+        }
+        if let Some(pause) = self.throttle {
+            walker = walker.throttle(pause);
+        }
+        if let Some(latency) = self.target_latency {
+            walker = walker.target_latency(latency);
+        }
+        if let Some(percent) = self.io_backoff {
+            walker = walker.io_backoff(percent);
+        }
+        if let Some(yes) = self.pause_on_battery {
+            walker = walker.pause_on_battery(yes);
+        }
+        if let Some(log) = &self.record {
+            walker = walker.record(log);
+        }
+        if let Some(interval) = self.throughput {
+            walker = walker.throughput(interval);
+        }
+        if let Some(files) = self.expected_files {
+            walker = walker.expected_files(files);
+        }
+        let extensions: Vec<&str> = self.extensions.iter().map(String::as_str).collect();
+        walker = walker.extensions(&extensions);
+        if let Some(bytes) = self.min_size {
+            walker = walker.min_size(bytes);
+        }
+        if let Some(bytes) = self.max_size {
+            walker = walker.max_size(bytes);
+        }
+        if let Some(yes) = self.follow_reparse_points {
+            walker = walker.follow_reparse_points(yes);
+        }
+        if let Some(yes) = self.treat_bundles_as_files {
+            walker = walker.treat_bundles_as_files(yes);
+        }
+        if let Some(yes) = self.report_bundles {
+            walker = walker.report_bundles(yes);
+        }
+        if let Some(yes) = self.spotlight {
+            walker = walker.spotlight(yes);
+        }
+        if let Some(yes) = self.mmap {
+            walker = walker.mmap(yes);
+        }
+        if let Some(depth) = self.min_depth {
+            walker = walker.min_depth(depth);
+        }
+        if let Some(hops) = self.max_symlink_depth {
+            walker = walker.max_symlink_depth(hops);
+        }
+        if let Some(priority) = self.priority {
+            walker = walker.prioritize(priority);
+        }
+        let priority_dirs: Vec<&str> = self.prioritize_dirs.iter().map(String::as_str).collect();
+        walker = walker.prioritize_dirs(&priority_dirs);
+        if let Some(seed) = self.shuffle {
+            walker = walker.shuffle(seed);
+        }
+        if let Some(yes) = self.thumbnails {
+            walker = walker.thumbnails(yes);
+        }
+        if let Some(yes) = self.include_trash {
+            walker = walker.include_trash(yes);
+        }
+        if let Some(names) = &self.workflow_caches {
+            let names: Vec<&str> = names.iter().map(String::as_str).collect();
+            walker = walker.workflow_caches(&names);
+        }
+        if let Some(strategy) = self.ids {
+            walker = walker.ids(strategy);
+        }
+        if let Some(yes) = self.flag_suspicious {
+            walker = walker.flag_suspicious(yes);
+        }
+        if let Some(yes) = self.check_paths {
+            walker = walker.check_paths(yes);
+        }
+        if let Some(form) = self.normalize_paths {
+            walker = walker.normalize_paths(form);
+        }
+        if let Some(yes) = self.dedupe_paths {
+            walker = walker.dedupe_paths(yes);
+        }
+        if let Some(threads) = self.hash_concurrency {
+            walker = walker.hash_concurrency(threads);
+        }
+        if let Some(yes) = self.progressive {
+            walker = walker.progressive(yes);
+        }
+        if let Some(dirs) = self.emit_dirs {
+            walker = walker.emit_dirs(dirs);
+        }
+        if let Some(threads) = self.traversal_threads {
+            walker = walker.traversal_threads(threads);
+        }
+        if let Some(bytes) = self.memory_budget {
+            walker = walker.memory_budget(usize::try_from(bytes).unwrap_or(usize::MAX));
+        }
+        for &(kind, threads) in &self.stage_concurrency {
+            walker = walker.stage_concurrency(kind, threads);
+        }
+        if let Some(yes) = self.report_dangling_symlinks {
+            walker = walker.report_dangling_symlinks(yes);
+        }
+        if let Some(yes) = self.video_signatures {
+            walker = walker.video_signatures(yes);
+        }
+        if let Some(yes) = self.color_info {
+            walker = walker.color_info(yes);
+        }
+        if let Some(yes) = self.detect_animation {
+            walker = walker.detect_animation(yes);
+        }
+        if let Some(yes) = self.motion_photos {
+            walker = walker.motion_photos(yes);
+        }
+        if let Some(yes) = self.lossy_paths {
+            walker = walker.lossy_paths(yes);
+        }
+        if let Some(yes) = self.check_permissions {
+            walker = walker.check_permissions(yes);
+        }
+        if let Some(handling) = self.locked_files {
+            walker = walker.locked_files(handling);
+        }
+        if let Some(yes) = self.artwork {
+            walker = walker.artwork(yes);
+        }
+        #[cfg(feature = "documents")]
+        if let Some(yes) = self.embedded_media {
+            walker = walker.embedded_media(yes);
+        }
+        if let Some(yes) = self.exif {
+            walker = walker.exif(yes);
+        }
+        if let Some(yes) = self.media_hints {
+            walker = walker.media_hints(yes);
+        }
+        if let Some(filter) = &self.filter {
+            walker = walker.filter(filter.clone());
+        }
+        walker
+    }
+
+    /// Creates a walker for the configured root with the configured options.
+    pub fn walker(&self) -> Result<MediaWalker, ConfigError> {
+        let root = self.root.as_ref().ok_or(ConfigError::MissingRoot)?;
+        Ok(self.apply(MediaWalker::new(root)))
+    }
+}
+
+/// A configuration value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Value {
+    Str(String),
+    Int(i64),
+    Bool(bool),
+    List(Vec<Value>),
+    /// An untyped value from an environment variable, parsed as needed.
+    Raw(String),
+}
+
+impl Value {
+    pub(crate) fn into_string(self) -> Result<String, String> {
+        match self {
+            Value::Str(s) | Value::Raw(s) => Ok(s),
+            _ => Err("expected a string".to_string()),
+        }
+    }
+
+    pub(crate) fn into_bool(self) -> Result<bool, String> {
+        match self {
+            Value::Bool(b) => Ok(b),
+            Value::Raw(s) => match s.to_ascii_lowercase().as_str() {
+                "true" | "yes" | "on" | "1" => Ok(true),
+                "false" | "no" | "off" | "0" => Ok(false),
+                _ => Err(format!("expected a boolean, not '{}'", s)),
+            },
+            _ => Err("expected a boolean".to_string()),
+        }
+    }
+
+    pub(crate) fn into_int(self) -> Result<i64, String> {
+        match self {
+            Value::Int(i) => Ok(i),
+            Value::Raw(s) => s
+                .trim()
+                .parse()
+                .map_err(|_| format!("expected an integer, not '{}'", s)),
+            _ => Err("expected an integer".to_string()),
+        }
+    }
+
+    /// A non-negative integer.
+    pub(crate) fn into_count(self) -> Result<usize, String> {
+        let i = self.into_int()?;
+        usize::try_from(i).map_err(|_| format!("expected a positive integer, not {}", i))
+    }
+
+    pub(crate) fn into_strings(self) -> Result<Vec<String>, String> {
+        match self {
+            Value::List(items) => items.into_iter().map(Value::into_string).collect(),
+            Value::Raw(s) => Ok(s
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+                .collect()),
+            _ => Err("expected an array of strings".to_string()),
+        }
+    }
+
+    /// A size in bytes: an integer, or a string with a unit, like
+    /// `"10kB"` or `"1GB"`.
+    pub(crate) fn into_size(self) -> Result<u64, String> {
+        let s = match self {
+            Value::Int(bytes) => {
+                return u64::try_from(bytes).map_err(|_| format!("invalid size {}", bytes))
+            }
+            Value::Str(s) | Value::Raw(s) => s,
+            _ => return Err("expected a size".to_string()),
+        };
+        parse_size(&s).ok_or_else(|| format!("expected a size, not '{}'", s))
+    }
+
+    /// A duration: an integer number of milliseconds, or a string with
+    /// a unit, like `"250ms"`, `"2s"`, `"5m"` or `"1h"`.
+    pub(crate) fn into_duration(self) -> Result<Duration, String> {
+        let s = match self {
+            Value::Int(ms) => return Ok(Duration::from_millis(ms.max(0) as u64)),
+            Value::Str(s) | Value::Raw(s) => s,
+            _ => return Err("expected a duration".to_string()),
+        };
+        parse_duration(&s).ok_or_else(|| format!("expected a duration, not '{}'", s))
+    }
+
+    /// Parses a list of strings, e.g. kind names.
+    pub(crate) fn parse_list<T>(self) -> Result<Vec<T>, String>
+    where
+        T: std::str::FromStr<Err = String>,
+    {
+        self.into_strings()?.iter().map(|s| s.parse()).collect()
+    }
+}
+
+/// Parses a size like `"100"`, `"10kB"`, `"1.5GB"` or `"4GiB"` into
+/// bytes. Units are case-insensitive; `k`, `M`, `G` and `T` are decimal,
+/// `Ki`, `Mi`, `Gi` and `Ti` binary.
+pub(crate) fn parse_size(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let unit = unit.trim().to_ascii_lowercase();
+    let unit = unit.strip_suffix('b').unwrap_or(&unit);
+    let multiplier: u64 = match unit {
+        "" => 1,
+        "k" => 1_000,
+        "m" => 1_000_000,
+        "g" => 1_000_000_000,
+        "t" => 1_000_000_000_000,
+        "ki" => 1 << 10,
+        "mi" => 1 << 20,
+        "gi" => 1 << 30,
+        "ti" => 1 << 40,
+        _ => return None,
+    };
+    Some((number * multiplier as f64).round() as u64)
+}
+
+/// Parses a duration like `"250ms"`, `"2s"`, `"5m"` or `"1h"`. A bare
+/// number is milliseconds.
+pub(crate) fn parse_duration(s: &str) -> Option<Duration> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: u64 = number.parse().ok()?;
+    match unit.trim() {
+        "" | "ms" => Some(Duration::from_millis(number)),
+        "s" => Some(Duration::from_secs(number)),
+        "m" | "min" => Some(Duration::from_secs(number * 60)),
+        "h" => Some(Duration::from_secs(number * 3600)),
+        _ => None,
+    }
+}
+
+/// A parser for the flat subset of TOML and JSON.
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    toml: bool,
+}
+
+impl Parser {
+    fn new(text: &str, toml: bool) -> Self {
+        Parser {
+            chars: text.chars().collect(),
+            pos: 0,
+            toml,
+        }
+    }
+
+    fn toml(mut self) -> Result<Vec<(String, Value)>, ConfigError> {
+        let mut entries = vec![];
+        loop {
+            self.skip_blank(true);
+            match self.peek() {
+                None => return Ok(entries),
+                Some('[') => return Err(self.error("tables are not supported")),
+                Some('"') | Some('\'') => {}
+                Some(c) if is_bare_key(c) => {}
+                Some(c) => return Err(self.error(&format!("unexpected '{}'", c))),
+            }
+            let key = match self.peek() {
+                Some('"') | Some('\'') => self.string()?,
+                _ => self.bare_key(),
+            };
+            self.skip_blank(false);
+            self.expect('=')?;
+            self.skip_blank(false);
+            let value = self.value()?;
+            self.skip_blank(false);
+            match self.peek() {
+                None | Some('\n') => {}
+                Some(c) => return Err(self.error(&format!("unexpected '{}'", c))),
+            }
+            if let Some(value) = value {
+                entries.push((key, value));
+            }
+        }
+    }
+
+    fn json(mut self) -> Result<Vec<(String, Value)>, ConfigError> {
+        let mut entries = vec![];
+        self.skip_blank(true);
+        self.expect('{')?;
+        self.skip_blank(true);
+        if self.peek() == Some('}') {
+            self.pos += 1;
+        } else {
+            loop {
+                self.skip_blank(true);
+                if self.peek() != Some('"') {
+                    return Err(self.error("expected a key"));
+                }
+                let key = self.string()?;
+                self.skip_blank(true);
+                self.expect(':')?;
+                self.skip_blank(true);
+                if let Some(value) = self.value()? {
+                    entries.push((key, value));
+                }
+                self.skip_blank(true);
+                match self.next() {
+                    Some(',') => continue,
+                    Some('}') => break,
+                    _ => return Err(self.error("expected ',' or '}'")),
+                }
+            }
+        }
+        self.skip_blank(true);
+        match self.peek() {
+            None => Ok(entries),
+            Some(c) => Err(self.error(&format!("unexpected '{}'", c))),
+        }
+    }
+
+    /// Parses a value. JSON `null` gives `None`.
+    fn value(&mut self) -> Result<Option<Value>, ConfigError> {
+        match self.peek() {
+            Some('"') => Ok(Some(Value::Str(self.string()?))),
+            Some('\'') if self.toml => Ok(Some(Value::Str(self.string()?))),
+            Some('[') => self.list().map(Some),
+            Some('{') => Err(self.error("nested tables are not supported")),
+            Some(c) if c == '-' || c == '+' || c.is_ascii_digit() => {
+                self.integer().map(|i| Some(Value::Int(i)))
+            }
+            Some(c) if c.is_ascii_alphabetic() => {
+                let word = self.bare_key();
+                match word.as_str() {
+                    "true" => Ok(Some(Value::Bool(true))),
+                    "false" => Ok(Some(Value::Bool(false))),
+                    "null" if !self.toml => Ok(None),
+                    _ => Err(self.error(&format!("unexpected '{}'", word))),
+                }
+            }
+            _ => Err(self.error("expected a value")),
+        }
+    }
+
+    fn list(&mut self) -> Result<Value, ConfigError> {
+        self.expect('[')?;
+        let mut items = vec![];
+        loop {
+            self.skip_blank(true);
+            if self.peek() == Some(']') {
+                self.pos += 1;
+                return Ok(Value::List(items));
+            }
+            match self.value()? {
+                Some(Value::List(_)) => return Err(self.error("nested arrays are not supported")),
+                Some(value) => items.push(value),
+                None => return Err(self.error("null in an array")),
+            }
+            self.skip_blank(true);
+            match self.next() {
+                Some(',') => {}
+                Some(']') => return Ok(Value::List(items)),
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, ConfigError> {
+        let quote = self.next().unwrap();
+        let mut s = String::new();
+        loop {
+            match self.next() {
+                None | Some('\n') => return Err(self.error("unterminated string")),
+                Some(c) if c == quote => return Ok(s),
+                Some('\\') if quote == '"' => match self.next() {
+                    Some('n') => s.push('\n'),
+                    Some('t') => s.push('\t'),
+                    Some('r') => s.push('\r'),
+                    Some('"') => s.push('"'),
+                    Some('\\') => s.push('\\'),
+                    Some('/') if !self.toml => s.push('/'),
+                    Some('u') => s.push(self.unicode_escape()?),
+                    _ => return Err(self.error("invalid escape")),
+                },
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    fn unicode_escape(&mut self) -> Result<char, ConfigError> {
+        let end = self.pos + 4;
+        let hex: String = self
+            .chars
+            .get(self.pos..end)
+            .unwrap_or(&[])
+            .iter()
+            .collect();
+        self.pos = end.min(self.chars.len());
+        u32::from_str_radix(&hex, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| self.error("invalid unicode escape"))
+    }
+
+    fn integer(&mut self) -> Result<i64, ConfigError> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '+' || c == '_' || c == '.' {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        let digits: String = self.chars[start..self.pos]
+            .iter()
+            .filter(|c| !(self.toml && **c == '_'))
+            .collect();
+        digits
+            .parse()
+            .map_err(|_| self.error(&format!("invalid integer '{}'", digits)))
+    }
+
+    fn bare_key(&mut self) -> String {
+        let start = self.pos;
+        while self.peek().is_some_and(is_bare_key) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    /// Skips spaces and, in TOML, comments. Newlines are skipped too, if
+    /// `newlines` is set.
+    fn skip_blank(&mut self, newlines: bool) {
+        while let Some(c) = self.peek() {
+            if c == '#' && self.toml {
+                while self.peek().is_some_and(|c| c != '\n') {
+                    self.pos += 1;
+                }
+            } else if c == ' ' || c == '\t' || c == '\r' || (c == '\n' && newlines) {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), ConfigError> {
+        if self.next() == Some(expected) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", expected)))
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        if c.is_some() {
+            self.pos += 1;
+        }
+        c
+    }
+
+    fn error(&self, message: &str) -> ConfigError {
+        let end = self.pos.min(self.chars.len());
+        ConfigError::Syntax {
+            line: 1 + self.chars[..end].iter().filter(|c| **c == '\n').count(),
+            message: message.to_string(),
+        }
+    }
+}
+
+fn is_bare_key(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '-'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempTree;
+
+    #[test]
+    fn it_reads_toml_and_json_alike() {
+        let tree = TempTree::new("config");
+        let toml = tree.file(
+            "photos.toml",
+            br#"
+            root = "/mnt/nas/photos" # The NAS share
+            kinds = [
+                "image",
+                'video',
+            ]
+            companions = false
+            error_policy = "fail-fast"
+            "#,
+        );
+        let json = tree.file(
+            "photos.json",
+            br#"{
+                "root": "/mnt/nas/photos",
+                "kinds": ["image", "video"],
+                "companions": false,
+                "error_policy": "fail-fast"
+            }"#,
+        );
+        let expected = WalkConfig {
+            root: Some(PathBuf::from("/mnt/nas/photos")),
+            kinds: Some(vec![Kind::Image, Kind::Video]),
+            extra_kinds: vec![],
+            companions: Some(false),
+            error_policy: Some(ErrorPolicy::FailFast),
+            ..WalkConfig::default()
+        };
+        assert_eq!(WalkConfig::from_file(toml).unwrap(), expected);
+        assert_eq!(WalkConfig::from_file(json).unwrap(), expected);
+    }
+
+    #[test]
+    fn it_reports_bad_configurations() {
+        match WalkConfig::from_toml("root = \"/\"\n\n[profile]\n") {
+            Err(ConfigError::Syntax { line, .. }) => assert_eq!(line, 3),
+            other => panic!("unexpected {:?}", other),
+        }
+        match WalkConfig::from_json(r#"{"kinds": ["movies"]}"#) {
+            Err(ConfigError::Invalid { key, .. }) => assert_eq!(key, "kinds"),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(matches!(
+            WalkConfig::from_toml("threads = -1"),
+            Err(ConfigError::Invalid { .. })
+        ));
+        assert!(matches!(
+            WalkConfig::from_toml("shuffle = -1"),
+            Err(ConfigError::Invalid { .. })
+        ));
+        match WalkConfig::from_toml(r#"filter = "size > lots""#) {
+            Err(ConfigError::Invalid { key, message }) => {
+                assert_eq!(
+                    (key.as_str(), message.as_str()),
+                    ("filter", "expected a size, not 'lots'")
+                )
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(matches!(
+            WalkConfig::from_toml("thread = 4"),
+            Err(ConfigError::Invalid { .. })
+        ));
+        assert!(matches!(
+            WalkConfig::default().walker(),
+            Err(ConfigError::MissingRoot)
+        ));
+    }
+
+    #[test]
+    fn it_layers_the_environment_over_the_file() {
+        let file = WalkConfig::from_toml(
+            r#"
+            root = "/photos"
+            threads = 2
+            excludes = ["*.tmp"]
+            throttle = 20
+            "#,
+        )
+        .unwrap();
+        let vars = [
+            ("HOME", "/root"),
+            ("MEDIAWALKER_THREADS", "8"),
+            ("MEDIAWALKER_KINDS", "image, video"),
+            ("MEDIAWALKER_EXCLUDES", "cache,thumbs"),
+            ("MEDIAWALKER_COMPANIONS", "yes"),
+            // A key of some other version.
+            ("MEDIAWALKER_TELEPORT", "yes"),
+        ];
+        let env = WalkConfig::from_vars(vars.map(|(k, v)| (k.to_string(), v.to_string()))).unwrap();
+        let config = file.overlay(env);
+        assert_eq!(config.root, Some(PathBuf::from("/photos")));
+        assert_eq!(config.threads, Some(8));
+        assert_eq!(config.kinds, Some(vec![Kind::Image, Kind::Video]));
+        assert_eq!(config.excludes, vec!["*.tmp", "cache", "thumbs"]);
+        assert_eq!(config.companions, Some(true));
+        assert_eq!(config.throttle, Some(Duration::from_millis(20)));
+
+        let sizes =
+            WalkConfig::from_toml("preset = 'photos'\nmin_size = '10kB'\nmax_size = '1.5GiB'")
+                .unwrap();
+        assert_eq!(sizes.preset, Some(Preset::Photos));
+        assert_eq!(sizes.min_size, Some(10_000));
+        assert_eq!(sizes.max_size, Some(1_610_612_736));
+
+        let bad = [("MEDIAWALKER_THROTTLE".to_string(), "fast".to_string())];
+        assert!(WalkConfig::from_vars(bad).is_err());
+    }
+}
@@ -0,0 +1,556 @@
+//! Periodic rescans, behind the `daemon` feature. Also run by
+//! `mediawalker daemon --interval 6h --config profile.toml`.
+//!
+//! A `schedule` in the configuration, like `schedule = "0 3 * * *"`,
+//! sets when the full scans happen, see [`Schedule`], the files being
+//! kept up with in between. An `index`, like `index = "library.db"`, is
+//! the SQLite database kept up to date with the scans, see
+//! [`index`].
+use crate::exif::days_from_civil;
+use crate::index;
+use crate::{ConfigError, FileEntry, MediaWalkError, WalkConfig, WalkStats};
+use std::fmt;
+use std::io;
+use std::ops::ControlFlow;
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How many times the next time of a schedule is looked for, a day, an
+/// hour or a minute ahead at a time, before it is taken to never come:
+/// enough for the rarest schedule, `0 0 29 2 *`.
+const SCHEDULE_STEPS: usize = 1 << 16;
+
+/// A cron schedule, of five fields: the minute, the hour, the day of the
+/// month, the month and the day of the week, from 0 for Sunday to 6, or
+/// 7. Each field is `*`, a number, a range `a-b`, any of them with a step
+/// `/n`, or a list of them separated by commas, as in `*/15 9-17 * * 1-5`.
+/// Where both days are given, either matches. `@hourly`, `@daily`,
+/// `@weekly`, `@monthly` and `@yearly` stand for their schedules. Times
+/// are in UTC.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Whether the day of the month, and of the week, are `*`.
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    /// Parses five fields, or a nickname, such as `0 3 * * *` or
+    /// `@daily`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = match s.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            s => s,
+        };
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(format!("not five fields: {}", s));
+        };
+        let weekdays = field(weekdays, 0, 7)?;
+        let schedule = Schedule {
+            minutes: field(minutes, 0, 59)?,
+            hours: field(hours, 0, 23)?,
+            days: field(days, 1, 31)?,
+            months: field(months, 1, 12)?,
+            // Sunday is 0 or 7.
+            weekdays: (weekdays | weekdays >> 7) & 0x7f,
+            any_day: days == "*",
+            any_weekday: fields[4] == "*",
+        };
+        match schedule.next_after(SystemTime::now()) {
+            Some(_) => Ok(schedule),
+            None => Err(format!("never comes: {}", s)),
+        }
+    }
+}
+
+/// Parses a field of a schedule into the set of its values, as bits.
+fn field(field: &str, min: u64, max: u64) -> Result<u64, String> {
+    let invalid = || format!("invalid schedule field: {}", field);
+    let mut values = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse().map_err(|_| invalid())?),
+            None => (part, 1),
+        };
+        let (first, last) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((first, last)) => (
+                first.parse().map_err(|_| invalid())?,
+                last.parse().map_err(|_| invalid())?,
+            ),
+            None => {
+                let value = range.parse().map_err(|_| invalid())?;
+                (value, if step > 1 { max } else { value })
+            }
+        };
+        if first < min || last > max || first > last || step == 0 {
+            return Err(invalid());
+        }
+        for value in (first..=last).step_by(step as usize) {
+            values |= 1 << value;
+        }
+    }
+    Ok(values)
+}
+
+impl Schedule {
+    /// The first time of the schedule after `time`, at the start of a
+    /// minute. `None` if there is none, as for `0 0 31 2 *`.
+    pub fn next_after(&self, time: SystemTime) -> Option<SystemTime> {
+        let since = time.duration_since(SystemTime::UNIX_EPOCH).ok()?;
+        let mut minute = since.as_secs() / 60 + 1;
+        for _ in 0..SCHEDULE_STEPS {
+            let day = (minute / 1440) as i64;
+            let (month, day_of_month) = civil_from_days(day);
+            // 1970-01-01 was a Thursday.
+            let weekday = (day + 4).rem_euclid(7);
+            let day_matches = match (self.any_day, self.any_weekday) {
+                (true, true) => true,
+                (false, true) => self.days & 1 << day_of_month != 0,
+                (true, false) => self.weekdays & 1 << weekday != 0,
+                (false, false) => {
+                    self.days & 1 << day_of_month != 0 || self.weekdays & 1 << weekday != 0
+                }
+            };
+            if self.months & 1 << month == 0 || !day_matches {
+                minute = (day as u64 + 1) * 1440;
+            } else if self.hours & 1 << (minute / 60 % 24) == 0 {
+                minute = (minute / 60 + 1) * 60;
+            } else if self.minutes & 1 << (minute % 60) == 0 {
+                minute += 1;
+            } else {
+                return Some(SystemTime::UNIX_EPOCH + Duration::from_secs(minute * 60));
+            }
+        }
+        None
+    }
+}
+
+/// The month and the day of the month of a day since 1970-01-01.
+fn civil_from_days(day: i64) -> (u32, u32) {
+    let year = (day as f64 / 365.2425) as i64 + 1970;
+    // The guess may be a year off either way around new year.
+    let year = (year - 1..=year + 1)
+        .rev()
+        .find(|&year| days_from_civil(year, 1, 1) <= day)
+        .unwrap_or(year);
+    let mut month = 12;
+    while days_from_civil(year, month, 1) > day {
+        month -= 1;
+    }
+    (
+        month as u32,
+        (day - days_from_civil(year, month, 1) + 1) as u32,
+    )
+}
+
+/// Parses an interval like `"30m"`, `"6h"` or `"1d"`. A bare number is
+/// seconds.
+pub fn parse_interval(s: &str) -> Option<Duration> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: u64 = number.parse().ok()?;
+    let seconds = match unit.trim() {
+        "" | "s" => 1,
+        "m" | "min" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        _ => return None,
+    };
+    Some(Duration::from_secs(number.checked_mul(seconds)?))
+}
+
+/// What a daemon did, as told to the `report` callback of [`run`].
+/// Shown, they make the lines of the log of `mediawalker daemon`.
+#[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
+pub enum DaemonEvent {
+    /// A scan went through every file if `full`, and otherwise only
+    /// through those modified since the scan before.
+    Scanned {
+        /// The statistics of the scan.
+        stats: WalkStats,
+        /// How long the scan took.
+        elapsed: Duration,
+        /// Whether the scan went through every file.
+        full: bool,
+    },
+    /// A scan could not start, and is tried again at the next interval.
+    ScanFailed(MediaWalkError),
+    /// The [`index`](WalkConfig::index) was written: the files of a full
+    /// scan replacing those indexed before, or the files of another scan
+    /// added or updated, and the files gone since removed.
+    Indexed {
+        /// How many files were written.
+        written: usize,
+        /// How many files were removed.
+        removed: usize,
+    },
+    /// The index could not be written. The next full scan writes it
+    /// anew.
+    IndexFailed(io::Error),
+    /// A file was added under the root, while watching it in between
+    /// full scans.
+    Added(String),
+    /// A file under the root was written to, while watching it.
+    Modified(String),
+    /// A file or a directory under the root went away, while watching
+    /// it.
+    Removed(String),
+}
+
+impl fmt::Display for DaemonEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DaemonEvent::Scanned { stats, elapsed, .. } => f.write_str(&summary(stats, *elapsed)),
+            DaemonEvent::ScanFailed(err) => write!(f, "scan failed: {}", err),
+            DaemonEvent::Indexed { written, removed } => {
+                write!(f, "indexed {} files, removed {}", written, removed)
+            }
+            DaemonEvent::IndexFailed(err) => write!(f, "indexing failed: {}", err),
+            DaemonEvent::Added(path) => write!(f, "added {}", path),
+            DaemonEvent::Modified(path) => write!(f, "modified {}", path),
+            DaemonEvent::Removed(path) => write!(f, "removed {}", path),
+        }
+    }
+}
+
+/// Why a daemon stops.
+enum Stop {
+    Config(ConfigError),
+    /// The `report` callback broke.
+    Asked,
+}
+
+/// Hands an event to the `report` callback of [`run`].
+fn tell<F>(report: &mut F, event: DaemonEvent) -> Result<(), Stop>
+where
+    F: FnMut(DaemonEvent) -> ControlFlow<()>,
+{
+    match report(event) {
+        ControlFlow::Continue(()) => Ok(()),
+        ControlFlow::Break(()) => Err(Stop::Asked),
+    }
+}
+
+/// Scans the configured root every `interval`, telling `report` how
+/// each scan went. The first scan goes through every file, the later
+/// ones only those modified since the scan before. A scan that cannot
+/// start is reported and tried again at the next interval. With an
+/// [`index`](WalkConfig::index), the results of the scans are written
+/// to it, with the files gone since removed, see [`DaemonEvent::Indexed`].
+/// Returns once `report` breaks, or if the configuration has no root.
+///
+/// With a [`schedule`](WalkConfig::schedule), the first scan and the
+/// ones at the times of the schedule go through every file, and the
+/// others in between, still every `interval`. With the `watch` feature
+/// on Linux, the root is watched in between instead, the changes being
+/// reported, and indexed, as they happen.
+///
+/// # Examples
+///
+/// ```no_run
+/// use mediawalker::daemon;
+/// use mediawalker::WalkConfig;
+/// use std::ops::ControlFlow;
+/// use std::time::Duration;
+/// let config = WalkConfig::from_file("profile.toml")?;
+/// daemon::run(&config, Duration::from_secs(6 * 3600), |event| {
+///     println!("{}", event);
+///     ControlFlow::Continue(())
+/// })?;
+/// # Ok::<(), mediawalker::ConfigError>(())
+/// ```
+pub fn run<F>(config: &WalkConfig, interval: Duration, mut report: F) -> Result<(), ConfigError>
+where
+    F: FnMut(DaemonEvent) -> ControlFlow<()>,
+{
+    match run_until_stopped(config, interval, &mut report) {
+        Err(Stop::Config(err)) => Err(err),
+        Err(Stop::Asked) | Ok(()) => Ok(()),
+    }
+}
+
+fn run_until_stopped<F>(config: &WalkConfig, interval: Duration, report: &mut F) -> Result<(), Stop>
+where
+    F: FnMut(DaemonEvent) -> ControlFlow<()>,
+{
+    let mut since = None;
+    loop {
+        let started = Instant::now();
+        scan_reported(config, &mut since, report)?;
+        let next_full = config
+            .schedule
+            .as_ref()
+            .and_then(|schedule| schedule.next_after(SystemTime::now()));
+        let Some(next_full) = next_full else {
+            thread::sleep(interval.saturating_sub(started.elapsed()));
+            continue;
+        };
+        keep_up(config, &mut since, interval, next_full, report)?;
+        since = None;
+    }
+}
+
+/// Keeps up with the changes until a time, watching for them where the
+/// `watch` feature is on, and scanning every `interval` otherwise.
+fn keep_up<F>(
+    config: &WalkConfig,
+    since: &mut Option<SystemTime>,
+    interval: Duration,
+    until: SystemTime,
+    report: &mut F,
+) -> Result<(), Stop>
+where
+    F: FnMut(DaemonEvent) -> ControlFlow<()>,
+{
+    #[cfg(all(feature = "watch", target_os = "linux"))]
+    if let Ok(watcher) = config.walker().map_err(Stop::Config)?.watch() {
+        use crate::WatchEvent;
+        while let Ok(left) = until.duration_since(SystemTime::now()) {
+            // Either no change came in time, or the watch ended.
+            let Some(event) = watcher.next_timeout(left) else {
+                break;
+            };
+            let told = match &event {
+                WatchEvent::Found(walk_result) | WatchEvent::Added(walk_result) => {
+                    DaemonEvent::Added(walk_result.path.clone())
+                }
+                WatchEvent::Modified(walk_result) => {
+                    DaemonEvent::Modified(walk_result.path.clone())
+                }
+                WatchEvent::Removed(path) => DaemonEvent::Removed(path.clone()),
+                WatchEvent::Overflowed => {
+                    scan_reported(config, since, report)?;
+                    continue;
+                }
+            };
+            tell(report, told)?;
+            if let Some(db) = &config.index {
+                if let Err(err) = index::apply(db, vec![event]) {
+                    tell(report, DaemonEvent::IndexFailed(err))?;
+                }
+            }
+        }
+    }
+    loop {
+        let left = until.duration_since(SystemTime::now()).unwrap_or_default();
+        if left <= interval {
+            thread::sleep(left);
+            return Ok(());
+        }
+        thread::sleep(interval);
+        scan_reported(config, since, report)?;
+    }
+}
+
+/// Scans once, as [`scan`] does, reporting how the scan went or why it
+/// could not start, and moving `since` to when the scan started.
+fn scan_reported<F>(
+    config: &WalkConfig,
+    since: &mut Option<SystemTime>,
+    report: &mut F,
+) -> Result<(), Stop>
+where
+    F: FnMut(DaemonEvent) -> ControlFlow<()>,
+{
+    let started = Instant::now();
+    let scanned_at = SystemTime::now();
+    match scan(config, *since) {
+        Ok((stats, indexed)) => {
+            let full = since.is_none();
+            *since = Some(scanned_at);
+            tell(
+                report,
+                DaemonEvent::Scanned {
+                    stats,
+                    elapsed: started.elapsed(),
+                    full,
+                },
+            )?;
+            match indexed {
+                Some(Ok((written, removed))) => {
+                    tell(report, DaemonEvent::Indexed { written, removed })
+                }
+                Some(Err(err)) => tell(report, DaemonEvent::IndexFailed(err)),
+                None => Ok(()),
+            }
+        }
+        Err(ScanError::Config(err)) => Err(Stop::Config(err)),
+        Err(ScanError::Walk(err)) => tell(report, DaemonEvent::ScanFailed(err)),
+    }
+}
+
+enum ScanError {
+    Config(ConfigError),
+    Walk(MediaWalkError),
+}
+
+/// How many files a scan wrote to the index, and how many it removed,
+/// if there is one.
+type Indexed = Option<io::Result<(usize, usize)>>;
+
+/// Scans once, going through the files modified since `since` only, if
+/// given, and writing the results to the index, if there is one: all of
+/// them in place of the files indexed before, or, with `since`, the
+/// modified ones, removing the files that are gone.
+fn scan(config: &WalkConfig, since: Option<SystemTime>) -> Result<(WalkStats, Indexed), ScanError> {
+    let mut walker = config.walker().map_err(ScanError::Config)?;
+    if let Some(since) = since {
+        walker = walker.filter_files(move |file: &FileEntry| {
+            file.metadata
+                .and_then(|metadata| metadata.modified().ok())
+                .is_none_or(|modified| modified >= since)
+        });
+    }
+    let mut handle = walker.try_start().map_err(ScanError::Walk)?;
+    let indexed = match (&config.index, since) {
+        (None, _) => {
+            handle.by_ref().for_each(drop);
+            None
+        }
+        (Some(db), None) => Some(index::write(db, handle.by_ref()).map(|written| (written, 0))),
+        (Some(db), Some(_)) => Some(
+            index::update(db, handle.by_ref()).and_then(|written| Ok((written, index::prune(db)?))),
+        ),
+    };
+    // What an index that failed did not take is left to the walk.
+    handle.by_ref().for_each(drop);
+    Ok((handle.stats(), indexed))
+}
+
+fn summary(stats: &WalkStats, elapsed: Duration) -> String {
+    format!(
+        "scanned {} files in {:.1}s: {} matched ({} bytes), {} unknown, {} errors",
+        stats.files,
+        elapsed.as_secs_f64(),
+        stats.matched,
+        stats.bytes,
+        stats.unknown,
+        stats.errors
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempTree;
+    use std::fs;
+    use std::path::PathBuf;
+
+    #[test]
+    fn it_rescans_modified_files_only() {
+        assert_eq!(parse_interval("6h"), Some(Duration::from_secs(6 * 3600)));
+        assert_eq!(parse_interval("1d"), Some(Duration::from_secs(86400)));
+        assert_eq!(parse_interval("90"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_interval("6 weeks"), None);
+
+        let tree = TempTree::new("daemon");
+        tree.sample("sample_640x426.jpg", "a.jpg");
+        let config = WalkConfig {
+            root: Some(tree.path().to_path_buf()),
+            ..WalkConfig::default()
+        };
+        let Ok((stats, None)) = scan(&config, None) else {
+            panic!("the scan failed");
+        };
+        assert_eq!(stats.matched, 1);
+        let later = SystemTime::now() + Duration::from_secs(60);
+        let Ok((stats, None)) = scan(&config, Some(later)) else {
+            panic!("the scan failed");
+        };
+        assert_eq!(stats.files, 0);
+        assert!(summary(&stats, Duration::from_millis(1500)).starts_with("scanned 0 files in 1.5s"));
+    }
+
+    #[test]
+    fn it_keeps_the_index_up_to_date() {
+        crate::index::require_sqlite3();
+        let tree = TempTree::new("daemon-index");
+        let elsewhere = TempTree::new("daemon-index-db");
+        let db = elsewhere.path().join("library.db");
+        let photo = tree.sample("sample_640x426.jpg", "a.jpg");
+        let config = WalkConfig {
+            root: Some(tree.path().to_path_buf()),
+            index: Some(db.clone()),
+            ..WalkConfig::default()
+        };
+        let indexed = || -> Vec<_> {
+            let index = crate::index::open(&db).unwrap();
+            let files = index.in_dir(tree.path()).unwrap();
+            files
+                .into_iter()
+                .map(|file| PathBuf::from(file.path))
+                .collect()
+        };
+        let mut scans = vec![];
+        let mut song = None;
+        run(&config, Duration::from_millis(10), |event| match event {
+            DaemonEvent::Scanned { full, .. } => {
+                scans.push(full);
+                ControlFlow::Continue(())
+            }
+            DaemonEvent::Indexed { written, removed } if scans.len() == 1 => {
+                assert_eq!((written, removed), (1, 0));
+                assert_eq!(indexed(), std::slice::from_ref(&photo));
+                fs::remove_file(&photo).unwrap();
+                song = Some(tree.sample("sample3.mp3", "song.mp3"));
+                ControlFlow::Continue(())
+            }
+            DaemonEvent::Indexed { written, removed } => {
+                assert_eq!((written, removed), (1, 1));
+                assert_eq!(indexed(), song.as_slice());
+                ControlFlow::Break(())
+            }
+            event => panic!("unexpected: {}", event),
+        })
+        .unwrap();
+        assert_eq!(scans, [true, false]);
+    }
+
+    #[test]
+    fn it_schedules_scans() {
+        let at = |days: i64, hours: u64, minutes: u64| {
+            let day = days_from_civil(2026, 1, 1) + days;
+            SystemTime::UNIX_EPOCH
+                + Duration::from_secs(day as u64 * 86400 + hours * 3600 + minutes * 60)
+        };
+        // 2026-01-01 was a Thursday.
+        let nightly: Schedule = "0 3 * * *".parse().unwrap();
+        assert_eq!(nightly.next_after(at(0, 2, 30)), Some(at(0, 3, 0)));
+        assert_eq!(nightly.next_after(at(0, 3, 0)), Some(at(1, 3, 0)));
+        let weekdays: Schedule = "*/15 9-17 * * 1-5".parse().unwrap();
+        assert_eq!(weekdays.next_after(at(1, 17, 50)), Some(at(4, 9, 0)));
+        assert_eq!(weekdays.next_after(at(4, 9, 0)), Some(at(4, 9, 15)));
+        let sundays: Schedule = "30 1 * * 7".parse().unwrap();
+        assert_eq!(sundays.next_after(at(0, 0, 0)), Some(at(3, 1, 30)));
+        // On the 1st of the month, or on Mondays.
+        let either: Schedule = "0 0 1 * 1".parse().unwrap();
+        assert_eq!(either.next_after(at(0, 1, 0)), Some(at(4, 0, 0)));
+        assert_eq!(either.next_after(at(26, 1, 0)), Some(at(31, 0, 0)));
+        let leap: Schedule = "0 0 29 2 *".parse().unwrap();
+        assert_eq!(leap.next_after(at(0, 0, 0)), Some(at(365 + 365 + 59, 0, 0)));
+        assert_eq!("@daily".parse::<Schedule>(), "0 0 * * *".parse());
+
+        assert!("0 3 * *".parse::<Schedule>().is_err());
+        assert!("60 3 * * *".parse::<Schedule>().is_err());
+        assert!("0 0 31 2 *".parse::<Schedule>().is_err());
+
+        let config = WalkConfig::from_toml("schedule = \"0 3 * * *\"").unwrap();
+        assert_eq!(config.schedule, Some(nightly));
+    }
+}
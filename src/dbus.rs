@@ -0,0 +1,788 @@
+//! A D-Bus service for desktop integration on Linux, behind the `dbus`
+//! feature. Also run by `mediawalker dbus`.
+//!
+//! The service takes the name [`BUS_NAME`] on the session bus, and
+//! exports the [`INTERFACE`] interface at [`OBJECT_PATH`]:
+//!
+//! - `StartScan(s root, as kinds) -> (u id)` starts walking `root` for
+//!   the named kinds, or the default ones if there are none, and returns
+//!   the id of the scan.
+//! - `CancelScan(u id)` stops a scan.
+//! - The `MediaFound(u id, s path, s mime, s kind)` signal is sent for
+//!   each media file a scan finds, and `ScanFinished(u id)` once it is
+//!   over, cancelled or not.
+//!
+//! It speaks the D-Bus wire protocol itself, over the Unix socket of the
+//! bus.
+use crate::{Kind, MediaWalker};
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::{env, thread};
+
+/// The name the service takes on the bus.
+pub const BUS_NAME: &str = "io.github.kontza.MediaWalker";
+
+/// The path of the object the service exports.
+pub const OBJECT_PATH: &str = "/io/github/kontza/MediaWalker";
+
+/// The interface of the service.
+pub const INTERFACE: &str = "io.github.kontza.MediaWalker1";
+
+/// The largest message accepted from the bus, in bytes.
+const MAX_MESSAGE: usize = 1024 * 1024;
+
+const METHOD_CALL: u8 = 1;
+const METHOD_RETURN: u8 = 2;
+const ERROR: u8 = 3;
+const SIGNAL: u8 = 4;
+
+/// The flag of a method call whose caller wants no reply.
+const NO_REPLY_EXPECTED: u8 = 0x1;
+
+/// The reply of `RequestName` when the name was taken.
+const PRIMARY_OWNER: u32 = 1;
+/// The flag of `RequestName` that fails instead of queueing for a name.
+const DO_NOT_QUEUE: u32 = 0x4;
+
+const INTROSPECTION: &str = r#"<!DOCTYPE node PUBLIC "-//freedesktop//DTD D-BUS Object Introspection 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/introspect.dtd">
+<node>
+  <interface name="io.github.kontza.MediaWalker1">
+    <method name="StartScan">
+      <arg name="root" type="s" direction="in"/>
+      <arg name="kinds" type="as" direction="in"/>
+      <arg name="id" type="u" direction="out"/>
+    </method>
+    <method name="CancelScan">
+      <arg name="id" type="u" direction="in"/>
+    </method>
+    <signal name="MediaFound">
+      <arg name="id" type="u"/>
+      <arg name="path" type="s"/>
+      <arg name="mime" type="s"/>
+      <arg name="kind" type="s"/>
+    </signal>
+    <signal name="ScanFinished">
+      <arg name="id" type="u"/>
+    </signal>
+  </interface>
+  <interface name="org.freedesktop.DBus.Introspectable">
+    <method name="Introspect">
+      <arg name="xml" type="s" direction="out"/>
+    </method>
+  </interface>
+  <interface name="org.freedesktop.DBus.Peer">
+    <method name="Ping"/>
+  </interface>
+</node>
+"#;
+
+extern "C" {
+    fn getuid() -> u32;
+}
+
+/// A D-Bus message, as far as the service needs it. Only the header
+/// fields are decoded; the body is left to the method called.
+#[derive(Debug, Default, Clone)]
+struct Message {
+    kind: u8,
+    flags: u8,
+    serial: u32,
+    path: Option<String>,
+    interface: Option<String>,
+    member: Option<String>,
+    error_name: Option<String>,
+    reply_serial: Option<u32>,
+    destination: Option<String>,
+    sender: Option<String>,
+    signature: String,
+    body: Vec<u8>,
+    big_endian: bool,
+}
+
+impl Message {
+    /// A call of a method of the bus itself.
+    fn bus_call(member: &str, signature: &str, body: Vec<u8>) -> Self {
+        Message {
+            kind: METHOD_CALL,
+            path: Some("/org/freedesktop/DBus".into()),
+            interface: Some("org.freedesktop.DBus".into()),
+            member: Some(member.into()),
+            destination: Some("org.freedesktop.DBus".into()),
+            signature: signature.into(),
+            body,
+            ..Message::default()
+        }
+    }
+
+    /// A reply to a method call.
+    fn reply(call: &Message, signature: &str, body: Vec<u8>) -> Self {
+        Message {
+            kind: METHOD_RETURN,
+            reply_serial: Some(call.serial),
+            destination: call.sender.clone(),
+            signature: signature.into(),
+            body,
+            ..Message::default()
+        }
+    }
+
+    /// An error reply to a method call.
+    fn error(call: &Message, name: &str, text: &str) -> Self {
+        let mut body = Writer::default();
+        body.string(text);
+        Message {
+            kind: ERROR,
+            error_name: Some(name.into()),
+            ..Message::reply(call, "s", body.bytes)
+        }
+    }
+
+    /// A signal of the service.
+    fn signal(member: &str, signature: &str, body: Vec<u8>) -> Self {
+        Message {
+            kind: SIGNAL,
+            path: Some(OBJECT_PATH.into()),
+            interface: Some(INTERFACE.into()),
+            member: Some(member.into()),
+            signature: signature.into(),
+            body,
+            ..Message::default()
+        }
+    }
+
+    /// The message on the wire, always little-endian.
+    fn encode(&self, serial: u32) -> Vec<u8> {
+        let mut message = Writer::default();
+        for byte in [b'l', self.kind, self.flags, 1] {
+            message.byte(byte);
+        }
+        message.u32(self.body.len() as u32);
+        message.u32(serial);
+        let strings = [
+            (1, "o", &self.path),
+            (2, "s", &self.interface),
+            (3, "s", &self.member),
+            (4, "s", &self.error_name),
+            (6, "s", &self.destination),
+            (7, "s", &self.sender),
+        ];
+        message.array(8, |fields| {
+            for (code, signature, value) in strings {
+                if let Some(value) = value {
+                    fields.align(8);
+                    fields.byte(code);
+                    fields.signature(signature);
+                    fields.string(value);
+                }
+            }
+            if let Some(reply_serial) = self.reply_serial {
+                fields.align(8);
+                fields.byte(5);
+                fields.signature("u");
+                fields.u32(reply_serial);
+            }
+            if !self.signature.is_empty() {
+                fields.align(8);
+                fields.byte(8);
+                fields.signature("g");
+                fields.signature(&self.signature);
+            }
+        });
+        message.align(8);
+        message.bytes.extend(&self.body);
+        message.bytes
+    }
+
+    /// Reads a message, `None` if the bus closed the connection.
+    fn read(reader: &mut impl Read) -> io::Result<Option<Message>> {
+        let mut fixed = [0; 16];
+        match reader.read_exact(&mut fixed) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err),
+        }
+        let big_endian = match fixed[0] {
+            b'l' => false,
+            b'B' => true,
+            _ => return Err(invalid("bad byte order")),
+        };
+        let mut header = Reader {
+            bytes: &fixed,
+            pos: 4,
+            big_endian,
+        };
+        let body_len = header.u32()? as usize;
+        let serial = header.u32()?;
+        let fields_len = header.u32()? as usize;
+        // The fields are padded to a multiple of 8.
+        let rest_len = fields_len.div_ceil(8) * 8;
+        if rest_len + body_len > MAX_MESSAGE {
+            return Err(invalid("message too large"));
+        }
+        let mut rest = vec![0; rest_len + body_len];
+        reader.read_exact(&mut rest)?;
+        let mut bytes = fixed.to_vec();
+        bytes.extend(&rest[..rest_len]);
+        let mut message = Message {
+            kind: fixed[1],
+            flags: fixed[2],
+            serial,
+            body: rest[rest_len..].to_vec(),
+            big_endian,
+            ..Message::default()
+        };
+        let mut fields = Reader {
+            bytes: &bytes[..16 + fields_len],
+            pos: 16,
+            big_endian,
+        };
+        while !fields.is_done() {
+            fields.align(8)?;
+            let code = fields.byte()?;
+            let signature = fields.signature()?;
+            let value = match signature.as_str() {
+                "s" | "o" => Value::String(fields.string()?),
+                "g" => Value::String(fields.signature()?),
+                "u" => Value::U32(fields.u32()?),
+                _ => return Err(invalid("unsupported header field")),
+            };
+            match (code, value) {
+                (1, Value::String(s)) => message.path = Some(s),
+                (2, Value::String(s)) => message.interface = Some(s),
+                (3, Value::String(s)) => message.member = Some(s),
+                (4, Value::String(s)) => message.error_name = Some(s),
+                (5, Value::U32(n)) => message.reply_serial = Some(n),
+                (6, Value::String(s)) => message.destination = Some(s),
+                (7, Value::String(s)) => message.sender = Some(s),
+                (8, Value::String(s)) => message.signature = s,
+                _ => {}
+            }
+        }
+        Ok(Some(message))
+    }
+
+    /// A reader of the body.
+    fn body(&self) -> Reader<'_> {
+        Reader {
+            bytes: &self.body,
+            pos: 0,
+            big_endian: self.big_endian,
+        }
+    }
+}
+
+/// A header field value.
+enum Value {
+    String(String),
+    U32(u32),
+}
+
+/// Marshals values, aligned from the start of the message or body.
+#[derive(Default)]
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn align(&mut self, to: usize) {
+        while !self.bytes.len().is_multiple_of(to) {
+            self.bytes.push(0);
+        }
+    }
+
+    fn byte(&mut self, byte: u8) {
+        self.bytes.push(byte);
+    }
+
+    fn u32(&mut self, n: u32) {
+        self.align(4);
+        self.bytes.extend(n.to_le_bytes());
+    }
+
+    fn string(&mut self, s: &str) {
+        self.u32(s.len() as u32);
+        self.bytes.extend(s.as_bytes());
+        self.bytes.push(0);
+    }
+
+    fn signature(&mut self, s: &str) {
+        self.byte(s.len() as u8);
+        self.bytes.extend(s.as_bytes());
+        self.bytes.push(0);
+    }
+
+    /// An array of elements aligned to `align`, written by `elements`.
+    fn array(&mut self, align: usize, elements: impl FnOnce(&mut Writer)) {
+        self.u32(0);
+        let len_at = self.bytes.len() - 4;
+        self.align(align);
+        let start = self.bytes.len();
+        elements(self);
+        let len = (self.bytes.len() - start) as u32;
+        self.bytes[len_at..len_at + 4].copy_from_slice(&len.to_le_bytes());
+    }
+}
+
+/// Unmarshals values, aligned from the start of the message or body.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    big_endian: bool,
+}
+
+impl Reader<'_> {
+    fn is_done(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+
+    fn take(&mut self, len: usize) -> io::Result<&[u8]> {
+        let bytes = self
+            .bytes
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| invalid("truncated message"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn align(&mut self, to: usize) -> io::Result<()> {
+        let padding = (to - self.pos % to) % to;
+        self.take(padding).map(|_| ())
+    }
+
+    fn byte(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        self.align(4)?;
+        let bytes = self.take(4)?.try_into().unwrap();
+        Ok(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let len = self.u32()? as usize;
+        let s = String::from_utf8(self.take(len)?.to_vec()).map_err(|_| invalid("bad string"))?;
+        self.take(1)?;
+        Ok(s)
+    }
+
+    fn signature(&mut self) -> io::Result<String> {
+        let len = usize::from(self.byte()?);
+        let s =
+            String::from_utf8(self.take(len)?.to_vec()).map_err(|_| invalid("bad signature"))?;
+        self.take(1)?;
+        Ok(s)
+    }
+
+    fn strings(&mut self) -> io::Result<Vec<String>> {
+        let len = self.u32()? as usize;
+        let end = self.pos + len;
+        let mut strings = vec![];
+        while self.pos < end {
+            strings.push(self.string()?);
+        }
+        Ok(strings)
+    }
+}
+
+/// The sending half of a connection, shared with the scans.
+struct Sender {
+    stream: Mutex<UnixStream>,
+    serial: AtomicU32,
+}
+
+impl Sender {
+    /// Sends a message, returning its serial.
+    fn send(&self, message: &Message) -> io::Result<u32> {
+        let serial = self.serial.fetch_add(1, Ordering::Relaxed);
+        let bytes = message.encode(serial);
+        self.stream.lock().unwrap().write_all(&bytes)?;
+        Ok(serial)
+    }
+}
+
+/// The service, connected to a bus, see the [module docs](self).
+pub struct Service {
+    reader: BufReader<UnixStream>,
+    sender: Arc<Sender>,
+    /// The stop flags of the running scans, by id.
+    scans: Arc<Mutex<HashMap<u32, Arc<AtomicBool>>>>,
+    next_scan: u32,
+    /// Hold the signals of the scans just started until their ids have
+    /// been replied with.
+    unreplied: Vec<mpsc::Sender<()>>,
+}
+
+impl Service {
+    /// Connects to the session bus and takes the service's name.
+    pub fn session() -> io::Result<Self> {
+        let address = env::var("DBUS_SESSION_BUS_ADDRESS").map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                "DBUS_SESSION_BUS_ADDRESS is not set",
+            )
+        })?;
+        Service::connect(&address)
+    }
+
+    /// Connects to the bus at a D-Bus address like
+    /// `unix:path=/run/user/1000/bus`, and takes the service's name.
+    pub fn connect(address: &str) -> io::Result<Self> {
+        let stream = connect(address)?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut stream = stream;
+        let uid: String = unsafe { getuid() }
+            .to_string()
+            .bytes()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        write!(stream, "\0AUTH EXTERNAL {}\r\n", uid)?;
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        if !line.starts_with("OK ") {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("the bus refused: {}", line.trim()),
+            ));
+        }
+        stream.write_all(b"BEGIN\r\n")?;
+        let mut service = Service {
+            reader,
+            sender: Arc::new(Sender {
+                stream: Mutex::new(stream),
+                serial: AtomicU32::new(1),
+            }),
+            scans: Arc::default(),
+            next_scan: 1,
+            unreplied: vec![],
+        };
+        service.call_bus(Message::bus_call("Hello", "", vec![]))?;
+        let mut body = Writer::default();
+        body.string(BUS_NAME);
+        body.u32(DO_NOT_QUEUE);
+        let reply = service.call_bus(Message::bus_call("RequestName", "su", body.bytes))?;
+        if reply.body().u32()? != PRIMARY_OWNER {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("{} is taken", BUS_NAME),
+            ));
+        }
+        Ok(service)
+    }
+
+    /// Calls a method of the bus and waits for its reply.
+    fn call_bus(&mut self, call: Message) -> io::Result<Message> {
+        let serial = self.sender.send(&call)?;
+        loop {
+            let reply = Message::read(&mut self.reader)?
+                .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+            if reply.reply_serial != Some(serial) {
+                continue;
+            }
+            if reply.kind == ERROR {
+                let text = reply.body().string().unwrap_or_default();
+                let name = reply.error_name.unwrap_or_default();
+                return Err(io::Error::other(format!("{}: {}", name, text)));
+            }
+            return Ok(reply);
+        }
+    }
+
+    /// Serves method calls until the bus closes the connection.
+    pub fn run(mut self) -> io::Result<()> {
+        while let Some(message) = Message::read(&mut self.reader)? {
+            if message.kind != METHOD_CALL {
+                continue;
+            }
+            let reply = self.answer(&message);
+            if message.flags & NO_REPLY_EXPECTED == 0 {
+                self.sender.send(&reply)?;
+            }
+            for replied in self.unreplied.drain(..) {
+                let _ = replied.send(());
+            }
+        }
+        Ok(())
+    }
+
+    /// The reply to a method call.
+    fn answer(&mut self, call: &Message) -> Message {
+        let unknown = || {
+            Message::error(
+                call,
+                "org.freedesktop.DBus.Error.UnknownMethod",
+                "no such method",
+            )
+        };
+        if call.path.as_deref() != Some(OBJECT_PATH) {
+            return Message::error(
+                call,
+                "org.freedesktop.DBus.Error.UnknownObject",
+                "no such object",
+            );
+        }
+        let interface = call.interface.as_deref();
+        let answer = match (interface, call.member.as_deref()) {
+            (Some(INTERFACE) | None, Some("StartScan")) => self.start_scan(call),
+            (Some(INTERFACE) | None, Some("CancelScan")) => self.cancel_scan(call),
+            (Some("org.freedesktop.DBus.Introspectable") | None, Some("Introspect")) => {
+                let mut body = Writer::default();
+                body.string(INTROSPECTION);
+                Ok(Message::reply(call, "s", body.bytes))
+            }
+            (Some("org.freedesktop.DBus.Peer") | None, Some("Ping")) => {
+                Ok(Message::reply(call, "", vec![]))
+            }
+            _ => return unknown(),
+        };
+        answer.unwrap_or_else(|err| {
+            Message::error(
+                call,
+                "org.freedesktop.DBus.Error.InvalidArgs",
+                &err.to_string(),
+            )
+        })
+    }
+
+    fn start_scan(&mut self, call: &Message) -> io::Result<Message> {
+        if call.signature != "sas" {
+            return Err(invalid("expected a root and kinds"));
+        }
+        let mut body = call.body();
+        let root = body.string()?;
+        let kinds = body
+            .strings()?
+            .iter()
+            .map(|kind| kind.parse::<Kind>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| invalid(&err))?;
+        let mut walker = MediaWalker::new(root);
+        if !kinds.is_empty() {
+            walker = walker.kinds(&kinds);
+        }
+        let handle = walker
+            .try_start()
+            .map_err(|err| invalid(&err.to_string()))?;
+        let id = self.next_scan;
+        self.next_scan += 1;
+        self.scans.lock().unwrap().insert(id, handle.stop_flag());
+
+        let sender = Arc::clone(&self.sender);
+        let scans = Arc::clone(&self.scans);
+        let (replied, reply_sent) = mpsc::channel();
+        self.unreplied.push(replied);
+        thread::spawn(move || {
+            let _ = reply_sent.recv();
+            for walk_result in handle {
+                if !matches!(walk_result.result, Ok(true)) {
+                    continue;
+                }
+                let mut body = Writer::default();
+                body.u32(id);
+                body.string(&walk_result.path);
+                body.string(&walk_result.mime);
+                body.string(walk_result.kind.map_or("", |kind| kind.name()));
+                // A bus gone away is noticed by the service.
+                if sender
+                    .send(&Message::signal("MediaFound", "usss", body.bytes))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+            scans.lock().unwrap().remove(&id);
+            let mut body = Writer::default();
+            body.u32(id);
+            let _ = sender.send(&Message::signal("ScanFinished", "u", body.bytes));
+        });
+
+        let mut body = Writer::default();
+        body.u32(id);
+        Ok(Message::reply(call, "u", body.bytes))
+    }
+
+    fn cancel_scan(&mut self, call: &Message) -> io::Result<Message> {
+        if call.signature != "u" {
+            return Err(invalid("expected a scan id"));
+        }
+        let id = call.body().u32()?;
+        let scans = self.scans.lock().unwrap();
+        let stopped = scans.get(&id).ok_or_else(|| invalid("no such scan"))?;
+        stopped.store(true, Ordering::Relaxed);
+        Ok(Message::reply(call, "", vec![]))
+    }
+}
+
+/// Runs the service on the session bus until the bus goes away, see
+/// [`Service`].
+pub fn serve() -> io::Result<()> {
+    Service::session()?.run()
+}
+
+/// Connects to the first reachable Unix socket of a D-Bus address.
+fn connect(address: &str) -> io::Result<UnixStream> {
+    let mut failure = invalid("no Unix socket in the bus address");
+    for address in address.split(';') {
+        let Some(keys) = address.strip_prefix("unix:") else {
+            continue;
+        };
+        let key = |name: &str| {
+            keys.split(',')
+                .find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))
+                .map(unescape)
+        };
+        let connected = if let Some(path) = key("path") {
+            UnixStream::connect(path)
+        } else if let Some(name) = key("abstract") {
+            use std::os::linux::net::SocketAddrExt;
+            std::os::unix::net::SocketAddr::from_abstract_name(name)
+                .and_then(|addr| UnixStream::connect_addr(&addr))
+        } else {
+            continue;
+        };
+        match connected {
+            Ok(stream) => return Ok(stream),
+            Err(err) => failure = err,
+        }
+    }
+    Err(failure)
+}
+
+/// Undoes the `%xx` escapes of a D-Bus address value.
+fn unescape(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut unescaped = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| value.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                unescaped.push(byte);
+                i += 3;
+            }
+            None => {
+                unescaped.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&unescaped).into_owned()
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempTree;
+    use std::os::unix::net::UnixListener;
+
+    /// Plays the bus for a service, up to the name being taken.
+    fn fake_bus(listener: &UnixListener) -> (UnixStream, BufReader<UnixStream>) {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut line = vec![];
+        reader.read_until(b'\n', &mut line).unwrap();
+        assert!(line.starts_with(b"\0AUTH EXTERNAL "));
+        stream.write_all(b"OK 0123456789abcdef\r\n").unwrap();
+        line.clear();
+        reader.read_until(b'\n', &mut line).unwrap();
+        assert_eq!(line, b"BEGIN\r\n");
+        let hello = Message::read(&mut reader).unwrap().unwrap();
+        assert_eq!(hello.member.as_deref(), Some("Hello"));
+        let mut body = Writer::default();
+        body.string(":1.1");
+        let reply = Message::reply(&hello, "s", body.bytes);
+        stream.write_all(&reply.encode(1)).unwrap();
+        let request = Message::read(&mut reader).unwrap().unwrap();
+        assert_eq!(request.member.as_deref(), Some("RequestName"));
+        assert_eq!(request.body().string().unwrap(), BUS_NAME);
+        let mut body = Writer::default();
+        body.u32(PRIMARY_OWNER);
+        let reply = Message::reply(&request, "u", body.bytes);
+        stream.write_all(&reply.encode(2)).unwrap();
+        (stream, reader)
+    }
+
+    #[test]
+    fn it_serves_scans_on_the_bus() {
+        let tree = TempTree::new("dbus");
+        tree.sample("sample3.mp3", "song.mp3");
+        tree.sample("sample_640x426.jpg", "photo.jpg");
+        let socket = tree.path().join("bus");
+        let listener = UnixListener::bind(&socket).unwrap();
+        let address = format!("unix:path={},guid=0123", socket.display());
+        let service = thread::spawn(move || Service::connect(&address).unwrap().run());
+        let (mut stream, mut reader) = fake_bus(&listener);
+
+        let call = |stream: &mut UnixStream, serial, member: &str, signature: &str, body| {
+            let interface = match member {
+                "Introspect" => "org.freedesktop.DBus.Introspectable",
+                _ => INTERFACE,
+            };
+            let message = Message {
+                kind: METHOD_CALL,
+                path: Some(OBJECT_PATH.into()),
+                interface: Some(interface.into()),
+                member: Some(member.into()),
+                sender: Some(":1.2".into()),
+                signature: signature.into(),
+                body,
+                ..Message::default()
+            };
+            stream.write_all(&message.encode(serial)).unwrap();
+        };
+        let mut body = Writer::default();
+        body.string(tree.path().to_str().unwrap());
+        body.array(4, |kinds| kinds.string("audio"));
+        call(&mut stream, 1, "StartScan", "sas", body.bytes);
+        let reply = Message::read(&mut reader).unwrap().unwrap();
+        assert_eq!(reply.kind, METHOD_RETURN);
+        assert_eq!(reply.reply_serial, Some(1));
+        assert_eq!(reply.destination.as_deref(), Some(":1.2"));
+        assert_eq!(reply.body().u32().unwrap(), 1);
+
+        let found = Message::read(&mut reader).unwrap().unwrap();
+        assert_eq!(found.kind, SIGNAL);
+        assert_eq!(found.member.as_deref(), Some("MediaFound"));
+        assert_eq!(found.signature, "usss");
+        let mut body = found.body();
+        assert_eq!(body.u32().unwrap(), 1);
+        assert!(body.string().unwrap().ends_with("/song.mp3"));
+        assert_eq!(body.string().unwrap(), "audio/mpeg");
+        assert_eq!(body.string().unwrap(), "audio");
+        let finished = Message::read(&mut reader).unwrap().unwrap();
+        assert_eq!(finished.member.as_deref(), Some("ScanFinished"));
+
+        let mut body = Writer::default();
+        body.u32(42);
+        call(&mut stream, 2, "CancelScan", "u", body.bytes);
+        let reply = Message::read(&mut reader).unwrap().unwrap();
+        assert_eq!(reply.kind, ERROR);
+        assert_eq!(
+            reply.error_name.as_deref(),
+            Some("org.freedesktop.DBus.Error.InvalidArgs")
+        );
+        assert_eq!(reply.body().string().unwrap(), "no such scan");
+
+        call(&mut stream, 3, "Introspect", "", vec![]);
+        let reply = Message::read(&mut reader).unwrap().unwrap();
+        assert!(reply.body().string().unwrap().contains("MediaFound"));
+
+        drop((stream, reader));
+        service.join().unwrap().unwrap();
+    }
+}
@@ -0,0 +1,161 @@
+//! Reading directories whole through `getdents64` on Linux, for walks of
+//! flat directories with hundreds of thousands of entries.
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io;
+use std::os::raw::{c_int, c_long, c_void};
+use std::os::unix::ffi::OsStringExt;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "riscv64"
+))]
+extern "C" {
+    fn syscall(number: c_long, ...) -> c_long;
+}
+
+#[cfg(target_arch = "x86_64")]
+const SYS_GETDENTS64: c_long = 217;
+#[cfg(any(target_arch = "aarch64", target_arch = "riscv64"))]
+const SYS_GETDENTS64: c_long = 61;
+
+/// The buffer of the first read. It grows for every read that nearly fills it,
+/// up to [`MAX_BUFFER`], so that small directories stay cheap.
+const MIN_BUFFER: usize = 32 * 1024;
+const MAX_BUFFER: usize = 1024 * 1024;
+
+/// The offsets of `d_type` and `d_name` in a `struct linux_dirent64`,
+/// after `d_ino`, `d_off` and `d_reclen`.
+const TYPE_OFFSET: usize = 18;
+const NAME_OFFSET: usize = 19;
+
+/// The `d_type` values of interest.
+const DT_UNKNOWN: u8 = 0;
+const DT_DIR: u8 = 4;
+const DT_REG: u8 = 8;
+const DT_LNK: u8 = 10;
+
+/// The type of an entry, as told by the directory itself. Filesystems
+/// like ext4, xfs and btrfs tell it for every entry, so that telling
+/// files from directories takes no `stat`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Hint {
+    File,
+    Dir,
+    Symlink,
+    /// A device, a FIFO or a socket.
+    Other,
+    /// The filesystem does not tell.
+    Unknown,
+}
+
+impl Hint {
+    fn from_d_type(d_type: u8) -> Self {
+        match d_type {
+            DT_UNKNOWN => Hint::Unknown,
+            DT_DIR => Hint::Dir,
+            DT_REG => Hint::File,
+            DT_LNK => Hint::Symlink,
+            _ => Hint::Other,
+        }
+    }
+}
+
+/// Lists the names in a directory along with the types of the entries,
+/// leaving out `.` and `..`.
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "riscv64"
+))]
+pub(crate) fn read_dir(dir: &Path) -> io::Result<Vec<(OsString, Hint)>> {
+    let file = File::open(dir)?;
+    let mut names = vec![];
+    let mut buffer = vec![0u8; MIN_BUFFER];
+    loop {
+        // SAFETY: the kernel writes at most `buffer.len()` bytes into the
+        // buffer; the result is checked before use.
+        let read = unsafe {
+            syscall(
+                SYS_GETDENTS64,
+                file.as_raw_fd() as c_int,
+                buffer.as_mut_ptr().cast::<c_void>(),
+                buffer.len(),
+            )
+        };
+        if read < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if read == 0 {
+            return Ok(names);
+        }
+        let read = read as usize;
+        let mut offset = 0;
+        while offset + NAME_OFFSET < read {
+            let reclen = u16::from_ne_bytes([buffer[offset + 16], buffer[offset + 17]]) as usize;
+            let record = &buffer[offset + NAME_OFFSET..offset + reclen];
+            let name = &record[..record.iter().position(|&b| b == 0).unwrap_or(record.len())];
+            if name != b"." && name != b".." {
+                let hint = Hint::from_d_type(buffer[offset + TYPE_OFFSET]);
+                names.push((OsString::from_vec(name.to_vec()), hint));
+            }
+            offset += reclen;
+        }
+        if read + MIN_BUFFER > buffer.len() && buffer.len() < MAX_BUFFER {
+            buffer.resize(buffer.len() * 2, 0);
+        }
+    }
+}
+
+/// Lists the names in a directory through the standard library, where
+/// the number of `getdents64` is not known.
+#[cfg(not(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "riscv64"
+)))]
+pub(crate) fn read_dir(dir: &Path) -> io::Result<Vec<(OsString, Hint)>> {
+    let mut names = vec![];
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let hint = match entry.file_type() {
+            Ok(file_type) if file_type.is_file() => Hint::File,
+            Ok(file_type) if file_type.is_dir() => Hint::Dir,
+            Ok(file_type) if file_type.is_symlink() => Hint::Symlink,
+            Ok(_) => Hint::Other,
+            Err(_) => Hint::Unknown,
+        };
+        names.push((entry.file_name(), hint));
+    }
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempTree;
+
+    #[test]
+    fn it_lists_large_directories() {
+        let tree = TempTree::new("dents");
+        let mut expected: Vec<_> = (0..5000)
+            .map(|i| {
+                let name = format!("{:0>60}.jpg", i);
+                tree.file(&name, b"");
+                (OsString::from(name), Hint::File)
+            })
+            .collect();
+        fs::create_dir(tree.path().join("sub")).unwrap();
+        expected.push((OsString::from("sub"), Hint::Dir));
+        std::os::unix::fs::symlink("sub", tree.path().join("link")).unwrap();
+        expected.push((OsString::from("link"), Hint::Symlink));
+        expected.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut names = read_dir(tree.path()).unwrap();
+        names.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(names, expected);
+        assert!(read_dir(&tree.path().join("missing")).is_err());
+    }
+}
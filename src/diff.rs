@@ -0,0 +1,146 @@
+use crate::MediaWalkResult;
+use std::collections::HashMap;
+
+/// A difference between two scans, see [`diff`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// A file found only by the later scan.
+    Added(String),
+    /// A file found only by the earlier scan.
+    Removed(String),
+    /// A file found by both scans, with a different ID.
+    Modified(String),
+    /// A file found under a new path, with the same ID.
+    Renamed {
+        /// The path in the earlier scan.
+        from: String,
+        /// The path in the later scan.
+        to: String,
+    },
+}
+
+impl Change {
+    /// The path of the file in the later scan, or in the earlier one if
+    /// it was removed.
+    pub fn path(&self) -> &str {
+        match self {
+            Change::Added(path) | Change::Removed(path) | Change::Modified(path) => path,
+            Change::Renamed { to, .. } => to,
+        }
+    }
+}
+
+/// Compares the results of two scans of the same root. Files that were
+/// moved or renamed are told apart from removed and added ones by their
+/// IDs, and modified files by a change of ID, so both scans should be
+/// made with [`MediaWalker::ids`](crate::MediaWalker::ids). Failed
+/// results are left out. The changes are sorted by path.
+///
+/// # Examples
+///
+/// ```
+/// use mediawalker::{diff, IdStrategy, MediaWalker};
+/// let scan = || {
+///     MediaWalker::new(env!("CARGO_MANIFEST_DIR"))
+///         .ids(IdStrategy::Inode)
+///         .start()
+///         .collect::<Vec<_>>()
+/// };
+/// let before = scan();
+/// for change in diff(&before, &scan()) {
+///     println!("{:?}", change);
+/// }
+/// ```
+pub fn diff(before: &[MediaWalkResult], after: &[MediaWalkResult]) -> Vec<Change> {
+    let found = |results: &'_ [MediaWalkResult]| -> HashMap<String, Option<u64>> {
+        results
+            .iter()
+            .filter(|r| r.result.is_ok())
+            .map(|r| (r.path.clone(), r.id))
+            .collect()
+    };
+    let before = found(before);
+    let after = found(after);
+
+    let mut removed: HashMap<u64, Vec<&String>> = HashMap::new();
+    let mut changes = vec![];
+    for (path, id) in &before {
+        if !after.contains_key(path) {
+            match id {
+                Some(id) => removed.entry(*id).or_default().push(path),
+                None => changes.push(Change::Removed(path.clone())),
+            }
+        }
+    }
+    for paths in removed.values_mut() {
+        // Paired with the added files in order, for renames to be stable.
+        paths.sort_by(|a, b| b.cmp(a));
+    }
+    let mut added: Vec<_> = after.iter().collect();
+    added.sort();
+    for (path, id) in added {
+        match before.get(path) {
+            Some(earlier) => {
+                if earlier.is_some() && id.is_some() && earlier != id {
+                    changes.push(Change::Modified(path.clone()));
+                }
+            }
+            None => match id.and_then(|id| removed.get_mut(&id)?.pop()) {
+                Some(from) => changes.push(Change::Renamed {
+                    from: from.clone(),
+                    to: path.clone(),
+                }),
+                None => changes.push(Change::Added(path.clone())),
+            },
+        }
+    }
+    changes.extend(
+        removed
+            .into_values()
+            .flatten()
+            .map(|path| Change::Removed(path.clone())),
+    );
+    changes.sort_by(|a, b| a.path().cmp(b.path()));
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempTree;
+    use crate::{IdStrategy, MediaWalker};
+    use std::fs;
+
+    #[test]
+    fn it_tells_renames_from_removals() {
+        let tree = TempTree::new("diff");
+        tree.sample("sample_640x426.jpg", "a.jpg");
+        tree.sample("sample1.heic", "b.heic");
+        tree.sample("sample3.mp3", "c.mp3");
+        let scan = || -> Vec<_> {
+            MediaWalker::new(tree.path())
+                .ids(IdStrategy::Content)
+                .start()
+                .collect()
+        };
+        let before = scan();
+        let path = |name: &str| tree.path().join(name).to_string_lossy().into_owned();
+        fs::rename(path("a.jpg"), path("renamed.jpg")).unwrap();
+        fs::remove_file(path("b.heic")).unwrap();
+        fs::copy(path("c.mp3"), path("d.mp3")).unwrap();
+        fs::write(path("c.mp3"), b"ID3\x03\0\0\0\0\0\0").unwrap();
+
+        assert_eq!(
+            diff(&before, &scan()),
+            vec![
+                Change::Removed(path("b.heic")),
+                Change::Modified(path("c.mp3")),
+                Change::Added(path("d.mp3")),
+                Change::Renamed {
+                    from: path("a.jpg"),
+                    to: path("renamed.jpg")
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,469 @@
+//! The media embedded in documents, behind the `documents` feature, see
+//! [`MediaWalker::embedded_media`](crate::MediaWalker::embedded_media):
+//! the pictures, sounds and videos in the `media` folders of Office
+//! Open XML files (`.docx`, `.xlsx`, `.pptx`) and the `Pictures` of
+//! OpenDocument ones, which are ZIP files, and the JPEG and JPEG 2000
+//! images of PDFs.
+use crate::inflate;
+use crate::Kind;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// The media of documents by extension, with their MIME types.
+const MEDIA: &[(&str, &str, Kind)] = &[
+    ("jpg", "image/jpeg", Kind::Image),
+    ("jpeg", "image/jpeg", Kind::Image),
+    ("png", "image/png", Kind::Image),
+    ("gif", "image/gif", Kind::Image),
+    ("bmp", "image/bmp", Kind::Image),
+    ("tif", "image/tiff", Kind::Image),
+    ("tiff", "image/tiff", Kind::Image),
+    ("webp", "image/webp", Kind::Image),
+    ("svg", "image/svg+xml", Kind::Image),
+    ("mp3", "audio/mpeg", Kind::Audio),
+    ("m4a", "audio/m4a", Kind::Audio),
+    ("wav", "audio/x-wav", Kind::Audio),
+    ("mp4", "video/mp4", Kind::Video),
+    ("mov", "video/quicktime", Kind::Video),
+    ("wmv", "video/x-ms-wmv", Kind::Video),
+];
+
+/// The folders of ZIP-based documents that media are kept in.
+const MEDIA_FOLDERS: &[&str] = &["word/media/", "ppt/media/", "xl/media/", "Pictures/"];
+
+/// How big a PDF is read whole at most to look for images.
+const MAX_PDF: u64 = 256 * 1024 * 1024;
+
+/// How big an embedded file is extracted at most.
+const MAX_EXTRACTED: u64 = 1024 * 1024 * 1024;
+
+/// A medium embedded in a document, reported as a virtual entry of the
+/// walk, see [`WalkEvent::Embedded`](crate::WalkEvent::Embedded).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmbeddedMedia {
+    /// The path of the document, as its result has it.
+    pub document: String,
+    /// The name of the medium in the document: its path in a ZIP-based
+    /// document, like `word/media/image1.png`, or like `image-12.jpg`
+    /// for the image object 12 of a PDF.
+    pub name: String,
+    /// The medium's MIME type.
+    pub mime: &'static str,
+    /// The medium's category.
+    pub kind: Kind,
+    /// The medium's size, in bytes.
+    pub size: u64,
+    /// The document to open, which may not be its reported path.
+    open: PathBuf,
+    /// Where the medium's data starts in the document.
+    offset: u64,
+    /// How many bytes of data there are.
+    len: u64,
+    /// Whether the data is deflated.
+    deflated: bool,
+}
+
+impl EmbeddedMedia {
+    /// The virtual path of the medium, the document's with the name of
+    /// the medium after a `#`, like `report.docx#word/media/image1.png`.
+    pub fn path(&self) -> String {
+        format!("{}#{}", self.document, self.name)
+    }
+
+    /// Reads the medium out of the document.
+    pub fn extract(&self) -> io::Result<Vec<u8>> {
+        if self.size > MAX_EXTRACTED {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "embedded file too big",
+            ));
+        }
+        let mut file = File::open(&self.open)?;
+        file.seek(SeekFrom::Start(self.offset))?;
+        let mut data = vec![];
+        file.take(self.len).read_to_end(&mut data)?;
+        if data.len() as u64 != self.len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        if !self.deflated {
+            return Ok(data);
+        }
+        let data = inflate::inflate(&data, self.size as usize)?;
+        if data.len() as u64 != self.size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "embedded file cut short",
+            ));
+        }
+        Ok(data)
+    }
+}
+
+/// The media embedded in a document of a MIME type, if it is a document
+/// that may have some. Documents that cannot be read, or are encrypted,
+/// have none.
+pub(crate) fn embedded(open: &Path, document: &str, mime: &str) -> Vec<EmbeddedMedia> {
+    let found = match mime {
+        "application/pdf" => pdf(open, document),
+        mime if mime.starts_with("application/vnd.openxmlformats-officedocument.")
+            || mime.starts_with("application/vnd.oasis.opendocument.") =>
+        {
+            zip(open, document)
+        }
+        _ => return vec![],
+    };
+    found.unwrap_or_default()
+}
+
+/// The media in the media folders of a ZIP file, from its central
+/// directory. ZIP64 files are left alone.
+fn zip(open: &Path, document: &str) -> io::Result<Vec<EmbeddedMedia>> {
+    let mut file = File::open(open)?;
+    let len = file.metadata()?.len();
+    // The end of the central directory, with a comment of up to 64 KiB.
+    let tail_len = len.min(22 + 0xffff);
+    file.seek(SeekFrom::Start(len - tail_len))?;
+    let mut tail = vec![];
+    file.by_ref().take(tail_len).read_to_end(&mut tail)?;
+    let Some(end) = tail
+        .windows(4)
+        .rposition(|window| window == b"PK\x05\x06")
+        .filter(|end| end + 22 <= tail.len())
+    else {
+        return Ok(vec![]);
+    };
+    let end = &tail[end..];
+    let directory_len = u32::from_le_bytes([end[12], end[13], end[14], end[15]]) as u64;
+    let directory_at = u32::from_le_bytes([end[16], end[17], end[18], end[19]]) as u64;
+    if directory_at + directory_len > len || directory_len > 64 * 1024 * 1024 {
+        return Ok(vec![]);
+    }
+    file.seek(SeekFrom::Start(directory_at))?;
+    let mut directory = vec![0; directory_len as usize];
+    file.read_exact(&mut directory)?;
+
+    let mut found = vec![];
+    let mut at = 0;
+    let u16_at = |bytes: &[u8], at: usize| u16::from_le_bytes([bytes[at], bytes[at + 1]]);
+    let u32_at = |bytes: &[u8], at: usize| {
+        u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+    };
+    while at + 46 <= directory.len() && &directory[at..at + 4] == b"PK\x01\x02" {
+        let entry = &directory[at..];
+        let flags = u16_at(entry, 8);
+        let method = u16_at(entry, 10);
+        let compressed = u32_at(entry, 20) as u64;
+        let size = u32_at(entry, 24) as u64;
+        let name_len = u16_at(entry, 28) as usize;
+        let extra_len = u16_at(entry, 30) as usize;
+        let comment_len = u16_at(entry, 32) as usize;
+        let local_at = u32_at(entry, 42) as u64;
+        let Some(name) = entry.get(46..46 + name_len) else {
+            break;
+        };
+        at += 46 + name_len + extra_len + comment_len;
+        let name = String::from_utf8_lossy(name).into_owned();
+        // Encrypted entries are left alone, and so are those of ZIP64.
+        if flags & 1 != 0 || !matches!(method, 0 | 8) || local_at == u32::MAX as u64 {
+            continue;
+        }
+        if !MEDIA_FOLDERS.iter().any(|folder| name.starts_with(folder)) {
+            continue;
+        }
+        let Some((mime, kind)) = media_type(&name) else {
+            continue;
+        };
+        // Where the data is, past the entry's local header.
+        file.seek(SeekFrom::Start(local_at))?;
+        let mut local = [0; 30];
+        file.read_exact(&mut local)?;
+        if &local[..4] != b"PK\x03\x04" {
+            continue;
+        }
+        let offset = local_at + 30 + u16_at(&local, 26) as u64 + u16_at(&local, 28) as u64;
+        if offset + compressed > len {
+            continue;
+        }
+        found.push(EmbeddedMedia {
+            document: document.to_string(),
+            name,
+            mime,
+            kind,
+            size,
+            open: open.to_path_buf(),
+            offset,
+            len: compressed,
+            deflated: method == 8,
+        });
+    }
+    Ok(found)
+}
+
+/// The JPEG and JPEG 2000 images of a PDF, which are kept as they are in
+/// their streams, unlike the images that are only compressed pixels.
+/// Encrypted PDFs are left alone.
+fn pdf(open: &Path, document: &str) -> io::Result<Vec<EmbeddedMedia>> {
+    let file = File::open(open)?;
+    let mut data = vec![];
+    file.take(MAX_PDF).read_to_end(&mut data)?;
+    if contains(&data, b"/Encrypt") {
+        return Ok(vec![]);
+    }
+    let mut found = vec![];
+    let mut from = 0;
+    while let Some(at) = find(&data[from..], b" obj").map(|at| from + at) {
+        from = at + 4;
+        let number = object_number(&data[..at]);
+        let Some(stream) = find(&data[from..], b"stream").map(|stream| from + stream) else {
+            break;
+        };
+        // The next object starts before this one's stream does, if it has
+        // none.
+        if let Some(next) = find(&data[from..stream], b" obj") {
+            from += next;
+            continue;
+        }
+        let dictionary = &data[from..stream];
+        let mut start = stream + b"stream".len();
+        if data.get(start) == Some(&b'\r') {
+            start += 1;
+        }
+        if data.get(start) == Some(&b'\n') {
+            start += 1;
+        }
+        let names = names(dictionary);
+        let is_image = names.windows(2).any(|pair| pair == ["Subtype", "Image"]);
+        let filters: Vec<&str> = names
+            .iter()
+            .copied()
+            .filter(|name| name.ends_with("Decode"))
+            .collect();
+        let (mime, extension) = match filters[..] {
+            ["DCTDecode"] => ("image/jpeg", "jpg"),
+            ["JPXDecode"] => ("image/jp2", "jp2"),
+            _ => ("", ""),
+        };
+        let end = direct_length(dictionary)
+            .map(|len| start + len)
+            .filter(|end| data.get(*end..).is_some_and(starts_with_end))
+            .or_else(|| {
+                find(&data[start..], b"endstream").map(|end| trim_eol(&data, start, start + end))
+            });
+        let Some(end) = end else {
+            break;
+        };
+        from = end;
+        if !is_image || mime.is_empty() {
+            continue;
+        }
+        let Some(number) = number else {
+            continue;
+        };
+        found.push(EmbeddedMedia {
+            document: document.to_string(),
+            name: format!("image-{}.{}", number, extension),
+            mime,
+            kind: Kind::Image,
+            size: (end - start) as u64,
+            open: open.to_path_buf(),
+            offset: start as u64,
+            len: (end - start) as u64,
+            deflated: false,
+        });
+    }
+    Ok(found)
+}
+
+/// The MIME type and kind of a file by its extension.
+fn media_type(name: &str) -> Option<(&'static str, Kind)> {
+    let (_, extension) = name.rsplit_once('.')?;
+    let extension = extension.to_ascii_lowercase();
+    MEDIA
+        .iter()
+        .find(|(known, _, _)| *known == extension)
+        .map(|&(_, mime, kind)| (mime, kind))
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    find(haystack, needle).is_some()
+}
+
+/// The number of the object whose `obj` keyword is at the end of `before`,
+/// as in `12 0 obj`.
+fn object_number(before: &[u8]) -> Option<u32> {
+    let text = String::from_utf8_lossy(&before[before.len().saturating_sub(24)..]).into_owned();
+    let mut words = text.split_ascii_whitespace().rev();
+    let _generation = words.next()?;
+    words.next()?.parse().ok()
+}
+
+/// The names of a PDF dictionary, like `Subtype` for `/Subtype`, in order.
+fn names(dictionary: &[u8]) -> Vec<&str> {
+    dictionary
+        .split(|&b| b == b'/')
+        .skip(1)
+        .filter_map(|name| {
+            let end = name
+                .iter()
+                .position(|&b| b.is_ascii_whitespace() || b"/<>[]()".contains(&b))
+                .unwrap_or(name.len());
+            std::str::from_utf8(&name[..end]).ok()
+        })
+        .collect()
+}
+
+/// The `/Length` of a stream, unless it is an indirect object.
+fn direct_length(dictionary: &[u8]) -> Option<usize> {
+    let at = find(dictionary, b"/Length")? + b"/Length".len();
+    let text = String::from_utf8_lossy(&dictionary[at..]).into_owned();
+    let mut words = text.split(|c: char| c.is_ascii_whitespace() || c == '/' || c == '>');
+    let len = words.find(|word| !word.is_empty())?.parse().ok()?;
+    // `12 0 R` is a reference to the object with the length.
+    match words.find(|word| !word.is_empty()) {
+        Some(word) if word.chars().all(|c| c.is_ascii_digit()) => None,
+        _ => Some(len),
+    }
+}
+
+/// Whether data starts with the end of a stream, maybe after a line
+/// break.
+fn starts_with_end(rest: &[u8]) -> bool {
+    let rest = rest.strip_prefix(b"\r").unwrap_or(rest);
+    let rest = rest.strip_prefix(b"\n").unwrap_or(rest);
+    rest.starts_with(b"endstream")
+}
+
+/// The end of a stream's data before its `endstream`, without the line
+/// break before it.
+fn trim_eol(data: &[u8], start: usize, mut end: usize) -> usize {
+    if end > start && data[end - 1] == b'\n' {
+        end -= 1;
+    }
+    if end > start && data[end - 1] == b'\r' {
+        end -= 1;
+    }
+    end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempTree;
+    use crate::{MediaWalker, WalkEvent};
+
+    /// A ZIP file of stored entries.
+    fn test_zip(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = vec![];
+        let mut directory = vec![];
+        for (name, data) in entries {
+            let local_at = zip.len() as u32;
+            let mut header = vec![];
+            header.extend(20u16.to_le_bytes());
+            header.extend([0; 8]);
+            header.extend([0; 4]);
+            header.extend((data.len() as u32).to_le_bytes());
+            header.extend((data.len() as u32).to_le_bytes());
+            header.extend((name.len() as u16).to_le_bytes());
+            header.extend(0u16.to_le_bytes());
+            zip.extend(b"PK\x03\x04");
+            zip.extend(&header);
+            zip.extend(name.as_bytes());
+            zip.extend(*data);
+            directory.extend(b"PK\x01\x02");
+            directory.extend(20u16.to_le_bytes());
+            directory.extend(&header);
+            directory.extend([0; 10]);
+            directory.extend(local_at.to_le_bytes());
+            directory.extend(name.as_bytes());
+        }
+        let directory_at = zip.len() as u32;
+        zip.extend(&directory);
+        zip.extend(b"PK\x05\x06");
+        zip.extend([0; 4]);
+        zip.extend((entries.len() as u16).to_le_bytes());
+        zip.extend((entries.len() as u16).to_le_bytes());
+        zip.extend((directory.len() as u32).to_le_bytes());
+        zip.extend(directory_at.to_le_bytes());
+        zip.extend([0; 2]);
+        zip
+    }
+
+    #[test]
+    fn it_finds_media_in_office_documents() {
+        let tree = TempTree::new("documents-zip");
+        let report = tree.file(
+            "report.docx",
+            &test_zip(&[
+                ("[Content_Types].xml", b"<Types/>"),
+                ("word/document.xml", b"<w:document/>"),
+                ("word/media/image1.png", b"\x89PNG picture"),
+                ("word/media/clip.mp4", b"a video"),
+                ("word/theme/theme1.jpg", b"not media of the document"),
+            ]),
+        );
+        let mime = "application/vnd.openxmlformats-officedocument.wordprocessingml.document";
+        let found = embedded(&report, "report.docx", mime);
+        let names: Vec<_> = found.iter().map(|media| media.path()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "report.docx#word/media/image1.png",
+                "report.docx#word/media/clip.mp4"
+            ]
+        );
+        assert_eq!((found[0].mime, found[1].kind), ("image/png", Kind::Video));
+        assert_eq!(found[0].extract().unwrap(), b"\x89PNG picture");
+        assert!(embedded(&report, "report.docx", "application/zip").is_empty());
+
+        let mut handle = MediaWalker::new(tree.path())
+            .extra_kinds(&[Kind::Document])
+            .embedded_media(true)
+            .start();
+        let mut events = handle.events();
+        assert!(
+            matches!(events.next(), Some(WalkEvent::Found(r)) if r.kind == Some(Kind::Document))
+        );
+        let media: Vec<_> = events
+            .filter_map(|event| match event {
+                WalkEvent::Embedded(media) => Some(media.name),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(media, vec!["word/media/image1.png", "word/media/clip.mp4"]);
+    }
+
+    #[test]
+    fn it_finds_images_in_pdfs() {
+        let tree = TempTree::new("documents-pdf");
+        let jpeg = b"\xff\xd8\xff\xe0 a photo \xff\xd9";
+        let mut pdf = b"%PDF-1.4\n1 0 obj\n<< /Type /Catalog >>\nendobj\n".to_vec();
+        pdf.extend(
+            format!(
+            "4 0 obj\n<< /Type /XObject /Subtype /Image /Filter /DCTDecode /Length {} >>\nstream\n",
+            jpeg.len()
+        )
+            .as_bytes(),
+        );
+        pdf.extend(jpeg);
+        pdf.extend(b"\nendstream\nendobj\n");
+        pdf.extend(b"5 0 obj\n<< /Subtype /Image /Filter /FlateDecode /Length 6 0 R >>\nstream\nxyz\nendstream\nendobj\n");
+        pdf.extend(b"7 0 obj\n<</Subtype/Image/Filter/JPXDecode/Length 8 0 R>>\nstream\r\njp2 data\r\nendstream\nendobj\n%%EOF\n");
+        let path = tree.file("scan.pdf", &pdf);
+        let found = embedded(&path, "scan.pdf", "application/pdf");
+        let names: Vec<_> = found.iter().map(|media| media.name.as_str()).collect();
+        assert_eq!(names, vec!["image-4.jpg", "image-7.jp2"]);
+        assert_eq!(found[0].extract().unwrap(), jpeg);
+        assert_eq!(found[1].extract().unwrap(), b"jp2 data");
+
+        pdf.extend(b"trailer << /Encrypt 9 0 R >>\n");
+        let path = tree.file("locked.pdf", &pdf);
+        assert!(embedded(&path, "locked.pdf", "application/pdf").is_empty());
+    }
+}
@@ -0,0 +1,126 @@
+//! Adding to results as they are found, see
+//! [`MediaWalker::enricher`](crate::MediaWalker::enricher).
+use crate::MediaWalkResult;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+
+/// Adds to each result before it is reported, like looking a song up by
+/// its fingerprint to fill in [`MediaWalkResult::annotations`]. Runs on
+/// the threads that finish results, see
+/// [`MediaWalker::stage_concurrency`](crate::MediaWalker::stage_concurrency)
+/// to run slow enrichers for a kind on threads of their own.
+pub trait Enricher: Send + Sync {
+    /// Adds to a result. Results that could not be inspected are given
+    /// too, with an error in [`MediaWalkResult::result`].
+    fn enrich(&self, walk_result: &mut MediaWalkResult);
+}
+
+impl<F: Fn(&mut MediaWalkResult) + Send + Sync> Enricher for F {
+    fn enrich(&self, walk_result: &mut MediaWalkResult) {
+        self(walk_result)
+    }
+}
+
+/// The future of an [`AsyncEnricher`].
+pub type EnrichFuture<'a> = Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+
+/// An [`Enricher`] that waits, like on a web service. The walk has no
+/// runtime of its own: the future is polled on the finishing thread,
+/// which sleeps until it is woken. Futures that need a runtime, like
+/// tokio's sockets, should be spawned onto one and waited on through a
+/// runtime-agnostic channel, like a oneshot receiver.
+pub trait AsyncEnricher: Send + Sync {
+    /// Adds to a result once the future completes.
+    fn enrich<'a>(&'a self, walk_result: &'a mut MediaWalkResult) -> EnrichFuture<'a>;
+}
+
+/// Wakes a thread blocked on a future.
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Runs a future to completion on the current thread.
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = std::pin::pin!(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut context = Context::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut context) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Kind, MediaWalker};
+    use std::sync::mpsc;
+
+    /// Looks results up on another thread, as a web service would.
+    struct Lookup;
+
+    impl AsyncEnricher for Lookup {
+        fn enrich<'a>(&'a self, walk_result: &'a mut MediaWalkResult) -> EnrichFuture<'a> {
+            Box::pin(async move {
+                let (tx, rx) = mpsc::channel();
+                let path = walk_result.path.clone();
+                thread::spawn(move || tx.send(path.len()).unwrap());
+                let len = Received(rx).await;
+                walk_result
+                    .annotations
+                    .insert("length".to_string(), len.to_string());
+            })
+        }
+    }
+
+    /// The future of a value sent over a channel.
+    struct Received<T>(mpsc::Receiver<T>);
+
+    impl<T> Future for Received<T> {
+        type Output = T;
+
+        fn poll(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<T> {
+            match self.0.try_recv() {
+                Ok(value) => Poll::Ready(value),
+                Err(_) => {
+                    // A real channel would wake once the value is sent.
+                    context.waker().wake_by_ref();
+                    Poll::Pending
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn it_enriches_results() {
+        let results: Vec<_> =
+            MediaWalker::new(concat!(env!("CARGO_MANIFEST_DIR"), "/resources/test"))
+                .enricher(|walk_result: &mut MediaWalkResult| {
+                    if walk_result.kind == Some(Kind::Audio) {
+                        let title = "Sample".to_string();
+                        walk_result.annotations.insert("title".to_string(), title);
+                    }
+                })
+                .async_enricher(Lookup)
+                .start()
+                .collect();
+        assert_eq!(results.len(), 9);
+        for walk_result in results {
+            let length = walk_result.path.len().to_string();
+            assert_eq!(walk_result.annotations["length"], length);
+            assert_eq!(
+                walk_result.annotations.contains_key("title"),
+                walk_result.kind == Some(Kind::Audio)
+            );
+        }
+    }
+}
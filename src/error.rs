@@ -0,0 +1,100 @@
+use crate::MediaWalkResult;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// What to do when a file cannot be inspected or a directory cannot be
+/// read, see [`MediaWalker::error_policy`](crate::MediaWalker::error_policy).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorPolicy {
+    /// Report the first error as a result, and stop the walk.
+    FailFast,
+    /// Report errors as results, and keep them in
+    /// [`WalkStats::failures`](crate::WalkStats::failures). The default.
+    #[default]
+    Collect,
+    /// Do not report errors, only count them in
+    /// [`WalkStats::errors`](crate::WalkStats::errors).
+    Ignore,
+}
+
+impl FromStr for ErrorPolicy {
+    type Err = String;
+
+    /// Parses `fail-fast`, `collect` or `ignore`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace('_', "-").as_str() {
+            "fail-fast" | "failfast" => Ok(ErrorPolicy::FailFast),
+            "collect" => Ok(ErrorPolicy::Collect),
+            "ignore" => Ok(ErrorPolicy::Ignore),
+            _ => Err(format!("unknown error policy: {}", s)),
+        }
+    }
+}
+
+/// An error kept in the statistics by [`ErrorPolicy::Collect`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CollectedError {
+    /// The path that could not be inspected, empty if it is not known.
+    pub path: String,
+    /// The kind of the error.
+    pub kind: io::ErrorKind,
+    /// The error message.
+    pub message: String,
+}
+
+impl CollectedError {
+    /// Returns the error of a failed result.
+    pub(crate) fn from_result(walk_result: &MediaWalkResult) -> Option<Self> {
+        let err = walk_result.result.as_ref().err()?;
+        Some(CollectedError {
+            path: walk_result.path.clone(),
+            kind: err.kind(),
+            message: err.to_string(),
+        })
+    }
+}
+
+/// An error that ends a walk, see [`walk_collect`](crate::walk_collect)
+/// and [`MediaWalker::try_start`](crate::MediaWalker::try_start).
+#[derive(Debug)]
+pub enum MediaWalkError {
+    /// The root does not exist.
+    RootNotFound(PathBuf),
+    /// The root is neither a directory nor a file.
+    InvalidRoot(PathBuf),
+    /// A file could not be inspected or a directory could not be read,
+    /// with [`ErrorPolicy::FailFast`].
+    Failed {
+        /// The path that failed, empty if it is not known.
+        path: String,
+        /// What went wrong.
+        source: io::Error,
+    },
+}
+
+impl fmt::Display for MediaWalkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MediaWalkError::RootNotFound(root) => {
+                write!(f, "{}: no such directory", root.display())
+            }
+            MediaWalkError::InvalidRoot(root) => {
+                write!(f, "{}: not a directory or a file", root.display())
+            }
+            MediaWalkError::Failed { path, source } if path.is_empty() => write!(f, "{}", source),
+            MediaWalkError::Failed { path, source } => write!(f, "{}: {}", path, source),
+        }
+    }
+}
+
+impl Error for MediaWalkError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MediaWalkError::Failed { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
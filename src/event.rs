@@ -0,0 +1,245 @@
+use crate::{ArtworkRef, Exif, MediaWalkResult, VideoSignature};
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Something that happened during a walk, see
+/// [`WalkHandle::events`](crate::WalkHandle::events).
+#[derive(Debug)]
+// Nearly all events are found files, which are not worth boxing.
+#[allow(clippy::large_enum_variant)]
+pub enum WalkEvent {
+    /// A file was found.
+    Found(MediaWalkResult),
+    /// An entry was left out of the walk on purpose.
+    Skipped {
+        /// The skipped entry.
+        path: PathBuf,
+        /// Why it was skipped.
+        reason: SkipReason,
+    },
+    /// A macOS bundle, like an `.app` or a `.photoslibrary`, which was
+    /// not walked into, see
+    /// [`MediaWalker::treat_bundles_as_files`](crate::MediaWalker::treat_bundles_as_files).
+    Bundle {
+        /// The bundle directory.
+        path: PathBuf,
+    },
+    /// A detail of a file found earlier, worked out apart from its
+    /// result, see [`MediaWalker::progressive`](crate::MediaWalker::progressive).
+    Enriched {
+        /// The [`result_id`](MediaWalkResult::result_id) of the result.
+        id: u64,
+        /// The detail.
+        field: Enrichment,
+    },
+    /// A directory known to hold media, see
+    /// [`MediaWalker::emit_dirs`](crate::MediaWalker::emit_dirs). It comes
+    /// before the first of its files, and after the directory it is in.
+    Directory {
+        /// The directory.
+        path: PathBuf,
+        /// The cover image in the directory, if asked for with
+        /// [`MediaWalker::artwork`](crate::MediaWalker::artwork).
+        artwork: Option<ArtworkRef>,
+    },
+    /// A medium embedded in a document found earlier, see
+    /// [`MediaWalker::embedded_media`](crate::MediaWalker::embedded_media).
+    #[cfg(feature = "documents")]
+    Embedded(crate::EmbeddedMedia),
+    /// A symbolic link whose target is gone, see
+    /// [`MediaWalker::report_dangling_symlinks`](crate::MediaWalker::report_dangling_symlinks).
+    DanglingSymlink {
+        /// The link.
+        link: PathBuf,
+        /// Where the link points, a relative target being taken from the
+        /// link's directory.
+        target: PathBuf,
+    },
+    /// How fast the walk goes, over the last few seconds, see
+    /// [`MediaWalker::throughput`](crate::MediaWalker::throughput).
+    Throughput {
+        /// The files gone through a second, whether found or not.
+        files_per_sec: f64,
+        /// The megabytes of those files a second, of the files whose
+        /// sizes the walk read.
+        mb_per_sec: f64,
+        /// How long until the walk is done at this speed, if it was told
+        /// how many files to expect with
+        /// [`MediaWalker::expected_files`](crate::MediaWalker::expected_files).
+        eta: Option<Duration>,
+    },
+}
+
+/// A detail of a result that came after it, see [`WalkEvent::Enriched`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Enrichment {
+    /// The [`IdStrategy::Content`](crate::IdStrategy::Content) ID, for
+    /// [`MediaWalkResult::id`].
+    Id(u64),
+    /// For [`MediaWalkResult::exif`].
+    Exif(Exif),
+    /// For [`MediaWalkResult::video_signature`].
+    VideoSignature(VideoSignature),
+    /// For [`MediaWalkResult::fingerprint`], which has the song's
+    /// duration.
+    #[cfg(feature = "acoustid")]
+    Fingerprint(crate::Fingerprint),
+}
+
+impl Enrichment {
+    /// Fills the detail into its result.
+    pub fn apply(self, walk_result: &mut MediaWalkResult) {
+        match self {
+            Enrichment::Id(id) => walk_result.id = Some(id),
+            Enrichment::Exif(exif) => walk_result.exif = Some(exif),
+            Enrichment::VideoSignature(signature) => walk_result.video_signature = Some(signature),
+            #[cfg(feature = "acoustid")]
+            Enrichment::Fingerprint(fingerprint) => walk_result.fingerprint = Some(fingerprint),
+        }
+    }
+}
+
+/// Which directories to report as [`WalkEvent::Directory`] events, see
+/// [`MediaWalker::emit_dirs`](crate::MediaWalker::emit_dirs).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum DirEmit {
+    /// None of them.
+    #[default]
+    Never,
+    /// The root and the directories below it with media files in them,
+    /// or in their subdirectories, as a tree of albums would show.
+    WithMatches,
+}
+
+impl FromStr for DirEmit {
+    type Err = String;
+
+    /// Parses `never` or `with_matches`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace('-', "_").as_str() {
+            "never" => Ok(DirEmit::Never),
+            "with_matches" => Ok(DirEmit::WithMatches),
+            _ => Err(format!("unknown directory emission: {}", s)),
+        }
+    }
+}
+
+/// Why an entry was skipped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SkipReason {
+    /// A Windows junction or directory symbolic link, which was not
+    /// followed, see
+    /// [`MediaWalker::follow_reparse_points`](crate::MediaWalker::follow_reparse_points).
+    ReparsePoint,
+    /// A file already reported under another path, see
+    /// [`MediaWalker::dedupe_paths`](crate::MediaWalker::dedupe_paths).
+    Duplicate,
+    /// A cache or sidecar directory of a photo workflow, which was not
+    /// walked into, see
+    /// [`MediaWalker::workflow_caches`](crate::MediaWalker::workflow_caches).
+    WorkflowCache,
+    /// An encrypted file, or an Apple Photos library that could not be
+    /// read, see
+    /// [`MediaWalker::locked_files`](crate::MediaWalker::locked_files).
+    Locked,
+    /// A file the walk may not read, as told by its permissions without
+    /// opening it, see
+    /// [`MediaWalker::check_permissions`](crate::MediaWalker::check_permissions).
+    NoPermission,
+    /// A symbolic link reaching its target through more links than
+    /// allowed, which was not followed, see
+    /// [`MediaWalker::max_symlink_depth`](crate::MediaWalker::max_symlink_depth).
+    SymlinkDepth,
+    /// An entry matching an exclusion pattern, or trash, see
+    /// [`MediaWalker::exclude`](crate::MediaWalker::exclude). Only counted
+    /// in [`WalkStats::skipped`](crate::WalkStats::skipped), like the
+    /// reasons below, rather than reported one by one.
+    Excluded,
+    /// A file of an extension not asked for, see
+    /// [`MediaWalker::extensions`](crate::MediaWalker::extensions).
+    Extension,
+    /// A file too small or too large, see
+    /// [`MediaWalker::min_size`](crate::MediaWalker::min_size) and
+    /// [`MediaWalker::max_size`](crate::MediaWalker::max_size).
+    Size,
+    /// An entry left out by
+    /// [`MediaWalker::filter_files`](crate::MediaWalker::filter_files) or
+    /// [`MediaWalker::filter_dirs`](crate::MediaWalker::filter_dirs).
+    Filtered,
+    /// Something other than a file or directory, such as a device, a
+    /// socket or a pipe.
+    SpecialFile,
+    /// A file of a kind not asked for, see
+    /// [`MediaWalker::kinds`](crate::MediaWalker::kinds).
+    Kind,
+    /// A file above the smallest depth walked, see
+    /// [`MediaWalker::min_depth`](crate::MediaWalker::min_depth).
+    Depth,
+    /// A bundle, reported as a whole rather than walked into, see
+    /// [`MediaWalker::treat_bundles_as_files`](crate::MediaWalker::treat_bundles_as_files).
+    Bundle,
+}
+
+/// What a walk would make of a path, see
+/// [`MediaWalker::explain`](crate::MediaWalker::explain).
+#[derive(Debug)]
+// A found file is what most paths are explained with, and not worth boxing.
+#[allow(clippy::large_enum_variant)]
+pub enum Explanation {
+    /// The walk would report the file, with this result.
+    Found(MediaWalkResult),
+    /// The walk would leave the path out, for this reason.
+    Skipped(SkipReason),
+    /// The path is a directory the walk would go into.
+    Walked,
+    /// The path is not under the root of the walk.
+    Outside,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SkipReason::ReparsePoint => "reparse point",
+            SkipReason::Duplicate => "duplicate",
+            SkipReason::WorkflowCache => "workflow cache",
+            SkipReason::Locked => "locked",
+            SkipReason::NoPermission => "no permission",
+            SkipReason::SymlinkDepth => "symlink depth",
+            SkipReason::Excluded => "excluded",
+            SkipReason::Extension => "extension",
+            SkipReason::Size => "size",
+            SkipReason::Filtered => "filtered",
+            SkipReason::SpecialFile => "special file",
+            SkipReason::Kind => "kind",
+            SkipReason::Depth => "depth",
+            SkipReason::Bundle => "bundle",
+        })
+    }
+}
+
+impl FromStr for SkipReason {
+    type Err = String;
+
+    /// Parses a reason as it is shown, e.g. `no permission`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "reparse point" => SkipReason::ReparsePoint,
+            "duplicate" => SkipReason::Duplicate,
+            "workflow cache" => SkipReason::WorkflowCache,
+            "locked" => SkipReason::Locked,
+            "no permission" => SkipReason::NoPermission,
+            "symlink depth" => SkipReason::SymlinkDepth,
+            "excluded" => SkipReason::Excluded,
+            "extension" => SkipReason::Extension,
+            "size" => SkipReason::Size,
+            "filtered" => SkipReason::Filtered,
+            "special file" => SkipReason::SpecialFile,
+            "kind" => SkipReason::Kind,
+            "depth" => SkipReason::Depth,
+            "bundle" => SkipReason::Bundle,
+            _ => return Err(format!("unknown skip reason: {}", s)),
+        })
+    }
+}
@@ -0,0 +1,700 @@
+//! The EXIF metadata of photos, read from their headers, see
+//! [`MediaWalker::exif`](crate::MediaWalker::exif).
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+/// The largest piece of a file read to parse: the EXIF of a JPEG is at
+/// most 64 kB, but the headers of a raw file can be larger.
+const MAX_READ: u64 = 1024 * 1024;
+
+/// How many entries of an image file directory are looked at, at most.
+const MAX_ENTRIES: usize = 1024;
+
+/// The tags looked for, in the first directory and in the EXIF one.
+const TAG_DATE_TIME: u16 = 0x0132;
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_GPS_IFD: u16 = 0x8825;
+const TAG_DATE_TIME_ORIGINAL: u16 = 0x9003;
+const TAG_DATE_TIME_DIGITIZED: u16 = 0x9004;
+const TAG_OFFSET_TIME_ORIGINAL: u16 = 0x9011;
+const TAG_SUB_SEC_TIME_ORIGINAL: u16 = 0x9291;
+
+/// The tags of the GPS directory looked for.
+const TAG_GPS_LATITUDE_REF: u16 = 0x0001;
+const TAG_GPS_LATITUDE: u16 = 0x0002;
+const TAG_GPS_LONGITUDE_REF: u16 = 0x0003;
+const TAG_GPS_LONGITUDE: u16 = 0x0004;
+
+/// The types of TIFF values read.
+const TYPE_ASCII: u16 = 2;
+const TYPE_RATIONAL: u16 = 5;
+
+/// What the EXIF of a photo tells, see [`MediaWalkResult::exif`](crate::MediaWalkResult::exif).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Exif {
+    /// When the photo was taken, to the fraction of a second where the
+    /// camera says. Taken as UTC unless the camera recorded its offset,
+    /// as cameras mostly keep local time.
+    pub taken_at: Option<SystemTime>,
+    /// Where the photo was taken, as the camera's GPS tells.
+    pub location: Option<GeoPoint>,
+}
+
+/// A point on Earth, in degrees.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GeoPoint {
+    /// The latitude, positive north of the equator.
+    pub latitude: f64,
+    /// The longitude, positive east of Greenwich.
+    pub longitude: f64,
+}
+
+impl GeoPoint {
+    /// The distance to another point along the surface of the Earth, in
+    /// meters, taking the Earth as a sphere.
+    pub fn distance(&self, other: &GeoPoint) -> f64 {
+        let (lat1, lat2) = (self.latitude.to_radians(), other.latitude.to_radians());
+        let half_lat = (lat2 - lat1) / 2.0;
+        let half_lon = (other.longitude - self.longitude).to_radians() / 2.0;
+        let a = half_lat.sin().powi(2) + lat1.cos() * lat2.cos() * half_lon.sin().powi(2);
+        2.0 * EARTH_RADIUS * a.sqrt().min(1.0).asin()
+    }
+}
+
+/// The mean radius of the Earth, in meters.
+pub(crate) const EARTH_RADIUS: f64 = 6_371_008.8;
+
+/// Reads the EXIF of a JPEG, PNG, WebP, HEIF or TIFF-based raw image, by
+/// its MIME type. `None` if it has none.
+pub(crate) fn read(path: &Path, mime: &str) -> Option<Exif> {
+    let mut file = File::open(path).ok()?;
+    let tiff = match mime {
+        "image/jpeg" => jpeg(&mut file),
+        "image/png" => png(&mut file),
+        "image/webp" => webp(&mut file),
+        "image/heif" | "image/avif" => heif(&mut file),
+        "image/tiff" | "image/x-canon-cr2" | "image/x-nikon-nef" | "image/x-sony-arw" => {
+            let mut data = vec![];
+            file.take(MAX_READ)
+                .read_to_end(&mut data)
+                .map(|_| Some(data))
+        }
+        _ => return None,
+    };
+    parse(&tiff.ok()??)
+}
+
+/// Reads the TIFF structure out of the `Exif` segment of a JPEG.
+fn jpeg<R: Read + Seek>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut marker = [0; 2];
+    reader.read_exact(&mut marker)?;
+    if marker != [0xff, 0xd8] {
+        return Err(io::ErrorKind::InvalidData.into());
+    }
+    loop {
+        reader.read_exact(&mut marker)?;
+        if marker[0] != 0xff {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+        match marker[1] {
+            // Padding, and markers without a length.
+            0xff | 0x01 | 0xd0..=0xd7 => continue,
+            // The image data starts, or the image ends.
+            0xda | 0xd9 => return Ok(None),
+            _ => {}
+        }
+        let len = u64::from(read_u16(reader)?).saturating_sub(2);
+        if marker[1] != 0xe1 {
+            reader.seek(SeekFrom::Current(len as i64))?;
+            continue;
+        }
+        let mut data = vec![0; len as usize];
+        reader.read_exact(&mut data)?;
+        // Other APP1 segments hold XMP.
+        if let Some(tiff) = data.strip_prefix(b"Exif\0\0") {
+            return Ok(Some(tiff.to_vec()));
+        }
+    }
+}
+
+/// Reads the `eXIf` chunk of a PNG, if it comes before the image data.
+fn png<R: Read + Seek>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut signature = [0; 8];
+    reader.read_exact(&mut signature)?;
+    if &signature != b"\x89PNG\r\n\x1a\n" {
+        return Err(io::ErrorKind::InvalidData.into());
+    }
+    loop {
+        let len = u64::from(read_u32(reader)?);
+        let mut kind = [0; 4];
+        reader.read_exact(&mut kind)?;
+        if matches!(&kind, b"IDAT" | b"IEND") {
+            return Ok(None);
+        }
+        if &kind != b"eXIf" || len > MAX_READ {
+            reader.seek(SeekFrom::Current(len as i64 + 4))?;
+            continue;
+        }
+        let mut data = vec![0; len as usize];
+        reader.read_exact(&mut data)?;
+        return Ok(Some(data));
+    }
+}
+
+/// Reads the `EXIF` chunk of an extended WebP.
+fn webp<R: Read + Seek>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut header = [0; 12];
+    reader.read_exact(&mut header)?;
+    if &header[..4] != b"RIFF" || &header[8..] != b"WEBP" {
+        return Err(io::ErrorKind::InvalidData.into());
+    }
+    loop {
+        let mut kind = [0; 4];
+        reader.read_exact(&mut kind)?;
+        let len = u64::from(read_u32_le(reader)?);
+        if &kind != b"EXIF" || len > MAX_READ {
+            // Chunks are padded to an even size.
+            reader.seek(SeekFrom::Current((len + len % 2) as i64))?;
+            continue;
+        }
+        let mut data = vec![0; len as usize];
+        reader.read_exact(&mut data)?;
+        // Some writers keep the JPEG segment's prefix.
+        let data = match data.strip_prefix(b"Exif\0\0") {
+            Some(tiff) => tiff.to_vec(),
+            None => data,
+        };
+        return Ok(Some(data));
+    }
+}
+
+/// Reads the `Exif` item of a HEIF or AVIF image: its `meta` box tells
+/// which item it is, and where its bytes are.
+fn heif<R: Read + Seek>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let end = reader.seek(SeekFrom::End(0))?;
+    let mut offset = 0;
+    let meta = loop {
+        if offset + 8 > end {
+            return Ok(None);
+        }
+        reader.seek(SeekFrom::Start(offset))?;
+        let size = u64::from(read_u32(reader)?);
+        let mut kind = [0; 4];
+        reader.read_exact(&mut kind)?;
+        if size < 8 {
+            return Ok(None);
+        }
+        if &kind == b"meta" && size - 8 <= MAX_READ {
+            let mut data = vec![0; (size - 8) as usize];
+            reader.read_exact(&mut data)?;
+            break data;
+        }
+        offset += size;
+    };
+    // A full box, with its children after the version and flags.
+    let children = boxes(meta.get(4..).unwrap_or_default());
+    let item = children
+        .iter()
+        .find(|(kind, _)| kind == b"iinf")
+        .and_then(|(_, iinf)| exif_item(iinf));
+    let extent = item.and_then(|item| {
+        let (_, iloc) = children.iter().find(|(kind, _)| kind == b"iloc")?;
+        item_extent(iloc, item)
+    });
+    let Some((at, len)) = extent else {
+        return Ok(None);
+    };
+    if !(4..=MAX_READ).contains(&len) {
+        return Ok(None);
+    }
+    reader.seek(SeekFrom::Start(at))?;
+    let mut data = vec![0; len as usize];
+    reader.read_exact(&mut data)?;
+    // The item starts with the offset of the TIFF header past the
+    // field.
+    let skip = 4 + u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize;
+    Ok(data.get(skip..).map(<[u8]>::to_vec))
+}
+
+/// The boxes in a piece of an ISO media file, by kind.
+fn boxes(mut data: &[u8]) -> Vec<([u8; 4], &[u8])> {
+    let mut found = vec![];
+    while data.len() >= 8 {
+        let size = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize;
+        if size < 8 || size > data.len() {
+            break;
+        }
+        found.push(([data[4], data[5], data[6], data[7]], &data[8..size]));
+        data = &data[size..];
+    }
+    found
+}
+
+/// The ID of the `Exif` item among the item infos of a `iinf` box.
+fn exif_item(iinf: &[u8]) -> Option<u32> {
+    let version = *iinf.first()?;
+    let entries_at = if version == 0 { 6 } else { 8 };
+    for (kind, infe) in boxes(iinf.get(entries_at..)?) {
+        let version = *infe.first()?;
+        if &kind != b"infe" || version < 2 {
+            continue;
+        }
+        let (id, item_type) = if version == 2 {
+            (be(infe, 4, 2)?, infe.get(8..12)?)
+        } else {
+            (be(infe, 4, 4)?, infe.get(10..14)?)
+        };
+        if item_type == b"Exif" {
+            return Some(id as u32);
+        }
+    }
+    None
+}
+
+/// Where the bytes of an item are, as its first extent in a `iloc` box
+/// tells: an absolute offset and a length.
+fn item_extent(iloc: &[u8], item: u32) -> Option<(u64, u64)> {
+    let version = *iloc.first()?;
+    let sizes = *iloc.get(4)?;
+    let (offset_size, length_size) = (usize::from(sizes >> 4), usize::from(sizes & 0xf));
+    let sizes = *iloc.get(5)?;
+    let base_size = usize::from(sizes >> 4);
+    let index_size = if version == 0 {
+        0
+    } else {
+        usize::from(sizes & 0xf)
+    };
+    let id_size = if version < 2 { 2 } else { 4 };
+    let (count, mut at) = if version < 2 {
+        (be(iloc, 6, 2)?, 8)
+    } else {
+        (be(iloc, 6, 4)?, 10)
+    };
+    for _ in 0..count {
+        let id = be(iloc, at, id_size)? as u32;
+        at += id_size;
+        // The construction method, only offsets in the file being
+        // understood.
+        let method = if version == 0 {
+            0
+        } else {
+            be(iloc, at, 2)? & 0xf
+        };
+        if version > 0 {
+            at += 2;
+        }
+        at += 2;
+        let base = be(iloc, at, base_size)? as u64;
+        at += base_size;
+        let extents = be(iloc, at, 2)?;
+        at += 2;
+        if id == item && method == 0 && extents > 0 {
+            at += index_size;
+            let offset = be(iloc, at, offset_size)? as u64;
+            let len = be(iloc, at + offset_size, length_size)? as u64;
+            return Some((base + offset, len));
+        }
+        at += extents * (index_size + offset_size + length_size);
+    }
+    None
+}
+
+/// Reads the tags of a TIFF structure, the body of EXIF.
+fn parse(tiff: &[u8]) -> Option<Exif> {
+    let tiff = Tiff::new(tiff)?;
+    let ifd0 = tiff.ifd(tiff.u32(4)? as usize)?;
+    let exif_ifd = ifd0
+        .iter()
+        .find(|entry| entry.tag == TAG_EXIF_IFD)
+        .and_then(|entry| tiff.ifd(tiff.u32(entry.at)? as usize))
+        .unwrap_or_default();
+    let gps_ifd = ifd0
+        .iter()
+        .find(|entry| entry.tag == TAG_GPS_IFD)
+        .and_then(|entry| tiff.ifd(tiff.u32(entry.at)? as usize))
+        .unwrap_or_default();
+    let find = |entries: &[IfdEntry], tag| entries.iter().find(|entry| entry.tag == tag).copied();
+    let text = |entries: &[IfdEntry], tag| find(entries, tag).and_then(|entry| tiff.ascii(&entry));
+    let offset = text(&exif_ifd, TAG_OFFSET_TIME_ORIGINAL);
+    let sub_sec = text(&exif_ifd, TAG_SUB_SEC_TIME_ORIGINAL);
+    let taken_at = match text(&exif_ifd, TAG_DATE_TIME_ORIGINAL) {
+        Some(original) => date_time(original, sub_sec, offset),
+        None => text(&exif_ifd, TAG_DATE_TIME_DIGITIZED)
+            .or_else(|| text(&ifd0, TAG_DATE_TIME))
+            .and_then(|date| date_time(date, None, None)),
+    };
+    let degrees = |tag, reference, negative| {
+        let degrees = tiff.degrees(&find(&gps_ifd, tag)?)?;
+        match text(&gps_ifd, reference) {
+            Some(reference) if reference == negative => Some(-degrees),
+            _ => Some(degrees),
+        }
+    };
+    let latitude = degrees(TAG_GPS_LATITUDE, TAG_GPS_LATITUDE_REF, "S");
+    let longitude = degrees(TAG_GPS_LONGITUDE, TAG_GPS_LONGITUDE_REF, "W");
+    let location = match (latitude, longitude) {
+        (Some(latitude), Some(longitude)) if latitude.abs() <= 90.0 && longitude.abs() <= 180.0 => {
+            Some(GeoPoint {
+                latitude,
+                longitude,
+            })
+        }
+        _ => None,
+    };
+    Some(Exif { taken_at, location })
+}
+
+/// An entry of an image file directory: its tag, type and count, and
+/// where its value, or the offset of it, is.
+#[derive(Debug, Clone, Copy)]
+struct IfdEntry {
+    tag: u16,
+    kind: u16,
+    count: u32,
+    at: usize,
+}
+
+/// A TIFF structure, in either byte order.
+struct Tiff<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+}
+
+impl<'a> Tiff<'a> {
+    fn new(data: &'a [u8]) -> Option<Self> {
+        let little_endian = match data.get(..4)? {
+            b"II*\0" => true,
+            b"MM\0*" => false,
+            _ => return None,
+        };
+        Some(Tiff {
+            data,
+            little_endian,
+        })
+    }
+
+    fn u16(&self, at: usize) -> Option<u16> {
+        let bytes = self.data.get(at..at.checked_add(2)?)?;
+        let bytes = [bytes[0], bytes[1]];
+        Some(match self.little_endian {
+            true => u16::from_le_bytes(bytes),
+            false => u16::from_be_bytes(bytes),
+        })
+    }
+
+    fn u32(&self, at: usize) -> Option<u32> {
+        let bytes = self.data.get(at..at.checked_add(4)?)?;
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        Some(match self.little_endian {
+            true => u32::from_le_bytes(bytes),
+            false => u32::from_be_bytes(bytes),
+        })
+    }
+
+    /// The entries of the directory at an offset.
+    fn ifd(&self, at: usize) -> Option<Vec<IfdEntry>> {
+        let count = usize::from(self.u16(at)?).min(MAX_ENTRIES);
+        (0..count)
+            .map(|i| {
+                let entry = at + 2 + i * 12;
+                Some(IfdEntry {
+                    tag: self.u16(entry)?,
+                    kind: self.u16(entry + 2)?,
+                    count: self.u32(entry + 4)?,
+                    at: entry + 8,
+                })
+            })
+            .collect()
+    }
+
+    /// The bytes of an entry's value: in the entry itself if they fit,
+    /// or at the offset it holds.
+    fn value(&self, entry: &IfdEntry, unit: usize) -> Option<&'a [u8]> {
+        let len = (entry.count as usize).checked_mul(unit)?;
+        let at = match len <= 4 {
+            true => entry.at,
+            false => self.u32(entry.at)? as usize,
+        };
+        self.data.get(at..at.checked_add(len)?)
+    }
+
+    /// An ASCII value, without its terminating zero.
+    fn ascii(&self, entry: &IfdEntry) -> Option<&'a str> {
+        if entry.kind != TYPE_ASCII {
+            return None;
+        }
+        let value = self.value(entry, 1)?;
+        let end = value.iter().position(|&b| b == 0).unwrap_or(value.len());
+        let text = std::str::from_utf8(&value[..end]).ok()?.trim();
+        (!text.is_empty()).then_some(text)
+    }
+
+    /// Degrees, minutes and seconds, as three rationals, in degrees.
+    fn degrees(&self, entry: &IfdEntry) -> Option<f64> {
+        if entry.kind != TYPE_RATIONAL || entry.count != 3 {
+            return None;
+        }
+        let at = self.u32(entry.at)? as usize;
+        let mut degrees = 0.0;
+        for (i, scale) in [1.0, 60.0, 3600.0].into_iter().enumerate() {
+            let rational = at + i * 8;
+            let (numerator, denominator) = (self.u32(rational)?, self.u32(rational + 4)?);
+            if denominator != 0 {
+                degrees += f64::from(numerator) / f64::from(denominator) / scale;
+            }
+        }
+        Some(degrees)
+    }
+}
+
+/// An EXIF date and time, like `2023:07:14 18:30:05`, with the
+/// fractions of a second, like `25`, and the offset from UTC, like
+/// `+02:00`, if known.
+fn date_time(text: &str, sub_sec: Option<&str>, offset: Option<&str>) -> Option<SystemTime> {
+    let number = |range: std::ops::Range<usize>| -> Option<i64> {
+        let digits = text.get(range)?;
+        if !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        digits.parse().ok()
+    };
+    let (year, month, day) = (number(0..4)?, number(5..7)?, number(8..10)?);
+    let (hour, minute, second) = (number(11..13)?, number(14..16)?, number(17..19)?);
+    // Unknown dates are written as blanks or zeros.
+    if year == 0 || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    if hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    let mut seconds =
+        days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second;
+    if let Some(offset) = offset.and_then(utc_offset) {
+        seconds -= offset;
+    }
+    let nanos = sub_sec
+        .filter(|digits| !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()))
+        .map_or(0, |digits| {
+            let digits = &digits[..digits.len().min(9)];
+            let fraction: u32 = digits.parse().unwrap_or(0);
+            fraction * 10u32.pow(9 - digits.len() as u32)
+        });
+    let whole = Duration::from_secs(seconds.unsigned_abs());
+    let time = match seconds >= 0 {
+        true => SystemTime::UNIX_EPOCH.checked_add(whole),
+        false => SystemTime::UNIX_EPOCH.checked_sub(whole),
+    };
+    time?.checked_add(Duration::new(0, nanos))
+}
+
+/// An offset from UTC like `+02:00`, in seconds.
+fn utc_offset(text: &str) -> Option<i64> {
+    let sign = match text.as_bytes().first()? {
+        b'+' => 1,
+        b'-' => -1,
+        _ => return None,
+    };
+    let (hours, minutes) = text.get(1..)?.split_once(':')?;
+    Some(sign * (hours.parse::<i64>().ok()? * 3600 + minutes.parse::<i64>().ok()? * 60))
+}
+
+/// The days since 1970-01-01 of a date in the proleptic Gregorian
+/// calendar.
+pub(crate) fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_from_march = (month + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// A big-endian number of `len` bytes at an offset.
+fn be(data: &[u8], at: usize, len: usize) -> Option<usize> {
+    let bytes = data.get(at..at.checked_add(len)?)?;
+    Some(bytes.iter().fold(0, |n, &b| n << 8 | usize::from(b)))
+}
+
+fn read_u16<R: Read>(reader: &mut R) -> io::Result<u16> {
+    let mut bytes = [0; 2];
+    reader.read_exact(&mut bytes)?;
+    Ok(u16::from_be_bytes(bytes))
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_be_bytes(bytes))
+}
+
+fn read_u32_le<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+/// Builds a little-endian TIFF structure with the given ASCII tags in
+/// its EXIF directory, and a location in its GPS one, for tests here
+/// and elsewhere.
+#[cfg(test)]
+pub(crate) fn test_tiff(exif_tags: &[(u16, &str)], location: Option<GeoPoint>) -> Vec<u8> {
+    let ascii = |tag, text: &str| {
+        let mut value = text.as_bytes().to_vec();
+        value.push(0);
+        (tag, TYPE_ASCII, value.len() as u32, value)
+    };
+    let exif: Vec<_> = exif_tags
+        .iter()
+        .map(|&(tag, text)| ascii(tag, text))
+        .collect();
+    let mut gps = vec![];
+    if let Some(point) = location {
+        let axes = [
+            (
+                TAG_GPS_LATITUDE_REF,
+                TAG_GPS_LATITUDE,
+                point.latitude,
+                "N",
+                "S",
+            ),
+            (
+                TAG_GPS_LONGITUDE_REF,
+                TAG_GPS_LONGITUDE,
+                point.longitude,
+                "E",
+                "W",
+            ),
+        ];
+        for (reference_tag, tag, degrees, positive, negative) in axes {
+            gps.push(ascii(
+                reference_tag,
+                if degrees < 0.0 { negative } else { positive },
+            ));
+            let millis = (degrees.abs() * 3_600_000.0).round() as u32;
+            let mut value = vec![];
+            for (numerator, denominator) in [
+                (millis / 3_600_000, 1_u32),
+                (millis / 60_000 % 60, 1),
+                (millis % 60_000, 1000),
+            ] {
+                value.extend(numerator.to_le_bytes());
+                value.extend(denominator.to_le_bytes());
+            }
+            gps.push((tag, TYPE_RATIONAL, 3, value));
+        }
+    }
+    let ifd_len = |entries: usize| 2 + entries * 12 + 4;
+    let exif_at = 8 + ifd_len(2);
+    let gps_at = exif_at + ifd_len(exif.len());
+    let values_at = gps_at + ifd_len(gps.len());
+    let ifd0 = vec![
+        (TAG_EXIF_IFD, 4, 1, (exif_at as u32).to_le_bytes().to_vec()),
+        (TAG_GPS_IFD, 4, 1, (gps_at as u32).to_le_bytes().to_vec()),
+    ];
+    let mut tiff = b"II*\0".to_vec();
+    tiff.extend(8u32.to_le_bytes());
+    let mut values = vec![];
+    for ifd in [ifd0, exif, gps] {
+        tiff.extend((ifd.len() as u16).to_le_bytes());
+        for (tag, kind, count, mut value) in ifd {
+            tiff.extend(tag.to_le_bytes());
+            tiff.extend(kind.to_le_bytes());
+            tiff.extend(count.to_le_bytes());
+            if value.len() <= 4 {
+                value.resize(4, 0);
+                tiff.extend(value);
+            } else {
+                tiff.extend(((values_at + values.len()) as u32).to_le_bytes());
+                values.extend(value);
+            }
+        }
+        tiff.extend(0u32.to_le_bytes());
+    }
+    tiff.extend(values);
+    tiff
+}
+
+/// Wraps a TIFF structure into the smallest JPEG that holds it.
+#[cfg(test)]
+pub(crate) fn test_jpeg(tiff: &[u8]) -> Vec<u8> {
+    let mut jpeg = vec![0xff, 0xd8, 0xff, 0xe1];
+    jpeg.extend(((tiff.len() + 8) as u16).to_be_bytes());
+    jpeg.extend(b"Exif\0\0");
+    jpeg.extend(tiff);
+    jpeg.extend([0xff, 0xda, 0, 2, 0xff, 0xd9]);
+    jpeg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempTree;
+    use crate::MediaWalker;
+
+    #[test]
+    fn it_reads_when_a_photo_was_taken() {
+        let tree = TempTree::new("exif");
+        let tiff = test_tiff(
+            &[
+                (TAG_DATE_TIME_ORIGINAL, "2023:07:14 18:30:05"),
+                (TAG_SUB_SEC_TIME_ORIGINAL, "25"),
+                (TAG_OFFSET_TIME_ORIGINAL, "+02:00"),
+            ],
+            None,
+        );
+        tree.file("a/taken.jpg", &test_jpeg(&tiff));
+        tree.sample("sample_640x426.jpg", "b/plain.jpg");
+
+        let results: Vec<_> = MediaWalker::new(tree.path()).exif(true).start().collect();
+        let taken = results
+            .iter()
+            .find(|r| r.path.ends_with("taken.jpg"))
+            .unwrap();
+        let plain = results
+            .iter()
+            .find(|r| r.path.ends_with("plain.jpg"))
+            .unwrap();
+        assert_eq!(plain.exif, None);
+        let taken_at = taken.exif.as_ref().unwrap().taken_at.unwrap();
+        let since_epoch = taken_at.duration_since(SystemTime::UNIX_EPOCH).unwrap();
+        // 2023-07-14T16:30:05.25Z.
+        assert_eq!(since_epoch, Duration::from_millis(1_689_352_205_250));
+
+        let blank = test_tiff(&[(TAG_DATE_TIME_ORIGINAL, "    :  :     :  :  ")], None);
+        assert_eq!(parse(&blank), Some(Exif::default()));
+        assert_eq!(parse(b"not a tiff"), None);
+    }
+
+    #[test]
+    fn it_reads_where_a_photo_was_taken() {
+        let rio = GeoPoint {
+            latitude: -22.9519,
+            longitude: -43.2105,
+        };
+        let location = parse(&test_tiff(&[], Some(rio))).unwrap().location.unwrap();
+        assert!((location.latitude - rio.latitude).abs() < 1e-6);
+        assert!((location.longitude - rio.longitude).abs() < 1e-6);
+
+        let eiffel_tower = GeoPoint {
+            latitude: 48.8584,
+            longitude: 2.2945,
+        };
+        let louvre = GeoPoint {
+            latitude: 48.8606,
+            longitude: 2.3376,
+        };
+        let distance = eiffel_tower.distance(&louvre);
+        assert!((3_100.0..3_200.0).contains(&distance), "{}", distance);
+        assert_eq!(louvre.distance(&louvre), 0.0);
+    }
+
+    #[test]
+    fn it_counts_days_from_the_epoch() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11_017);
+        assert_eq!(days_from_civil(1969, 12, 31), -1);
+    }
+}
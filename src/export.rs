@@ -0,0 +1,5 @@
+//! Writing the results of a walk in the formats other tools read.
+pub mod checksums;
+pub mod csv;
+pub mod json;
+pub mod nfo;
@@ -0,0 +1,241 @@
+//! Checksum manifests in the format of `sha256sum`, for checking that
+//! an archive of media is intact.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use mediawalker::export::checksums;
+//! use mediawalker::MediaWalker;
+//! use std::fs::File;
+//! let results = MediaWalker::new("/mnt/archive").start();
+//! checksums::write(results, File::create("SHA256SUMS")?)?;
+//! // Later on, or with `sha256sum -c SHA256SUMS`.
+//! for mismatch in checksums::verify(File::open("SHA256SUMS")?, "/mnt/archive")? {
+//!     println!("{:?}", mismatch);
+//! }
+//! # Ok::<(), std::io::Error>(())
+//! ```
+use crate::sha256;
+use crate::{MediaWalkResult, MediaWalker};
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// A file that does not agree with a manifest, see [`verify`].
+#[derive(Debug)]
+pub enum Mismatch {
+    /// The file's contents are not what they were.
+    Changed(PathBuf),
+    /// The file is in the manifest, but not on disk.
+    Missing(PathBuf),
+    /// The file was found by the walk, but is not in the manifest.
+    Unlisted(PathBuf),
+    /// The file could not be read.
+    Unreadable(PathBuf, io::Error),
+}
+
+impl Mismatch {
+    /// The path of the file.
+    pub fn path(&self) -> &Path {
+        match self {
+            Mismatch::Changed(path)
+            | Mismatch::Missing(path)
+            | Mismatch::Unlisted(path)
+            | Mismatch::Unreadable(path, _) => path,
+        }
+    }
+}
+
+/// Writes a line of checksum and path for each media file among the
+/// results of a walk, as `sha256sum` does, and returns how many were
+/// written. Paths are written as the walk reported them, so that a walk
+/// of a relative root makes a manifest to check from where the walk
+/// started. Files that fail to be read fail the manifest.
+pub fn write<I, W>(results: I, mut writer: W) -> io::Result<usize>
+where
+    I: IntoIterator<Item = MediaWalkResult>,
+    W: Write,
+{
+    let mut count = 0;
+    for walk_result in results {
+        if !matches!(walk_result.result, Ok(true)) {
+            continue;
+        }
+        let path = walk_result
+            .raw_path
+            .as_deref()
+            .unwrap_or(Path::new(&walk_result.path));
+        let digest = sha256::file(path)?;
+        writeln!(writer, "{}", line(&sha256::hex(&digest), &walk_result.path))?;
+        count += 1;
+    }
+    writer.flush()?;
+    Ok(count)
+}
+
+/// Checks the files of a manifest against the media files found under
+/// `root` with the default options, sorted by path. Paths in the
+/// manifest that are relative are taken to be relative to the root.
+pub fn verify<R: Read, P: AsRef<Path>>(manifest: R, root: P) -> io::Result<Vec<Mismatch>> {
+    let root = root.as_ref();
+    let mut listed: BTreeMap<PathBuf, String> = BTreeMap::new();
+    for (number, text) in BufReader::new(manifest).lines().enumerate() {
+        let text = text?;
+        if text.trim().is_empty() {
+            continue;
+        }
+        let (checksum, path) = parse(&text).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: not a checksum and a path", number + 1),
+            )
+        })?;
+        listed.insert(root.join(path), checksum);
+    }
+    let mut mismatches = vec![];
+    for walk_result in MediaWalker::new(root).start() {
+        if !matches!(walk_result.result, Ok(true)) {
+            continue;
+        }
+        let path = PathBuf::from(&walk_result.path);
+        match listed.remove(&path) {
+            Some(checksum) => match sha256::file(&path) {
+                Ok(digest) if sha256::hex(&digest) == checksum => {}
+                Ok(_) => mismatches.push(Mismatch::Changed(path)),
+                Err(err) => mismatches.push(Mismatch::Unreadable(path, err)),
+            },
+            None => mismatches.push(Mismatch::Unlisted(path)),
+        }
+    }
+    // What the walk did not find may be there, but not media any more.
+    for (path, checksum) in listed {
+        mismatches.push(match sha256::file(&path) {
+            Ok(digest) if sha256::hex(&digest) == checksum => continue,
+            Ok(_) => Mismatch::Changed(path),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Mismatch::Missing(path),
+            Err(err) => Mismatch::Unreadable(path, err),
+        });
+    }
+    mismatches.sort_by(|a, b| a.path().cmp(b.path()));
+    Ok(mismatches)
+}
+
+/// A manifest line. As with `sha256sum`, a path with a backslash or a
+/// line break is escaped, and the line starts with a backslash.
+fn line(checksum: &str, path: &str) -> String {
+    if path.contains(['\\', '\n', '\r']) {
+        let escaped = path
+            .replace('\\', "\\\\")
+            .replace('\n', "\\n")
+            .replace('\r', "\\r");
+        format!("\\{}  {}", checksum, escaped)
+    } else {
+        format!("{}  {}", checksum, path)
+    }
+}
+
+/// The checksum and the path of a manifest line, in the text format of
+/// `sha256sum` or its binary one, `<checksum> *<path>`.
+fn parse(line: &str) -> Option<(String, String)> {
+    let (escaped, line) = match line.strip_prefix('\\') {
+        Some(line) => (true, line),
+        None => (false, line),
+    };
+    let (checksum, path) = line.split_once(' ')?;
+    let path = path.strip_prefix([' ', '*'])?;
+    if checksum.len() != 64 || !checksum.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let path = if escaped {
+        let mut unescaped = String::with_capacity(path.len());
+        let mut chars = path.chars();
+        while let Some(c) = chars.next() {
+            unescaped.push(match (c, c == '\\') {
+                (_, true) => match chars.next()? {
+                    'n' => '\n',
+                    'r' => '\r',
+                    '\\' => '\\',
+                    _ => return None,
+                },
+                (c, false) => c,
+            });
+        }
+        unescaped
+    } else {
+        path.to_string()
+    };
+    Some((checksum.to_ascii_lowercase(), path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempTree;
+    use std::fs;
+
+    #[test]
+    fn it_escapes_paths_as_sha256sum_does() {
+        let checksum = "a".repeat(64);
+        for path in ["photos/a.jpg", "odd\\name\n.jpg", "two  spaces.jpg"] {
+            assert_eq!(
+                parse(&line(&checksum, path)),
+                Some((checksum.clone(), path.to_string()))
+            );
+        }
+        assert_eq!(
+            line(&checksum, "a\\b.jpg"),
+            format!("\\{}  a\\\\b.jpg", checksum)
+        );
+        assert!(parse(&format!("{} *binary.jpg", checksum)).is_some());
+        assert_eq!(parse("abc  short.jpg"), None);
+    }
+
+    #[test]
+    fn it_writes_and_verifies_manifests() {
+        let tree = TempTree::new("checksums");
+        tree.sample("sample3.mp3", "music/song.mp3");
+        let photo = tree.sample("sample_640x426.jpg", "photos/a.jpg");
+        let video = tree.sample("sample_640x360.mp4", "videos/clip.mp4");
+        tree.file("notes.txt", b"not media");
+
+        let mut manifest = vec![];
+        let results = MediaWalker::new(tree.path()).start();
+        assert_eq!(write(results, &mut manifest).unwrap(), 3);
+        let text = String::from_utf8(manifest.clone()).unwrap();
+        let expected = format!(
+            "{}  {}",
+            sha256::hex(&sha256::file(&photo).unwrap()),
+            photo.display()
+        );
+        assert!(text.lines().any(|line| line == expected), "{}", text);
+        assert!(verify(&manifest[..], tree.path()).unwrap().is_empty());
+
+        // A relative manifest, as written from the root, checks just as well.
+        let root = format!("{}/", tree.path().display());
+        let relative = text.replace(&root, "");
+        assert!(verify(relative.as_bytes(), tree.path()).unwrap().is_empty());
+
+        fs::write(&photo, b"\xff\xd8\xff\xe0 not the photo it was").unwrap();
+        fs::remove_file(&video).unwrap();
+        let added = tree.sample("sample1.heic", "photos/b.heic");
+        let mismatches: Vec<String> = verify(&manifest[..], tree.path())
+            .unwrap()
+            .iter()
+            .map(|mismatch| match mismatch {
+                Mismatch::Changed(path) => format!("changed {}", path.display()),
+                Mismatch::Missing(path) => format!("missing {}", path.display()),
+                Mismatch::Unlisted(path) => format!("unlisted {}", path.display()),
+                Mismatch::Unreadable(path, err) => format!("{}: {}", path.display(), err),
+            })
+            .collect();
+        assert_eq!(
+            mismatches,
+            vec![
+                format!("changed {}", photo.display()),
+                format!("unlisted {}", added.display()),
+                format!("missing {}", video.display()),
+            ]
+        );
+        assert!(verify(&b"not a manifest\n"[..], tree.path()).is_err());
+    }
+}
@@ -0,0 +1,74 @@
+//! Results as CSV, for spreadsheets, with a column for each field of the
+//! [JSON](super::json) results, in the same order, `schema_version`
+//! included.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use mediawalker::export::csv;
+//! use mediawalker::MediaWalker;
+//! use std::fs::File;
+//! let results = MediaWalker::new("/mnt/nas/photos").start();
+//! csv::write(results, File::create("photos.csv")?)?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+use super::json::{Value, RESULT_FIELDS};
+use crate::MediaWalkResult;
+use std::io::{self, Write};
+
+/// Writes a header row, and a row for each of the results of a walk, as
+/// RFC 4180 has them, and returns how many results were written. Fields
+/// that are null are left empty.
+pub fn write<I, W>(results: I, mut writer: W) -> io::Result<usize>
+where
+    I: IntoIterator<Item = MediaWalkResult>,
+    W: Write,
+{
+    let header: Vec<_> = RESULT_FIELDS.iter().map(|field| field.name).collect();
+    write!(writer, "{}\r\n", header.join(","))?;
+    let mut count = 0;
+    for walk_result in results {
+        let row: Vec<_> = RESULT_FIELDS
+            .iter()
+            .map(|field| match (field.value)(&walk_result) {
+                Value::Str(s) | Value::Object(s) => quote(&s),
+                Value::Count(n) => n.to_string(),
+                Value::Null => String::new(),
+            })
+            .collect();
+        write!(writer, "{}\r\n", row.join(","))?;
+        count += 1;
+    }
+    writer.flush()?;
+    Ok(count)
+}
+
+/// A field, quoted if it has to be.
+fn quote(field: &str) -> String {
+    match field.contains([',', '"', '\r', '\n']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MediaWalker;
+
+    #[test]
+    fn it_writes_a_column_per_field() {
+        let root = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/test");
+        let mut csv = vec![];
+        assert_eq!(write(MediaWalker::new(root).start(), &mut csv).unwrap(), 9);
+        let csv = String::from_utf8(csv).unwrap();
+        let mut rows = csv.split_terminator("\r\n");
+        assert_eq!(
+            rows.next(),
+            Some("path,mime,kind,result,error,schema_version")
+        );
+        let jpeg = rows.find(|row| row.ends_with("/sample_640x426.jpg,image/jpeg,image,media,,1"));
+        assert!(jpeg.is_some(), "{}", csv);
+        assert_eq!(quote(r#"a "b", c"#), r#""a ""b"", c""#);
+    }
+}
@@ -0,0 +1,362 @@
+//! Results and statistics as JSON, one object per line, as the server of
+//! the `serve` feature sends them, with the [`schema`] they follow.
+//!
+//! Each object carries the [`SCHEMA_VERSION`] it follows as its
+//! `schema_version`. The fields of the objects and their schemas are
+//! kept in a single table, see [`RESULT_FIELDS`], from which both the
+//! objects and the schema are made, and the [CSV](super::csv) columns.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use mediawalker::export::json;
+//! use mediawalker::MediaWalker;
+//! use std::fs::File;
+//! let results = MediaWalker::new("/mnt/nas/photos").start();
+//! json::write(results, File::create("photos.jsonl")?)?;
+//! std::fs::write("photos.schema.json", json::schema())?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+use crate::{Kind, MediaWalkResult, WalkStats};
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+/// The version of the JSON, and CSV, written, bumped whenever a field
+/// changes meaning or goes away, and given as `schema_version` in each
+/// result and statistics object. Added fields do not bump it.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The value of a field, as written to JSON or CSV.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    /// A string.
+    Str(String),
+    /// A count, or a size.
+    Count(u64),
+    /// Nothing.
+    Null,
+    /// A JSON object, written to CSV as its JSON text.
+    Object(String),
+}
+
+impl Value {
+    /// The value as JSON.
+    pub fn json(&self) -> String {
+        match self {
+            Value::Str(s) => json_string(s),
+            Value::Count(n) => n.to_string(),
+            Value::Null => "null".to_string(),
+            Value::Object(json) => json.clone(),
+        }
+    }
+}
+
+/// A field of the objects written, with its JSON Schema.
+pub struct Field<T: ?Sized> {
+    /// The name the field is written under.
+    pub name: &'static str,
+    /// The JSON Schema of the value.
+    pub schema: fn() -> String,
+    /// The value of the field of an object.
+    pub value: fn(&T) -> Value,
+}
+
+/// The fields of a result, in the order they are written.
+pub const RESULT_FIELDS: &[Field<MediaWalkResult>] = &[
+    Field {
+        name: "path",
+        schema: || r#"{"type":"string"}"#.to_string(),
+        value: |walk_result| Value::Str(walk_result.path.clone()),
+    },
+    Field {
+        name: "mime",
+        schema: || r#"{"type":"string","description":"Empty if the type is not known."}"#.into(),
+        value: |walk_result| Value::Str(walk_result.mime.to_string()),
+    },
+    Field {
+        name: "kind",
+        schema: || format!(r#"{{"enum":[{},null]}}"#, kinds()),
+        value: |walk_result| match walk_result.kind {
+            Some(kind) => Value::Str(kind.name().to_string()),
+            None => Value::Null,
+        },
+    },
+    Field {
+        name: "result",
+        schema: || r#"{"enum":["media","unknown","error"]}"#.to_string(),
+        value: |walk_result| {
+            let result = match &walk_result.result {
+                Ok(true) => "media",
+                Ok(false) => "unknown",
+                Err(_) => "error",
+            };
+            Value::Str(result.to_string())
+        },
+    },
+    Field {
+        name: "error",
+        schema: || r#"{"type":["string","null"]}"#.to_string(),
+        value: |walk_result| match &walk_result.result {
+            Err(err) => Value::Str(err.to_string()),
+            Ok(_) => Value::Null,
+        },
+    },
+    Field {
+        name: "schema_version",
+        schema: version_schema,
+        value: |_| Value::Count(u64::from(SCHEMA_VERSION)),
+    },
+];
+
+/// The fields of the statistics of a walk, in the order they are
+/// written.
+pub const STATS_FIELDS: &[Field<WalkStats>] = &[
+    Field {
+        name: "files",
+        schema: count_schema,
+        value: |stats| Value::Count(stats.files),
+    },
+    Field {
+        name: "matched",
+        schema: count_schema,
+        value: |stats| Value::Count(stats.matched),
+    },
+    Field {
+        name: "unknown",
+        schema: count_schema,
+        value: |stats| Value::Count(stats.unknown),
+    },
+    Field {
+        name: "errors",
+        schema: count_schema,
+        value: |stats| Value::Count(stats.errors),
+    },
+    Field {
+        name: "bytes",
+        schema: count_schema,
+        value: |stats| Value::Count(stats.bytes),
+    },
+    Field {
+        name: "by_kind",
+        schema: || {
+            format!(
+                r##"{{"type":"object","propertyNames":{{"enum":[{}]}},"additionalProperties":{{"$ref":"#/$defs/totals"}}}}"##,
+                kinds()
+            )
+        },
+        value: |stats| {
+            let mut by_kind: Vec<_> = stats
+                .by_kind
+                .iter()
+                .map(|(kind, totals)| (kind.name(), totals.count, totals.bytes))
+                .collect();
+            by_kind.sort();
+            Value::Object(totals(by_kind))
+        },
+    },
+    Field {
+        name: "by_mime",
+        schema: || {
+            r##"{"type":"object","additionalProperties":{"$ref":"#/$defs/totals"}}"##.to_string()
+        },
+        value: |stats| {
+            let mut by_mime: Vec<_> = stats
+                .by_mime
+                .iter()
+                .map(|(mime, totals)| (mime.as_ref(), totals.count, totals.bytes))
+                .collect();
+            by_mime.sort();
+            Value::Object(totals(by_mime))
+        },
+    },
+    Field {
+        name: "schema_version",
+        schema: version_schema,
+        value: |_| Value::Count(u64::from(SCHEMA_VERSION)),
+    },
+];
+
+fn count_schema() -> String {
+    r##"{"$ref":"#/$defs/count"}"##.to_string()
+}
+
+fn version_schema() -> String {
+    format!(r#"{{"const":{}}}"#, SCHEMA_VERSION)
+}
+
+/// The names of the kinds, as a list of JSON strings.
+fn kinds() -> String {
+    let kinds: Vec<_> = Kind::ALL
+        .iter()
+        .map(|kind| json_string(kind.name()))
+        .collect();
+    kinds.join(",")
+}
+
+/// The counts and sizes of files by name, as a JSON object.
+fn totals(totals: Vec<(&str, u64, u64)>) -> String {
+    let totals: Vec<_> = totals
+        .into_iter()
+        .map(|(name, count, bytes)| {
+            format!(
+                r#"{}:{{"count":{},"bytes":{}}}"#,
+                json_string(name),
+                count,
+                bytes
+            )
+        })
+        .collect();
+    format!("{{{}}}", totals.join(","))
+}
+
+/// An object with the fields of a table.
+fn object<T: ?Sized>(fields: &[Field<T>], of: &T) -> String {
+    let members: Vec<_> = fields
+        .iter()
+        .map(|field| format!("{}:{}", json_string(field.name), (field.value)(of).json()))
+        .collect();
+    format!("{{{}}}", members.join(","))
+}
+
+/// The JSON Schema of an object with the fields of a table.
+fn object_schema<T: ?Sized>(fields: &[Field<T>]) -> String {
+    let required: Vec<_> = fields.iter().map(|field| json_string(field.name)).collect();
+    let properties: Vec<_> = fields
+        .iter()
+        .map(|field| format!("{}:{}", json_string(field.name), (field.schema)()))
+        .collect();
+    format!(
+        r#"{{"type":"object","required":[{}],"properties":{{{}}}}}"#,
+        required.join(","),
+        properties.join(",")
+    )
+}
+
+/// A result as a JSON object.
+pub fn result(walk_result: &MediaWalkResult) -> String {
+    object(RESULT_FIELDS, walk_result)
+}
+
+/// The statistics of a walk as a JSON object.
+pub fn stats(stats: &WalkStats) -> String {
+    object(STATS_FIELDS, stats)
+}
+
+/// The JSON Schema, draft 2020-12, of the results and the statistics
+/// written, of [`SCHEMA_VERSION`], for downstream tools to validate them
+/// by.
+pub fn schema() -> String {
+    format!(
+        r##"{{"$schema":"https://json-schema.org/draft/2020-12/schema","title":"mediawalker","description":"A scan result or the statistics of a scan.","oneOf":[{{"$ref":"#/$defs/result"}},{{"$ref":"#/$defs/stats"}}],"$defs":{{"result":{},"stats":{},"totals":{{"type":"object","required":["count","bytes"],"properties":{{"count":{{"$ref":"#/$defs/count"}},"bytes":{{"$ref":"#/$defs/count"}}}}}},"count":{{"type":"integer","minimum":0}}}}}}"##,
+        object_schema(RESULT_FIELDS),
+        object_schema(STATS_FIELDS)
+    )
+}
+
+/// Writes the results of a walk as JSON, a line per result, and returns
+/// how many were written.
+pub fn write<I, W>(results: I, mut writer: W) -> io::Result<usize>
+where
+    I: IntoIterator<Item = MediaWalkResult>,
+    W: Write,
+{
+    let mut count = 0;
+    for walk_result in results {
+        writeln!(writer, "{}", result(&walk_result))?;
+        count += 1;
+    }
+    writer.flush()?;
+    Ok(count)
+}
+
+/// A string as a JSON string.
+pub(crate) fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c < ' ' => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MediaWalker;
+
+    /// The names of the members of a JSON object, at its top level.
+    fn members(json: &str) -> Vec<String> {
+        let mut names = vec![];
+        let (mut depth, mut chars) = (0, json.chars().peekable());
+        let mut at_name = false;
+        while let Some(c) = chars.next() {
+            match c {
+                '{' | '[' => {
+                    depth += 1;
+                    at_name = depth == 1;
+                }
+                '}' | ']' => depth -= 1,
+                ',' if depth == 1 => at_name = true,
+                '"' => {
+                    let mut s = String::new();
+                    while let Some(c) = chars.next() {
+                        match c {
+                            '\\' => s.extend(chars.next()),
+                            '"' => break,
+                            c => s.push(c),
+                        }
+                    }
+                    if at_name && chars.peek() == Some(&':') {
+                        names.push(s);
+                    }
+                    at_name = false;
+                }
+                _ => {}
+            }
+        }
+        names
+    }
+
+    /// The names a schema of an object requires.
+    fn required(schema: &str) -> Vec<String> {
+        let list = schema.split(r#""required":["#).nth(1).unwrap();
+        let list = &list[..list.find(']').unwrap()];
+        list.split(',')
+            .map(|name| name.trim_matches('"').to_string())
+            .collect()
+    }
+
+    #[test]
+    fn it_writes_the_fields_of_its_schema() {
+        let root = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/test");
+        let mut handle = MediaWalker::new(root).start();
+        let mut lines = vec![];
+        assert_eq!(write(handle.by_ref(), &mut lines).unwrap(), 9);
+        let stats = stats(&handle.stats());
+
+        let schema = schema();
+        let result_schema = &schema[schema.find(r#""result":{"type""#).unwrap()..];
+        let stats_schema = &schema[schema.find(r#""stats":{"type""#).unwrap()..];
+        for line in String::from_utf8(lines).unwrap().lines() {
+            assert_eq!(members(line), required(result_schema));
+            assert!(line.ends_with(&format!(r#""schema_version":{}}}"#, SCHEMA_VERSION)));
+        }
+        assert_eq!(members(&stats), required(stats_schema));
+        assert!(stats.starts_with(r#"{"files":9,"matched":8,"#));
+        assert_eq!(
+            members(&schema)[..4],
+            ["$schema", "title", "description", "oneOf"]
+        );
+    }
+}
@@ -0,0 +1,306 @@
+//! Kodi-style `.nfo` files and Jellyfin collections, from which home
+//! media servers like Kodi, Jellyfin, Emby and Plex (with an NFO agent)
+//! can bootstrap their libraries.
+//!
+//! Titles and years are taken from the [annotations] of the results, as
+//! enrichers find them, or else from the names of the files and folders:
+//! `The.Matrix.1999.1080p.mkv` is _The Matrix_ of 1999. The annotations
+//! read are `title`, `year`, `plot`, `artist`, `album`, `imdb_id`,
+//! `tmdb_id` and `musicbrainz_recording_id`.
+//!
+//! [annotations]: crate::MediaWalkResult::annotations
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use mediawalker::export::nfo;
+//! use mediawalker::MediaWalker;
+//! let results: Vec<_> = MediaWalker::new("/mnt/nas/movies").start().collect();
+//! for written in nfo::write(&results)? {
+//!     println!("Wrote {}", written.display());
+//! }
+//! # Ok::<(), std::io::Error>(())
+//! ```
+use crate::{Kind, MediaWalkResult};
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fmt::Write as _;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// The name of the file describing an album, in its folder.
+pub const ALBUM_NFO: &str = "album.nfo";
+
+/// Writes a `.nfo` file next to each video, like `movie.nfo` for
+/// `movie.mkv`, and an [`ALBUM_NFO`] in each folder of songs. Files that
+/// are there already are left as they are, as they may have been edited
+/// by hand. Returns the paths of the files written.
+pub fn write(results: &[MediaWalkResult]) -> io::Result<Vec<PathBuf>> {
+    let mut written = vec![];
+    let mut albums: BTreeMap<&Path, Vec<&MediaWalkResult>> = BTreeMap::new();
+    for walk_result in results.iter().filter(|r| r.result.is_ok()) {
+        let path = Path::new(&walk_result.path);
+        match walk_result.kind {
+            Some(Kind::Video) => {
+                let nfo = path.with_extension("nfo");
+                if create(&nfo, &movie(walk_result))? {
+                    written.push(nfo);
+                }
+            }
+            Some(Kind::Audio) => {
+                if let Some(dir) = path.parent() {
+                    albums.entry(dir).or_default().push(walk_result);
+                }
+            }
+            _ => {}
+        }
+    }
+    for (dir, tracks) in albums {
+        let nfo = dir.join(ALBUM_NFO);
+        if create(&nfo, &album(&tracks))? {
+            written.push(nfo);
+        }
+    }
+    Ok(written)
+}
+
+/// Writes a new file, unless there is one already.
+fn create(path: &Path, contents: &str) -> io::Result<bool> {
+    match OpenOptions::new().write(true).create_new(true).open(path) {
+        Ok(mut file) => file.write_all(contents.as_bytes()).map(|()| true),
+        Err(err) if err.kind() == io::ErrorKind::AlreadyExists => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/// The `<movie>` document of a video.
+pub fn movie(walk_result: &MediaWalkResult) -> String {
+    let annotations = &walk_result.annotations;
+    let (title, year) = title_and_year(Path::new(&walk_result.path).file_stem());
+    let mut nfo = document("movie");
+    element(
+        &mut nfo,
+        1,
+        "title",
+        annotations.get("title").unwrap_or(&title),
+    );
+    if let Some(year) = annotations.get("year").or(year.as_ref()) {
+        element(&mut nfo, 1, "year", year);
+    }
+    if let Some(plot) = annotations.get("plot") {
+        element(&mut nfo, 1, "plot", plot);
+    }
+    for (annotation, kind) in [("imdb_id", "imdb"), ("tmdb_id", "tmdb")] {
+        if let Some(id) = annotations.get(annotation) {
+            let _ = writeln!(
+                nfo,
+                "  <uniqueid type=\"{}\">{}</uniqueid>",
+                kind,
+                escape(id)
+            );
+        }
+    }
+    nfo.push_str("</movie>\n");
+    nfo
+}
+
+/// The `<album>` document of the songs of a folder, in the order of
+/// their paths.
+pub fn album(tracks: &[&MediaWalkResult]) -> String {
+    let mut tracks = tracks.to_vec();
+    tracks.sort_by(|a, b| a.path.cmp(&b.path));
+    let annotation = |name: &str| {
+        tracks
+            .iter()
+            .find_map(|track| track.annotations.get(name))
+            .cloned()
+    };
+    let dir = tracks
+        .first()
+        .and_then(|track| Path::new(&track.path).parent())
+        .unwrap_or(Path::new(""));
+    let (title, year) = title_and_year(dir.file_name());
+    let mut nfo = document("album");
+    element(&mut nfo, 1, "title", &annotation("album").unwrap_or(title));
+    if let Some(artist) = annotation("artist") {
+        element(&mut nfo, 1, "artist", &artist);
+    }
+    if let Some(year) = annotation("year").or(year) {
+        element(&mut nfo, 1, "year", &year);
+    }
+    for (position, track) in tracks.iter().enumerate() {
+        let (title, _) = title_and_year(Path::new(&track.path).file_stem());
+        nfo.push_str("  <track>\n");
+        element(&mut nfo, 2, "position", &(position + 1).to_string());
+        element(
+            &mut nfo,
+            2,
+            "title",
+            track.annotations.get("title").unwrap_or(&title),
+        );
+        if let Some(id) = track.annotations.get("musicbrainz_recording_id") {
+            element(&mut nfo, 2, "musicbrainztrackid", id);
+        }
+        nfo.push_str("  </track>\n");
+    }
+    nfo.push_str("</album>\n");
+    nfo
+}
+
+/// A Jellyfin `collection.xml`, grouping videos into a collection (a
+/// box set) of a name. Jellyfin keeps these in its data folder, under
+/// `collections/<name> [boxset]/`.
+pub fn collection(name: &str, results: &[MediaWalkResult]) -> String {
+    let mut xml =
+        "<?xml version=\"1.0\" encoding=\"utf-8\" standalone=\"yes\"?>\n<Item>\n".to_string();
+    element(&mut xml, 1, "LocalTitle", name);
+    xml.push_str("  <CollectionItems>\n");
+    for walk_result in results.iter().filter(|r| r.kind == Some(Kind::Video)) {
+        xml.push_str("    <CollectionItem>\n");
+        element(&mut xml, 3, "Path", &walk_result.path);
+        xml.push_str("    </CollectionItem>\n");
+    }
+    xml.push_str("  </CollectionItems>\n</Item>\n");
+    xml
+}
+
+/// Writes a [`collection`] into Jellyfin's data folder, like
+/// `/var/lib/jellyfin/data`, for Jellyfin to pick up on its next scan.
+/// Returns the path of the file written.
+pub fn write_collection<P: AsRef<Path>>(
+    data_dir: P,
+    name: &str,
+    results: &[MediaWalkResult],
+) -> io::Result<PathBuf> {
+    let dir = data_dir
+        .as_ref()
+        .join("collections")
+        .join(format!("{} [boxset]", name.replace(['/', '\\'], "_")));
+    fs::create_dir_all(&dir)?;
+    let path = dir.join("collection.xml");
+    fs::write(&path, collection(name, results))?;
+    Ok(path)
+}
+
+/// The title and the year of a file or a folder going by its name
+/// without the extension, like `Alien (1979)` or
+/// `The.Matrix.1999.1080p`: the words before the year, if there is one.
+fn title_and_year(name: Option<&OsStr>) -> (String, Option<String>) {
+    let name = name.map(|name| name.to_string_lossy()).unwrap_or_default();
+    let words: Vec<&str> = name
+        .split(|c: char| c == '.' || c == '_' || c.is_whitespace())
+        .filter(|word| !word.is_empty())
+        .collect();
+    let is_year = |word: &str| {
+        let word = word.trim_matches(|c| matches!(c, '(' | ')' | '[' | ']'));
+        let year = word.parse::<u32>().ok()?;
+        (word.len() == 4 && (1900..2100).contains(&year)).then(|| word.to_string())
+    };
+    // A title that is a year, like 1917, is not cut down to nothing.
+    match words
+        .iter()
+        .skip(1)
+        .position(|word| is_year(word).is_some())
+    {
+        Some(at) => (words[..=at].join(" "), is_year(words[at + 1])),
+        None => (words.join(" "), None),
+    }
+}
+
+fn document(root: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n<{}>\n",
+        root
+    )
+}
+
+fn element(xml: &mut String, depth: usize, name: &str, text: &str) {
+    let _ = writeln!(
+        xml,
+        "{}<{}>{}</{}>",
+        "  ".repeat(depth),
+        name,
+        escape(text),
+        name
+    );
+}
+
+/// Text escaped for XML.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempTree;
+    use crate::MediaWalker;
+
+    #[test]
+    fn it_tells_titles_from_names() {
+        for (name, title, year) in [
+            ("The.Matrix.1999.1080p.BluRay", "The Matrix", Some("1999")),
+            ("Alien (1979)", "Alien", Some("1979")),
+            ("1917.2019", "1917", Some("2019")),
+            ("Pink Floyd - The Wall", "Pink Floyd - The Wall", None),
+        ] {
+            let (found, found_year) = title_and_year(Some(OsStr::new(name)));
+            assert_eq!((found.as_str(), found_year.as_deref()), (title, year));
+        }
+    }
+
+    #[test]
+    fn it_writes_nfo_files() {
+        let tree = TempTree::new("nfo");
+        tree.sample("sample_640x360.mp4", "movies/Alien (1979)/Alien (1979).mp4");
+        tree.sample("sample_640x360.mp4", "movies/Heat.1995.mp4");
+        tree.file("movies/Heat.1995.nfo", b"<movie>edited</movie>");
+        tree.sample("sample3.mp3", "music/Wall & Co/01 In the Flesh.mp3");
+        tree.sample("sample3.mp3", "music/Wall & Co/02 Thin Ice.mp3");
+
+        let mut results: Vec<_> = MediaWalker::new(tree.path()).start().collect();
+        let flesh = results
+            .iter_mut()
+            .find(|r| r.path.ends_with("In the Flesh.mp3"))
+            .unwrap();
+        flesh
+            .annotations
+            .insert("title".to_string(), "In the Flesh?".to_string());
+        let written = write(&results).unwrap();
+        assert_eq!(
+            written,
+            vec![
+                tree.path().join("movies/Alien (1979)/Alien (1979).nfo"),
+                tree.path().join("music/Wall & Co/album.nfo"),
+            ]
+        );
+        let movie = fs::read_to_string(&written[0]).unwrap();
+        assert!(movie.contains("<movie>\n  <title>Alien</title>\n  <year>1979</year>\n</movie>"));
+        let album = fs::read_to_string(&written[1]).unwrap();
+        assert!(album.contains("  <title>Wall &amp; Co</title>\n  <track>\n    <position>1</position>\n    <title>In the Flesh?</title>"));
+        assert!(album.contains("<position>2</position>\n    <title>02 Thin Ice</title>"));
+        let edited = fs::read_to_string(tree.path().join("movies/Heat.1995.nfo")).unwrap();
+        assert_eq!(edited, "<movie>edited</movie>");
+
+        let data = tree.path().join("jellyfin");
+        let written = write_collection(&data, "Classics", &results).unwrap();
+        assert_eq!(
+            written,
+            data.join("collections/Classics [boxset]/collection.xml")
+        );
+        let xml = fs::read_to_string(written).unwrap();
+        assert_eq!(xml.matches("<CollectionItem>").count(), 2);
+        assert!(xml.contains("<LocalTitle>Classics</LocalTitle>"));
+    }
+}
@@ -0,0 +1,494 @@
+//! Filter expressions over the results of a walk, see [`Filter`].
+use crate::config::parse_size;
+use crate::exif::days_from_civil;
+use crate::glob::Glob;
+use crate::{Kind, MediaWalkResult};
+use std::fmt;
+use std::fs::Metadata;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+/// An expression the results of a walk are kept by, see
+/// [`MediaWalker::filter`](crate::MediaWalker::filter), like
+/// `kind == video && size > 100MB && mtime < 2020-01-01`.
+///
+/// Comparisons are of a field and a value:
+///
+/// - `kind`, with `==` and `!=`, against a [`Kind`] name.
+/// - `mime`, `path`, `name` and `ext`, with `==` and `!=`, against a
+///   glob pattern, like `mime == image/*` or `name == "IMG_*"`, matched
+///   as by [`MediaWalker::excludes`](crate::MediaWalker::excludes).
+///   Extensions are without the dot, ignoring case.
+/// - `size`, with `==`, `!=`, `<`, `<=`, `>` and `>=`, against a size,
+///   like `100MB` or `4GiB`.
+/// - `mtime`, likewise, against a UTC date, like `2020-01-01`, or a
+///   date and time, like `2020-01-01T12:30:00`.
+///
+/// They are combined with `&&`, `||`, `!` and parentheses. Values with
+/// spaces or operators are quoted with `"`. A size or time that cannot
+/// be told compares as false.
+///
+/// # Examples
+///
+/// ```
+/// use mediawalker::Filter;
+/// let filter: Filter = "kind == video && (size > 1GB || ext == mkv)".parse().unwrap();
+/// assert!("size > lots".parse::<Filter>().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filter {
+    source: String,
+    expr: Expr,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Kind(bool, Kind),
+    Text(bool, Field, Glob),
+    Size(Op, u64),
+    Mtime(Op, SystemTime),
+}
+
+/// The fields compared against glob patterns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Mime,
+    Path,
+    Name,
+    Ext,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Op {
+    fn compare<T: PartialOrd>(self, a: T, b: T) -> bool {
+        match self {
+            Op::Eq => a == b,
+            Op::Ne => a != b,
+            Op::Lt => a < b,
+            Op::Le => a <= b,
+            Op::Gt => a > b,
+            Op::Ge => a >= b,
+        }
+    }
+}
+
+impl Filter {
+    /// Whether a result, of a file with the given metadata, is kept.
+    pub fn matches(&self, walk_result: &MediaWalkResult, metadata: Option<&Metadata>) -> bool {
+        self.expr.matches(walk_result, metadata)
+    }
+
+    /// Whether the filter compares sizes or times, which need metadata.
+    pub(crate) fn needs_metadata(&self) -> bool {
+        self.expr.needs_metadata()
+    }
+
+    /// Keeps what both filters keep.
+    pub(crate) fn and(self, other: Filter) -> Filter {
+        Filter {
+            source: format!("({}) && ({})", self.source, other.source),
+            expr: Expr::And(Box::new(self.expr), Box::new(other.expr)),
+        }
+    }
+}
+
+impl Expr {
+    fn matches(&self, walk_result: &MediaWalkResult, metadata: Option<&Metadata>) -> bool {
+        match self {
+            Expr::And(a, b) => a.matches(walk_result, metadata) && b.matches(walk_result, metadata),
+            Expr::Or(a, b) => a.matches(walk_result, metadata) || b.matches(walk_result, metadata),
+            Expr::Not(expr) => !expr.matches(walk_result, metadata),
+            Expr::Kind(equal, kind) => (walk_result.kind == Some(*kind)) == *equal,
+            Expr::Text(equal, field, glob) => {
+                let path = Path::new(&walk_result.path);
+                let text = match field {
+                    Field::Mime => Some(walk_result.mime.to_string()),
+                    Field::Path => Some(walk_result.path.clone()),
+                    Field::Name => path.file_name().map(|name| name.to_string_lossy().into()),
+                    Field::Ext => path
+                        .extension()
+                        .map(|ext| ext.to_string_lossy().to_ascii_lowercase()),
+                };
+                let text = text.unwrap_or_default();
+                glob.matches(Path::new(&text)) == *equal
+            }
+            Expr::Size(op, bytes) => {
+                metadata.is_some_and(|metadata| op.compare(metadata.len(), *bytes))
+            }
+            Expr::Mtime(op, time) => metadata
+                .and_then(|metadata| metadata.modified().ok())
+                .is_some_and(|modified| op.compare(modified, *time)),
+        }
+    }
+
+    fn needs_metadata(&self) -> bool {
+        match self {
+            Expr::And(a, b) | Expr::Or(a, b) => a.needs_metadata() || b.needs_metadata(),
+            Expr::Not(expr) => expr.needs_metadata(),
+            Expr::Size(..) | Expr::Mtime(..) => true,
+            Expr::Kind(..) | Expr::Text(..) => false,
+        }
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    /// Parses a filter expression.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokens(s)?,
+            at: 0,
+        };
+        let expr = parser.or()?;
+        match parser.tokens.get(parser.at) {
+            None => Ok(Filter {
+                source: s.trim().to_string(),
+                expr,
+            }),
+            Some((token, column)) => Err(format!("unexpected '{}' at {}", token, column)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Not,
+    And,
+    Or,
+    Op(Op),
+    Word(String),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Token::Open => "(",
+            Token::Close => ")",
+            Token::Not => "!",
+            Token::And => "&&",
+            Token::Or => "||",
+            Token::Op(Op::Eq) => "==",
+            Token::Op(Op::Ne) => "!=",
+            Token::Op(Op::Lt) => "<",
+            Token::Op(Op::Le) => "<=",
+            Token::Op(Op::Gt) => ">",
+            Token::Op(Op::Ge) => ">=",
+            Token::Word(word) => word,
+        })
+    }
+}
+
+/// Splits an expression into tokens, with the columns they start at,
+/// counting from 1.
+fn tokens(s: &str) -> Result<Vec<(Token, usize)>, String> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let column = i + 1;
+        let next = chars.get(i + 1).copied();
+        let (token, len) = match (chars[i], next) {
+            (c, _) if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            ('(', _) => (Token::Open, 1),
+            (')', _) => (Token::Close, 1),
+            ('&', Some('&')) => (Token::And, 2),
+            ('|', Some('|')) => (Token::Or, 2),
+            ('=', Some('=')) => (Token::Op(Op::Eq), 2),
+            ('!', Some('=')) => (Token::Op(Op::Ne), 2),
+            ('<', Some('=')) => (Token::Op(Op::Le), 2),
+            ('>', Some('=')) => (Token::Op(Op::Ge), 2),
+            ('<', _) => (Token::Op(Op::Lt), 1),
+            ('>', _) => (Token::Op(Op::Gt), 1),
+            ('!', _) => (Token::Not, 1),
+            ('"', _) => {
+                let end = chars[i + 1..]
+                    .iter()
+                    .position(|&c| c == '"')
+                    .ok_or_else(|| format!("unterminated string at {}", column))?;
+                let word = chars[i + 1..i + 1 + end].iter().collect();
+                (Token::Word(word), end + 2)
+            }
+            ('&' | '|' | '=', _) => return Err(format!("unexpected '{}' at {}", chars[i], column)),
+            _ => {
+                let len = chars[i..]
+                    .iter()
+                    .position(|&c| c.is_whitespace() || "()!&|=<>\"".contains(c))
+                    .unwrap_or(chars.len() - i);
+                (Token::Word(chars[i..i + len].iter().collect()), len)
+            }
+        };
+        tokens.push((token, column));
+        i += len;
+    }
+    Ok(tokens)
+}
+
+/// A recursive descent parser, with `||` binding looser than `&&`, and
+/// `&&` looser than `!`.
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    at: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Result<(Token, usize), String> {
+        let token = self.tokens.get(self.at).cloned();
+        self.at += 1;
+        token.ok_or_else(|| "unexpected end of the filter".to_string())
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        let found = self
+            .tokens
+            .get(self.at)
+            .is_some_and(|(next, _)| next == token);
+        if found {
+            self.at += 1;
+        }
+        found
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.eat(&Token::Or) {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.not()?;
+        while self.eat(&Token::And) {
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr, String> {
+        if self.eat(&Token::Not) {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        if self.eat(&Token::Open) {
+            let expr = self.or()?;
+            return match self.next()? {
+                (Token::Close, _) => Ok(expr),
+                (token, column) => Err(format!("expected ')', not '{}' at {}", token, column)),
+            };
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let (field, column) = match self.next()? {
+            (Token::Word(field), column) => (field, column),
+            (token, column) => {
+                return Err(format!("expected a field, not '{}' at {}", token, column))
+            }
+        };
+        let op = match self.next()? {
+            (Token::Op(op), _) => op,
+            (token, column) => {
+                return Err(format!(
+                    "expected a comparison, not '{}' at {}",
+                    token, column
+                ))
+            }
+        };
+        let value = match self.next()? {
+            (Token::Word(value), _) => value,
+            (token, column) => {
+                return Err(format!("expected a value, not '{}' at {}", token, column))
+            }
+        };
+        let equal = match op {
+            Op::Eq => Some(true),
+            Op::Ne => Some(false),
+            _ => None,
+        };
+        let text = |field| {
+            equal
+                .map(|equal| Expr::Text(equal, field, Glob::new(&value)))
+                .ok_or_else(|| format!("{} can only be compared with == and !=", field_name(field)))
+        };
+        match field.as_str() {
+            "kind" => match equal {
+                Some(equal) => Ok(Expr::Kind(equal, value.parse()?)),
+                None => Err("kind can only be compared with == and !=".to_string()),
+            },
+            "mime" => text(Field::Mime),
+            "path" => text(Field::Path),
+            "name" => text(Field::Name),
+            "ext" => {
+                let value = value.trim_start_matches('.').to_ascii_lowercase();
+                equal
+                    .map(|equal| Expr::Text(equal, Field::Ext, Glob::new(&value)))
+                    .ok_or_else(|| "ext can only be compared with == and !=".to_string())
+            }
+            "size" => match parse_size(&value) {
+                Some(bytes) => Ok(Expr::Size(op, bytes)),
+                None => Err(format!("expected a size, not '{}'", value)),
+            },
+            "mtime" => match parse_time(&value) {
+                Some(time) => Ok(Expr::Mtime(op, time)),
+                None => Err(format!("expected a date, not '{}'", value)),
+            },
+            _ => Err(format!("unknown field '{}' at {}", field, column)),
+        }
+    }
+}
+
+fn field_name(field: Field) -> &'static str {
+    match field {
+        Field::Mime => "mime",
+        Field::Path => "path",
+        Field::Name => "name",
+        Field::Ext => "ext",
+    }
+}
+
+/// Parses a UTC date like `2020-01-01`, or a date and time like
+/// `2020-01-01T12:30:00` or `2020-01-01 12:30`.
+fn parse_time(s: &str) -> Option<SystemTime> {
+    let (date, time) = match s.split_once(['T', ' ']) {
+        Some((date, time)) => (date, Some(time.trim_end_matches('Z'))),
+        None => (s, None),
+    };
+    let mut date = date.splitn(3, '-').map(str::parse::<i64>);
+    let (year, month, day) = (date.next()?.ok()?, date.next()?.ok()?, date.next()?.ok()?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let mut seconds = days_from_civil(year, month, day) * 86400;
+    if let Some(time) = time {
+        let parts: Vec<i64> = time
+            .split(':')
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .ok()?;
+        let (hour, minute, second) = match parts[..] {
+            [hour, minute] => (hour, minute, 0),
+            [hour, minute, second] => (hour, minute, second),
+            _ => return None,
+        };
+        if !(0..24).contains(&hour) || !(0..60).contains(&minute) || !(0..=60).contains(&second) {
+            return None;
+        }
+        seconds += hour * 3600 + minute * 60 + second;
+    }
+    let offset = Duration::from_secs(seconds.unsigned_abs());
+    if seconds >= 0 {
+        SystemTime::UNIX_EPOCH.checked_add(offset)
+    } else {
+        SystemTime::UNIX_EPOCH.checked_sub(offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempTree;
+    use crate::MediaWalker;
+
+    #[test]
+    fn it_parses_filters() {
+        for (filter, error) in [
+            ("kind == video && size > 100MB && mtime < 2020-01-01", None),
+            (
+                "!(ext == mkv || name == \"my clip*\") && mime != image/*",
+                None,
+            ),
+            ("mtime >= \"2020-01-01 12:30\"", None),
+            ("kind == film", Some("unknown kind: film")),
+            (
+                "kind < video",
+                Some("kind can only be compared with == and !="),
+            ),
+            ("size > lots", Some("expected a size, not 'lots'")),
+            (
+                "mtime < 2020-13-01",
+                Some("expected a date, not '2020-13-01'"),
+            ),
+            ("depth > 2", Some("unknown field 'depth' at 1")),
+            ("(size > 1", Some("unexpected end of the filter")),
+            ("size > 1 size", Some("unexpected 'size' at 10")),
+            ("ext = mkv", Some("unexpected '=' at 5")),
+        ] {
+            assert_eq!(
+                filter.parse::<Filter>().err().as_deref(),
+                error,
+                "{}",
+                filter
+            );
+        }
+        assert_eq!(
+            parse_time("1970-01-02T00:00:01Z"),
+            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(86401))
+        );
+    }
+
+    #[test]
+    fn it_filters_results() {
+        let tree = TempTree::new("filter");
+        tree.sample("sample3.mp3", "music/song.mp3");
+        tree.sample("sample_640x360.mp4", "videos/clip.mp4");
+        tree.sample("sample_640x426.jpg", "photos/IMG_0001.JPG");
+        tree.sample("sample1.heic", "photos/IMG_0002.heic");
+
+        let names = |filter: &str| {
+            let mut names: Vec<_> = MediaWalker::new(tree.path())
+                .filter(filter.parse().unwrap())
+                .start()
+                .map(|r| {
+                    Path::new(&r.path)
+                        .file_name()
+                        .unwrap()
+                        .to_string_lossy()
+                        .into_owned()
+                })
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(names("kind == video"), vec!["clip.mp4"]);
+        assert_eq!(names("mime == image/* && ext == jpg"), vec!["IMG_0001.JPG"]);
+        assert_eq!(
+            names("name == IMG_* && !(size > 1GB) && mtime > 2000-01-01"),
+            vec!["IMG_0001.JPG", "IMG_0002.heic"]
+        );
+        assert_eq!(
+            names("kind == audio || path == \"**/videos/*\""),
+            vec!["clip.mp4", "song.mp3"]
+        );
+        assert!(names("mtime < 2000-01-01").is_empty());
+
+        let walker = MediaWalker::new(tree.path())
+            .filter("kind == image".parse().unwrap())
+            .filter("ext == heic".parse().unwrap());
+        assert_eq!(walker.start().count(), 1);
+    }
+}
@@ -0,0 +1,144 @@
+//! Chromaprint fingerprints of songs, behind the `acoustid` feature, see
+//! [`MediaWalker::fingerprints`](crate::MediaWalker::fingerprints).
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// How far apart, in points, two fingerprints are lined up when
+/// comparing them: about ten seconds either way.
+const MAX_OFFSET: usize = 80;
+
+/// The Chromaprint fingerprint of a song, as computed by `fpcalc`. The
+/// same recording gives much the same fingerprint whatever its encoding
+/// and bitrate, which finds duplicate songs; the fingerprint can also be
+/// looked up on AcoustID.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fingerprint {
+    /// The length of the song, in seconds.
+    pub duration: u32,
+    /// The raw fingerprint: a 32-bit feature for every eighth of a second
+    /// or so.
+    pub points: Vec<u32>,
+}
+
+/// `fpcalc`, or the program named by the `FPCALC` environment variable.
+pub(crate) fn fpcalc() -> OsString {
+    env::var_os("FPCALC").unwrap_or_else(|| "fpcalc".into())
+}
+
+impl Fingerprint {
+    /// Fingerprints a song with `fpcalc`, see [`fpcalc`].
+    pub(crate) fn compute(path: &Path, fpcalc: &OsStr) -> io::Result<Fingerprint> {
+        let output = Command::new(fpcalc)
+            .arg("-raw")
+            .arg(path)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()?;
+        if !output.status.success() {
+            return Err(io::Error::other(format!(
+                "fpcalc failed with {}",
+                output.status
+            )));
+        }
+        Self::parse(&String::from_utf8_lossy(&output.stdout))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "unexpected fpcalc output"))
+    }
+
+    /// Parses the `DURATION=` and `FINGERPRINT=` lines of `fpcalc -raw`.
+    fn parse(output: &str) -> Option<Fingerprint> {
+        let mut duration = None;
+        let mut points = None;
+        for line in output.lines() {
+            if let Some(value) = line.strip_prefix("DURATION=") {
+                duration = value.trim().parse::<f64>().ok().map(|d| d as u32);
+            } else if let Some(value) = line.strip_prefix("FINGERPRINT=") {
+                // Older versions print the points as signed numbers.
+                let parsed: Option<Vec<u32>> = value
+                    .trim()
+                    .split(',')
+                    .map(|point| point.parse::<i64>().ok().map(|point| point as u32))
+                    .collect();
+                points = parsed;
+            }
+        }
+        Some(Fingerprint {
+            duration: duration?,
+            points: points?,
+        })
+    }
+
+    /// How alike two fingerprints are, from 0.0 to 1.0: the share of
+    /// matching bits where the two line up best. Unrelated songs score
+    /// about 0.5, so that a score well above, like 0.8, points to the
+    /// same recording.
+    pub fn similarity(&self, other: &Fingerprint) -> f64 {
+        let (a, b) = (&self.points, &other.points);
+        let shortest = a.len().min(b.len());
+        if shortest == 0 {
+            return 0.0;
+        }
+        // Line up at least half of the shorter fingerprint.
+        let max_offset = MAX_OFFSET.min(shortest / 2);
+        let mut best = 0.0f64;
+        for offset in 0..=max_offset {
+            for (a, b) in [(a, b), (b, a)] {
+                let overlap = a.len().saturating_sub(offset).min(b.len());
+                if overlap == 0 {
+                    continue;
+                }
+                let errors: u32 = a[offset..offset + overlap]
+                    .iter()
+                    .zip(&b[..overlap])
+                    .map(|(a, b)| (a ^ b).count_ones())
+                    .sum();
+                best = best.max(1.0 - errors as f64 / (32 * overlap) as f64);
+            }
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_compares_fingerprints() {
+        let song = Fingerprint::parse("FILE=a.mp3\nDURATION=212\nFINGERPRINT=1,-1,3,4\n").unwrap();
+        assert_eq!(song.duration, 212);
+        assert_eq!(song.points, [1, u32::MAX, 3, 4]);
+        assert_eq!(Fingerprint::parse("DURATION=1\n"), None);
+
+        let mut state = 0x2545_f491_u32;
+        let mut random = || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state
+        };
+        let points: Vec<u32> = (0..500).map(|_| random()).collect();
+        let song = Fingerprint {
+            duration: 60,
+            points: points.clone(),
+        };
+        // Another encoding: a little later, with a few bits flipped.
+        let encoded = Fingerprint {
+            duration: 60,
+            points: [random(), random()]
+                .into_iter()
+                .chain(points.iter().map(|point| point ^ 1))
+                .collect(),
+        };
+        let other = Fingerprint {
+            duration: 60,
+            points: (0..500).map(|_| random()).collect(),
+        };
+        assert_eq!(song.similarity(&song), 1.0);
+        assert!(song.similarity(&encoded) > 0.95);
+        assert!(encoded.similarity(&song) > 0.95);
+        assert!(song.similarity(&other) < 0.6);
+    }
+}
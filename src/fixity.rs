@@ -0,0 +1,227 @@
+//! Fixity audits of long-term archives, behind the `index` feature:
+//! telling silent corruption, bitrot, from files that were edited.
+//!
+//! The SHA-256 checksum, size and modification time of each media file
+//! are kept in a `fixity` table of a SQLite database, see the
+//! [`index`] module, which may be the one of the index:
+//!
+//! ```sql
+//! CREATE TABLE fixity (
+//!     path TEXT PRIMARY KEY,
+//!     sha256 TEXT NOT NULL,
+//!     size INTEGER,
+//!     modified INTEGER,      -- In nanoseconds since the Unix epoch.
+//!     verified INTEGER       -- When the checksum was last found good.
+//! );
+//! ```
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use mediawalker::fixity;
+//! let report = fixity::audit("archive.db", "/mnt/archive/photos")?;
+//! for path in &report.corrupted {
+//!     eprintln!("Restore {} from a backup", path);
+//! }
+//! # Ok::<(), std::io::Error>(())
+//! ```
+use crate::index::{self, nanos, number, quote};
+use crate::sha256;
+use crate::MediaWalker;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::SystemTime;
+
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS fixity (
+    path TEXT PRIMARY KEY,
+    sha256 TEXT NOT NULL,
+    size INTEGER,
+    modified INTEGER,
+    verified INTEGER
+);
+";
+
+/// What an [`audit`] found, with the paths in the order of the walk,
+/// and the missing ones sorted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FixityReport {
+    /// How many files were checked against their stored checksums.
+    pub verified: usize,
+    /// The files seen for the first time, whose checksums were stored.
+    pub added: Vec<String>,
+    /// The files that changed along with their modification time or
+    /// size, as edits do, whose new checksums were stored.
+    pub modified: Vec<String>,
+    /// The files whose contents changed although their modification
+    /// time and size did not: silent corruption. The stored checksums
+    /// are kept, for the files to be checked again once restored.
+    pub corrupted: Vec<String>,
+    /// The files with stored checksums that are gone. Their checksums
+    /// are kept.
+    pub missing: Vec<String>,
+    /// The files that could not be read, with why.
+    pub unreadable: Vec<(String, String)>,
+}
+
+impl FixityReport {
+    /// Whether the archive is intact: nothing corrupted, missing or
+    /// unreadable.
+    pub fn is_intact(&self) -> bool {
+        self.corrupted.is_empty() && self.missing.is_empty() && self.unreadable.is_empty()
+    }
+}
+
+/// A checksum as stored.
+struct Stored {
+    sha256: String,
+    size: Option<i64>,
+    modified: Option<i64>,
+}
+
+/// Walks the media files under `root` with the default options, checks
+/// their checksums against those stored in the database, creating it if
+/// need be, and stores the checksums of new and edited files.
+pub fn audit<P: AsRef<Path>, Q: AsRef<Path>>(db: P, root: Q) -> io::Result<FixityReport> {
+    let db = db.as_ref();
+    let root = root.as_ref();
+    index::run(db, |sql| sql.write_all(SCHEMA.as_bytes()))?;
+    let output = index::run(db, |sql| {
+        writeln!(
+            sql,
+            ".mode ascii\nSELECT {}, sha256, size, modified FROM fixity;",
+            index::escaped("path")
+        )
+    })?;
+    let mut stored: HashMap<String, Stored> = index::rows(&output)
+        .filter_map(|row| match &row[..] {
+            [path, sha256, size, modified] => Some((
+                path.clone(),
+                Stored {
+                    sha256: sha256.clone(),
+                    size: size.parse().ok(),
+                    modified: modified.parse().ok(),
+                },
+            )),
+            _ => None,
+        })
+        .collect();
+
+    let mut report = FixityReport::default();
+    let now = nanos(SystemTime::now());
+    let mut updates = String::new();
+    for walk_result in MediaWalker::new(root).start() {
+        if !matches!(walk_result.result, Ok(true)) {
+            continue;
+        }
+        let path = walk_result.path;
+        let read = fs::metadata(&path).and_then(|metadata| {
+            let digest = sha256::file(Path::new(&path))?;
+            Ok((metadata, sha256::hex(&digest)))
+        });
+        let (metadata, checksum) = match read {
+            Ok(read) => read,
+            Err(err) => {
+                stored.remove(&path);
+                report.unreadable.push((path, err.to_string()));
+                continue;
+            }
+        };
+        let size = i64::try_from(metadata.len()).ok();
+        let modified = metadata.modified().ok().and_then(nanos);
+        let store = |updates: &mut String| {
+            updates.push_str(&format!(
+                "INSERT OR REPLACE INTO fixity VALUES ({}, {}, {}, {}, {});\n",
+                quote(&path),
+                quote(&checksum),
+                number(size),
+                number(modified),
+                number(now)
+            ));
+        };
+        match stored.remove(&path) {
+            None => {
+                store(&mut updates);
+                report.added.push(path);
+            }
+            Some(known) if known.sha256 == checksum => {
+                updates.push_str(&format!(
+                    "UPDATE fixity SET verified = {} WHERE path = {};\n",
+                    number(now),
+                    quote(&path)
+                ));
+                report.verified += 1;
+            }
+            Some(known) if (known.size, known.modified) != (size, modified) => {
+                store(&mut updates);
+                report.modified.push(path);
+            }
+            Some(_) => {
+                report.verified += 1;
+                report.corrupted.push(path);
+            }
+        }
+    }
+    let mut missing: Vec<String> = stored
+        .into_keys()
+        .filter(|path| Path::new(path).starts_with(root))
+        .collect();
+    missing.sort();
+    report.missing = missing;
+    index::run(db, |sql| write!(sql, "BEGIN;\n{}COMMIT;\n", updates))?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::require_sqlite3;
+    use crate::testutil::TempTree;
+    use std::fs::File;
+
+    #[test]
+    fn it_tells_bitrot_from_edits() {
+        require_sqlite3();
+        let tree = TempTree::new("fixity");
+        let elsewhere = TempTree::new("fixity-db");
+        let db = elsewhere.path().join("archive.db");
+        let rotten = tree.sample("sample_640x426.jpg", "2019/rotten.jpg");
+        let edited = tree.sample("sample_640x426.jpg", "2019/edited.jpg");
+        let gone = tree.sample("sample3.mp3", "2019/gone.mp3");
+        tree.sample("sample1.heic", "2019/fine.heic");
+        let path = |path: &Path| path.to_string_lossy().into_owned();
+
+        let report = audit(&db, tree.path()).unwrap();
+        assert_eq!((report.added.len(), report.verified), (4, 0));
+        assert!(report.is_intact());
+
+        // A flipped bit, with the times put back as bitrot leaves them.
+        let modified = fs::metadata(&rotten).unwrap().modified().unwrap();
+        let mut bytes = fs::read(&rotten).unwrap();
+        let middle = bytes.len() / 2;
+        bytes[middle] ^= 1;
+        fs::write(&rotten, bytes).unwrap();
+        File::options()
+            .write(true)
+            .open(&rotten)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        let bytes = fs::read(&edited).unwrap();
+        fs::write(&edited, &bytes[..4096]).unwrap();
+        fs::remove_file(&gone).unwrap();
+
+        let report = audit(&db, tree.path()).unwrap();
+        assert_eq!(report.corrupted, vec![path(&rotten)]);
+        assert_eq!(report.modified, vec![path(&edited)]);
+        assert_eq!(report.missing, vec![path(&gone)]);
+        assert_eq!(report.verified, 2);
+        assert!(!report.is_intact());
+
+        // The good checksum is kept, and the edit taken in.
+        let report = audit(&db, tree.path()).unwrap();
+        assert_eq!(report.corrupted, vec![path(&rotten)]);
+        assert!(report.modified.is_empty() && report.added.is_empty());
+    }
+}
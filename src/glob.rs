@@ -0,0 +1,126 @@
+use std::path::{Component, Path};
+
+/// A glob pattern for excluding paths.
+///
+/// Patterns are matched against paths relative to the walk root, with
+/// `/` as the separator. A pattern without a `/` is matched against the
+/// file name alone, so `node_modules` and `*.tmp` match at any depth.
+/// `*` matches anything within a path component, `?` any single
+/// character, and a `**` component any number of components.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Glob {
+    pattern: String,
+    components: Vec<String>,
+    name_only: bool,
+}
+
+impl Glob {
+    pub(crate) fn new(pattern: &str) -> Self {
+        let trimmed = pattern.trim_start_matches('/');
+        Glob {
+            pattern: pattern.to_string(),
+            components: trimmed
+                .split('/')
+                .filter(|c| !c.is_empty())
+                .map(str::to_string)
+                .collect(),
+            name_only: !pattern.contains('/'),
+        }
+    }
+
+    /// The pattern as given.
+    pub(crate) fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Whether the pattern matches a path relative to the walk root.
+    pub(crate) fn matches(&self, relative: &Path) -> bool {
+        let components: Vec<_> = relative
+            .components()
+            .filter_map(|c| match c {
+                Component::Normal(name) => Some(name.to_string_lossy()),
+                _ => None,
+            })
+            .collect();
+        if self.name_only {
+            return match components.last() {
+                Some(name) => self
+                    .components
+                    .first()
+                    .is_some_and(|pattern| matches_component(pattern, name)),
+                None => false,
+            };
+        }
+        let components: Vec<&str> = components.iter().map(|c| c.as_ref()).collect();
+        matches_components(&self.components, &components)
+    }
+}
+
+fn matches_components(patterns: &[String], components: &[&str]) -> bool {
+    match patterns.split_first() {
+        None => components.is_empty(),
+        Some((pattern, rest)) if pattern == "**" => {
+            (0..=components.len()).any(|skip| matches_components(rest, &components[skip..]))
+        }
+        Some((pattern, rest)) => match components.split_first() {
+            Some((component, others)) => {
+                matches_component(pattern, component) && matches_components(rest, others)
+            }
+            None => false,
+        },
+    }
+}
+
+/// Matches a single path component against a pattern with `*` and `?`.
+fn matches_component(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Where to resume after the last `*`, if the match fails later on.
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some('?') => {
+                p += 1;
+                n += 1;
+            }
+            Some(c) if *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_matches_globs() {
+        let matches = |pattern: &str, path: &str| Glob::new(pattern).matches(Path::new(path));
+        assert!(matches("node_modules", "a/b/node_modules"));
+        assert!(matches("*.tmp", "photos/x.tmp"));
+        assert!(!matches("*.tmp", "photos/x.tmp.jpg"));
+        assert!(matches("IMG_????.jpg", "IMG_0001.jpg"));
+        assert!(matches("photos/*/raw", "photos/2023/raw"));
+        assert!(!matches("photos/*/raw", "old/photos/2023/raw"));
+        assert!(matches("**/Cache", "a/b/Cache"));
+        assert!(matches("**/Cache", "Cache"));
+        assert!(matches("music/**/*.m3u", "music/a/b/list.m3u"));
+        assert!(!matches("music/**", "video/x"));
+    }
+}
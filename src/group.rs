@@ -0,0 +1,263 @@
+//! Grouping the photos of a scan into moments, by when and where they
+//! were taken.
+use crate::exif::EARTH_RADIUS;
+use crate::{GeoPoint, MediaWalkResult};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+/// Groups the photos among the results of a scan by when they were
+/// taken, as read with [`MediaWalker::exif`](crate::MediaWalker::exif):
+/// photos taken at most `gap` after the one before are in the same
+/// group, so that a short gap makes bursts, and a gap of hours events.
+/// The groups, and the photos in each, are in the order they were
+/// taken. Results without a time are left out.
+///
+/// # Examples
+///
+/// ```
+/// use mediawalker::{group_by_time, Kind, MediaWalker};
+/// use std::time::Duration;
+/// let results: Vec<_> = MediaWalker::new(env!("CARGO_MANIFEST_DIR"))
+///     .kinds(&[Kind::Image])
+///     .exif(true)
+///     .start()
+///     .collect();
+/// for event in group_by_time(&results, Duration::from_secs(3 * 3600)) {
+///     println!("{} photos, from {}", event.len(), event[0].path);
+/// }
+/// ```
+pub fn group_by_time(results: &[MediaWalkResult], gap: Duration) -> Vec<Vec<&MediaWalkResult>> {
+    let mut taken: Vec<(SystemTime, &MediaWalkResult)> = results
+        .iter()
+        .filter_map(|walk_result| Some((walk_result.exif.as_ref()?.taken_at?, walk_result)))
+        .collect();
+    taken.sort_by(|(a, a_result), (b, b_result)| a.cmp(b).then(a_result.path.cmp(&b_result.path)));
+    let mut groups: Vec<Vec<&MediaWalkResult>> = vec![];
+    let mut last = None;
+    for (time, walk_result) in taken {
+        let apart = last.and_then(|last| time.duration_since(last).ok());
+        match (groups.last_mut(), apart) {
+            (Some(group), Some(apart)) if apart <= gap => group.push(walk_result),
+            _ => groups.push(vec![walk_result]),
+        }
+        last = Some(time);
+    }
+    groups
+}
+
+/// Photos taken around the same place, see [`group_by_location`].
+#[derive(Debug, Clone)]
+pub struct LocationGroup<'a> {
+    /// The middle of where the photos were taken.
+    pub center: GeoPoint,
+    /// The photos, in the order of their paths.
+    pub photos: Vec<&'a MediaWalkResult>,
+}
+
+impl LocationGroup<'_> {
+    /// The name of the place the photos were taken at, as the geocoder
+    /// tells from their center.
+    pub fn place<G: ReverseGeocoder + ?Sized>(&self, geocoder: &G) -> Option<String> {
+        geocoder.place(self.center)
+    }
+}
+
+/// Names the place at a point, like `Paris, France`, for
+/// [`LocationGroup::place`]. Any lookup will do: an offline gazetteer,
+/// or a web service like Nominatim.
+pub trait ReverseGeocoder {
+    /// The name of the place at a point, if known.
+    fn place(&self, point: GeoPoint) -> Option<String>;
+}
+
+impl<F: Fn(GeoPoint) -> Option<String>> ReverseGeocoder for F {
+    fn place(&self, point: GeoPoint) -> Option<String> {
+        self(point)
+    }
+}
+
+/// Groups the photos among the results of a scan by where they were
+/// taken, as read with [`MediaWalker::exif`](crate::MediaWalker::exif):
+/// going by path, each photo joins the group of the nearest photo at
+/// most `radius` meters away that started one, or starts a group of its
+/// own. The largest groups come first. Results without a location are
+/// left out.
+///
+/// # Examples
+///
+/// ```
+/// use mediawalker::{group_by_location, GeoPoint, Kind, MediaWalker};
+/// let results: Vec<_> = MediaWalker::new(env!("CARGO_MANIFEST_DIR"))
+///     .kinds(&[Kind::Image])
+///     .exif(true)
+///     .start()
+///     .collect();
+/// let geocoder = |point: GeoPoint| Some(format!("{:.1}, {:.1}", point.latitude, point.longitude));
+/// for album in group_by_location(&results, 5_000.0) {
+///     println!("{}: {} photos", album.place(&geocoder).unwrap(), album.photos.len());
+/// }
+/// ```
+pub fn group_by_location(results: &[MediaWalkResult], radius: f64) -> Vec<LocationGroup<'_>> {
+    let mut located: Vec<(GeoPoint, &MediaWalkResult)> = results
+        .iter()
+        .filter_map(|walk_result| Some((walk_result.exif.as_ref()?.location?, walk_result)))
+        .collect();
+    located.sort_by(|(_, a), (_, b)| a.path.cmp(&b.path));
+    // The photos that started groups are kept in a grid of cubes, as
+    // wide as the straight line through the Earth between two points
+    // `radius` apart, so that the ones near enough to join are in the
+    // cube of a photo or the ones around it.
+    let chord = 2.0 * (radius.max(0.0) / (2.0 * EARTH_RADIUS)).min(1.0).sin();
+    let cell = chord.max(1e-9);
+    let cube = |point: &GeoPoint| {
+        let [x, y, z] = unit_vector(point);
+        [x, y, z].map(|axis| (axis / cell).floor() as i64)
+    };
+    let mut starters: HashMap<[i64; 3], Vec<usize>> = HashMap::new();
+    let mut groups: Vec<(GeoPoint, Vec<(GeoPoint, &MediaWalkResult)>)> = vec![];
+    for (point, walk_result) in located {
+        let here = cube(&point);
+        let mut nearest: Option<(f64, usize)> = None;
+        for &group in around(here)
+            .filter_map(|cube| starters.get(&cube))
+            .flatten()
+        {
+            let distance = groups[group].0.distance(&point);
+            if distance <= radius && nearest.is_none_or(|(nearest, _)| distance < nearest) {
+                nearest = Some((distance, group));
+            }
+        }
+        match nearest {
+            Some((_, group)) => groups[group].1.push((point, walk_result)),
+            None => {
+                starters.entry(here).or_default().push(groups.len());
+                groups.push((point, vec![(point, walk_result)]));
+            }
+        }
+    }
+    let mut groups: Vec<LocationGroup> = groups
+        .into_iter()
+        .map(|(_, photos)| LocationGroup {
+            center: center(photos.iter().map(|(point, _)| point)),
+            photos: photos
+                .into_iter()
+                .map(|(_, walk_result)| walk_result)
+                .collect(),
+        })
+        .collect();
+    // Stable, so that groups of a size stay in the order of their paths.
+    groups.sort_by_key(|group| std::cmp::Reverse(group.photos.len()));
+    groups
+}
+
+/// A cube of the grid, and the 26 around it.
+fn around([x, y, z]: [i64; 3]) -> impl Iterator<Item = [i64; 3]> {
+    (0..27).map(move |i| [x + i % 3 - 1, y + i / 3 % 3 - 1, z + i / 9 - 1])
+}
+
+/// A point as a vector from the center of the Earth, of length 1.
+fn unit_vector(point: &GeoPoint) -> [f64; 3] {
+    let (latitude, longitude) = (point.latitude.to_radians(), point.longitude.to_radians());
+    [
+        latitude.cos() * longitude.cos(),
+        latitude.cos() * longitude.sin(),
+        latitude.sin(),
+    ]
+}
+
+/// The middle of some points: the direction of the sum of their unit
+/// vectors, which does not trip over the antimeridian.
+fn center<'a>(points: impl Iterator<Item = &'a GeoPoint>) -> GeoPoint {
+    let [x, y, z] = points.map(unit_vector).fold([0.0; 3], |sum, vector| {
+        [sum[0] + vector[0], sum[1] + vector[1], sum[2] + vector[2]]
+    });
+    GeoPoint {
+        latitude: z.atan2(x.hypot(y)).to_degrees(),
+        longitude: y.atan2(x).to_degrees(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Exif;
+
+    fn photo(path: &str, secs: Option<u64>) -> MediaWalkResult {
+        let mut walk_result = MediaWalkResult::new(path.to_string());
+        walk_result.exif = Some(Exif {
+            taken_at: secs.map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs)),
+            ..Exif::default()
+        });
+        walk_result
+    }
+
+    fn photo_at(path: &str, latitude: f64, longitude: f64) -> MediaWalkResult {
+        let mut walk_result = MediaWalkResult::new(path.to_string());
+        walk_result.exif = Some(Exif {
+            location: Some(GeoPoint {
+                latitude,
+                longitude,
+            }),
+            ..Exif::default()
+        });
+        walk_result
+    }
+
+    #[test]
+    fn it_groups_photos_taken_close_together() {
+        let results = vec![
+            photo("d.jpg", Some(10_000)),
+            photo("b.jpg", Some(101)),
+            photo("a.jpg", Some(100)),
+            photo("c.jpg", Some(103)),
+            photo("e.jpg", None),
+            MediaWalkResult::new("f.jpg".to_string()),
+        ];
+        let groups: Vec<Vec<&str>> = group_by_time(&results, Duration::from_secs(2))
+            .into_iter()
+            .map(|group| group.iter().map(|r| r.path.as_str()).collect())
+            .collect();
+        assert_eq!(groups, vec![vec!["a.jpg", "b.jpg", "c.jpg"], vec!["d.jpg"]]);
+
+        let groups = group_by_time(&results, Duration::ZERO);
+        assert_eq!(groups.len(), 4);
+    }
+
+    #[test]
+    fn it_groups_photos_taken_close_by() {
+        let results = vec![
+            photo_at("paris/louvre.jpg", 48.8606, 2.3376),
+            photo_at("paris/eiffel.jpg", 48.8584, 2.2945),
+            photo_at("fiji/east.jpg", -17.0, 179.999),
+            photo_at("fiji/west.jpg", -17.0, -179.999),
+            photo_at("paris/notre-dame.jpg", 48.853, 2.3499),
+            photo_at("nyc/liberty.jpg", 40.6892, -74.0445),
+            photo("undated.jpg", None),
+        ];
+        let groups = group_by_location(&results, 5_000.0);
+        let paths: Vec<Vec<&str>> = groups
+            .iter()
+            .map(|group| group.photos.iter().map(|r| r.path.as_str()).collect())
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                vec![
+                    "paris/eiffel.jpg",
+                    "paris/louvre.jpg",
+                    "paris/notre-dame.jpg"
+                ],
+                vec!["fiji/east.jpg", "fiji/west.jpg"],
+                vec!["nyc/liberty.jpg"],
+            ]
+        );
+        let paris = groups[0].center;
+        assert!((48.85..48.86).contains(&paris.latitude), "{:?}", paris);
+        assert!((179.999 - groups[1].center.longitude.abs()).abs() < 0.01);
+
+        let geocoder = |point: GeoPoint| (point.latitude > 45.0).then(|| "Paris".to_string());
+        assert_eq!(groups[0].place(&geocoder), Some("Paris".to_string()));
+        assert_eq!(groups[2].place(&geocoder), None);
+        assert_eq!(group_by_location(&results, 0.0).len(), 6);
+    }
+}
@@ -0,0 +1,845 @@
+//! A gRPC server for scanning from other languages, behind the `grpc`
+//! feature. Also run by `mediawalker grpc --root PATH --listen :50051`.
+//!
+//! It serves the `MediaWalker` service of `proto/mediawalker.proto`,
+//! whose `Scan` call walks the root and streams a `MediaResult` per file
+//! found. It speaks HTTP/2 over cleartext with prior knowledge, as gRPC
+//! clients do for `http://` targets, and serves one call at a time on a
+//! connection, which it tells clients in its settings. Compressed
+//! messages are not supported.
+use crate::hpack::{self, Decoder};
+use crate::{Kind, MediaWalkResult, MediaWalker};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// The path of the `Scan` call.
+pub const SCAN_PATH: &str = "/mediawalker.MediaWalker/Scan";
+
+/// How many connections are served at once; more are turned away.
+const MAX_CONNECTIONS: usize = 32;
+
+/// How long a client may stay quiet, between calls or while it has the
+/// server wait for room to send.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// The largest request message accepted, in bytes.
+const MAX_MESSAGE: usize = 64 * 1024;
+
+/// What every HTTP/2 client sends first.
+const PREFACE: &[u8; 24] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+/// The largest frame payload, until the client allows more, and the
+/// window each side starts with.
+const DEFAULT_MAX_FRAME: usize = 16 * 1024;
+const DEFAULT_WINDOW: i64 = 65_535;
+
+const DATA: u8 = 0x0;
+const HEADERS: u8 = 0x1;
+const RST_STREAM: u8 = 0x3;
+const SETTINGS: u8 = 0x4;
+const PING: u8 = 0x6;
+const GOAWAY: u8 = 0x7;
+const WINDOW_UPDATE: u8 = 0x8;
+const CONTINUATION: u8 = 0x9;
+
+const END_STREAM: u8 = 0x1;
+const ACK: u8 = 0x1;
+const END_HEADERS: u8 = 0x4;
+const PADDED: u8 = 0x8;
+const PRIORITY: u8 = 0x20;
+
+const SETTINGS_MAX_CONCURRENT_STREAMS: u16 = 0x3;
+const SETTINGS_INITIAL_WINDOW_SIZE: u16 = 0x4;
+const SETTINGS_MAX_FRAME_SIZE: u16 = 0x5;
+
+/// The HTTP/2 error code of a stream refused before it was worked on.
+const REFUSED_STREAM: u32 = 0x7;
+
+/// The gRPC status codes used.
+const OK: u32 = 0;
+const INVALID_ARGUMENT: u32 = 3;
+const UNIMPLEMENTED: u32 = 12;
+const INTERNAL: u32 = 13;
+
+/// A gRPC server scanning a single root, see the [module docs](self).
+#[derive(Debug)]
+pub struct Server {
+    root: PathBuf,
+    listener: TcpListener,
+    /// How many connections are being served.
+    connections: AtomicUsize,
+}
+
+impl Server {
+    /// Binds a server for `root` to an address like `0.0.0.0:50051`, or
+    /// `:50051` for the loopback interface only.
+    pub fn bind<P: AsRef<Path>>(root: P, listen: &str) -> io::Result<Self> {
+        let root = root.as_ref().to_path_buf();
+        if !root.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{}: no such directory", root.display()),
+            ));
+        }
+        let listen = match listen.strip_prefix(':') {
+            Some(port) => format!("127.0.0.1:{}", port),
+            None => listen.to_string(),
+        };
+        Ok(Server {
+            root,
+            listener: TcpListener::bind(listen)?,
+            connections: AtomicUsize::new(0),
+        })
+    }
+
+    /// The address the server is listening on.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Serves calls, each connection in a thread of its own, until
+    /// accepting a connection fails. Connections beyond the first few
+    /// dozen are closed until others finish.
+    pub fn run(self) -> io::Result<()> {
+        let server = Arc::new(self);
+        loop {
+            let (stream, _) = server.listener.accept()?;
+            if server.connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+                server.connections.fetch_sub(1, Ordering::SeqCst);
+                continue;
+            }
+            let server = Arc::clone(&server);
+            thread::spawn(move || {
+                // A client gone away is no concern of the server.
+                let _ = stream
+                    .set_read_timeout(Some(IDLE_TIMEOUT))
+                    .and_then(|_| Connection::new(stream))
+                    .and_then(|mut connection| connection.serve(&server.root));
+                server.connections.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+    }
+}
+
+/// Serves scans of `root` over gRPC on `listen` until the server fails,
+/// see [`Server`].
+pub fn serve<P: AsRef<Path>>(root: P, listen: &str) -> io::Result<()> {
+    Server::bind(root, listen)?.run()
+}
+
+/// An HTTP/2 frame.
+struct Frame {
+    kind: u8,
+    flags: u8,
+    stream: u32,
+    payload: Vec<u8>,
+}
+
+/// A call as it is being received.
+struct Call {
+    stream: u32,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+/// What a frame received while busy with a call meant for it.
+enum Interrupt {
+    None,
+    /// The client cancelled the call.
+    Reset,
+}
+
+/// An HTTP/2 connection to a client.
+struct Connection {
+    reader: BufReader<TcpStream>,
+    stream: TcpStream,
+    decoder: Decoder,
+    /// How much the client lets the server send on the connection.
+    window: i64,
+    /// How much the client lets the server send on the stream of the
+    /// call, and on new streams.
+    stream_window: i64,
+    initial_window: i64,
+    max_frame: usize,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> io::Result<Self> {
+        Ok(Connection {
+            reader: BufReader::new(stream.try_clone()?),
+            stream,
+            decoder: Decoder::new(),
+            window: DEFAULT_WINDOW,
+            stream_window: DEFAULT_WINDOW,
+            initial_window: DEFAULT_WINDOW,
+            max_frame: DEFAULT_MAX_FRAME,
+        })
+    }
+
+    /// Serves the calls of the connection until the client closes it.
+    fn serve(&mut self, root: &Path) -> io::Result<()> {
+        let mut preface = [0; PREFACE.len()];
+        self.reader.read_exact(&mut preface)?;
+        if &preface != PREFACE {
+            return Err(invalid("not an HTTP/2 connection"));
+        }
+        let mut settings = vec![];
+        settings.extend(SETTINGS_MAX_CONCURRENT_STREAMS.to_be_bytes());
+        settings.extend(1u32.to_be_bytes());
+        self.write_frame(SETTINGS, 0, 0, &settings)?;
+
+        let mut call: Option<Call> = None;
+        loop {
+            let Some(frame) = self.read_frame()? else {
+                return Ok(());
+            };
+            let ended = frame.flags & END_STREAM != 0;
+            match frame.kind {
+                HEADERS => {
+                    let headers = self.header_block(&frame)?;
+                    match &mut call {
+                        // The client's trailers, which tell nothing.
+                        Some(call) if call.stream == frame.stream => {}
+                        Some(_) => self.refuse(frame.stream)?,
+                        None => {
+                            call = Some(Call {
+                                stream: frame.stream,
+                                headers,
+                                body: vec![],
+                            });
+                        }
+                    }
+                }
+                DATA => {
+                    let data = unpadded(&frame)?;
+                    self.acknowledge(&frame)?;
+                    if let Some(call) = call.as_mut().filter(|call| call.stream == frame.stream) {
+                        call.body.extend(data);
+                        if call.body.len() > MAX_MESSAGE + 5 {
+                            return Err(invalid("request too large"));
+                        }
+                    }
+                }
+                RST_STREAM
+                    if call
+                        .as_ref()
+                        .is_some_and(|call| call.stream == frame.stream) =>
+                {
+                    call = None;
+                }
+                _ => {
+                    if !self.control(&frame)? {
+                        return Ok(());
+                    }
+                }
+            }
+            if ended
+                && call
+                    .as_ref()
+                    .is_some_and(|call| call.stream == frame.stream)
+            {
+                let call = call.take().unwrap();
+                self.answer(root, call)?;
+                // The next call starts with the window the client set.
+                self.stream_window = self.initial_window;
+            }
+        }
+    }
+
+    /// Answers a call, once the client has sent all of it.
+    fn answer(&mut self, root: &Path, call: Call) -> io::Result<()> {
+        let header = |name: &str| {
+            call.headers
+                .iter()
+                .find(|(header, _)| header == name)
+                .map(|(_, value)| value.as_str())
+        };
+        let is_grpc =
+            header("content-type").is_some_and(|value| value.starts_with("application/grpc"));
+        if header(":method") != Some("POST") || !is_grpc {
+            return self.write_headers(call.stream, &[(":status", "415")], true);
+        }
+        if header(":path") != Some(SCAN_PATH) {
+            return self.trailers_only(call.stream, UNIMPLEMENTED, "unknown method");
+        }
+        let request = match message(&call.body).and_then(ScanRequest::decode) {
+            Ok(request) => request,
+            Err(err) => return self.trailers_only(call.stream, INVALID_ARGUMENT, &err.to_string()),
+        };
+        let walker = match request.apply(MediaWalker::new(root)) {
+            Ok(walker) => walker,
+            Err(err) => return self.trailers_only(call.stream, INVALID_ARGUMENT, &err),
+        };
+        let mut handle = match walker.try_start() {
+            Ok(handle) => handle,
+            Err(err) => return self.trailers_only(call.stream, INTERNAL, &err.to_string()),
+        };
+        self.write_headers(
+            call.stream,
+            &[(":status", "200"), ("content-type", "application/grpc")],
+            false,
+        )?;
+        for walk_result in handle.by_ref() {
+            let result = media_result(&walk_result);
+            let mut data = vec![0];
+            data.extend((result.len() as u32).to_be_bytes());
+            data.extend(result);
+            let sent = self.send_data(call.stream, &data);
+            if !matches!(sent, Ok(Interrupt::None)) {
+                // The client cancelled the call, or went away, so nobody
+                // wants the rest.
+                handle.stop();
+                return sent.map(|_| ());
+            }
+        }
+        self.write_headers(call.stream, &[("grpc-status", "0")], true)
+    }
+
+    /// Ends a call with a status right away, without a response.
+    fn trailers_only(&mut self, stream: u32, status: u32, message: &str) -> io::Result<()> {
+        let code = status.to_string();
+        let message = percent_encode(message);
+        let mut headers = vec![
+            (":status", "200"),
+            ("content-type", "application/grpc"),
+            ("grpc-status", code.as_str()),
+        ];
+        if status != OK {
+            headers.push(("grpc-message", message.as_str()));
+        }
+        self.write_headers(stream, &headers, true)
+    }
+
+    /// Sends data on a stream, as fast as the client lets it, handling
+    /// the frames the client sends meanwhile.
+    fn send_data(&mut self, stream: u32, mut data: &[u8]) -> io::Result<Interrupt> {
+        while !data.is_empty() {
+            // Look at what the client sent without waiting, unless the
+            // server has to wait for room to send.
+            while self.window <= 0 || self.stream_window <= 0 || self.is_readable()? {
+                let frame = self
+                    .read_frame()?
+                    .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+                if let Interrupt::Reset = self.interrupt(stream, &frame)? {
+                    return Ok(Interrupt::Reset);
+                }
+            }
+            let len = (data.len() as i64)
+                .min(self.window)
+                .min(self.stream_window)
+                .min(self.max_frame as i64) as usize;
+            self.write_frame(DATA, 0, stream, &data[..len])?;
+            self.window -= len as i64;
+            self.stream_window -= len as i64;
+            data = &data[len..];
+        }
+        Ok(Interrupt::None)
+    }
+
+    /// Handles a frame received while answering a call on `stream`.
+    fn interrupt(&mut self, stream: u32, frame: &Frame) -> io::Result<Interrupt> {
+        match frame.kind {
+            HEADERS => {
+                // The block is decoded all the same, to keep the table
+                // in step with the client's.
+                self.header_block(frame)?;
+                if frame.stream != stream {
+                    self.refuse(frame.stream)?;
+                }
+            }
+            DATA => self.acknowledge(frame)?,
+            RST_STREAM if frame.stream == stream => return Ok(Interrupt::Reset),
+            _ => {
+                if !self.control(frame)? {
+                    return Err(io::ErrorKind::ConnectionAborted.into());
+                }
+            }
+        }
+        Ok(Interrupt::None)
+    }
+
+    /// Handles a frame about the connection. `false` once the client is
+    /// going away.
+    fn control(&mut self, frame: &Frame) -> io::Result<bool> {
+        match frame.kind {
+            SETTINGS if frame.flags & ACK == 0 => {
+                for setting in frame.payload.chunks_exact(6) {
+                    let id = u16::from_be_bytes([setting[0], setting[1]]);
+                    let value =
+                        u32::from_be_bytes([setting[2], setting[3], setting[4], setting[5]]);
+                    match id {
+                        SETTINGS_INITIAL_WINDOW_SIZE => {
+                            let value = i64::from(value);
+                            self.stream_window += value - self.initial_window;
+                            self.initial_window = value;
+                        }
+                        SETTINGS_MAX_FRAME_SIZE => self.max_frame = value as usize,
+                        _ => {}
+                    }
+                }
+                self.write_frame(SETTINGS, ACK, 0, &[])?;
+            }
+            PING if frame.flags & ACK == 0 => self.write_frame(PING, ACK, 0, &frame.payload)?,
+            WINDOW_UPDATE => {
+                let increment = frame
+                    .payload
+                    .get(..4)
+                    .map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()) & 0x7fff_ffff)
+                    .ok_or_else(|| invalid("bad window update"))?;
+                if frame.stream == 0 {
+                    self.window += i64::from(increment);
+                } else {
+                    // Only the stream of the call is sent on.
+                    self.stream_window += i64::from(increment);
+                }
+            }
+            GOAWAY => return Ok(false),
+            CONTINUATION => return Err(invalid("unexpected continuation")),
+            // Priorities, acknowledgements and resets of refused streams.
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    /// Whether the client has sent something, without waiting for it.
+    fn is_readable(&mut self) -> io::Result<bool> {
+        if !self.reader.buffer().is_empty() {
+            return Ok(true);
+        }
+        self.stream.set_nonblocking(true)?;
+        let read = self.reader.fill_buf().map(|buffer| !buffer.is_empty());
+        self.stream.set_nonblocking(false)?;
+        match read {
+            Ok(true) => Ok(true),
+            Ok(false) => Err(io::ErrorKind::UnexpectedEof.into()),
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Decodes the header block a frame starts, reading the frames that
+    /// continue it.
+    fn header_block(&mut self, frame: &Frame) -> io::Result<Vec<(String, String)>> {
+        let mut block = unpadded(frame)?;
+        if frame.flags & PRIORITY != 0 {
+            block = block.get(5..).ok_or_else(|| invalid("bad headers"))?;
+        }
+        let mut block = block.to_vec();
+        let mut ended = frame.flags & END_HEADERS != 0;
+        while !ended {
+            let next = self
+                .read_frame()?
+                .filter(|next| next.kind == CONTINUATION && next.stream == frame.stream)
+                .ok_or_else(|| invalid("expected a continuation"))?;
+            if block.len() + next.payload.len() > MAX_MESSAGE {
+                return Err(invalid("headers too large"));
+            }
+            block.extend(&next.payload);
+            ended = next.flags & END_HEADERS != 0;
+        }
+        self.decoder.decode(&block)
+    }
+
+    /// Refuses a stream, for one call at a time is served.
+    fn refuse(&mut self, stream: u32) -> io::Result<()> {
+        self.write_frame(RST_STREAM, 0, stream, &REFUSED_STREAM.to_be_bytes())
+    }
+
+    /// Gives the client back the room the data of a frame took.
+    fn acknowledge(&mut self, frame: &Frame) -> io::Result<()> {
+        let len = frame.payload.len() as u32;
+        if len > 0 {
+            self.write_frame(WINDOW_UPDATE, 0, 0, &len.to_be_bytes())?;
+            if frame.flags & END_STREAM == 0 {
+                self.write_frame(WINDOW_UPDATE, 0, frame.stream, &len.to_be_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a frame, `None` if the client closed the connection.
+    fn read_frame(&mut self) -> io::Result<Option<Frame>> {
+        let mut header = [0; 9];
+        match self.reader.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err),
+        }
+        let len = u32::from_be_bytes([0, header[0], header[1], header[2]]) as usize;
+        if len > DEFAULT_MAX_FRAME {
+            return Err(invalid("frame too large"));
+        }
+        let mut payload = vec![0; len];
+        self.reader.read_exact(&mut payload)?;
+        Ok(Some(Frame {
+            kind: header[3],
+            flags: header[4],
+            stream: u32::from_be_bytes([header[5], header[6], header[7], header[8]]) & 0x7fff_ffff,
+            payload,
+        }))
+    }
+
+    fn write_headers(
+        &mut self,
+        stream: u32,
+        headers: &[(&str, &str)],
+        ended: bool,
+    ) -> io::Result<()> {
+        let flags = if ended {
+            END_HEADERS | END_STREAM
+        } else {
+            END_HEADERS
+        };
+        self.write_frame(HEADERS, flags, stream, &hpack::encode(headers))
+    }
+
+    fn write_frame(&mut self, kind: u8, flags: u8, stream: u32, payload: &[u8]) -> io::Result<()> {
+        let mut frame = (payload.len() as u32).to_be_bytes()[1..].to_vec();
+        frame.push(kind);
+        frame.push(flags);
+        frame.extend(stream.to_be_bytes());
+        frame.extend(payload);
+        self.stream.write_all(&frame)
+    }
+}
+
+/// The payload of a frame, without its padding.
+fn unpadded(frame: &Frame) -> io::Result<&[u8]> {
+    if frame.flags & PADDED == 0 {
+        return Ok(&frame.payload);
+    }
+    let padding = usize::from(
+        *frame
+            .payload
+            .first()
+            .ok_or_else(|| invalid("bad padding"))?,
+    );
+    frame
+        .payload
+        .get(1..frame.payload.len().saturating_sub(padding))
+        .ok_or_else(|| invalid("bad padding"))
+}
+
+/// The single message of a gRPC request body, without its prefix.
+fn message(body: &[u8]) -> io::Result<&[u8]> {
+    let Some((prefix, message)) = body.split_first_chunk::<5>() else {
+        // No message at all, which reads as the defaults.
+        return if body.is_empty() {
+            Ok(&[])
+        } else {
+            Err(invalid("truncated message"))
+        };
+    };
+    if prefix[0] != 0 {
+        return Err(invalid("compressed messages are not supported"));
+    }
+    let len = u32::from_be_bytes([prefix[1], prefix[2], prefix[3], prefix[4]]) as usize;
+    if len != message.len() {
+        return Err(invalid("expected a single message"));
+    }
+    Ok(message)
+}
+
+/// A `ScanRequest` of the proto file.
+#[derive(Debug, Default, PartialEq)]
+struct ScanRequest {
+    kinds: Vec<String>,
+    threads: u32,
+    excludes: Vec<String>,
+    extensions: Vec<String>,
+    min_size: u64,
+    max_size: u64,
+}
+
+impl ScanRequest {
+    fn decode(mut message: &[u8]) -> io::Result<Self> {
+        let mut request = ScanRequest::default();
+        while !message.is_empty() {
+            let key = varint(&mut message)?;
+            let value = field(&mut message, key)?;
+            let string = || match value {
+                Value::Bytes(bytes) => String::from_utf8(bytes.to_vec())
+                    .map_err(|_| invalid("expected a UTF-8 string")),
+                _ => Err(invalid("expected a string")),
+            };
+            let number = || match value {
+                Value::Varint(n) => Ok(n),
+                _ => Err(invalid("expected a number")),
+            };
+            match key >> 3 {
+                1 => request.kinds.push(string()?),
+                2 => request.threads = number()? as u32,
+                3 => request.excludes.push(string()?),
+                4 => request.extensions.push(string()?),
+                5 => request.min_size = number()?,
+                6 => request.max_size = number()?,
+                _ => {}
+            }
+        }
+        Ok(request)
+    }
+
+    /// Sets the options of the request on a walker, with the thread count
+    /// capped at the number of CPUs.
+    fn apply(&self, mut walker: MediaWalker) -> Result<MediaWalker, String> {
+        if !self.kinds.is_empty() {
+            let kinds = self
+                .kinds
+                .iter()
+                .map(|kind| kind.parse::<Kind>())
+                .collect::<Result<Vec<_>, _>>()?;
+            walker = walker.kinds(&kinds);
+        }
+        if self.threads > 0 {
+            let cpus = thread::available_parallelism().map_or(1, |n| n.get());
+            walker = walker.threads((self.threads as usize).min(cpus));
+        }
+        let excludes: Vec<_> = self.excludes.iter().map(String::as_str).collect();
+        let extensions: Vec<_> = self.extensions.iter().map(String::as_str).collect();
+        walker = walker.excludes(&excludes).extensions(&extensions);
+        if self.min_size > 0 {
+            walker = walker.min_size(self.min_size);
+        }
+        if self.max_size > 0 {
+            walker = walker.max_size(self.max_size);
+        }
+        Ok(walker)
+    }
+}
+
+/// A protobuf field value.
+enum Value<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+/// Reads the value of a field of the given key.
+fn field<'a>(message: &mut &'a [u8], key: u64) -> io::Result<Value<'a>> {
+    let mut take = |len: usize| {
+        let bytes = message
+            .get(..len)
+            .ok_or_else(|| invalid("truncated message"))?;
+        *message = &message[len..];
+        Ok(bytes)
+    };
+    match key & 0x7 {
+        0 => Ok(Value::Varint(varint(message)?)),
+        1 => take(8).map(|_| Value::Fixed),
+        2 => {
+            let len = varint(message)? as usize;
+            let bytes = message
+                .get(..len)
+                .ok_or_else(|| invalid("truncated message"))?;
+            *message = &message[len..];
+            Ok(Value::Bytes(bytes))
+        }
+        5 => take(4).map(|_| Value::Fixed),
+        _ => Err(invalid("unsupported wire type")),
+    }
+}
+
+fn varint(message: &mut &[u8]) -> io::Result<u64> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = message
+            .split_first()
+            .ok_or_else(|| invalid("truncated message"))?;
+        *message = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(invalid("varint too long"))
+}
+
+fn put_varint(message: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        message.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    message.push(value as u8);
+}
+
+fn put_string(message: &mut Vec<u8>, field: u64, s: &str) {
+    if !s.is_empty() {
+        put_varint(message, field << 3 | 2);
+        put_varint(message, s.len() as u64);
+        message.extend(s.as_bytes());
+    }
+}
+
+/// A result as a `MediaResult` of the proto file.
+fn media_result(walk_result: &MediaWalkResult) -> Vec<u8> {
+    let (status, error) = match &walk_result.result {
+        Ok(true) => (0, None),
+        Ok(false) => (1, None),
+        Err(err) => (2, Some(err.to_string())),
+    };
+    let mut message = vec![];
+    put_string(&mut message, 1, &walk_result.path);
+    put_string(&mut message, 2, &walk_result.mime);
+    put_string(
+        &mut message,
+        3,
+        walk_result.kind.map_or("", |kind| kind.name()),
+    );
+    if status != 0 {
+        put_varint(&mut message, 4 << 3);
+        put_varint(&mut message, status);
+    }
+    put_string(&mut message, 5, error.as_deref().unwrap_or_default());
+    message
+}
+
+/// Percent-encodes a `grpc-message`, as gRPC has it.
+fn percent_encode(message: &str) -> String {
+    message
+        .bytes()
+        .map(|byte| match byte {
+            b' '..=b'~' if byte != b'%' => char::from(byte).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A client of the server, speaking just enough HTTP/2.
+    struct Client {
+        connection: Connection,
+    }
+
+    impl Client {
+        fn connect(addr: SocketAddr) -> Self {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write_all(PREFACE).unwrap();
+            let mut connection = Connection::new(stream).unwrap();
+            // A small window, for the server to wait for room.
+            let mut settings = SETTINGS_INITIAL_WINDOW_SIZE.to_be_bytes().to_vec();
+            settings.extend(100u32.to_be_bytes());
+            connection.write_frame(SETTINGS, 0, 0, &settings).unwrap();
+            Client { connection }
+        }
+
+        /// Makes a call, returning the messages and the trailers.
+        fn call(
+            &mut self,
+            stream: u32,
+            path: &str,
+            request: &[u8],
+        ) -> (Vec<Vec<u8>>, Vec<(String, String)>) {
+            let headers = [
+                (":method", "POST"),
+                (":scheme", "http"),
+                (":path", path),
+                (":authority", "localhost"),
+                ("content-type", "application/grpc"),
+                ("te", "trailers"),
+            ];
+            let connection = &mut self.connection;
+            connection.write_headers(stream, &headers, false).unwrap();
+            let mut body = vec![0];
+            body.extend((request.len() as u32).to_be_bytes());
+            body.extend(request);
+            connection
+                .write_frame(DATA, END_STREAM, stream, &body)
+                .unwrap();
+            let mut data = vec![];
+            loop {
+                let frame = connection.read_frame().unwrap().unwrap();
+                match frame.kind {
+                    DATA if frame.stream == stream => {
+                        data.extend(&frame.payload);
+                        let len = (frame.payload.len() as u32).to_be_bytes();
+                        connection.write_frame(WINDOW_UPDATE, 0, 0, &len).unwrap();
+                        connection
+                            .write_frame(WINDOW_UPDATE, 0, stream, &len)
+                            .unwrap();
+                    }
+                    HEADERS => {
+                        let headers = connection.header_block(&frame).unwrap();
+                        if frame.flags & END_STREAM != 0 {
+                            let mut messages = vec![];
+                            let mut data = &data[..];
+                            while let Some((prefix, rest)) = data.split_first_chunk::<5>() {
+                                let len = u32::from_be_bytes([
+                                    prefix[1], prefix[2], prefix[3], prefix[4],
+                                ]) as usize;
+                                messages.push(rest[..len].to_vec());
+                                data = &rest[len..];
+                            }
+                            return (messages, headers);
+                        }
+                    }
+                    SETTINGS if frame.flags & ACK == 0 => {
+                        connection.write_frame(SETTINGS, ACK, 0, &[]).unwrap();
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// The string fields of a message, by number.
+    fn strings(mut message: &[u8]) -> Vec<(u64, String)> {
+        let mut strings = vec![];
+        while !message.is_empty() {
+            let key = varint(&mut message).unwrap();
+            if let Value::Bytes(bytes) = field(&mut message, key).unwrap() {
+                strings.push((key >> 3, String::from_utf8(bytes.to_vec()).unwrap()));
+            }
+        }
+        strings
+    }
+
+    #[test]
+    fn it_serves_scans() {
+        let root = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/test");
+        let server = Server::bind(root, "127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || server.run());
+
+        let mut client = Client::connect(addr);
+        let (messages, trailers) = client.call(1, SCAN_PATH, &[]);
+        assert_eq!(messages.len(), 9);
+        assert!(trailers.contains(&("grpc-status".into(), "0".into())));
+        assert!(messages.iter().any(|message| {
+            let strings = strings(message);
+            strings[0].1.ends_with("/sample3.mp3")
+                && strings[1..] == [(2, "audio/mpeg".into()), (3, "audio".into())]
+        }));
+
+        let mut request = vec![];
+        put_string(&mut request, 1, "image");
+        put_varint(&mut request, 2 << 3);
+        put_varint(&mut request, 2);
+        put_string(&mut request, 3, "subdir");
+        put_string(&mut request, 4, "jpg");
+        let (messages, _) = client.call(3, SCAN_PATH, &request);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(strings(&messages[0])[1], (2, "image/jpeg".into()));
+
+        let mut request = vec![];
+        put_string(&mut request, 1, "sounds");
+        let (messages, trailers) = client.call(5, SCAN_PATH, &request);
+        assert!(messages.is_empty());
+        assert!(trailers.contains(&("grpc-status".into(), "3".into())));
+        assert!(trailers.contains(&("grpc-message".into(), "unknown kind: sounds".into())));
+
+        let (_, trailers) = client.call(7, "/mediawalker.MediaWalker/Nope", &[]);
+        assert!(trailers.contains(&("grpc-status".into(), "12".into())));
+    }
+}
@@ -0,0 +1,156 @@
+use crate::{MediaWalkResult, SessionId, WalkEvent, WalkStats};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// A condition to stop a walk on, see [`WalkHandle::stop_after`].
+pub(crate) type StopCondition = Box<dyn FnMut(&WalkStats) -> bool + Send>;
+
+/// A handle to a running walk. Iterate over it to receive the results,
+/// or use [`WalkHandle::events`] to receive everything that happens.
+pub struct WalkHandle {
+    rx: Receiver<WalkEvent>,
+    stats: Arc<Mutex<WalkStats>>,
+    stopped: Arc<AtomicBool>,
+    /// Checked by the walk as it reports each file.
+    stop_after: Arc<Mutex<Option<StopCondition>>>,
+    session_id: SessionId,
+}
+
+impl WalkHandle {
+    pub(crate) fn new(
+        rx: Receiver<WalkEvent>,
+        stats: Arc<Mutex<WalkStats>>,
+        stopped: Arc<AtomicBool>,
+        stop_after: Arc<Mutex<Option<StopCondition>>>,
+        session_id: SessionId,
+    ) -> Self {
+        WalkHandle {
+            rx,
+            stats,
+            stopped,
+            stop_after,
+            session_id,
+        }
+    }
+
+    /// The ID of the walk, which its results carry in
+    /// [`MediaWalkResult::session_id`], to tell them and the events
+    /// received through this handle from those of other walks.
+    pub fn session_id(&self) -> SessionId {
+        self.session_id
+    }
+
+    /// Returns a snapshot of the walk's statistics so far. Once the
+    /// results have been exhausted, these are the final statistics.
+    pub fn stats(&self) -> WalkStats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    /// Stops the walk. Results already on their way may still be
+    /// received.
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+
+    /// The statistics as they are kept by the walk.
+    pub(crate) fn shared_stats(&self) -> Arc<Mutex<WalkStats>> {
+        Arc::clone(&self.stats)
+    }
+
+    /// The flag that stops the walk, as set by [`stop`](Self::stop).
+    pub(crate) fn stop_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.stopped)
+    }
+
+    /// Stops the walk once `done` returns `true` for the statistics,
+    /// which it is given as each file is reported. The file that met the
+    /// condition is the last one reported. For example
+    /// `.stop_after(|stats| stats.matched >= 1000)`, or
+    /// `.stop_after(move |_| started.elapsed() > limit)`.
+    pub fn stop_after<F>(self, done: F) -> Self
+    where
+        F: FnMut(&WalkStats) -> bool + Send + 'static,
+    {
+        *self.stop_after.lock().unwrap() = Some(Box::new(done));
+        self
+    }
+
+    /// Returns an iterator over all the events of the walk, not just the
+    /// found files.
+    pub fn events(&mut self) -> Events<'_> {
+        Events { handle: self }
+    }
+
+    /// Converts the handle into a channel of results.
+    pub fn into_receiver(self) -> Receiver<MediaWalkResult> {
+        let (tx, rx) = mpsc::sync_channel(crate::walker::QUEUE_CAPACITY);
+        thread::spawn(move || {
+            for walk_result in self {
+                if tx.send(walk_result).is_err() {
+                    return;
+                }
+            }
+        });
+        rx
+    }
+}
+
+impl fmt::Debug for WalkHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WalkHandle")
+            .field("stats", &self.stats)
+            .field("stopped", &self.stopped)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Iterator for WalkHandle {
+    type Item = MediaWalkResult;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let WalkEvent::Found(walk_result) = self.rx.recv().ok()? {
+                return Some(walk_result);
+            }
+        }
+    }
+}
+
+/// An iterator over the events of a walk, see [`WalkHandle::events`].
+#[derive(Debug)]
+pub struct Events<'a> {
+    handle: &'a mut WalkHandle,
+}
+
+impl Iterator for Events<'_> {
+    type Item = WalkEvent;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.handle.rx.recv().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testutil::TempTree;
+    use crate::MediaWalker;
+    use std::time::Duration;
+
+    #[test]
+    fn it_stops_after_a_condition() {
+        let tree = TempTree::new("stop-after");
+        for i in 0..10 {
+            tree.sample("sample_640x426.jpg", &format!("{}.jpg", i));
+        }
+        let mut handle = MediaWalker::new(tree.path())
+            .throttle(Duration::from_millis(20))
+            .start()
+            .stop_after(|stats| stats.matched >= 3);
+        assert_eq!(handle.by_ref().count(), 3);
+        assert_eq!(handle.stats().matched, 3);
+    }
+}
@@ -0,0 +1,233 @@
+//! Telling screenshots and document scans from photos, see
+//! [`MediaWalker::media_hints`](crate::MediaWalker::media_hints).
+use crate::exif::{self, Exif};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// The resolutions of common displays, landscape: desktops and laptops,
+/// tablets, and phones.
+const SCREENS: &[(u32, u32)] = &[
+    (1280, 720),
+    (1280, 800),
+    (1366, 768),
+    (1440, 900),
+    (1536, 864),
+    (1600, 900),
+    (1680, 1050),
+    (1920, 1080),
+    (1920, 1200),
+    (2560, 1440),
+    (2560, 1600),
+    (2880, 1800),
+    (3024, 1964),
+    (3456, 2234),
+    (3840, 2160),
+    (5120, 2880),
+    (2048, 1536),
+    (2224, 1668),
+    (2360, 1640),
+    (2388, 1668),
+    (2732, 2048),
+    (1334, 750),
+    (1792, 828),
+    (2208, 1242),
+    (2436, 1125),
+    (2532, 1170),
+    (2556, 1179),
+    (2688, 1242),
+    (2778, 1284),
+    (2796, 1290),
+    (2340, 1080),
+    (2400, 1080),
+    (2960, 1440),
+    (3088, 1440),
+    (3200, 1440),
+];
+
+/// The aspect ratios of paper: ISO A sizes, US Letter and US Legal.
+const PAPERS: &[f64] = &[std::f64::consts::SQRT_2, 11.0 / 8.5, 14.0 / 8.5];
+
+/// How far an aspect ratio may be from a paper's, relatively.
+const PAPER_TOLERANCE: f64 = 0.01;
+
+/// The words screenshots are named with, lowercased: English, German,
+/// Dutch and Spanish.
+const SCREENSHOT_NAMES: &[&str] = &[
+    "screenshot",
+    "screen shot",
+    "screen_shot",
+    "bildschirmfoto",
+    "schermafbeelding",
+    "captura de pantalla",
+];
+
+/// The words scans are named with, lowercased, which may be followed
+/// by a number, like `scan0001`.
+const SCAN_NAMES: &[&str] = &["scan", "scanned", "camscanner"];
+
+/// What an image likely is other than a photo, see
+/// [`MediaWalkResult::hints`](crate::MediaWalkResult::hints).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MediaHint {
+    /// A screenshot: named like one, or a PNG at the resolution of a
+    /// common display without the EXIF of a camera.
+    Screenshot,
+    /// A scanned or photographed document: named like a scan, or of the
+    /// aspect ratio of a sheet of paper.
+    DocumentScan,
+}
+
+impl fmt::Display for MediaHint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            MediaHint::Screenshot => "screenshot",
+            MediaHint::DocumentScan => "document scan",
+        })
+    }
+}
+
+/// Tells what an image of a MIME type likely is, from its name, size
+/// and EXIF.
+pub(crate) fn hints(path: &Path, mime: &str) -> Vec<MediaHint> {
+    let name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let mut hints = vec![];
+    let size = dimensions(path, mime).ok().flatten();
+    let screen = size.is_some_and(|(width, height)| {
+        let landscape = (width.max(height), width.min(height));
+        SCREENS.contains(&landscape)
+    });
+    let camera = || exif::read(path, mime).is_some_and(|exif| exif != Exif::default());
+    if SCREENSHOT_NAMES.iter().any(|word| name.contains(word))
+        || (mime == "image/png" && screen && !camera())
+    {
+        hints.push(MediaHint::Screenshot);
+    }
+    let paper = size.is_some_and(|(width, height)| {
+        let ratio = f64::from(width.max(height)) / f64::from(width.min(height).max(1));
+        PAPERS
+            .iter()
+            .any(|paper| (ratio / paper - 1.0).abs() <= PAPER_TOLERANCE)
+    });
+    let scan_name = name
+        .split(|c: char| !c.is_alphanumeric())
+        .map(|word| word.trim_end_matches(|c: char| c.is_ascii_digit()))
+        .any(|word| SCAN_NAMES.contains(&word));
+    if scan_name || paper {
+        hints.push(MediaHint::DocumentScan);
+    }
+    hints
+}
+
+/// The width and height of a PNG or a JPEG, from its header.
+fn dimensions(path: &Path, mime: &str) -> io::Result<Option<(u32, u32)>> {
+    let mut file = File::open(path)?;
+    match mime {
+        "image/png" => {
+            // The signature, then the IHDR chunk's length and type.
+            let mut header = [0; 24];
+            file.read_exact(&mut header)?;
+            if &header[12..16] != b"IHDR" {
+                return Ok(None);
+            }
+            let width = u32::from_be_bytes([header[16], header[17], header[18], header[19]]);
+            let height = u32::from_be_bytes([header[20], header[21], header[22], header[23]]);
+            Ok(Some((width, height)))
+        }
+        "image/jpeg" => jpeg_dimensions(&mut file),
+        _ => Ok(None),
+    }
+}
+
+/// Reads the segments of a JPEG up to its first start of frame.
+fn jpeg_dimensions<R: Read + Seek>(reader: &mut R) -> io::Result<Option<(u32, u32)>> {
+    let mut marker = [0; 2];
+    reader.read_exact(&mut marker)?;
+    if marker != [0xff, 0xd8] {
+        return Err(io::ErrorKind::InvalidData.into());
+    }
+    loop {
+        reader.read_exact(&mut marker)?;
+        if marker[0] != 0xff {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+        match marker[1] {
+            // Padding, and markers without a length.
+            0xff | 0x01 | 0xd0..=0xd7 => continue,
+            // The image data starts, or the image ends.
+            0xda | 0xd9 => return Ok(None),
+            _ => {}
+        }
+        let mut len = [0; 2];
+        reader.read_exact(&mut len)?;
+        let len = u16::from_be_bytes(len).saturating_sub(2);
+        if matches!(marker[1], 0xc0..=0xcf if !matches!(marker[1], 0xc4 | 0xc8 | 0xcc)) {
+            // The sample precision, then the height and the width.
+            let mut frame = [0; 5];
+            reader.read_exact(&mut frame)?;
+            let height = u16::from_be_bytes([frame[1], frame[2]]);
+            let width = u16::from_be_bytes([frame[3], frame[4]]);
+            return Ok(Some((u32::from(width), u32::from(height))));
+        }
+        reader.seek(SeekFrom::Current(i64::from(len)))?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempTree;
+    use crate::MediaWalker;
+
+    /// The header of a PNG of a size, enough to be sniffed and measured.
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend(width.to_be_bytes());
+        png.extend(height.to_be_bytes());
+        png.extend([8, 6, 0, 0, 0, 0, 0, 0, 0]);
+        png
+    }
+
+    #[test]
+    fn it_hints_at_screenshots_and_scans() {
+        let tree = TempTree::new("hints");
+        tree.file("phone.png", &png(1179, 2556));
+        tree.file("Screenshot 2024-01-01.png", &png(640, 480));
+        tree.file("letter.png", &png(2550, 3300));
+        tree.file("scan_0001.png", &png(640, 480));
+        tree.file("icon.png", &png(512, 512));
+        tree.file("scandinavia.png", &png(512, 512));
+        tree.sample("sample_640x426.jpg", "photo.jpg");
+
+        let mut hints: Vec<_> = MediaWalker::new(tree.path())
+            .media_hints(true)
+            .start()
+            .map(|r| {
+                let name = Path::new(&r.path).file_name().unwrap().to_owned();
+                (name.to_string_lossy().into_owned(), r.hints)
+            })
+            .collect();
+        hints.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            hints,
+            vec![
+                (
+                    "Screenshot 2024-01-01.png".to_string(),
+                    vec![MediaHint::Screenshot]
+                ),
+                ("icon.png".to_string(), vec![]),
+                ("letter.png".to_string(), vec![MediaHint::DocumentScan]),
+                ("phone.png".to_string(), vec![MediaHint::Screenshot]),
+                ("photo.jpg".to_string(), vec![]),
+                ("scan_0001.png".to_string(), vec![MediaHint::DocumentScan]),
+                ("scandinavia.png".to_string(), vec![]),
+            ]
+        );
+        let hints = MediaWalker::new(tree.path()).start().map(|r| r.hints.len());
+        assert_eq!(hints.sum::<usize>(), 0);
+    }
+}
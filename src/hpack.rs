@@ -0,0 +1,406 @@
+//! HPACK, the header compression of HTTP/2, as RFC 7541 has it, for the
+//! gRPC server. Headers are decoded in full, with the dynamic table and
+//! Huffman-coded strings, and encoded as plain literals.
+use std::collections::VecDeque;
+use std::io;
+use std::sync::OnceLock;
+
+/// The largest dynamic table allowed, the default of HTTP/2, which the
+/// server does not raise.
+const MAX_TABLE_SIZE: usize = 4096;
+
+/// The static table, indexed from 1.
+const STATIC_TABLE: [(&str, &str); 61] = [
+    (":authority", ""),
+    (":method", "GET"),
+    (":method", "POST"),
+    (":path", "/"),
+    (":path", "/index.html"),
+    (":scheme", "http"),
+    (":scheme", "https"),
+    (":status", "200"),
+    (":status", "204"),
+    (":status", "206"),
+    (":status", "304"),
+    (":status", "400"),
+    (":status", "404"),
+    (":status", "500"),
+    ("accept-charset", ""),
+    ("accept-encoding", "gzip, deflate"),
+    ("accept-language", ""),
+    ("accept-ranges", ""),
+    ("accept", ""),
+    ("access-control-allow-origin", ""),
+    ("age", ""),
+    ("allow", ""),
+    ("authorization", ""),
+    ("cache-control", ""),
+    ("content-disposition", ""),
+    ("content-encoding", ""),
+    ("content-language", ""),
+    ("content-length", ""),
+    ("content-location", ""),
+    ("content-range", ""),
+    ("content-type", ""),
+    ("cookie", ""),
+    ("date", ""),
+    ("etag", ""),
+    ("expect", ""),
+    ("expires", ""),
+    ("from", ""),
+    ("host", ""),
+    ("if-match", ""),
+    ("if-modified-since", ""),
+    ("if-none-match", ""),
+    ("if-range", ""),
+    ("if-unmodified-since", ""),
+    ("last-modified", ""),
+    ("link", ""),
+    ("location", ""),
+    ("max-forwards", ""),
+    ("proxy-authenticate", ""),
+    ("proxy-authorization", ""),
+    ("range", ""),
+    ("referer", ""),
+    ("refresh", ""),
+    ("retry-after", ""),
+    ("server", ""),
+    ("set-cookie", ""),
+    ("strict-transport-security", ""),
+    ("transfer-encoding", ""),
+    ("user-agent", ""),
+    ("vary", ""),
+    ("via", ""),
+    ("www-authenticate", ""),
+];
+
+/// The length of the Huffman code of each byte, and of the end of string
+/// as the last. The code is canonical: the codes of a length follow each
+/// other in the order of their symbols, after those of the shorter
+/// lengths, so the lengths tell it all.
+const HUFFMAN_LENGTHS: [u8; 257] = [
+    13, 23, 28, 28, 28, 28, 28, 28, 28, 24, 30, 28, 28, 30, 28, 28, 28, 28, 28, 28, 28, 28, 30, 28,
+    28, 28, 28, 28, 28, 28, 28, 28, 6, 10, 10, 12, 13, 6, 8, 11, 10, 10, 8, 11, 8, 6, 6, 6, 5, 5,
+    5, 6, 6, 6, 6, 6, 6, 6, 7, 8, 15, 6, 12, 10, 13, 6, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7,
+    7, 7, 7, 7, 7, 7, 7, 7, 8, 7, 8, 13, 19, 13, 14, 6, 15, 5, 6, 5, 6, 5, 6, 6, 6, 5, 7, 7, 6, 6,
+    6, 5, 6, 7, 6, 5, 5, 6, 7, 7, 7, 7, 7, 15, 11, 14, 13, 28, 20, 22, 20, 20, 22, 22, 22, 23, 22,
+    23, 23, 23, 23, 23, 24, 23, 24, 24, 22, 23, 24, 23, 23, 23, 23, 21, 22, 23, 22, 23, 23, 24, 22,
+    21, 20, 22, 22, 23, 23, 21, 23, 22, 22, 24, 21, 22, 23, 23, 21, 21, 22, 21, 23, 22, 23, 23, 20,
+    22, 22, 22, 23, 22, 22, 23, 26, 26, 20, 19, 22, 23, 22, 25, 26, 26, 26, 27, 27, 26, 24, 25, 19,
+    21, 26, 27, 27, 26, 27, 24, 21, 21, 26, 26, 28, 27, 27, 27, 20, 24, 20, 21, 22, 21, 21, 23, 22,
+    22, 25, 25, 24, 24, 26, 23, 26, 27, 26, 26, 27, 27, 27, 27, 27, 28, 27, 27, 27, 27, 27, 26, 30,
+];
+
+/// The longest Huffman code.
+const MAX_CODE_LENGTH: usize = 30;
+
+/// The end of string, which must not be coded.
+const EOS: u16 = 256;
+
+/// The canonical Huffman code, as its decoder needs it.
+struct Huffman {
+    /// The first code of each length.
+    first: [u32; MAX_CODE_LENGTH + 1],
+    /// How many codes there are of each length.
+    count: [u32; MAX_CODE_LENGTH + 1],
+    /// Where the symbols of each length start in `symbols`.
+    offset: [usize; MAX_CODE_LENGTH + 1],
+    /// The symbols, by the length of their code, then by value.
+    symbols: Vec<u16>,
+}
+
+fn huffman() -> &'static Huffman {
+    static HUFFMAN: OnceLock<Huffman> = OnceLock::new();
+    HUFFMAN.get_or_init(|| {
+        let mut count = [0; MAX_CODE_LENGTH + 1];
+        for len in HUFFMAN_LENGTHS {
+            count[usize::from(len)] += 1;
+        }
+        let mut first = [0; MAX_CODE_LENGTH + 1];
+        let mut offset = [0; MAX_CODE_LENGTH + 1];
+        let mut code = 0;
+        let mut start = 0;
+        for len in 1..=MAX_CODE_LENGTH {
+            first[len] = code;
+            offset[len] = start;
+            code = (code + count[len]) << 1;
+            start += count[len] as usize;
+        }
+        let mut symbols: Vec<u16> = (0..=EOS).collect();
+        symbols.sort_by_key(|symbol| HUFFMAN_LENGTHS[usize::from(*symbol)]);
+        Huffman {
+            first,
+            count,
+            offset,
+            symbols,
+        }
+    })
+}
+
+/// Decodes a Huffman-coded string.
+fn huffman_decode(coded: &[u8]) -> io::Result<Vec<u8>> {
+    let huffman = huffman();
+    let mut decoded = vec![];
+    let mut code = 0u32;
+    let mut len = 0;
+    for byte in coded {
+        for bit in (0..8).rev() {
+            code = code << 1 | u32::from(byte >> bit & 1);
+            len += 1;
+            if len > MAX_CODE_LENGTH {
+                return Err(invalid("bad Huffman code"));
+            }
+            let Some(index) = code.checked_sub(huffman.first[len]) else {
+                continue;
+            };
+            if index < huffman.count[len] {
+                let symbol = huffman.symbols[huffman.offset[len] + index as usize];
+                if symbol == EOS {
+                    return Err(invalid("Huffman-coded end of string"));
+                }
+                decoded.push(symbol as u8);
+                code = 0;
+                len = 0;
+            }
+        }
+    }
+    // The padding is the start of the end of string: all ones, shorter
+    // than a byte.
+    if len > 7 || code != (1 << len) - 1 {
+        return Err(invalid("bad Huffman padding"));
+    }
+    Ok(decoded)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Decodes the header blocks of a connection, keeping its dynamic table.
+pub(crate) struct Decoder {
+    /// The dynamic table, newest first.
+    table: VecDeque<(String, String)>,
+    size: usize,
+    max_size: usize,
+}
+
+impl Decoder {
+    pub(crate) fn new() -> Self {
+        Decoder {
+            table: VecDeque::new(),
+            size: 0,
+            max_size: MAX_TABLE_SIZE,
+        }
+    }
+
+    /// Decodes a header block into its names and values.
+    pub(crate) fn decode(&mut self, block: &[u8]) -> io::Result<Vec<(String, String)>> {
+        let mut headers = vec![];
+        let mut pos = 0;
+        while let Some(&first) = block.get(pos) {
+            if first & 0x80 != 0 {
+                // An indexed field.
+                let index = integer(block, &mut pos, 7)?;
+                headers.push(self.entry(index)?);
+            } else if first & 0xe0 == 0x20 {
+                // A dynamic table size update.
+                let size = integer(block, &mut pos, 5)?;
+                if size > MAX_TABLE_SIZE {
+                    return Err(invalid("dynamic table too large"));
+                }
+                self.max_size = size;
+                self.evict(0);
+            } else {
+                // A literal, added to the table or not.
+                let indexed = first & 0x40 != 0;
+                let index = integer(block, &mut pos, if indexed { 6 } else { 4 })?;
+                let name = match index {
+                    0 => string(block, &mut pos)?,
+                    index => self.entry(index)?.0,
+                };
+                let value = string(block, &mut pos)?;
+                if indexed {
+                    self.insert(name.clone(), value.clone());
+                }
+                headers.push((name, value));
+            }
+        }
+        Ok(headers)
+    }
+
+    /// The field at an index of the static table, then the dynamic one.
+    fn entry(&self, index: usize) -> io::Result<(String, String)> {
+        let (name, value) = match index {
+            0 => None,
+            1..=61 => Some(STATIC_TABLE[index - 1]),
+            _ => self
+                .table
+                .get(index - 62)
+                .map(|(name, value)| (name.as_str(), value.as_str())),
+        }
+        .ok_or_else(|| invalid("bad header index"))?;
+        Ok((name.to_string(), value.to_string()))
+    }
+
+    fn insert(&mut self, name: String, value: String) {
+        let size = name.len() + value.len() + 32;
+        self.evict(size);
+        if size <= self.max_size {
+            self.size += size;
+            self.table.push_front((name, value));
+        }
+    }
+
+    /// Evicts the oldest fields until `room` more bytes fit.
+    fn evict(&mut self, room: usize) {
+        while self.size + room > self.max_size {
+            let Some((name, value)) = self.table.pop_back() else {
+                break;
+            };
+            self.size -= name.len() + value.len() + 32;
+        }
+    }
+}
+
+/// Decodes an integer with a prefix of `bits` bits.
+fn integer(block: &[u8], pos: &mut usize, bits: u32) -> io::Result<usize> {
+    let truncated = || invalid("truncated header block");
+    let mask = (1 << bits) - 1;
+    let mut value = usize::from(*block.get(*pos).ok_or_else(truncated)? & mask);
+    *pos += 1;
+    if value < usize::from(mask) {
+        return Ok(value);
+    }
+    let mut shift = 0;
+    loop {
+        let byte = *block.get(*pos).ok_or_else(truncated)?;
+        *pos += 1;
+        if shift > 28 {
+            return Err(invalid("header integer too large"));
+        }
+        value += usize::from(byte & 0x7f) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+}
+
+/// Decodes a string literal, Huffman-coded or not.
+fn string(block: &[u8], pos: &mut usize) -> io::Result<String> {
+    let huffman = block.get(*pos).is_some_and(|first| first & 0x80 != 0);
+    let len = integer(block, pos, 7)?;
+    let bytes = block
+        .get(*pos..*pos + len)
+        .ok_or_else(|| invalid("truncated header block"))?;
+    *pos += len;
+    let bytes = if huffman {
+        huffman_decode(bytes)?
+    } else {
+        bytes.to_vec()
+    };
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Encodes headers as literals, neither indexed nor Huffman-coded.
+pub(crate) fn encode(headers: &[(&str, &str)]) -> Vec<u8> {
+    let mut block = vec![];
+    for (name, value) in headers {
+        block.push(0);
+        for s in [name, value] {
+            put_integer(&mut block, s.len(), 7, 0);
+            block.extend(s.as_bytes());
+        }
+    }
+    block
+}
+
+/// Encodes an integer with a prefix of `bits` bits, after `flags`.
+fn put_integer(block: &mut Vec<u8>, mut value: usize, bits: u32, flags: u8) {
+    let mask = (1 << bits) - 1;
+    if value < mask {
+        block.push(flags | value as u8);
+        return;
+    }
+    block.push(flags | mask as u8);
+    value -= mask;
+    while value >= 0x80 {
+        block.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    block.push(value as u8);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(s: &str) -> Vec<u8> {
+        let s: String = s.split_whitespace().collect();
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn it_decodes_the_examples_of_the_rfc() {
+        // The requests of RFC 7541, C.4, with Huffman coding.
+        let mut decoder = Decoder::new();
+        let headers = |headers: &[(&str, &str)]| {
+            headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            decoder
+                .decode(&hex("8286 8441 8cf1 e3c2 e5f2 3a6b a0ab 90f4 ff"))
+                .unwrap(),
+            headers(&[
+                (":method", "GET"),
+                (":scheme", "http"),
+                (":path", "/"),
+                (":authority", "www.example.com"),
+            ])
+        );
+        assert_eq!(
+            decoder
+                .decode(&hex("8286 84be 5886 a8eb 1064 9cbf"))
+                .unwrap(),
+            headers(&[
+                (":method", "GET"),
+                (":scheme", "http"),
+                (":path", "/"),
+                (":authority", "www.example.com"),
+                ("cache-control", "no-cache"),
+            ])
+        );
+        assert_eq!(
+            decoder
+                .decode(&hex(
+                    "8287 85bf 4088 25a8 49e9 5ba9 7d7f 8925 a849 e95b b8e8 b4bf"
+                ))
+                .unwrap(),
+            headers(&[
+                (":method", "GET"),
+                (":scheme", "https"),
+                (":path", "/index.html"),
+                (":authority", "www.example.com"),
+                ("custom-key", "custom-value"),
+            ])
+        );
+        assert_eq!(decoder.size, 164);
+
+        // A padding of zeros, and an index past the tables.
+        assert!(huffman_decode(&hex("f1e3 c2e5 f23a 6ba0 ab90 f400")).is_err());
+        assert!(Decoder::new().decode(&[0xbe]).is_err());
+
+        let long = "x".repeat(300);
+        let block = encode(&[(":status", "200"), ("grpc-message", &long)]);
+        assert_eq!(
+            Decoder::new().decode(&block).unwrap(),
+            headers(&[(":status", "200"), ("grpc-message", &long)])
+        );
+    }
+}
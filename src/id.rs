@@ -0,0 +1,120 @@
+use std::fs::{self, File, Metadata};
+use std::io::{self, Read};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::UNIX_EPOCH;
+
+/// How to compute the stable IDs of found files, see
+/// [`MediaWalker::ids`](crate::MediaWalker::ids).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IdStrategy {
+    /// From the device, inode, size and modification time: cheap, and
+    /// kept when a file is renamed or moved within its filesystem. Falls
+    /// back to the path, size and modification time off Unix.
+    Inode,
+    /// From the size and the whole content: kept across copies and
+    /// filesystems, at the cost of reading every file through.
+    Content,
+}
+
+impl IdStrategy {
+    /// Computes the ID of a file. `metadata` is read if not given.
+    pub(crate) fn id(self, path: &Path, metadata: Option<&Metadata>) -> io::Result<u64> {
+        let read;
+        let metadata = match metadata {
+            Some(metadata) => metadata,
+            None => {
+                read = fs::metadata(path)?;
+                &read
+            }
+        };
+        let mut hasher = Fnv::new();
+        hasher.write(&metadata.len().to_le_bytes());
+        match self {
+            IdStrategy::Inode => {
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::MetadataExt;
+                    hasher.write(&metadata.dev().to_le_bytes());
+                    hasher.write(&metadata.ino().to_le_bytes());
+                }
+                #[cfg(not(unix))]
+                hasher.write(path.to_string_lossy().as_bytes());
+                let modified = metadata
+                    .modified()
+                    .ok()
+                    .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                    .map_or(0, |modified| modified.as_nanos());
+                hasher.write(&modified.to_le_bytes());
+            }
+            IdStrategy::Content => {
+                let mut file = File::open(path)?;
+                let mut buffer = vec![0; 64 * 1024];
+                loop {
+                    match file.read(&mut buffer)? {
+                        0 => break,
+                        n => hasher.write(&buffer[..n]),
+                    }
+                }
+            }
+        }
+        Ok(hasher.0)
+    }
+}
+
+impl FromStr for IdStrategy {
+    type Err = String;
+
+    /// Parses `inode` or `content`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "inode" => Ok(IdStrategy::Inode),
+            "content" => Ok(IdStrategy::Content),
+            _ => Err(format!("unknown ID strategy: {}", s)),
+        }
+    }
+}
+
+/// The 64-bit FNV-1a hash, which unlike the standard library's hashers
+/// is the same from release to release.
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Self {
+        Fnv(0xcbf29ce484222325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempTree;
+
+    #[test]
+    fn it_keeps_ids_across_renames() {
+        let mut hasher = Fnv::new();
+        hasher.write(b"a");
+        assert_eq!(hasher.0, 0xaf63dc4c8601ec8c);
+
+        let tree = TempTree::new("ids");
+        let a = tree.sample("sample_640x426.jpg", "a.jpg");
+        let b = tree.sample("sample_640x426.jpg", "b.jpg");
+        let inode = IdStrategy::Inode.id(&a, None).unwrap();
+        let content = IdStrategy::Content.id(&a, None).unwrap();
+        assert_eq!(IdStrategy::Content.id(&b, None).unwrap(), content);
+        assert_ne!(IdStrategy::Inode.id(&b, None).unwrap(), inode);
+
+        let moved = tree.path().join("moved.jpg");
+        fs::rename(&a, &moved).unwrap();
+        assert_eq!(IdStrategy::Inode.id(&moved, None).unwrap(), inode);
+        assert_eq!(IdStrategy::Content.id(&moved, None).unwrap(), content);
+        assert_eq!("Content".parse(), Ok(IdStrategy::Content));
+    }
+}
@@ -0,0 +1,698 @@
+//! A SQLite index of the results of a walk, behind the `index` feature,
+//! for answering library questions without walking again.
+//!
+//! The database is kept with the `sqlite3` program, or the one named by
+//! the `SQLITE3` environment variable, so that no SQLite library is
+//! linked in. It has a single table:
+//!
+//! ```sql
+//! CREATE TABLE files (
+//!     path TEXT PRIMARY KEY,
+//!     dir TEXT NOT NULL,      -- The directory the file is in.
+//!     mime TEXT NOT NULL,     -- Empty if the type is not known.
+//!     kind TEXT,              -- A Kind name, like 'video'.
+//!     size INTEGER,           -- In bytes.
+//!     modified INTEGER,       -- In nanoseconds since the Unix epoch.
+//!     id INTEGER              -- See MediaWalker::ids.
+//! );
+//! ```
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use mediawalker::{index, IdStrategy, Kind, MediaWalker};
+//! let results = MediaWalker::new("/mnt/nas").ids(IdStrategy::Content).start();
+//! index::write("library.db", results)?;
+//! let library = index::open("library.db")?;
+//! println!("{} videos", library.by_kind(Kind::Video)?.len());
+//! for copies in library.duplicates()? {
+//!     println!("{} copies of {}", copies.len(), copies[0].path);
+//! }
+//! # Ok::<(), std::io::Error>(())
+//! ```
+#[cfg(all(feature = "watch", target_os = "linux"))]
+pub(crate) use indexer::apply;
+#[cfg(all(feature = "watch", target_os = "linux"))]
+pub use indexer::{IndexChange, Indexer};
+
+use crate::{Kind, MediaWalkResult};
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf, MAIN_SEPARATOR};
+use std::process::{Command, Stdio};
+use std::time::{Duration, SystemTime};
+
+/// The version of the table, kept as the database's `user_version`.
+pub const SCHEMA_VERSION: u32 = 1;
+
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS files (
+    path TEXT PRIMARY KEY,
+    dir TEXT NOT NULL,
+    mime TEXT NOT NULL,
+    kind TEXT,
+    size INTEGER,
+    modified INTEGER,
+    id INTEGER
+);
+CREATE INDEX IF NOT EXISTS files_dir ON files (dir);
+CREATE INDEX IF NOT EXISTS files_kind ON files (kind);
+CREATE INDEX IF NOT EXISTS files_mime ON files (mime);
+CREATE INDEX IF NOT EXISTS files_copies ON files (size, id);
+";
+
+/// The columns a query reads, in the order [`IndexedFile::parse`] takes
+/// them.
+fn columns() -> String {
+    format!("{}, mime, kind, size, modified, id", escaped("path"))
+}
+
+/// How long to wait for a database another process is writing, in
+/// milliseconds.
+const BUSY_TIMEOUT: u32 = 10_000;
+
+/// Separate the fields and the rows of `.mode ascii` output. Paths may
+/// hold them too, so they are read [`escaped`], with `ESCAPE` and the
+/// two hexadecimal digits of the character.
+const FIELD: char = '\x1f';
+const ROW: char = '\x1e';
+const ESCAPE: char = '%';
+
+/// A file in an [`Index`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexedFile {
+    /// The path of the file, as reported by the walk.
+    pub path: String,
+    /// The file's MIME type, empty if it is not known.
+    pub mime: String,
+    /// The file's category, `None` if the media type is not known.
+    pub kind: Option<Kind>,
+    /// The file's size, in bytes.
+    pub size: Option<u64>,
+    /// When the file was last modified.
+    pub modified: Option<SystemTime>,
+    /// The file's stable ID, if the walk computed them.
+    pub id: Option<u64>,
+}
+
+impl IndexedFile {
+    fn parse(row: Vec<String>) -> Option<IndexedFile> {
+        let mut fields = row.into_iter();
+        let mut next = || fields.next().filter(|field| !field.is_empty());
+        let path = next()?;
+        let mime = next().unwrap_or_default();
+        let kind = next().and_then(|kind| kind.parse().ok());
+        let size = next().and_then(|size| size.parse().ok());
+        let modified = next()
+            .and_then(|nanos| nanos.parse().ok())
+            .and_then(from_nanos);
+        // IDs are kept as SQLite's signed integers.
+        let id = next()
+            .and_then(|id| id.parse::<i64>().ok())
+            .map(|id| id as u64);
+        Some(IndexedFile {
+            path,
+            mime,
+            kind,
+            size,
+            modified,
+            id,
+        })
+    }
+}
+
+/// Writes the results of a walk to a database, creating it if need be,
+/// and replacing the files indexed before. Failed results are left
+/// out. Returns how many files were indexed.
+pub fn write<P, I>(db: P, results: I) -> io::Result<usize>
+where
+    P: AsRef<Path>,
+    I: IntoIterator<Item = MediaWalkResult>,
+{
+    let mut count = 0;
+    run(db.as_ref(), |sql| {
+        write!(sql, "{}BEGIN;\nDELETE FROM files;\n", schema())?;
+        for walk_result in results {
+            if walk_result.result.is_ok() {
+                upsert(sql, &walk_result)?;
+                count += 1;
+            }
+        }
+        sql.write_all(b"COMMIT;\n")
+    })?;
+    Ok(count)
+}
+
+/// Writes the results of a walk to a database, adding the files not
+/// indexed yet and updating the others. Returns how many files were
+/// written.
+pub(crate) fn update<I>(db: &Path, results: I) -> io::Result<usize>
+where
+    I: IntoIterator<Item = MediaWalkResult>,
+{
+    let mut count = 0;
+    run(db, |sql| {
+        writeln!(sql, "{}BEGIN;", schema())?;
+        for walk_result in results {
+            if walk_result.result.is_ok() {
+                upsert(sql, &walk_result)?;
+                count += 1;
+            }
+        }
+        sql.write_all(b"COMMIT;\n")
+    })?;
+    Ok(count)
+}
+
+/// Removes the files that are gone from a database. Returns how many
+/// were removed.
+pub(crate) fn prune(db: &Path) -> io::Result<usize> {
+    let output = run(db, |sql| {
+        writeln!(sql, ".mode ascii\nSELECT {} FROM files;", escaped("path"))
+    })?;
+    let gone: Vec<String> = rows(&output)
+        .filter_map(|row| row.into_iter().next())
+        .filter(|path| {
+            fs::symlink_metadata(path).is_err_and(|err| err.kind() == io::ErrorKind::NotFound)
+        })
+        .collect();
+    if !gone.is_empty() {
+        run(db, |sql| {
+            sql.write_all(b"BEGIN;\n")?;
+            for path in &gone {
+                writeln!(sql, "DELETE FROM files WHERE path = {};", quote(path))?;
+            }
+            sql.write_all(b"COMMIT;\n")
+        })?;
+    }
+    Ok(gone.len())
+}
+
+/// Opens a database written by [`write()`].
+pub fn open<P: AsRef<Path>>(db: P) -> io::Result<Index> {
+    let index = Index {
+        db: db.as_ref().to_path_buf(),
+    };
+    // sqlite3 would create a missing database.
+    fs::metadata(&index.db)?;
+    index.query("1 = 0")?;
+    Ok(index)
+}
+
+/// A database of found files, see the [module docs](self).
+#[derive(Debug, Clone)]
+pub struct Index {
+    db: PathBuf,
+}
+
+impl Index {
+    /// The files of a kind, in the order of their paths.
+    pub fn by_kind(&self, kind: Kind) -> io::Result<Vec<IndexedFile>> {
+        self.query(&format!("kind = {}", quote(kind.name())))
+    }
+
+    /// The files of a MIME type, in the order of their paths. The type
+    /// may be a glob pattern, like `image/*`.
+    pub fn by_mime(&self, mime: &str) -> io::Result<Vec<IndexedFile>> {
+        self.query(&format!("mime GLOB {}", quote(mime)))
+    }
+
+    /// The files in a directory and below it, in the order of their
+    /// paths.
+    pub fn in_dir<P: AsRef<Path>>(&self, dir: P) -> io::Result<Vec<IndexedFile>> {
+        self.query(&below(&dir.as_ref().to_string_lossy()))
+    }
+
+    /// The files with copies: groups of files of the same size and ID,
+    /// largest first. With [`IdStrategy::Content`](crate::IdStrategy::Content)
+    /// they have the same bytes; files indexed without IDs are left out.
+    pub fn duplicates(&self) -> io::Result<Vec<Vec<IndexedFile>>> {
+        let files = self.select(
+            "id IS NOT NULL AND (size, id) IN (SELECT size, id FROM files \
+             WHERE id IS NOT NULL GROUP BY size, id HAVING count(*) > 1)",
+            "size DESC, id, path",
+        )?;
+        let mut groups: Vec<Vec<IndexedFile>> = vec![];
+        for file in files {
+            match groups.last_mut() {
+                Some(group) if (group[0].size, group[0].id) == (file.size, file.id) => {
+                    group.push(file)
+                }
+                _ => groups.push(vec![file]),
+            }
+        }
+        Ok(groups)
+    }
+
+    /// The files matching an SQL condition, in the order of their paths.
+    fn query(&self, condition: &str) -> io::Result<Vec<IndexedFile>> {
+        self.select(condition, "path")
+    }
+
+    fn select(&self, condition: &str, order: &str) -> io::Result<Vec<IndexedFile>> {
+        let output = run(&self.db, |sql| {
+            write!(
+                sql,
+                ".mode ascii\nSELECT {} FROM files WHERE {} ORDER BY {};\n",
+                columns(),
+                condition,
+                order
+            )
+        })?;
+        Ok(rows(&output).filter_map(IndexedFile::parse).collect())
+    }
+}
+
+/// The statements creating the table, if it is not there yet.
+pub(crate) fn schema() -> String {
+    format!("{}PRAGMA user_version = {};\n", SCHEMA, SCHEMA_VERSION)
+}
+
+/// Writes the statement adding a result to the database, or updating it.
+pub(crate) fn upsert<W: Write + ?Sized>(
+    sql: &mut W,
+    walk_result: &MediaWalkResult,
+) -> io::Result<()> {
+    let path = Path::new(&walk_result.path);
+    let metadata = fs::metadata(walk_result.raw_path.as_deref().unwrap_or(path)).ok();
+    let dir = path.parent().unwrap_or(Path::new("")).to_string_lossy();
+    let modified = metadata
+        .as_ref()
+        .and_then(|metadata| metadata.modified().ok())
+        .and_then(nanos);
+    writeln!(
+        sql,
+        "INSERT OR REPLACE INTO files VALUES ({}, {}, {}, {}, {}, {}, {});",
+        quote(&walk_result.path),
+        quote(&dir),
+        quote(&walk_result.mime),
+        walk_result
+            .kind
+            .map_or("NULL".to_string(), |kind| quote(kind.name())),
+        number(metadata.map(|metadata| metadata.len() as i64)),
+        number(modified),
+        number(walk_result.id.map(|id| id as i64))
+    )
+}
+
+/// A time in nanoseconds since the Unix epoch.
+pub(crate) fn nanos(time: SystemTime) -> Option<i64> {
+    match time.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(since) => i64::try_from(since.as_nanos()).ok(),
+        Err(before) => i64::try_from(before.duration().as_nanos()).ok().map(|n| -n),
+    }
+}
+
+/// A time from nanoseconds since the Unix epoch.
+pub(crate) fn from_nanos(nanos: i64) -> Option<SystemTime> {
+    let since = Duration::from_nanos(nanos.unsigned_abs());
+    if nanos >= 0 {
+        SystemTime::UNIX_EPOCH.checked_add(since)
+    } else {
+        SystemTime::UNIX_EPOCH.checked_sub(since)
+    }
+}
+
+/// An integer as an SQL literal, `NULL` if there is none.
+pub(crate) fn number(n: Option<i64>) -> String {
+    n.map_or("NULL".to_string(), |n| n.to_string())
+}
+
+/// The rows of `.mode ascii` output, split into their fields, with the
+/// [`escaped`] ones unescaped.
+pub(crate) fn rows(output: &str) -> impl Iterator<Item = Vec<String>> + '_ {
+    output
+        .split(ROW)
+        .filter(|row| !row.is_empty())
+        .map(|row| row.split(FIELD).map(unescape).collect())
+}
+
+/// A text column as it is selected for `.mode ascii` output, with the
+/// separators of the output and [`ESCAPE`] escaped.
+pub(crate) fn escaped(column: &str) -> String {
+    [ESCAPE, FIELD, ROW]
+        .iter()
+        .fold(column.to_string(), |sql, &c| {
+            format!(
+                "replace({}, char({}), '{}{:02X}')",
+                sql, c as u32, ESCAPE, c as u32
+            )
+        })
+}
+
+/// A field of `.mode ascii` output, unescaped. Fields not [`escaped`]
+/// have nothing to unescape.
+fn unescape(field: &str) -> String {
+    let mut unescaped = String::with_capacity(field.len());
+    let mut rest = field;
+    while let Some(at) = rest.find(ESCAPE) {
+        unescaped.push_str(&rest[..at]);
+        let code = rest.get(at + 1..at + 3);
+        match code.and_then(|code| u8::from_str_radix(code, 16).ok()) {
+            Some(code) => {
+                unescaped.push(char::from(code));
+                rest = &rest[at + 3..];
+            }
+            None => {
+                unescaped.push(ESCAPE);
+                rest = &rest[at + 1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+/// Runs the SQL written by `input` on a database with `sqlite3`, and
+/// returns what it prints. Stops at the first failing statement.
+pub(crate) fn run<F>(db: &Path, input: F) -> io::Result<String>
+where
+    F: FnOnce(&mut dyn Write) -> io::Result<()>,
+{
+    let sqlite3 = env::var_os("SQLITE3").unwrap_or_else(|| "sqlite3".into());
+    let mut child = Command::new(sqlite3)
+        .arg("-batch")
+        .arg("-bail")
+        .arg(db)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut stdin = io::BufWriter::new(child.stdin.take().expect("piped stdin"));
+    let written = writeln!(stdin, ".timeout {}", BUSY_TIMEOUT)
+        .and_then(|()| input(&mut stdin))
+        .and_then(|()| stdin.flush());
+    drop(stdin);
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr);
+        return Err(io::Error::other(format!("sqlite3: {}", message.trim())));
+    }
+    written?;
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(all(feature = "watch", target_os = "linux"))]
+mod indexer {
+    use super::{below, quote, run, upsert, write};
+    use crate::walker::QUEUE_CAPACITY;
+    use crate::{MediaWalker, WatchEvent};
+    use std::io::{self, Write};
+    use std::path::Path;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::mpsc::{self, Receiver, SyncSender};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    /// How often the indexing thread looks for having been stopped.
+    const POLL: Duration = Duration::from_millis(100);
+
+    /// How many changes are written to the database at a time.
+    const BATCH: usize = 1024;
+
+    /// A change to the database of an [`Indexer`].
+    #[derive(Debug)]
+    pub enum IndexChange {
+        /// The whole tree was walked and indexed anew, with how many
+        /// files: when the indexer starts, and when changes were lost.
+        Reindexed(usize),
+        /// A file was added to the database.
+        Added(String),
+        /// A file in the database was updated.
+        Updated(String),
+        /// A file or a directory was removed from the database, with
+        /// whatever was under it.
+        Removed(String),
+        /// The database could not be written, and misses the changes
+        /// being written until the tree is indexed anew.
+        Failed(io::Error),
+    }
+
+    /// Keeps a database, see the [module docs](super), consistent with
+    /// a tree on Linux, behind the `watch` feature as well: the tree is
+    /// watched, walked and indexed, and the files coming and going are
+    /// then written as they do. Iterate over it to receive the changes;
+    /// they hold the indexing back until they are received. Dropping it
+    /// stops the indexing.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use mediawalker::index::{IndexChange, Indexer};
+    /// for change in Indexer::run("/mnt/nas", "library.db")? {
+    ///     if let IndexChange::Added(path) = change {
+    ///         println!("New: {}", path);
+    ///     }
+    /// }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[derive(Debug)]
+    pub struct Indexer {
+        rx: Receiver<IndexChange>,
+        stopped: Arc<AtomicBool>,
+    }
+
+    impl Indexer {
+        /// Starts indexing a tree with the default options of a
+        /// [`MediaWalker`].
+        pub fn run<P: AsRef<Path>, Q: AsRef<Path>>(root: P, db: Q) -> io::Result<Indexer> {
+            Indexer::run_with(MediaWalker::new(root), db)
+        }
+
+        /// Starts indexing the tree of a walker, with its options. Fails
+        /// if the tree cannot be watched, see [`MediaWalker::watch`].
+        pub fn run_with<Q: AsRef<Path>>(walker: MediaWalker, db: Q) -> io::Result<Indexer> {
+            let db = db.as_ref().to_path_buf();
+            // Watching first, so that nothing changing during the walk
+            // is missed.
+            let watcher = walker.clone().watch()?;
+            let (tx, rx) = mpsc::sync_channel(QUEUE_CAPACITY);
+            let stopped = Arc::new(AtomicBool::new(false));
+            let indexing = Arc::clone(&stopped);
+            thread::spawn(move || {
+                let reindex = |tx: &SyncSender<IndexChange>| {
+                    let change = match write(&db, walker.clone().start()) {
+                        Ok(count) => IndexChange::Reindexed(count),
+                        Err(err) => IndexChange::Failed(err),
+                    };
+                    tx.send(change).is_ok()
+                };
+                if !reindex(&tx) {
+                    return;
+                }
+                while !indexing.load(Ordering::Relaxed) {
+                    let Some(event) = watcher.next_timeout(POLL) else {
+                        continue;
+                    };
+                    let mut events = vec![event];
+                    while events.len() < BATCH {
+                        match watcher.next_timeout(Duration::ZERO) {
+                            Some(event) => events.push(event),
+                            None => break,
+                        }
+                    }
+                    let sent = if events
+                        .iter()
+                        .any(|event| matches!(event, WatchEvent::Overflowed))
+                    {
+                        reindex(&tx)
+                    } else {
+                        apply(&db, events)
+                            .unwrap_or_else(|err| vec![IndexChange::Failed(err)])
+                            .into_iter()
+                            .all(|change| tx.send(change).is_ok())
+                    };
+                    if !sent {
+                        return;
+                    }
+                }
+            });
+            Ok(Indexer { rx, stopped })
+        }
+
+        /// Waits up to `timeout` for the next change. `None` if there
+        /// was none, or the indexing has ended.
+        pub fn next_timeout(&self, timeout: Duration) -> Option<IndexChange> {
+            self.rx.recv_timeout(timeout).ok()
+        }
+
+        /// Stops the indexing. Changes already on their way may still be
+        /// received.
+        pub fn stop(&self) {
+            self.stopped.store(true, Ordering::Relaxed);
+        }
+    }
+
+    impl Iterator for Indexer {
+        type Item = IndexChange;
+
+        fn next(&mut self) -> Option<IndexChange> {
+            self.rx.recv().ok()
+        }
+    }
+
+    impl Drop for Indexer {
+        fn drop(&mut self) {
+            self.stop();
+        }
+    }
+
+    /// Writes changes to the database in a single transaction.
+    pub(crate) fn apply(db: &Path, events: Vec<WatchEvent>) -> io::Result<Vec<IndexChange>> {
+        let mut changes = vec![];
+        run(db, |sql| {
+            sql.write_all(b"BEGIN;\n")?;
+            for event in events {
+                match event {
+                    WatchEvent::Added(walk_result) if walk_result.result.is_ok() => {
+                        upsert(sql, &walk_result)?;
+                        changes.push(IndexChange::Added(walk_result.path));
+                    }
+                    WatchEvent::Modified(walk_result) if walk_result.result.is_ok() => {
+                        upsert(sql, &walk_result)?;
+                        changes.push(IndexChange::Updated(walk_result.path));
+                    }
+                    WatchEvent::Removed(path) => {
+                        writeln!(
+                            sql,
+                            "DELETE FROM files WHERE path = {} OR ({});",
+                            quote(&path),
+                            below(&path)
+                        )?;
+                        changes.push(IndexChange::Removed(path));
+                    }
+                    _ => {}
+                }
+            }
+            sql.write_all(b"COMMIT;\n")
+        })?;
+        Ok(changes)
+    }
+}
+
+/// The SQL condition of the paths below a directory.
+fn below(dir: &str) -> String {
+    let dir = dir.trim_end_matches(MAIN_SEPARATOR);
+    // The paths starting with the directory and a separator sort
+    // between those two, and those with the next character after.
+    let after = char::from_u32(MAIN_SEPARATOR as u32 + 1).unwrap_or(MAIN_SEPARATOR);
+    format!(
+        "path > {} AND path < {}",
+        quote(&format!("{}{}", dir, MAIN_SEPARATOR)),
+        quote(&format!("{}{}", dir, after))
+    )
+}
+
+/// A string as an SQL literal.
+pub(crate) fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+/// Fails the test at hand if there is no `sqlite3` to run, rather than
+/// with whatever query went first.
+#[cfg(test)]
+pub(crate) fn require_sqlite3() {
+    let found = Command::new("sqlite3").arg("-version").output().is_ok();
+    assert!(found, "the tests of the index need sqlite3 on the PATH");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempTree;
+    use crate::{IdStrategy, MediaWalker};
+
+    #[test]
+    fn it_answers_queries_from_the_index() {
+        require_sqlite3();
+        let tree = TempTree::new("index");
+        tree.sample("sample_640x426.jpg", "photos/a.jpg");
+        tree.sample("sample_640x426.jpg", "photos/2024/it's a copy.jpg");
+        tree.sample("sample1.heic", "photos/b.heic");
+        tree.sample("sample3.mp3", "music/song.mp3");
+        tree.sample("sample_640x360.mp4", "photos-old/clip.mp4");
+        // The separators of the output, and the escape, in a path.
+        tree.file("photos-old/odd\x1f%1F\x1e.txt", b"notes");
+        let db = tree.path().join("library.db");
+        let results = MediaWalker::new(tree.path())
+            .ids(IdStrategy::Content)
+            .start();
+        assert_eq!(write(&db, results).unwrap(), 6);
+        assert!(open(tree.path().join("missing.db")).is_err());
+
+        let index = open(&db).unwrap();
+        let names = |files: Vec<IndexedFile>| -> Vec<String> {
+            let root = tree.path().to_string_lossy().into_owned();
+            files
+                .iter()
+                .map(|file| file.path[root.len() + 1..].replace(MAIN_SEPARATOR, "/"))
+                .collect()
+        };
+        let songs = index.by_kind(Kind::Audio).unwrap();
+        assert_eq!(songs[0].mime, "audio/mpeg");
+        assert_eq!(
+            songs[0].size,
+            Some(fs::metadata(&songs[0].path).unwrap().len())
+        );
+        assert!(songs[0].modified.is_some());
+        assert_eq!(names(songs), vec!["music/song.mp3"]);
+        assert_eq!(
+            names(index.by_mime("image/*").unwrap()),
+            vec![
+                "photos/2024/it's a copy.jpg",
+                "photos/a.jpg",
+                "photos/b.heic"
+            ]
+        );
+        assert_eq!(
+            names(index.in_dir(tree.path().join("photos")).unwrap()),
+            vec![
+                "photos/2024/it's a copy.jpg",
+                "photos/a.jpg",
+                "photos/b.heic"
+            ]
+        );
+        assert_eq!(
+            names(index.in_dir(tree.path().join("photos-old")).unwrap()),
+            vec!["photos-old/clip.mp4", "photos-old/odd\x1f%1F\x1e.txt"]
+        );
+        let copies: Vec<_> = index.duplicates().unwrap().into_iter().map(names).collect();
+        assert_eq!(
+            copies,
+            vec![vec!["photos/2024/it's a copy.jpg", "photos/a.jpg"]]
+        );
+    }
+
+    #[cfg(all(feature = "watch", target_os = "linux"))]
+    #[test]
+    fn it_keeps_the_index_up_to_date() {
+        require_sqlite3();
+        let tree = TempTree::new("indexer");
+        let elsewhere = TempTree::new("indexer-db");
+        let db = elsewhere.path().join("library.db");
+        let song = tree.sample("sample3.mp3", "music/song.mp3");
+        let indexer = Indexer::run(tree.path(), &db).unwrap();
+        let mut next = |wanted: fn(&IndexChange) -> bool| loop {
+            match indexer.next_timeout(Duration::from_secs(5)) {
+                Some(change) if wanted(&change) => return change,
+                Some(IndexChange::Failed(err)) => panic!("{}", err),
+                Some(_) => {}
+                None => panic!("no change"),
+            }
+        };
+        assert!(matches!(next(|_| true), IndexChange::Reindexed(1)));
+
+        let photo = tree.sample("sample_640x426.jpg", "photos/new.jpg");
+        match next(|change| matches!(change, IndexChange::Added(_))) {
+            IndexChange::Added(path) => assert_eq!(Path::new(&path), photo),
+            _ => unreachable!(),
+        }
+        let index = open(&db).unwrap();
+        assert_eq!(index.by_kind(Kind::Image).unwrap().len(), 1);
+
+        fs::remove_dir_all(song.parent().unwrap()).unwrap();
+        next(|change| matches!(change, IndexChange::Removed(_)));
+        assert!(index.by_kind(Kind::Audio).unwrap().is_empty());
+    }
+}
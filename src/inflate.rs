@@ -0,0 +1,296 @@
+//! Raw DEFLATE decompression, as in RFC 1951, for the deflated entries of
+//! ZIP files.
+use std::io;
+
+/// The base lengths of the length codes 257 to 285, and the extra bits
+/// after them.
+const LENGTHS: [(u16, u8); 29] = [
+    (3, 0),
+    (4, 0),
+    (5, 0),
+    (6, 0),
+    (7, 0),
+    (8, 0),
+    (9, 0),
+    (10, 0),
+    (11, 1),
+    (13, 1),
+    (15, 1),
+    (17, 1),
+    (19, 2),
+    (23, 2),
+    (27, 2),
+    (31, 2),
+    (35, 3),
+    (43, 3),
+    (51, 3),
+    (59, 3),
+    (67, 4),
+    (83, 4),
+    (99, 4),
+    (115, 4),
+    (131, 5),
+    (163, 5),
+    (195, 5),
+    (227, 5),
+    (258, 0),
+];
+
+/// The base distances of the distance codes, and the extra bits after
+/// them.
+const DISTANCES: [(u16, u8); 30] = [
+    (1, 0),
+    (2, 0),
+    (3, 0),
+    (4, 0),
+    (5, 1),
+    (7, 1),
+    (9, 2),
+    (13, 2),
+    (17, 3),
+    (25, 3),
+    (33, 4),
+    (49, 4),
+    (65, 5),
+    (97, 5),
+    (129, 6),
+    (193, 6),
+    (257, 7),
+    (385, 7),
+    (513, 8),
+    (769, 8),
+    (1025, 9),
+    (1537, 9),
+    (2049, 10),
+    (3073, 10),
+    (4097, 11),
+    (6145, 11),
+    (8193, 12),
+    (12289, 12),
+    (16385, 13),
+    (24577, 13),
+];
+
+/// The order in which the code lengths of the code length alphabet come.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// A canonical Huffman code: how many codes there are of each length,
+/// and the symbols in the order of their codes.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0; 16];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0; 16];
+        for len in 1..16 {
+            offsets[len] = offsets[len - 1] + counts[len - 1];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+        Huffman { counts, symbols }
+    }
+}
+
+/// The bits of the compressed data, least significant first.
+struct Bits<'a> {
+    data: &'a [u8],
+    at: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl Bits<'_> {
+    fn take(&mut self, n: u32) -> io::Result<u32> {
+        while self.count < n {
+            let byte = *self.data.get(self.at).ok_or_else(corrupt)?;
+            self.at += 1;
+            self.buffer |= (byte as u32) << self.count;
+            self.count += 8;
+        }
+        let bits = self.buffer & ((1 << n) - 1);
+        self.buffer >>= n;
+        self.count -= n;
+        Ok(bits)
+    }
+
+    fn decode(&mut self, huffman: &Huffman) -> io::Result<u16> {
+        let (mut code, mut first, mut index) = (0, 0, 0);
+        for len in 1..16 {
+            code |= self.take(1)? as usize;
+            let count = huffman.counts[len] as usize;
+            if code < first + count {
+                return Ok(huffman.symbols[index + code - first]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(corrupt())
+    }
+}
+
+fn corrupt() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "corrupt deflated data")
+}
+
+/// Decompresses raw DEFLATE data, of `size` bytes once decompressed.
+pub(crate) fn inflate(data: &[u8], size: usize) -> io::Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(size.min(1 << 24));
+    let mut bits = Bits {
+        data,
+        at: 0,
+        buffer: 0,
+        count: 0,
+    };
+    loop {
+        let last = bits.take(1)? == 1;
+        match bits.take(2)? {
+            0 => {
+                bits.buffer = 0;
+                bits.count = 0;
+                let header = data.get(bits.at..bits.at + 4).ok_or_else(corrupt)?;
+                let len = u16::from_le_bytes([header[0], header[1]]) as usize;
+                bits.at += 4;
+                let stored = data.get(bits.at..bits.at + len).ok_or_else(corrupt)?;
+                out.extend_from_slice(stored);
+                bits.at += len;
+            }
+            1 => {
+                let mut lengths = [8; 288];
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                let (literals, distances) = (Huffman::new(&lengths), Huffman::new(&[5; 30]));
+                inflate_block(&mut bits, &mut out, size, &literals, &distances)?;
+            }
+            2 => {
+                let literals = bits.take(5)? as usize + 257;
+                let distances = bits.take(5)? as usize + 1;
+                let code_lengths = bits.take(4)? as usize + 4;
+                let mut lengths = [0; 19];
+                for &at in &CODE_LENGTH_ORDER[..code_lengths] {
+                    lengths[at] = bits.take(3)? as u8;
+                }
+                let code = Huffman::new(&lengths);
+                let mut lengths = Vec::with_capacity(literals + distances);
+                while lengths.len() < literals + distances {
+                    let (len, times) = match bits.decode(&code)? {
+                        symbol @ 0..=15 => (symbol as u8, 1),
+                        16 => (*lengths.last().ok_or_else(corrupt)?, 3 + bits.take(2)?),
+                        17 => (0, 3 + bits.take(3)?),
+                        _ => (0, 11 + bits.take(7)?),
+                    };
+                    lengths.extend(std::iter::repeat_n(len, times as usize));
+                }
+                if lengths.len() != literals + distances {
+                    return Err(corrupt());
+                }
+                let (literals, distances) = lengths.split_at(literals);
+                inflate_block(
+                    &mut bits,
+                    &mut out,
+                    size,
+                    &Huffman::new(literals),
+                    &Huffman::new(distances),
+                )?;
+            }
+            _ => return Err(corrupt()),
+        }
+        if out.len() > size {
+            return Err(corrupt());
+        }
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+/// Decompresses a block of Huffman codes, up to its end code, failing
+/// once the output is longer than `size`.
+fn inflate_block(
+    bits: &mut Bits,
+    out: &mut Vec<u8>,
+    size: usize,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> io::Result<()> {
+    loop {
+        if out.len() > size {
+            return Err(corrupt());
+        }
+        let symbol = bits.decode(literals)? as usize;
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let &(base, extra) = LENGTHS.get(symbol - 257).ok_or_else(corrupt)?;
+                let len = base as usize + bits.take(extra as u32)? as usize;
+                let symbol = bits.decode(distances)? as usize;
+                let &(base, extra) = DISTANCES.get(symbol).ok_or_else(corrupt)?;
+                let distance = base as usize + bits.take(extra as u32)? as usize;
+                if distance > out.len() {
+                    return Err(corrupt());
+                }
+                // The copy may overlap what it makes, a byte at a time.
+                let from = out.len() - distance;
+                for i in 0..len {
+                    out.push(out[from + i]);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unhex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|at| u8::from_str_radix(&hex[at..at + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn it_inflates() {
+        // By zlib, with fixed codes.
+        let fixed = unhex("4b4c4a4e842100");
+        assert_eq!(inflate(&fixed, 12).unwrap(), b"abcabcabcabc");
+
+        // By zlib, with dynamic codes.
+        let dynamic = unhex(concat!(
+            "f348cdc9c9d751c840a214153c4605079320032313330b2b1b3b072717370f2f1fbf80",
+            "a090b088a898b884a494b48cac9cbc82a292b28aaa9aba86a696b68eae9ebe81a191b1",
+            "89a999b985a595b58dad9dbd83a393b38bab9bbb87a797b78faf9f7f40605070486858",
+            "78446454744c6c5c7c426252724a6a5a7a466656764e6e5e7e41615171496959794565",
+            "55754d6d5d7d436353734b6b5b7b476757774f6f5fff848993264f993a6dfa8c99b366",
+            "cf993b6ffe82858b162f59ba6cf98a95ab56af59bb6efd868d9b366fd9ba6dfb8e9dbb",
+            "76efd9bb6fff8183870e1f397aecf88993a74e9f397beefc858b972e5fb97aedfa8d9b",
+            "b76edfb97beffe83878f1e3f79faecf98b97af5ebf79fbeefd878f9f3e7ff9faedfb8f",
+            "9fbf7efff9fbef3f00"
+        ));
+        let mut expected = b"Hello, hello, hello! ".repeat(20);
+        expected.extend(0..=255);
+        assert_eq!(inflate(&dynamic, expected.len()).unwrap(), expected);
+
+        // Stored, then cut short.
+        let stored = [1, 3, 0, 0xfc, 0xff, b'a', b'b', b'c'];
+        assert_eq!(inflate(&stored, 3).unwrap(), b"abc");
+        assert!(inflate(&stored[..6], 3).is_err());
+        assert!(inflate(&dynamic, 10).is_err());
+    }
+}
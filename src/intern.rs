@@ -0,0 +1,24 @@
+//! Shared MIME type strings. There are only a few dozen MIME types, so
+//! results share one allocation of each instead of a string apiece.
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Returns the shared copy of a MIME type.
+pub(crate) fn mime(mime: &'static str) -> Arc<str> {
+    static MIMES: OnceLock<Mutex<HashMap<&'static str, Arc<str>>>> = OnceLock::new();
+    let mut mimes = MIMES.get_or_init(Default::default).lock().unwrap();
+    Arc::clone(mimes.entry(mime).or_insert_with(|| Arc::from(mime)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_shares_mime_strings() {
+        let (a, b) = (mime("image/jpeg"), mime("image/jpeg"));
+        assert_eq!(&*a, "image/jpeg");
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(!Arc::ptr_eq(&a, &mime("image/png")));
+    }
+}
@@ -0,0 +1,250 @@
+use crate::brand::{self, Brand, Sniffed};
+use infer::MatcherType;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+const AUDIO: &str = "audio";
+const IMAGE: &str = "image";
+const VIDEO: &str = "video";
+
+/// Companion files, keyed by lowercase extension, with the MIME type
+/// reported for them. None of these can be sniffed reliably, as they are
+/// plain text, so they are recognized by their extension.
+const COMPANIONS: &[(&str, &str)] = &[
+    ("srt", "application/x-subrip"),
+    ("ass", "text/x-ssa"),
+    ("vtt", "text/vtt"),
+    ("cue", "application/x-cue"),
+    ("m3u", "audio/x-mpegurl"),
+    ("nfo", "text/x-nfo"),
+];
+
+/// Document types that infer files under archives.
+const ARCHIVED_DOCUMENTS: &[&str] = &[
+    "application/pdf",
+    "application/rtf",
+    "application/postscript",
+];
+
+/// The category of a found file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Kind {
+    /// An audio file.
+    Audio,
+    /// An image file.
+    Image,
+    /// A video file.
+    Video,
+    /// A file that accompanies media files: subtitles (`.srt`, `.ass`,
+    /// `.vtt`), cue sheets (`.cue`), playlists (`.m3u`) and `.nfo` files.
+    /// Opt-in, see [`MediaWalker::companions`](crate::MediaWalker::companions).
+    Companion,
+    /// An application: executables, libraries, bytecode. Opt-in, see
+    /// [`MediaWalker::extra_kinds`](crate::MediaWalker::extra_kinds).
+    Application,
+    /// An archive, like zip or tar. Opt-in.
+    Archive,
+    /// A document, like PDF or an office file. Opt-in.
+    Document,
+    /// An ebook, like epub or mobi. Opt-in.
+    Ebook,
+    /// A font file. Opt-in.
+    Font,
+    /// A sniffable text file, like HTML or XML. Opt-in.
+    Text,
+}
+
+impl Kind {
+    /// The kinds reported by default.
+    pub const MEDIA: [Kind; 3] = [Kind::Audio, Kind::Image, Kind::Video];
+
+    /// All the kinds.
+    pub const ALL: [Kind; 10] = [
+        Kind::Audio,
+        Kind::Image,
+        Kind::Video,
+        Kind::Companion,
+        Kind::Application,
+        Kind::Archive,
+        Kind::Document,
+        Kind::Ebook,
+        Kind::Font,
+        Kind::Text,
+    ];
+
+    /// Returns the lowercase name of the kind, e.g. `"image"`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Kind::Audio => AUDIO,
+            Kind::Image => IMAGE,
+            Kind::Video => VIDEO,
+            Kind::Companion => "companion",
+            Kind::Application => "application",
+            Kind::Archive => "archive",
+            Kind::Document => "document",
+            Kind::Ebook => "ebook",
+            Kind::Font => "font",
+            Kind::Text => "text",
+        }
+    }
+
+    /// Maps a sniffed file type to a kind.
+    pub(crate) fn from_type(info: &infer::Type) -> Option<Kind> {
+        // Go by the MIME type for media, as infer files e.g. some
+        // audio containers under their own matcher types.
+        let mime = info.mime_type();
+        if mime.starts_with(AUDIO) {
+            return Some(Kind::Audio);
+        } else if mime.starts_with(IMAGE) {
+            return Some(Kind::Image);
+        } else if mime.starts_with(VIDEO) {
+            return Some(Kind::Video);
+        } else if ARCHIVED_DOCUMENTS.contains(&mime) {
+            return Some(Kind::Document);
+        }
+        match info.matcher_type() {
+            MatcherType::App => Some(Kind::Application),
+            MatcherType::Archive => Some(Kind::Archive),
+            MatcherType::Doc => Some(Kind::Document),
+            MatcherType::Book => Some(Kind::Ebook),
+            MatcherType::Font => Some(Kind::Font),
+            MatcherType::Text => Some(Kind::Text),
+            _ => None,
+        }
+    }
+
+    /// Returns the companion MIME type for the given path, if its
+    /// extension is one of the recognized companion extensions.
+    pub(crate) fn companion_mime(path: &Path) -> Option<&'static str> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        COMPANIONS
+            .iter()
+            .find(|(known, _)| *known == ext)
+            .map(|(_, mime)| *mime)
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Kind {
+    type Err = String;
+
+    /// Parses a kind from its name, ignoring case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Kind::ALL
+            .into_iter()
+            .find(|kind| kind.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown kind: {}", s))
+    }
+}
+
+/// The type of a file, as told from its first bytes, see
+/// [`classify_bytes`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Classified {
+    /// The MIME type, e.g. `image/jpeg`.
+    pub mime: &'static str,
+    /// The category of the type, `None` for a type of none of them, which
+    /// walks leave out.
+    pub kind: Option<Kind>,
+    /// The major brand of an ISO-BMFF file, like `heic` or `isom`.
+    pub brand: Option<Brand>,
+}
+
+/// Tells the type of a file from its first bytes, as walks do with the
+/// first 8 KiB of each file, before their filters. `None` is for a file
+/// of no recognizable type. For fuzzing and testing the sniffing apart
+/// from files.
+///
+/// # Examples
+///
+/// ```
+/// use mediawalker::{classify_bytes, Kind};
+/// let png = classify_bytes(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+/// assert_eq!((png.mime, png.kind), ("image/png", Some(Kind::Image)));
+/// assert_eq!(classify_bytes(b"plain text"), None);
+/// ```
+pub fn classify_bytes(header: &[u8]) -> Option<Classified> {
+    let Sniffed { info, brand } = brand::sniff(header)?;
+    Some(Classified {
+        mime: info.mime_type(),
+        kind: Kind::from_type(&info),
+        brand,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Sample;
+
+    #[test]
+    fn it_classifies_any_bytes() {
+        for sample in Sample::ALL {
+            let classified = classify_bytes(sample.bytes()).unwrap();
+            assert_eq!(classified.mime, sample.mime());
+            assert!(classified
+                .kind
+                .is_some_and(|kind| Kind::MEDIA.contains(&kind)));
+        }
+        // Whatever the bytes, and however they are cut short, sniffing
+        // tells a type or none, with no panic.
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut random = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        for _ in 0..2000 {
+            let len = (random() % 64) as usize;
+            let mut bytes: Vec<u8> = (0..len).map(|_| random() as u8).collect();
+            if let Some(sample) = Sample::ALL.get((random() % 8) as usize) {
+                let header = sample.bytes();
+                bytes.splice(
+                    0..0,
+                    header[..(random() as usize) % header.len()].iter().copied(),
+                );
+            }
+            if let Some(classified) = classify_bytes(&bytes) {
+                assert!(classified.mime.contains('/'));
+            }
+        }
+    }
+
+    #[test]
+    fn it_recognizes_companions_by_extension() {
+        assert_eq!(
+            Kind::companion_mime(Path::new("movie.en.SRT")),
+            Some("application/x-subrip")
+        );
+        assert_eq!(
+            Kind::companion_mime(Path::new("album.cue")),
+            Some("application/x-cue")
+        );
+        assert_eq!(Kind::companion_mime(Path::new("notes.txt")), None);
+        assert_eq!(Kind::companion_mime(Path::new("nfo")), None);
+    }
+
+    #[test]
+    fn it_maps_sniffed_types_to_kinds() {
+        let pdf = infer::get(b"%PDF-1.7").unwrap();
+        assert_eq!(Kind::from_type(&pdf), Some(Kind::Document));
+        let jpeg = infer::get(&[0xFF, 0xD8, 0xFF, 0xAA]).unwrap();
+        assert_eq!(Kind::from_type(&jpeg), Some(Kind::Image));
+    }
+
+    #[test]
+    fn it_parses_kind_names() {
+        for kind in Kind::ALL {
+            assert_eq!(kind.name().parse(), Ok(kind));
+        }
+        assert_eq!("Video".parse(), Ok(Kind::Video));
+        assert!("movies".parse::<Kind>().is_err());
+    }
+}
@@ -3,44 +3,273 @@
 //!
 #![warn(missing_docs)]
 #![allow(unused)]
-use infer;
+use std::collections::BTreeMap;
 use std::io;
-use std::path::PathBuf;
-use std::sync::mpsc;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::mpsc::Receiver;
-use std::thread;
-use walkdir::WalkDir;
+use std::sync::Arc;
+use std::time::SystemTime;
 
-const AUDIO: &str = "audio";
-const IMAGE: &str = "image";
-const VIDEO: &str = "video";
+mod access;
+mod animation;
+mod artwork;
+#[cfg(feature = "bench")]
+pub mod bench;
+mod brand;
+#[cfg(feature = "capi")]
+pub mod capi;
+mod color;
+mod compact;
+mod config;
+#[cfg(feature = "daemon")]
+pub mod daemon;
+#[cfg(all(feature = "dbus", target_os = "linux"))]
+pub mod dbus;
+#[cfg(target_os = "linux")]
+mod dents;
+mod diff;
+#[cfg(feature = "documents")]
+mod document;
+mod enrich;
+mod error;
+mod event;
+mod exif;
+pub mod export;
+mod filter;
+#[cfg(feature = "acoustid")]
+mod fingerprint;
+#[cfg(feature = "index")]
+pub mod fixity;
+mod glob;
+mod group;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+mod handle;
+mod hint;
+#[cfg(feature = "grpc")]
+mod hpack;
+mod id;
+#[cfg(feature = "index")]
+pub mod index;
+#[cfg(feature = "documents")]
+mod inflate;
+mod intern;
+mod kind;
+mod locked;
+mod manager;
+mod mmap;
+mod motion;
+#[cfg(all(feature = "node", unix))]
+mod node;
+mod normalize;
+mod paths;
+mod power;
+mod preset;
+mod pressure;
+mod priority;
+mod record;
+pub mod roots;
+#[cfg(feature = "serve")]
+pub mod serve;
+mod session;
+mod sha256;
+mod shuffle;
+mod signature;
+mod source;
+mod spotlight;
+mod stats;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+#[cfg(test)]
+mod testutil;
+mod throughput;
+mod thumbnail;
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+mod uring;
+mod walker;
+#[cfg(all(feature = "watch", target_os = "linux"))]
+mod watch;
+#[cfg(feature = "xattr")]
+mod xattr;
+
+pub use artwork::ArtworkRef;
+pub use brand::Brand;
+pub use color::ColorInfo;
+pub use compact::{CompactResult, ResultArena};
+pub use config::{ConfigError, WalkConfig};
+pub use diff::{diff, Change};
+#[cfg(feature = "documents")]
+pub use document::EmbeddedMedia;
+pub use enrich::{AsyncEnricher, EnrichFuture, Enricher};
+pub use error::{CollectedError, ErrorPolicy, MediaWalkError};
+pub use event::{DirEmit, Enrichment, Explanation, SkipReason, WalkEvent};
+pub use exif::{Exif, GeoPoint};
+pub use filter::Filter;
+#[cfg(feature = "acoustid")]
+pub use fingerprint::Fingerprint;
+pub use group::{group_by_location, group_by_time, LocationGroup, ReverseGeocoder};
+pub use handle::{Events, WalkHandle};
+pub use hint::MediaHint;
+pub use id::IdStrategy;
+pub use kind::{classify_bytes, Classified, Kind};
+pub use locked::{LockedContainer, LockedFiles};
+pub use manager::{ManagedEvent, ManagedWalks, WalkManager};
+pub use motion::{pair_live_photos, Motion};
+pub use normalize::Normalization;
+pub use paths::PathIssue;
+pub use preset::Preset;
+pub use priority::Priority;
+pub use record::Replay;
+pub use session::SessionId;
+pub use signature::VideoSignature;
+pub use source::{FsSource, SourceEntry, WalkSource};
+pub use stats::{KindStats, MimeStats, SizeHistogram, WalkStats};
+pub use walker::{FileEntry, MediaWalker, SourceWalk};
+#[cfg(all(feature = "watch", target_os = "linux"))]
+pub use watch::{WatchBackend, WatchEvent, Watcher};
+#[cfg(feature = "xattr")]
+pub use xattr::Xattrs;
 
 /// This struct contains the result for a single found file.
 /// - `path`: The path of the found file.
 /// - `mime`: The MIME type of the found file.
+/// - `kind`: The category of the found file.
 /// - `result`:
 ///   - _bool_:
 ///     - `true`: A file and a media type for it was found.
 ///     - `false`: A file was found, but no media
-///         type could not be found for it.
+///       type could not be found for it.
 ///   - _io::Error_: Something went wrong while trying to figure out
-///         the media type.
+///     the media type.
+///
+/// Fields are added as walks find out more about files, so results are
+/// only made by walks.
+#[derive(Debug)]
+#[non_exhaustive]
 pub struct MediaWalkResult {
     /// The path of the found file.
     pub path: String,
-    /// The file's MIME type.
-    pub mime: String,
+    /// The file's MIME type, shared by all the results of the type.
+    pub mime: Arc<str>,
+    /// The file's category, `None` if the media type could not be found.
+    pub kind: Option<Kind>,
     /// - _bool_:
     ///   - `true`: A file and a media type for it was found.
     ///   - `false`: A file was found, but no media
-    ///       type could not be found for it.
+    ///     type could not be found for it.
     /// - _io::Error_: Something went wrong while trying to figure out
-    ///       the media type.
+    ///   the media type.
     pub result: Result<bool, io::Error>,
+    /// The file's Finder tags and other extended attributes, if asked
+    /// for with [`MediaWalker::capture_xattrs`].
+    #[cfg(feature = "xattr")]
+    pub xattrs: Option<Xattrs>,
+    /// The file's cached thumbnail, if asked for with
+    /// [`MediaWalker::thumbnails`] and there is one up to date.
+    pub thumbnail: Option<PathBuf>,
+    /// The file's stable ID, if asked for with [`MediaWalker::ids`].
+    pub id: Option<u64>,
+    /// A number telling this result apart from every other one of the
+    /// process, which the [`WalkEvent::Enriched`] events filling in the
+    /// result carry.
+    pub result_id: u64,
+    /// The walk the result is of, set as it is sent: the same for all
+    /// the results of a walk, see [`WalkHandle::session_id`].
+    pub session_id: Option<SessionId>,
+    /// When the result was sent.
+    pub emitted_at: Option<SystemTime>,
+    /// Whether the file's content does not go with its media
+    /// extension, see [`MediaWalker::flag_suspicious`].
+    pub suspicious: bool,
+    /// Whether [`path`](Self::path) is the lossy conversion of a path
+    /// that is not UTF-8, see [`MediaWalker::lossy_paths`]: it then
+    /// cannot be opened as such.
+    pub lossy: bool,
+    /// What is wrong with the file's path, if checked with
+    /// [`MediaWalker::check_paths`].
+    pub path_issues: Vec<PathIssue>,
+    /// The file's path as it is on disk, if it was changed by
+    /// [`MediaWalker::normalize_paths`].
+    pub raw_path: Option<PathBuf>,
+    /// Where the file's bytes actually are, if it was found through a
+    /// symbolic link: the canonical path of the link's target.
+    pub resolved_path: Option<PathBuf>,
+    /// The video's perceptual signature, if asked for with
+    /// [`MediaWalker::video_signatures`].
+    pub video_signature: Option<VideoSignature>,
+    /// What [`Enricher`]s found out about the file, by name, like a
+    /// MusicBrainz recording ID.
+    pub annotations: BTreeMap<String, String>,
+    /// The bit depth, HDR-ness and color profile of an image or a video,
+    /// if asked for with [`MediaWalker::color_info`].
+    pub color_info: Option<ColorInfo>,
+    /// Whether a GIF, PNG, WebP, AVIF or HEIF image is animated, if
+    /// asked for with [`MediaWalker::detect_animation`].
+    pub animated: Option<bool>,
+    /// The major brand of an ISO-BMFF file, like `heic` for an iPhone
+    /// picture or `isom` for an MP4 video.
+    pub brand: Option<Brand>,
+    /// The motion component of a Live Photo or a motion photo, see
+    /// [`pair_live_photos`] and [`MediaWalker::motion_photos`].
+    pub motion: Option<Motion>,
+    /// What the photo's EXIF tells, like when it was taken, if asked for
+    /// with [`MediaWalker::exif`].
+    pub exif: Option<Exif>,
+    /// What the image likely is other than a photo, like a screenshot,
+    /// if asked for with [`MediaWalker::media_hints`].
+    pub hints: Vec<MediaHint>,
+    /// The song's album art, if asked for with [`MediaWalker::artwork`].
+    pub artwork: Option<ArtworkRef>,
+    /// The encrypted container the file is, whose contents could not be
+    /// inspected, if asked for with [`MediaWalker::locked_files`].
+    pub locked: Option<LockedContainer>,
+    /// The song's Chromaprint fingerprint, if asked for with
+    /// [`MediaWalker::fingerprints`].
+    #[cfg(feature = "acoustid")]
+    pub fingerprint: Option<Fingerprint>,
 }
 
-/// Start walkding through the given directory. Returns a channel of
-/// MediaWalkResult structs.
+impl MediaWalkResult {
+    /// Creates a result for a file with a media type yet to be found.
+    pub(crate) fn new(path: String) -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        MediaWalkResult {
+            path,
+            mime: intern::mime(""),
+            kind: None,
+            result: Ok(true),
+            #[cfg(feature = "xattr")]
+            xattrs: None,
+            thumbnail: None,
+            id: None,
+            result_id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            session_id: None,
+            emitted_at: None,
+            suspicious: false,
+            lossy: false,
+            path_issues: vec![],
+            raw_path: None,
+            resolved_path: None,
+            video_signature: None,
+            annotations: BTreeMap::new(),
+            color_info: None,
+            animated: None,
+            brand: None,
+            motion: None,
+            exif: None,
+            hints: vec![],
+            artwork: None,
+            locked: None,
+            #[cfg(feature = "acoustid")]
+            fingerprint: None,
+        }
+    }
+}
+
+/// Start walking through the given directory, or classify the given
+/// file. Returns a channel of MediaWalkResult structs. See
+/// [`MediaWalker`] for more options.
 ///
 /// # Examples
 ///
@@ -56,7 +285,7 @@ pub struct MediaWalkResult {
 /// for received in rx {
 ///     match received.result {
 ///         Ok(result) => {
-///             if result == true {
+///             if result {
 ///                 println!("A good file: {}", received.path);
 ///             } else {
 ///                 println!("Unknown media type: {}", received.path);
@@ -67,52 +296,45 @@ pub struct MediaWalkResult {
 ///         }
 ///     }
 /// }
+/// ```
 pub fn start_walking(first_step: &PathBuf) -> Receiver<MediaWalkResult> {
-    let (tx, rx) = mpsc::channel();
+    MediaWalker::new(first_step).start().into_receiver()
+}
 
-    let starter = first_step.clone();
-    thread::spawn(move || {
-        let walker = WalkDir::new(starter).follow_links(true).into_iter();
-        for entry_result in walker {
-            if let Ok(entry) = entry_result {
-                if entry.file_type().is_file() {
-                    if let Some(path) = entry.path().to_str() {
-                        let mut walk_result = MediaWalkResult {
-                            path: path.to_string(),
-                            mime: "".to_string(),
-                            result: Ok(true),
-                        };
-                        match infer::get_from_path(path.to_string()) {
-                            Ok(Some(info)) => {
-                                if info.mime_type().starts_with(AUDIO)
-                                    || info.mime_type().starts_with(IMAGE)
-                                    || info.mime_type().starts_with(VIDEO)
-                                {
-                                    walk_result.mime = info.mime_type().to_string();
-                                    tx.send(walk_result).unwrap();
-                                }
-                            }
-                            Ok(None) => {
-                                // eprintln!("Unknown file type");
-                                walk_result.result = Ok(false);
-                                tx.send(walk_result).unwrap();
-                            }
-                            Err(e) => {
-                                // eprintln!("Looks like something went wrong");
-                                // eprintln!("{}", e);
-                                walk_result.result = Err(e);
-                                tx.send(walk_result).unwrap();
-                            }
-                        }
-                    }
-                }
-            }
+/// Walks through the given directory with the given options, and
+/// returns all the results and the final statistics at once. Fails if
+/// the root is neither a directory nor a file, and with
+/// [`ErrorPolicy::FailFast`], on the first error.
+///
+/// # Examples
+///
+/// ```
+/// use mediawalker::{walk_collect, WalkConfig};
+/// let (results, stats) = walk_collect(env!("CARGO_MANIFEST_DIR"), &WalkConfig::default())?;
+/// assert_eq!(results.len() as u64, stats.files);
+/// # Ok::<(), mediawalker::MediaWalkError>(())
+/// ```
+pub fn walk_collect<P: AsRef<Path>>(
+    root: P,
+    options: &WalkConfig,
+) -> Result<(Vec<MediaWalkResult>, WalkStats), MediaWalkError> {
+    let mut handle = options.apply(MediaWalker::new(root)).try_start()?;
+    let mut results: Vec<_> = handle.by_ref().collect();
+    if options.error_policy.unwrap_or_default() == ErrorPolicy::FailFast {
+        if let Some(failed) = results.iter().position(|r| r.result.is_err()) {
+            let failed = results.swap_remove(failed);
+            return Err(MediaWalkError::Failed {
+                path: failed.path,
+                source: failed.result.unwrap_err(),
+            });
         }
-    });
-    return rx;
+    }
+    Ok((results, handle.stats()))
 }
 
 #[cfg(test)]
+// The baseline test compares with `true`, kept as it was written.
+#[allow(clippy::bool_comparison)]
 mod tests {
     use super::*;
     use std::env;
@@ -147,4 +369,19 @@ mod tests {
         assert_eq!(items.len(), 8);
         assert_eq!(invalid_count, 1);
     }
+
+    #[test]
+    fn it_collects_results_and_stats() {
+        let root = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/test");
+        let (results, stats) = walk_collect(root, &WalkConfig::default()).unwrap();
+        assert_eq!(results.len(), 9);
+        assert_eq!(stats.files, 9);
+        assert_eq!(stats.matched, 8);
+
+        let missing = Path::new(root).join("missing");
+        assert!(matches!(
+            walk_collect(&missing, &WalkConfig::default()),
+            Err(MediaWalkError::RootNotFound(root)) if root == missing
+        ));
+    }
 }
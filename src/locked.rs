@@ -0,0 +1,326 @@
+//! Telling encrypted containers, whose contents cannot be inspected, see
+//! [`MediaWalker::locked_files`](crate::MediaWalker::locked_files).
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::str::FromStr;
+
+/// How much of the end of a PDF is searched for its encryption
+/// dictionary, which its trailer or last cross-reference stream points
+/// at.
+const PDF_TAIL: u64 = 64 * 1024;
+
+/// The ID of 7-Zip's AES-256 coder.
+const SEVEN_ZIP_AES: [u8; 4] = [0x06, 0xf1, 0x07, 0x01];
+
+/// What to do with the files that are encrypted, see
+/// [`MediaWalker::locked_files`](crate::MediaWalker::locked_files).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum LockedFiles {
+    /// Treat them as any other file: they are not looked for.
+    #[default]
+    Ignore,
+    /// Report them with the container they are in
+    /// [`MediaWalkResult::locked`](crate::MediaWalkResult::locked), also
+    /// when their kind is not one asked for.
+    Report,
+    /// Leave them out, as [`SkipReason::Locked`](crate::SkipReason::Locked)
+    /// events.
+    Skip,
+}
+
+impl FromStr for LockedFiles {
+    type Err = String;
+
+    /// Parses `ignore`, `report` or `skip`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ignore" => Ok(LockedFiles::Ignore),
+            "report" => Ok(LockedFiles::Report),
+            "skip" => Ok(LockedFiles::Skip),
+            _ => Err(format!("unknown handling of locked files: {}", s)),
+        }
+    }
+}
+
+/// An encrypted container, whose contents exist but could not be
+/// inspected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LockedContainer {
+    /// A password-protected ZIP file, or a file in a ZIP-based format.
+    Zip,
+    /// An encrypted PDF.
+    Pdf,
+    /// A password-protected Office document, kept in an OLE compound
+    /// file.
+    Office,
+    /// A 7-Zip archive encrypted with AES.
+    SevenZip,
+    /// A RAR archive with an encrypted header.
+    Rar,
+    /// An Apple Photos library that cannot be read, its access not being
+    /// granted.
+    PhotosLibrary,
+}
+
+impl LockedContainer {
+    /// Returns the lowercase name of the container, e.g. `"zip"`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            LockedContainer::Zip => "zip",
+            LockedContainer::Pdf => "pdf",
+            LockedContainer::Office => "office",
+            LockedContainer::SevenZip => "7z",
+            LockedContainer::Rar => "rar",
+            LockedContainer::PhotosLibrary => "photos library",
+        }
+    }
+}
+
+impl fmt::Display for LockedContainer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The encrypted container a file of a MIME type is, if it is one.
+pub(crate) fn detect(open: &Path, mime: &str) -> Option<LockedContainer> {
+    let mut file = File::open(open).ok()?;
+    let locked = match mime {
+        "application/pdf" => is_encrypted_pdf(&mut file),
+        "application/x-7z-compressed" => is_encrypted_7z(&mut file),
+        "application/vnd.rar" => is_encrypted_rar(&mut file),
+        "application/x-ole-storage"
+        | "application/msword"
+        | "application/vnd.ms-excel"
+        | "application/vnd.ms-powerpoint" => is_encrypted_office(&mut file),
+        mime if mime == "application/zip"
+            || mime == "application/epub+zip"
+            || mime.starts_with("application/vnd.openxmlformats-officedocument.")
+            || mime.starts_with("application/vnd.oasis.opendocument.") =>
+        {
+            is_encrypted_zip(&mut file)
+        }
+        _ => return None,
+    };
+    let container = match mime {
+        "application/pdf" => LockedContainer::Pdf,
+        "application/x-7z-compressed" => LockedContainer::SevenZip,
+        "application/vnd.rar" => LockedContainer::Rar,
+        mime if mime.starts_with("application/x-ole") || mime.contains("ms-") => {
+            LockedContainer::Office
+        }
+        _ => LockedContainer::Zip,
+    };
+    locked.unwrap_or(false).then_some(container)
+}
+
+/// Whether a directory that could not be read is an Apple Photos
+/// library, which macOS keeps from the programs not granted access to
+/// the user's photos.
+pub(crate) fn is_photos_library(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("photoslibrary"))
+}
+
+/// Whether the first entry of a ZIP file is encrypted, as all of them
+/// are in a password-protected one.
+fn is_encrypted_zip(file: &mut File) -> io::Result<bool> {
+    let mut header = [0; 8];
+    file.read_exact(&mut header)?;
+    Ok(&header[..4] == b"PK\x03\x04" && header[6] & 1 != 0)
+}
+
+/// Whether a PDF has an encryption dictionary.
+fn is_encrypted_pdf(file: &mut File) -> io::Result<bool> {
+    let len = file.metadata()?.len();
+    file.seek(SeekFrom::Start(len.saturating_sub(PDF_TAIL)))?;
+    let mut tail = vec![];
+    file.read_to_end(&mut tail)?;
+    Ok(tail.windows(8).any(|window| window == b"/Encrypt"))
+}
+
+/// Whether the headers of a 7-Zip archive are encrypted with AES, as
+/// they are when the names of its files are hidden, or its files are.
+fn is_encrypted_7z(file: &mut File) -> io::Result<bool> {
+    let mut start = [0; 32];
+    file.read_exact(&mut start)?;
+    let next_at = u64::from_le_bytes(start[12..20].try_into().unwrap());
+    let next_len = u64::from_le_bytes(start[20..28].try_into().unwrap());
+    if next_len > 16 * 1024 * 1024 {
+        return Ok(false);
+    }
+    file.seek(SeekFrom::Start(32 + next_at))?;
+    let mut next = vec![];
+    file.take(next_len).read_to_end(&mut next)?;
+    Ok(next.windows(4).any(|window| window == SEVEN_ZIP_AES))
+}
+
+/// Whether a RAR archive has its headers encrypted: in RAR 5, an
+/// encryption header comes first, and in RAR 4, the main header has its
+/// password flag.
+fn is_encrypted_rar(file: &mut File) -> io::Result<bool> {
+    let mut header = [0; 16];
+    let read = file.read(&mut header)?;
+    let header = &header[..read];
+    if let Some(rest) = header.strip_prefix(b"Rar!\x1a\x07\x01\x00") {
+        // The CRC, then the header size and type, as variable-length
+        // integers.
+        let mut fields = rest.get(4..).unwrap_or_default().iter();
+        let _ = fields.by_ref().position(|byte| byte & 0x80 == 0);
+        return Ok(fields.next() == Some(&4));
+    }
+    match header.strip_prefix(b"Rar!\x1a\x07\x00") {
+        Some([_, _, 0x73, flags, ..]) => Ok(flags & 0x80 != 0),
+        _ => Ok(false),
+    }
+}
+
+/// Whether a compound file holds an `EncryptedPackage`, the encrypted
+/// Office Open XML document of a password-protected Office document.
+fn is_encrypted_office(file: &mut File) -> io::Result<bool> {
+    let mut header = [0; 512];
+    file.read_exact(&mut header)?;
+    let shift = u16::from_le_bytes([header[0x1e], header[0x1f]]);
+    if !matches!(shift, 9 | 12) {
+        return Ok(false);
+    }
+    let directory = u32::from_le_bytes(header[0x30..0x34].try_into().unwrap()) as u64;
+    file.seek(SeekFrom::Start((directory + 1) << shift))?;
+    let mut entries = vec![0; 1 << shift];
+    file.read_exact(&mut entries)?;
+    let name: Vec<u8> = "EncryptedPackage"
+        .encode_utf16()
+        .flat_map(u16::to_le_bytes)
+        .collect();
+    Ok(entries
+        .chunks_exact(128)
+        .any(|entry| entry.starts_with(&name) && entry[name.len()..name.len() + 2] == [0, 0]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempTree;
+    use crate::{Kind, MediaWalker, SkipReason, WalkEvent};
+
+    /// A compound file of one directory sector, with entries of names.
+    fn compound_file(names: &[&str]) -> Vec<u8> {
+        let mut file = vec![0; 512];
+        file[..8].copy_from_slice(&[0xd0, 0xcf, 0x11, 0xe0, 0xa1, 0xb1, 0x1a, 0xe1]);
+        file[0x1e] = 9;
+        for name in names {
+            let mut entry: Vec<u8> = name.encode_utf16().flat_map(u16::to_le_bytes).collect();
+            entry.resize(128, 0);
+            file.extend(entry);
+        }
+        file.resize(1024, 0);
+        file
+    }
+
+    #[test]
+    fn it_tells_encrypted_containers() {
+        let tree = TempTree::new("locked");
+        let detect =
+            |name: &str, contents: &[u8], mime: &str| detect(&tree.file(name, contents), mime);
+        let zip = |flags: u8| [b'P', b'K', 3, 4, 20, 0, flags, 0, 0, 0];
+        assert_eq!(
+            detect("a.zip", &zip(1), "application/zip"),
+            Some(LockedContainer::Zip)
+        );
+        assert_eq!(detect("b.zip", &zip(0), "application/zip"), None);
+        assert_eq!(
+            detect(
+                "a.pdf",
+                b"%PDF-1.7\n...\ntrailer\n<< /Encrypt 5 0 R >>\n%%EOF",
+                "application/pdf"
+            ),
+            Some(LockedContainer::Pdf)
+        );
+        assert_eq!(
+            detect("b.pdf", b"%PDF-1.7\ntrailer\n<< >>\n", "application/pdf"),
+            None
+        );
+        let office = compound_file(&["Root Entry", "EncryptionInfo", "EncryptedPackage"]);
+        assert_eq!(
+            detect("a.docx", &office, "application/x-ole-storage"),
+            Some(LockedContainer::Office)
+        );
+        let office = compound_file(&["Root Entry", "WordDocument"]);
+        assert_eq!(detect("b.doc", &office, "application/msword"), None);
+        // A RAR 5 archive starting with its encryption header.
+        assert_eq!(
+            detect(
+                "a.rar",
+                b"Rar!\x1a\x07\x01\x00\x12\x34\x56\x78\x21\x04\x00",
+                "application/vnd.rar"
+            ),
+            Some(LockedContainer::Rar)
+        );
+        assert_eq!(
+            detect(
+                "b.rar",
+                b"Rar!\x1a\x07\x00\xcf\x90\x73\x80\x00",
+                "application/vnd.rar"
+            ),
+            Some(LockedContainer::Rar)
+        );
+        let mut seven = b"7z\xbc\xaf\x27\x1c\x00\x04".to_vec();
+        seven.extend([0; 4]);
+        seven.extend(0u64.to_le_bytes());
+        seven.extend(8u64.to_le_bytes());
+        seven.extend([0; 4]);
+        seven.extend([0x17, 0x06, 0x01, 0x24, 0x06, 0xf1, 0x07, 0x01]);
+        assert_eq!(
+            detect("a.7z", &seven, "application/x-7z-compressed"),
+            Some(LockedContainer::SevenZip)
+        );
+        assert!(is_photos_library(Path::new(
+            "/Users/me/Pictures/Photos Library.photoslibrary"
+        )));
+    }
+
+    #[test]
+    fn it_reports_or_skips_locked_files() {
+        let tree = TempTree::new("locked-walk");
+        tree.file("secret.zip", &[b'P', b'K', 3, 4, 20, 0, 9, 0, 8, 0]);
+        tree.file("open.zip", &[b'P', b'K', 3, 4, 20, 0, 0, 0, 8, 0]);
+        tree.sample("sample_640x426.jpg", "photo.jpg");
+
+        // Unwanted kinds are left out, unless they are locked.
+        let results: Vec<_> = MediaWalker::new(tree.path())
+            .locked_files(LockedFiles::Report)
+            .start()
+            .collect();
+        let locked: Vec<_> = results
+            .iter()
+            .filter_map(|r| Some((Path::new(&r.path).file_name()?, r.locked?)))
+            .collect();
+        assert_eq!(locked, vec![("secret.zip".as_ref(), LockedContainer::Zip)]);
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().any(|r| r.kind == Some(Kind::Archive)));
+
+        let mut handle = MediaWalker::new(tree.path())
+            .extra_kinds(&[Kind::Archive])
+            .locked_files(LockedFiles::Skip)
+            .start();
+        let mut found = 0;
+        for event in handle.events() {
+            match event {
+                WalkEvent::Found(walk_result) => {
+                    assert!(!walk_result.path.ends_with("secret.zip"));
+                    found += 1;
+                }
+                WalkEvent::Skipped { path, reason } => {
+                    assert_eq!(
+                        (path.file_name().unwrap(), reason),
+                        ("secret.zip".as_ref(), SkipReason::Locked)
+                    );
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(found, 2);
+    }
+}
@@ -0,0 +1,236 @@
+//! Running several walks at once, see [`WalkManager`].
+use crate::walker::QUEUE_CAPACITY;
+use crate::{MediaWalker, SessionId, WalkEvent, WalkStats};
+use std::collections::VecDeque;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Runs several walks, of libraries to index at once, a few at a time
+/// and within a budget of threads, with their events on a single
+/// channel, see [`ManagedEvent`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use mediawalker::{ManagedEvent, MediaWalker, WalkEvent, WalkManager};
+/// let walks = WalkManager::new()
+///     .walk(MediaWalker::new("/srv/photos"))
+///     .walk(MediaWalker::new("/srv/music"))
+///     .thread_budget(8)
+///     .start();
+/// for event in walks {
+///     if let ManagedEvent::Event { session_id, event: WalkEvent::Found(r) } = event {
+///         println!("{}\t{}", session_id, r.path);
+///     }
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct WalkManager {
+    walks: Vec<MediaWalker>,
+    concurrency: Option<usize>,
+    thread_budget: Option<usize>,
+}
+
+/// An event of one of the walks of a [`WalkManager`], tagged with the
+/// walk's [`SessionId`].
+#[derive(Debug)]
+// Nearly all events are found files, which are not worth boxing.
+#[allow(clippy::large_enum_variant)]
+pub enum ManagedEvent {
+    /// A walk started, before any of its events.
+    Started {
+        /// The walk.
+        session_id: SessionId,
+        /// The root it walks.
+        root: PathBuf,
+    },
+    /// An event of a walk.
+    Event {
+        /// The walk.
+        session_id: SessionId,
+        /// The event.
+        event: WalkEvent,
+    },
+    /// A walk ended, after all of its events.
+    Finished {
+        /// The walk.
+        session_id: SessionId,
+        /// Its final statistics.
+        stats: WalkStats,
+    },
+}
+
+impl WalkManager {
+    /// Creates a manager with no walks.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a walk, to be started in the order added.
+    pub fn walk(mut self, walker: MediaWalker) -> Self {
+        self.walks.push(walker);
+        self
+    }
+
+    /// How many walks run at a time, the others waiting for their turn.
+    /// Defaults to all of them.
+    pub fn concurrency(mut self, walks: usize) -> Self {
+        self.concurrency = Some(walks.max(1));
+        self
+    }
+
+    /// How many threads inspect files across the walks running at a
+    /// time, a share of them going to each, replacing their own
+    /// [`threads`](MediaWalker::threads): the budget of IO the walks
+    /// share. Defaults to the walks' own.
+    pub fn thread_budget(mut self, threads: usize) -> Self {
+        self.thread_budget = Some(threads.max(1));
+        self
+    }
+
+    /// Starts the walks in background threads.
+    pub fn start(self) -> ManagedWalks {
+        let concurrency = self
+            .concurrency
+            .unwrap_or(self.walks.len())
+            .min(self.walks.len());
+        let threads = self
+            .thread_budget
+            .map(|budget| (budget / concurrency.max(1)).max(1));
+        let queue = Arc::new(Mutex::new(VecDeque::from(self.walks)));
+        let (tx, rx) = mpsc::sync_channel(QUEUE_CAPACITY);
+        let stopped = Arc::new(AtomicBool::new(false));
+        for _ in 0..concurrency {
+            let queue = Arc::clone(&queue);
+            let tx = tx.clone();
+            let stopped = Arc::clone(&stopped);
+            thread::spawn(move || loop {
+                let Some(mut walker) = queue.lock().unwrap().pop_front() else {
+                    return;
+                };
+                if stopped.load(Ordering::Relaxed) {
+                    return;
+                }
+                if let Some(threads) = threads {
+                    walker = walker.threads(threads);
+                }
+                if !run(walker, &tx, &stopped) {
+                    return;
+                }
+            });
+        }
+        ManagedWalks { rx, stopped }
+    }
+}
+
+/// Runs a walk, sending its events. Returns `false` once no one is
+/// listening.
+fn run(walker: MediaWalker, tx: &SyncSender<ManagedEvent>, stopped: &AtomicBool) -> bool {
+    let root = walker.root().to_path_buf();
+    let mut handle = walker.start();
+    let session_id = handle.session_id();
+    if tx.send(ManagedEvent::Started { session_id, root }).is_err() {
+        handle.stop();
+        return false;
+    }
+    for event in handle.events() {
+        let event = ManagedEvent::Event { session_id, event };
+        if stopped.load(Ordering::Relaxed) || tx.send(event).is_err() {
+            handle.stop();
+            return false;
+        }
+    }
+    let stats = handle.stats();
+    tx.send(ManagedEvent::Finished { session_id, stats })
+        .is_ok()
+}
+
+/// The running walks of a [`WalkManager`]. Iterate over it to receive
+/// their events, which end once all the walks have. Dropping it stops
+/// the walks.
+pub struct ManagedWalks {
+    rx: Receiver<ManagedEvent>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl ManagedWalks {
+    /// Stops the walks. Events already on their way may still be
+    /// received.
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+impl fmt::Debug for ManagedWalks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ManagedWalks")
+            .field("stopped", &self.stopped)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Iterator for ManagedWalks {
+    type Item = ManagedEvent;
+
+    fn next(&mut self) -> Option<ManagedEvent> {
+        self.rx.recv().ok()
+    }
+}
+
+impl Drop for ManagedWalks {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempTree;
+    use std::collections::HashMap;
+
+    #[test]
+    fn it_runs_walks_a_few_at_a_time() {
+        let tree = TempTree::new("manager");
+        tree.sample("sample_640x426.jpg", "photo.jpg");
+        tree.sample("sample3.mp3", "song.mp3");
+        let walks = WalkManager::new()
+            .walk(MediaWalker::new("resources/test"))
+            .walk(MediaWalker::new(tree.path()))
+            .walk(MediaWalker::new(tree.path()))
+            .concurrency(2)
+            .thread_budget(4)
+            .start();
+        let mut found: HashMap<SessionId, usize> = HashMap::new();
+        let mut running = 0;
+        let mut finished = vec![];
+        for event in walks {
+            match event {
+                ManagedEvent::Started { session_id, .. } => {
+                    running += 1;
+                    assert!(running <= 2);
+                    found.insert(session_id, 0);
+                }
+                ManagedEvent::Event {
+                    session_id,
+                    event: WalkEvent::Found(walk_result),
+                } => {
+                    assert_eq!(walk_result.session_id, Some(session_id));
+                    *found.get_mut(&session_id).unwrap() += 1;
+                }
+                ManagedEvent::Event { .. } => {}
+                ManagedEvent::Finished { session_id, stats } => {
+                    running -= 1;
+                    assert_eq!(stats.files, found[&session_id] as u64);
+                    finished.push(found[&session_id]);
+                }
+            }
+        }
+        finished.sort();
+        assert_eq!(finished, [2, 2, 9]);
+    }
+}
@@ -0,0 +1,463 @@
+//! Read-only memory maps of files, see
+//! [`MediaWalker::mmap`](crate::MediaWalker::mmap).
+//!
+//! On Unix, reading a page of a map past the end of a file truncated
+//! after it was mapped raises SIGBUS. While maps are open, a handler of
+//! the signal maps zeroed pages over the rest of the map the page is in,
+//! and reading the map fails as the file having been truncated. Faults
+//! elsewhere are left to the handler there was before. Windows does not
+//! let mapped files be truncated.
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{compiler_fence, Ordering};
+
+/// A read-only memory map of a whole file.
+pub(crate) struct Mmap {
+    ptr: *const u8,
+    len: usize,
+    /// Where the map is registered with the handler of faults, unused
+    /// where there is none.
+    #[allow(dead_code)]
+    slot: usize,
+}
+
+// SAFETY: the map is read-only and owned.
+unsafe impl Send for Mmap {}
+unsafe impl Sync for Mmap {}
+
+#[cfg(any(
+    target_os = "macos",
+    all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    )
+))]
+mod sys {
+    use super::Mmap;
+    use std::fs::File;
+    use std::io;
+    use std::os::raw::{c_int, c_long, c_void};
+    use std::os::unix::io::AsRawFd;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Once;
+
+    extern "C" {
+        fn mmap(
+            addr: *mut c_void,
+            len: usize,
+            prot: c_int,
+            flags: c_int,
+            fd: c_int,
+            offset: i64,
+        ) -> *mut c_void;
+        fn munmap(addr: *mut c_void, len: usize) -> c_int;
+        fn sigaction(signal: c_int, action: *const SigAction, old: *mut SigAction) -> c_int;
+        fn sysconf(name: c_int) -> c_long;
+    }
+
+    const PROT_READ: c_int = 0x1;
+    const MAP_PRIVATE: c_int = 0x02;
+    const MAP_FIXED: c_int = 0x10;
+    const MAP_FAILED: *mut c_void = !0 as *mut c_void;
+    const SIG_DFL: usize = 0;
+    const SIG_IGN: usize = 1;
+
+    #[cfg(target_os = "linux")]
+    const MAP_ANONYMOUS: c_int = 0x20;
+    #[cfg(target_os = "linux")]
+    const SIGBUS: c_int = 7;
+    #[cfg(target_os = "linux")]
+    const SA_SIGINFO: c_int = 0x4;
+    #[cfg(target_os = "linux")]
+    const SC_PAGESIZE: c_int = 30;
+
+    #[cfg(target_os = "macos")]
+    const MAP_ANONYMOUS: c_int = 0x1000;
+    #[cfg(target_os = "macos")]
+    const SIGBUS: c_int = 10;
+    #[cfg(target_os = "macos")]
+    const SA_SIGINFO: c_int = 0x40;
+    #[cfg(target_os = "macos")]
+    const SC_PAGESIZE: c_int = 29;
+
+    /// The `struct sigaction` of glibc and musl.
+    #[cfg(target_os = "linux")]
+    #[repr(C)]
+    struct SigAction {
+        handler: usize,
+        mask: [u64; 16],
+        flags: c_int,
+        restorer: usize,
+    }
+
+    /// The `struct sigaction` of macOS.
+    #[cfg(target_os = "macos")]
+    #[repr(C)]
+    struct SigAction {
+        handler: usize,
+        mask: u32,
+        flags: c_int,
+    }
+
+    impl SigAction {
+        fn new(handler: usize, flags: c_int) -> Self {
+            // SAFETY: all zeros is an empty mask, and no restorer.
+            let mut action: SigAction = unsafe { std::mem::zeroed() };
+            action.handler = handler;
+            action.flags = flags;
+            action
+        }
+    }
+
+    /// The start of `siginfo_t`, up to the faulting address.
+    #[repr(C)]
+    struct SigInfo {
+        #[cfg(target_os = "linux")]
+        head: [c_int; 4],
+        #[cfg(target_os = "macos")]
+        head: [c_int; 6],
+        addr: usize,
+    }
+
+    /// How many maps can be open at once, one per sniffing thread being
+    /// plenty. Files are read instead when all are taken.
+    const SLOTS: usize = 256;
+
+    /// A map the handler of faults knows about.
+    struct Slot {
+        taken: AtomicBool,
+        /// Where the map starts, 0 while there is none.
+        start: AtomicUsize,
+        len: AtomicUsize,
+        truncated: AtomicBool,
+    }
+
+    #[allow(clippy::declare_interior_mutable_const)]
+    const FREE: Slot = Slot {
+        taken: AtomicBool::new(false),
+        start: AtomicUsize::new(0),
+        len: AtomicUsize::new(0),
+        truncated: AtomicBool::new(false),
+    };
+
+    static MAPS: [Slot; SLOTS] = [FREE; SLOTS];
+    static PAGE: AtomicUsize = AtomicUsize::new(0);
+    static INSTALLED: Once = Once::new();
+    static INSTALL_FAILED: AtomicBool = AtomicBool::new(false);
+    /// The handler of SIGBUS there was before, and whether it takes a
+    /// `siginfo_t`.
+    static PREVIOUS: AtomicUsize = AtomicUsize::new(SIG_DFL);
+    static PREVIOUS_SIGINFO: AtomicBool = AtomicBool::new(false);
+
+    extern "C" fn on_sigbus(signal: c_int, info: *mut SigInfo, context: *mut c_void) {
+        // SAFETY: the kernel passes a valid `siginfo_t` with SA_SIGINFO.
+        let addr = unsafe { (*info).addr };
+        for slot in &MAPS {
+            let start = slot.start.load(Ordering::Acquire);
+            let len = slot.len.load(Ordering::Relaxed);
+            if start == 0 || addr < start || addr >= start + len {
+                continue;
+            }
+            let from = addr & !(PAGE.load(Ordering::Relaxed) - 1);
+            // SAFETY: replaces pages of a map of ours, which nothing but
+            // the read that faulted is looking at.
+            let zeroed = unsafe {
+                mmap(
+                    from as *mut c_void,
+                    start + len - from,
+                    PROT_READ,
+                    MAP_PRIVATE | MAP_FIXED | MAP_ANONYMOUS,
+                    -1,
+                    0,
+                )
+            };
+            if zeroed != MAP_FAILED {
+                slot.truncated.store(true, Ordering::Relaxed);
+                return;
+            }
+            break;
+        }
+        match PREVIOUS.load(Ordering::Relaxed) {
+            SIG_DFL | SIG_IGN => {
+                // Faulting again on return, with the default action.
+                let default = SigAction::new(SIG_DFL, 0);
+                // SAFETY: sigaction is async-signal-safe.
+                unsafe { sigaction(SIGBUS, &default, std::ptr::null_mut()) };
+            }
+            handler if PREVIOUS_SIGINFO.load(Ordering::Relaxed) => {
+                // SAFETY: the handler was installed to be called so.
+                let handler: extern "C" fn(c_int, *mut SigInfo, *mut c_void) =
+                    unsafe { std::mem::transmute(handler) };
+                handler(signal, info, context);
+            }
+            handler => {
+                // SAFETY: the handler was installed to be called so.
+                let handler: extern "C" fn(c_int) = unsafe { std::mem::transmute(handler) };
+                handler(signal);
+            }
+        }
+    }
+
+    /// Installs the handler of faults, once.
+    fn install() -> bool {
+        INSTALLED.call_once(|| {
+            // SAFETY: sysconf has no preconditions.
+            PAGE.store(unsafe { sysconf(SC_PAGESIZE) } as usize, Ordering::Relaxed);
+            let action = SigAction::new(on_sigbus as *const () as usize, SA_SIGINFO);
+            let mut previous = SigAction::new(SIG_DFL, 0);
+            // SAFETY: installs a handler only touching atomics and maps
+            // of ours, keeping the one there was to hand other faults to.
+            if unsafe { sigaction(SIGBUS, &action, &mut previous) } != 0 {
+                INSTALL_FAILED.store(true, Ordering::Relaxed);
+                return;
+            }
+            PREVIOUS.store(previous.handler, Ordering::Relaxed);
+            PREVIOUS_SIGINFO.store(previous.flags & SA_SIGINFO != 0, Ordering::Relaxed);
+        });
+        !INSTALL_FAILED.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn map(file: &File, len: usize) -> io::Result<Option<Mmap>> {
+        if !install() {
+            return Ok(None);
+        }
+        let Some(slot) = MAPS.iter().position(|slot| {
+            slot.taken
+                .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+        }) else {
+            return Ok(None);
+        };
+        // SAFETY: maps `len` bytes of an open file read-only; the result
+        // is checked before use.
+        let ptr = unsafe {
+            mmap(
+                std::ptr::null_mut(),
+                len,
+                PROT_READ,
+                MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == MAP_FAILED {
+            MAPS[slot].taken.store(false, Ordering::Release);
+            return Err(io::Error::last_os_error());
+        }
+        MAPS[slot].truncated.store(false, Ordering::Relaxed);
+        MAPS[slot].len.store(len, Ordering::Relaxed);
+        MAPS[slot].start.store(ptr as usize, Ordering::Release);
+        Ok(Some(Mmap {
+            ptr: ptr.cast(),
+            len,
+            slot,
+        }))
+    }
+
+    pub(super) fn truncated(map: &Mmap) -> bool {
+        MAPS[map.slot].truncated.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn unmap(map: &Mmap) {
+        let slot = &MAPS[map.slot];
+        slot.start.store(0, Ordering::Release);
+        // SAFETY: unmaps what `map` mapped, zeroed pages and all.
+        unsafe { munmap(map.ptr as *mut c_void, map.len) };
+        slot.taken.store(false, Ordering::Release);
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use super::Mmap;
+    use std::fs::File;
+    use std::io;
+    use std::os::raw::c_void;
+    use std::os::windows::io::AsRawHandle;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn CreateFileMappingW(
+            file: *mut c_void,
+            attributes: *mut c_void,
+            protect: u32,
+            size_high: u32,
+            size_low: u32,
+            name: *const u16,
+        ) -> *mut c_void;
+        fn MapViewOfFile(
+            mapping: *mut c_void,
+            access: u32,
+            offset_high: u32,
+            offset_low: u32,
+            len: usize,
+        ) -> *mut c_void;
+        fn UnmapViewOfFile(addr: *const c_void) -> i32;
+        fn CloseHandle(handle: *mut c_void) -> i32;
+    }
+
+    const PAGE_READONLY: u32 = 0x02;
+    const FILE_MAP_READ: u32 = 0x04;
+
+    pub(super) fn map(file: &File, len: usize) -> io::Result<Option<Mmap>> {
+        // SAFETY: creates a read-only mapping of an open file; the view
+        // keeps the mapping alive after its handle is closed.
+        unsafe {
+            let mapping = CreateFileMappingW(
+                file.as_raw_handle(),
+                std::ptr::null_mut(),
+                PAGE_READONLY,
+                0,
+                0,
+                std::ptr::null(),
+            );
+            if mapping.is_null() {
+                return Err(io::Error::last_os_error());
+            }
+            let ptr = MapViewOfFile(mapping, FILE_MAP_READ, 0, 0, len);
+            let err = io::Error::last_os_error();
+            CloseHandle(mapping);
+            if ptr.is_null() {
+                return Err(err);
+            }
+            Ok(Some(Mmap {
+                ptr: ptr.cast(),
+                len,
+                slot: 0,
+            }))
+        }
+    }
+
+    /// Mapped files cannot be truncated.
+    pub(super) fn truncated(_map: &Mmap) -> bool {
+        false
+    }
+
+    pub(super) fn unmap(map: &Mmap) {
+        // SAFETY: unmaps what `map` mapped.
+        unsafe { UnmapViewOfFile(map.ptr.cast()) };
+    }
+}
+
+#[cfg(not(any(
+    windows,
+    target_os = "macos",
+    all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    )
+)))]
+mod sys {
+    use super::Mmap;
+    use std::fs::File;
+    use std::io;
+
+    /// Files are not mapped where faults on truncated files are not
+    /// handled.
+    pub(super) fn map(_file: &File, _len: usize) -> io::Result<Option<Mmap>> {
+        Ok(None)
+    }
+
+    pub(super) fn truncated(_map: &Mmap) -> bool {
+        false
+    }
+
+    pub(super) fn unmap(_map: &Mmap) {}
+}
+
+impl Mmap {
+    /// Maps a whole file, or returns `None` when it is not to be mapped
+    /// here, to be read instead.
+    pub(crate) fn open(path: &Path) -> io::Result<Option<Mmap>> {
+        let file = File::open(path)?;
+        let len = usize::try_from(file.metadata()?.len())
+            .map_err(|_| io::Error::other("file too large to map"))?;
+        if len == 0 {
+            // Empty maps are not allowed.
+            return Ok(Some(Mmap {
+                ptr: std::ptr::null(),
+                len,
+                slot: 0,
+            }));
+        }
+        sys::map(&file, len)
+    }
+
+    /// Runs `read` over the bytes of the map, failing if the file was
+    /// truncated while they were read, when what `read` saw past the new
+    /// end of the file was zeros.
+    pub(crate) fn read<T>(&self, read: impl FnOnce(&[u8]) -> T) -> io::Result<T> {
+        if self.len == 0 {
+            return Ok(read(&[]));
+        }
+        // SAFETY: `ptr` maps `len` readable bytes for as long as `self`,
+        // pages past the end of a truncated file being replaced by zeros.
+        let read = read(unsafe { std::slice::from_raw_parts(self.ptr, self.len) });
+        // The flag is set by the handler of faults, in between.
+        compiler_fence(Ordering::SeqCst);
+        if sys::truncated(self) {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "file truncated while mapped",
+            ));
+        }
+        Ok(read)
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        if self.len != 0 {
+            sys::unmap(self);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempTree;
+
+    #[test]
+    fn it_maps_files() {
+        let tree = TempTree::new("mmap");
+        let file = tree.file("a.bin", b"hello");
+        let empty = tree.file("empty.bin", b"");
+        let read = |path: &Path| match Mmap::open(path).unwrap() {
+            Some(map) => map.read(<[u8]>::to_vec).unwrap(),
+            None => std::fs::read(path).unwrap(),
+        };
+        assert_eq!(read(&file), b"hello");
+        assert!(read(&empty).is_empty());
+        assert!(Mmap::open(&tree.path().join("missing")).is_err());
+    }
+
+    #[cfg(any(
+        target_os = "macos",
+        all(
+            target_os = "linux",
+            any(target_arch = "x86_64", target_arch = "aarch64")
+        )
+    ))]
+    #[test]
+    fn it_fails_reading_files_truncated_while_mapped() {
+        let tree = TempTree::new("mmap-truncated");
+        let path = tree.file("big.bin", &vec![7; 64 * 1024]);
+        let map = Mmap::open(&path).unwrap().unwrap();
+        assert_eq!(map.read(|bytes| bytes[1000]).unwrap(), 7);
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(100)
+            .unwrap();
+        let err = map.read(|bytes| bytes.iter().map(|&b| b as u64).sum::<u64>());
+        assert_eq!(err.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        drop(map);
+
+        // Maps opened later are not taken as truncated.
+        let map = Mmap::open(&path).unwrap().unwrap();
+        assert_eq!(map.read(|bytes| bytes.len()).unwrap(), 100);
+    }
+}
@@ -0,0 +1,235 @@
+//! Keeping photos and their motion together: Apple Live Photos, paired
+//! by [`pair_live_photos`], and Android motion photos, found by
+//! [`MediaWalker::motion_photos`](crate::MediaWalker::motion_photos).
+use crate::{Kind, MediaWalkResult};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// How much XMP is read at most, which is plenty for the few fields
+/// looked for.
+const MAX_XMP: u64 = 1024 * 1024;
+
+/// The motion component of a photo, see [`MediaWalkResult::motion`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Motion {
+    /// The photo's motion is the video at this path, as for a Live
+    /// Photo.
+    Video(String),
+    /// The video is the motion of the photo at this path.
+    Photo(String),
+    /// The video is embedded in the photo, as in an Android motion photo.
+    Embedded {
+        /// Where the video starts in the file, in bytes.
+        offset: u64,
+        /// The size of the video, in bytes.
+        len: u64,
+    },
+}
+
+/// Pairs the halves of Live Photos among the results of a scan: a HEIF
+/// or JPEG photo and a video of the same name, up to case, in the same
+/// directory, like `IMG_0001.HEIC` and `IMG_0001.MOV`. The video is
+/// usually QuickTime, but exports may have converted it. Both get the
+/// path of the other in [`MediaWalkResult::motion`]. A video next to a
+/// photo in several formats, like a HEIC and its exported JPEG, is
+/// paired with each of them and points at the first by path.
+///
+/// # Examples
+///
+/// ```
+/// use mediawalker::{pair_live_photos, MediaWalker, Motion};
+/// let mut results: Vec<_> = MediaWalker::new(env!("CARGO_MANIFEST_DIR"))
+///     .start()
+///     .collect();
+/// pair_live_photos(&mut results);
+/// for walk_result in &results {
+///     if let Some(Motion::Video(video)) = &walk_result.motion {
+///         println!("{} moves in {}", walk_result.path, video);
+///     }
+/// }
+/// ```
+pub fn pair_live_photos(results: &mut [MediaWalkResult]) {
+    let mut photos: HashMap<(String, String), Vec<usize>> = HashMap::new();
+    let mut videos = HashMap::new();
+    for (i, walk_result) in results.iter().enumerate() {
+        if walk_result.result.is_err() {
+            continue;
+        }
+        let Some(key) = pairing_key(&walk_result.path) else {
+            continue;
+        };
+        let mime = &*walk_result.mime;
+        if walk_result.kind == Some(Kind::Image) && matches!(mime, "image/heif" | "image/jpeg") {
+            photos.entry(key).or_default().push(i);
+        } else if walk_result.kind == Some(Kind::Video) {
+            videos.insert(key, i);
+        }
+    }
+    for (key, mut photos) in photos {
+        let Some(&video) = videos.get(&key) else {
+            continue;
+        };
+        photos.sort_by(|a, b| results[*a].path.cmp(&results[*b].path));
+        let video_path = results[video].path.clone();
+        results[video].motion = Some(Motion::Photo(results[photos[0]].path.clone()));
+        for photo in photos {
+            results[photo].motion = Some(Motion::Video(video_path.clone()));
+        }
+    }
+}
+
+/// The directory and lowercased stem of a path, which the halves of a
+/// Live Photo share.
+fn pairing_key(path: &str) -> Option<(String, String)> {
+    let path = Path::new(path);
+    let dir = path.parent()?.to_string_lossy().into_owned();
+    let stem = path.file_stem()?.to_string_lossy().to_lowercase();
+    Some((dir, stem))
+}
+
+/// Finds the video embedded in a JPEG motion photo, as its XMP tells.
+pub(crate) fn embedded(path: &Path) -> Option<Motion> {
+    let mut file = BufReader::new(File::open(path).ok()?);
+    let xmp = jpeg_xmp(&mut file).ok()??;
+    let len = file.get_ref().metadata().ok()?.len();
+    let video_len = embedded_len(&xmp)?;
+    let offset = len.checked_sub(video_len)?;
+    Some(Motion::Embedded {
+        offset,
+        len: video_len,
+    })
+}
+
+/// The size of the video at the end of a motion photo, from its XMP:
+/// the length of the motion photo item of its container directory, or
+/// the offset of the older micro videos, counted from the end.
+fn embedded_len(xmp: &str) -> Option<u64> {
+    if let Some(at) = xmp.find("Item:Semantic=\"MotionPhoto\"") {
+        let start = xmp[..at].rfind('<')?;
+        let end = at + xmp[at..].find('>')?;
+        return attribute(&xmp[start..end], "Item:Length");
+    }
+    if attribute(xmp, "GCamera:MicroVideo") == Some(1) {
+        return attribute(xmp, "GCamera:MicroVideoOffset");
+    }
+    None
+}
+
+/// The number an XMP property is set to, as an attribute or an element.
+fn attribute(xmp: &str, name: &str) -> Option<u64> {
+    let mut from = 0;
+    while let Some(at) = xmp[from..].find(name) {
+        let rest = &xmp[from + at + name.len()..];
+        let value = rest.strip_prefix("=\"").or_else(|| rest.strip_prefix('>'));
+        if let Some(value) = value {
+            let end = value.find(['"', '<']).unwrap_or(value.len());
+            return value[..end].trim().parse().ok();
+        }
+        from += at + name.len();
+    }
+    None
+}
+
+/// Reads the XMP packet of a JPEG, if it has one before the image data.
+fn jpeg_xmp<R: Read + Seek>(reader: &mut R) -> io::Result<Option<String>> {
+    let mut marker = [0; 2];
+    reader.read_exact(&mut marker)?;
+    if marker != [0xff, 0xd8] {
+        return Err(io::ErrorKind::InvalidData.into());
+    }
+    loop {
+        reader.read_exact(&mut marker)?;
+        if marker[0] != 0xff {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+        match marker[1] {
+            // Padding, and markers without a length.
+            0xff | 0x01 | 0xd0..=0xd7 => continue,
+            // The image data starts, or the image ends.
+            0xda | 0xd9 => return Ok(None),
+            _ => {}
+        }
+        let mut len = [0; 2];
+        reader.read_exact(&mut len)?;
+        let len = u64::from(u16::from_be_bytes(len)).saturating_sub(2);
+        if marker[1] != 0xe1 || len > MAX_XMP {
+            reader.seek(SeekFrom::Current(len as i64))?;
+            continue;
+        }
+        let mut data = vec![0; len as usize];
+        reader.read_exact(&mut data)?;
+        if let Some(xmp) = data.strip_prefix(b"http://ns.adobe.com/xap/1.0/\0") {
+            return Ok(Some(String::from_utf8_lossy(xmp).into_owned()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempTree;
+    use crate::MediaWalker;
+
+    /// A JPEG with an XMP packet, followed by an embedded video.
+    fn motion_photo(xmp: &str, video: &[u8]) -> Vec<u8> {
+        let mut app1 = b"http://ns.adobe.com/xap/1.0/\0".to_vec();
+        app1.extend(xmp.as_bytes());
+        let mut bytes = vec![0xff, 0xd8, 0xff, 0xe1];
+        bytes.extend(((app1.len() + 2) as u16).to_be_bytes());
+        bytes.extend(app1);
+        bytes.extend([0xff, 0xda, 0, 2, 0xff, 0xd9]);
+        bytes.extend(video);
+        bytes
+    }
+
+    #[test]
+    fn it_pairs_live_photos() {
+        let tree = TempTree::new("live-photos");
+        tree.sample("sample_640x426.jpg", "IMG_0001.JPG");
+        tree.sample("sample1.heic", "IMG_0001.HEIC");
+        tree.sample("sample_640x360.mp4", "IMG_0001.MOV");
+        tree.sample("sample1.heic", "IMG_0002.HEIC");
+        tree.sample("sample1.heic", "other/IMG_0003.HEIC");
+        tree.sample("sample_640x360.mp4", "IMG_0003.MOV");
+        let video = b"\0\0\0\x18ftypmp42\0\0\0\0mp42isom";
+        let container = r#"<Container:Item Item:Mime="video/mp4"
+            Item:Semantic="MotionPhoto" Item:Length="24" Item:Padding="0"/>"#;
+        tree.file("PXL_0001.MP.jpg", &motion_photo(container, video));
+        let micro = r#"GCamera:MicroVideo="1" GCamera:MicroVideoOffset="24""#;
+        tree.file("MVIMG_0001.jpg", &motion_photo(micro, video));
+
+        let mut results: Vec<_> = MediaWalker::new(tree.path())
+            .motion_photos(true)
+            .start()
+            .collect();
+        pair_live_photos(&mut results);
+        let path = |name: &str| tree.path().join(name).to_str().unwrap().to_string();
+        let motion = |name: &str| {
+            let path = path(name);
+            let walk_result = results.iter().find(|r| r.path == path).unwrap();
+            walk_result.motion.clone()
+        };
+        let video = Some(Motion::Video(path("IMG_0001.MOV")));
+        assert_eq!(motion("IMG_0001.HEIC"), video);
+        assert_eq!(motion("IMG_0001.JPG"), video);
+        assert_eq!(
+            motion("IMG_0001.MOV"),
+            Some(Motion::Photo(path("IMG_0001.HEIC")))
+        );
+        assert_eq!(motion("IMG_0002.HEIC"), None);
+        assert_eq!(motion("other/IMG_0003.HEIC"), None);
+        assert_eq!(motion("IMG_0003.MOV"), None);
+        // The videos are the last 24 bytes of the photos.
+        let embedded = |name: &str| {
+            let len = std::fs::metadata(path(name)).unwrap().len();
+            Some(Motion::Embedded {
+                offset: len - 24,
+                len: 24,
+            })
+        };
+        assert_eq!(motion("PXL_0001.MP.jpg"), embedded("PXL_0001.MP.jpg"));
+        assert_eq!(motion("MVIMG_0001.jpg"), embedded("MVIMG_0001.jpg"));
+    }
+}
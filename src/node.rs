@@ -0,0 +1,536 @@
+//! Node.js bindings over N-API, behind the `node` feature, for the
+//! wrapper in `mediawalker-node`. The N-API functions are looked up in
+//! the process when Node loads the library, so it builds and links
+//! without Node around.
+use crate::{Kind, MediaWalkResult, MediaWalker, WalkHandle};
+use std::ffi::{c_void, CString};
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+type Env = *mut c_void;
+type Value = *mut c_void;
+type Info = *mut c_void;
+type Ref = *mut c_void;
+type Work = *mut c_void;
+type Status = c_int;
+type Callback = unsafe extern "C" fn(Env, Info) -> Value;
+type Finalize = unsafe extern "C" fn(Env, *mut c_void, *mut c_void);
+type Execute = unsafe extern "C" fn(Env, *mut c_void);
+type Complete = unsafe extern "C" fn(Env, Status, *mut c_void);
+
+/// `napi_ok`.
+const OK: Status = 0;
+
+/// The `napi_valuetype`s of `undefined` and `null`.
+const UNDEFINED: c_int = 0;
+const NULL: c_int = 1;
+
+/// A `napi_type_tag`, marking externals as made by this module.
+#[repr(C)]
+struct TypeTag {
+    lower: u64,
+    upper: u64,
+}
+
+/// The tag of the externals wrapping walks: those of other modules,
+/// wrapping something else, are not to be taken for walks.
+const WALK_TAG: TypeTag = TypeTag {
+    lower: 0x6d65_6469_6177_616c,
+    upper: 0x9f3c_41d2_7b0e_58a6,
+};
+
+#[cfg(target_os = "macos")]
+const RTLD_DEFAULT: *mut c_void = -2isize as *mut c_void;
+#[cfg(not(target_os = "macos"))]
+const RTLD_DEFAULT: *mut c_void = ptr::null_mut();
+
+extern "C" {
+    fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+}
+
+/// Declares the N-API functions used, and how to find them.
+macro_rules! napi {
+    ($($name:ident($($arg:ty),*);)*) => {
+        /// The N-API functions used, as found in the process.
+        struct Napi {
+            $($name: unsafe extern "C" fn($($arg),*) -> Status,)*
+        }
+
+        impl Napi {
+            /// Looks up the functions, `None` if one is missing, as it
+            /// is outside of Node.
+            unsafe fn load() -> Option<Napi> {
+                Some(Napi {
+                    $($name: {
+                        let name = concat!(stringify!($name), "\0");
+                        let symbol = dlsym(RTLD_DEFAULT, name.as_ptr().cast());
+                        if symbol.is_null() {
+                            return None;
+                        }
+                        std::mem::transmute::<
+                            *mut c_void,
+                            unsafe extern "C" fn($($arg),*) -> Status,
+                        >(symbol)
+                    },)*
+                })
+            }
+        }
+    };
+}
+
+napi! {
+    napi_create_function(Env, *const c_char, usize, Callback, *mut c_void, *mut Value);
+    napi_set_named_property(Env, Value, *const c_char, Value);
+    napi_get_cb_info(Env, Info, *mut usize, *mut Value, *mut Value, *mut *mut c_void);
+    napi_typeof(Env, Value, *mut c_int);
+    napi_get_value_string_utf8(Env, Value, *mut c_char, usize, *mut usize);
+    napi_get_value_uint32(Env, Value, *mut u32);
+    napi_get_array_length(Env, Value, *mut u32);
+    napi_get_element(Env, Value, u32, *mut Value);
+    napi_create_external(Env, *mut c_void, Finalize, *mut c_void, *mut Value);
+    napi_get_value_external(Env, Value, *mut *mut c_void);
+    napi_type_tag_object(Env, Value, *const TypeTag);
+    napi_check_object_type_tag(Env, Value, *const TypeTag, *mut bool);
+    napi_create_object(Env, *mut Value);
+    napi_create_string_utf8(Env, *const c_char, usize, *mut Value);
+    napi_get_null(Env, *mut Value);
+    napi_get_undefined(Env, *mut Value);
+    napi_create_reference(Env, Value, u32, *mut Ref);
+    napi_get_reference_value(Env, Ref, *mut Value);
+    napi_delete_reference(Env, Ref);
+    napi_create_async_work(Env, Value, Value, Execute, Complete, *mut c_void, *mut Work);
+    napi_queue_async_work(Env, Work);
+    napi_delete_async_work(Env, Work);
+    napi_call_function(Env, Value, Value, usize, *const Value, *mut Value);
+    napi_throw_error(Env, *const c_char, *const c_char);
+}
+
+static NAPI: OnceLock<Napi> = OnceLock::new();
+
+/// The N-API functions, found when the module was registered.
+fn napi() -> &'static Napi {
+    NAPI.get().expect("the module is registered")
+}
+
+/// Turns an N-API status into a result.
+fn check(status: Status) -> Result<(), String> {
+    match status {
+        OK => Ok(()),
+        status => Err(format!("N-API call failed with status {}", status)),
+    }
+}
+
+/// A running walk, as handed to JavaScript.
+struct NodeWalk {
+    /// Locked by the worker thread waiting for the next result.
+    handle: Mutex<WalkHandle>,
+    /// Shared with the handle, so that the walk can be cancelled while
+    /// a worker thread waits on the handle.
+    stopped: Arc<AtomicBool>,
+}
+
+/// A call to `next`, from the main thread to a worker thread and back.
+struct Next {
+    walk: Arc<NodeWalk>,
+    callback: Ref,
+    work: Work,
+    result: Option<MediaWalkResult>,
+}
+
+/// Registers the module's functions: `start(root, kinds, threads)`,
+/// which returns a walk, `next(walk, callback)`, which calls back with
+/// the next result, or `null` once the walk is over, and
+/// `cancel(walk)`.
+///
+/// # Safety
+///
+/// Only to be called by Node, as it loads the module.
+#[no_mangle]
+pub unsafe extern "C" fn napi_register_module_v1(env: Env, exports: Value) -> Value {
+    let Some(api) = Napi::load() else {
+        return ptr::null_mut();
+    };
+    let api = NAPI.get_or_init(|| api);
+    let functions: [(&str, Callback); 3] =
+        [("start\0", start), ("next\0", next), ("cancel\0", cancel)];
+    for (name, function) in functions {
+        let mut value = ptr::null_mut();
+        let status = (api.napi_create_function)(
+            env,
+            name.as_ptr().cast(),
+            name.len() - 1,
+            function,
+            ptr::null_mut(),
+            &mut value,
+        );
+        if status != OK
+            || (api.napi_set_named_property)(env, exports, name.as_ptr().cast(), value) != OK
+        {
+            return ptr::null_mut();
+        }
+    }
+    exports
+}
+
+/// Runs a function called from JavaScript, throwing its error.
+unsafe fn throwing(env: Env, f: impl FnOnce() -> Result<Value, String>) -> Value {
+    match f() {
+        Ok(value) => value,
+        Err(message) => {
+            let message = CString::new(message).unwrap_or_default();
+            (napi().napi_throw_error)(env, ptr::null(), message.as_ptr());
+            ptr::null_mut()
+        }
+    }
+}
+
+/// The first `N` arguments of a call, `undefined` if missing.
+unsafe fn args<const N: usize>(env: Env, info: Info) -> Result<[Value; N], String> {
+    let mut args = [ptr::null_mut(); N];
+    let mut argc = N;
+    check((napi().napi_get_cb_info)(
+        env,
+        info,
+        &mut argc,
+        args.as_mut_ptr(),
+        ptr::null_mut(),
+        ptr::null_mut(),
+    ))?;
+    Ok(args)
+}
+
+/// Whether a value is `undefined` or `null`.
+unsafe fn is_nullish(env: Env, value: Value) -> Result<bool, String> {
+    let mut value_type = 0;
+    check((napi().napi_typeof)(env, value, &mut value_type))?;
+    Ok(value_type == UNDEFINED || value_type == NULL)
+}
+
+/// A JavaScript string as a Rust one.
+unsafe fn string(env: Env, value: Value) -> Result<String, String> {
+    let mut len = 0;
+    check((napi().napi_get_value_string_utf8)(
+        env,
+        value,
+        ptr::null_mut(),
+        0,
+        &mut len,
+    ))
+    .map_err(|_| "expected a string".to_string())?;
+    let mut bytes = vec![0u8; len + 1];
+    check((napi().napi_get_value_string_utf8)(
+        env,
+        value,
+        bytes.as_mut_ptr().cast(),
+        bytes.len(),
+        &mut len,
+    ))?;
+    bytes.truncate(len);
+    String::from_utf8(bytes).map_err(|err| err.to_string())
+}
+
+/// A Rust string as a JavaScript one, or `null`.
+unsafe fn js_string(env: Env, s: Option<&str>) -> Result<Value, String> {
+    let mut value = ptr::null_mut();
+    match s {
+        Some(s) => check((napi().napi_create_string_utf8)(
+            env,
+            s.as_ptr().cast(),
+            s.len(),
+            &mut value,
+        ))?,
+        None => check((napi().napi_get_null)(env, &mut value))?,
+    }
+    Ok(value)
+}
+
+/// The walk a value wraps, checking that it is an external made by
+/// `start` before looking into it.
+unsafe fn walk_of(env: Env, value: Value) -> Result<Arc<NodeWalk>, String> {
+    let api = napi();
+    let mut tagged = false;
+    let checked = check((api.napi_check_object_type_tag)(
+        env,
+        value,
+        &WALK_TAG,
+        &mut tagged,
+    ));
+    if checked.is_err() || !tagged {
+        return Err("expected a walk".to_string());
+    }
+    let mut walk = ptr::null_mut();
+    check((api.napi_get_value_external)(env, value, &mut walk))
+        .map_err(|_| "expected a walk".to_string())?;
+    Ok(Arc::clone(&*walk.cast::<Arc<NodeWalk>>()))
+}
+
+/// `start(root, kinds, threads)`: starts walking `root`, reporting the
+/// kinds named in `kinds` instead of the defaults, and inspecting files
+/// on `threads` threads, if given.
+unsafe extern "C" fn start(env: Env, info: Info) -> Value {
+    throwing(env, || {
+        let api = napi();
+        let [root, kinds, threads] = args(env, info)?;
+        let mut walker = MediaWalker::new(string(env, root)?);
+        if !is_nullish(env, kinds)? {
+            let mut len = 0;
+            check((api.napi_get_array_length)(env, kinds, &mut len))
+                .map_err(|_| "expected an array of kinds".to_string())?;
+            let mut names = vec![];
+            for i in 0..len {
+                let mut name = ptr::null_mut();
+                check((api.napi_get_element)(env, kinds, i, &mut name))?;
+                names.push(string(env, name)?.parse::<Kind>()?);
+            }
+            walker = walker.kinds(&names);
+        }
+        if !is_nullish(env, threads)? {
+            let mut count = 0;
+            check((api.napi_get_value_uint32)(env, threads, &mut count))
+                .map_err(|_| "expected a thread count".to_string())?;
+            walker = walker.threads(count as usize);
+        }
+        let handle = walker.start();
+        let stopped = handle.stop_flag();
+        let walk = Arc::new(NodeWalk {
+            handle: Mutex::new(handle),
+            stopped,
+        });
+        let data = Box::into_raw(Box::new(walk));
+        let mut value = ptr::null_mut();
+        if let Err(err) = check((api.napi_create_external)(
+            env,
+            data.cast(),
+            free_walk,
+            ptr::null_mut(),
+            &mut value,
+        )) {
+            free_walk(env, data.cast(), ptr::null_mut());
+            return Err(err);
+        }
+        // Freed with the external if tagging fails.
+        check((api.napi_type_tag_object)(env, value, &WALK_TAG))?;
+        Ok(value)
+    })
+}
+
+/// Stops and frees a walk, once JavaScript is done with it.
+unsafe extern "C" fn free_walk(_env: Env, data: *mut c_void, _hint: *mut c_void) {
+    let walk = Box::from_raw(data.cast::<Arc<NodeWalk>>());
+    walk.stopped.store(true, Ordering::Relaxed);
+}
+
+/// `next(walk, callback)`: waits for the next result on a worker thread,
+/// then calls `callback` with it, or with `null` once the walk is over.
+unsafe extern "C" fn next(env: Env, info: Info) -> Value {
+    throwing(env, || {
+        let api = napi();
+        let [walk, callback] = args(env, info)?;
+        let walk = walk_of(env, walk)?;
+        let mut next = Box::new(Next {
+            walk,
+            callback: ptr::null_mut(),
+            work: ptr::null_mut(),
+            result: None,
+        });
+        check((api.napi_create_reference)(
+            env,
+            callback,
+            1,
+            &mut next.callback,
+        ))?;
+        let name = js_string(env, Some("mediawalker.next"))?;
+        let next = Box::into_raw(next);
+        let queued = check((api.napi_create_async_work)(
+            env,
+            ptr::null_mut(),
+            name,
+            wait_for_next,
+            call_back,
+            next.cast(),
+            &mut (*next).work,
+        ))
+        .and_then(|()| check((api.napi_queue_async_work)(env, (*next).work)));
+        if let Err(err) = queued {
+            let next = Box::from_raw(next);
+            if !next.work.is_null() {
+                (api.napi_delete_async_work)(env, next.work);
+            }
+            (api.napi_delete_reference)(env, next.callback);
+            return Err(err);
+        }
+        let mut undefined = ptr::null_mut();
+        check((api.napi_get_undefined)(env, &mut undefined))?;
+        Ok(undefined)
+    })
+}
+
+/// Waits for the next result, on a worker thread.
+unsafe extern "C" fn wait_for_next(_env: Env, data: *mut c_void) {
+    let next = &mut *data.cast::<Next>();
+    next.result = next.walk.handle.lock().unwrap().next();
+}
+
+/// Hands the result to the callback of `next`, on the main thread.
+unsafe extern "C" fn call_back(env: Env, _status: Status, data: *mut c_void) {
+    let api = napi();
+    let next = Box::from_raw(data.cast::<Next>());
+    let call = || -> Result<(), String> {
+        let result = match &next.result {
+            Some(walk_result) => result(env, walk_result)?,
+            None => js_string(env, None)?,
+        };
+        let mut callback = ptr::null_mut();
+        check((api.napi_get_reference_value)(
+            env,
+            next.callback,
+            &mut callback,
+        ))?;
+        let mut undefined = ptr::null_mut();
+        check((api.napi_get_undefined)(env, &mut undefined))?;
+        // An exception thrown by the callback is left pending, for Node
+        // to report.
+        (api.napi_call_function)(env, undefined, callback, 1, &result, ptr::null_mut());
+        Ok(())
+    };
+    if let Err(err) = call() {
+        let message = CString::new(err).unwrap_or_default();
+        (api.napi_throw_error)(env, ptr::null(), message.as_ptr());
+    }
+    (api.napi_delete_reference)(env, next.callback);
+    (api.napi_delete_async_work)(env, next.work);
+}
+
+/// A result as a JavaScript object, with its `path`, `mime` type, `kind`
+/// name or `null`, `status` and `error`, like the Python bindings have
+/// it.
+unsafe fn result(env: Env, walk_result: &MediaWalkResult) -> Result<Value, String> {
+    let api = napi();
+    let (status, error) = match &walk_result.result {
+        Ok(true) => ("found", None),
+        Ok(false) => ("unknown", None),
+        Err(err) => ("error", Some(err.to_string())),
+    };
+    let mut object = ptr::null_mut();
+    check((api.napi_create_object)(env, &mut object))?;
+    let fields = [
+        ("path\0", Some(&*walk_result.path)),
+        ("mime\0", Some(&*walk_result.mime)),
+        ("kind\0", walk_result.kind.map(|kind| kind.name())),
+        ("status\0", Some(status)),
+        ("error\0", error.as_deref()),
+    ];
+    for (name, value) in fields {
+        let value = js_string(env, value)?;
+        check((api.napi_set_named_property)(
+            env,
+            object,
+            name.as_ptr().cast(),
+            value,
+        ))?;
+    }
+    Ok(object)
+}
+
+/// `cancel(walk)`: stops a walk. The pending `next` calls back with
+/// `null` soon after.
+unsafe extern "C" fn cancel(env: Env, info: Info) -> Value {
+    throwing(env, || {
+        let [walk] = args(env, info)?;
+        walk_of(env, walk)?.stopped.store(true, Ordering::Relaxed);
+        let mut undefined = ptr::null_mut();
+        check((napi().napi_get_undefined)(env, &mut undefined))?;
+        Ok(undefined)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+
+    /// Builds the module as Node loads it, into a target directory of its
+    /// own, as the one of the tests is locked while they are built.
+    fn build_module() -> PathBuf {
+        let manifest = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let target = manifest.join("target/node");
+        let cargo = env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+        let status = Command::new(cargo)
+            .current_dir(manifest)
+            .args([
+                "rustc",
+                "--lib",
+                "--features",
+                "node",
+                "--crate-type",
+                "cdylib",
+            ])
+            .arg("--target-dir")
+            .arg(&target)
+            .status()
+            .unwrap();
+        assert!(status.success(), "building the module failed");
+        let name = format!(
+            "{}mediawalker{}",
+            env::consts::DLL_PREFIX,
+            env::consts::DLL_SUFFIX
+        );
+        target.join("debug").join(name)
+    }
+
+    /// Walks through the wrapper in `mediawalker-node`, then hands the
+    /// module values that are not walks.
+    const SCRIPT: &str = r#"
+        const path = require('path');
+        const { walk } = require(process.env.WRAPPER);
+        (async () => {
+          const lines = [];
+          for await (const result of walk(process.env.ROOT, { kinds: ['audio'] })) {
+            lines.push(`${result.status} ${result.kind} ${path.basename(result.path)}`);
+          }
+          const module = { exports: {} };
+          process.dlopen(module, process.env.MEDIAWALKER_NODE);
+          for (const value of [{}, 42]) {
+            try {
+              module.exports.cancel(value);
+            } catch (err) {
+              lines.push(err.message);
+            }
+          }
+          console.log(lines.sort().join('\n'));
+        })();
+    "#;
+
+    #[test]
+    fn it_walks_through_node() {
+        if Command::new("node").arg("--version").output().is_err() {
+            eprintln!("skipping it_walks_through_node: node not found");
+            return;
+        }
+        let manifest = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let output = Command::new("node")
+            .arg("-e")
+            .arg(SCRIPT)
+            .env("MEDIAWALKER_NODE", build_module())
+            .env("WRAPPER", manifest.join("mediawalker-node/index.js"))
+            .env("ROOT", manifest.join("resources/test"))
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(output.status.success(), "node failed: {}", stderr);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert_eq!(
+            stdout.lines().collect::<Vec<_>>(),
+            [
+                "expected a walk",
+                "expected a walk",
+                "found audio sample3.mp3",
+                "found audio sample3.mp3",
+                "unknown null README.md",
+            ]
+        );
+    }
+}
@@ -0,0 +1,473 @@
+use std::str::FromStr;
+
+/// A Unicode normalization form for reported paths, see
+/// [`MediaWalker::normalize_paths`](crate::MediaWalker::normalize_paths).
+///
+/// Only the letters of the Latin, Greek and Cyrillic scripts, Hangul
+/// syllables and the voiced kana of Japanese are composed and
+/// decomposed, which covers the file names macOS and the other systems
+/// disagree on in practice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Normalization {
+    /// Composed characters, as used on Linux and Windows: `é` is a
+    /// single character.
+    Nfc,
+    /// Decomposed characters, as used on macOS: `é` is an `e` followed
+    /// by a combining acute accent.
+    Nfd,
+}
+
+impl Normalization {
+    /// Normalizes a string, returning `None` if it already is normal.
+    pub(crate) fn apply(self, s: &str) -> Option<String> {
+        if s.is_ascii() {
+            return None;
+        }
+        let decomposed = decompose(s);
+        let normalized: String = match self {
+            Normalization::Nfc => compose(decomposed).into_iter().collect(),
+            Normalization::Nfd => decomposed.into_iter().collect(),
+        };
+        (normalized != s).then_some(normalized)
+    }
+}
+
+impl FromStr for Normalization {
+    type Err = String;
+
+    /// Parses `nfc` or `nfd`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "nfc" => Ok(Normalization::Nfc),
+            "nfd" => Ok(Normalization::Nfd),
+            _ => Err(format!("unknown normalization: {}", s)),
+        }
+    }
+}
+
+const HANGUL_BASE: u32 = 0xAC00;
+const HANGUL_COUNT: u32 = 11172;
+const JAMO_L: u32 = 0x1100;
+const JAMO_V: u32 = 0x1161;
+const JAMO_T: u32 = 0x11A7;
+const JAMO_V_COUNT: u32 = 21;
+const JAMO_T_COUNT: u32 = 28;
+
+fn combining_class(c: char) -> u8 {
+    COMBINING_CLASSES
+        .binary_search_by_key(&c, |&(mark, _)| mark)
+        .map_or(0, |i| COMBINING_CLASSES[i].1)
+}
+
+/// Decomposes fully, with the marks in canonical order.
+fn decompose(s: &str) -> Vec<char> {
+    fn push(c: char, out: &mut Vec<char>) {
+        let code = c as u32;
+        if (HANGUL_BASE..HANGUL_BASE + HANGUL_COUNT).contains(&code) {
+            let index = code - HANGUL_BASE;
+            let t = index % JAMO_T_COUNT;
+            let chars = [
+                JAMO_L + index / (JAMO_V_COUNT * JAMO_T_COUNT),
+                JAMO_V + index % (JAMO_V_COUNT * JAMO_T_COUNT) / JAMO_T_COUNT,
+            ];
+            out.extend(chars.into_iter().filter_map(char::from_u32));
+            if t > 0 {
+                out.extend(char::from_u32(JAMO_T + t));
+            }
+            return;
+        }
+        match COMPOSITIONS.binary_search_by_key(&c, |&(composed, _, _)| composed) {
+            Ok(i) => {
+                let (_, base, mark) = COMPOSITIONS[i];
+                push(base, out);
+                out.push(mark);
+            }
+            Err(_) => out.push(c),
+        }
+    }
+    let mut out = Vec::with_capacity(s.len());
+    for c in s.chars() {
+        push(c, &mut out);
+    }
+    // Order each run of marks by combining class, keeping equal ones
+    // in place.
+    let mut start = 0;
+    while start < out.len() {
+        let len = out[start..]
+            .iter()
+            .take_while(|&&c| combining_class(c) != 0)
+            .count();
+        out[start..start + len].sort_by_key(|&c| combining_class(c));
+        start += len.max(1);
+    }
+    out
+}
+
+/// Composes a decomposed string.
+fn compose(decomposed: Vec<char>) -> Vec<char> {
+    let mut out: Vec<char> = Vec::with_capacity(decomposed.len());
+    let mut starter: Option<usize> = None;
+    // The combining class of the last character since the starter.
+    let mut last_class = 0;
+    for c in decomposed {
+        let class = combining_class(c);
+        if let Some(at) = starter {
+            let adjacent = out.len() == at + 1;
+            if adjacent || (last_class != 0 && last_class < class) {
+                if let Some(composed) = compose_pair(out[at], c) {
+                    out[at] = composed;
+                    continue;
+                }
+            }
+        }
+        if class == 0 {
+            starter = Some(out.len());
+        }
+        last_class = class;
+        out.push(c);
+    }
+    out
+}
+
+fn compose_pair(base: char, mark: char) -> Option<char> {
+    let (b, m) = (base as u32, mark as u32);
+    if (JAMO_L..JAMO_L + 19).contains(&b) && (JAMO_V..JAMO_V + JAMO_V_COUNT).contains(&m) {
+        let index = ((b - JAMO_L) * JAMO_V_COUNT + m - JAMO_V) * JAMO_T_COUNT;
+        return char::from_u32(HANGUL_BASE + index);
+    }
+    if (HANGUL_BASE..HANGUL_BASE + HANGUL_COUNT).contains(&b)
+        && (b - HANGUL_BASE).is_multiple_of(JAMO_T_COUNT)
+        && (JAMO_T + 1..JAMO_T + JAMO_T_COUNT).contains(&m)
+    {
+        return char::from_u32(b + m - JAMO_T);
+    }
+    COMPOSITIONS
+        .iter()
+        .find(|&&(_, b, m)| b == base && m == mark)
+        .map(|&(composed, _, _)| composed)
+}
+
+/// Canonical compositions of Latin, Greek and Cyrillic letters and of
+/// kana: the composed character, its base and its combining mark.
+/// Sorted by the composed character. Generated from the Unicode
+/// Character Database.
+#[rustfmt::skip]
+const COMPOSITIONS: &[(char, char, char)] = &[
+    ('\u{C0}', '\u{41}', '\u{300}'), ('\u{C1}', '\u{41}', '\u{301}'), ('\u{C2}', '\u{41}', '\u{302}'),
+    ('\u{C3}', '\u{41}', '\u{303}'), ('\u{C4}', '\u{41}', '\u{308}'), ('\u{C5}', '\u{41}', '\u{30A}'),
+    ('\u{C7}', '\u{43}', '\u{327}'), ('\u{C8}', '\u{45}', '\u{300}'), ('\u{C9}', '\u{45}', '\u{301}'),
+    ('\u{CA}', '\u{45}', '\u{302}'), ('\u{CB}', '\u{45}', '\u{308}'), ('\u{CC}', '\u{49}', '\u{300}'),
+    ('\u{CD}', '\u{49}', '\u{301}'), ('\u{CE}', '\u{49}', '\u{302}'), ('\u{CF}', '\u{49}', '\u{308}'),
+    ('\u{D1}', '\u{4E}', '\u{303}'), ('\u{D2}', '\u{4F}', '\u{300}'), ('\u{D3}', '\u{4F}', '\u{301}'),
+    ('\u{D4}', '\u{4F}', '\u{302}'), ('\u{D5}', '\u{4F}', '\u{303}'), ('\u{D6}', '\u{4F}', '\u{308}'),
+    ('\u{D9}', '\u{55}', '\u{300}'), ('\u{DA}', '\u{55}', '\u{301}'), ('\u{DB}', '\u{55}', '\u{302}'),
+    ('\u{DC}', '\u{55}', '\u{308}'), ('\u{DD}', '\u{59}', '\u{301}'), ('\u{E0}', '\u{61}', '\u{300}'),
+    ('\u{E1}', '\u{61}', '\u{301}'), ('\u{E2}', '\u{61}', '\u{302}'), ('\u{E3}', '\u{61}', '\u{303}'),
+    ('\u{E4}', '\u{61}', '\u{308}'), ('\u{E5}', '\u{61}', '\u{30A}'), ('\u{E7}', '\u{63}', '\u{327}'),
+    ('\u{E8}', '\u{65}', '\u{300}'), ('\u{E9}', '\u{65}', '\u{301}'), ('\u{EA}', '\u{65}', '\u{302}'),
+    ('\u{EB}', '\u{65}', '\u{308}'), ('\u{EC}', '\u{69}', '\u{300}'), ('\u{ED}', '\u{69}', '\u{301}'),
+    ('\u{EE}', '\u{69}', '\u{302}'), ('\u{EF}', '\u{69}', '\u{308}'), ('\u{F1}', '\u{6E}', '\u{303}'),
+    ('\u{F2}', '\u{6F}', '\u{300}'), ('\u{F3}', '\u{6F}', '\u{301}'), ('\u{F4}', '\u{6F}', '\u{302}'),
+    ('\u{F5}', '\u{6F}', '\u{303}'), ('\u{F6}', '\u{6F}', '\u{308}'), ('\u{F9}', '\u{75}', '\u{300}'),
+    ('\u{FA}', '\u{75}', '\u{301}'), ('\u{FB}', '\u{75}', '\u{302}'), ('\u{FC}', '\u{75}', '\u{308}'),
+    ('\u{FD}', '\u{79}', '\u{301}'), ('\u{FF}', '\u{79}', '\u{308}'), ('\u{100}', '\u{41}', '\u{304}'),
+    ('\u{101}', '\u{61}', '\u{304}'), ('\u{102}', '\u{41}', '\u{306}'), ('\u{103}', '\u{61}', '\u{306}'),
+    ('\u{104}', '\u{41}', '\u{328}'), ('\u{105}', '\u{61}', '\u{328}'), ('\u{106}', '\u{43}', '\u{301}'),
+    ('\u{107}', '\u{63}', '\u{301}'), ('\u{108}', '\u{43}', '\u{302}'), ('\u{109}', '\u{63}', '\u{302}'),
+    ('\u{10A}', '\u{43}', '\u{307}'), ('\u{10B}', '\u{63}', '\u{307}'), ('\u{10C}', '\u{43}', '\u{30C}'),
+    ('\u{10D}', '\u{63}', '\u{30C}'), ('\u{10E}', '\u{44}', '\u{30C}'), ('\u{10F}', '\u{64}', '\u{30C}'),
+    ('\u{112}', '\u{45}', '\u{304}'), ('\u{113}', '\u{65}', '\u{304}'), ('\u{114}', '\u{45}', '\u{306}'),
+    ('\u{115}', '\u{65}', '\u{306}'), ('\u{116}', '\u{45}', '\u{307}'), ('\u{117}', '\u{65}', '\u{307}'),
+    ('\u{118}', '\u{45}', '\u{328}'), ('\u{119}', '\u{65}', '\u{328}'), ('\u{11A}', '\u{45}', '\u{30C}'),
+    ('\u{11B}', '\u{65}', '\u{30C}'), ('\u{11C}', '\u{47}', '\u{302}'), ('\u{11D}', '\u{67}', '\u{302}'),
+    ('\u{11E}', '\u{47}', '\u{306}'), ('\u{11F}', '\u{67}', '\u{306}'), ('\u{120}', '\u{47}', '\u{307}'),
+    ('\u{121}', '\u{67}', '\u{307}'), ('\u{122}', '\u{47}', '\u{327}'), ('\u{123}', '\u{67}', '\u{327}'),
+    ('\u{124}', '\u{48}', '\u{302}'), ('\u{125}', '\u{68}', '\u{302}'), ('\u{128}', '\u{49}', '\u{303}'),
+    ('\u{129}', '\u{69}', '\u{303}'), ('\u{12A}', '\u{49}', '\u{304}'), ('\u{12B}', '\u{69}', '\u{304}'),
+    ('\u{12C}', '\u{49}', '\u{306}'), ('\u{12D}', '\u{69}', '\u{306}'), ('\u{12E}', '\u{49}', '\u{328}'),
+    ('\u{12F}', '\u{69}', '\u{328}'), ('\u{130}', '\u{49}', '\u{307}'), ('\u{134}', '\u{4A}', '\u{302}'),
+    ('\u{135}', '\u{6A}', '\u{302}'), ('\u{136}', '\u{4B}', '\u{327}'), ('\u{137}', '\u{6B}', '\u{327}'),
+    ('\u{139}', '\u{4C}', '\u{301}'), ('\u{13A}', '\u{6C}', '\u{301}'), ('\u{13B}', '\u{4C}', '\u{327}'),
+    ('\u{13C}', '\u{6C}', '\u{327}'), ('\u{13D}', '\u{4C}', '\u{30C}'), ('\u{13E}', '\u{6C}', '\u{30C}'),
+    ('\u{143}', '\u{4E}', '\u{301}'), ('\u{144}', '\u{6E}', '\u{301}'), ('\u{145}', '\u{4E}', '\u{327}'),
+    ('\u{146}', '\u{6E}', '\u{327}'), ('\u{147}', '\u{4E}', '\u{30C}'), ('\u{148}', '\u{6E}', '\u{30C}'),
+    ('\u{14C}', '\u{4F}', '\u{304}'), ('\u{14D}', '\u{6F}', '\u{304}'), ('\u{14E}', '\u{4F}', '\u{306}'),
+    ('\u{14F}', '\u{6F}', '\u{306}'), ('\u{150}', '\u{4F}', '\u{30B}'), ('\u{151}', '\u{6F}', '\u{30B}'),
+    ('\u{154}', '\u{52}', '\u{301}'), ('\u{155}', '\u{72}', '\u{301}'), ('\u{156}', '\u{52}', '\u{327}'),
+    ('\u{157}', '\u{72}', '\u{327}'), ('\u{158}', '\u{52}', '\u{30C}'), ('\u{159}', '\u{72}', '\u{30C}'),
+    ('\u{15A}', '\u{53}', '\u{301}'), ('\u{15B}', '\u{73}', '\u{301}'), ('\u{15C}', '\u{53}', '\u{302}'),
+    ('\u{15D}', '\u{73}', '\u{302}'), ('\u{15E}', '\u{53}', '\u{327}'), ('\u{15F}', '\u{73}', '\u{327}'),
+    ('\u{160}', '\u{53}', '\u{30C}'), ('\u{161}', '\u{73}', '\u{30C}'), ('\u{162}', '\u{54}', '\u{327}'),
+    ('\u{163}', '\u{74}', '\u{327}'), ('\u{164}', '\u{54}', '\u{30C}'), ('\u{165}', '\u{74}', '\u{30C}'),
+    ('\u{168}', '\u{55}', '\u{303}'), ('\u{169}', '\u{75}', '\u{303}'), ('\u{16A}', '\u{55}', '\u{304}'),
+    ('\u{16B}', '\u{75}', '\u{304}'), ('\u{16C}', '\u{55}', '\u{306}'), ('\u{16D}', '\u{75}', '\u{306}'),
+    ('\u{16E}', '\u{55}', '\u{30A}'), ('\u{16F}', '\u{75}', '\u{30A}'), ('\u{170}', '\u{55}', '\u{30B}'),
+    ('\u{171}', '\u{75}', '\u{30B}'), ('\u{172}', '\u{55}', '\u{328}'), ('\u{173}', '\u{75}', '\u{328}'),
+    ('\u{174}', '\u{57}', '\u{302}'), ('\u{175}', '\u{77}', '\u{302}'), ('\u{176}', '\u{59}', '\u{302}'),
+    ('\u{177}', '\u{79}', '\u{302}'), ('\u{178}', '\u{59}', '\u{308}'), ('\u{179}', '\u{5A}', '\u{301}'),
+    ('\u{17A}', '\u{7A}', '\u{301}'), ('\u{17B}', '\u{5A}', '\u{307}'), ('\u{17C}', '\u{7A}', '\u{307}'),
+    ('\u{17D}', '\u{5A}', '\u{30C}'), ('\u{17E}', '\u{7A}', '\u{30C}'), ('\u{1A0}', '\u{4F}', '\u{31B}'),
+    ('\u{1A1}', '\u{6F}', '\u{31B}'), ('\u{1AF}', '\u{55}', '\u{31B}'), ('\u{1B0}', '\u{75}', '\u{31B}'),
+    ('\u{1CD}', '\u{41}', '\u{30C}'), ('\u{1CE}', '\u{61}', '\u{30C}'), ('\u{1CF}', '\u{49}', '\u{30C}'),
+    ('\u{1D0}', '\u{69}', '\u{30C}'), ('\u{1D1}', '\u{4F}', '\u{30C}'), ('\u{1D2}', '\u{6F}', '\u{30C}'),
+    ('\u{1D3}', '\u{55}', '\u{30C}'), ('\u{1D4}', '\u{75}', '\u{30C}'), ('\u{1D5}', '\u{DC}', '\u{304}'),
+    ('\u{1D6}', '\u{FC}', '\u{304}'), ('\u{1D7}', '\u{DC}', '\u{301}'), ('\u{1D8}', '\u{FC}', '\u{301}'),
+    ('\u{1D9}', '\u{DC}', '\u{30C}'), ('\u{1DA}', '\u{FC}', '\u{30C}'), ('\u{1DB}', '\u{DC}', '\u{300}'),
+    ('\u{1DC}', '\u{FC}', '\u{300}'), ('\u{1DE}', '\u{C4}', '\u{304}'), ('\u{1DF}', '\u{E4}', '\u{304}'),
+    ('\u{1E0}', '\u{226}', '\u{304}'), ('\u{1E1}', '\u{227}', '\u{304}'), ('\u{1E2}', '\u{C6}', '\u{304}'),
+    ('\u{1E3}', '\u{E6}', '\u{304}'), ('\u{1E6}', '\u{47}', '\u{30C}'), ('\u{1E7}', '\u{67}', '\u{30C}'),
+    ('\u{1E8}', '\u{4B}', '\u{30C}'), ('\u{1E9}', '\u{6B}', '\u{30C}'), ('\u{1EA}', '\u{4F}', '\u{328}'),
+    ('\u{1EB}', '\u{6F}', '\u{328}'), ('\u{1EC}', '\u{1EA}', '\u{304}'), ('\u{1ED}', '\u{1EB}', '\u{304}'),
+    ('\u{1EE}', '\u{1B7}', '\u{30C}'), ('\u{1EF}', '\u{292}', '\u{30C}'), ('\u{1F0}', '\u{6A}', '\u{30C}'),
+    ('\u{1F4}', '\u{47}', '\u{301}'), ('\u{1F5}', '\u{67}', '\u{301}'), ('\u{1F8}', '\u{4E}', '\u{300}'),
+    ('\u{1F9}', '\u{6E}', '\u{300}'), ('\u{1FA}', '\u{C5}', '\u{301}'), ('\u{1FB}', '\u{E5}', '\u{301}'),
+    ('\u{1FC}', '\u{C6}', '\u{301}'), ('\u{1FD}', '\u{E6}', '\u{301}'), ('\u{1FE}', '\u{D8}', '\u{301}'),
+    ('\u{1FF}', '\u{F8}', '\u{301}'), ('\u{200}', '\u{41}', '\u{30F}'), ('\u{201}', '\u{61}', '\u{30F}'),
+    ('\u{202}', '\u{41}', '\u{311}'), ('\u{203}', '\u{61}', '\u{311}'), ('\u{204}', '\u{45}', '\u{30F}'),
+    ('\u{205}', '\u{65}', '\u{30F}'), ('\u{206}', '\u{45}', '\u{311}'), ('\u{207}', '\u{65}', '\u{311}'),
+    ('\u{208}', '\u{49}', '\u{30F}'), ('\u{209}', '\u{69}', '\u{30F}'), ('\u{20A}', '\u{49}', '\u{311}'),
+    ('\u{20B}', '\u{69}', '\u{311}'), ('\u{20C}', '\u{4F}', '\u{30F}'), ('\u{20D}', '\u{6F}', '\u{30F}'),
+    ('\u{20E}', '\u{4F}', '\u{311}'), ('\u{20F}', '\u{6F}', '\u{311}'), ('\u{210}', '\u{52}', '\u{30F}'),
+    ('\u{211}', '\u{72}', '\u{30F}'), ('\u{212}', '\u{52}', '\u{311}'), ('\u{213}', '\u{72}', '\u{311}'),
+    ('\u{214}', '\u{55}', '\u{30F}'), ('\u{215}', '\u{75}', '\u{30F}'), ('\u{216}', '\u{55}', '\u{311}'),
+    ('\u{217}', '\u{75}', '\u{311}'), ('\u{218}', '\u{53}', '\u{326}'), ('\u{219}', '\u{73}', '\u{326}'),
+    ('\u{21A}', '\u{54}', '\u{326}'), ('\u{21B}', '\u{74}', '\u{326}'), ('\u{21E}', '\u{48}', '\u{30C}'),
+    ('\u{21F}', '\u{68}', '\u{30C}'), ('\u{226}', '\u{41}', '\u{307}'), ('\u{227}', '\u{61}', '\u{307}'),
+    ('\u{228}', '\u{45}', '\u{327}'), ('\u{229}', '\u{65}', '\u{327}'), ('\u{22A}', '\u{D6}', '\u{304}'),
+    ('\u{22B}', '\u{F6}', '\u{304}'), ('\u{22C}', '\u{D5}', '\u{304}'), ('\u{22D}', '\u{F5}', '\u{304}'),
+    ('\u{22E}', '\u{4F}', '\u{307}'), ('\u{22F}', '\u{6F}', '\u{307}'), ('\u{230}', '\u{22E}', '\u{304}'),
+    ('\u{231}', '\u{22F}', '\u{304}'), ('\u{232}', '\u{59}', '\u{304}'), ('\u{233}', '\u{79}', '\u{304}'),
+    ('\u{385}', '\u{A8}', '\u{301}'), ('\u{386}', '\u{391}', '\u{301}'), ('\u{388}', '\u{395}', '\u{301}'),
+    ('\u{389}', '\u{397}', '\u{301}'), ('\u{38A}', '\u{399}', '\u{301}'), ('\u{38C}', '\u{39F}', '\u{301}'),
+    ('\u{38E}', '\u{3A5}', '\u{301}'), ('\u{38F}', '\u{3A9}', '\u{301}'), ('\u{390}', '\u{3CA}', '\u{301}'),
+    ('\u{3AA}', '\u{399}', '\u{308}'), ('\u{3AB}', '\u{3A5}', '\u{308}'), ('\u{3AC}', '\u{3B1}', '\u{301}'),
+    ('\u{3AD}', '\u{3B5}', '\u{301}'), ('\u{3AE}', '\u{3B7}', '\u{301}'), ('\u{3AF}', '\u{3B9}', '\u{301}'),
+    ('\u{3B0}', '\u{3CB}', '\u{301}'), ('\u{3CA}', '\u{3B9}', '\u{308}'), ('\u{3CB}', '\u{3C5}', '\u{308}'),
+    ('\u{3CC}', '\u{3BF}', '\u{301}'), ('\u{3CD}', '\u{3C5}', '\u{301}'), ('\u{3CE}', '\u{3C9}', '\u{301}'),
+    ('\u{3D3}', '\u{3D2}', '\u{301}'), ('\u{3D4}', '\u{3D2}', '\u{308}'), ('\u{400}', '\u{415}', '\u{300}'),
+    ('\u{401}', '\u{415}', '\u{308}'), ('\u{403}', '\u{413}', '\u{301}'), ('\u{407}', '\u{406}', '\u{308}'),
+    ('\u{40C}', '\u{41A}', '\u{301}'), ('\u{40D}', '\u{418}', '\u{300}'), ('\u{40E}', '\u{423}', '\u{306}'),
+    ('\u{419}', '\u{418}', '\u{306}'), ('\u{439}', '\u{438}', '\u{306}'), ('\u{450}', '\u{435}', '\u{300}'),
+    ('\u{451}', '\u{435}', '\u{308}'), ('\u{453}', '\u{433}', '\u{301}'), ('\u{457}', '\u{456}', '\u{308}'),
+    ('\u{45C}', '\u{43A}', '\u{301}'), ('\u{45D}', '\u{438}', '\u{300}'), ('\u{45E}', '\u{443}', '\u{306}'),
+    ('\u{476}', '\u{474}', '\u{30F}'), ('\u{477}', '\u{475}', '\u{30F}'), ('\u{4C1}', '\u{416}', '\u{306}'),
+    ('\u{4C2}', '\u{436}', '\u{306}'), ('\u{4D0}', '\u{410}', '\u{306}'), ('\u{4D1}', '\u{430}', '\u{306}'),
+    ('\u{4D2}', '\u{410}', '\u{308}'), ('\u{4D3}', '\u{430}', '\u{308}'), ('\u{4D6}', '\u{415}', '\u{306}'),
+    ('\u{4D7}', '\u{435}', '\u{306}'), ('\u{4DA}', '\u{4D8}', '\u{308}'), ('\u{4DB}', '\u{4D9}', '\u{308}'),
+    ('\u{4DC}', '\u{416}', '\u{308}'), ('\u{4DD}', '\u{436}', '\u{308}'), ('\u{4DE}', '\u{417}', '\u{308}'),
+    ('\u{4DF}', '\u{437}', '\u{308}'), ('\u{4E2}', '\u{418}', '\u{304}'), ('\u{4E3}', '\u{438}', '\u{304}'),
+    ('\u{4E4}', '\u{418}', '\u{308}'), ('\u{4E5}', '\u{438}', '\u{308}'), ('\u{4E6}', '\u{41E}', '\u{308}'),
+    ('\u{4E7}', '\u{43E}', '\u{308}'), ('\u{4EA}', '\u{4E8}', '\u{308}'), ('\u{4EB}', '\u{4E9}', '\u{308}'),
+    ('\u{4EC}', '\u{42D}', '\u{308}'), ('\u{4ED}', '\u{44D}', '\u{308}'), ('\u{4EE}', '\u{423}', '\u{304}'),
+    ('\u{4EF}', '\u{443}', '\u{304}'), ('\u{4F0}', '\u{423}', '\u{308}'), ('\u{4F1}', '\u{443}', '\u{308}'),
+    ('\u{4F2}', '\u{423}', '\u{30B}'), ('\u{4F3}', '\u{443}', '\u{30B}'), ('\u{4F4}', '\u{427}', '\u{308}'),
+    ('\u{4F5}', '\u{447}', '\u{308}'), ('\u{4F8}', '\u{42B}', '\u{308}'), ('\u{4F9}', '\u{44B}', '\u{308}'),
+    ('\u{1E00}', '\u{41}', '\u{325}'), ('\u{1E01}', '\u{61}', '\u{325}'), ('\u{1E02}', '\u{42}', '\u{307}'),
+    ('\u{1E03}', '\u{62}', '\u{307}'), ('\u{1E04}', '\u{42}', '\u{323}'), ('\u{1E05}', '\u{62}', '\u{323}'),
+    ('\u{1E06}', '\u{42}', '\u{331}'), ('\u{1E07}', '\u{62}', '\u{331}'), ('\u{1E08}', '\u{C7}', '\u{301}'),
+    ('\u{1E09}', '\u{E7}', '\u{301}'), ('\u{1E0A}', '\u{44}', '\u{307}'), ('\u{1E0B}', '\u{64}', '\u{307}'),
+    ('\u{1E0C}', '\u{44}', '\u{323}'), ('\u{1E0D}', '\u{64}', '\u{323}'), ('\u{1E0E}', '\u{44}', '\u{331}'),
+    ('\u{1E0F}', '\u{64}', '\u{331}'), ('\u{1E10}', '\u{44}', '\u{327}'), ('\u{1E11}', '\u{64}', '\u{327}'),
+    ('\u{1E12}', '\u{44}', '\u{32D}'), ('\u{1E13}', '\u{64}', '\u{32D}'), ('\u{1E14}', '\u{112}', '\u{300}'),
+    ('\u{1E15}', '\u{113}', '\u{300}'), ('\u{1E16}', '\u{112}', '\u{301}'), ('\u{1E17}', '\u{113}', '\u{301}'),
+    ('\u{1E18}', '\u{45}', '\u{32D}'), ('\u{1E19}', '\u{65}', '\u{32D}'), ('\u{1E1A}', '\u{45}', '\u{330}'),
+    ('\u{1E1B}', '\u{65}', '\u{330}'), ('\u{1E1C}', '\u{228}', '\u{306}'), ('\u{1E1D}', '\u{229}', '\u{306}'),
+    ('\u{1E1E}', '\u{46}', '\u{307}'), ('\u{1E1F}', '\u{66}', '\u{307}'), ('\u{1E20}', '\u{47}', '\u{304}'),
+    ('\u{1E21}', '\u{67}', '\u{304}'), ('\u{1E22}', '\u{48}', '\u{307}'), ('\u{1E23}', '\u{68}', '\u{307}'),
+    ('\u{1E24}', '\u{48}', '\u{323}'), ('\u{1E25}', '\u{68}', '\u{323}'), ('\u{1E26}', '\u{48}', '\u{308}'),
+    ('\u{1E27}', '\u{68}', '\u{308}'), ('\u{1E28}', '\u{48}', '\u{327}'), ('\u{1E29}', '\u{68}', '\u{327}'),
+    ('\u{1E2A}', '\u{48}', '\u{32E}'), ('\u{1E2B}', '\u{68}', '\u{32E}'), ('\u{1E2C}', '\u{49}', '\u{330}'),
+    ('\u{1E2D}', '\u{69}', '\u{330}'), ('\u{1E2E}', '\u{CF}', '\u{301}'), ('\u{1E2F}', '\u{EF}', '\u{301}'),
+    ('\u{1E30}', '\u{4B}', '\u{301}'), ('\u{1E31}', '\u{6B}', '\u{301}'), ('\u{1E32}', '\u{4B}', '\u{323}'),
+    ('\u{1E33}', '\u{6B}', '\u{323}'), ('\u{1E34}', '\u{4B}', '\u{331}'), ('\u{1E35}', '\u{6B}', '\u{331}'),
+    ('\u{1E36}', '\u{4C}', '\u{323}'), ('\u{1E37}', '\u{6C}', '\u{323}'), ('\u{1E38}', '\u{1E36}', '\u{304}'),
+    ('\u{1E39}', '\u{1E37}', '\u{304}'), ('\u{1E3A}', '\u{4C}', '\u{331}'), ('\u{1E3B}', '\u{6C}', '\u{331}'),
+    ('\u{1E3C}', '\u{4C}', '\u{32D}'), ('\u{1E3D}', '\u{6C}', '\u{32D}'), ('\u{1E3E}', '\u{4D}', '\u{301}'),
+    ('\u{1E3F}', '\u{6D}', '\u{301}'), ('\u{1E40}', '\u{4D}', '\u{307}'), ('\u{1E41}', '\u{6D}', '\u{307}'),
+    ('\u{1E42}', '\u{4D}', '\u{323}'), ('\u{1E43}', '\u{6D}', '\u{323}'), ('\u{1E44}', '\u{4E}', '\u{307}'),
+    ('\u{1E45}', '\u{6E}', '\u{307}'), ('\u{1E46}', '\u{4E}', '\u{323}'), ('\u{1E47}', '\u{6E}', '\u{323}'),
+    ('\u{1E48}', '\u{4E}', '\u{331}'), ('\u{1E49}', '\u{6E}', '\u{331}'), ('\u{1E4A}', '\u{4E}', '\u{32D}'),
+    ('\u{1E4B}', '\u{6E}', '\u{32D}'), ('\u{1E4C}', '\u{D5}', '\u{301}'), ('\u{1E4D}', '\u{F5}', '\u{301}'),
+    ('\u{1E4E}', '\u{D5}', '\u{308}'), ('\u{1E4F}', '\u{F5}', '\u{308}'), ('\u{1E50}', '\u{14C}', '\u{300}'),
+    ('\u{1E51}', '\u{14D}', '\u{300}'), ('\u{1E52}', '\u{14C}', '\u{301}'), ('\u{1E53}', '\u{14D}', '\u{301}'),
+    ('\u{1E54}', '\u{50}', '\u{301}'), ('\u{1E55}', '\u{70}', '\u{301}'), ('\u{1E56}', '\u{50}', '\u{307}'),
+    ('\u{1E57}', '\u{70}', '\u{307}'), ('\u{1E58}', '\u{52}', '\u{307}'), ('\u{1E59}', '\u{72}', '\u{307}'),
+    ('\u{1E5A}', '\u{52}', '\u{323}'), ('\u{1E5B}', '\u{72}', '\u{323}'), ('\u{1E5C}', '\u{1E5A}', '\u{304}'),
+    ('\u{1E5D}', '\u{1E5B}', '\u{304}'), ('\u{1E5E}', '\u{52}', '\u{331}'), ('\u{1E5F}', '\u{72}', '\u{331}'),
+    ('\u{1E60}', '\u{53}', '\u{307}'), ('\u{1E61}', '\u{73}', '\u{307}'), ('\u{1E62}', '\u{53}', '\u{323}'),
+    ('\u{1E63}', '\u{73}', '\u{323}'), ('\u{1E64}', '\u{15A}', '\u{307}'), ('\u{1E65}', '\u{15B}', '\u{307}'),
+    ('\u{1E66}', '\u{160}', '\u{307}'), ('\u{1E67}', '\u{161}', '\u{307}'), ('\u{1E68}', '\u{1E62}', '\u{307}'),
+    ('\u{1E69}', '\u{1E63}', '\u{307}'), ('\u{1E6A}', '\u{54}', '\u{307}'), ('\u{1E6B}', '\u{74}', '\u{307}'),
+    ('\u{1E6C}', '\u{54}', '\u{323}'), ('\u{1E6D}', '\u{74}', '\u{323}'), ('\u{1E6E}', '\u{54}', '\u{331}'),
+    ('\u{1E6F}', '\u{74}', '\u{331}'), ('\u{1E70}', '\u{54}', '\u{32D}'), ('\u{1E71}', '\u{74}', '\u{32D}'),
+    ('\u{1E72}', '\u{55}', '\u{324}'), ('\u{1E73}', '\u{75}', '\u{324}'), ('\u{1E74}', '\u{55}', '\u{330}'),
+    ('\u{1E75}', '\u{75}', '\u{330}'), ('\u{1E76}', '\u{55}', '\u{32D}'), ('\u{1E77}', '\u{75}', '\u{32D}'),
+    ('\u{1E78}', '\u{168}', '\u{301}'), ('\u{1E79}', '\u{169}', '\u{301}'), ('\u{1E7A}', '\u{16A}', '\u{308}'),
+    ('\u{1E7B}', '\u{16B}', '\u{308}'), ('\u{1E7C}', '\u{56}', '\u{303}'), ('\u{1E7D}', '\u{76}', '\u{303}'),
+    ('\u{1E7E}', '\u{56}', '\u{323}'), ('\u{1E7F}', '\u{76}', '\u{323}'), ('\u{1E80}', '\u{57}', '\u{300}'),
+    ('\u{1E81}', '\u{77}', '\u{300}'), ('\u{1E82}', '\u{57}', '\u{301}'), ('\u{1E83}', '\u{77}', '\u{301}'),
+    ('\u{1E84}', '\u{57}', '\u{308}'), ('\u{1E85}', '\u{77}', '\u{308}'), ('\u{1E86}', '\u{57}', '\u{307}'),
+    ('\u{1E87}', '\u{77}', '\u{307}'), ('\u{1E88}', '\u{57}', '\u{323}'), ('\u{1E89}', '\u{77}', '\u{323}'),
+    ('\u{1E8A}', '\u{58}', '\u{307}'), ('\u{1E8B}', '\u{78}', '\u{307}'), ('\u{1E8C}', '\u{58}', '\u{308}'),
+    ('\u{1E8D}', '\u{78}', '\u{308}'), ('\u{1E8E}', '\u{59}', '\u{307}'), ('\u{1E8F}', '\u{79}', '\u{307}'),
+    ('\u{1E90}', '\u{5A}', '\u{302}'), ('\u{1E91}', '\u{7A}', '\u{302}'), ('\u{1E92}', '\u{5A}', '\u{323}'),
+    ('\u{1E93}', '\u{7A}', '\u{323}'), ('\u{1E94}', '\u{5A}', '\u{331}'), ('\u{1E95}', '\u{7A}', '\u{331}'),
+    ('\u{1E96}', '\u{68}', '\u{331}'), ('\u{1E97}', '\u{74}', '\u{308}'), ('\u{1E98}', '\u{77}', '\u{30A}'),
+    ('\u{1E99}', '\u{79}', '\u{30A}'), ('\u{1E9B}', '\u{17F}', '\u{307}'), ('\u{1EA0}', '\u{41}', '\u{323}'),
+    ('\u{1EA1}', '\u{61}', '\u{323}'), ('\u{1EA2}', '\u{41}', '\u{309}'), ('\u{1EA3}', '\u{61}', '\u{309}'),
+    ('\u{1EA4}', '\u{C2}', '\u{301}'), ('\u{1EA5}', '\u{E2}', '\u{301}'), ('\u{1EA6}', '\u{C2}', '\u{300}'),
+    ('\u{1EA7}', '\u{E2}', '\u{300}'), ('\u{1EA8}', '\u{C2}', '\u{309}'), ('\u{1EA9}', '\u{E2}', '\u{309}'),
+    ('\u{1EAA}', '\u{C2}', '\u{303}'), ('\u{1EAB}', '\u{E2}', '\u{303}'), ('\u{1EAC}', '\u{1EA0}', '\u{302}'),
+    ('\u{1EAD}', '\u{1EA1}', '\u{302}'), ('\u{1EAE}', '\u{102}', '\u{301}'), ('\u{1EAF}', '\u{103}', '\u{301}'),
+    ('\u{1EB0}', '\u{102}', '\u{300}'), ('\u{1EB1}', '\u{103}', '\u{300}'), ('\u{1EB2}', '\u{102}', '\u{309}'),
+    ('\u{1EB3}', '\u{103}', '\u{309}'), ('\u{1EB4}', '\u{102}', '\u{303}'), ('\u{1EB5}', '\u{103}', '\u{303}'),
+    ('\u{1EB6}', '\u{1EA0}', '\u{306}'), ('\u{1EB7}', '\u{1EA1}', '\u{306}'), ('\u{1EB8}', '\u{45}', '\u{323}'),
+    ('\u{1EB9}', '\u{65}', '\u{323}'), ('\u{1EBA}', '\u{45}', '\u{309}'), ('\u{1EBB}', '\u{65}', '\u{309}'),
+    ('\u{1EBC}', '\u{45}', '\u{303}'), ('\u{1EBD}', '\u{65}', '\u{303}'), ('\u{1EBE}', '\u{CA}', '\u{301}'),
+    ('\u{1EBF}', '\u{EA}', '\u{301}'), ('\u{1EC0}', '\u{CA}', '\u{300}'), ('\u{1EC1}', '\u{EA}', '\u{300}'),
+    ('\u{1EC2}', '\u{CA}', '\u{309}'), ('\u{1EC3}', '\u{EA}', '\u{309}'), ('\u{1EC4}', '\u{CA}', '\u{303}'),
+    ('\u{1EC5}', '\u{EA}', '\u{303}'), ('\u{1EC6}', '\u{1EB8}', '\u{302}'), ('\u{1EC7}', '\u{1EB9}', '\u{302}'),
+    ('\u{1EC8}', '\u{49}', '\u{309}'), ('\u{1EC9}', '\u{69}', '\u{309}'), ('\u{1ECA}', '\u{49}', '\u{323}'),
+    ('\u{1ECB}', '\u{69}', '\u{323}'), ('\u{1ECC}', '\u{4F}', '\u{323}'), ('\u{1ECD}', '\u{6F}', '\u{323}'),
+    ('\u{1ECE}', '\u{4F}', '\u{309}'), ('\u{1ECF}', '\u{6F}', '\u{309}'), ('\u{1ED0}', '\u{D4}', '\u{301}'),
+    ('\u{1ED1}', '\u{F4}', '\u{301}'), ('\u{1ED2}', '\u{D4}', '\u{300}'), ('\u{1ED3}', '\u{F4}', '\u{300}'),
+    ('\u{1ED4}', '\u{D4}', '\u{309}'), ('\u{1ED5}', '\u{F4}', '\u{309}'), ('\u{1ED6}', '\u{D4}', '\u{303}'),
+    ('\u{1ED7}', '\u{F4}', '\u{303}'), ('\u{1ED8}', '\u{1ECC}', '\u{302}'), ('\u{1ED9}', '\u{1ECD}', '\u{302}'),
+    ('\u{1EDA}', '\u{1A0}', '\u{301}'), ('\u{1EDB}', '\u{1A1}', '\u{301}'), ('\u{1EDC}', '\u{1A0}', '\u{300}'),
+    ('\u{1EDD}', '\u{1A1}', '\u{300}'), ('\u{1EDE}', '\u{1A0}', '\u{309}'), ('\u{1EDF}', '\u{1A1}', '\u{309}'),
+    ('\u{1EE0}', '\u{1A0}', '\u{303}'), ('\u{1EE1}', '\u{1A1}', '\u{303}'), ('\u{1EE2}', '\u{1A0}', '\u{323}'),
+    ('\u{1EE3}', '\u{1A1}', '\u{323}'), ('\u{1EE4}', '\u{55}', '\u{323}'), ('\u{1EE5}', '\u{75}', '\u{323}'),
+    ('\u{1EE6}', '\u{55}', '\u{309}'), ('\u{1EE7}', '\u{75}', '\u{309}'), ('\u{1EE8}', '\u{1AF}', '\u{301}'),
+    ('\u{1EE9}', '\u{1B0}', '\u{301}'), ('\u{1EEA}', '\u{1AF}', '\u{300}'), ('\u{1EEB}', '\u{1B0}', '\u{300}'),
+    ('\u{1EEC}', '\u{1AF}', '\u{309}'), ('\u{1EED}', '\u{1B0}', '\u{309}'), ('\u{1EEE}', '\u{1AF}', '\u{303}'),
+    ('\u{1EEF}', '\u{1B0}', '\u{303}'), ('\u{1EF0}', '\u{1AF}', '\u{323}'), ('\u{1EF1}', '\u{1B0}', '\u{323}'),
+    ('\u{1EF2}', '\u{59}', '\u{300}'), ('\u{1EF3}', '\u{79}', '\u{300}'), ('\u{1EF4}', '\u{59}', '\u{323}'),
+    ('\u{1EF5}', '\u{79}', '\u{323}'), ('\u{1EF6}', '\u{59}', '\u{309}'), ('\u{1EF7}', '\u{79}', '\u{309}'),
+    ('\u{1EF8}', '\u{59}', '\u{303}'), ('\u{1EF9}', '\u{79}', '\u{303}'), ('\u{1F00}', '\u{3B1}', '\u{313}'),
+    ('\u{1F01}', '\u{3B1}', '\u{314}'), ('\u{1F02}', '\u{1F00}', '\u{300}'), ('\u{1F03}', '\u{1F01}', '\u{300}'),
+    ('\u{1F04}', '\u{1F00}', '\u{301}'), ('\u{1F05}', '\u{1F01}', '\u{301}'), ('\u{1F06}', '\u{1F00}', '\u{342}'),
+    ('\u{1F07}', '\u{1F01}', '\u{342}'), ('\u{1F08}', '\u{391}', '\u{313}'), ('\u{1F09}', '\u{391}', '\u{314}'),
+    ('\u{1F0A}', '\u{1F08}', '\u{300}'), ('\u{1F0B}', '\u{1F09}', '\u{300}'), ('\u{1F0C}', '\u{1F08}', '\u{301}'),
+    ('\u{1F0D}', '\u{1F09}', '\u{301}'), ('\u{1F0E}', '\u{1F08}', '\u{342}'), ('\u{1F0F}', '\u{1F09}', '\u{342}'),
+    ('\u{1F10}', '\u{3B5}', '\u{313}'), ('\u{1F11}', '\u{3B5}', '\u{314}'), ('\u{1F12}', '\u{1F10}', '\u{300}'),
+    ('\u{1F13}', '\u{1F11}', '\u{300}'), ('\u{1F14}', '\u{1F10}', '\u{301}'), ('\u{1F15}', '\u{1F11}', '\u{301}'),
+    ('\u{1F18}', '\u{395}', '\u{313}'), ('\u{1F19}', '\u{395}', '\u{314}'), ('\u{1F1A}', '\u{1F18}', '\u{300}'),
+    ('\u{1F1B}', '\u{1F19}', '\u{300}'), ('\u{1F1C}', '\u{1F18}', '\u{301}'), ('\u{1F1D}', '\u{1F19}', '\u{301}'),
+    ('\u{1F20}', '\u{3B7}', '\u{313}'), ('\u{1F21}', '\u{3B7}', '\u{314}'), ('\u{1F22}', '\u{1F20}', '\u{300}'),
+    ('\u{1F23}', '\u{1F21}', '\u{300}'), ('\u{1F24}', '\u{1F20}', '\u{301}'), ('\u{1F25}', '\u{1F21}', '\u{301}'),
+    ('\u{1F26}', '\u{1F20}', '\u{342}'), ('\u{1F27}', '\u{1F21}', '\u{342}'), ('\u{1F28}', '\u{397}', '\u{313}'),
+    ('\u{1F29}', '\u{397}', '\u{314}'), ('\u{1F2A}', '\u{1F28}', '\u{300}'), ('\u{1F2B}', '\u{1F29}', '\u{300}'),
+    ('\u{1F2C}', '\u{1F28}', '\u{301}'), ('\u{1F2D}', '\u{1F29}', '\u{301}'), ('\u{1F2E}', '\u{1F28}', '\u{342}'),
+    ('\u{1F2F}', '\u{1F29}', '\u{342}'), ('\u{1F30}', '\u{3B9}', '\u{313}'), ('\u{1F31}', '\u{3B9}', '\u{314}'),
+    ('\u{1F32}', '\u{1F30}', '\u{300}'), ('\u{1F33}', '\u{1F31}', '\u{300}'), ('\u{1F34}', '\u{1F30}', '\u{301}'),
+    ('\u{1F35}', '\u{1F31}', '\u{301}'), ('\u{1F36}', '\u{1F30}', '\u{342}'), ('\u{1F37}', '\u{1F31}', '\u{342}'),
+    ('\u{1F38}', '\u{399}', '\u{313}'), ('\u{1F39}', '\u{399}', '\u{314}'), ('\u{1F3A}', '\u{1F38}', '\u{300}'),
+    ('\u{1F3B}', '\u{1F39}', '\u{300}'), ('\u{1F3C}', '\u{1F38}', '\u{301}'), ('\u{1F3D}', '\u{1F39}', '\u{301}'),
+    ('\u{1F3E}', '\u{1F38}', '\u{342}'), ('\u{1F3F}', '\u{1F39}', '\u{342}'), ('\u{1F40}', '\u{3BF}', '\u{313}'),
+    ('\u{1F41}', '\u{3BF}', '\u{314}'), ('\u{1F42}', '\u{1F40}', '\u{300}'), ('\u{1F43}', '\u{1F41}', '\u{300}'),
+    ('\u{1F44}', '\u{1F40}', '\u{301}'), ('\u{1F45}', '\u{1F41}', '\u{301}'), ('\u{1F48}', '\u{39F}', '\u{313}'),
+    ('\u{1F49}', '\u{39F}', '\u{314}'), ('\u{1F4A}', '\u{1F48}', '\u{300}'), ('\u{1F4B}', '\u{1F49}', '\u{300}'),
+    ('\u{1F4C}', '\u{1F48}', '\u{301}'), ('\u{1F4D}', '\u{1F49}', '\u{301}'), ('\u{1F50}', '\u{3C5}', '\u{313}'),
+    ('\u{1F51}', '\u{3C5}', '\u{314}'), ('\u{1F52}', '\u{1F50}', '\u{300}'), ('\u{1F53}', '\u{1F51}', '\u{300}'),
+    ('\u{1F54}', '\u{1F50}', '\u{301}'), ('\u{1F55}', '\u{1F51}', '\u{301}'), ('\u{1F56}', '\u{1F50}', '\u{342}'),
+    ('\u{1F57}', '\u{1F51}', '\u{342}'), ('\u{1F59}', '\u{3A5}', '\u{314}'), ('\u{1F5B}', '\u{1F59}', '\u{300}'),
+    ('\u{1F5D}', '\u{1F59}', '\u{301}'), ('\u{1F5F}', '\u{1F59}', '\u{342}'), ('\u{1F60}', '\u{3C9}', '\u{313}'),
+    ('\u{1F61}', '\u{3C9}', '\u{314}'), ('\u{1F62}', '\u{1F60}', '\u{300}'), ('\u{1F63}', '\u{1F61}', '\u{300}'),
+    ('\u{1F64}', '\u{1F60}', '\u{301}'), ('\u{1F65}', '\u{1F61}', '\u{301}'), ('\u{1F66}', '\u{1F60}', '\u{342}'),
+    ('\u{1F67}', '\u{1F61}', '\u{342}'), ('\u{1F68}', '\u{3A9}', '\u{313}'), ('\u{1F69}', '\u{3A9}', '\u{314}'),
+    ('\u{1F6A}', '\u{1F68}', '\u{300}'), ('\u{1F6B}', '\u{1F69}', '\u{300}'), ('\u{1F6C}', '\u{1F68}', '\u{301}'),
+    ('\u{1F6D}', '\u{1F69}', '\u{301}'), ('\u{1F6E}', '\u{1F68}', '\u{342}'), ('\u{1F6F}', '\u{1F69}', '\u{342}'),
+    ('\u{1F70}', '\u{3B1}', '\u{300}'), ('\u{1F72}', '\u{3B5}', '\u{300}'), ('\u{1F74}', '\u{3B7}', '\u{300}'),
+    ('\u{1F76}', '\u{3B9}', '\u{300}'), ('\u{1F78}', '\u{3BF}', '\u{300}'), ('\u{1F7A}', '\u{3C5}', '\u{300}'),
+    ('\u{1F7C}', '\u{3C9}', '\u{300}'), ('\u{1F80}', '\u{1F00}', '\u{345}'), ('\u{1F81}', '\u{1F01}', '\u{345}'),
+    ('\u{1F82}', '\u{1F02}', '\u{345}'), ('\u{1F83}', '\u{1F03}', '\u{345}'), ('\u{1F84}', '\u{1F04}', '\u{345}'),
+    ('\u{1F85}', '\u{1F05}', '\u{345}'), ('\u{1F86}', '\u{1F06}', '\u{345}'), ('\u{1F87}', '\u{1F07}', '\u{345}'),
+    ('\u{1F88}', '\u{1F08}', '\u{345}'), ('\u{1F89}', '\u{1F09}', '\u{345}'), ('\u{1F8A}', '\u{1F0A}', '\u{345}'),
+    ('\u{1F8B}', '\u{1F0B}', '\u{345}'), ('\u{1F8C}', '\u{1F0C}', '\u{345}'), ('\u{1F8D}', '\u{1F0D}', '\u{345}'),
+    ('\u{1F8E}', '\u{1F0E}', '\u{345}'), ('\u{1F8F}', '\u{1F0F}', '\u{345}'), ('\u{1F90}', '\u{1F20}', '\u{345}'),
+    ('\u{1F91}', '\u{1F21}', '\u{345}'), ('\u{1F92}', '\u{1F22}', '\u{345}'), ('\u{1F93}', '\u{1F23}', '\u{345}'),
+    ('\u{1F94}', '\u{1F24}', '\u{345}'), ('\u{1F95}', '\u{1F25}', '\u{345}'), ('\u{1F96}', '\u{1F26}', '\u{345}'),
+    ('\u{1F97}', '\u{1F27}', '\u{345}'), ('\u{1F98}', '\u{1F28}', '\u{345}'), ('\u{1F99}', '\u{1F29}', '\u{345}'),
+    ('\u{1F9A}', '\u{1F2A}', '\u{345}'), ('\u{1F9B}', '\u{1F2B}', '\u{345}'), ('\u{1F9C}', '\u{1F2C}', '\u{345}'),
+    ('\u{1F9D}', '\u{1F2D}', '\u{345}'), ('\u{1F9E}', '\u{1F2E}', '\u{345}'), ('\u{1F9F}', '\u{1F2F}', '\u{345}'),
+    ('\u{1FA0}', '\u{1F60}', '\u{345}'), ('\u{1FA1}', '\u{1F61}', '\u{345}'), ('\u{1FA2}', '\u{1F62}', '\u{345}'),
+    ('\u{1FA3}', '\u{1F63}', '\u{345}'), ('\u{1FA4}', '\u{1F64}', '\u{345}'), ('\u{1FA5}', '\u{1F65}', '\u{345}'),
+    ('\u{1FA6}', '\u{1F66}', '\u{345}'), ('\u{1FA7}', '\u{1F67}', '\u{345}'), ('\u{1FA8}', '\u{1F68}', '\u{345}'),
+    ('\u{1FA9}', '\u{1F69}', '\u{345}'), ('\u{1FAA}', '\u{1F6A}', '\u{345}'), ('\u{1FAB}', '\u{1F6B}', '\u{345}'),
+    ('\u{1FAC}', '\u{1F6C}', '\u{345}'), ('\u{1FAD}', '\u{1F6D}', '\u{345}'), ('\u{1FAE}', '\u{1F6E}', '\u{345}'),
+    ('\u{1FAF}', '\u{1F6F}', '\u{345}'), ('\u{1FB0}', '\u{3B1}', '\u{306}'), ('\u{1FB1}', '\u{3B1}', '\u{304}'),
+    ('\u{1FB2}', '\u{1F70}', '\u{345}'), ('\u{1FB3}', '\u{3B1}', '\u{345}'), ('\u{1FB4}', '\u{3AC}', '\u{345}'),
+    ('\u{1FB6}', '\u{3B1}', '\u{342}'), ('\u{1FB7}', '\u{1FB6}', '\u{345}'), ('\u{1FB8}', '\u{391}', '\u{306}'),
+    ('\u{1FB9}', '\u{391}', '\u{304}'), ('\u{1FBA}', '\u{391}', '\u{300}'), ('\u{1FBC}', '\u{391}', '\u{345}'),
+    ('\u{1FC1}', '\u{A8}', '\u{342}'), ('\u{1FC2}', '\u{1F74}', '\u{345}'), ('\u{1FC3}', '\u{3B7}', '\u{345}'),
+    ('\u{1FC4}', '\u{3AE}', '\u{345}'), ('\u{1FC6}', '\u{3B7}', '\u{342}'), ('\u{1FC7}', '\u{1FC6}', '\u{345}'),
+    ('\u{1FC8}', '\u{395}', '\u{300}'), ('\u{1FCA}', '\u{397}', '\u{300}'), ('\u{1FCC}', '\u{397}', '\u{345}'),
+    ('\u{1FCD}', '\u{1FBF}', '\u{300}'), ('\u{1FCE}', '\u{1FBF}', '\u{301}'), ('\u{1FCF}', '\u{1FBF}', '\u{342}'),
+    ('\u{1FD0}', '\u{3B9}', '\u{306}'), ('\u{1FD1}', '\u{3B9}', '\u{304}'), ('\u{1FD2}', '\u{3CA}', '\u{300}'),
+    ('\u{1FD6}', '\u{3B9}', '\u{342}'), ('\u{1FD7}', '\u{3CA}', '\u{342}'), ('\u{1FD8}', '\u{399}', '\u{306}'),
+    ('\u{1FD9}', '\u{399}', '\u{304}'), ('\u{1FDA}', '\u{399}', '\u{300}'), ('\u{1FDD}', '\u{1FFE}', '\u{300}'),
+    ('\u{1FDE}', '\u{1FFE}', '\u{301}'), ('\u{1FDF}', '\u{1FFE}', '\u{342}'), ('\u{1FE0}', '\u{3C5}', '\u{306}'),
+    ('\u{1FE1}', '\u{3C5}', '\u{304}'), ('\u{1FE2}', '\u{3CB}', '\u{300}'), ('\u{1FE4}', '\u{3C1}', '\u{313}'),
+    ('\u{1FE5}', '\u{3C1}', '\u{314}'), ('\u{1FE6}', '\u{3C5}', '\u{342}'), ('\u{1FE7}', '\u{3CB}', '\u{342}'),
+    ('\u{1FE8}', '\u{3A5}', '\u{306}'), ('\u{1FE9}', '\u{3A5}', '\u{304}'), ('\u{1FEA}', '\u{3A5}', '\u{300}'),
+    ('\u{1FEC}', '\u{3A1}', '\u{314}'), ('\u{1FED}', '\u{A8}', '\u{300}'), ('\u{1FF2}', '\u{1F7C}', '\u{345}'),
+    ('\u{1FF3}', '\u{3C9}', '\u{345}'), ('\u{1FF4}', '\u{3CE}', '\u{345}'), ('\u{1FF6}', '\u{3C9}', '\u{342}'),
+    ('\u{1FF7}', '\u{1FF6}', '\u{345}'), ('\u{1FF8}', '\u{39F}', '\u{300}'), ('\u{1FFA}', '\u{3A9}', '\u{300}'),
+    ('\u{1FFC}', '\u{3A9}', '\u{345}'),
+    ('\u{304C}', '\u{304B}', '\u{3099}'), ('\u{304E}', '\u{304D}', '\u{3099}'), ('\u{3050}', '\u{304F}', '\u{3099}'),
+    ('\u{3052}', '\u{3051}', '\u{3099}'), ('\u{3054}', '\u{3053}', '\u{3099}'), ('\u{3056}', '\u{3055}', '\u{3099}'),
+    ('\u{3058}', '\u{3057}', '\u{3099}'), ('\u{305A}', '\u{3059}', '\u{3099}'), ('\u{305C}', '\u{305B}', '\u{3099}'),
+    ('\u{305E}', '\u{305D}', '\u{3099}'), ('\u{3060}', '\u{305F}', '\u{3099}'), ('\u{3062}', '\u{3061}', '\u{3099}'),
+    ('\u{3065}', '\u{3064}', '\u{3099}'), ('\u{3067}', '\u{3066}', '\u{3099}'), ('\u{3069}', '\u{3068}', '\u{3099}'),
+    ('\u{3070}', '\u{306F}', '\u{3099}'), ('\u{3071}', '\u{306F}', '\u{309A}'), ('\u{3073}', '\u{3072}', '\u{3099}'),
+    ('\u{3074}', '\u{3072}', '\u{309A}'), ('\u{3076}', '\u{3075}', '\u{3099}'), ('\u{3077}', '\u{3075}', '\u{309A}'),
+    ('\u{3079}', '\u{3078}', '\u{3099}'), ('\u{307A}', '\u{3078}', '\u{309A}'), ('\u{307C}', '\u{307B}', '\u{3099}'),
+    ('\u{307D}', '\u{307B}', '\u{309A}'), ('\u{3094}', '\u{3046}', '\u{3099}'), ('\u{309E}', '\u{309D}', '\u{3099}'),
+    ('\u{30AC}', '\u{30AB}', '\u{3099}'), ('\u{30AE}', '\u{30AD}', '\u{3099}'), ('\u{30B0}', '\u{30AF}', '\u{3099}'),
+    ('\u{30B2}', '\u{30B1}', '\u{3099}'), ('\u{30B4}', '\u{30B3}', '\u{3099}'), ('\u{30B6}', '\u{30B5}', '\u{3099}'),
+    ('\u{30B8}', '\u{30B7}', '\u{3099}'), ('\u{30BA}', '\u{30B9}', '\u{3099}'), ('\u{30BC}', '\u{30BB}', '\u{3099}'),
+    ('\u{30BE}', '\u{30BD}', '\u{3099}'), ('\u{30C0}', '\u{30BF}', '\u{3099}'), ('\u{30C2}', '\u{30C1}', '\u{3099}'),
+    ('\u{30C5}', '\u{30C4}', '\u{3099}'), ('\u{30C7}', '\u{30C6}', '\u{3099}'), ('\u{30C9}', '\u{30C8}', '\u{3099}'),
+    ('\u{30D0}', '\u{30CF}', '\u{3099}'), ('\u{30D1}', '\u{30CF}', '\u{309A}'), ('\u{30D3}', '\u{30D2}', '\u{3099}'),
+    ('\u{30D4}', '\u{30D2}', '\u{309A}'), ('\u{30D6}', '\u{30D5}', '\u{3099}'), ('\u{30D7}', '\u{30D5}', '\u{309A}'),
+    ('\u{30D9}', '\u{30D8}', '\u{3099}'), ('\u{30DA}', '\u{30D8}', '\u{309A}'), ('\u{30DC}', '\u{30DB}', '\u{3099}'),
+    ('\u{30DD}', '\u{30DB}', '\u{309A}'), ('\u{30F4}', '\u{30A6}', '\u{3099}'), ('\u{30F7}', '\u{30EF}', '\u{3099}'),
+    ('\u{30F8}', '\u{30F0}', '\u{3099}'), ('\u{30F9}', '\u{30F1}', '\u{3099}'), ('\u{30FA}', '\u{30F2}', '\u{3099}'),
+    ('\u{30FE}', '\u{30FD}', '\u{3099}'),
+];
+
+/// The canonical combining classes of the marks in [`COMPOSITIONS`],
+/// sorted.
+#[rustfmt::skip]
+const COMBINING_CLASSES: &[(char, u8)] = &[
+    ('\u{300}', 230), ('\u{301}', 230), ('\u{302}', 230), ('\u{303}', 230), ('\u{304}', 230),
+    ('\u{306}', 230), ('\u{307}', 230), ('\u{308}', 230), ('\u{309}', 230), ('\u{30A}', 230),
+    ('\u{30B}', 230), ('\u{30C}', 230), ('\u{30F}', 230), ('\u{311}', 230), ('\u{313}', 230),
+    ('\u{314}', 230), ('\u{31B}', 216), ('\u{323}', 220), ('\u{324}', 220), ('\u{325}', 220),
+    ('\u{326}', 220), ('\u{327}', 202), ('\u{328}', 202), ('\u{32D}', 220), ('\u{32E}', 220),
+    ('\u{330}', 220), ('\u{331}', 220), ('\u{342}', 230), ('\u{345}', 240),
+    ('\u{3099}', 8), ('\u{309A}', 8),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_normalizes_paths() {
+        let nfc = "/music/Åström/Việt/한국/Ωμέγα/Ёлка/がっこうのパン.mp3";
+        let nfd = "/music/A\u{30A}stro\u{308}m/Vie\u{323}\u{302}t/\
+                   \u{1112}\u{1161}\u{11AB}\u{1100}\u{116E}\u{11A8}/\
+                   Ωμε\u{301}γα/Е\u{308}лка/\
+                   か\u{3099}っこうのハ\u{309A}ン.mp3";
+        assert_eq!(Normalization::Nfd.apply(nfc).as_deref(), Some(nfd));
+        assert_eq!(Normalization::Nfc.apply(nfd).as_deref(), Some(nfc));
+        assert_eq!(Normalization::Nfc.apply(nfc), None);
+        assert_eq!(Normalization::Nfd.apply("/music/plain.mp3"), None);
+        // Marks out of canonical order are reordered before composing.
+        assert_eq!(
+            Normalization::Nfc.apply("e\u{302}\u{323}").as_deref(),
+            Some("ệ")
+        );
+    }
+}
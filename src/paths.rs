@@ -0,0 +1,359 @@
+use std::path::{Component, Path, PathBuf};
+
+/// `FILE_ATTRIBUTE_REPARSE_POINT`
+#[cfg(windows)]
+const REPARSE_POINT: u32 = 0x400;
+
+/// The prefix of extended-length (verbatim) Windows paths.
+const VERBATIM: &str = r"\\?\";
+/// The prefix of extended-length UNC paths.
+const VERBATIM_UNC: &str = r"\\?\UNC\";
+
+/// Returns the extended-length form (`\\?\C:\...`) of a path on Windows,
+/// so that paths longer than 260 characters can be opened and walked.
+/// Relative paths are made absolute, as extended-length paths must be.
+/// Elsewhere, returns the path as it is.
+pub(crate) fn extended_length(path: &Path) -> PathBuf {
+    if cfg!(windows) {
+        let absolute = if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            match std::env::current_dir() {
+                Ok(dir) => dir.join(path),
+                Err(_) => return path.to_path_buf(),
+            }
+        };
+        match absolute.to_str() {
+            Some(absolute) => PathBuf::from(to_extended(absolute)),
+            None => absolute,
+        }
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// Extensions of macOS bundles: directories that are logically single
+/// items, like applications and photo libraries.
+const BUNDLE_EXTENSIONS: &[&str] = &[
+    "app",
+    "bundle",
+    "framework",
+    "plugin",
+    "kext",
+    "photoslibrary",
+    "aplibrary",
+    "migratedphotolibrary",
+    "photolibrary",
+    "musiclibrary",
+    "tvlibrary",
+    "imovielibrary",
+    "fcpbundle",
+    "logicx",
+    "band",
+    "rtfd",
+    "pages",
+    "numbers",
+    "key",
+    "xcodeproj",
+    "xcworkspace",
+];
+
+/// Whether a directory is a macOS bundle, going by its extension.
+pub(crate) fn is_bundle(dir: &Path) -> bool {
+    dir.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            BUNDLE_EXTENSIONS
+                .iter()
+                .any(|bundle| bundle.eq_ignore_ascii_case(ext))
+        })
+}
+
+/// Names of trash and recycle bin directories, matched ignoring case.
+const TRASH_DIRS: &[&str] = &["$RECYCLE.BIN", "RECYCLER", "#recycle", ".Trashes", ".Trash"];
+
+/// Whether a path is a trash or recycle bin directory: one of
+/// [`TRASH_DIRS`], a per-user `.Trash-1000` on removable media, or the
+/// home trash in `.local/share/Trash`.
+pub(crate) fn is_trash(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+    TRASH_DIRS
+        .iter()
+        .any(|trash| trash.eq_ignore_ascii_case(name))
+        || name.starts_with(".Trash-")
+        || path.ends_with(".local/share/Trash")
+}
+
+/// Names of the cache and sidecar directories of cameras, photo editors
+/// and NAS indexers, matched ignoring case, a leading `*` matching any
+/// start: thumbnails, Adobe Bridge and Lightroom previews, Photo
+/// Mechanic, Synology and QNAP thumbnails, and AppleDouble forks.
+pub(crate) const WORKFLOW_CACHES: &[&str] = &[
+    ".thumbnails",
+    "Adobe Bridge Cache",
+    "*.lrdata",
+    "_gsdata_",
+    "@eaDir",
+    ".@__thumb",
+    ".AppleDouble",
+];
+
+/// Whether a path is named like one of the given cache directories, see
+/// [`WORKFLOW_CACHES`].
+pub(crate) fn is_workflow_cache(path: &Path, caches: &[String]) -> bool {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+    let name = name.to_lowercase();
+    caches.iter().any(|cache| {
+        let cache = cache.to_lowercase();
+        match cache.strip_prefix('*') {
+            Some(suffix) => name.ends_with(suffix),
+            None => name == cache,
+        }
+    })
+}
+
+/// The classic Windows path length limit, `MAX_PATH`.
+const MAX_PATH: usize = 260;
+/// The file name length limit of most filesystems, in bytes.
+const MAX_NAME: usize = 255;
+/// Device names Windows reserves, with or without an extension.
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// A trait of a path that some tools choke on, see
+/// [`MediaWalker::check_paths`](crate::MediaWalker::check_paths).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PathIssue {
+    /// The path is longer than 260 characters, the classic Windows limit.
+    TooLong,
+    /// A file or directory name is longer than 255 bytes.
+    NameTooLong,
+    /// The path contains control characters, like a newline.
+    ControlCharacter,
+    /// A name contains one of `<>:"|?*`, which Windows does not allow.
+    ReservedCharacter,
+    /// A name is a device name Windows reserves, like `CON` or `nul.txt`.
+    ReservedName,
+    /// A name ends in a space or a dot, which Windows strips.
+    TrailingSpaceOrDot,
+}
+
+/// Returns the issues of a path, each once, in the order they are
+/// listed in [`PathIssue`].
+pub(crate) fn issues(path: &str) -> Vec<PathIssue> {
+    let mut found = vec![];
+    if path.chars().count() > MAX_PATH {
+        found.push(PathIssue::TooLong);
+    }
+    let names: Vec<_> = Path::new(path)
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(name) => name.to_str(),
+            _ => None,
+        })
+        .collect();
+    if names.iter().any(|name| name.len() > MAX_NAME) {
+        found.push(PathIssue::NameTooLong);
+    }
+    if path.chars().any(char::is_control) {
+        found.push(PathIssue::ControlCharacter);
+    }
+    if names
+        .iter()
+        .any(|name| name.contains(['<', '>', ':', '"', '|', '?', '*']))
+    {
+        found.push(PathIssue::ReservedCharacter);
+    }
+    if names.iter().any(|name| {
+        let stem = name.split('.').next().unwrap_or_default().trim_end();
+        RESERVED_NAMES
+            .iter()
+            .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+    }) {
+        found.push(PathIssue::ReservedName);
+    }
+    if names
+        .iter()
+        .any(|name| name.ends_with(' ') || name.ends_with('.'))
+    {
+        found.push(PathIssue::TrailingSpaceOrDot);
+    }
+    found
+}
+
+/// Whether an entry is a directory reparse point on Windows: a junction
+/// or a directory symbolic link.
+#[cfg(windows)]
+pub(crate) fn is_reparse_dir(path: &Path, is_dir: bool) -> bool {
+    use std::os::windows::fs::MetadataExt;
+    is_dir
+        && std::fs::symlink_metadata(path)
+            .is_ok_and(|metadata| metadata.file_attributes() & REPARSE_POINT != 0)
+}
+
+/// Reparse points are a Windows thing.
+#[cfg(not(windows))]
+pub(crate) fn is_reparse_dir(_path: &Path, _is_dir: bool) -> bool {
+    false
+}
+
+/// Whether the filesystem of a directory ignores case, found by looking
+/// the directory or one of its ancestors up with the case of its name
+/// flipped. Where no name has letters to flip, goes by the platform.
+pub(crate) fn is_case_insensitive(dir: &Path) -> bool {
+    let Ok(dir) = std::fs::canonicalize(dir) else {
+        return cfg!(any(windows, target_os = "macos"));
+    };
+    for ancestor in dir.ancestors() {
+        let (Some(parent), Some(name)) = (ancestor.parent(), ancestor.file_name()) else {
+            continue;
+        };
+        let name = name.to_string_lossy();
+        let flipped: String = name
+            .chars()
+            .map(|c| match c {
+                c if c.is_lowercase() => c.to_uppercase().next().unwrap_or(c),
+                c => c.to_lowercase().next().unwrap_or(c),
+            })
+            .collect();
+        if flipped == name {
+            continue;
+        }
+        let flipped = parent.join(flipped);
+        return match (std::fs::metadata(ancestor), std::fs::metadata(&flipped)) {
+            (Ok(original), Ok(flipped)) => is_same_file(&original, &flipped),
+            _ => false,
+        };
+    }
+    cfg!(any(windows, target_os = "macos"))
+}
+
+#[cfg(unix)]
+fn is_same_file(a: &std::fs::Metadata, b: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    a.dev() == b.dev() && a.ino() == b.ino()
+}
+
+/// Off Unix, a flipped name that exists is taken to be the same entry.
+#[cfg(not(unix))]
+fn is_same_file(_a: &std::fs::Metadata, _b: &std::fs::Metadata) -> bool {
+    true
+}
+
+/// Converts an absolute Windows path to its extended-length form. The
+/// system does not process extended-length paths, so separators are
+/// normalized and `.` and `..` components resolved here.
+fn to_extended(absolute: &str) -> String {
+    if absolute.starts_with(VERBATIM) || absolute.starts_with(r"\\.\") {
+        return absolute.to_string();
+    }
+    let absolute = absolute.replace('/', "\\");
+    let (prefix, rest) = match absolute.strip_prefix(r"\\") {
+        Some(unc) => (VERBATIM_UNC.to_string(), unc.to_string()),
+        None => (VERBATIM.to_string(), absolute),
+    };
+    let mut components: Vec<&str> = vec![];
+    for component in rest.split('\\') {
+        match component {
+            "" | "." => {}
+            // Never pop the drive, or the UNC server and share.
+            ".." if components.len() > if prefix == VERBATIM { 1 } else { 2 } => {
+                components.pop();
+            }
+            ".." => {}
+            _ => components.push(component),
+        }
+    }
+    let mut extended = prefix + &components.join("\\");
+    if components.len() == 1 && prefix_is_drive(&extended) {
+        // A bare drive needs its root: `\\?\C:\`.
+        extended.push('\\');
+    }
+    extended
+}
+
+fn prefix_is_drive(extended: &str) -> bool {
+    extended.starts_with(VERBATIM) && !extended.starts_with(VERBATIM_UNC)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempTree;
+    use crate::MediaWalker;
+
+    #[test]
+    fn it_converts_to_extended_length_paths() {
+        assert_eq!(
+            to_extended(r"C:\Music\..\Music\.\Album/Track.flac"),
+            r"\\?\C:\Music\Album\Track.flac"
+        );
+        assert_eq!(to_extended(r"C:\"), r"\\?\C:\");
+        assert_eq!(to_extended(r"C:\.."), r"\\?\C:\");
+        assert_eq!(
+            to_extended(r"\\nas\media\..\..\photos"),
+            r"\\?\UNC\nas\media\photos"
+        );
+        assert_eq!(to_extended(r"\\?\D:\x"), r"\\?\D:\x");
+    }
+
+    #[test]
+    fn it_walks_paths_longer_than_260_characters() {
+        let tree = TempTree::new("long-paths");
+        let mut relative = PathBuf::new();
+        for level in 0..6 {
+            relative.push(format!(
+                "{}-{}",
+                level,
+                "a very long music folder name".repeat(2)
+            ));
+        }
+        relative.push("track.mp3");
+        let track = tree.sample("sample3.mp3", relative.to_str().unwrap());
+        assert!(track.as_os_str().len() > 260);
+
+        let paths: Vec<_> = MediaWalker::new(tree.path())
+            .start()
+            .filter(|r| matches!(r.result, Ok(true)))
+            .map(|r| r.path)
+            .collect();
+        assert_eq!(paths, vec![track.to_str().unwrap()]);
+    }
+
+    #[test]
+    fn it_finds_path_issues() {
+        assert_eq!(issues("/music/a/track.mp3"), vec![]);
+        assert_eq!(
+            issues("/music/nul.mp3/what?/line\nbreak /x."),
+            vec![
+                PathIssue::ControlCharacter,
+                PathIssue::ReservedCharacter,
+                PathIssue::ReservedName,
+                PathIssue::TrailingSpaceOrDot,
+            ]
+        );
+        let long = format!("/{}/x.jpg", "a".repeat(300));
+        assert_eq!(
+            issues(&long),
+            vec![PathIssue::TooLong, PathIssue::NameTooLong]
+        );
+
+        // Control characters are not allowed in Windows file names.
+        #[cfg(unix)]
+        {
+            let tree = TempTree::new("path-issues");
+            tree.sample("sample_640x426.jpg", "tab\tbed.jpg");
+            let results: Vec<_> = MediaWalker::new(tree.path())
+                .check_paths(true)
+                .start()
+                .collect();
+            assert_eq!(results[0].path_issues, vec![PathIssue::ControlCharacter]);
+        }
+    }
+}
@@ -0,0 +1,127 @@
+//! Whether the machine runs on battery, for walks to pause until it is
+//! plugged in, see
+//! [`MediaWalker::pause_on_battery`](crate::MediaWalker::pause_on_battery).
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often the power supply is looked at, at most.
+const CHECK_EVERY: Duration = Duration::from_secs(5);
+
+/// How long a paused walk sleeps at a time, before looking whether it
+/// was stopped.
+const IDLE_POLL: Duration = Duration::from_millis(100);
+
+/// The pauses of a walk while the machine runs on battery.
+#[derive(Debug, Default)]
+pub(crate) struct Power {
+    /// When the power supply was last looked at, on mains power.
+    checked: Mutex<Option<Instant>>,
+}
+
+impl Power {
+    /// Waits for the machine to run on mains power, unless the walk is
+    /// stopped.
+    pub(crate) fn wait_for_mains(&self, stopped: &AtomicBool) {
+        let mut checked = self.checked.lock().unwrap();
+        if checked.is_some_and(|checked| checked.elapsed() < CHECK_EVERY) {
+            return;
+        }
+        while on_battery() {
+            let paused = Instant::now();
+            while paused.elapsed() < CHECK_EVERY {
+                if stopped.load(Ordering::Relaxed) {
+                    return;
+                }
+                thread::sleep(IDLE_POLL);
+            }
+        }
+        *checked = Some(Instant::now());
+    }
+}
+
+/// Whether the machine runs on battery: it has a battery, and no power
+/// supply online.
+#[cfg(target_os = "linux")]
+fn on_battery() -> bool {
+    on_battery_in(std::path::Path::new("/sys/class/power_supply"))
+}
+
+/// Whether `pmset` tells that the machine draws from its battery.
+#[cfg(target_os = "macos")]
+fn on_battery() -> bool {
+    std::process::Command::new("pmset")
+        .args(["-g", "ps"])
+        .output()
+        .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).contains("'Battery Power'"))
+}
+
+/// Whether Windows tells that the machine is off its AC line.
+#[cfg(windows)]
+fn on_battery() -> bool {
+    #[repr(C)]
+    #[derive(Default)]
+    struct SystemPowerStatus {
+        ac_line_status: u8,
+        battery_flag: u8,
+        battery_life_percent: u8,
+        system_status_flag: u8,
+        battery_life_time: u32,
+        battery_full_life_time: u32,
+    }
+    extern "system" {
+        fn GetSystemPowerStatus(status: *mut SystemPowerStatus) -> i32;
+    }
+    let mut status = SystemPowerStatus::default();
+    // SAFETY: The status is written to a struct of its layout.
+    unsafe { GetSystemPowerStatus(&mut status) != 0 && status.ac_line_status == 0 }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn on_battery() -> bool {
+    false
+}
+
+/// Whether the power supplies of a sysfs directory tell that the
+/// machine runs on battery: one of them is a battery, and none of the
+/// others is online.
+#[cfg(any(target_os = "linux", test))]
+fn on_battery_in(dir: &std::path::Path) -> bool {
+    let Ok(supplies) = std::fs::read_dir(dir) else {
+        return false;
+    };
+    let read = |supply: &std::path::Path, name: &str| {
+        std::fs::read_to_string(supply.join(name)).unwrap_or_default()
+    };
+    let mut has_battery = false;
+    for supply in supplies.filter_map(Result::ok) {
+        let supply = supply.path();
+        match read(&supply, "type").trim() {
+            "Battery" => has_battery = true,
+            _ if read(&supply, "online").trim() == "1" => return false,
+            _ => {}
+        }
+    }
+    has_battery
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempTree;
+
+    #[test]
+    fn it_tells_when_on_battery() {
+        let tree = TempTree::new("power");
+        assert!(!on_battery_in(&tree.path().join("gone")));
+        tree.file("AC/type", b"Mains\n");
+        tree.file("AC/online", b"0\n");
+        assert!(!on_battery_in(tree.path()));
+        tree.file("BAT0/type", b"Battery\n");
+        tree.file("BAT0/online", b"1\n");
+        assert!(on_battery_in(tree.path()));
+        tree.file("AC/online", b"1\n");
+        assert!(!on_battery_in(tree.path()));
+    }
+}
@@ -0,0 +1,115 @@
+use crate::{Kind, MediaWalker};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Directories that never contain anything worth reporting: version
+/// control, caches, thumbnails, trash and NAS metadata.
+const JUNK_DIRS: &[&str] = &[
+    ".git",
+    ".svn",
+    ".hg",
+    "node_modules",
+    ".cache",
+    ".thumbnails",
+    "@eaDir",
+    "#recycle",
+    "$RECYCLE.BIN",
+    ".Trash-*",
+    ".Trashes",
+    ".Spotlight-V100",
+    ".fseventsd",
+    "System Volume Information",
+];
+
+const PHOTO_EXTENSIONS: &[&str] = &[
+    "jpg", "jpeg", "png", "heic", "heif", "avif", "webp", "gif", "bmp", "tif", "tiff", "jxr",
+    "cr2", "cr3", "nef", "arw", "dng", "orf", "rw2", "raf",
+];
+
+const MUSIC_EXTENSIONS: &[&str] = &[
+    "mp3", "flac", "m4a", "aac", "ogg", "oga", "opus", "wav", "aif", "aiff", "ape", "wma", "mka",
+];
+
+const VIDEO_EXTENSIONS: &[&str] = &[
+    "mp4", "m4v", "mkv", "mov", "avi", "webm", "wmv", "mpg", "mpeg", "ts", "m2ts", "flv", "3gp",
+];
+
+/// Whether a path has the extension of a photo, music or video file.
+pub(crate) fn has_media_extension(path: &Path) -> bool {
+    let Some(ext) = path.extension().and_then(|ext| ext.to_str()) else {
+        return false;
+    };
+    [PHOTO_EXTENSIONS, MUSIC_EXTENSIONS, VIDEO_EXTENSIONS]
+        .iter()
+        .any(|extensions| {
+            extensions
+                .iter()
+                .any(|known| known.eq_ignore_ascii_case(ext))
+        })
+}
+
+/// A bundle of walk options for a common kind of scan, see
+/// [`MediaWalker::preset`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Preset {
+    /// Photos of at least 10 kB, so icons and thumbnails are left out.
+    Photos,
+    /// Music files of at least 32 kB, together with their playlists and
+    /// cue sheets.
+    Music,
+    /// Videos of at least 1 MB, together with their subtitles.
+    Videos,
+    /// All audio, image and video files.
+    Everything,
+}
+
+impl Preset {
+    /// Applies the preset's options to a walker.
+    pub(crate) fn apply(self, walker: MediaWalker) -> MediaWalker {
+        let walker = walker.excludes(JUNK_DIRS);
+        match self {
+            Preset::Photos => walker
+                .kinds(&[Kind::Image])
+                .extensions(PHOTO_EXTENSIONS)
+                .min_size(10_000),
+            Preset::Music => walker
+                .kinds(&[Kind::Audio, Kind::Companion])
+                .extensions(MUSIC_EXTENSIONS)
+                .extensions(&["m3u", "cue"])
+                .min_size(32_000),
+            Preset::Videos => walker
+                .kinds(&[Kind::Video, Kind::Companion])
+                .extensions(VIDEO_EXTENSIONS)
+                .extensions(&["srt", "ass", "vtt", "nfo"])
+                .min_size(1_000_000),
+            Preset::Everything => walker.kinds(&Kind::MEDIA),
+        }
+    }
+}
+
+impl fmt::Display for Preset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Preset::Photos => "photos",
+            Preset::Music => "music",
+            Preset::Videos => "videos",
+            Preset::Everything => "everything",
+        })
+    }
+}
+
+impl FromStr for Preset {
+    type Err = String;
+
+    /// Parses `photos`, `music`, `videos` or `everything`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "photos" => Ok(Preset::Photos),
+            "music" => Ok(Preset::Music),
+            "videos" => Ok(Preset::Videos),
+            "everything" => Ok(Preset::Everything),
+            _ => Err(format!("unknown preset: {}", s)),
+        }
+    }
+}
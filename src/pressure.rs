@@ -0,0 +1,117 @@
+//! How busy the disks are, for walks to back off from, see
+//! [`MediaWalker::io_backoff`](crate::MediaWalker::io_backoff).
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How often the pressure is read, at most.
+const CHECK_EVERY: Duration = Duration::from_secs(1);
+
+/// The longest pause after a file, under full pressure.
+const MAX_PAUSE: Duration = Duration::from_millis(500);
+
+/// The shortest pause, once the pressure is over the threshold.
+const MIN_PAUSE: Duration = Duration::from_millis(5);
+
+/// The pauses of a walk backing off from busy disks.
+#[derive(Debug)]
+pub(crate) struct Backoff {
+    threshold: f64,
+    /// When the pressure was last read, and what it was.
+    last: Mutex<Option<(Instant, f64)>>,
+}
+
+impl Backoff {
+    /// Backs off once the pressure is over `percent`.
+    pub(crate) fn new(percent: u32) -> Self {
+        Backoff {
+            threshold: (percent.min(99) as f64) / 100.0,
+            last: Mutex::new(None),
+        }
+    }
+
+    /// How long to pause after a file, if at all, as of the pressure
+    /// read last.
+    pub(crate) fn pause(&self) -> Option<Duration> {
+        let pressure = {
+            let mut last = self.last.lock().unwrap();
+            match *last {
+                Some((checked, pressure)) if checked.elapsed() < CHECK_EVERY => pressure,
+                _ => {
+                    let pressure = io_pressure().unwrap_or(0.0);
+                    *last = Some((Instant::now(), pressure));
+                    pressure
+                }
+            }
+        };
+        self.pause_for(pressure)
+    }
+
+    /// How long to pause under a pressure, from none at the threshold to
+    /// [`MAX_PAUSE`] under full pressure.
+    fn pause_for(&self, pressure: f64) -> Option<Duration> {
+        if pressure <= self.threshold {
+            return None;
+        }
+        let over = (pressure - self.threshold) / (1.0 - self.threshold);
+        Some(MAX_PAUSE.mul_f64(over.min(1.0)).max(MIN_PAUSE))
+    }
+}
+
+/// The share of the last ten seconds in which some tasks waited for IO,
+/// from the pressure stall information of Linux.
+#[cfg(target_os = "linux")]
+fn io_pressure() -> Option<f64> {
+    parse_pressure(&std::fs::read_to_string("/proc/pressure/io").ok()?)
+}
+
+/// The load average of the last minute per CPU, which macOS has instead
+/// of a measure of the pressure on the disks: tasks waiting for IO count
+/// as running.
+#[cfg(target_os = "macos")]
+fn io_pressure() -> Option<f64> {
+    extern "C" {
+        fn getloadavg(loadavg: *mut f64, count: std::os::raw::c_int) -> std::os::raw::c_int;
+    }
+    let mut load = 0.0;
+    // SAFETY: The average is written to a single f64.
+    if unsafe { getloadavg(&mut load, 1) } != 1 {
+        return None;
+    }
+    let cpus = std::thread::available_parallelism().ok()?.get() as f64;
+    Some((load / cpus).min(1.0))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn io_pressure() -> Option<f64> {
+    None
+}
+
+/// Parses the `some avg10=` of `/proc/pressure/io`, in percent.
+#[cfg(any(target_os = "linux", test))]
+fn parse_pressure(text: &str) -> Option<f64> {
+    let some = text.lines().find(|line| line.starts_with("some "))?;
+    let avg10 = some
+        .split_whitespace()
+        .find_map(|field| field.strip_prefix("avg10="))?;
+    Some(avg10.parse::<f64>().ok()? / 100.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_backs_off_under_pressure() {
+        let text = "some avg10=42.50 avg60=10.00 avg300=2.00 total=123456\n\
+                    full avg10=20.00 avg60=5.00 avg300=1.00 total=65432\n";
+        assert_eq!(parse_pressure(text), Some(0.425));
+        assert_eq!(parse_pressure("full avg10=1.00"), None);
+
+        let backoff = Backoff::new(50);
+        assert_eq!(backoff.pause_for(0.1), None);
+        assert_eq!(backoff.pause_for(0.5), None);
+        assert_eq!(backoff.pause_for(0.5001), Some(MIN_PAUSE));
+        assert_eq!(backoff.pause_for(0.75), Some(MAX_PAUSE / 2));
+        assert_eq!(backoff.pause_for(1.0), Some(MAX_PAUSE));
+    }
+}
@@ -0,0 +1,68 @@
+use std::cmp::Ordering;
+use std::fs::Metadata;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+/// The order to walk each directory in, see
+/// [`MediaWalker::prioritize`](crate::MediaWalker::prioritize).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Priority {
+    /// The order the filesystem lists the entries in. The default.
+    #[default]
+    WalkOrder,
+    /// The most recently modified entries first.
+    NewestFirst,
+    /// The least recently modified entries first.
+    OldestFirst,
+}
+
+impl Priority {
+    /// Compares two entries by their metadata. Entries whose
+    /// modification time cannot be read go last.
+    pub(crate) fn compare(self, a: Option<&Metadata>, b: Option<&Metadata>) -> Ordering {
+        let modified = |m: Option<&Metadata>| m.and_then(|m| m.modified().ok());
+        compare_times(self, modified(a), modified(b))
+    }
+}
+
+fn compare_times(priority: Priority, a: Option<SystemTime>, b: Option<SystemTime>) -> Ordering {
+    match (priority, a, b) {
+        (Priority::WalkOrder, _, _) => Ordering::Equal,
+        (_, Some(_), None) => Ordering::Less,
+        (_, None, Some(_)) => Ordering::Greater,
+        (Priority::NewestFirst, a, b) => b.cmp(&a),
+        (Priority::OldestFirst, a, b) => a.cmp(&b),
+    }
+}
+
+impl FromStr for Priority {
+    type Err = String;
+
+    /// Parses `walk-order`, `newest-first` or `oldest-first`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace('_', "-").as_str() {
+            "walk-order" => Ok(Priority::WalkOrder),
+            "newest-first" => Ok(Priority::NewestFirst),
+            "oldest-first" => Ok(Priority::OldestFirst),
+            _ => Err(format!("unknown priority: {}", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn it_orders_by_modification_time() {
+        let old = Some(SystemTime::UNIX_EPOCH);
+        let new = Some(SystemTime::UNIX_EPOCH + Duration::from_secs(60));
+        let mut times = vec![old, None, new];
+        times.sort_by(|a, b| compare_times(Priority::NewestFirst, *a, *b));
+        assert_eq!(times, vec![new, old, None]);
+        times.sort_by(|a, b| compare_times(Priority::OldestFirst, *a, *b));
+        assert_eq!(times, vec![old, new, None]);
+        assert_eq!("newest_first".parse(), Ok(Priority::NewestFirst));
+    }
+}
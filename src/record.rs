@@ -0,0 +1,321 @@
+//! Logs of walks, to play them back without the filesystem, see
+//! [`MediaWalker::record`](crate::MediaWalker::record) and
+//! [`MediaWalker::replay`](crate::MediaWalker::replay).
+//!
+//! A log is a line of its version, then a line per event of tab
+//! separated fields, the first telling the event: `found`, `skipped`,
+//! `bundle`, `dir` or `dangling`. Backslashes, tabs and line breaks in
+//! the fields are escaped.
+use crate::{Kind, MediaWalkResult, SkipReason, WalkEvent};
+use std::collections::vec_deque::{self, VecDeque};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// The first line of a log.
+const HEADER: &str = "mediawalker-record 1";
+
+/// The kinds of the errors of failed results that are told apart in
+/// logs. The others are played back as [`io::ErrorKind::Other`].
+const ERROR_KINDS: [io::ErrorKind; 14] = [
+    io::ErrorKind::NotFound,
+    io::ErrorKind::PermissionDenied,
+    io::ErrorKind::AlreadyExists,
+    io::ErrorKind::WouldBlock,
+    io::ErrorKind::InvalidInput,
+    io::ErrorKind::InvalidData,
+    io::ErrorKind::TimedOut,
+    io::ErrorKind::WriteZero,
+    io::ErrorKind::Interrupted,
+    io::ErrorKind::Unsupported,
+    io::ErrorKind::UnexpectedEof,
+    io::ErrorKind::OutOfMemory,
+    io::ErrorKind::BrokenPipe,
+    io::ErrorKind::Other,
+];
+
+/// Writes the events of a walk to its log as they are sent.
+#[derive(Debug)]
+pub(crate) struct Recorder {
+    out: Mutex<BufWriter<File>>,
+    /// The first error writing the log, if any.
+    failed: Mutex<Option<io::Error>>,
+}
+
+impl Recorder {
+    /// Creates the log, replacing any there was.
+    pub(crate) fn create(log: &Path) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(log)?);
+        writeln!(out, "{}", HEADER)?;
+        Ok(Recorder {
+            out: Mutex::new(out),
+            failed: Mutex::default(),
+        })
+    }
+
+    /// Writes an event, unless it is not one kept in logs.
+    pub(crate) fn write(&self, event: &WalkEvent) {
+        let Some(line) = line(event) else {
+            return;
+        };
+        let mut failed = self.failed.lock().unwrap();
+        if failed.is_none() {
+            *failed = writeln!(self.out.lock().unwrap(), "{}", line).err();
+        }
+    }
+
+    /// Writes out what is left of the log, failing if any of it could
+    /// not be written.
+    pub(crate) fn finish(&self) -> io::Result<()> {
+        if let Some(err) = self.failed.lock().unwrap().take() {
+            return Err(err);
+        }
+        self.out.lock().unwrap().flush()
+    }
+}
+
+/// The line of an event, `None` for the events that are not kept: the
+/// details of results coming after them, and embedded media.
+fn line(event: &WalkEvent) -> Option<String> {
+    let fields = match event {
+        WalkEvent::Found(walk_result) => {
+            let outcome = match &walk_result.result {
+                Ok(true) => "media".to_string(),
+                Ok(false) => "unknown".to_string(),
+                Err(err) => format!("failed:{:?}:{}", err.kind(), err),
+            };
+            let mut flags = vec![];
+            if walk_result.suspicious {
+                flags.push("suspicious");
+            }
+            if walk_result.lossy {
+                flags.push("lossy");
+            }
+            vec![
+                "found".to_string(),
+                walk_result.path.clone(),
+                walk_result.mime.to_string(),
+                walk_result
+                    .kind
+                    .map_or("-".to_string(), |kind| kind.to_string()),
+                outcome,
+                walk_result.id.map_or("-".to_string(), |id| id.to_string()),
+                flags.join(","),
+            ]
+        }
+        WalkEvent::Skipped { path, reason } => {
+            vec!["skipped".to_string(), lossy(path), reason.to_string()]
+        }
+        WalkEvent::Bundle { path } => vec!["bundle".to_string(), lossy(path)],
+        WalkEvent::Directory { path, .. } => vec!["dir".to_string(), lossy(path)],
+        WalkEvent::DanglingSymlink { link, target } => {
+            vec!["dangling".to_string(), lossy(link), lossy(target)]
+        }
+        _ => return None,
+    };
+    let fields: Vec<_> = fields.iter().map(|field| escape(field)).collect();
+    Some(fields.join("\t"))
+}
+
+fn lossy(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+fn escape(field: &str) -> String {
+    let mut escaped = String::with_capacity(field.len());
+    for c in field.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape(field: &str) -> String {
+    let mut unescaped = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some(c) => unescaped.push(c),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// A walk played back from its log, see
+/// [`MediaWalker::replay`](crate::MediaWalker::replay). Iterate over it
+/// for the results, as over a [`WalkHandle`](crate::WalkHandle), or go
+/// through all of its events with [`events`](Self::events).
+///
+/// Only the path, MIME type, kind, outcome and ID of a found file are
+/// logged, and whether it was suspicious or its path lossy: the rest of
+/// its [`MediaWalkResult`] is left out, as are the events of details
+/// coming after results, and of embedded media.
+#[derive(Debug)]
+pub struct Replay {
+    events: VecDeque<WalkEvent>,
+}
+
+impl Replay {
+    /// The events of the walk, in the order they were sent.
+    pub fn events(&mut self) -> vec_deque::Drain<'_, WalkEvent> {
+        self.events.drain(..)
+    }
+}
+
+impl Iterator for Replay {
+    type Item = MediaWalkResult;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let WalkEvent::Found(walk_result) = self.events.pop_front()? {
+                return Some(walk_result);
+            }
+        }
+    }
+}
+
+/// Reads a log, failing on any line that is not an event.
+pub(crate) fn replay(log: &Path) -> io::Result<Replay> {
+    let mut lines = BufReader::new(File::open(log)?).lines();
+    if lines.next().transpose()?.as_deref() != Some(HEADER) {
+        return Err(invalid(log, 1, "not a walk log"));
+    }
+    let mut events = VecDeque::new();
+    for (number, line) in lines.enumerate() {
+        let line = line?;
+        let event = parse(&line).map_err(|message| invalid(log, number + 2, &message))?;
+        events.push_back(event);
+    }
+    Ok(Replay { events })
+}
+
+fn invalid(log: &Path, line: usize, message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{}:{}: {}", log.display(), line, message),
+    )
+}
+
+/// Parses the line of an event.
+fn parse(line: &str) -> Result<WalkEvent, String> {
+    let fields: Vec<_> = line.split('\t').map(unescape).collect();
+    let fields: Vec<&str> = fields.iter().map(String::as_str).collect();
+    let event = match fields[..] {
+        ["found", path, mime, kind, outcome, id, flags] => {
+            let mut walk_result = MediaWalkResult::new(path.to_string());
+            walk_result.mime = mime.into();
+            walk_result.kind = match kind {
+                "-" => None,
+                kind => Some(kind.parse::<Kind>()?),
+            };
+            walk_result.result = match outcome.split_once(':') {
+                None if outcome == "media" => Ok(true),
+                None if outcome == "unknown" => Ok(false),
+                Some(("failed", err)) => {
+                    let (kind, message) = err.split_once(':').unwrap_or(("Other", err));
+                    let kind = ERROR_KINDS
+                        .into_iter()
+                        .find(|known| format!("{:?}", known) == kind)
+                        .unwrap_or(io::ErrorKind::Other);
+                    Err(io::Error::new(kind, message))
+                }
+                _ => return Err(format!("unknown outcome: {}", outcome)),
+            };
+            walk_result.id = match id {
+                "-" => None,
+                id => Some(id.parse().map_err(|_| format!("bad ID: {}", id))?),
+            };
+            walk_result.suspicious = flags.split(',').any(|flag| flag == "suspicious");
+            walk_result.lossy = flags.split(',').any(|flag| flag == "lossy");
+            WalkEvent::Found(walk_result)
+        }
+        ["skipped", path, reason] => WalkEvent::Skipped {
+            path: PathBuf::from(path),
+            reason: reason.parse()?,
+        },
+        ["bundle", path] => WalkEvent::Bundle {
+            path: PathBuf::from(path),
+        },
+        ["dir", path] => WalkEvent::Directory {
+            path: PathBuf::from(path),
+            artwork: None,
+        },
+        ["dangling", link, target] => WalkEvent::DanglingSymlink {
+            link: PathBuf::from(link),
+            target: PathBuf::from(target),
+        },
+        _ => return Err(format!("not an event: {}", line)),
+    };
+    Ok(event)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testutil::TempTree;
+    use crate::{DirEmit, Explanation, IdStrategy, Kind, MediaWalker, WalkEvent};
+
+    #[test]
+    fn it_replays_recorded_walks() {
+        let tree = TempTree::new("record");
+        tree.sample("sample_640x426.jpg", "a\tb.jpg");
+        tree.sample("sample3.mp3", "song.mp3");
+        tree.file("notes.txt", b"nothing to see");
+        tree.sample("sample_640x426.jpg", "skip/c.jpg");
+        let log = tree.path().join("walk.log");
+
+        let walker = MediaWalker::new(tree.path())
+            .kinds(&[Kind::Image])
+            .exclude("/skip")
+            .exclude("*.log")
+            .ids(IdStrategy::Inode)
+            .emit_dirs(DirEmit::WithMatches);
+        let walker = walker.record(&log);
+        let mut found: Vec<_> = walker.clone().start().collect();
+        found.sort_by(|a, b| a.path.cmp(&b.path));
+        // Explaining a file walks it alone, not to be recorded over the
+        // walk.
+        assert!(matches!(walker.explain("a\tb.jpg"), Explanation::Found(_)));
+
+        let mut replayed: Vec<_> = MediaWalker::replay(&log).unwrap().collect();
+        replayed.sort_by(|a, b| a.path.cmp(&b.path));
+        assert_eq!(replayed.len(), 2);
+        for (found, replayed) in found.iter().zip(&replayed) {
+            assert_eq!(found.path, replayed.path);
+            assert_eq!(found.mime, replayed.mime);
+            assert_eq!(found.kind, replayed.kind);
+            assert_eq!(found.id, replayed.id);
+            assert_eq!(found.result.is_ok(), replayed.result.is_ok());
+        }
+        assert!(replayed[0].path.ends_with("a\tb.jpg"));
+
+        let mut replay = MediaWalker::replay(&log).unwrap();
+        let dirs: Vec<_> = replay
+            .events()
+            .filter_map(|event| match event {
+                WalkEvent::Directory { path, .. } => Some(path),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(dirs, [tree.path()]);
+
+        tree.file("bad.log", b"mediawalker-record 1\nfound\tx\n");
+        let err = MediaWalker::replay(tree.path().join("bad.log")).unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("bad.log:2: not an event: found\tx"));
+    }
+}
@@ -0,0 +1,317 @@
+//! Candidate scan roots of the machine: drives on Windows, mount points
+//! elsewhere. Handy for "scan my whole machine" kinds of UIs.
+use std::io;
+use std::path::PathBuf;
+
+/// Filesystem types that are network shares.
+const NETWORK_FS: &[&str] = &[
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smb",
+    "smb3",
+    "smbfs",
+    "afpfs",
+    "webdav",
+    "davfs",
+    "9p",
+    "afs",
+    "ceph",
+    "glusterfs",
+    "sshfs",
+    "fuse.sshfs",
+    "fuse.rclone",
+];
+
+/// Kernel pseudo filesystems, which are never worth scanning.
+const PSEUDO_FS: &[&str] = &[
+    "proc",
+    "sysfs",
+    "devtmpfs",
+    "devpts",
+    "devfs",
+    "cgroup",
+    "cgroup2",
+    "debugfs",
+    "securityfs",
+    "pstore",
+    "bpf",
+    "tracefs",
+    "mqueue",
+    "hugetlbfs",
+    "autofs",
+    "configfs",
+    "fusectl",
+    "binfmt_misc",
+    "nsfs",
+    "efivarfs",
+    "rpc_pipefs",
+    "selinuxfs",
+    "fdescfs",
+    "overlay",
+    "squashfs",
+    "ramfs",
+    "nullfs",
+];
+
+const OPTICAL_FS: &[&str] = &["iso9660", "udf", "cd9660", "cddafs"];
+
+/// The kind of storage a scan root is on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RootKind {
+    /// A fixed, local disk.
+    Fixed,
+    /// A removable disk, like a USB stick or a memory card.
+    Removable,
+    /// A network share.
+    Network,
+    /// An optical disc.
+    Optical,
+    /// A RAM disk, like tmpfs.
+    RamDisk,
+    /// The kind could not be figured out.
+    Unknown,
+}
+
+/// A candidate root for a walk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanRoot {
+    /// Where the drive or filesystem is mounted.
+    pub path: PathBuf,
+    /// The kind of storage.
+    pub kind: RootKind,
+    /// The filesystem type, like `ext4` or `apfs`, if known.
+    pub fs_type: Option<String>,
+}
+
+/// Returns the drives of the machine, like `C:\` and `D:\`.
+#[cfg(windows)]
+pub fn available_drives() -> io::Result<Vec<ScanRoot>> {
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetLogicalDrives() -> u32;
+        fn GetDriveTypeW(root: *const u16) -> u32;
+    }
+
+    // SAFETY: takes no arguments.
+    let mask = unsafe { GetLogicalDrives() };
+    if mask == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut drives = vec![];
+    for (bit, letter) in (b'A'..=b'Z').enumerate() {
+        if mask & (1 << bit) == 0 {
+            continue;
+        }
+        let root = format!("{}:\\", letter as char);
+        let wide: Vec<u16> = root.encode_utf16().chain(Some(0)).collect();
+        // SAFETY: `wide` is a NUL-terminated UTF-16 string.
+        let kind = match unsafe { GetDriveTypeW(wide.as_ptr()) } {
+            // DRIVE_NO_ROOT_DIR
+            1 => continue,
+            2 => RootKind::Removable,
+            3 => RootKind::Fixed,
+            4 => RootKind::Network,
+            5 => RootKind::Optical,
+            6 => RootKind::RamDisk,
+            _ => RootKind::Unknown,
+        };
+        drives.push(ScanRoot {
+            path: PathBuf::from(root),
+            kind,
+            fs_type: None,
+        });
+    }
+    Ok(drives)
+}
+
+/// Returns the mounted filesystems of the machine, leaving out kernel
+/// pseudo filesystems like `/proc`.
+#[cfg(unix)]
+pub fn mount_points() -> io::Result<Vec<ScanRoot>> {
+    if cfg!(target_os = "linux") {
+        let mounts = std::fs::read_to_string("/proc/self/mounts")?;
+        Ok(parse_proc_mounts(&mounts, is_removable_block_device))
+    } else {
+        let output = std::process::Command::new("mount").output()?;
+        if !output.status.success() {
+            return Err(io::Error::other("mount failed"));
+        }
+        Ok(parse_mount_output(&String::from_utf8_lossy(&output.stdout)))
+    }
+}
+
+/// Parses `/proc/mounts`: `device mount-point fs-type options 0 0`.
+fn parse_proc_mounts(mounts: &str, is_removable: impl Fn(&str) -> bool) -> Vec<ScanRoot> {
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let (device, path, fs_type) = (fields.next()?, fields.next()?, fields.next()?);
+            let path = unescape_octal(path);
+            let kind = classify(fs_type, &path, true)?;
+            let kind = if kind == RootKind::Fixed && is_removable(device) {
+                RootKind::Removable
+            } else {
+                kind
+            };
+            Some(ScanRoot {
+                path: PathBuf::from(path),
+                kind,
+                fs_type: Some(fs_type.to_string()),
+            })
+        })
+        .collect()
+}
+
+/// Parses the output of BSD and macOS `mount`:
+/// `device on mount-point (fs-type, option, ...)`.
+fn parse_mount_output(output: &str) -> Vec<ScanRoot> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (_, rest) = line.split_once(" on ")?;
+            let (path, options) = rest.rsplit_once(" (")?;
+            let mut options = options.trim_end_matches(')').split(", ");
+            let fs_type = options.next()?;
+            let local = options.any(|option| option == "local");
+            let kind = classify(fs_type, path, local)?;
+            Some(ScanRoot {
+                path: PathBuf::from(path),
+                kind,
+                fs_type: Some(fs_type.to_string()),
+            })
+        })
+        .collect()
+}
+
+/// Figures out the kind of a mounted filesystem, `None` for pseudo
+/// filesystems.
+fn classify(fs_type: &str, path: &str, local: bool) -> Option<RootKind> {
+    if PSEUDO_FS.contains(&fs_type) || fs_type.starts_with("cgroup") {
+        return None;
+    }
+    // macOS system volumes are mounted under the root volume.
+    if path.starts_with("/System/Volumes/") || path.starts_with("/private/var/vm") {
+        return None;
+    }
+    let kind = if NETWORK_FS.contains(&fs_type) || !local {
+        RootKind::Network
+    } else if OPTICAL_FS.contains(&fs_type) {
+        RootKind::Optical
+    } else if fs_type == "tmpfs" {
+        RootKind::RamDisk
+    } else if ["/media/", "/run/media/", "/Volumes/"]
+        .iter()
+        .any(|prefix| path.starts_with(prefix))
+    {
+        RootKind::Removable
+    } else {
+        RootKind::Fixed
+    };
+    Some(kind)
+}
+
+/// Whether a `/dev/...` device is flagged removable in `/sys/block`.
+fn is_removable_block_device(device: &str) -> bool {
+    let Some(name) = device.strip_prefix("/dev/") else {
+        return false;
+    };
+    // Go from a partition, like sdb1 or mmcblk0p1, to its disk.
+    let disk = if name.starts_with("mmcblk") || name.starts_with("nvme") {
+        match name.rsplit_once('p') {
+            Some((disk, partition)) if partition.bytes().all(|b| b.is_ascii_digit()) => {
+                disk.to_string()
+            }
+            _ => name.to_string(),
+        }
+    } else {
+        name.trim_end_matches(|c: char| c.is_ascii_digit())
+            .to_string()
+    };
+    std::fs::read_to_string(format!("/sys/block/{}/removable", disk))
+        .is_ok_and(|removable| removable.trim() == "1")
+}
+
+/// Undoes the octal escapes of `/proc/mounts`, like `\040` for a space.
+fn unescape_octal(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut unescaped = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let octal = bytes
+            .get(i + 1..i + 4)
+            .filter(|digits| bytes[i] == b'\\' && digits.iter().all(|d| (b'0'..=b'7').contains(d)));
+        match octal {
+            Some(digits) => {
+                let value = digits
+                    .iter()
+                    .fold(0u32, |acc, d| acc * 8 + (d - b'0') as u32);
+                unescaped.push(value as u8);
+                i += 4;
+            }
+            None => {
+                unescaped.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&unescaped).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_proc_mounts() {
+        let mounts = "\
+/dev/nvme0n1p2 / ext4 rw,relatime 0 0
+proc /proc proc rw,nosuid 0 0
+tmpfs /run tmpfs rw,nosuid 0 0
+/dev/sdb1 /mnt/My\\040Photos vfat rw 0 0
+nas:/music /mnt/music nfs4 rw 0 0
+/dev/sdc1 /run/media/me/CARD exfat rw 0 0
+cgroup2 /sys/fs/cgroup cgroup2 rw 0 0
+";
+        let roots = parse_proc_mounts(mounts, |device| device == "/dev/sdb1");
+        let found: Vec<_> = roots
+            .iter()
+            .map(|root| (root.path.to_str().unwrap(), root.kind))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("/", RootKind::Fixed),
+                ("/run", RootKind::RamDisk),
+                ("/mnt/My Photos", RootKind::Removable),
+                ("/mnt/music", RootKind::Network),
+                ("/run/media/me/CARD", RootKind::Removable),
+            ]
+        );
+        assert_eq!(roots[0].fs_type.as_deref(), Some("ext4"));
+    }
+
+    #[test]
+    fn it_parses_bsd_mount_output() {
+        let output = "\
+/dev/disk3s1s1 on / (apfs, sealed, local, read-only, journaled)
+devfs on /dev (devfs, local, nobrowse)
+/dev/disk3s5 on /System/Volumes/Data (apfs, local, journaled, nobrowse)
+//me@nas/Photos on /Volumes/Photos (smbfs, nodev, nosuid, mounted by me)
+/dev/disk4s1 on /Volumes/SD Card (msdos, local, nodev, nosuid, noowners)
+";
+        let found: Vec<_> = parse_mount_output(output)
+            .into_iter()
+            .map(|root| (root.path, root.kind))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (PathBuf::from("/"), RootKind::Fixed),
+                (PathBuf::from("/Volumes/Photos"), RootKind::Network),
+                (PathBuf::from("/Volumes/SD Card"), RootKind::Removable),
+            ]
+        );
+    }
+}
@@ -0,0 +1,760 @@
+//! An HTTP server for scanning over the network, behind the `serve`
+//! feature. Also run by `mediawalker serve --root PATH --listen :8080`.
+//!
+//! - `POST /scan` walks the root and streams the results, one JSON
+//!   object per line, or as server-sent events if the request accepts
+//!   `text/event-stream`. The request body may hold walk options as a
+//!   JSON [`WalkConfig`], limited to the keys of [`SCAN_KEYS`], with
+//!   the thread counts capped at the number of CPUs.
+//! - `GET /stats` returns the statistics of the latest scan, as JSON.
+//! - `GET /schema` returns the JSON Schema of the results and the
+//!   statistics, see [`schema`]. Each of them carries the
+//!   [`SCHEMA_VERSION`] they follow as `schema_version`.
+//! - `GET /watch`, with the `watch` feature on Linux, upgrades to a
+//!   WebSocket over which the changes under the root are pushed as they
+//!   happen, one JSON text message per [`WatchEvent`], like
+//!   `{"event":"added","result":{...}}`, with the result as `/scan` has
+//!   it, `{"event":"removed","path":"..."}`, or `{"event":"overflowed"}`
+//!   when changes were lost.
+use crate::export::json;
+use crate::{MediaWalkResult, MediaWalker, WalkConfig, WalkStats};
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+#[cfg(all(feature = "watch", target_os = "linux"))]
+use {crate::WatchEvent, std::sync::atomic::AtomicBool};
+
+pub use crate::export::json::{schema, SCHEMA_VERSION};
+
+/// The largest request body accepted, in bytes.
+const MAX_BODY: usize = 64 * 1024;
+
+/// The most bytes the request line and headers may take together.
+const MAX_HEADER: u64 = 16 * 1024;
+
+/// How many connections are served at once; more are turned away.
+const MAX_CONNECTIONS: usize = 32;
+
+/// How long a client may take to send its whole request, however
+/// slowly it trickles in.
+const REQUEST_DEADLINE: Duration = Duration::from_secs(10);
+
+/// How long to wait for a change at a time, before looking whether the
+/// WebSocket client went away.
+#[cfg(all(feature = "watch", target_os = "linux"))]
+const WATCH_POLL: Duration = Duration::from_millis(500);
+
+/// What the WebSocket handshake appends to the client's key, as RFC 6455
+/// has it.
+#[cfg(all(feature = "watch", target_os = "linux"))]
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// The WebSocket opcodes used.
+#[cfg(all(feature = "watch", target_os = "linux"))]
+const OPCODE_TEXT: u8 = 0x1;
+#[cfg(all(feature = "watch", target_os = "linux"))]
+const OPCODE_CLOSE: u8 = 0x8;
+#[cfg(all(feature = "watch", target_os = "linux"))]
+const OPCODE_PING: u8 = 0x9;
+#[cfg(all(feature = "watch", target_os = "linux"))]
+const OPCODE_PONG: u8 = 0xa;
+
+/// The options a scan request may set: what to report and how, but not
+/// the stages that read whole files or write to disk, like thumbnails
+/// and signatures, nor how the tree is walked.
+pub const SCAN_KEYS: &[&str] = &[
+    "preset",
+    "kinds",
+    "extra_kinds",
+    "companions",
+    "error_policy",
+    "threads",
+    "traversal_threads",
+    "excludes",
+    "extensions",
+    "min_size",
+    "max_size",
+    "min_depth",
+    "priority",
+    "prioritize_dirs",
+    "shuffle",
+    "include_trash",
+    "workflow_caches",
+    "ids",
+    "flag_suspicious",
+    "check_paths",
+    "normalize_paths",
+    "filter",
+];
+
+/// A server scanning a single root, see the [module docs](self).
+#[derive(Debug)]
+pub struct Server {
+    root: PathBuf,
+    listener: TcpListener,
+    /// The statistics of the latest scan, shared with its walk.
+    latest: Arc<Mutex<Arc<Mutex<WalkStats>>>>,
+    /// How many connections are being served.
+    connections: AtomicUsize,
+    /// How long clients may take to send their requests.
+    deadline: Duration,
+}
+
+impl Server {
+    /// Binds a server for `root` to an address like `0.0.0.0:8080`, or
+    /// `:8080` for the loopback interface only.
+    pub fn bind<P: AsRef<Path>>(root: P, listen: &str) -> io::Result<Self> {
+        let root = root.as_ref().to_path_buf();
+        if !root.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{}: no such directory", root.display()),
+            ));
+        }
+        let listen = match listen.strip_prefix(':') {
+            Some(port) => format!("127.0.0.1:{}", port),
+            None => listen.to_string(),
+        };
+        Ok(Server {
+            root,
+            listener: TcpListener::bind(listen)?,
+            latest: Arc::default(),
+            connections: AtomicUsize::new(0),
+            deadline: REQUEST_DEADLINE,
+        })
+    }
+
+    /// The address the server is listening on.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Serves requests, each connection in a thread of its own, until
+    /// accepting a connection fails. Connections beyond the first few
+    /// dozen are turned away until others finish.
+    pub fn run(self) -> io::Result<()> {
+        let server = Arc::new(self);
+        loop {
+            let (mut stream, _) = server.listener.accept()?;
+            if server.connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+                server.connections.fetch_sub(1, Ordering::SeqCst);
+                let _ = reply(
+                    &mut stream,
+                    "503 Service Unavailable",
+                    "too many connections",
+                );
+                continue;
+            }
+            let server = Arc::clone(&server);
+            thread::spawn(move || {
+                // A client gone away is no concern of the server.
+                let _ = server.respond(stream);
+                server.connections.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+    }
+
+    fn respond(&self, stream: TcpStream) -> io::Result<()> {
+        let mut reader = BufReader::new(Request {
+            stream: stream.try_clone()?,
+            left: MAX_HEADER,
+            until: Some(Instant::now() + self.deadline),
+        });
+        let mut stream = stream;
+        let mut line = String::new();
+        // A line, `None` if the headers go on past their limit.
+        let mut read_header = |line: &mut String| -> io::Result<Option<usize>> {
+            line.clear();
+            let read = reader.read_line(line)?;
+            let cut = read > 0 && !line.ends_with('\n') && reader.get_ref().left == 0;
+            Ok((!cut).then_some(read))
+        };
+        if read_header(&mut line)?.is_none() {
+            return too_large(&mut stream, &mut reader);
+        }
+        let mut parts = line.split_whitespace();
+        let method = parts.next().unwrap_or_default().to_string();
+        let target = parts.next().unwrap_or_default();
+        let path = target.split('?').next().unwrap_or_default().to_string();
+        let mut length = 0;
+        let mut events = target.contains("format=sse");
+        let mut websocket_key = None;
+        loop {
+            match read_header(&mut line)? {
+                None => return too_large(&mut stream, &mut reader),
+                Some(0) => break,
+                Some(_) if line.trim().is_empty() => break,
+                Some(_) => {}
+            }
+            let Some((name, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.parse().unwrap_or(0);
+            } else if name.eq_ignore_ascii_case("accept") {
+                events |= value.contains("text/event-stream");
+            } else if name.eq_ignore_ascii_case("sec-websocket-key") {
+                websocket_key = Some(value.to_string());
+            }
+        }
+        if length > MAX_BODY {
+            return reply(
+                &mut stream,
+                "413 Payload Too Large",
+                "request body too large",
+            );
+        }
+        reader.get_mut().left = length as u64;
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body)?;
+
+        match (method.as_str(), path.as_str()) {
+            ("POST", "/scan") => self.scan(&mut stream, &body, events),
+            #[cfg(all(feature = "watch", target_os = "linux"))]
+            ("GET", "/watch") => self.watch(stream, reader, websocket_key.as_deref()),
+            #[cfg(all(feature = "watch", target_os = "linux"))]
+            (_, "/watch") => reply(&mut stream, "405 Method Not Allowed", "bad method"),
+            ("GET", "/stats") => {
+                let stats = self.latest.lock().unwrap().lock().unwrap().clone();
+                let body = json::stats(&stats);
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+            }
+            ("GET", "/schema") => {
+                let body = schema();
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/schema+json\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+            }
+            (_, "/scan" | "/stats" | "/schema") => {
+                reply(&mut stream, "405 Method Not Allowed", "bad method")
+            }
+            _ => reply(&mut stream, "404 Not Found", "no such endpoint"),
+        }
+    }
+
+    fn scan(&self, stream: &mut TcpStream, body: &[u8], events: bool) -> io::Result<()> {
+        let body = String::from_utf8_lossy(body);
+        let mut config = if body.trim().is_empty() {
+            WalkConfig::default()
+        } else {
+            match WalkConfig::from_json_keys(&body, SCAN_KEYS) {
+                Ok(config) => config,
+                Err(err) => return reply(stream, "400 Bad Request", &err.to_string()),
+            }
+        };
+        let cpus = thread::available_parallelism().map_or(1, |n| n.get());
+        config.threads = config.threads.map(|threads| threads.min(cpus));
+        config.traversal_threads = config.traversal_threads.map(|threads| threads.min(cpus));
+        let mut handle = match config.apply(MediaWalker::new(&self.root)).try_start() {
+            Ok(handle) => handle,
+            Err(err) => return reply(stream, "500 Internal Server Error", &err.to_string()),
+        };
+        *self.latest.lock().unwrap() = handle.shared_stats();
+
+        let content_type = if events {
+            "text/event-stream"
+        } else {
+            "application/x-ndjson"
+        };
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nTransfer-Encoding: chunked\r\n\
+             Connection: close\r\n\r\n",
+            content_type
+        )?;
+        let streamed = handle.by_ref().try_for_each(|walk_result| {
+            let json = json::result(&walk_result);
+            if events {
+                chunk(stream, &format!("event: result\ndata: {}\n\n", json))
+            } else {
+                chunk(stream, &format!("{}\n", json))
+            }
+        });
+        if streamed.is_err() {
+            // The client went away, so nobody wants the rest.
+            handle.stop();
+            return streamed;
+        }
+        if events {
+            chunk(
+                stream,
+                &format!("event: stats\ndata: {}\n\n", json::stats(&handle.stats())),
+            )?;
+        }
+        stream.write_all(b"0\r\n\r\n")
+    }
+
+    /// Upgrades a connection to a WebSocket, and pushes the changes
+    /// under the root over it until the client closes it or goes away.
+    #[cfg(all(feature = "watch", target_os = "linux"))]
+    fn watch(
+        &self,
+        mut stream: TcpStream,
+        mut reader: BufReader<Request>,
+        key: Option<&str>,
+    ) -> io::Result<()> {
+        let Some(key) = key else {
+            return reply(
+                &mut stream,
+                "426 Upgrade Required",
+                "expected a WebSocket handshake",
+            );
+        };
+        let watcher = match MediaWalker::new(&self.root).watch() {
+            Ok(watcher) => watcher,
+            Err(err) => return reply(&mut stream, "500 Internal Server Error", &err.to_string()),
+        };
+        let accept = base64(&sha1(format!("{}{}", key, WEBSOCKET_GUID).as_bytes()));
+        write!(
+            stream,
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\
+             Connection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+            accept
+        )?;
+        // The client may stay quiet for as long as it likes from now on.
+        reader.get_mut().lift()?;
+        let stream = Arc::new(Mutex::new(stream));
+        let closed = Arc::new(AtomicBool::new(false));
+        let reading = {
+            let stream = Arc::clone(&stream);
+            let closed = Arc::clone(&closed);
+            thread::spawn(move || {
+                let _ = read_frames(&mut reader, &stream);
+                closed.store(true, Ordering::Relaxed);
+            })
+        };
+        let mut pushed = Ok(());
+        while !closed.load(Ordering::Relaxed) {
+            if let Some(event) = watcher.next_timeout(WATCH_POLL) {
+                pushed = send(&stream, OPCODE_TEXT, watch_event_json(&event).as_bytes());
+                if pushed.is_err() {
+                    break;
+                }
+            }
+        }
+        watcher.stop();
+        let _ = send(&stream, OPCODE_CLOSE, &[]);
+        let _ = stream.lock().unwrap().shutdown(Shutdown::Both);
+        let _ = reading.join();
+        pushed
+    }
+}
+
+/// The reading end of a connection, limited in how many more bytes may
+/// be read from it, and until when.
+struct Request {
+    stream: TcpStream,
+    left: u64,
+    until: Option<Instant>,
+}
+
+impl Request {
+    /// Lifts the limits, for connections that stay open.
+    #[cfg(all(feature = "watch", target_os = "linux"))]
+    fn lift(&mut self) -> io::Result<()> {
+        self.left = u64::MAX;
+        self.until = None;
+        self.stream.set_read_timeout(None)
+    }
+}
+
+/// Turns away a request whose headers are too large, reading on what the
+/// client still sends, up to a limit, so that closing the connection
+/// does not reset it before the reply gets there.
+fn too_large(stream: &mut TcpStream, reader: &mut BufReader<Request>) -> io::Result<()> {
+    reply(
+        stream,
+        "431 Request Header Fields Too Large",
+        "request headers too large",
+    )?;
+    stream.shutdown(Shutdown::Write)?;
+    reader.get_mut().left = MAX_HEADER;
+    io::copy(reader, &mut io::sink()).map(drop)
+}
+
+impl Read for Request {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.left == 0 {
+            return Ok(0);
+        }
+        if let Some(until) = self.until {
+            let left = until.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "request took too long",
+                ));
+            }
+            self.stream.set_read_timeout(Some(left))?;
+        }
+        let len = buf
+            .len()
+            .min(usize::try_from(self.left).unwrap_or(usize::MAX));
+        let read = self.stream.read(&mut buf[..len])?;
+        self.left -= read as u64;
+        Ok(read)
+    }
+}
+
+/// Serves scans of `root` on `listen` until the server fails, see
+/// [`Server`].
+pub fn serve<P: AsRef<Path>>(root: P, listen: &str) -> io::Result<()> {
+    Server::bind(root, listen)?.run()
+}
+
+fn reply(stream: &mut TcpStream, status: &str, message: &str) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{}\n",
+        status,
+        message.len() + 1,
+        message
+    )
+}
+
+fn chunk(stream: &mut TcpStream, data: &str) -> io::Result<()> {
+    write!(stream, "{:x}\r\n{}\r\n", data.len(), data)
+}
+
+#[cfg(all(feature = "watch", target_os = "linux"))]
+fn watch_event_json(event: &WatchEvent) -> String {
+    match event {
+        WatchEvent::Found(walk_result) => {
+            format!(
+                r#"{{"event":"found","result":{}}}"#,
+                json::result(walk_result)
+            )
+        }
+        WatchEvent::Added(walk_result) => {
+            format!(
+                r#"{{"event":"added","result":{}}}"#,
+                json::result(walk_result)
+            )
+        }
+        WatchEvent::Modified(walk_result) => {
+            format!(
+                r#"{{"event":"modified","result":{}}}"#,
+                json::result(walk_result)
+            )
+        }
+        WatchEvent::Removed(path) => {
+            format!(
+                r#"{{"event":"removed","path":{}}}"#,
+                json::json_string(path)
+            )
+        }
+        WatchEvent::Overflowed => r#"{"event":"overflowed"}"#.to_string(),
+    }
+}
+
+/// Reads the frames of a WebSocket client, answering pings, until it
+/// closes the connection.
+#[cfg(all(feature = "watch", target_os = "linux"))]
+fn read_frames(reader: &mut impl Read, stream: &Mutex<TcpStream>) -> io::Result<()> {
+    loop {
+        let mut header = [0; 2];
+        reader.read_exact(&mut header)?;
+        let opcode = header[0] & 0x0f;
+        // Clients must mask what they send.
+        if header[1] & 0x80 == 0 {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+        let len = match header[1] & 0x7f {
+            126 => {
+                let mut len = [0; 2];
+                reader.read_exact(&mut len)?;
+                u64::from(u16::from_be_bytes(len))
+            }
+            127 => {
+                let mut len = [0; 8];
+                reader.read_exact(&mut len)?;
+                u64::from_be_bytes(len)
+            }
+            len => u64::from(len),
+        };
+        if len > MAX_BODY as u64 {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+        let mut mask = [0; 4];
+        reader.read_exact(&mut mask)?;
+        let mut payload = vec![0; len as usize];
+        reader.read_exact(&mut payload)?;
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+        match opcode {
+            OPCODE_CLOSE => return Ok(()),
+            OPCODE_PING => send(stream, OPCODE_PONG, &payload)?,
+            // Nothing the client says is listened to.
+            _ => {}
+        }
+    }
+}
+
+/// Sends a single unmasked WebSocket frame.
+#[cfg(all(feature = "watch", target_os = "linux"))]
+fn send(stream: &Mutex<TcpStream>, opcode: u8, payload: &[u8]) -> io::Result<()> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        len @ 0..=125 => frame.push(len as u8),
+        len @ 126..=0xffff => {
+            frame.push(126);
+            frame.extend((len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend((len as u64).to_be_bytes());
+        }
+    }
+    frame.extend(payload);
+    stream.lock().unwrap().write_all(&frame)
+}
+
+/// The SHA-1 digest of the WebSocket handshake.
+#[cfg(all(feature = "watch", target_os = "linux"))]
+fn sha1(message: &[u8]) -> [u8; 20] {
+    let mut padded = message.to_vec();
+    padded.push(0x80);
+    while padded.len() % 64 != 56 {
+        padded.push(0);
+    }
+    padded.extend_from_slice(&((message.len() as u64).wrapping_mul(8)).to_be_bytes());
+
+    let mut state: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+    for block in padded.chunks(64) {
+        let mut words = [0u32; 80];
+        for (word, bytes) in words.iter_mut().zip(block.chunks(4)) {
+            *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        for i in 16..80 {
+            words[i] = (words[i - 3] ^ words[i - 8] ^ words[i - 14] ^ words[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+        for (i, word) in words.iter().enumerate() {
+            let (f, k) = match i / 20 {
+                0 => ((b & c) | (!b & d), 0x5a827999),
+                1 => (b ^ c ^ d, 0x6ed9eba1),
+                2 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+                _ => (b ^ c ^ d, 0xca62c1d6),
+            };
+            let t = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = t;
+        }
+        for (word, add) in state.iter_mut().zip([a, b, c, d, e]) {
+            *word = word.wrapping_add(add);
+        }
+    }
+    let mut digest = [0; 20];
+    for (bytes, word) in digest.chunks_mut(4).zip(state) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+/// Encodes bytes as padded base64.
+#[cfg(all(feature = "watch", target_os = "linux"))]
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, byte)| n | u32::from(*byte) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(addr: SocketAddr, request: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn it_serves_scans() {
+        let root = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/test");
+        let server = Server::bind(root, "127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || server.run());
+
+        let config = r#"{"kinds": ["audio"]}"#;
+        let response = request(
+            addr,
+            &format!(
+                "POST /scan HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}",
+                config.len(),
+                config
+            ),
+        );
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains(r#""mime":"audio/mpeg","kind":"audio","result":"media""#));
+        assert!(!response.contains("image/jpeg"));
+
+        let response = request(
+            addr,
+            "POST /scan HTTP/1.1\r\nAccept: text/event-stream\r\n\r\n",
+        );
+        assert!(response.contains("event: result\ndata: {"));
+        assert!(response.contains("event: stats\ndata: {\"files\":9,"));
+
+        let response = request(addr, "GET /stats HTTP/1.1\r\n\r\n");
+        assert!(response.contains(r#""files":9,"matched":8,"#));
+        assert!(response.contains(r#""audio/mpeg":{"count":2,"#));
+        assert!(response.contains(r#","schema_version":1}"#));
+
+        let response = request(addr, "GET /schema HTTP/1.1\r\n\r\n");
+        assert!(response.contains("Content-Type: application/schema+json"));
+        assert!(response.contains(r#""kind":{"enum":["audio","#));
+        assert!(response.contains(r#""schema_version":{"const":1}"#));
+        let body = response.split("\r\n\r\n").nth(1).unwrap();
+        let depth = body.chars().try_fold(0_i32, |depth, c| match c {
+            '{' | '[' => Some(depth + 1),
+            '}' | ']' => (depth > 0).then(|| depth - 1),
+            _ => Some(depth),
+        });
+        assert_eq!(depth, Some(0));
+
+        let response = request(addr, "POST /scan HTTP/1.1\r\nContent-Length: 1\r\n\r\n{");
+        assert!(response.starts_with("HTTP/1.1 400"));
+        let config = r#"{"thumbnails": true}"#;
+        let response = request(
+            addr,
+            &format!(
+                "POST /scan HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}",
+                config.len(),
+                config
+            ),
+        );
+        assert!(response.starts_with("HTTP/1.1 400"));
+        assert!(response.contains("thumbnails"));
+        assert!(request(addr, "GET /nope HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 404"));
+    }
+
+    #[test]
+    fn it_limits_requests() {
+        let root = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/test");
+        let mut server = Server::bind(root, "127.0.0.1:0").unwrap();
+        server.deadline = Duration::from_millis(300);
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || server.run());
+
+        let huge = format!(
+            "GET /stats HTTP/1.1\r\nX-Filler: {}\r\n\r\n",
+            "a".repeat(20_000)
+        );
+        assert!(request(addr, &huge).starts_with("HTTP/1.1 431"));
+
+        // A header every 50 ms would never time out a single read.
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(b"GET /stats HTTP/1.1\r\n").unwrap();
+        let mut trickle = stream.try_clone().unwrap();
+        thread::spawn(move || {
+            while trickle.write_all(b"X-Slow: yes\r\n").is_ok() {
+                thread::sleep(Duration::from_millis(50));
+            }
+        });
+        let started = Instant::now();
+        let mut response = vec![];
+        let _ = stream.read_to_end(&mut response);
+        assert!(response.is_empty());
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[cfg(all(feature = "watch", target_os = "linux"))]
+    #[test]
+    fn it_pushes_changes_over_a_websocket() {
+        let tree = crate::testutil::TempTree::new("serve-watch");
+        let server = Server::bind(tree.path(), "127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || server.run());
+
+        let response = request(addr, "GET /watch HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 426"));
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .unwrap();
+        // The example handshake of RFC 6455.
+        stream
+            .write_all(
+                b"GET /watch HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+                  Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n",
+            )
+            .unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut response = String::new();
+        while !response.ends_with("\r\n\r\n") {
+            reader.read_line(&mut response).unwrap();
+        }
+        assert!(response.starts_with("HTTP/1.1 101"));
+        assert!(response.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+
+        let frame = |reader: &mut BufReader<TcpStream>| {
+            let mut header = [0; 2];
+            reader.read_exact(&mut header).unwrap();
+            let mut len = usize::from(header[1] & 0x7f);
+            if len == 126 {
+                let mut extended = [0; 2];
+                reader.read_exact(&mut extended).unwrap();
+                len = usize::from(u16::from_be_bytes(extended));
+            }
+            let mut payload = vec![0; len];
+            reader.read_exact(&mut payload).unwrap();
+            (header[0], String::from_utf8(payload).unwrap())
+        };
+        tree.sample("sample3.mp3", "song.mp3");
+        let (header, message) = frame(&mut reader);
+        assert_eq!(header, 0x81);
+        assert!(message.starts_with(r#"{"event":"added","result":{"path":"#));
+        assert!(message.contains(r#""mime":"audio/mpeg""#));
+
+        // A masked ping, then a close.
+        stream
+            .write_all(&[0x89, 0x82, 1, 2, 3, 4, b'h' ^ 1, b'i' ^ 2])
+            .unwrap();
+        assert_eq!(frame(&mut reader), (0x8a, "hi".to_string()));
+        stream.write_all(&[0x88, 0x80, 1, 2, 3, 4]).unwrap();
+        assert_eq!(frame(&mut reader), (0x88, String::new()));
+    }
+}
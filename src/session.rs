@@ -0,0 +1,75 @@
+//! The IDs of walks, telling apart the output of concurrent ones.
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+
+/// The ID of a walk, which its results carry in
+/// [`MediaWalkResult::session_id`](crate::MediaWalkResult::session_id):
+/// a random, version 4 UUID, shown in its usual form, e.g.
+/// `9f1c2b4e-7a3d-4c5e-8b6f-0d1e2f3a4b5c`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SessionId(u128);
+
+impl SessionId {
+    /// A new random ID, from the random keys the standard library seeds
+    /// its hash maps with, the time, the process and a count.
+    pub(crate) fn new() -> Self {
+        static COUNT: AtomicU64 = AtomicU64::new(0);
+        let count = COUNT.fetch_add(1, Ordering::Relaxed);
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |since| since.as_nanos());
+        let half = || {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u128(now);
+            hasher.write_u32(process::id());
+            hasher.write_u64(count);
+            hasher.finish() as u128
+        };
+        let random = (half() << 64) | half();
+        // The version, 4, and the variant, 0b10.
+        SessionId(random & !(0xf << 76) & !(0x3 << 62) | (0x4 << 76) | (0x2 << 62))
+    }
+
+    /// The 128 bits of the ID.
+    pub fn as_u128(&self) -> u128 {
+        self.0
+    }
+}
+
+impl fmt::Display for SessionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hex = format!("{:032x}", self.0);
+        write!(
+            f,
+            "{}-{}-{}-{}-{}",
+            &hex[..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..]
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_makes_random_uuids() {
+        let (a, b) = (SessionId::new(), SessionId::new());
+        assert_ne!(a, b);
+        let shown = a.to_string();
+        assert_eq!(shown.len(), 36);
+        assert_eq!(shown.as_bytes()[14], b'4');
+        assert!(matches!(shown.as_bytes()[19], b'8' | b'9' | b'a' | b'b'));
+        assert_eq!(
+            SessionId(0x0123_4567_89ab_cdef_0123_4567_89ab_cdef).to_string(),
+            "01234567-89ab-cdef-0123-456789abcdef"
+        );
+    }
+}
@@ -0,0 +1,86 @@
+/// Shuffles a stream of items within a bounded window: an item is held
+/// back until a later one pushes it out at random.
+#[derive(Debug)]
+pub(crate) struct Shuffle<T> {
+    state: u64,
+    window: usize,
+    held: Vec<T>,
+}
+
+impl<T> Shuffle<T> {
+    pub(crate) fn new(seed: u64, window: usize) -> Self {
+        Shuffle {
+            state: seed,
+            window: window.max(1),
+            held: Vec::with_capacity(window),
+        }
+    }
+
+    /// A SplitMix64 step: fast, and plenty random for reordering.
+    fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    /// Adds an item, returning the one to let through, if any.
+    pub(crate) fn push(&mut self, item: T) -> Option<T> {
+        if self.held.len() < self.window {
+            self.held.push(item);
+            return None;
+        }
+        let i = self.below(self.held.len() + 1);
+        if i == self.held.len() {
+            return Some(item);
+        }
+        Some(std::mem::replace(&mut self.held[i], item))
+    }
+
+    /// Lets through the held items that are not to be kept, in the order
+    /// they are held in.
+    pub(crate) fn release(&mut self, mut keep: impl FnMut(&T) -> bool) -> Vec<T> {
+        let (kept, released) = std::mem::take(&mut self.held)
+            .into_iter()
+            .partition(|item| keep(item));
+        self.held = kept;
+        released
+    }
+
+    /// Lets the held items through, in random order.
+    pub(crate) fn drain(&mut self) -> Vec<T> {
+        for i in (1..self.held.len()).rev() {
+            let j = self.below(i + 1);
+            self.held.swap(i, j);
+        }
+        std::mem::take(&mut self.held)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_shuffles_within_the_window() {
+        let shuffled = |seed| {
+            let mut shuffle = Shuffle::new(seed, 10);
+            let mut out: Vec<_> = (0..100).filter_map(|i| shuffle.push(i)).collect();
+            assert_eq!(out.len(), 90);
+            out.extend(shuffle.drain());
+            out
+        };
+        let out = shuffled(7);
+        assert_ne!(out, (0..100).collect::<Vec<_>>());
+        assert_eq!(out, shuffled(7));
+        assert_ne!(out, shuffled(8));
+        let mut sorted = out.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..100).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,154 @@
+//! Perceptual signatures of videos, see
+//! [`MediaWalker::video_signatures`](crate::MediaWalker::video_signatures).
+use std::env;
+use std::ffi::OsString;
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// How many frames a signature is made of, spread evenly over the video.
+const FRAMES: usize = 8;
+
+/// The size of the grayscale thumbnails hashed: a pixel more than wide
+/// for each bit of a row.
+const WIDTH: usize = 9;
+const HEIGHT: usize = 8;
+
+/// A compact signature of a video: a perceptual hash of a keyframe at
+/// each of a few points through it. Re-encoding, scaling or a new
+/// container leave the hashes much the same, which groups the copies of
+/// a clip.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VideoSignature {
+    /// The difference hashes of the sampled frames, in order.
+    pub frames: Vec<u64>,
+}
+
+/// The programs signatures are made with.
+#[derive(Debug, Clone)]
+pub(crate) struct Ffmpeg {
+    pub(crate) ffprobe: OsString,
+    pub(crate) ffmpeg: OsString,
+}
+
+impl Ffmpeg {
+    /// `ffprobe` and `ffmpeg`, or the programs named by the `FFPROBE` and
+    /// `FFMPEG` environment variables.
+    pub(crate) fn from_env() -> Self {
+        Ffmpeg {
+            ffprobe: program("FFPROBE", "ffprobe"),
+            ffmpeg: program("FFMPEG", "ffmpeg"),
+        }
+    }
+}
+
+impl VideoSignature {
+    /// Samples and hashes the keyframes of a video with `ffprobe` and
+    /// `ffmpeg`.
+    pub(crate) fn compute(path: &Path, tools: &Ffmpeg) -> io::Result<VideoSignature> {
+        let duration = run(Command::new(&tools.ffprobe)
+            .args(["-v", "error", "-show_entries", "format=duration"])
+            .args(["-of", "default=noprint_wrappers=1:nokey=1"])
+            .arg(path))?;
+        let duration: f64 = String::from_utf8_lossy(&duration)
+            .trim()
+            .parse()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "unknown duration"))?;
+        let mut frames = Vec::with_capacity(FRAMES);
+        for i in 0..FRAMES {
+            // Seeking before the input lands on the keyframe before.
+            let at = duration * (i as f64 + 0.5) / FRAMES as f64;
+            let scale = format!("scale={}:{}:flags=area,format=gray", WIDTH, HEIGHT);
+            let pixels = run(Command::new(&tools.ffmpeg)
+                .args(["-v", "error", "-ss", &format!("{:.3}", at), "-i"])
+                .arg(path)
+                .args(["-frames:v", "1", "-vf", &scale, "-f", "rawvideo", "-"]))?;
+            if pixels.len() < WIDTH * HEIGHT {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "no frame"));
+            }
+            frames.push(dhash(&pixels));
+        }
+        Ok(VideoSignature { frames })
+    }
+
+    /// How alike two signatures are, from 0.0 to 1.0: the share of the
+    /// hash bits of their frames that match. Unrelated videos score about
+    /// 0.5; copies of the same clip above 0.9.
+    pub fn similarity(&self, other: &VideoSignature) -> f64 {
+        let compared = self.frames.len().min(other.frames.len());
+        if compared == 0 {
+            return 0.0;
+        }
+        let differing: u32 = self
+            .frames
+            .iter()
+            .zip(&other.frames)
+            .map(|(a, b)| (a ^ b).count_ones())
+            .sum();
+        1.0 - differing as f64 / (64 * compared) as f64
+    }
+}
+
+/// The program to run for a tool, from an environment variable if set.
+fn program(var: &str, default: &str) -> OsString {
+    env::var_os(var).unwrap_or_else(|| default.into())
+}
+
+/// Runs a command to completion, returning what it printed.
+fn run(command: &mut Command) -> io::Result<Vec<u8>> {
+    let output = command
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "{:?} failed with {}",
+            command.get_program(),
+            output.status
+        )));
+    }
+    Ok(output.stdout)
+}
+
+/// The difference hash of a grayscale thumbnail: a bit for each pixel
+/// brighter than its neighbour to the right.
+fn dhash(pixels: &[u8]) -> u64 {
+    let mut hash = 0;
+    for row in pixels.chunks_exact(WIDTH).take(HEIGHT) {
+        for pair in row.windows(2) {
+            hash = hash << 1 | u64::from(pair[0] > pair[1]);
+        }
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_hashes_frames() {
+        let falling: Vec<u8> = (0..HEIGHT)
+            .flat_map(|_| (0..WIDTH as u8).rev().map(|x| x * 20))
+            .collect();
+        assert_eq!(dhash(&falling), u64::MAX);
+        let rising: Vec<u8> = falling.iter().rev().copied().collect();
+        assert_eq!(dhash(&rising), 0);
+        // A re-encode brightens it a little, flipping a bit or two.
+        let mut brighter: Vec<u8> = falling.iter().map(|p| p + 5).collect();
+        brighter[1] = brighter[0];
+
+        let clip = VideoSignature {
+            frames: vec![dhash(&falling), dhash(&rising)],
+        };
+        let copy = VideoSignature {
+            frames: vec![dhash(&brighter), dhash(&rising)],
+        };
+        let other = VideoSignature {
+            frames: vec![0x5555_5555_5555_5555; 2],
+        };
+        assert_eq!(clip.similarity(&clip), 1.0);
+        assert!(clip.similarity(&copy) > 0.9);
+        assert_eq!(clip.similarity(&other), 0.5);
+    }
+}
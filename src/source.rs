@@ -0,0 +1,162 @@
+//! Pluggable filesystems, see [`MediaWalker::walk_source`](crate::MediaWalker::walk_source).
+use crate::walker;
+use std::fs::{self, File};
+use std::io;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// An entry of a [`WalkSource`]: a file or a directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceEntry {
+    /// The path of the entry.
+    pub path: PathBuf,
+    /// Whether the entry is a directory.
+    pub is_dir: bool,
+    /// The size of a file, in bytes.
+    pub len: u64,
+    /// When the entry was last modified, if known.
+    pub modified: Option<SystemTime>,
+}
+
+/// A filesystem to walk. Walking a source takes no threads of its own,
+/// and the sniffing and filtering can run over files that are not on a
+/// disk at all, like those of an archive or held in memory.
+pub trait WalkSource {
+    /// Returns the entry at a path.
+    fn entry(&self, path: &Path) -> io::Result<SourceEntry>;
+
+    /// Lists the entries of a directory.
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<SourceEntry>>;
+
+    /// Reads up to `len` bytes from the start of a file.
+    fn read_header(&self, path: &Path, len: usize) -> io::Result<Vec<u8>>;
+}
+
+/// The local filesystem, through [`std::fs`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FsSource;
+
+impl FsSource {
+    fn entry_of(path: PathBuf, metadata: fs::Metadata) -> SourceEntry {
+        SourceEntry {
+            path,
+            is_dir: metadata.is_dir(),
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        }
+    }
+}
+
+impl WalkSource for FsSource {
+    fn entry(&self, path: &Path) -> io::Result<SourceEntry> {
+        Ok(Self::entry_of(path.to_path_buf(), fs::metadata(path)?))
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<SourceEntry>> {
+        // The directories above a path are the ones being walked, so a
+        // link to one of them is a loop.
+        if fs::symlink_metadata(path)?.file_type().is_symlink() {
+            let id = walker::dir_id(path, None);
+            if id.is_some()
+                && path
+                    .ancestors()
+                    .skip(1)
+                    .any(|a| walker::dir_id(a, None) == id)
+            {
+                return Err(walker::loop_error(path, path));
+            }
+        }
+        let mut entries = vec![];
+        for entry in fs::read_dir(path)? {
+            let path = entry?.path();
+            // Follows symbolic links, like the threaded walks do. A
+            // dangling link is listed as itself, and fails to be read.
+            let metadata = fs::metadata(&path).or_else(|_| fs::symlink_metadata(&path))?;
+            entries.push(Self::entry_of(path, metadata));
+        }
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(entries)
+    }
+
+    fn read_header(&self, path: &Path, len: usize) -> io::Result<Vec<u8>> {
+        let mut header = Vec::with_capacity(len);
+        File::open(path)?
+            .take(len as u64)
+            .read_to_end(&mut header)?;
+        Ok(header)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{MemorySource, Sample};
+    use crate::testutil::TempTree;
+    use crate::{ErrorPolicy, Kind, MediaWalkResult, MediaWalker};
+
+    #[test]
+    fn it_walks_a_source() {
+        let source = MemorySource::new()
+            .sample("/m/a/cover.png", Sample::Png)
+            .file("/m/a/notes.txt", "hello")
+            .sample("/m/b/skipped/x.png", Sample::Png);
+        let results: Vec<_> = MediaWalker::new("/m")
+            .kinds(&[Kind::Image])
+            .exclude("b/skipped")
+            .walk_source(&source)
+            .collect();
+        let paths: Vec<_> = results.iter().map(|r| r.path.as_str()).collect();
+        assert_eq!(paths, ["/m/a/cover.png", "/m/a/notes.txt"]);
+        assert_eq!(&*results[0].mime, "image/png");
+        assert!(matches!(results[1].result, Ok(false)));
+
+        let results: Vec<_> = MediaWalker::new("/gone")
+            .error_policy(ErrorPolicy::Ignore)
+            .walk_source(&source)
+            .collect();
+        assert!(results.is_empty());
+        let results: Vec<_> = MediaWalker::new("/gone").walk_source(&source).collect();
+        assert!(results[0].result.is_err());
+
+        let results: Vec<_> =
+            MediaWalker::new(concat!(env!("CARGO_MANIFEST_DIR"), "/resources/test"))
+                .walk_source(&FsSource)
+                .collect();
+        assert_eq!(results.len(), 9);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn it_stops_at_links_to_ancestors() {
+        let tree = TempTree::new("source-loops");
+        tree.sample("sample_640x426.jpg", "a/x.jpg");
+        tree.sample("sample_640x426.jpg", "b/y.jpg");
+        std::os::unix::fs::symlink("../b", tree.path().join("a/tob")).unwrap();
+        std::os::unix::fs::symlink("../a", tree.path().join("b/toa")).unwrap();
+        std::os::unix::fs::symlink("..", tree.path().join("a/up")).unwrap();
+
+        let results: Vec<_> = MediaWalker::new(tree.path())
+            .walk_source(&FsSource)
+            .collect();
+        let relative = |r: &MediaWalkResult| {
+            let path = r.path.strip_prefix(tree.path().to_str().unwrap());
+            path.unwrap().to_string()
+        };
+        let found: Vec<_> = results
+            .iter()
+            .filter(|r| r.result.is_ok())
+            .map(relative)
+            .collect();
+        assert_eq!(
+            found,
+            ["/a/tob/y.jpg", "/a/x.jpg", "/b/toa/x.jpg", "/b/y.jpg"]
+        );
+        let loops: Vec<_> = results
+            .iter()
+            .filter(|r| r.result.is_err())
+            .map(relative)
+            .collect();
+        assert_eq!(loops, ["/a/tob/toa", "/a/up", "/b/toa/tob", "/b/toa/up"]);
+    }
+}
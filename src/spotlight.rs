@@ -0,0 +1,86 @@
+//! Seeding walks from the Spotlight index on macOS, see
+//! [`MediaWalker::spotlight`](crate::MediaWalker::spotlight).
+use crate::Kind;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The Spotlight content types of the kinds it can look up.
+const CONTENT_TYPES: &[(Kind, &str)] = &[
+    (Kind::Audio, "public.audio"),
+    (Kind::Image, "public.image"),
+    (Kind::Video, "public.movie"),
+];
+
+/// Builds the `mdfind` query for files of the given kinds. Returns
+/// `None` if Spotlight cannot look up all of them.
+fn query(kinds: &[Kind]) -> Option<String> {
+    let clauses: Vec<String> = kinds
+        .iter()
+        .map(|kind| {
+            CONTENT_TYPES
+                .iter()
+                .find(|(known, _)| known == kind)
+                .map(|(_, uti)| format!("kMDItemContentTypeTree == \"{}\"", uti))
+        })
+        .collect::<Option<_>>()?;
+    (!clauses.is_empty()).then(|| clauses.join(" || "))
+}
+
+/// Splits the NUL-separated output of `mdfind -0` into paths.
+fn parse_output(output: &[u8]) -> Vec<PathBuf> {
+    output
+        .split(|b| *b == 0)
+        .filter(|path| !path.is_empty())
+        .map(|path| PathBuf::from(String::from_utf8_lossy(path).into_owned()))
+        .collect()
+}
+
+/// Asks Spotlight for the files of the given kinds under `root`,
+/// returned under `root` as it was given. Fails if Spotlight is not
+/// around, or cannot look up all the kinds.
+pub(crate) fn candidates(root: &Path, kinds: &[Kind]) -> io::Result<Vec<PathBuf>> {
+    if !cfg!(target_os = "macos") {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Spotlight is a macOS thing",
+        ));
+    }
+    let query = query(kinds).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Spotlight cannot look up all the kinds",
+        )
+    })?;
+    let absolute = root.canonicalize()?;
+    let output = std::process::Command::new("mdfind")
+        .arg("-0")
+        .arg("-onlyin")
+        .arg(&absolute)
+        .arg(query)
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other("mdfind failed"));
+    }
+    Ok(parse_output(&output.stdout)
+        .into_iter()
+        .filter_map(|path| Some(root.join(path.strip_prefix(&absolute).ok()?)))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_queries_spotlight_for_media_kinds_only() {
+        assert_eq!(
+            query(&[Kind::Audio, Kind::Video]).as_deref(),
+            Some("kMDItemContentTypeTree == \"public.audio\" || kMDItemContentTypeTree == \"public.movie\"")
+        );
+        assert_eq!(query(&[Kind::Image, Kind::Companion]), None);
+        assert_eq!(
+            parse_output(b"/a/b.jpg\0/a/c d.mp3\0"),
+            vec![PathBuf::from("/a/b.jpg"), PathBuf::from("/a/c d.mp3")]
+        );
+    }
+}
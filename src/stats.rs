@@ -0,0 +1,184 @@
+use crate::{CollectedError, Kind, MediaWalkResult, SkipReason};
+use std::collections::HashMap;
+use std::fs::Metadata;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+const MB: u64 = 1_000_000;
+
+/// Running totals of a walk, see [`WalkHandle::stats`](crate::WalkHandle::stats).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WalkStats {
+    /// How many files have been reported, whatever their result.
+    pub files: u64,
+    /// How many files of a wanted kind have been found.
+    pub matched: u64,
+    /// How many files had no recognizable media type.
+    pub unknown: u64,
+    /// How many files were flagged as suspicious, see
+    /// [`MediaWalker::flag_suspicious`](crate::MediaWalker::flag_suspicious).
+    /// They are not counted as matched.
+    pub suspicious: u64,
+    /// How many files could not be inspected, or directories read.
+    pub errors: u64,
+    /// The errors themselves, when walking with
+    /// [`ErrorPolicy::Collect`](crate::ErrorPolicy::Collect).
+    pub failures: Vec<CollectedError>,
+    /// How many cache directories of photo workflows were skipped, see
+    /// [`MediaWalker::workflow_caches`](crate::MediaWalker::workflow_caches).
+    pub pruned_caches: u64,
+    /// How many entries were left out, by why: those the filters left
+    /// out, as well as the ones reported as
+    /// [`WalkEvent::Skipped`](crate::WalkEvent::Skipped).
+    pub skipped: HashMap<SkipReason, u64>,
+    /// The total size of the matched files, in bytes.
+    pub bytes: u64,
+    /// The matched files broken down by MIME type.
+    pub by_mime: HashMap<Arc<str>, MimeStats>,
+    /// The matched files broken down by kind.
+    pub by_kind: HashMap<Kind, KindStats>,
+    /// The oldest modification time of the matched files.
+    pub oldest: Option<SystemTime>,
+    /// The newest modification time of the matched files.
+    pub newest: Option<SystemTime>,
+}
+
+/// Totals for a single MIME type.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MimeStats {
+    /// How many files of this type have been found.
+    pub count: u64,
+    /// The total size of those files, in bytes.
+    pub bytes: u64,
+}
+
+/// Totals for a single kind.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KindStats {
+    /// How many files of this kind have been found.
+    pub count: u64,
+    /// The total size of those files, in bytes.
+    pub bytes: u64,
+    /// The distribution of their sizes.
+    pub sizes: SizeHistogram,
+    /// The oldest modification time of those files.
+    pub oldest: Option<SystemTime>,
+    /// The newest modification time of those files.
+    pub newest: Option<SystemTime>,
+}
+
+/// A log-scale file size histogram: <1 MB, 1–10 MB, 10–100 MB,
+/// 100 MB–1 GB, 1–10 GB, and 10 GB or more.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SizeHistogram {
+    /// The file counts per bucket, smallest sizes first.
+    pub counts: [u64; SizeHistogram::BUCKETS],
+}
+
+impl SizeHistogram {
+    /// The number of buckets.
+    pub const BUCKETS: usize = 6;
+
+    /// Returns the index of the bucket for the given size.
+    pub fn bucket(size: u64) -> usize {
+        let mut bucket = 0;
+        let mut limit = MB;
+        while bucket < Self::BUCKETS - 1 && size >= limit {
+            bucket += 1;
+            limit = limit.saturating_mul(10);
+        }
+        bucket
+    }
+
+    /// Returns the size range of a bucket in bytes: the inclusive lower
+    /// bound, and the exclusive upper bound, if any.
+    pub fn range(bucket: usize) -> (u64, Option<u64>) {
+        let lower = match bucket {
+            0 => 0,
+            _ => MB * 10u64.pow(bucket as u32 - 1),
+        };
+        let upper = (bucket < Self::BUCKETS - 1).then(|| MB * 10u64.pow(bucket as u32));
+        (lower, upper)
+    }
+
+    pub(crate) fn add(&mut self, size: u64) {
+        self.counts[Self::bucket(size)] += 1;
+    }
+}
+
+impl WalkStats {
+    /// Accounts for a single reported file.
+    pub(crate) fn record(&mut self, walk_result: &MediaWalkResult, metadata: Option<&Metadata>) {
+        let size = metadata.map_or(0, |m| m.len());
+        let modified = metadata.and_then(|m| m.modified().ok());
+        self.files += 1;
+        if walk_result.suspicious {
+            self.suspicious += 1;
+            return;
+        }
+        match walk_result.result {
+            Ok(true) => {
+                self.matched += 1;
+                self.bytes += size;
+                widen(&mut self.oldest, &mut self.newest, modified);
+                let mime = self.by_mime.entry(walk_result.mime.clone()).or_default();
+                mime.count += 1;
+                mime.bytes += size;
+                if let Some(kind) = walk_result.kind {
+                    let kind = self.by_kind.entry(kind).or_default();
+                    kind.count += 1;
+                    kind.bytes += size;
+                    kind.sizes.add(size);
+                    widen(&mut kind.oldest, &mut kind.newest, modified);
+                }
+            }
+            Ok(false) => self.unknown += 1,
+            Err(_) => self.errors += 1,
+        }
+    }
+}
+
+/// Widens the `oldest`..`newest` range to cover `time`.
+fn widen(
+    oldest: &mut Option<SystemTime>,
+    newest: &mut Option<SystemTime>,
+    time: Option<SystemTime>,
+) {
+    if let Some(time) = time {
+        if oldest.is_none_or(|oldest| time < oldest) {
+            *oldest = Some(time);
+        }
+        if newest.is_none_or(|newest| time > newest) {
+            *newest = Some(time);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_buckets_sizes_on_a_log_scale() {
+        assert_eq!(SizeHistogram::bucket(0), 0);
+        assert_eq!(SizeHistogram::bucket(MB - 1), 0);
+        assert_eq!(SizeHistogram::bucket(MB), 1);
+        assert_eq!(SizeHistogram::bucket(99 * MB), 2);
+        assert_eq!(SizeHistogram::bucket(5_000 * MB), 4);
+        assert_eq!(SizeHistogram::bucket(u64::MAX), 5);
+        assert_eq!(SizeHistogram::range(0), (0, Some(MB)));
+        assert_eq!(SizeHistogram::range(2), (10 * MB, Some(100 * MB)));
+        assert_eq!(SizeHistogram::range(5), (10_000 * MB, None));
+    }
+
+    #[test]
+    fn it_widens_the_time_range() {
+        let (mut oldest, mut newest) = (None, None);
+        let early = SystemTime::UNIX_EPOCH;
+        let late = early + std::time::Duration::from_secs(60);
+        widen(&mut oldest, &mut newest, Some(late));
+        widen(&mut oldest, &mut newest, None);
+        widen(&mut oldest, &mut newest, Some(early));
+        assert_eq!((oldest, newest), (Some(early), Some(late)));
+    }
+}
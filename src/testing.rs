@@ -0,0 +1,435 @@
+//! Helpers for testing what consumes the results of walks, without
+//! directories of sample media: a filesystem held in memory, to walk
+//! with [`MediaWalker::walk_source`](crate::MediaWalker::walk_source),
+//! and the smallest files that are told apart as media of each type,
+//! also written to temporary trees with [`generate_tree`].
+//!
+//! # Examples
+//!
+//! ```
+//! use mediawalker::testing::{MemorySource, Sample};
+//! use mediawalker::MediaWalker;
+//! let source = MemorySource::new()
+//!     .file("/photos/a.jpg", Sample::Jpeg.bytes())
+//!     .file("/photos/notes.txt", "not media");
+//! let found: Vec<_> = MediaWalker::new("/photos").walk_source(&source).collect();
+//! assert_eq!(&*found[0].mime, "image/jpeg");
+//! assert_eq!(found[1].result.as_ref().ok(), Some(&false));
+//! ```
+use crate::{SourceEntry, WalkSource};
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::SystemTime;
+
+/// A filesystem held in memory, built up a file at a time. The
+/// directories above the files are there without being added.
+#[derive(Debug, Clone, Default)]
+pub struct MemorySource {
+    files: BTreeMap<PathBuf, MemoryFile>,
+    dirs: BTreeSet<PathBuf>,
+}
+
+#[derive(Debug, Clone)]
+struct MemoryFile {
+    bytes: Vec<u8>,
+    modified: Option<SystemTime>,
+    /// The error reading the file fails with, if it does.
+    error: Option<io::ErrorKind>,
+}
+
+impl MemorySource {
+    /// Creates an empty filesystem.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a file, replacing any at its path.
+    pub fn file<P: AsRef<Path>>(mut self, path: P, bytes: impl Into<Vec<u8>>) -> Self {
+        let file = MemoryFile {
+            bytes: bytes.into(),
+            modified: None,
+            error: None,
+        };
+        self.files.insert(path.as_ref().to_path_buf(), file);
+        self
+    }
+
+    /// Adds a file of a sample type, see [`Sample::bytes`].
+    pub fn sample<P: AsRef<Path>>(self, path: P, sample: Sample) -> Self {
+        self.file(path, sample.bytes())
+    }
+
+    /// Adds a directory, which is only needed for an empty one.
+    pub fn dir<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.dirs.insert(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Sets when a file added before was last modified.
+    pub fn modified<P: AsRef<Path>>(mut self, path: P, time: SystemTime) -> Self {
+        if let Some(file) = self.files.get_mut(path.as_ref()) {
+            file.modified = Some(time);
+        }
+        self
+    }
+
+    /// Adds a file that is listed, but fails to be read with an error of
+    /// the given kind, as one the walk may not read would.
+    pub fn unreadable<P: AsRef<Path>>(mut self, path: P, kind: io::ErrorKind) -> Self {
+        let file = MemoryFile {
+            bytes: vec![],
+            modified: None,
+            error: Some(kind),
+        };
+        self.files.insert(path.as_ref().to_path_buf(), file);
+        self
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.dirs.iter().any(|dir| dir.starts_with(path))
+            || self
+                .files
+                .keys()
+                .any(|file| file != path && file.starts_with(path))
+    }
+}
+
+impl WalkSource for MemorySource {
+    fn entry(&self, path: &Path) -> io::Result<SourceEntry> {
+        let (is_dir, len, modified) = match self.files.get(path) {
+            Some(file) => (false, file.bytes.len() as u64, file.modified),
+            None if self.is_dir(path) => (true, 0, None),
+            None => return Err(io::ErrorKind::NotFound.into()),
+        };
+        Ok(SourceEntry {
+            path: path.to_path_buf(),
+            is_dir,
+            len,
+            modified,
+        })
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<SourceEntry>> {
+        if !self.is_dir(path) {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+        let children: BTreeSet<_> = self
+            .files
+            .keys()
+            .chain(&self.dirs)
+            .filter_map(|entry| Some(path.join(entry.strip_prefix(path).ok()?.iter().next()?)))
+            .collect();
+        children.iter().map(|child| self.entry(child)).collect()
+    }
+
+    fn read_header(&self, path: &Path, len: usize) -> io::Result<Vec<u8>> {
+        let file = self.files.get(path).ok_or(io::ErrorKind::NotFound)?;
+        if let Some(kind) = file.error {
+            return Err(kind.into());
+        }
+        Ok(file.bytes[..len.min(file.bytes.len())].to_vec())
+    }
+}
+
+/// A type of media, of which [`bytes`](Self::bytes) are the smallest
+/// file that is told apart as one. The files are not valid beyond their
+/// headers: they are for walks to find, not for decoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Sample {
+    /// A JPEG image, `image/jpeg`.
+    Jpeg,
+    /// A 1×1 PNG image, `image/png`.
+    Png,
+    /// An MP3 song, `audio/mpeg`.
+    Mp3,
+    /// An MP4 video, `video/mp4`.
+    Mp4,
+}
+
+impl Sample {
+    /// All the samples.
+    pub const ALL: [Sample; 4] = [Sample::Jpeg, Sample::Png, Sample::Mp3, Sample::Mp4];
+
+    /// The bytes of the file.
+    pub fn bytes(self) -> &'static [u8] {
+        match self {
+            Sample::Jpeg => &[
+                0xff, 0xd8, 0xff, 0xe0, 0x00, 0x10, b'J', b'F', b'I', b'F', 0x00, 0x01, 0x01, 0x00,
+                0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0xff, 0xd9,
+            ],
+            Sample::Png => &[
+                0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n', 0x00, 0x00, 0x00, 0x0d, b'I',
+                b'H', b'D', b'R', 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00,
+                0x00, 0x00, 0x1f, 0x15, 0xc4, 0x89, 0x00, 0x00, 0x00, 0x00, b'I', b'E', b'N', b'D',
+                0xae, 0x42, 0x60, 0x82,
+            ],
+            Sample::Mp3 => &[
+                b'I', b'D', b'3', 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0xfb, 0x90, 0x00,
+            ],
+            Sample::Mp4 => &[
+                0x00, 0x00, 0x00, 0x18, b'f', b't', b'y', b'p', b'i', b's', b'o', b'm', 0x00, 0x00,
+                0x02, 0x00, b'i', b's', b'o', b'm', b'm', b'p', b'4', b'2',
+            ],
+        }
+    }
+
+    /// The sample of the files of an extension, ignoring case.
+    pub fn from_extension(ext: &str) -> Option<Sample> {
+        match ext.to_ascii_lowercase().as_str() {
+            "jpg" | "jpeg" => Some(Sample::Jpeg),
+            "png" => Some(Sample::Png),
+            "mp3" => Some(Sample::Mp3),
+            "mp4" | "m4v" => Some(Sample::Mp4),
+            _ => None,
+        }
+    }
+
+    /// The usual extension of the files, e.g. `jpg`.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Sample::Jpeg => "jpg",
+            Sample::Png => "png",
+            Sample::Mp3 => "mp3",
+            Sample::Mp4 => "mp4",
+        }
+    }
+
+    /// The MIME type the files are found with.
+    pub fn mime(self) -> &'static str {
+        match self {
+            Sample::Jpeg => "image/jpeg",
+            Sample::Png => "image/png",
+            Sample::Mp3 => "audio/mpeg",
+            Sample::Mp4 => "video/mp4",
+        }
+    }
+}
+
+/// A temporary directory tree made by [`generate_tree`], removed when
+/// dropped.
+#[derive(Debug)]
+pub struct GeneratedTree {
+    root: PathBuf,
+    files: Vec<PathBuf>,
+}
+
+impl GeneratedTree {
+    /// The root of the tree.
+    pub fn path(&self) -> &Path {
+        &self.root
+    }
+
+    /// The files of the tree, in the order of the spec.
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+}
+
+impl Drop for GeneratedTree {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+/// Makes a temporary directory tree of the files of a spec, a line per
+/// file or directory:
+///
+/// - `photos/a.jpg` is a file, of the [`Sample`] of its extension, or
+///   of a line of text if there is none;
+/// - `photos/img{}.jpg 100` is 100 files, `{}` being replaced with
+///   their numbers from 1, or a number being added before the
+///   extension if there is no `{}`;
+/// - `empty/`, ending with a slash, is a directory.
+///
+/// Blank lines, and the lines starting with `#`, are left out. Fails on
+/// a path that is not relative, or a count that is not a number, which
+/// a path with whitespace in it is taken to have.
+///
+/// # Examples
+///
+/// ```
+/// use mediawalker::testing::generate_tree;
+/// use mediawalker::MediaWalker;
+/// let tree = generate_tree(
+///     "photos/2024/img{}.jpg 3
+///      music/song.mp3
+///      notes.txt",
+/// )?;
+/// let found: Vec<_> = MediaWalker::new(tree.path()).start().collect();
+/// assert_eq!(found.len(), 5);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn generate_tree(spec: &str) -> io::Result<GeneratedTree> {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let root = env::temp_dir().join(format!(
+        "mediawalker-tree-{}-{}",
+        process::id(),
+        COUNT.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::create_dir_all(&root)?;
+    // Removes what was made if the spec turns out bad.
+    let mut tree = GeneratedTree {
+        root,
+        files: vec![],
+    };
+    for (number, line) in spec.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = |message: &str| {
+            let message = format!("line {}: {}: {}", number + 1, message, line);
+            io::Error::new(io::ErrorKind::InvalidInput, message)
+        };
+        let (path, count) = match line.rsplit_once(char::is_whitespace) {
+            Some((path, count)) => {
+                let count = count.parse().map_err(|_| invalid("bad count"))?;
+                (path.trim_end(), Some(count))
+            }
+            None => (line, None),
+        };
+        if Path::new(path).is_absolute() || path.split('/').any(|part| part == "..") {
+            return Err(invalid("not a relative path"));
+        }
+        if let Some(dir) = path.strip_suffix('/') {
+            fs::create_dir_all(tree.root.join(dir))?;
+            continue;
+        }
+        let paths: Vec<String> = match count {
+            None => vec![path.to_string()],
+            Some(count) => (1..=count).map(|n| numbered(path, n)).collect(),
+        };
+        for path in paths {
+            let path = tree.root.join(path);
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            let sample = path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .and_then(Sample::from_extension);
+            match sample {
+                Some(sample) => fs::write(&path, sample.bytes())?,
+                None => fs::write(&path, b"not media\n")?,
+            }
+            tree.files.push(path);
+        }
+    }
+    Ok(tree)
+}
+
+/// The path of the `n`th of the files of a line of a spec.
+fn numbered(path: &str, n: usize) -> String {
+    if path.contains("{}") {
+        return path.replace("{}", &n.to_string());
+    }
+    let name_start = path.rfind('/').map_or(0, |slash| slash + 1);
+    match path[name_start..].rfind('.') {
+        Some(dot) if dot > 0 => {
+            let dot = name_start + dot;
+            format!("{}{}{}", &path[..dot], n, &path[dot..])
+        }
+        _ => format!("{}{}", path, n),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ErrorPolicy, Kind, MediaWalker};
+
+    #[test]
+    fn it_walks_files_held_in_memory() {
+        let mut source = MemorySource::new().dir("/m/empty");
+        for sample in Sample::ALL {
+            source = source.sample(format!("/m/a/x.{}", sample.extension()), sample);
+        }
+        let source = source
+            .file("/m/a/notes.txt", "hello")
+            .unreadable("/m/b/locked.jpg", io::ErrorKind::PermissionDenied);
+        let results: Vec<_> = MediaWalker::new("/m")
+            .kinds(&Kind::MEDIA)
+            .walk_source(&source)
+            .collect();
+        let found: Vec<_> = results
+            .iter()
+            .map(|r| (r.path.as_str(), &*r.mime, r.result.as_ref().ok().copied()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("/m/a/notes.txt", "", Some(false)),
+                ("/m/a/x.jpg", Sample::Jpeg.mime(), Some(true)),
+                ("/m/a/x.mp3", Sample::Mp3.mime(), Some(true)),
+                ("/m/a/x.mp4", Sample::Mp4.mime(), Some(true)),
+                ("/m/a/x.png", Sample::Png.mime(), Some(true)),
+                ("/m/b/locked.jpg", "", None),
+            ]
+        );
+
+        let results: Vec<_> = MediaWalker::new("/m")
+            .error_policy(ErrorPolicy::Ignore)
+            .walk_source(&source)
+            .collect();
+        assert_eq!(results.len(), 5);
+        assert_eq!(source.read_dir(Path::new("/m")).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn it_generates_trees_from_specs() {
+        let tree = generate_tree(
+            "# A library.
+             photos/img{}.jpg 3
+             photos/raw.png 2
+             video/clip.MP4
+             music/
+             README",
+        )
+        .unwrap();
+        let relative: Vec<_> = tree
+            .files()
+            .iter()
+            .map(|file| file.strip_prefix(tree.path()).unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(
+            relative,
+            [
+                "photos/img1.jpg",
+                "photos/img2.jpg",
+                "photos/img3.jpg",
+                "photos/raw1.png",
+                "photos/raw2.png",
+                "video/clip.MP4",
+                "README",
+            ]
+        );
+        assert!(tree.path().join("music").is_dir());
+
+        let mut found: Vec<_> = MediaWalker::new(tree.path()).start().collect();
+        found.sort_by(|a, b| a.path.cmp(&b.path));
+        let mimes: Vec<_> = found.iter().map(|r| &*r.mime).collect();
+        assert_eq!(
+            mimes,
+            [
+                "",
+                "image/jpeg",
+                "image/jpeg",
+                "image/jpeg",
+                "image/png",
+                "image/png",
+                "video/mp4"
+            ]
+        );
+
+        let root = tree.path().to_path_buf();
+        drop(tree);
+        assert!(!root.exists());
+        assert!(generate_tree("a.jpg many").is_err());
+        assert!(generate_tree("../a.jpg").is_err());
+    }
+}
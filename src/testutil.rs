@@ -0,0 +1,54 @@
+//! Helpers shared by the unit tests.
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A temporary directory tree, removed when dropped.
+pub struct TempTree {
+    root: PathBuf,
+}
+
+impl TempTree {
+    pub fn new(name: &str) -> Self {
+        let mut root = env::temp_dir();
+        root.push(format!(
+            "mediawalker-{}-{}-{}",
+            name,
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        fs::create_dir_all(&root).unwrap();
+        TempTree { root }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.root
+    }
+
+    /// Writes a file, creating its parent directories as needed.
+    pub fn file(&self, relative: &str, contents: &[u8]) -> PathBuf {
+        let path = self.root.join(relative);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    /// Copies a file from `resources/test` into the tree.
+    pub fn sample(&self, sample: &str, relative: &str) -> PathBuf {
+        let mut source = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        source.push("resources");
+        source.push("test");
+        source.push(sample);
+        self.file(relative, &fs::read(source).unwrap())
+    }
+}
+
+impl Drop for TempTree {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
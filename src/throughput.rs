@@ -0,0 +1,118 @@
+//! How fast a walk goes, for the events telling so, see
+//! [`MediaWalker::throughput`](crate::MediaWalker::throughput).
+use crate::WalkEvent;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How far back the speed is measured over.
+const WINDOW: Duration = Duration::from_secs(10);
+
+const MB: f64 = 1_000_000.0;
+
+/// The files and bytes a walk has gone through, and the totals of them
+/// in the last [`WINDOW`].
+#[derive(Debug)]
+pub(crate) struct Meter {
+    files: AtomicU64,
+    bytes: AtomicU64,
+    /// When the totals were taken, oldest first, with the totals.
+    samples: Mutex<VecDeque<(Instant, u64, u64)>>,
+}
+
+impl Meter {
+    pub(crate) fn new() -> Self {
+        Meter {
+            files: AtomicU64::new(0),
+            bytes: AtomicU64::new(0),
+            samples: Mutex::new(VecDeque::from([(Instant::now(), 0, 0)])),
+        }
+    }
+
+    /// Counts a file gone through, of `bytes`.
+    pub(crate) fn count(&self, bytes: u64) {
+        self.files.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// The speed over the last [`WINDOW`], and how long the rest of the
+    /// files to expect would take at it.
+    pub(crate) fn read(&self, expected_files: Option<u64>) -> WalkEvent {
+        let now = Instant::now();
+        let files = self.files.load(Ordering::Relaxed);
+        let bytes = self.bytes.load(Ordering::Relaxed);
+        let mut samples = self.samples.lock().unwrap();
+        samples.push_back((now, files, bytes));
+        while samples.len() > 2 && now - samples[1].0 >= WINDOW {
+            samples.pop_front();
+        }
+        speed(samples[0], (now, files, bytes), expected_files)
+    }
+}
+
+/// The speed between two samples of the totals.
+fn speed(
+    (then, files_then, bytes_then): (Instant, u64, u64),
+    (now, files, bytes): (Instant, u64, u64),
+    expected_files: Option<u64>,
+) -> WalkEvent {
+    let secs = (now - then).as_secs_f64().max(f64::MIN_POSITIVE);
+    let files_per_sec = (files - files_then) as f64 / secs;
+    let mb_per_sec = (bytes - bytes_then) as f64 / MB / secs;
+    let eta = expected_files.and_then(|expected| {
+        let left = expected.saturating_sub(files) as f64;
+        match files_per_sec > 0.0 {
+            true => Duration::try_from_secs_f64(left / files_per_sec).ok(),
+            false => None,
+        }
+    });
+    WalkEvent::Throughput {
+        files_per_sec,
+        mb_per_sec,
+        eta,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MediaWalker;
+
+    #[test]
+    fn it_tells_how_fast_walks_go() {
+        let start = Instant::now();
+        let later = start + Duration::from_secs(2);
+        let event = speed((start, 100, 0), (later, 300, 4_000_000), Some(1300));
+        let WalkEvent::Throughput {
+            files_per_sec,
+            mb_per_sec,
+            eta,
+        } = event
+        else {
+            panic!("not a throughput: {:?}", event);
+        };
+        assert_eq!((files_per_sec, mb_per_sec), (100.0, 2.0));
+        assert_eq!(eta, Some(Duration::from_secs(10)));
+        let event = speed((start, 300, 0), (later, 300, 0), Some(1300));
+        assert!(matches!(event, WalkEvent::Throughput { eta: None, .. }));
+
+        let mut handle = MediaWalker::new("resources/test")
+            .throughput(Duration::from_millis(1))
+            .throttle(Duration::from_millis(5))
+            .start();
+        let (mut found, mut reported) = (0, 0);
+        for event in handle.events() {
+            match event {
+                WalkEvent::Found(_) => found += 1,
+                WalkEvent::Throughput { eta, .. } => {
+                    assert_eq!(eta, None);
+                    reported += 1;
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(found, 9);
+        assert!(reported > 0);
+    }
+}
@@ -0,0 +1,186 @@
+//! Lookups in the freedesktop.org thumbnail cache, as used on Linux and
+//! the BSDs, see [`MediaWalker::thumbnails`](crate::MediaWalker::thumbnails).
+use std::env;
+use std::fs::{self, Metadata};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// The thumbnail sizes, smallest first.
+const SIZES: [&str; 4] = ["normal", "large", "x-large", "xx-large"];
+
+/// The thumbnail cache directory: `$XDG_CACHE_HOME/thumbnails`, or
+/// `~/.cache/thumbnails`.
+pub(crate) fn cache_dir() -> Option<PathBuf> {
+    let cache = match env::var_os("XDG_CACHE_HOME") {
+        Some(cache) if !cache.is_empty() => PathBuf::from(cache),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".cache"),
+    };
+    Some(cache.join("thumbnails"))
+}
+
+/// Returns the smallest cached thumbnail of a file that is up to date:
+/// one that records the file's modification time, if it is known.
+pub(crate) fn find(cache: &Path, path: &Path, metadata: Option<&Metadata>) -> Option<PathBuf> {
+    let path = std::path::absolute(path).ok()?;
+    let name = format!("{}.png", hex(&md5(uri(&path).as_bytes())));
+    let mtime = metadata
+        .and_then(|metadata| metadata.modified().ok())
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|modified| modified.as_secs().to_string());
+    SIZES.iter().find_map(|size| {
+        let thumbnail = cache.join(size).join(&name);
+        let png = fs::read(&thumbnail).ok()?;
+        match &mtime {
+            Some(mtime) => (text(&png, "Thumb::MTime")? == mtime.as_bytes()).then_some(thumbnail),
+            None => Some(thumbnail),
+        }
+    })
+}
+
+/// The `file://` URI of an absolute path, as thumbnails are named by.
+fn uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for &byte in path.to_string_lossy().as_bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+/// Returns the text of a PNG `tEXt` chunk.
+fn text<'a>(png: &'a [u8], keyword: &str) -> Option<&'a [u8]> {
+    let mut rest = png.strip_prefix(b"\x89PNG\r\n\x1a\n")?;
+    while rest.len() >= 12 {
+        let len = u32::from_be_bytes(rest[..4].try_into().ok()?) as usize;
+        let data = rest.get(8..8 + len)?;
+        match &rest[4..8] {
+            b"tEXt" => {
+                if let Some(text) = data
+                    .strip_prefix(keyword.as_bytes())
+                    .and_then(|text| text.strip_prefix(b"\0"))
+                {
+                    return Some(text);
+                }
+            }
+            b"IEND" => break,
+            _ => {}
+        }
+        rest = rest.get(12 + len..)?;
+    }
+    None
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// The MD5 digest thumbnails are named by.
+fn md5(message: &[u8]) -> [u8; 16] {
+    const SHIFTS: [u32; 64] = [
+        7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 5, 9, 14, 20, 5, 9, 14, 20, 5,
+        9, 14, 20, 5, 9, 14, 20, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 6, 10,
+        15, 21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21,
+    ];
+    let constants: Vec<u32> = (0..64)
+        .map(|i| ((i as f64 + 1.0).sin().abs() * 4294967296.0) as u32)
+        .collect();
+    let mut padded = message.to_vec();
+    padded.push(0x80);
+    while padded.len() % 64 != 56 {
+        padded.push(0);
+    }
+    padded.extend_from_slice(&((message.len() as u64).wrapping_mul(8)).to_le_bytes());
+
+    let mut state: [u32; 4] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
+    for block in padded.chunks(64) {
+        let words: Vec<u32> = block
+            .chunks(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+            .collect();
+        let [mut a, mut b, mut c, mut d] = state;
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let f = f
+                .wrapping_add(a)
+                .wrapping_add(constants[i])
+                .wrapping_add(words[g]);
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(f.rotate_left(SHIFTS[i]));
+        }
+        for (word, add) in state.iter_mut().zip([a, b, c, d]) {
+            *word = word.wrapping_add(add);
+        }
+    }
+    let mut digest = [0; 16];
+    for (bytes, word) in digest.chunks_mut(4).zip(state) {
+        bytes.copy_from_slice(&word.to_le_bytes());
+    }
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempTree;
+
+    fn png(mtime: &str) -> Vec<u8> {
+        let text = format!("Thumb::MTime\0{}", mtime);
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        png.extend_from_slice(&(text.len() as u32).to_be_bytes());
+        png.extend_from_slice(b"tEXt");
+        png.extend_from_slice(text.as_bytes());
+        png.extend_from_slice(&[0; 4]);
+        png.extend_from_slice(&[0, 0, 0, 0]);
+        png.extend_from_slice(b"IEND");
+        png.extend_from_slice(&[0; 4]);
+        png
+    }
+
+    #[test]
+    fn it_finds_cached_thumbnails() {
+        assert_eq!(hex(&md5(b"")), "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(
+            hex(&md5(b"file:///home/jens/photos/me.png")),
+            "c6ee772d9e49320e97ec29a7eb5b1697"
+        );
+        assert_eq!(uri(Path::new("/a b/ä.jpg")), "file:///a%20b/%C3%A4.jpg");
+
+        let tree = TempTree::new("thumbnails");
+        let image = tree.sample("sample_640x426.jpg", "photos/a.jpg");
+        let metadata = fs::metadata(&image).unwrap();
+        let name = format!("{}.png", hex(&md5(uri(&image).as_bytes())));
+        let cache = tree.path().join("thumbnails");
+        assert_eq!(find(&cache, &image, Some(&metadata)), None);
+
+        let mtime = metadata
+            .modified()
+            .unwrap()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        tree.file(&format!("thumbnails/normal/{}", name), &png("1"));
+        tree.file(
+            &format!("thumbnails/large/{}", name),
+            &png(&mtime.to_string()),
+        );
+        assert_eq!(
+            find(&cache, &image, Some(&metadata)),
+            Some(cache.join("large").join(&name))
+        );
+        assert_eq!(
+            find(&cache, &image, None),
+            Some(cache.join("normal").join(name))
+        );
+    }
+}
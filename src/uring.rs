@@ -0,0 +1,450 @@
+//! Batched header reads through io_uring on Linux, see
+//! [`MediaWalker::io_uring`](crate::MediaWalker::io_uring).
+use std::cell::RefCell;
+use std::fs::File;
+use std::io;
+use std::os::raw::{c_int, c_long, c_void};
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+extern "C" {
+    fn syscall(number: c_long, ...) -> c_long;
+    fn mmap(
+        addr: *mut c_void,
+        len: usize,
+        prot: c_int,
+        flags: c_int,
+        fd: c_int,
+        offset: OffT,
+    ) -> *mut c_void;
+    fn munmap(addr: *mut c_void, len: usize) -> c_int;
+    fn close(fd: c_int) -> c_int;
+}
+
+// The same on every architecture, as syscalls added after 5.1 are.
+const SYS_IO_URING_SETUP: c_long = 425;
+const SYS_IO_URING_ENTER: c_long = 426;
+/// The off_t of mmap: as wide as a long with glibc, always 64 bits with
+/// musl.
+#[cfg(not(target_env = "musl"))]
+type OffT = c_long;
+#[cfg(target_env = "musl")]
+type OffT = i64;
+
+const IORING_OFF_SQ_RING: OffT = 0;
+const IORING_OFF_CQ_RING: OffT = 0x800_0000;
+const IORING_OFF_SQES: OffT = 0x1000_0000;
+const IORING_ENTER_GETEVENTS: u32 = 1;
+const IORING_OP_READ: u8 = 22;
+const PROT_READ_WRITE: c_int = 0x1 | 0x2;
+const MAP_SHARED_POPULATE: c_int = 0x01 | 0x8000;
+const MAP_FAILED: *mut c_void = !0 as *mut c_void;
+
+/// How many reads a ring takes at a time.
+const RING_ENTRIES: u32 = 64;
+
+#[repr(C)]
+#[derive(Default)]
+struct SqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    flags: u32,
+    dropped: u32,
+    array: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct CqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    overflow: u32,
+    cqes: u32,
+    flags: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Params {
+    sq_entries: u32,
+    cq_entries: u32,
+    flags: u32,
+    sq_thread_cpu: u32,
+    sq_thread_idle: u32,
+    features: u32,
+    wq_fd: u32,
+    resv: [u32; 3],
+    sq_off: SqringOffsets,
+    cq_off: CqringOffsets,
+}
+
+/// A submission queue entry.
+#[repr(C)]
+#[derive(Default)]
+struct Sqe {
+    opcode: u8,
+    flags: u8,
+    ioprio: u16,
+    fd: i32,
+    off: u64,
+    addr: u64,
+    len: u32,
+    rw_flags: u32,
+    user_data: u64,
+    pad: [u64; 3],
+}
+
+/// A completion queue entry.
+#[repr(C)]
+struct Cqe {
+    user_data: u64,
+    res: i32,
+    flags: u32,
+}
+
+/// A memory mapping of the ring, unmapped on drop.
+struct Mapping {
+    ptr: *mut u8,
+    len: usize,
+}
+
+impl Mapping {
+    fn new(fd: c_int, len: usize, offset: OffT) -> io::Result<Self> {
+        // SAFETY: maps memory the kernel set up for the ring; the result
+        // is checked before use.
+        let ptr = unsafe {
+            mmap(
+                std::ptr::null_mut(),
+                len,
+                PROT_READ_WRITE,
+                MAP_SHARED_POPULATE,
+                fd,
+                offset,
+            )
+        };
+        if ptr == MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Mapping {
+            ptr: ptr.cast(),
+            len,
+        })
+    }
+
+    /// A pointer `offset` bytes into the mapping.
+    fn at<T>(&self, offset: u32) -> *mut T {
+        debug_assert!(offset as usize + std::mem::size_of::<T>() <= self.len);
+        // SAFETY: the kernel-given offsets lie within the mapping.
+        unsafe { self.ptr.add(offset as usize).cast() }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        // SAFETY: unmaps what `new` mapped.
+        unsafe { munmap(self.ptr.cast(), self.len) };
+    }
+}
+
+/// An io_uring instance.
+struct Ring {
+    fd: c_int,
+    params: Params,
+    /// How many submitted reads have yet to complete.
+    in_flight: u32,
+    sq: Mapping,
+    cq: Mapping,
+    sqes: Mapping,
+}
+
+impl Ring {
+    fn new(entries: u32) -> io::Result<Self> {
+        let mut params = Params::default();
+        // SAFETY: `params` is a properly laid out io_uring_params.
+        let fd = unsafe { syscall(SYS_IO_URING_SETUP, entries, &mut params as *mut Params) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = fd as c_int;
+        let ring = (|| {
+            let sq_len = params.sq_off.array as usize + params.sq_entries as usize * 4;
+            let cq_len = params.cq_off.cqes as usize
+                + params.cq_entries as usize * std::mem::size_of::<Cqe>();
+            let sqes_len = params.sq_entries as usize * std::mem::size_of::<Sqe>();
+            Ok((
+                Mapping::new(fd, sq_len, IORING_OFF_SQ_RING)?,
+                Mapping::new(fd, cq_len, IORING_OFF_CQ_RING)?,
+                Mapping::new(fd, sqes_len, IORING_OFF_SQES)?,
+            ))
+        })();
+        match ring {
+            Ok((sq, cq, sqes)) => Ok(Ring {
+                fd,
+                params,
+                in_flight: 0,
+                sq,
+                cq,
+                sqes,
+            }),
+            Err(err) => {
+                // SAFETY: closes the ring just set up.
+                unsafe { close(fd) };
+                Err(err)
+            }
+        }
+    }
+
+    fn atomic(mapping: &Mapping, offset: u32) -> &AtomicU32 {
+        // SAFETY: the ring's head and tail are aligned u32s shared with
+        // the kernel, so they are accessed atomically.
+        unsafe { &*mapping.at::<AtomicU32>(offset) }
+    }
+
+    /// Queues a read of `buf.len()` bytes from the start of `fd`.
+    /// Returns `false` if the submission queue is full.
+    fn push_read(&mut self, fd: c_int, buf: &mut [u8], user_data: u64) -> bool {
+        let off = &self.params.sq_off;
+        let head = Self::atomic(&self.sq, off.head).load(Ordering::Acquire);
+        let tail = Self::atomic(&self.sq, off.tail).load(Ordering::Relaxed);
+        if tail.wrapping_sub(head) >= self.params.sq_entries {
+            return false;
+        }
+        // SAFETY: the ring mask is in the mapping.
+        let mask = unsafe { *self.sq.at::<u32>(off.ring_mask) };
+        let index = tail & mask;
+        let sqe = Sqe {
+            opcode: IORING_OP_READ,
+            fd,
+            addr: buf.as_mut_ptr() as u64,
+            len: buf.len() as u32,
+            user_data,
+            ..Sqe::default()
+        };
+        // SAFETY: `index` is within the submission queue entries and the
+        // array, which the kernel does not touch until the tail moves.
+        unsafe {
+            self.sqes
+                .at::<Sqe>(index * std::mem::size_of::<Sqe>() as u32)
+                .write(sqe);
+            *self.sq.at::<u32>(off.array + index * 4) = index;
+        }
+        Self::atomic(&self.sq, off.tail).store(tail.wrapping_add(1), Ordering::Release);
+        true
+    }
+
+    /// Enters the ring to submit `submit` queued reads and wait for
+    /// `wait` completions, returning how many were submitted.
+    fn enter(&self, submit: u32, wait: u32) -> io::Result<u32> {
+        loop {
+            // SAFETY: enters the ring this instance owns.
+            let entered = unsafe {
+                syscall(
+                    SYS_IO_URING_ENTER,
+                    self.fd,
+                    submit,
+                    wait,
+                    IORING_ENTER_GETEVENTS,
+                    std::ptr::null::<c_void>(),
+                    0usize,
+                )
+            };
+            if entered >= 0 {
+                return Ok(entered as u32);
+            }
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+        }
+    }
+
+    /// Hands the completions there are to `complete`, returning how
+    /// many there were.
+    fn reap(&mut self, mut complete: impl FnMut(u64, i32)) -> u32 {
+        let off = &self.params.cq_off;
+        // SAFETY: the ring mask is in the mapping.
+        let mask = unsafe { *self.cq.at::<u32>(off.ring_mask) };
+        let mut head = Self::atomic(&self.cq, off.head).load(Ordering::Relaxed);
+        let tail = Self::atomic(&self.cq, off.tail).load(Ordering::Acquire);
+        let mut reaped = 0;
+        while head != tail {
+            let cqe_offset = off.cqes + (head & mask) * std::mem::size_of::<Cqe>() as u32;
+            // SAFETY: entries between head and tail were written by the
+            // kernel.
+            let cqe = unsafe { self.cq.at::<Cqe>(cqe_offset).read() };
+            complete(cqe.user_data, cqe.res);
+            head = head.wrapping_add(1);
+            reaped += 1;
+        }
+        Self::atomic(&self.cq, off.head).store(head, Ordering::Release);
+        self.in_flight = self.in_flight.saturating_sub(reaped);
+        reaped
+    }
+
+    /// Submits the queued reads and waits for `count` of them to
+    /// complete, handing each completion to `complete`. On failure, the
+    /// reads already submitted are waited for, as far as the ring lets
+    /// them be; `in_flight` tells if any are left.
+    fn submit_and_wait(
+        &mut self,
+        count: u32,
+        mut complete: impl FnMut(u64, i32),
+    ) -> io::Result<()> {
+        let mut submitted = 0;
+        let mut completed = 0;
+        while completed < count {
+            match self.enter(count - submitted, 1) {
+                Ok(entered) => {
+                    submitted += entered;
+                    self.in_flight += entered;
+                }
+                Err(err) => {
+                    while self.in_flight > 0 && self.enter(0, self.in_flight).is_ok() {
+                        self.reap(|_, _| {});
+                    }
+                    return Err(err);
+                }
+            }
+            completed += self.reap(&mut complete);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Ring {
+    fn drop(&mut self) {
+        // SAFETY: closes the ring this instance owns; the mappings are
+        // dropped after.
+        unsafe { close(self.fd) };
+    }
+}
+
+thread_local! {
+    /// The ring of each thread reading headers, set up on first use and
+    /// reused for every batch after. A ring that failed is dropped.
+    static RING: RefCell<Option<Ring>> = const { RefCell::new(None) };
+}
+
+/// Reads up to `len` bytes from the start of each file, in batches as
+/// large as the thread's ring takes. Fails as a whole only if io_uring
+/// is not available; failures to open or read single files are returned
+/// in their place.
+pub(crate) fn read_headers(paths: &[&Path], len: usize) -> io::Result<Vec<io::Result<Vec<u8>>>> {
+    RING.with(|cell| {
+        let mut cell = cell.borrow_mut();
+        let ring = match &mut *cell {
+            Some(ring) => ring,
+            None => cell.insert(Ring::new(RING_ENTRIES)?),
+        };
+        let mut headers = Vec::with_capacity(paths.len());
+        for batch in paths.chunks(ring.params.sq_entries as usize) {
+            match read_batch(ring, batch, len) {
+                Ok(batch) => headers.extend(batch),
+                Err(err) => {
+                    *cell = None;
+                    return Err(err);
+                }
+            }
+        }
+        Ok(headers)
+    })
+}
+
+/// Reads the headers of as many files as the ring takes at once.
+fn read_batch(
+    ring: &mut Ring,
+    paths: &[&Path],
+    len: usize,
+) -> io::Result<Vec<io::Result<Vec<u8>>>> {
+    let mut headers: Vec<io::Result<Vec<u8>>> = Vec::with_capacity(paths.len());
+    let mut files = Vec::with_capacity(paths.len());
+    for path in paths {
+        match File::open(path) {
+            Ok(file) => {
+                headers.push(Ok(vec![0; len]));
+                files.push(Some(file));
+            }
+            Err(err) => {
+                headers.push(Err(err));
+                files.push(None);
+            }
+        }
+    }
+    let queued: Vec<usize> = (0..paths.len()).filter(|i| files[*i].is_some()).collect();
+    if queued.is_empty() {
+        return Ok(headers);
+    }
+    let mut lengths = vec![0i32; paths.len()];
+    for i in &queued {
+        let fd = files[*i].as_ref().unwrap().as_raw_fd();
+        let Ok(buf) = headers[*i].as_mut() else {
+            continue;
+        };
+        if !ring.push_read(fd, buf, *i as u64) {
+            return Err(io::Error::other("io_uring submission queue full"));
+        }
+    }
+    let waited = ring.submit_and_wait(queued.len() as u32, |user_data, res| {
+        lengths[user_data as usize] = res
+    });
+    if let Err(err) = waited {
+        // The kernel may still write to the buffers of reads left in
+        // flight, so they are leaked rather than freed.
+        if ring.in_flight > 0 {
+            std::mem::forget(headers);
+        }
+        return Err(err);
+    }
+    for i in queued {
+        let res = lengths[i];
+        if res < 0 {
+            headers[i] = Err(io::Error::from_raw_os_error(-res));
+        } else if let Ok(buf) = headers[i].as_mut() {
+            buf.truncate(res as usize);
+        }
+    }
+    Ok(headers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempTree;
+
+    #[test]
+    fn it_reads_headers_in_a_batch() {
+        let tree = TempTree::new("uring");
+        let short = tree.file("short.bin", b"abc");
+        let long = tree.file("long.bin", &[7; 10_000]);
+        let missing = tree.path().join("missing.bin");
+        let headers = read_headers(&[short.as_path(), &missing, &long], 8192).unwrap();
+        assert_eq!(headers[0].as_deref().unwrap(), b"abc");
+        assert_eq!(
+            headers[1].as_ref().unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+        assert_eq!(headers[2].as_deref().unwrap(), &[7; 8192][..]);
+
+        // More files than a ring takes are read in several batches, on
+        // the same ring.
+        let paths: Vec<_> = (0..150)
+            .map(|i| tree.file(&format!("{}.bin", i), &[i as u8]))
+            .collect();
+        let paths: Vec<_> = paths.iter().map(|path| path.as_path()).collect();
+        let headers = read_headers(&paths, 16).unwrap();
+        assert_eq!(headers.len(), 150);
+        for (i, header) in headers.iter().enumerate() {
+            assert_eq!(header.as_deref().unwrap(), &[i as u8]);
+        }
+        assert!(RING.with(|ring| ring.borrow().is_some()));
+    }
+}
@@ -0,0 +1,3927 @@
+use crate::access;
+use crate::animation;
+use crate::artwork;
+use crate::color;
+#[cfg(target_os = "linux")]
+use crate::dents;
+use crate::enrich;
+use crate::exif;
+use crate::glob::Glob;
+use crate::handle::StopCondition;
+use crate::hint;
+use crate::id::IdStrategy;
+use crate::intern;
+use crate::locked::{self, LockedContainer, LockedFiles};
+use crate::mmap::Mmap;
+use crate::motion;
+use crate::normalize::Normalization;
+use crate::paths;
+use crate::power::Power;
+use crate::preset;
+use crate::pressure::Backoff;
+use crate::record::{self, Recorder};
+use crate::shuffle::Shuffle;
+use crate::signature::Ffmpeg;
+use crate::source::{SourceEntry, WalkSource};
+use crate::spotlight;
+use crate::throughput::Meter;
+use crate::thumbnail;
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+use crate::uring;
+#[cfg(all(feature = "watch", target_os = "linux"))]
+use crate::watch::{self, Watcher};
+use crate::{
+    classify_bytes, ArtworkRef, AsyncEnricher, Classified, CollectedError, ConfigError, DirEmit,
+    Enricher, ErrorPolicy, Explanation, Filter, Kind, MediaWalkError, MediaWalkResult, Preset,
+    Priority, Replay, SessionId, SkipReason, WalkConfig, WalkEvent, WalkHandle, WalkStats,
+};
+use std::borrow::Cow;
+use std::cell::{Cell, OnceCell, RefCell};
+use std::collections::{HashMap, HashSet, VecDeque};
+#[cfg(feature = "acoustid")]
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{self, File, Metadata};
+use std::io;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::mpsc::SyncSender;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use walkdir::{DirEntry, WalkDir};
+
+/// A builder for configuring a walk.
+///
+/// # Examples
+///
+/// ```
+/// use mediawalker::{Kind, MediaWalker};
+/// let walker = MediaWalker::new(env!("CARGO_MANIFEST_DIR")).companions(true);
+/// for received in walker.start() {
+///     if received.kind == Some(Kind::Companion) {
+///         println!("A companion file: {}", received.path);
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct MediaWalker {
+    root: PathBuf,
+    kinds: Vec<Kind>,
+    error_policy: ErrorPolicy,
+    threads: usize,
+    excludes: Vec<Glob>,
+    throttle: Option<Duration>,
+    target_latency: Option<Duration>,
+    io_backoff: Option<u32>,
+    pause_on_battery: bool,
+    throughput: Option<Duration>,
+    expected_files: Option<u64>,
+    extensions: Vec<String>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    record: Option<PathBuf>,
+    follow_reparse_points: bool,
+    treat_bundles_as_files: bool,
+    report_bundles: bool,
+    spotlight: bool,
+    mmap: bool,
+    min_depth: usize,
+    dir_filter: Option<Hook<DirFilter>>,
+    file_filter: Option<Hook<FileFilter>>,
+    result_map: Option<Hook<ResultMap>>,
+    filter: Option<Filter>,
+    enrichers: Vec<Enrichment>,
+    priority: Priority,
+    priority_dirs: Vec<Glob>,
+    shuffle: Option<u64>,
+    thumbnails: bool,
+    include_trash: bool,
+    workflow_caches: Vec<String>,
+    ids: Option<IdStrategy>,
+    flag_suspicious: bool,
+    lossy_paths: bool,
+    check_paths: bool,
+    normalize_paths: Option<Normalization>,
+    dedupe_paths: bool,
+    stages: Vec<(Kind, usize)>,
+    hash_threads: usize,
+    emit_dirs: DirEmit,
+    progressive: bool,
+    traversal_threads: usize,
+    memory_budget: Option<usize>,
+    report_dangling_symlinks: bool,
+    max_symlink_depth: Option<usize>,
+    video_signatures: bool,
+    /// The programs making video signatures, set by tests.
+    ffmpeg: Ffmpeg,
+    color_info: bool,
+    detect_animation: bool,
+    motion_photos: bool,
+    artwork: bool,
+    locked_files: LockedFiles,
+    check_permissions: bool,
+    #[cfg(feature = "documents")]
+    embedded_media: bool,
+    exif: bool,
+    media_hints: bool,
+    #[cfg(feature = "xattr")]
+    xattrs: Option<Vec<String>>,
+    #[cfg(feature = "io-uring")]
+    io_uring: bool,
+    #[cfg(feature = "acoustid")]
+    fingerprints: bool,
+    /// The program making fingerprints, set by tests.
+    #[cfg(feature = "acoustid")]
+    fpcalc: OsString,
+}
+
+impl MediaWalker {
+    /// The cache and sidecar directories of photo workflows skipped by
+    /// default, see [`workflow_caches`](Self::workflow_caches).
+    pub const WORKFLOW_CACHES: &'static [&'static str] = paths::WORKFLOW_CACHES;
+
+    /// Creates a walker for the given root directory, or a single file,
+    /// reporting audio, image and video files.
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        MediaWalker {
+            root: root.as_ref().to_path_buf(),
+            kinds: Kind::MEDIA.to_vec(),
+            error_policy: ErrorPolicy::default(),
+            threads: 1,
+            excludes: vec![],
+            throttle: None,
+            target_latency: None,
+            io_backoff: None,
+            pause_on_battery: false,
+            throughput: None,
+            expected_files: None,
+            extensions: vec![],
+            min_size: None,
+            max_size: None,
+            record: None,
+            follow_reparse_points: false,
+            treat_bundles_as_files: false,
+            report_bundles: false,
+            spotlight: false,
+            mmap: false,
+            min_depth: 0,
+            dir_filter: None,
+            file_filter: None,
+            result_map: None,
+            filter: None,
+            enrichers: vec![],
+            priority: Priority::WalkOrder,
+            priority_dirs: vec![],
+            shuffle: None,
+            thumbnails: false,
+            include_trash: false,
+            workflow_caches: Self::WORKFLOW_CACHES
+                .iter()
+                .map(|cache| cache.to_string())
+                .collect(),
+            ids: None,
+            flag_suspicious: false,
+            lossy_paths: false,
+            check_paths: false,
+            normalize_paths: None,
+            dedupe_paths: false,
+            stages: vec![],
+            hash_threads: 0,
+            emit_dirs: DirEmit::Never,
+            progressive: false,
+            traversal_threads: 1,
+            memory_budget: None,
+            report_dangling_symlinks: false,
+            max_symlink_depth: None,
+            video_signatures: false,
+            ffmpeg: Ffmpeg::from_env(),
+            color_info: false,
+            detect_animation: false,
+            motion_photos: false,
+            artwork: false,
+            locked_files: LockedFiles::Ignore,
+            check_permissions: false,
+            #[cfg(feature = "documents")]
+            embedded_media: false,
+            exif: false,
+            media_hints: false,
+            #[cfg(feature = "xattr")]
+            xattrs: None,
+            #[cfg(feature = "io-uring")]
+            io_uring: false,
+            #[cfg(feature = "acoustid")]
+            fingerprints: false,
+            #[cfg(feature = "acoustid")]
+            fpcalc: crate::fingerprint::fpcalc(),
+        }
+    }
+
+    /// Sets the kinds to report, replacing the defaults. For example
+    /// `.kinds(&[Kind::Image])` reports images only.
+    pub fn kinds(mut self, kinds: &[Kind]) -> Self {
+        self.kinds.clear();
+        self.extra_kinds(kinds)
+    }
+
+    /// Whether to report companion files (subtitles, cue sheets,
+    /// playlists and `.nfo` files) as [`Kind::Companion`] instead of
+    /// unknown. Defaults to `false`.
+    pub fn companions(mut self, yes: bool) -> Self {
+        self.kinds.retain(|kind| *kind != Kind::Companion);
+        if yes {
+            self.kinds.push(Kind::Companion);
+        }
+        self
+    }
+
+    /// Reports files of the given kinds in addition to the audio, image
+    /// and video files, e.g. `.extra_kinds(&[Kind::Document])`.
+    pub fn extra_kinds(mut self, kinds: &[Kind]) -> Self {
+        for kind in kinds {
+            if !self.kinds.contains(kind) {
+                self.kinds.push(*kind);
+            }
+        }
+        self
+    }
+
+    /// What to do when a file cannot be inspected or a directory cannot
+    /// be read. Defaults to [`ErrorPolicy::Collect`].
+    pub fn error_policy(mut self, policy: ErrorPolicy) -> Self {
+        self.error_policy = policy;
+        self
+    }
+
+    /// How many threads inspect the found files. The directory tree is
+    /// walked by a thread of its own. Defaults to 1, with which files are
+    /// inspected by the walking thread, and reported in walking order.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// How many threads walk the directory tree, taking directories off
+    /// a shared work-stealing queue, for trees where a few directories
+    /// are much larger than the rest. With more than one, files are
+    /// found out of walking order. Defaults to 1.
+    pub fn traversal_threads(mut self, threads: usize) -> Self {
+        self.traversal_threads = threads.max(1);
+        self
+    }
+
+    /// Skips the files and directories matching the given glob pattern,
+    /// see [`MediaWalker::excludes`].
+    pub fn exclude(mut self, pattern: &str) -> Self {
+        self.excludes.push(Glob::new(pattern));
+        self
+    }
+
+    /// Skips the files and directories matching any of the given glob
+    /// patterns. Patterns are matched against paths relative to the root,
+    /// with `/` as the separator; a pattern without a `/` is matched
+    /// against the file name only. `*` and `?` match within a path
+    /// component, and `**` matches any number of components:
+    /// `.excludes(&["node_modules", "*.tmp", "photos/**/cache"])`.
+    pub fn excludes(mut self, patterns: &[&str]) -> Self {
+        self.excludes
+            .extend(patterns.iter().map(|pattern| Glob::new(pattern)));
+        self
+    }
+
+    /// Whether to walk into trash and recycle bin directories, like
+    /// `.local/share/Trash`, `.Trash-1000` and `$RECYCLE.BIN`, which
+    /// hold deleted files. Defaults to `false`, so they are skipped as
+    /// though excluded.
+    pub fn include_trash(mut self, yes: bool) -> Self {
+        self.include_trash = yes;
+        self
+    }
+
+    /// Sets the names of the directories that cameras, photo editors and
+    /// NAS indexers keep caches and previews in, to skip as though
+    /// excluded, replacing [`WORKFLOW_CACHES`](Self::WORKFLOW_CACHES):
+    /// `.thumbnails`, `Adobe Bridge Cache`, `*.lrdata` and the like. Names
+    /// are matched ignoring case, and a leading `*` matches any start.
+    /// Each skipped directory is reported as
+    /// [`SkipReason::WorkflowCache`] and counted in
+    /// [`WalkStats::pruned_caches`]. `.workflow_caches(&[])` walks into
+    /// all of them.
+    pub fn workflow_caches(mut self, names: &[&str]) -> Self {
+        self.workflow_caches = names.iter().map(|name| name.to_string()).collect();
+        self
+    }
+
+    /// Inspects only files with the given extensions, ignoring case.
+    /// Can be called repeatedly to add more. By default, all files are
+    /// inspected.
+    pub fn extensions(mut self, extensions: &[&str]) -> Self {
+        self.extensions.extend(
+            extensions
+                .iter()
+                .map(|ext| ext.trim_start_matches('.').to_ascii_lowercase()),
+        );
+        self
+    }
+
+    /// Skips files smaller than the given size in bytes. Companion files
+    /// are not subject to the size limits.
+    pub fn min_size(mut self, bytes: u64) -> Self {
+        self.min_size = Some(bytes);
+        self
+    }
+
+    /// Skips files larger than the given size in bytes.
+    pub fn max_size(mut self, bytes: u64) -> Self {
+        self.max_size = Some(bytes);
+        self
+    }
+
+    /// Applies a bundle of options for a common kind of scan: the kinds,
+    /// extensions and sizes to look for, and junk directories to skip.
+    /// Call this first, as it replaces the kinds set before it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mediawalker::{MediaWalker, Preset};
+    /// let photos = MediaWalker::new("/mnt/nas").preset(Preset::Photos);
+    /// ```
+    pub fn preset(self, preset: Preset) -> Self {
+        preset.apply(self)
+    }
+
+    /// Pauses for the given duration after each found file, to go easy
+    /// on busy disks. Off by default.
+    pub fn throttle(mut self, pause: Duration) -> Self {
+        self.throttle = Some(pause).filter(|pause| !pause.is_zero());
+        self
+    }
+
+    /// Bounds how long files and results wait in the walk's own buffers
+    /// before going on, for live views to get a steady stream of results
+    /// rather than bursts: the results held back by
+    /// [`shuffle`](Self::shuffle) are let through once they have waited
+    /// for `latency`, and a batch of files whose headers are read
+    /// together through io_uring, with the `io-uring` feature, is read
+    /// once its first file has waited for it, as the next file is found.
+    /// Off by default.
+    pub fn target_latency(mut self, latency: Duration) -> Self {
+        self.target_latency = Some(latency);
+        self
+    }
+
+    /// Slows the walk down while other workloads keep the disks busy:
+    /// once tasks have been waiting for IO more than `percent` of the
+    /// last ten seconds, as the pressure stall information of Linux
+    /// tells, the walk pauses after each file, the longer the higher the
+    /// pressure, up to half a second. macOS having no such measure, the
+    /// load average per CPU stands in for it there; elsewhere, the walk
+    /// does not back off. Off by default.
+    pub fn io_backoff(mut self, percent: u32) -> Self {
+        self.io_backoff = Some(percent);
+        self
+    }
+
+    /// Whether to pause the walk while the machine runs on battery, and
+    /// resume it once plugged in, for background indexing to go easy on
+    /// laptops. The power supply is looked at every few seconds: through
+    /// sysfs on Linux, `pmset` on macOS and `GetSystemPowerStatus` on
+    /// Windows. Defaults to `false`.
+    pub fn pause_on_battery(mut self, yes: bool) -> Self {
+        self.pause_on_battery = yes;
+        self
+    }
+
+    /// Sends a [`WalkEvent::Throughput`] every `interval`, telling how
+    /// many files and megabytes a second the walk has gone through over
+    /// the last few seconds, for showing the speed of a scan as it goes.
+    /// Off by default.
+    pub fn throughput(mut self, interval: Duration) -> Self {
+        self.throughput = Some(interval).filter(|interval| !interval.is_zero());
+        self
+    }
+
+    /// How many files the walk is expected to go through, such as the
+    /// [`files`](WalkStats::files) of the last walk of the root, for the
+    /// [`eta`](WalkEvent::Throughput::eta) of throughput events. Unknown
+    /// by default.
+    pub fn expected_files(mut self, files: u64) -> Self {
+        self.expected_files = Some(files);
+        self
+    }
+
+    /// Whether to walk into Windows junctions and directory symbolic
+    /// links. Junctions like `Application Data` loop back to their
+    /// parents, so by default they are not followed, but reported as
+    /// [`SkipReason::ReparsePoint`] events. Has no effect elsewhere.
+    pub fn follow_reparse_points(mut self, yes: bool) -> Self {
+        self.follow_reparse_points = yes;
+        self
+    }
+
+    /// Whether to stop at macOS bundles, like `.app`, `.photoslibrary`
+    /// and `.fcpbundle` directories, instead of walking into them. They
+    /// look like directories, but are logically single items. Defaults to
+    /// `false`.
+    pub fn treat_bundles_as_files(mut self, yes: bool) -> Self {
+        self.treat_bundles_as_files = yes;
+        self
+    }
+
+    /// Whether to report the bundles not walked into as
+    /// [`WalkEvent::Bundle`] events. Implies
+    /// [`MediaWalker::treat_bundles_as_files`].
+    pub fn report_bundles(mut self, yes: bool) -> Self {
+        self.report_bundles = yes;
+        if yes {
+            self.treat_bundles_as_files = true;
+        }
+        self
+    }
+
+    /// Whether to report symbolic links whose targets are gone as
+    /// [`WalkEvent::DanglingSymlink`] events, rather than as failed
+    /// results, so that links left behind by moving media around can be
+    /// listed for cleanup. Defaults to `false`.
+    pub fn report_dangling_symlinks(mut self, yes: bool) -> Self {
+        self.report_dangling_symlinks = yes;
+        self
+    }
+
+    /// Stops resolving a symbolic link after `hops` links, skipping it as
+    /// [`SkipReason::SymlinkDepth`] if its target is further away, so
+    /// that long chains of links, each pointing at the next, are not
+    /// resolved again and again. `.max_symlink_depth(0)` follows no
+    /// symbolic link. Defaults to no limit but the system's own.
+    pub fn max_symlink_depth(mut self, hops: usize) -> Self {
+        self.max_symlink_depth = Some(hops);
+        self
+    }
+
+    /// Skips the files fewer than `depth` directories below the root:
+    /// `.min_depth(1)` skips the files directly in the root. Defaults
+    /// to 0.
+    pub fn min_depth(mut self, depth: usize) -> Self {
+        self.min_depth = depth;
+        self
+    }
+
+    /// Walks only into the directories for which `keep` returns `true`.
+    /// It is given the path of each directory below the root, and its
+    /// depth: 1 for the directories directly in the root. For example
+    /// `.filter_dirs(|path, _| !seen.contains(path))`. Filters set with
+    /// earlier calls still apply.
+    pub fn filter_dirs<F>(mut self, keep: F) -> Self
+    where
+        F: Fn(&Path, usize) -> bool + Send + Sync + 'static,
+    {
+        let keep: Arc<DirFilter> = match self.dir_filter.take() {
+            Some(Hook(earlier)) => {
+                Arc::new(move |path, depth| earlier(path, depth) && keep(path, depth))
+            }
+            None => Arc::new(keep),
+        };
+        self.dir_filter = Some(Hook(keep));
+        self
+    }
+
+    /// Inspects only the files for which `keep` returns `true`. It is
+    /// called before a file is opened, after the extension and size
+    /// filters, so it can cheaply skip files that are already known.
+    /// Filters set with earlier calls still apply.
+    pub fn filter_files<F>(mut self, keep: F) -> Self
+    where
+        F: Fn(&FileEntry) -> bool + Send + Sync + 'static,
+    {
+        let keep: Arc<FileFilter> = match self.file_filter.take() {
+            Some(Hook(earlier)) => Arc::new(move |entry| earlier(entry) && keep(entry)),
+            None => Arc::new(keep),
+        };
+        self.file_filter = Some(Hook(keep));
+        self
+    }
+
+    /// Reports only the results `filter` keeps, like those of
+    /// `"kind == video && size > 100MB".parse()?`. They are filtered
+    /// once their type is known, before anything else is read from the
+    /// files, and the results left out are not counted in the
+    /// statistics. Filters set with earlier calls still apply.
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filter = Some(match self.filter.take() {
+            Some(earlier) => earlier.and(filter),
+            None => filter,
+        });
+        self
+    }
+
+    /// Passes each result through `map` before it is reported, on the
+    /// inspecting threads. `map` can add to the result, or drop it by
+    /// returning `None`. Dropped results are not counted in the
+    /// statistics. Maps set with earlier calls run first.
+    pub fn map_result<F>(mut self, map: F) -> Self
+    where
+        F: Fn(MediaWalkResult) -> Option<MediaWalkResult> + Send + Sync + 'static,
+    {
+        let map: Arc<ResultMap> = match self.result_map.take() {
+            Some(Hook(earlier)) => Arc::new(move |walk_result| earlier(walk_result).and_then(&map)),
+            None => Arc::new(map),
+        };
+        self.result_map = Some(Hook(map));
+        self
+    }
+
+    /// Adds to each result with `enricher` before it is mapped and
+    /// reported, e.g. to resolve track metadata during the walk rather
+    /// than in a separate batch job. Enrichers run in the order they
+    /// were added.
+    pub fn enricher<E: Enricher + 'static>(mut self, enricher: E) -> Self {
+        let enricher: Arc<dyn Enricher> = Arc::new(enricher);
+        self.enrichers.push(Enrichment::Sync(Hook(enricher)));
+        self
+    }
+
+    /// Adds to each result with an enricher that waits, like on a web
+    /// service, see [`AsyncEnricher`] for how it is run.
+    pub fn async_enricher<E: AsyncEnricher + 'static>(mut self, enricher: E) -> Self {
+        let enricher: Arc<dyn AsyncEnricher> = Arc::new(enricher);
+        self.enrichers.push(Enrichment::Async(Hook(enricher)));
+        self
+    }
+
+    /// The order to walk each directory in, e.g.
+    /// `.prioritize(Priority::NewestFirst)` to find recently added media
+    /// early on. With more than one thread, files may still be reported
+    /// a little out of order. Defaults to [`Priority::WalkOrder`].
+    pub fn prioritize(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Walks the directories matching any of the given glob patterns,
+    /// and the ones in them, ahead of the others found so far, so that
+    /// the most relevant media is found long before the walk completes:
+    /// `.prioritize_dirs(&["**/Camera", "**/DCIM"])`. See
+    /// [`excludes`](Self::excludes) for the syntax of the patterns. Can be
+    /// called repeatedly to add more.
+    pub fn prioritize_dirs(mut self, patterns: &[&str]) -> Self {
+        self.priority_dirs
+            .extend(patterns.iter().map(|pattern| Glob::new(pattern)));
+        self
+    }
+
+    /// Reports the files in random order, for slideshows and the like.
+    /// Results are held back and let through at random, a thousand or
+    /// so at a time, so the whole walk need not be buffered. The same
+    /// seed gives the same order on an unchanged tree with one thread.
+    pub fn shuffle(mut self, seed: u64) -> Self {
+        self.shuffle = Some(seed);
+        self
+    }
+
+    /// Whether to seed the walk from the Spotlight index on macOS,
+    /// instead of reading every directory. The files Spotlight finds are
+    /// still inspected as usual, so results are the same as long as the
+    /// index is up to date. Walks fall back to reading the directories
+    /// elsewhere, when Spotlight cannot be asked, and when reporting
+    /// kinds other than audio, images and video. Bundles are not
+    /// reported in Spotlight walks. Defaults to `false`.
+    pub fn spotlight(mut self, yes: bool) -> Self {
+        self.spotlight = yes;
+        self
+    }
+
+    /// Whether to sniff files through memory maps instead of reading
+    /// their headers into buffers, saving a copy on large files. Files
+    /// truncated while mapped are reported as failed results instead of
+    /// bringing the process down: on Unix this takes a handler of SIGBUS,
+    /// handing the faults that are not of maps to the handler there was.
+    /// Where such faults cannot be handled, as on Unix other than Linux
+    /// on x86-64 and AArch64 and macOS, files are read as usual.
+    /// Defaults to `false`.
+    pub fn mmap(mut self, yes: bool) -> Self {
+        self.mmap = yes;
+        self
+    }
+
+    /// Whether to look up the up-to-date cached thumbnails of the found
+    /// images in the freedesktop.org thumbnail cache, as
+    /// [`MediaWalkResult::thumbnail`], so that applications can share
+    /// the thumbnails instead of making their own. Defaults to `false`.
+    pub fn thumbnails(mut self, yes: bool) -> Self {
+        self.thumbnails = yes;
+        self
+    }
+
+    /// Writes the events of the walk to a log as they are sent, to be
+    /// played back with [`replay`](Self::replay), as when reproducing a
+    /// bug report or testing what comes after the walk. A log that
+    /// cannot be written is reported as a failed result. Off by default.
+    pub fn record<P: AsRef<Path>>(mut self, log: P) -> Self {
+        self.record = Some(log.as_ref().to_path_buf());
+        self
+    }
+
+    /// Plays back a walk from a log written as asked to with
+    /// [`record`](Self::record), without touching the files walked.
+    /// Fails if the log cannot be read, or was not written by a walk.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use mediawalker::MediaWalker;
+    /// MediaWalker::new("/srv/photos").record("walk.log").start().for_each(drop);
+    /// for walk_result in MediaWalker::replay("walk.log")? {
+    ///     println!("{}", walk_result.path);
+    /// }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn replay<P: AsRef<Path>>(log: P) -> io::Result<Replay> {
+        record::replay(log.as_ref())
+    }
+
+    /// Whether to report the files whose paths are not UTF-8, with their
+    /// paths converted lossily, replacing what is not UTF-8 with
+    /// `U+FFFD`, and flagged as [`lossy`](MediaWalkResult::lossy), rather
+    /// than leaving them out. Defaults to `false`.
+    pub fn lossy_paths(mut self, yes: bool) -> Self {
+        self.lossy_paths = yes;
+        self
+    }
+
+    /// Reports the files with the extension of a photo, music or video
+    /// file but the content of an application or archive, like a
+    /// `movie.mp4` that is really a Windows executable, whatever the
+    /// kinds to report. They are flagged as
+    /// [`suspicious`](MediaWalkResult::suspicious). Defaults to `false`.
+    pub fn flag_suspicious(mut self, yes: bool) -> Self {
+        self.flag_suspicious = yes;
+        self
+    }
+
+    /// Checks the paths of the found files for what some tools choke on,
+    /// like control characters and names too long, into
+    /// [`MediaWalkResult::path_issues`], so that import pipelines can
+    /// sanitize them before copying. Defaults to `false`.
+    pub fn check_paths(mut self, yes: bool) -> Self {
+        self.check_paths = yes;
+        self
+    }
+
+    /// Normalizes the reported paths of the found files to the given
+    /// Unicode form, so that paths from macOS and elsewhere can be
+    /// compared. A path that changes keeps its form on disk in
+    /// [`MediaWalkResult::raw_path`]. By default, paths are reported as
+    /// they are.
+    pub fn normalize_paths(mut self, form: Normalization) -> Self {
+        self.normalize_paths = Some(form);
+        self
+    }
+
+    /// Reports each file once, however many paths lead to it, say through
+    /// symbolic links or in different case: files are told apart by
+    /// their canonical paths, ignoring case if the root's filesystem
+    /// does. Defaults to `false`.
+    pub fn dedupe_paths(mut self, yes: bool) -> Self {
+        self.dedupe_paths = yes;
+        self
+    }
+
+    /// Finishes the files of a kind in a stage of their own, with
+    /// `threads` threads: the reading of extended attributes, the
+    /// computing of IDs, the lookup of thumbnails and the
+    /// [`map_result`](Self::map_result) maps. Heavyweight work on, say,
+    /// videos then cannot hold up the other kinds, which are finished by
+    /// the inspecting threads as usual. Results of a kind with a stage
+    /// come out of walking order. Calling again for the same kind
+    /// replaces its thread count; 0 threads removes the stage.
+    pub fn stage_concurrency(mut self, kind: Kind, threads: usize) -> Self {
+        self.stages.retain(|(staged, _)| *staged != kind);
+        if threads > 0 {
+            self.stages.push((kind, threads));
+        }
+        self
+    }
+
+    /// Hashes the files for their [`IdStrategy::Content`] IDs in a stage
+    /// of their own, with `threads` threads, rather than before their
+    /// results are sent, as [`progressive`](Self::progressive) does,
+    /// with which it sets how many threads work out the details. Reading
+    /// whole files takes far longer than sniffing their headers, so the
+    /// results then come out as soon as the files are classified, without
+    /// IDs, and each ID follows in a [`WalkEvent::Enriched`] event.
+    /// Defaults to 0: hashing the files before their results are sent,
+    /// or with as many threads as inspect the files if progressive.
+    pub fn hash_concurrency(mut self, threads: usize) -> Self {
+        self.hash_threads = threads;
+        self
+    }
+
+    /// Which directories to report as [`WalkEvent::Directory`] events, see
+    /// [`WalkHandle::events`]: with [`DirEmit::WithMatches`], each
+    /// directory holding media is reported once its first media file is
+    /// found, right before the file, for consumers to build a tree of
+    /// albums as the walk goes. Defaults to [`DirEmit::Never`].
+    pub fn emit_dirs(mut self, dirs: DirEmit) -> Self {
+        self.emit_dirs = dirs;
+        self
+    }
+
+    /// Whether to send each result as soon as its file is classified,
+    /// and its slow details in [`WalkEvent::Enriched`] events following
+    /// it, as they are worked out in a stage of their own: the
+    /// [`IdStrategy::Content`] ID, the [`exif`](Self::exif), the
+    /// [`video_signatures`](Self::video_signatures) and, with the
+    /// `acoustid` feature, the fingerprint with the song's duration.
+    /// User interfaces can then show the files at once and fill in the
+    /// details as they come. Only [`WalkHandle::events`] has the details;
+    /// [`Enricher`](crate::Enricher)s and [`map_result`](Self::map_result)
+    /// see the results without them. The stage queues as many files as
+    /// the walk does; a full queue holds up the walk. With
+    /// [`shuffle`](Self::shuffle), details may come before their result.
+    /// Defaults to `false`.
+    pub fn progressive(mut self, yes: bool) -> Self {
+        self.progressive = yes;
+        self
+    }
+
+    /// Computes a stable ID for each found file into
+    /// [`MediaWalkResult::id`], so that downstream databases can track
+    /// files across rescans and renames. See [`IdStrategy`] for what the
+    /// IDs are computed from. By default, no IDs are computed.
+    pub fn ids(mut self, strategy: IdStrategy) -> Self {
+        self.ids = Some(strategy);
+        self
+    }
+
+    /// Whether to compute a perceptual signature of each found video
+    /// into [`MediaWalkResult::video_signature`], from keyframes sampled
+    /// through it, to group re-encoded copies of a clip with
+    /// [`VideoSignature::similarity`](crate::VideoSignature::similarity).
+    /// Runs `ffprobe` and `ffmpeg`, or the programs named by the `FFPROBE`
+    /// and `FFMPEG` environment variables; videos they cannot read get
+    /// no signature. See [`stage_concurrency`](Self::stage_concurrency)
+    /// for [`Kind::Video`] to keep it from holding up the walk. Defaults
+    /// to `false`.
+    pub fn video_signatures(mut self, yes: bool) -> Self {
+        self.video_signatures = yes;
+        self
+    }
+
+    /// Whether to read the bit depth, HDR-ness and ICC profile of found
+    /// JPEG, PNG, HEIF, AVIF and MP4 files into
+    /// [`MediaWalkResult::color_info`], so that photo apps can badge HDR
+    /// content. Only the headers are parsed, skipping the image and video
+    /// data. Defaults to `false`.
+    pub fn color_info(mut self, yes: bool) -> Self {
+        self.color_info = yes;
+        self
+    }
+
+    /// Whether to tell animated GIFs, APNGs, WebPs and AVIF sequences
+    /// from still ones into [`MediaWalkResult::animated`], so that
+    /// galleries can show them like videos. Only the container headers
+    /// are parsed. Defaults to `false`.
+    pub fn detect_animation(mut self, yes: bool) -> Self {
+        self.detect_animation = yes;
+        self
+    }
+
+    /// Whether to find the videos embedded in Android motion photos into
+    /// [`MediaWalkResult::motion`], from the XMP of found JPEGs. See
+    /// [`pair_live_photos`](crate::pair_live_photos) for Apple's Live
+    /// Photos, whose videos are files of their own. Defaults to `false`.
+    pub fn motion_photos(mut self, yes: bool) -> Self {
+        self.motion_photos = yes;
+        self
+    }
+
+    /// Whether to tell from their permissions the files the walk may not
+    /// read, and skip them without trying to open them, as
+    /// [`SkipReason::NoPermission`], rather than reporting each with a
+    /// permission error: scans of other users' home directories are then
+    /// not flooded with them. The check is of the permission bits of the
+    /// file against the effective user and groups of the process, so that
+    /// access granted by access control lists alone is not seen; it is
+    /// not made for the superuser, nor off Unix. Defaults to `false`.
+    pub fn check_permissions(mut self, yes: bool) -> Self {
+        self.check_permissions = yes;
+        self
+    }
+
+    /// What to do with encrypted files, whose contents cannot be
+    /// inspected: password-protected ZIP files, Office documents and RAR
+    /// archives, encrypted PDFs and 7-Zip archives, and the Apple Photos
+    /// libraries the walk is not granted access to. With
+    /// [`LockedFiles::Report`], they are reported with the container they
+    /// are in [`MediaWalkResult::locked`], even when their kind is not
+    /// one of [`kinds`](Self::kinds), so that users know there is content
+    /// that could not be looked into. Defaults to [`LockedFiles::Ignore`].
+    pub fn locked_files(mut self, handling: LockedFiles) -> Self {
+        self.locked_files = handling;
+        self
+    }
+
+    /// Whether to find the album art of found songs into
+    /// [`MediaWalkResult::artwork`]: the picture embedded in an MP3's
+    /// ID3v2 tag or in a FLAC file, the front cover if there are several,
+    /// or else the cover image next to the song, like `cover.jpg`,
+    /// `folder.png` or `front.webp`. The [`WalkEvent::Directory`] events
+    /// of [`emit_dirs`](Self::emit_dirs) get the cover images of the
+    /// directories. Defaults to `false`.
+    pub fn artwork(mut self, yes: bool) -> Self {
+        self.artwork = yes;
+        self
+    }
+
+    /// Whether to look for the media embedded in found documents, which
+    /// are reported by [`extra_kinds`](Self::extra_kinds) with
+    /// [`Kind::Document`]: the pictures, sounds and videos of Office and
+    /// OpenDocument files, and the JPEG images of PDFs. Each is reported
+    /// as a virtual entry, in a [`WalkEvent::Embedded`] event after its
+    /// document's result, from which it can be
+    /// [extracted](crate::EmbeddedMedia::extract). Encrypted documents
+    /// have none. Defaults to `false`.
+    #[cfg(feature = "documents")]
+    pub fn embedded_media(mut self, yes: bool) -> Self {
+        self.embedded_media = yes;
+        self
+    }
+
+    /// Whether to read the EXIF of found JPEG, PNG, WebP, HEIF and
+    /// TIFF-based raw images into [`MediaWalkResult::exif`], for
+    /// [`group_by_time`](crate::group_by_time) and
+    /// [`group_by_location`](crate::group_by_location) to group them by.
+    /// Only the headers are parsed. Defaults to `false`.
+    pub fn exif(mut self, yes: bool) -> Self {
+        self.exif = yes;
+        self
+    }
+
+    /// Whether to tag found images that are likely screenshots or
+    /// document scans into [`MediaWalkResult::hints`], so that cleanup
+    /// tools can set them apart from photos. The hints are guesses from
+    /// the names, sizes and EXIF of the images, see
+    /// [`MediaHint`](crate::MediaHint). Defaults to `false`.
+    pub fn media_hints(mut self, yes: bool) -> Self {
+        self.media_hints = yes;
+        self
+    }
+
+    /// Captures the Finder tags and color label (or the freedesktop
+    /// `user.xdg.tags` on Linux) of the found files into
+    /// [`MediaWalkResult::xattrs`], together with the extended
+    /// attributes of the given names, e.g.
+    /// `.capture_xattrs(&["com.apple.metadata:kMDItemWhereFroms"])`.
+    #[cfg(feature = "xattr")]
+    pub fn capture_xattrs(mut self, names: &[&str]) -> Self {
+        self.xattrs
+            .get_or_insert_with(Vec::new)
+            .extend(names.iter().map(|name| name.to_string()));
+        self
+    }
+
+    /// Whether to read the headers of files in batches through io_uring
+    /// on Linux, which pays off with many small files on fast disks.
+    /// Walks fall back to reading files one by one elsewhere, and when
+    /// the kernel does not support io_uring. Defaults to `false`.
+    #[cfg(feature = "io-uring")]
+    pub fn io_uring(mut self, yes: bool) -> Self {
+        self.io_uring = yes;
+        self
+    }
+
+    /// Whether to fingerprint the found audio files into
+    /// [`MediaWalkResult::fingerprint`], to find the same songs in other
+    /// encodings with [`Fingerprint::similarity`](crate::Fingerprint::similarity).
+    /// Runs Chromaprint's `fpcalc`, or the program named by the `FPCALC`
+    /// environment variable, for each song; songs it cannot fingerprint
+    /// get none. Decoding makes this slow, so
+    /// [`stage_concurrency`](Self::stage_concurrency) for
+    /// [`Kind::Audio`] helps. Defaults to `false`.
+    #[cfg(feature = "acoustid")]
+    pub fn fingerprints(mut self, yes: bool) -> Self {
+        self.fingerprints = yes;
+        self
+    }
+
+    /// Caps the memory the queues of the walk take at about `bytes`: the
+    /// files found but yet to be inspected, and the results yet to be
+    /// received, which the walk waits on while full, and the results
+    /// held back by [`shuffle`](Self::shuffle). Each queue holds 1024
+    /// files or results at most anyway, so that a slow consumer on a
+    /// small device, like a Raspberry Pi, holds the traversal back rather
+    /// than running it out of memory. Defaults to no budget.
+    pub fn memory_budget(mut self, bytes: usize) -> Self {
+        self.memory_budget = Some(bytes);
+        self
+    }
+
+    /// Overrides the options with the `MEDIAWALKER_*` environment
+    /// variables, see [`WalkConfig::from_env`].
+    pub fn with_env(self) -> Result<Self, ConfigError> {
+        Ok(WalkConfig::from_env()?.apply(self))
+    }
+
+    /// Walks a [`WalkSource`] on the calling thread, instead of the
+    /// local filesystem in the background. The options that concern
+    /// what to report apply; the ones about threads, ordering and
+    /// platform specifics do not.
+    pub fn walk_source<S: WalkSource + ?Sized>(self, source: &S) -> SourceWalk<'_, S> {
+        let (pending, failure) = match source.entry(&self.root) {
+            Ok(root) => (vec![(root, 0)], None),
+            Err(err) => (vec![], Some(err)),
+        };
+        SourceWalk {
+            options: self,
+            source,
+            pending,
+            failure,
+            done: false,
+            session_id: SessionId::new(),
+        }
+    }
+
+    /// Starts walking in a background thread, like
+    /// [`start`](Self::start), after checking that the root is a
+    /// directory or a file.
+    pub fn try_start(self) -> Result<WalkHandle, MediaWalkError> {
+        match std::fs::metadata(paths::extended_length(&self.root)) {
+            Ok(metadata) if metadata.is_dir() || metadata.is_file() => Ok(self.start()),
+            Ok(_) => Err(MediaWalkError::InvalidRoot(self.root)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                Err(MediaWalkError::RootNotFound(self.root))
+            }
+            Err(err) => Err(MediaWalkError::Failed {
+                path: self.root.to_string_lossy().into_owned(),
+                source: err,
+            }),
+        }
+    }
+
+    /// Starts walking in a background thread. A file given as the root
+    /// is inspected on its own, even if the extension and size filters
+    /// leave it out. A root that cannot be
+    /// walked is reported as a failed result, see
+    /// [`try_start`](Self::try_start) for failing right away instead.
+    pub fn start(self) -> WalkHandle {
+        let (tx, rx) = mpsc::sync_channel(self.queue_capacity());
+        let walk = Arc::new(Walk::new(self, tx));
+        let stats = Arc::clone(&walk.stats);
+        let stopped = Arc::clone(&walk.stopped);
+        let stop_after = Arc::clone(&walk.stop_after);
+        let session_id = walk.session_id;
+        thread::spawn(move || walk.run());
+        WalkHandle::new(rx, stats, stopped, stop_after, session_id)
+    }
+
+    /// Watches the root for files coming and going, see [`Watcher`].
+    /// The watch is set up before this returns, so that no change after
+    /// is missed. Changed files are inspected as a walk would, with the
+    /// same filters. Fails if neither fanotify nor inotify can watch the
+    /// root.
+    #[cfg(all(feature = "watch", target_os = "linux"))]
+    pub fn watch(self) -> io::Result<Watcher> {
+        watch::watch(self, None, false)
+    }
+
+    /// Walks the root, then watches it as [`watch`](Self::watch) does,
+    /// sending the results of the walk as [`WatchEvent::Found`](crate::WatchEvent::Found) events
+    /// before the changes. The watch is set up before the walk, so that
+    /// no change during it is missed, and a file found by the walk is not
+    /// reported again as added if it has not changed since.
+    #[cfg(all(feature = "watch", target_os = "linux"))]
+    pub fn scan_and_watch(self) -> io::Result<Watcher> {
+        watch::watch(self, None, true)
+    }
+
+    /// Tells what the walk would make of a single path, relative to the
+    /// root or not: the result it would report the file with, or the
+    /// filter that would leave it out. The file is inspected on the
+    /// calling thread, as a walk would have found it.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use mediawalker::{Explanation, MediaWalker};
+    /// let walker = MediaWalker::new("/srv/photos").exclude("**/thumbs");
+    /// if let Explanation::Skipped(reason) = walker.explain("2024/thumbs/a.jpg") {
+    ///     println!("left out: {}", reason);
+    /// }
+    /// ```
+    pub fn explain(&self, path: impl AsRef<Path>) -> Explanation {
+        let path = self.root.join(path);
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return Explanation::Outside;
+        };
+        let depth = relative.components().count();
+        if depth > 0 {
+            if let Some(reason) = path.parent().and_then(|dir| self.unwalked_dir(dir)) {
+                return Explanation::Skipped(reason);
+            }
+            if self.is_excluded(&path, depth, &self.root) {
+                return Explanation::Skipped(SkipReason::Excluded);
+            }
+        }
+        let metadata = fs::metadata(&path).ok();
+        if metadata.as_ref().is_some_and(Metadata::is_dir) {
+            return match self.unwalked_dir(&path) {
+                Some(reason) => Explanation::Skipped(reason),
+                None => Explanation::Walked,
+            };
+        }
+        if metadata.as_ref().is_some_and(|m| !m.is_file()) {
+            return Explanation::Skipped(SkipReason::SpecialFile);
+        }
+        if depth > 0 {
+            if depth <= self.min_depth {
+                return Explanation::Skipped(SkipReason::Depth);
+            }
+            if let Some(reason) = self.unwanted(&path, metadata.as_ref().map(Metadata::len)) {
+                return Explanation::Skipped(reason);
+            }
+        }
+        if !self.is_kept(&path, metadata.as_ref()) {
+            return Explanation::Skipped(SkipReason::Filtered);
+        }
+        if self.check_permissions && metadata.as_ref().is_some_and(|m| !access::may_read(m)) {
+            return Explanation::Skipped(SkipReason::NoPermission);
+        }
+        let mut walker = self.clone();
+        walker.root = path;
+        // The file is walked alone: not recorded over the log of the
+        // walk, nor timed, nor held back on battery.
+        walker.record = None;
+        walker.throughput = None;
+        walker.pause_on_battery = false;
+        let mut handle = walker.start();
+        for event in handle.events() {
+            match event {
+                WalkEvent::Found(walk_result) => return Explanation::Found(walk_result),
+                WalkEvent::Skipped { reason, .. } => return Explanation::Skipped(reason),
+                _ => {}
+            }
+        }
+        Explanation::Skipped(SkipReason::Kind)
+    }
+
+    /// How many files or results each queue of the walk holds at most,
+    /// the budget being split between the queues of files, of results,
+    /// and of the results of each stage.
+    fn queue_capacity(&self) -> usize {
+        match self.memory_budget {
+            Some(bytes) => {
+                let queues = 2 + self.stages.len() + usize::from(self.shuffle.is_some());
+                (bytes / queues / QUEUED_COST).clamp(1, QUEUE_CAPACITY)
+            }
+            None => QUEUE_CAPACITY,
+        }
+    }
+
+    /// The root of the walk, as it was given.
+    pub(crate) fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Whether a directory under the root is walked into, as far as the
+    /// exclusions, directory filters and bundles tell. Directories
+    /// found through links are not told apart.
+    pub(crate) fn is_walked_dir(&self, dir: &Path) -> bool {
+        dir.starts_with(&self.root) && self.unwalked_dir(dir).is_none()
+    }
+
+    /// Why a directory under the root is not walked into, if it is not,
+    /// see [`is_walked_dir`](Self::is_walked_dir).
+    fn unwalked_dir(&self, dir: &Path) -> Option<SkipReason> {
+        let relative = dir.strip_prefix(&self.root).ok()?;
+        let mut dir = self.root.clone();
+        for (depth, component) in relative.components().enumerate() {
+            dir.push(component);
+            if self.is_excluded(&dir, depth + 1, &self.root) {
+                return Some(SkipReason::Excluded);
+            }
+            if !self.is_dir_kept(&dir, depth + 1) {
+                return Some(SkipReason::Filtered);
+            }
+            if self.treat_bundles_as_files && paths::is_bundle(&dir) {
+                return Some(SkipReason::Bundle);
+            }
+        }
+        None
+    }
+
+    /// Inspects a single file under the root on the calling thread, as a
+    /// walk would have found it, filters and all. `None` if the walk
+    /// would have left it out, or found nothing.
+    pub(crate) fn inspect_file(&self, path: &Path) -> Option<MediaWalkResult> {
+        if !path.starts_with(&self.root) || path == self.root {
+            return None;
+        }
+        match self.explain(path) {
+            Explanation::Found(walk_result) => Some(walk_result),
+            _ => None,
+        }
+    }
+
+    /// Whether an entry found `depth` directories under `root` is
+    /// excluded.
+    fn is_excluded(&self, path: &Path, depth: usize, root: &Path) -> bool {
+        if depth == 0 {
+            return false;
+        }
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        self.is_excluded_path(relative)
+    }
+
+    /// Whether a path relative to the root matches an exclusion pattern,
+    /// or is trash not to be walked into.
+    fn is_excluded_path(&self, relative: &Path) -> bool {
+        (!self.include_trash && paths::is_trash(relative))
+            || paths::is_workflow_cache(relative, &self.workflow_caches)
+            || self.excludes.iter().any(|glob| glob.matches(relative))
+    }
+
+    /// Whether a directory under `root` matches a pattern of
+    /// [`prioritize_dirs`](Self::prioritize_dirs).
+    fn is_priority_dir(&self, path: &Path, root: &Path) -> bool {
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        self.priority_dirs.iter().any(|glob| glob.matches(relative))
+    }
+
+    /// Whether a directory passes the filters set with
+    /// [`filter_dirs`](Self::filter_dirs).
+    fn is_dir_kept(&self, path: &Path, depth: usize) -> bool {
+        self.dir_filter
+            .as_ref()
+            .is_none_or(|Hook(keep)| keep(path, depth))
+    }
+
+    /// Whether a file passes the extension and size filters.
+    fn is_wanted(&self, path: &Path, size: Option<u64>) -> bool {
+        self.unwanted(path, size).is_none()
+    }
+
+    /// Which of the extension and size filters leaves a file out, if any.
+    fn unwanted(&self, path: &Path, size: Option<u64>) -> Option<SkipReason> {
+        if !self.extensions.is_empty() {
+            let ext = path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .map(str::to_ascii_lowercase);
+            if !ext.is_some_and(|ext| self.extensions.contains(&ext)) {
+                return Some(SkipReason::Extension);
+            }
+        }
+        // Companion files are small by nature, so sizes do not apply.
+        if self.kinds.contains(&Kind::Companion) && Kind::companion_mime(path).is_some() {
+            return None;
+        }
+        let size = size?;
+        (self.min_size.is_some_and(|min| size < min) || self.max_size.is_some_and(|max| size > max))
+            .then_some(SkipReason::Size)
+    }
+
+    /// Passes a result through the enrichers.
+    fn enrich(&self, walk_result: &mut MediaWalkResult) {
+        for enrichment in &self.enrichers {
+            match enrichment {
+                Enrichment::Sync(Hook(enricher)) => enricher.enrich(walk_result),
+                Enrichment::Async(Hook(enricher)) => enrich::block_on(enricher.enrich(walk_result)),
+            }
+        }
+    }
+
+    /// The path to report for a file, `None` if not UTF-8 and not lossy.
+    fn path_str<'a>(&self, path: &'a Path) -> Option<Cow<'a, str>> {
+        match path.to_str() {
+            Some(path) => Some(Cow::Borrowed(path)),
+            None if self.lossy_paths => Some(path.to_string_lossy()),
+            None => None,
+        }
+    }
+
+    /// Whether a file passes the filters set with
+    /// [`filter_files`](Self::filter_files).
+    fn is_kept(&self, path: &Path, metadata: Option<&Metadata>) -> bool {
+        self.file_filter
+            .as_ref()
+            .is_none_or(|Hook(keep)| keep(&FileEntry { path, metadata }))
+    }
+
+    /// Figures out the media type of a single file, reported as `path`
+    /// and sniffed by `sniff`, unless it is a companion file. Returns
+    /// `None` for files of a known, but unwanted, type.
+    fn classify(
+        &self,
+        path: &str,
+        sniff: impl FnOnce() -> io::Result<Option<Classified>>,
+        locked: impl FnOnce(&str) -> Option<LockedContainer>,
+    ) -> Option<MediaWalkResult> {
+        let mut walk_result = MediaWalkResult::new(path.to_string());
+        if let Some(normalized) = self.normalize_paths.and_then(|form| form.apply(path)) {
+            walk_result.path = normalized;
+            walk_result.raw_path = Some(PathBuf::from(path));
+        }
+        if self.check_paths {
+            walk_result.path_issues = paths::issues(path);
+        }
+        if self.kinds.contains(&Kind::Companion) {
+            if let Some(mime) = Kind::companion_mime(Path::new(path)) {
+                walk_result.mime = intern::mime(mime);
+                walk_result.kind = Some(Kind::Companion);
+                return Some(walk_result);
+            }
+        }
+        match sniff() {
+            Ok(Some(Classified { mime, kind, brand })) => {
+                let kind = kind?;
+                let suspicious = self.flag_suspicious
+                    && matches!(kind, Kind::Application | Kind::Archive)
+                    && preset::has_media_extension(Path::new(path));
+                let locked = match self.locked_files {
+                    LockedFiles::Ignore => None,
+                    _ if Kind::MEDIA.contains(&kind) => None,
+                    _ => locked(mime),
+                };
+                if !suspicious && locked.is_none() && !self.kinds.contains(&kind) {
+                    return None;
+                }
+                walk_result.suspicious = suspicious;
+                walk_result.locked = locked;
+                walk_result.mime = intern::mime(mime);
+                walk_result.kind = Some(kind);
+                walk_result.brand = brand;
+            }
+            Ok(None) => {
+                walk_result.result = Ok(false);
+            }
+            Err(e) => {
+                walk_result.result = Err(e);
+            }
+        }
+        Some(walk_result)
+    }
+}
+
+/// A directory predicate, see [`MediaWalker::filter_dirs`].
+type DirFilter = dyn Fn(&Path, usize) -> bool + Send + Sync;
+
+/// A file predicate, see [`MediaWalker::filter_files`].
+type FileFilter = dyn Fn(&FileEntry) -> bool + Send + Sync;
+
+/// An iterator over the results of walking a [`WalkSource`], see
+/// [`MediaWalker::walk_source`].
+pub struct SourceWalk<'a, S: ?Sized> {
+    options: MediaWalker,
+    source: &'a S,
+    /// The entries yet to be gone through, with their depths, the next
+    /// one last.
+    pending: Vec<(SourceEntry, usize)>,
+    /// The error reading the root, reported first.
+    failure: Option<io::Error>,
+    done: bool,
+    session_id: SessionId,
+}
+
+impl<S: WalkSource + ?Sized> SourceWalk<'_, S> {
+    /// The ID of the walk, which its results carry in
+    /// [`MediaWalkResult::session_id`].
+    pub fn session_id(&self) -> SessionId {
+        self.session_id
+    }
+
+    /// Reports a failure as the error policy dictates.
+    fn fail(&mut self, path: &Path, err: io::Error) -> Option<MediaWalkResult> {
+        let mut walk_result = MediaWalkResult::new(path.to_string_lossy().into_owned());
+        walk_result.result = Err(err);
+        self.report(walk_result)
+    }
+
+    /// Passes a result through the enrichers, the result maps and the
+    /// error policy.
+    fn report(&mut self, mut walk_result: MediaWalkResult) -> Option<MediaWalkResult> {
+        self.options.enrich(&mut walk_result);
+        let mut walk_result = match &self.options.result_map {
+            Some(Hook(map)) => map(walk_result)?,
+            None => walk_result,
+        };
+        if walk_result.result.is_err() {
+            match self.options.error_policy {
+                ErrorPolicy::Ignore => return None,
+                ErrorPolicy::FailFast => self.done = true,
+                ErrorPolicy::Collect => {}
+            }
+        }
+        walk_result.session_id = Some(self.session_id);
+        walk_result.emitted_at = Some(SystemTime::now());
+        Some(walk_result)
+    }
+}
+
+impl<S: WalkSource + ?Sized> Iterator for SourceWalk<'_, S> {
+    type Item = MediaWalkResult;
+
+    fn next(&mut self) -> Option<MediaWalkResult> {
+        if let Some(err) = self.failure.take() {
+            let root = self.options.root.clone();
+            if let Some(walk_result) = self.fail(&root, err) {
+                return Some(walk_result);
+            }
+        }
+        while !self.done {
+            let (entry, depth) = self.pending.pop()?;
+            let options = &self.options;
+            let relative = entry
+                .path
+                .strip_prefix(&options.root)
+                .unwrap_or(&entry.path);
+            if depth > 0 && options.is_excluded_path(relative) {
+                continue;
+            }
+            if entry.is_dir {
+                if depth > 0
+                    && (!options.is_dir_kept(&entry.path, depth)
+                        || options.treat_bundles_as_files && paths::is_bundle(&entry.path))
+                {
+                    continue;
+                }
+                match self.source.read_dir(&entry.path) {
+                    Ok(entries) => self
+                        .pending
+                        .extend(entries.into_iter().rev().map(|e| (e, depth + 1))),
+                    Err(err) => {
+                        if let Some(walk_result) = self.fail(&entry.path, err) {
+                            return Some(walk_result);
+                        }
+                    }
+                }
+                continue;
+            }
+            if depth > 0
+                && (depth <= options.min_depth || !options.is_wanted(&entry.path, Some(entry.len)))
+            {
+                continue;
+            }
+            if !options.is_kept(&entry.path, None) {
+                continue;
+            }
+            let Some(path) = options.path_str(&entry.path) else {
+                continue;
+            };
+            let source = self.source;
+            let sniff = || {
+                let header = source.read_header(&entry.path, HEADER_LEN)?;
+                Ok(classify_bytes(&header))
+            };
+            if let Some(mut walk_result) = options.classify(&path, sniff, |_| None) {
+                walk_result.lossy = entry.path.to_str().is_none();
+                if let Some(walk_result) = self.report(walk_result) {
+                    return Some(walk_result);
+                }
+            }
+        }
+        None
+    }
+}
+
+impl<S: ?Sized> fmt::Debug for SourceWalk<'_, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SourceWalk")
+            .field("options", &self.options)
+            .field("pending", &self.pending)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A file about to be inspected, see [`MediaWalker::filter_files`].
+#[derive(Debug, Clone, Copy)]
+pub struct FileEntry<'a> {
+    /// The path of the file.
+    pub path: &'a Path,
+    /// The metadata of the file, `None` if it could not be read.
+    pub metadata: Option<&'a Metadata>,
+}
+
+/// A result transform, see [`MediaWalker::map_result`].
+type ResultMap = dyn Fn(MediaWalkResult) -> Option<MediaWalkResult> + Send + Sync;
+
+/// A callback given to a walker, shared by its clones.
+struct Hook<F: ?Sized>(Arc<F>);
+
+impl<F: ?Sized> Clone for Hook<F> {
+    fn clone(&self) -> Self {
+        Hook(Arc::clone(&self.0))
+    }
+}
+
+impl<F: ?Sized> fmt::Debug for Hook<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Hook")
+    }
+}
+
+/// An enricher, see [`MediaWalker::enricher`].
+#[derive(Debug, Clone)]
+enum Enrichment {
+    Sync(Hook<dyn Enricher>),
+    Async(Hook<dyn AsyncEnricher>),
+}
+
+/// How many bytes of a file are read to sniff its type, as by
+/// [`infer::get_from_path`].
+const HEADER_LEN: usize = 8192;
+
+/// How many results are held back to shuffle them.
+const SHUFFLE_WINDOW: usize = 1024;
+
+/// How many files, or results, each queue of a walk holds at most: the
+/// files found but yet to be inspected, the results of each stage, and
+/// the results yet to be received.
+pub(crate) const QUEUE_CAPACITY: usize = 1024;
+
+/// About how much memory a queued file or result takes, with its path
+/// and metadata, to size the queues by a budget.
+const QUEUED_COST: usize = std::mem::size_of::<MediaWalkResult>() + 512;
+
+/// How many headers are read at a time through io_uring.
+const URING_BATCH: usize = 64;
+
+/// How often idle traversal threads look whether the walk was stopped.
+const IDLE_POLL: Duration = Duration::from_millis(50);
+
+/// The type of a directory entry, or of its target if it is a symbolic
+/// link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EntryType {
+    File,
+    Dir,
+    Other,
+}
+
+impl From<fs::FileType> for EntryType {
+    fn from(file_type: fs::FileType) -> Self {
+        if file_type.is_file() {
+            EntryType::File
+        } else if file_type.is_dir() {
+            EntryType::Dir
+        } else {
+            EntryType::Other
+        }
+    }
+}
+
+/// An entry of a directory being walked.
+struct Entry {
+    path: PathBuf,
+    entry_type: EntryType,
+    is_symlink: bool,
+    /// The metadata of the entry, read at most once and shared by the
+    /// sorting, the filters and the results.
+    metadata: OnceCell<Option<Metadata>>,
+}
+
+impl Entry {
+    fn from_walkdir(entry: &DirEntry) -> Self {
+        Entry {
+            path: entry.path().to_path_buf(),
+            entry_type: entry.file_type().into(),
+            is_symlink: entry.path_is_symlink(),
+            metadata: OnceCell::new(),
+        }
+    }
+
+    /// The metadata of the entry, following symbolic links.
+    fn metadata(&self) -> Option<&Metadata> {
+        self.metadata
+            .get_or_init(|| fs::metadata(&self.path).ok())
+            .as_ref()
+    }
+}
+
+/// A directory waiting to be read.
+struct Dir {
+    path: PathBuf,
+    /// How many directories below the root it is.
+    depth: usize,
+    /// Whether it was reached through a symbolic link.
+    linked: bool,
+    /// The identities of the directory and its ancestors.
+    ancestry: Option<Arc<Ancestry>>,
+    /// Whether it is walked ahead of the others, see
+    /// [`MediaWalker::prioritize_dirs`].
+    prioritized: bool,
+}
+
+/// The identity of a directory: where a link leads to one already being
+/// walked, it would be walked forever.
+#[cfg(unix)]
+pub(crate) type DirId = (u64, u64);
+#[cfg(not(unix))]
+pub(crate) type DirId = PathBuf;
+
+/// The error for a link at `path` to a directory being walked.
+pub(crate) fn loop_error(path: &Path, link: &Path) -> io::Error {
+    let target = fs::canonicalize(link).unwrap_or_default();
+    io::Error::other(format!(
+        "file system loop found: {} points to an ancestor {}",
+        path.display(),
+        target.display()
+    ))
+}
+
+/// The identity of a directory, from its metadata where it has been
+/// read.
+pub(crate) fn dir_id(path: &Path, metadata: Option<&Metadata>) -> Option<DirId> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let metadata = match metadata {
+            Some(metadata) => Cow::Borrowed(metadata),
+            None => Cow::Owned(fs::metadata(path).ok()?),
+        };
+        Some((metadata.dev(), metadata.ino()))
+    }
+    #[cfg(not(unix))]
+    {
+        fs::canonicalize(path).ok()
+    }
+}
+
+/// A directory's identity, and those of its ancestors through `parent`.
+struct Ancestry {
+    id: DirId,
+    parent: Option<Arc<Ancestry>>,
+}
+
+impl Ancestry {
+    /// The ancestry of a child of a directory, by the child's identity.
+    fn child(parent: &Option<Arc<Ancestry>>, id: Option<DirId>) -> Option<Arc<Ancestry>> {
+        match id {
+            Some(id) => Some(Arc::new(Ancestry {
+                id,
+                parent: parent.clone(),
+            })),
+            None => parent.clone(),
+        }
+    }
+
+    /// Whether the directory or one of its ancestors has the identity.
+    fn contains(self: &Arc<Self>, id: &DirId) -> bool {
+        let mut ancestry = Some(self);
+        while let Some(here) = ancestry {
+            if here.id == *id {
+                return true;
+            }
+            ancestry = here.parent.as_ref();
+        }
+        false
+    }
+}
+
+/// A file waiting to be inspected.
+struct Job {
+    /// The path to report.
+    path: String,
+    /// The path to open.
+    open: PathBuf,
+    metadata: Option<Metadata>,
+    /// Whether the file was reached through a symbolic link.
+    linked: bool,
+}
+
+/// The details of a result to work out after it was sent, see
+/// [`MediaWalker::progressive`].
+struct Followup {
+    /// The path to open.
+    open: PathBuf,
+    metadata: Option<Metadata>,
+    result_id: u64,
+    kind: Option<Kind>,
+    mime: Arc<str>,
+    /// Whether to hash the file for its [`IdStrategy::Content`] ID.
+    content_id: bool,
+}
+
+/// The state of a running walk, shared by its threads.
+struct Walk {
+    options: MediaWalker,
+    /// The root to traverse, in extended-length form on Windows.
+    walk_root: PathBuf,
+    tx: SyncSender<WalkEvent>,
+    stats: Arc<Mutex<WalkStats>>,
+    /// Set to stop the walk, also by the handle.
+    stopped: Arc<AtomicBool>,
+    /// See [`WalkHandle::stop_after`].
+    stop_after: Arc<Mutex<Option<StopCondition>>>,
+    /// See [`WalkHandle::session_id`].
+    session_id: SessionId,
+    /// See [`MediaWalker::io_backoff`].
+    backoff: Option<Backoff>,
+    /// See [`MediaWalker::pause_on_battery`].
+    power: Option<Power>,
+    /// The log the events are written to, or the error creating it, see
+    /// [`MediaWalker::record`].
+    recorder: Option<io::Result<Recorder>>,
+    /// See [`MediaWalker::throughput`].
+    meter: Option<Meter>,
+    buffers: BufferPool,
+    /// The results held back, with when they were, see
+    /// [`MediaWalker::shuffle`].
+    shuffled: Option<Mutex<Shuffle<(Instant, MediaWalkResult)>>>,
+    /// The thumbnail cache, see [`MediaWalker::thumbnails`].
+    thumbnails: Option<PathBuf>,
+    /// Where to finish the results of kinds with stages of their own,
+    /// see [`MediaWalker::stage_concurrency`]. Emptied to end the stages.
+    stages: Mutex<HashMap<Kind, SyncSender<(Job, MediaWalkResult)>>>,
+    /// Where to work out the details of the results sent, see
+    /// [`MediaWalker::progressive`]. Emptied to end the stage.
+    followups: Mutex<Option<SyncSender<Followup>>>,
+    /// The directories reported so far, see [`MediaWalker::emit_dirs`].
+    dirs: Mutex<HashSet<PathBuf>>,
+    /// The cover images of the directories of the songs so far, by the
+    /// directories to open, see [`MediaWalker::artwork`].
+    covers: Mutex<HashMap<PathBuf, Option<ArtworkRef>>>,
+    /// The canonical paths reported so far, see
+    /// [`MediaWalker::dedupe_paths`].
+    seen: Option<Mutex<HashSet<PathBuf>>>,
+    /// Whether to ignore case when comparing canonical paths.
+    ignore_case: bool,
+}
+
+/// Header buffers, reused from file to file to save allocations.
+struct BufferPool {
+    buffers: Mutex<Vec<Vec<u8>>>,
+    /// How many buffers to keep: one per inspecting thread.
+    capacity: usize,
+}
+
+impl BufferPool {
+    fn new(capacity: usize) -> Self {
+        BufferPool {
+            buffers: Mutex::new(Vec::with_capacity(capacity)),
+            capacity,
+        }
+    }
+
+    /// Takes an empty buffer with room for a header.
+    fn take(&self) -> Vec<u8> {
+        self.buffers
+            .lock()
+            .unwrap()
+            .pop()
+            .unwrap_or_else(|| Vec::with_capacity(HEADER_LEN))
+    }
+
+    /// Gives a buffer back for reuse.
+    fn give(&self, mut buffer: Vec<u8>) {
+        let mut buffers = self.buffers.lock().unwrap();
+        if buffers.len() < self.capacity {
+            buffer.clear();
+            buffers.push(buffer);
+        }
+    }
+}
+
+impl Walk {
+    /// A walk with the given options, yet to be run, sending its events
+    /// to `tx`.
+    fn new(options: MediaWalker, tx: SyncSender<WalkEvent>) -> Self {
+        Walk {
+            walk_root: paths::extended_length(&options.root),
+            buffers: BufferPool::new(options.threads),
+            thumbnails: options.thumbnails.then(thumbnail::cache_dir).flatten(),
+            seen: options.dedupe_paths.then(Mutex::default),
+            stages: Mutex::default(),
+            followups: Mutex::default(),
+            dirs: Mutex::default(),
+            covers: Mutex::default(),
+            ignore_case: options.dedupe_paths && paths::is_case_insensitive(&options.root),
+            shuffled: options.shuffle.map(|seed| {
+                Mutex::new(Shuffle::new(
+                    seed,
+                    SHUFFLE_WINDOW.min(options.queue_capacity()),
+                ))
+            }),
+            backoff: options.io_backoff.map(Backoff::new),
+            power: options.pause_on_battery.then(Power::default),
+            recorder: options.record.as_deref().map(Recorder::create),
+            meter: options.throughput.map(|_| Meter::new()),
+            options,
+            tx,
+            stats: Arc::default(),
+            stopped: Arc::default(),
+            stop_after: Arc::default(),
+            session_id: SessionId::new(),
+        }
+    }
+
+    /// Walks the directory tree, handing the found files to the
+    /// inspecting threads.
+    fn run(self: Arc<Self>) {
+        let options = &self.options;
+        if let (Some(Err(err)), Some(log)) = (&self.recorder, &options.record) {
+            let err = io::Error::new(err.kind(), err.to_string());
+            self.emit(self.io_failure(log, err), None);
+            return;
+        }
+        let mut followup_workers = vec![];
+        if options.progressive
+            || options.hash_threads > 0 && options.ids == Some(IdStrategy::Content)
+        {
+            let (followup_tx, followup_rx) =
+                mpsc::sync_channel::<Followup>(options.queue_capacity());
+            let followup_rx = Arc::new(Mutex::new(followup_rx));
+            let threads = match options.hash_threads {
+                0 => options.threads,
+                threads => threads,
+            };
+            for _ in 0..threads {
+                let walk = Arc::clone(&self);
+                let followup_rx = Arc::clone(&followup_rx);
+                let worker = thread::Builder::new()
+                    .name("mediawalker-followup".to_string())
+                    .spawn(move || loop {
+                        let Ok(followup) = followup_rx.lock().unwrap().recv() else {
+                            return;
+                        };
+                        walk.follow_up(followup);
+                    });
+                followup_workers.extend(worker.ok());
+            }
+            *self.followups.lock().unwrap() = Some(followup_tx);
+        }
+        let running = Arc::new(AtomicBool::new(true));
+        let flusher = match options.target_latency {
+            Some(latency) if self.shuffled.is_some() => {
+                let walk = Arc::clone(&self);
+                let running = Arc::clone(&running);
+                thread::Builder::new()
+                    .name("mediawalker-flush".to_string())
+                    .spawn(move || walk.flush_shuffled(latency, &running))
+                    .ok()
+            }
+            _ => None,
+        };
+        let metering = options.throughput.and_then(|interval| {
+            let walk = Arc::clone(&self);
+            let running = Arc::clone(&running);
+            thread::Builder::new()
+                .name("mediawalker-throughput".to_string())
+                .spawn(move || walk.send_throughput(interval, &running))
+                .ok()
+        });
+        let mut stage_workers = vec![];
+        for &(kind, threads) in &options.stages {
+            let (stage_tx, stage_rx) =
+                mpsc::sync_channel::<(Job, MediaWalkResult)>(options.queue_capacity());
+            let stage_rx = Arc::new(Mutex::new(stage_rx));
+            for _ in 0..threads {
+                let walk = Arc::clone(&self);
+                let stage_rx = Arc::clone(&stage_rx);
+                let worker = thread::Builder::new()
+                    .name(format!("mediawalker-{}", kind))
+                    .spawn(move || loop {
+                        let Ok((job, walk_result)) = stage_rx.lock().unwrap().recv() else {
+                            return;
+                        };
+                        if !walk.stopped.load(Ordering::Relaxed) {
+                            walk.finish(&job, walk_result);
+                        }
+                    });
+                stage_workers.extend(worker.ok());
+            }
+            self.stages.lock().unwrap().insert(kind, stage_tx);
+        }
+
+        let (job_tx, workers) = if options.threads > 1 {
+            let (job_tx, job_rx) = mpsc::sync_channel::<Job>(options.queue_capacity());
+            let job_rx = Arc::new(Mutex::new(job_rx));
+            let workers: Vec<_> = (0..options.threads)
+                .map(|_| {
+                    let walk = Arc::clone(&self);
+                    let job_rx = Arc::clone(&job_rx);
+                    thread::spawn(move || loop {
+                        let jobs = {
+                            let job_rx = job_rx.lock().unwrap();
+                            let Ok(job) = job_rx.recv() else {
+                                return;
+                            };
+                            let mut jobs = vec![job];
+                            while jobs.len() < walk.batch_size() {
+                                match job_rx.try_recv() {
+                                    Ok(job) => jobs.push(job),
+                                    Err(_) => break,
+                                }
+                            }
+                            jobs
+                        };
+                        walk.inspect_all(jobs);
+                    })
+                })
+                .collect();
+            (Some(job_tx), workers)
+        } else {
+            (None, vec![])
+        };
+
+        let pending = RefCell::new(vec![]);
+        let oldest_pending = Cell::new(Instant::now());
+        let submit = |job: Job| {
+            match &job_tx {
+                Some(job_tx) => job_tx.send(job).unwrap(),
+                None => {
+                    let mut pending = pending.borrow_mut();
+                    if pending.is_empty() {
+                        oldest_pending.set(Instant::now());
+                    }
+                    pending.push(job);
+                    let overdue = options
+                        .target_latency
+                        .is_some_and(|latency| oldest_pending.get().elapsed() >= latency);
+                    if pending.len() >= self.batch_size() || overdue {
+                        self.inspect_all(std::mem::take(&mut *pending));
+                    }
+                }
+            }
+            self.pace();
+        };
+        let seeded = if options.spotlight && self.walk_root.is_dir() {
+            spotlight::candidates(&options.root, &options.kinds).ok()
+        } else {
+            None
+        };
+        match seeded {
+            Some(candidates) => self.walk_candidates(candidates, submit),
+            None if options.traversal_threads > 1 && self.walk_root.is_dir() => {
+                let submit = |job: Job| {
+                    match &job_tx {
+                        Some(job_tx) => job_tx.send(job).unwrap(),
+                        None => self.inspect(job),
+                    }
+                    self.pace();
+                };
+                self.walk_parallel(options.traversal_threads, &submit);
+            }
+            None => self.walk_tree(submit),
+        }
+        self.inspect_all(pending.into_inner());
+        drop(job_tx);
+        for worker in workers {
+            let _ = worker.join();
+        }
+        self.stages.lock().unwrap().clear();
+        for worker in stage_workers {
+            let _ = worker.join();
+        }
+        running.store(false, Ordering::Relaxed);
+        for thread in flusher.into_iter().chain(metering) {
+            let _ = thread.join();
+        }
+        if let Some(shuffled) = &self.shuffled {
+            for (_, walk_result) in shuffled.lock().unwrap().drain() {
+                if !self.send_found(walk_result) {
+                    break;
+                }
+            }
+        }
+        self.followups.lock().unwrap().take();
+        for worker in followup_workers {
+            let _ = worker.join();
+        }
+        if let (Some(Ok(recorder)), Some(log)) = (&self.recorder, &options.record) {
+            if let Err(err) = recorder.finish() {
+                self.emit(self.io_failure(log, err), None);
+            }
+        }
+    }
+
+    /// Reads the directory tree for files, a directory at a time, see
+    /// [`read_dir`](Self::read_dir).
+    fn walk_tree(&self, submit: impl Fn(Job)) {
+        let root_pruned =
+            !self.options.follow_reparse_points && paths::is_reparse_dir(&self.walk_root, true);
+        if self.walk_root.is_dir() && !root_pruned {
+            // The prioritized directories are read first, in walking
+            // order among themselves.
+            let (mut first, mut dirs) = (vec![], vec![self.root_dir()]);
+            while let Some(dir) = first.pop().or_else(|| dirs.pop()) {
+                if self.stopped.load(Ordering::Relaxed) {
+                    break;
+                }
+                for child in self.read_dir(&dir, &submit).into_iter().rev() {
+                    match child.prioritized {
+                        true => first.push(child),
+                        false => dirs.push(child),
+                    }
+                }
+            }
+            return;
+        }
+        // A file given as the root, or a root that cannot be walked.
+        let walker = WalkDir::new(&self.walk_root)
+            .follow_links(true)
+            .into_iter()
+            .filter_entry(|entry| !self.is_pruned(&Entry::from_walkdir(entry), entry.depth()));
+        for entry_result in walker {
+            if self.stopped.load(Ordering::Relaxed) {
+                break;
+            }
+            let entry = match entry_result {
+                Ok(entry) => entry,
+                Err(err) => {
+                    if !self.is_dangling_walkdir(&err) {
+                        self.emit(self.traversal_failure(err), None);
+                    }
+                    continue;
+                }
+            };
+            let depth = entry.depth();
+            if let Some(job) = self.visit_file(&Entry::from_walkdir(&entry), depth, false) {
+                submit(job);
+            }
+        }
+    }
+
+    /// Reads the directory tree for files on `threads` threads. Each
+    /// takes directories off a queue of its own, newest first, and
+    /// steals the oldest ones off the others' queues when its own runs
+    /// dry, so that a directory much larger than the rest does not hold
+    /// up the walk. Prioritized directories go on a queue shared by all,
+    /// taken off first.
+    fn walk_parallel(&self, threads: usize, submit: &(impl Fn(Job) + Sync)) {
+        let queues: Vec<Mutex<VecDeque<Dir>>> = (0..threads).map(|_| Mutex::default()).collect();
+        let first: Mutex<Vec<Dir>> = Mutex::default();
+        queues[0].lock().unwrap().push_back(self.root_dir());
+        // The directories queued or being read.
+        let pending = AtomicUsize::new(1);
+        // Bumped when directories are queued and when the last one has
+        // been read, for idle workers to wait on.
+        let (signal, wake) = (Mutex::new(0_u64), Condvar::new());
+        let bump = || {
+            *signal.lock().unwrap() += 1;
+            wake.notify_all();
+        };
+        thread::scope(|scope| {
+            for worker in 0..threads {
+                let (queues, first, pending, signal, wake, bump) =
+                    (&queues, &first, &pending, &signal, &wake, &bump);
+                scope.spawn(move || {
+                    while !self.stopped.load(Ordering::Relaxed) {
+                        let seen = *signal.lock().unwrap();
+                        let next = first.lock().unwrap().pop();
+                        let next = next.or_else(|| queues[worker].lock().unwrap().pop_back());
+                        let next = next.or_else(|| {
+                            (1..threads).find_map(|offset| {
+                                queues[(worker + offset) % threads]
+                                    .lock()
+                                    .unwrap()
+                                    .pop_front()
+                            })
+                        });
+                        let Some(dir) = next else {
+                            if pending.load(Ordering::SeqCst) == 0 {
+                                return;
+                            }
+                            let signaled = signal.lock().unwrap();
+                            if *signaled == seen && pending.load(Ordering::SeqCst) != 0 {
+                                // Woken now and then to see if the walk
+                                // was stopped.
+                                let _ = wake.wait_timeout(signaled, IDLE_POLL).unwrap();
+                            }
+                            continue;
+                        };
+                        let children = self.read_dir(&dir, submit);
+                        if !children.is_empty() {
+                            pending.fetch_add(children.len(), Ordering::SeqCst);
+                            let (prioritized, children): (Vec<_>, Vec<_>) =
+                                children.into_iter().partition(|child| child.prioritized);
+                            first.lock().unwrap().extend(prioritized.into_iter().rev());
+                            queues[worker].lock().unwrap().extend(children);
+                            bump();
+                        }
+                        if pending.fetch_sub(1, Ordering::SeqCst) == 1 {
+                            bump();
+                        }
+                    }
+                });
+            }
+        });
+    }
+
+    /// The root, as the first directory to read.
+    fn root_dir(&self) -> Dir {
+        Dir {
+            path: self.walk_root.clone(),
+            depth: 0,
+            linked: fs::symlink_metadata(&self.walk_root)
+                .is_ok_and(|metadata| metadata.file_type().is_symlink()),
+            ancestry: Ancestry::child(&None, dir_id(&self.walk_root, None)),
+            prioritized: false,
+        }
+    }
+
+    /// Reads a directory, submitting its files and returning its
+    /// subdirectories to walk.
+    fn read_dir(&self, dir: &Dir, submit: &impl Fn(Job)) -> Vec<Dir> {
+        let depth = dir.depth;
+        let mut entries = self.list_dir(&dir.path);
+        let priority = self.options.priority;
+        if priority != Priority::WalkOrder {
+            entries.sort_by(|a, b| priority.compare(a.metadata(), b.metadata()));
+        }
+        let mut subdirs = vec![];
+        for entry in entries {
+            if self.stopped.load(Ordering::Relaxed) {
+                break;
+            }
+            if self.is_pruned(&entry, depth + 1) {
+                continue;
+            }
+            if entry.entry_type == EntryType::Dir {
+                // Each directory is read on its own, so loops are looked
+                // for here instead of by walkdir: a link to a directory
+                // being walked, be it an ancestor or one that links back.
+                let id = dir_id(&entry.path, entry.metadata());
+                let ancestry = dir.ancestry.as_ref();
+                if let (Some(ancestry), Some(id), true) = (ancestry, &id, entry.is_symlink) {
+                    if ancestry.contains(id) {
+                        let path = self.reported_path(&entry.path);
+                        let mut walk_result =
+                            MediaWalkResult::new(path.to_string_lossy().into_owned());
+                        walk_result.result = Err(loop_error(&path, &entry.path));
+                        self.emit(walk_result, None);
+                        continue;
+                    }
+                }
+                let prioritized =
+                    dir.prioritized || self.options.is_priority_dir(&entry.path, &self.walk_root);
+                subdirs.push(Dir {
+                    prioritized,
+                    linked: dir.linked || entry.is_symlink,
+                    path: entry.path,
+                    depth: depth + 1,
+                    ancestry: Ancestry::child(&dir.ancestry, id),
+                });
+            } else if let Some(job) = self.visit_file(&entry, depth + 1, dir.linked) {
+                submit(job);
+            }
+        }
+        subdirs
+    }
+
+    /// Lists a directory, reporting the entries that cannot be read. On
+    /// Linux, the directory is read whole through `getdents64` and large
+    /// buffers, rather than an entry at a time, which speeds up
+    /// directories of hundreds of thousands of files. Only the entries
+    /// the directory does not tell the type of are stat'ed.
+    #[cfg(target_os = "linux")]
+    fn list_dir(&self, dir: &Path) -> Vec<Entry> {
+        let names = match dents::read_dir(dir) {
+            Ok(names) => names,
+            Err(err) => {
+                self.emit(self.io_failure(dir, err), None);
+                return vec![];
+            }
+        };
+        let mut entries = Vec::with_capacity(names.len());
+        for (name, hint) in names {
+            if self.stopped.load(Ordering::Relaxed) {
+                break;
+            }
+            let path = dir.join(name);
+            // Where the directory tells the type, the metadata is only
+            // read once needed, if at all.
+            let entry_type = match hint {
+                dents::Hint::File => Some(EntryType::File),
+                dents::Hint::Dir => Some(EntryType::Dir),
+                dents::Hint::Other => Some(EntryType::Other),
+                dents::Hint::Symlink | dents::Hint::Unknown => None,
+            };
+            if let Some(entry_type) = entry_type {
+                entries.push(Entry {
+                    path,
+                    entry_type,
+                    is_symlink: false,
+                    metadata: OnceCell::new(),
+                });
+                continue;
+            }
+            let linked = fs::symlink_metadata(&path);
+            let is_symlink = linked
+                .as_ref()
+                .is_ok_and(|metadata| metadata.file_type().is_symlink());
+            if is_symlink && self.is_too_deep(&path) {
+                continue;
+            }
+            let metadata = match linked {
+                Ok(_) if is_symlink => fs::metadata(&path),
+                linked => linked,
+            };
+            match metadata {
+                Ok(metadata) => entries.push(Entry {
+                    path,
+                    entry_type: metadata.file_type().into(),
+                    is_symlink,
+                    metadata: OnceCell::from(Some(metadata)),
+                }),
+                Err(err) if is_symlink && self.is_dangling(&path, &err) => {}
+                Err(err) => self.emit(self.io_failure(&path, err), None),
+            }
+        }
+        entries
+    }
+
+    /// Lists a directory, reporting the entries that cannot be read.
+    #[cfg(not(target_os = "linux"))]
+    fn list_dir(&self, dir: &Path) -> Vec<Entry> {
+        let walker = WalkDir::new(dir)
+            .min_depth(1)
+            .max_depth(1)
+            .follow_links(true);
+        let mut entries = vec![];
+        for entry_result in walker {
+            match entry_result {
+                Ok(entry) if entry.path_is_symlink() && self.is_too_deep(entry.path()) => {}
+                Ok(entry) => entries.push(Entry::from_walkdir(&entry)),
+                Err(err) if self.is_dangling_walkdir(&err) => {}
+                Err(err) => self.emit(self.traversal_failure(err), None),
+            }
+        }
+        entries
+    }
+
+    /// Makes a job of an entry found `depth` directories below the root,
+    /// if it is a file to inspect. `linked` is whether its directory was
+    /// reached through a symbolic link.
+    fn visit_file(&self, entry: &Entry, depth: usize, linked: bool) -> Option<Job> {
+        let options = &self.options;
+        if entry.entry_type != EntryType::File {
+            if entry.entry_type == EntryType::Other {
+                self.tally(SkipReason::SpecialFile);
+            }
+            return None;
+        }
+        let reported = self.reported_path(&entry.path);
+        let path = options.path_str(&reported)?;
+        // A file given as the root is inspected whatever the filters.
+        if depth > 0 && depth <= options.min_depth {
+            self.tally(SkipReason::Depth);
+            return None;
+        }
+        // Left out by their extensions, files are not even stat'ed.
+        if let Some(reason) = (depth > 0)
+            .then(|| options.unwanted(&entry.path, None))
+            .flatten()
+        {
+            self.tally(reason);
+            return None;
+        }
+        let metadata = entry.metadata();
+        if let Some(reason) = (depth > 0)
+            .then(|| options.unwanted(&entry.path, metadata.map(Metadata::len)))
+            .flatten()
+        {
+            self.tally(reason);
+            return None;
+        }
+        if !options.is_kept(&reported, metadata) {
+            self.tally(SkipReason::Filtered);
+            return None;
+        }
+        if options.check_permissions && metadata.is_some_and(|m| !access::may_read(m)) {
+            self.skip(&entry.path, SkipReason::NoPermission);
+            return None;
+        }
+        Some(Job {
+            path: path.into_owned(),
+            open: entry.path.clone(),
+            metadata: metadata.cloned(),
+            linked: linked || entry.is_symlink,
+        })
+    }
+
+    /// Goes through files found by Spotlight, leaving out the ones a
+    /// directory walk would not reach.
+    fn walk_candidates(&self, candidates: Vec<PathBuf>, submit: impl Fn(Job)) {
+        let options = &self.options;
+        // Read up front only to sort by, and kept for the results.
+        let mut candidates: Vec<_> = candidates.into_iter().map(|c| (c, None)).collect();
+        if options.priority != Priority::WalkOrder {
+            for (candidate, metadata) in &mut candidates {
+                *metadata = std::fs::metadata(candidate).ok();
+            }
+            candidates.sort_by(|(_, a), (_, b)| options.priority.compare(a.as_ref(), b.as_ref()));
+        }
+        if !options.priority_dirs.is_empty() {
+            // Stable, so that the files of prioritized directories stay
+            // in order.
+            candidates.sort_by_key(|(candidate, _)| {
+                !candidate
+                    .ancestors()
+                    .skip(1)
+                    .take_while(|dir| *dir != options.root)
+                    .any(|dir| options.is_priority_dir(dir, &options.root))
+            });
+        }
+        for (candidate, metadata) in candidates {
+            if self.stopped.load(Ordering::Relaxed) {
+                break;
+            }
+            let relative = candidate.strip_prefix(&options.root).unwrap_or(&candidate);
+            let pruned = relative.ancestors().any(|ancestor| {
+                !ancestor.as_os_str().is_empty()
+                    && (options.is_excluded_path(ancestor)
+                        || (ancestor != relative
+                            && options.treat_bundles_as_files
+                            && paths::is_bundle(ancestor))
+                        || (ancestor != relative
+                            && options.dir_filter.as_ref().is_some_and(|Hook(keep)| {
+                                !keep(&options.root.join(ancestor), ancestor.components().count())
+                            })))
+            });
+            if pruned || relative.components().count() <= options.min_depth {
+                continue;
+            }
+            let Some(path) = options.path_str(&candidate) else {
+                continue;
+            };
+            let path = path.into_owned();
+            let metadata = match metadata.or_else(|| std::fs::metadata(&candidate).ok()) {
+                Some(metadata) if metadata.is_file() => metadata,
+                _ => continue,
+            };
+            if let Some(reason) = options.unwanted(&candidate, Some(metadata.len())) {
+                self.tally(reason);
+                continue;
+            }
+            if !options.is_kept(&candidate, Some(&metadata)) {
+                self.tally(SkipReason::Filtered);
+                continue;
+            }
+            submit(Job {
+                path,
+                open: candidate.clone(),
+                metadata: Some(metadata),
+                linked: false,
+            });
+        }
+    }
+
+    /// Reports a skipped entry.
+    fn skip(&self, path: &Path, reason: SkipReason) {
+        self.tally(reason);
+        let path = self.reported_path(path).into_owned();
+        self.send(WalkEvent::Skipped { path, reason });
+    }
+
+    /// Counts a skipped entry in [`WalkStats::skipped`].
+    fn tally(&self, reason: SkipReason) {
+        *self
+            .stats
+            .lock()
+            .unwrap()
+            .skipped
+            .entry(reason)
+            .or_default() += 1;
+    }
+
+    /// Sends an event, stopping the walk if no one is listening. Returns
+    /// whether the event was sent.
+    fn send(&self, event: WalkEvent) -> bool {
+        if let Some(Ok(recorder)) = &self.recorder {
+            recorder.write(&event);
+        }
+        let sent = self.tx.send(event).is_ok();
+        if !sent {
+            self.stopped.store(true, Ordering::Relaxed);
+        }
+        sent
+    }
+
+    /// Sends a result, after the directories holding it that are yet to
+    /// be reported, outermost first, see [`MediaWalker::emit_dirs`].
+    fn send_found(&self, mut walk_result: MediaWalkResult) -> bool {
+        if self.options.emit_dirs == DirEmit::WithMatches && matches!(walk_result.result, Ok(true))
+        {
+            let root = &self.options.root;
+            let mut new_dirs = vec![];
+            {
+                let mut dirs = self.dirs.lock().unwrap();
+                for dir in Path::new(&walk_result.path).ancestors().skip(1) {
+                    if !dir.starts_with(root) || !dirs.insert(dir.to_path_buf()) {
+                        break;
+                    }
+                    new_dirs.push(dir.to_path_buf());
+                }
+            }
+            for dir in new_dirs.into_iter().rev() {
+                let artwork = self
+                    .options
+                    .artwork
+                    .then(|| artwork::in_dir(&dir, &dir))
+                    .flatten();
+                if !self.send(WalkEvent::Directory { path: dir, artwork }) {
+                    return false;
+                }
+            }
+        }
+        walk_result.session_id = Some(self.session_id);
+        walk_result.emitted_at = Some(SystemTime::now());
+        self.send(WalkEvent::Found(walk_result))
+    }
+
+    /// Pauses after a file, as asked to with [`MediaWalker::throttle`],
+    /// to back off from busy disks, and while on battery.
+    fn pace(&self) {
+        if let Some(power) = &self.power {
+            power.wait_for_mains(&self.stopped);
+        }
+        if let Some(pause) = self.options.throttle {
+            thread::sleep(pause);
+        }
+        if let Some(pause) = self.backoff.as_ref().and_then(Backoff::pause) {
+            thread::sleep(pause);
+        }
+    }
+
+    /// Lets through the shuffled results that have been held back for
+    /// `latency`, until the walk is done, see
+    /// [`MediaWalker::target_latency`].
+    fn flush_shuffled(&self, latency: Duration, running: &AtomicBool) {
+        let Some(shuffled) = &self.shuffled else {
+            return;
+        };
+        let tick = (latency / 2).max(Duration::from_millis(1));
+        while running.load(Ordering::Relaxed) && !self.stopped.load(Ordering::Relaxed) {
+            thread::sleep(tick);
+            let overdue = shuffled
+                .lock()
+                .unwrap()
+                .release(|(held, _)| held.elapsed() < latency);
+            for (_, walk_result) in overdue {
+                if !self.send_found(walk_result) {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Sends how fast the walk goes every `interval`, until it is done,
+    /// see [`MediaWalker::throughput`].
+    fn send_throughput(&self, interval: Duration, running: &AtomicBool) {
+        let Some(meter) = &self.meter else {
+            return;
+        };
+        let mut sent = Instant::now();
+        while running.load(Ordering::Relaxed) && !self.stopped.load(Ordering::Relaxed) {
+            thread::sleep(interval.min(IDLE_POLL));
+            if sent.elapsed() >= interval {
+                sent = Instant::now();
+                if !self.send(meter.read(self.options.expected_files)) {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Whether an entry should be left out of the walk altogether.
+    /// `depth` is how many directories below the root it is.
+    fn is_pruned(&self, entry: &Entry, depth: usize) -> bool {
+        let options = &self.options;
+        let is_dir = entry.entry_type == EntryType::Dir;
+        if is_dir && depth > 0 && paths::is_workflow_cache(&entry.path, &options.workflow_caches) {
+            self.stats.lock().unwrap().pruned_caches += 1;
+            self.skip(&entry.path, SkipReason::WorkflowCache);
+            return true;
+        }
+        if options.is_excluded(&entry.path, depth, &self.walk_root) {
+            self.tally(SkipReason::Excluded);
+            return true;
+        }
+        if let Some(Hook(keep)) = &options.dir_filter {
+            if depth > 0 && is_dir && !keep(&self.reported_path(&entry.path), depth) {
+                self.tally(SkipReason::Filtered);
+                return true;
+            }
+        }
+        if !options.follow_reparse_points && paths::is_reparse_dir(&entry.path, is_dir) {
+            self.skip(&entry.path, SkipReason::ReparsePoint);
+            return true;
+        }
+        if options.treat_bundles_as_files && depth > 0 && is_dir && paths::is_bundle(&entry.path) {
+            self.tally(SkipReason::Bundle);
+            if options.report_bundles {
+                let path = self.reported_path(&entry.path).into_owned();
+                self.send(WalkEvent::Bundle { path });
+            }
+            return true;
+        }
+        false
+    }
+
+    /// Returns the path of an entry as it should be reported: under the
+    /// root as it was given, rather than its extended-length form.
+    fn reported_path<'a>(&'a self, path: &'a Path) -> Cow<'a, Path> {
+        if self.walk_root == self.options.root {
+            return Cow::Borrowed(path);
+        }
+        match path.strip_prefix(&self.walk_root) {
+            Ok(relative) if relative.as_os_str().is_empty() => Cow::Borrowed(&self.options.root),
+            Ok(relative) => Cow::Owned(self.options.root.join(relative)),
+            Err(_) => Cow::Borrowed(path),
+        }
+    }
+
+    /// Whether an entry that could not be read is a symbolic link whose
+    /// target is gone, reporting it if asked to, see
+    /// [`MediaWalker::report_dangling_symlinks`].
+    fn is_dangling(&self, path: &Path, err: &io::Error) -> bool {
+        if !self.options.report_dangling_symlinks || err.kind() != io::ErrorKind::NotFound {
+            return false;
+        }
+        let Ok(target) = fs::read_link(path) else {
+            return false;
+        };
+        let link = self.reported_path(path).into_owned();
+        let target = match link.parent() {
+            Some(dir) => dir.join(target),
+            None => target,
+        };
+        self.send(WalkEvent::DanglingSymlink { link, target });
+        true
+    }
+
+    /// Whether a symbolic link takes more links to resolve than allowed,
+    /// skipping it if so, see [`MediaWalker::max_symlink_depth`].
+    fn is_too_deep(&self, link: &Path) -> bool {
+        let Some(max) = self.options.max_symlink_depth else {
+            return false;
+        };
+        let mut path = link.to_path_buf();
+        let mut hops = 0;
+        while hops <= max {
+            let Ok(target) = fs::read_link(&path) else {
+                return false;
+            };
+            path = match path.parent() {
+                Some(dir) => dir.join(target),
+                None => target,
+            };
+            hops += 1;
+        }
+        self.skip(link, SkipReason::SymlinkDepth);
+        true
+    }
+
+    /// [`is_dangling`](Self::is_dangling) for a traversal error.
+    fn is_dangling_walkdir(&self, err: &walkdir::Error) -> bool {
+        match (err.path(), err.io_error()) {
+            (Some(path), Some(io_err)) => self.is_dangling(path, io_err),
+            _ => false,
+        }
+    }
+
+    /// Turns an error reading a directory entry into a failed result,
+    /// worded like walkdir's.
+    fn io_failure(&self, path: &Path, err: io::Error) -> MediaWalkResult {
+        let path = self.reported_path(path);
+        let mut walk_result = MediaWalkResult::new(path.to_string_lossy().into_owned());
+        walk_result.lossy = path.to_str().is_none();
+        let message = format!("IO error for operation on {}: {}", path.display(), err);
+        walk_result.result = Err(io::Error::new(err.kind(), message));
+        walk_result
+    }
+
+    /// Turns a directory traversal error into a failed result.
+    fn traversal_failure(&self, err: walkdir::Error) -> MediaWalkResult {
+        let path = err.path().map(|path| self.reported_path(path));
+        let mut walk_result = MediaWalkResult::new(
+            path.as_deref()
+                .map(|path| path.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        );
+        walk_result.lossy = path.is_some_and(|path| path.to_str().is_none());
+        walk_result.result = Err(io::Error::from(err));
+        walk_result
+    }
+
+    /// How many files to inspect at a time.
+    fn batch_size(&self) -> usize {
+        #[cfg(all(feature = "io-uring", target_os = "linux"))]
+        if self.options.io_uring {
+            return URING_BATCH;
+        }
+        1
+    }
+
+    /// Inspects and reports a batch of files, reading their headers
+    /// through io_uring if asked to.
+    fn inspect_all(&self, jobs: Vec<Job>) {
+        #[cfg(all(feature = "io-uring", target_os = "linux"))]
+        if self.options.io_uring && jobs.len() > 1 {
+            let opens: Vec<&Path> = jobs.iter().map(|job| job.open.as_path()).collect();
+            if let Ok(headers) = uring::read_headers(&opens, HEADER_LEN) {
+                for (job, header) in jobs.into_iter().zip(headers) {
+                    if self.stopped.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    let sniff = || header.map(|header| classify_bytes(&header));
+                    let locked = |mime: &str| locked::detect(&job.open, mime);
+                    let walk_result = self.options.classify(&job.path, sniff, locked);
+                    self.report(job, walk_result);
+                }
+                return;
+            }
+        }
+        for job in jobs {
+            self.inspect(job);
+        }
+    }
+
+    /// Inspects and reports a single file.
+    fn inspect(&self, job: Job) {
+        if self.stopped.load(Ordering::Relaxed) {
+            return;
+        }
+        let sniff = || self.sniff(&job.open);
+        let locked = |mime: &str| locked::detect(&job.open, mime);
+        let walk_result = self.options.classify(&job.path, sniff, locked);
+        self.report(job, walk_result);
+    }
+
+    /// Reads the header of a file and figures out its type.
+    fn sniff(&self, open: &Path) -> io::Result<Option<Classified>> {
+        if self.options.mmap {
+            if let Some(map) = Mmap::open(open)? {
+                return map.read(|bytes| classify_bytes(&bytes[..bytes.len().min(HEADER_LEN)]));
+            }
+        }
+        let mut header = self.buffers.take();
+        let read =
+            File::open(open).and_then(|file| file.take(HEADER_LEN as u64).read_to_end(&mut header));
+        let sniffed = read.map(|_| classify_bytes(&header));
+        self.buffers.give(header);
+        sniffed
+    }
+
+    /// Reports the result of inspecting a file, if any.
+    fn report(&self, job: Job, walk_result: Option<MediaWalkResult>) {
+        if let Some(meter) = &self.meter {
+            meter.count(job.metadata.as_ref().map_or(0, Metadata::len));
+        }
+        let Some(mut walk_result) = walk_result else {
+            self.tally(SkipReason::Kind);
+            return;
+        };
+        walk_result.lossy = job.open.to_str().is_none();
+        if let Some(seen) = &self.seen {
+            if walk_result.result.is_ok() && !self.is_first_sight(seen, &job.open) {
+                self.skip(&job.open, SkipReason::Duplicate);
+                return;
+            }
+        }
+        if walk_result.locked.is_some() && self.options.locked_files == LockedFiles::Skip {
+            self.skip(&job.open, SkipReason::Locked);
+            return;
+        }
+        if let Some(kind) = walk_result.kind {
+            // Not holding the lock while the stage's queue is full, for
+            // the other workers to get at theirs.
+            let stage = self.stages.lock().unwrap().get(&kind).cloned();
+            if let Some(stage) = stage {
+                let _ = stage.send((job, walk_result));
+                return;
+            }
+        }
+        self.finish(&job, walk_result);
+    }
+
+    /// Completes and emits a result: the last stage of inspecting a
+    /// file, see [`MediaWalker::stage_concurrency`].
+    fn finish(&self, job: &Job, mut walk_result: MediaWalkResult) {
+        if let Some(filter) = &self.options.filter {
+            let looked_up;
+            let mut metadata = job.metadata.as_ref();
+            if metadata.is_none() && filter.needs_metadata() {
+                looked_up = fs::metadata(&job.open).ok();
+                metadata = looked_up.as_ref();
+            }
+            if !filter.matches(&walk_result, metadata) {
+                return;
+            }
+        }
+        #[cfg(feature = "xattr")]
+        if let Some(names) = &self.options.xattrs {
+            if walk_result.result.is_ok() {
+                walk_result.xattrs = crate::Xattrs::read(&job.open, names);
+            }
+        }
+        if job.linked && walk_result.result.is_ok() {
+            walk_result.resolved_path = fs::canonicalize(&job.open).ok();
+        }
+        let followups = match walk_result.result {
+            Ok(_) => self.followups.lock().unwrap().clone(),
+            Err(_) => None,
+        };
+        let later = followups.is_some() && self.options.progressive;
+        let content_id_later = followups.is_some() && self.options.ids == Some(IdStrategy::Content);
+        #[cfg(feature = "acoustid")]
+        if self.options.fingerprints && walk_result.kind == Some(Kind::Audio) && !later {
+            walk_result.fingerprint =
+                crate::Fingerprint::compute(&job.open, &self.options.fpcalc).ok();
+        }
+        if self.options.color_info && matches!(walk_result.kind, Some(Kind::Image | Kind::Video)) {
+            walk_result.color_info = color::read(&job.open, &walk_result.mime);
+        }
+        if self.options.detect_animation && walk_result.kind == Some(Kind::Image) {
+            walk_result.animated = animation::is_animated(&job.open, &walk_result.mime);
+        }
+        if self.options.motion_photos && &*walk_result.mime == "image/jpeg" {
+            walk_result.motion = motion::embedded(&job.open);
+        }
+        if self.options.artwork && walk_result.kind == Some(Kind::Audio) {
+            walk_result.artwork = artwork::embedded(&job.open, &walk_result.mime)
+                .or_else(|| self.cover(&job.open, &walk_result.path));
+        }
+        if self.options.exif && walk_result.kind == Some(Kind::Image) && !later {
+            walk_result.exif = exif::read(&job.open, &walk_result.mime);
+        }
+        if self.options.media_hints && walk_result.kind == Some(Kind::Image) {
+            walk_result.hints = hint::hints(&job.open, &walk_result.mime);
+        }
+        if self.options.video_signatures && walk_result.kind == Some(Kind::Video) && !later {
+            walk_result.video_signature =
+                crate::VideoSignature::compute(&job.open, &self.options.ffmpeg).ok();
+        }
+        if let Some(strategy) = self.options.ids {
+            if walk_result.result.is_ok() && !content_id_later {
+                walk_result.id = strategy.id(&job.open, job.metadata.as_ref()).ok();
+            }
+        }
+        if let Some(cache) = &self.thumbnails {
+            if walk_result.kind == Some(Kind::Image) {
+                let path = walk_result
+                    .raw_path
+                    .as_deref()
+                    .unwrap_or(Path::new(&walk_result.path));
+                walk_result.thumbnail = thumbnail::find(cache, path, job.metadata.as_ref());
+            }
+        }
+        self.options.enrich(&mut walk_result);
+        if let Some(Hook(map)) = &self.options.result_map {
+            match map(walk_result) {
+                Some(mapped) => walk_result = mapped,
+                None => return,
+            }
+        }
+        #[cfg(feature = "documents")]
+        let embedded = match walk_result.kind {
+            Some(Kind::Document) if self.options.embedded_media => {
+                crate::document::embedded(&job.open, &walk_result.path, &walk_result.mime)
+            }
+            _ => vec![],
+        };
+        let followup = (later || content_id_later).then(|| Followup {
+            open: job.open.clone(),
+            metadata: job.metadata.clone(),
+            result_id: walk_result.result_id,
+            kind: walk_result.kind,
+            mime: Arc::clone(&walk_result.mime),
+            content_id: content_id_later,
+        });
+        self.emit(walk_result, job.metadata.as_ref());
+        #[cfg(feature = "documents")]
+        for media in embedded {
+            if self.stopped.load(Ordering::Relaxed) || !self.send(WalkEvent::Embedded(media)) {
+                break;
+            }
+        }
+        if let (Some(followups), Some(followup)) = (followups, followup) {
+            let _ = followups.send(followup);
+        }
+    }
+
+    /// Works out the details of a result sent, sending each as it comes.
+    /// Whatever is left when the walk was stopped is dropped.
+    fn follow_up(&self, followup: Followup) {
+        let id = followup.result_id;
+        let send = |field: Option<crate::Enrichment>| match field {
+            Some(field) if !self.stopped.load(Ordering::Relaxed) => {
+                self.send(WalkEvent::Enriched { id, field })
+            }
+            Some(_) => false,
+            None => !self.stopped.load(Ordering::Relaxed),
+        };
+        let open = &followup.open;
+        let details = match followup.kind {
+            _ if !self.options.progressive => None,
+            Some(Kind::Image) if self.options.exif => {
+                exif::read(open, &followup.mime).map(crate::Enrichment::Exif)
+            }
+            Some(Kind::Video) if self.options.video_signatures => {
+                crate::VideoSignature::compute(open, &self.options.ffmpeg)
+                    .ok()
+                    .map(crate::Enrichment::VideoSignature)
+            }
+            #[cfg(feature = "acoustid")]
+            Some(Kind::Audio) if self.options.fingerprints => {
+                crate::Fingerprint::compute(open, &self.options.fpcalc)
+                    .ok()
+                    .map(crate::Enrichment::Fingerprint)
+            }
+            _ => None,
+        };
+        if send(details) && followup.content_id {
+            let content_id = IdStrategy::Content.id(open, followup.metadata.as_ref());
+            send(content_id.ok().map(crate::Enrichment::Id));
+        }
+    }
+
+    /// The cover image next to a song, looked up once a directory.
+    fn cover(&self, open: &Path, path: &str) -> Option<ArtworkRef> {
+        let (open, dir) = (open.parent()?, Path::new(path).parent()?);
+        if let Some(cover) = self.covers.lock().unwrap().get(open) {
+            return cover.clone();
+        }
+        let cover = artwork::in_dir(open, dir);
+        self.covers
+            .lock()
+            .unwrap()
+            .insert(open.to_path_buf(), cover.clone());
+        cover
+    }
+
+    /// Whether a file has not been reported before under any path.
+    fn is_first_sight(&self, seen: &Mutex<HashSet<PathBuf>>, path: &Path) -> bool {
+        let mut canonical = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        if self.ignore_case {
+            canonical = PathBuf::from(canonical.to_string_lossy().to_lowercase());
+        }
+        seen.lock().unwrap().insert(canonical)
+    }
+
+    /// Accounts for and sends a result, as the error policy dictates.
+    /// Stops the walk when it should not go on.
+    fn emit(&self, mut walk_result: MediaWalkResult, metadata: Option<&Metadata>) {
+        if let Err(err) = &walk_result.result {
+            if self.options.locked_files != LockedFiles::Ignore
+                && err.kind() == io::ErrorKind::PermissionDenied
+                && locked::is_photos_library(Path::new(&walk_result.path))
+            {
+                if self.options.locked_files == LockedFiles::Skip {
+                    self.tally(SkipReason::Locked);
+                    let path = PathBuf::from(walk_result.path);
+                    self.send(WalkEvent::Skipped {
+                        path,
+                        reason: SkipReason::Locked,
+                    });
+                    return;
+                }
+                walk_result.locked = Some(LockedContainer::PhotosLibrary);
+            }
+        }
+        let policy = self.options.error_policy;
+        let failed = walk_result.result.is_err();
+        let done = {
+            let mut stats = self.stats.lock().unwrap();
+            // Whatever was in flight when the walk was stopped is dropped.
+            if self.stopped.load(Ordering::Relaxed) {
+                return;
+            }
+            stats.record(&walk_result, metadata);
+            if policy == ErrorPolicy::Collect {
+                stats
+                    .failures
+                    .extend(CollectedError::from_result(&walk_result));
+            }
+            let mut stop_after = self.stop_after.lock().unwrap();
+            stop_after.as_mut().is_some_and(|done| done(&stats))
+        };
+        if failed && policy == ErrorPolicy::Ignore {
+            return;
+        }
+        let released = match &self.shuffled {
+            Some(shuffled) => shuffled
+                .lock()
+                .unwrap()
+                .push((Instant::now(), walk_result))
+                .map(|(_, walk_result)| walk_result),
+            None => Some(walk_result),
+        };
+        if let Some(walk_result) = released {
+            self.send_found(walk_result);
+        }
+        if done || failed && policy == ErrorPolicy::FailFast {
+            self.stopped.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempTree;
+
+    #[test]
+    fn it_reports_companions_only_when_asked() {
+        let tree = TempTree::new("companions");
+        tree.file("movie.srt", b"1\n00:00:01,000 --> 00:00:02,000\nHello\n");
+        tree.file("album/album.cue", b"FILE \"album.flac\" WAVE\n");
+        tree.file("album/notes.txt", b"just text\n");
+
+        let unknown = MediaWalker::new(tree.path())
+            .start()
+            .filter(|r| matches!(r.result, Ok(false)))
+            .count();
+        assert_eq!(unknown, 3);
+
+        let mut companions: Vec<_> = MediaWalker::new(tree.path())
+            .companions(true)
+            .start()
+            .filter(|r| r.kind == Some(Kind::Companion))
+            .map(|r| r.mime.to_string())
+            .collect();
+        companions.sort();
+        assert_eq!(
+            companions,
+            vec!["application/x-cue", "application/x-subrip"]
+        );
+    }
+
+    #[test]
+    fn it_reports_extra_kinds_only_when_asked() {
+        let tree = TempTree::new("extra-kinds");
+        tree.file("docs/manual.pdf", b"%PDF-1.7\n");
+        tree.sample("sample_640x426.jpg", "photo.jpg");
+
+        let kinds: Vec<_> = MediaWalker::new(tree.path())
+            .start()
+            .map(|r| r.kind)
+            .collect();
+        assert_eq!(kinds, vec![Some(Kind::Image)]);
+
+        let mut kinds: Vec<_> = MediaWalker::new(tree.path())
+            .extra_kinds(&[Kind::Document])
+            .start()
+            .map(|r| r.kind)
+            .collect();
+        kinds.sort();
+        assert_eq!(kinds, vec![Some(Kind::Image), Some(Kind::Document)]);
+    }
+
+    #[test]
+    fn it_breaks_stats_down_by_mime() {
+        let tree = TempTree::new("by-mime");
+        let jpeg = tree.sample("sample_640x426.jpg", "a.jpg");
+        tree.sample("sample_640x426.jpg", "b/b.jpg");
+        tree.sample("sample3.mp3", "c.mp3");
+        tree.file("notes.txt", b"just text\n");
+
+        let mut handle = MediaWalker::new(tree.path()).start();
+        handle.by_ref().for_each(drop);
+        let stats = handle.stats();
+        let jpeg_size = std::fs::metadata(jpeg).unwrap().len();
+        assert_eq!(stats.files, 4);
+        assert_eq!(stats.matched, 3);
+        assert_eq!(stats.unknown, 1);
+        assert_eq!(
+            stats.by_mime["image/jpeg"],
+            crate::MimeStats {
+                count: 2,
+                bytes: 2 * jpeg_size
+            }
+        );
+        assert_eq!(stats.by_mime["audio/mpeg"].count, 1);
+        assert_eq!(stats.by_kind[&Kind::Image].sizes.counts[0], 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn it_applies_the_error_policy() {
+        let tree = TempTree::new("error-policy");
+        tree.sample("sample_640x426.jpg", "a.jpg");
+        tree.sample("sample3.mp3", "b.mp3");
+        std::os::unix::fs::symlink(tree.path().join("gone.jpg"), tree.path().join("c.jpg"))
+            .unwrap();
+
+        let mut handle = MediaWalker::new(tree.path()).start();
+        assert_eq!(handle.by_ref().filter(|r| r.result.is_err()).count(), 1);
+        let failures = handle.stats().failures;
+        assert_eq!(failures.len(), 1);
+        assert!(failures[0].path.ends_with("c.jpg"));
+
+        let mut handle = MediaWalker::new(tree.path())
+            .error_policy(ErrorPolicy::Ignore)
+            .start();
+        assert_eq!(handle.by_ref().filter(|r| r.result.is_err()).count(), 0);
+        let stats = handle.stats();
+        assert_eq!((stats.errors, stats.failures.len()), (1, 0));
+
+        let results: Vec<_> = MediaWalker::new(tree.path())
+            .error_policy(ErrorPolicy::FailFast)
+            .start()
+            .collect();
+        assert!(results.last().unwrap().result.is_err());
+        assert_eq!(results.iter().filter(|r| r.result.is_err()).count(), 1);
+    }
+
+    #[test]
+    fn it_skips_excluded_paths() {
+        let tree = TempTree::new("excludes");
+        tree.sample("sample_640x426.jpg", "keep/a.jpg");
+        tree.sample("sample_640x426.jpg", "keep/a.tmp");
+        tree.sample("sample_640x426.jpg", "keep/cache/b.jpg");
+        tree.sample("sample_640x426.jpg", "skip/c.jpg");
+
+        let mut paths: Vec<_> = MediaWalker::new(tree.path())
+            .threads(3)
+            .excludes(&["*.tmp", "/skip"])
+            .exclude("**/cache")
+            .start()
+            .map(|r| r.path)
+            .collect();
+        paths.sort();
+        assert_eq!(paths.len(), 1);
+        assert!(paths[0].ends_with("a.jpg"));
+    }
+
+    #[test]
+    fn it_counts_skipped_entries_by_reason() {
+        let tree = TempTree::new("skipped");
+        tree.sample("sample_640x426.jpg", "a.jpg");
+        tree.sample("sample_640x426.jpg", "b.png");
+        tree.sample("sample_640x426.jpg", "c.jpeg");
+        tree.file("d.jpg", b"tiny");
+        tree.sample("sample_640x426.jpg", "skip/e.jpg");
+        tree.sample("sample_640x426.jpg", "skip/f.jpg");
+
+        let mut walk = MediaWalker::new(tree.path())
+            .extensions(&["jpg", "jpeg"])
+            .min_size(100)
+            .exclude("/skip")
+            .filter_files(|file| !file.path.ends_with("c.jpeg"))
+            .start();
+        assert_eq!(walk.by_ref().count(), 1);
+        let skipped = walk.stats().skipped;
+        assert_eq!(skipped.len(), 4);
+        assert_eq!(skipped[&SkipReason::Extension], 1);
+        assert_eq!(skipped[&SkipReason::Size], 1);
+        assert_eq!(skipped[&SkipReason::Filtered], 1);
+        assert_eq!(skipped[&SkipReason::Excluded], 1);
+    }
+
+    #[test]
+    fn it_explains_why_files_are_left_out() {
+        let tree = TempTree::new("explain");
+        tree.sample("sample_640x426.jpg", "a.jpg");
+        tree.sample("sample3.mp3", "b.mp3");
+        tree.file("c.jpg", b"tiny");
+        tree.sample("sample_640x426.jpg", "skip/d.jpg");
+
+        let walker = MediaWalker::new(tree.path())
+            .kinds(&[Kind::Image])
+            .min_size(100)
+            .exclude("/skip");
+        let reason = |path: &str| match walker.explain(path) {
+            Explanation::Skipped(reason) => Some(reason),
+            _ => None,
+        };
+        assert!(matches!(
+            walker.explain(tree.path().join("a.jpg")),
+            Explanation::Found(walk_result) if walk_result.kind == Some(Kind::Image)
+        ));
+        assert_eq!(reason("b.mp3"), Some(SkipReason::Kind));
+        assert_eq!(reason("c.jpg"), Some(SkipReason::Size));
+        assert_eq!(reason("skip/d.jpg"), Some(SkipReason::Excluded));
+        assert!(matches!(
+            walker.explain("skip"),
+            Explanation::Skipped(SkipReason::Excluded)
+        ));
+        assert!(matches!(
+            walker.explain("/elsewhere/a.jpg"),
+            Explanation::Outside
+        ));
+        assert!(matches!(walker.explain(""), Explanation::Walked));
+    }
+
+    #[test]
+    fn it_skips_trash_unless_included() {
+        let tree = TempTree::new("trash");
+        tree.sample("sample_640x426.jpg", "home/a.jpg");
+        tree.sample("sample_640x426.jpg", "home/.local/share/Trash/files/b.jpg");
+        tree.sample("sample_640x426.jpg", "usb/.Trash-1000/files/c.jpg");
+        tree.sample("sample_640x426.jpg", "c/$Recycle.Bin/S-1-5-21/d.jpg");
+
+        let found = |walker: MediaWalker| walker.start().count();
+        assert_eq!(found(MediaWalker::new(tree.path())), 1);
+        assert_eq!(found(MediaWalker::new(tree.path()).include_trash(true)), 4);
+        let trash = tree.path().join("home/.local/share/Trash");
+        assert_eq!(found(MediaWalker::new(trash)), 1);
+    }
+
+    #[test]
+    fn it_skips_workflow_caches() {
+        let tree = TempTree::new("workflow-caches");
+        tree.sample("sample_640x426.jpg", "shoot/a.jpg");
+        tree.sample("sample_640x426.jpg", "shoot/.thumbnails/a.jpg");
+        tree.sample("sample_640x426.jpg", "shoot/_gsdata_/a.jpg");
+        tree.sample("sample_640x426.jpg", "Catalog Previews.lrdata/0/a.jpg");
+        tree.sample(
+            "sample_640x426.jpg",
+            "nas/@eaDir/a.jpg/SYNOPHOTO_THUMB_M.jpg",
+        );
+
+        let mut handle = MediaWalker::new(tree.path()).start();
+        let skipped = handle
+            .events()
+            .filter(|event| {
+                matches!(
+                    event,
+                    WalkEvent::Skipped {
+                        reason: SkipReason::WorkflowCache,
+                        ..
+                    }
+                )
+            })
+            .count();
+        assert_eq!(skipped, 4);
+        let stats = handle.stats();
+        assert_eq!((stats.files, stats.pruned_caches), (1, 4));
+
+        let found = MediaWalker::new(tree.path())
+            .workflow_caches(&[".THUMBNAILS"])
+            .start()
+            .count();
+        assert_eq!(found, 4);
+        let found = MediaWalker::new(tree.path())
+            .workflow_caches(&[])
+            .start()
+            .count();
+        assert_eq!(found, 5);
+    }
+
+    #[test]
+    fn it_holds_the_traversal_back_within_the_memory_budget() {
+        let tree = TempTree::new("memory-budget");
+        for i in 0..20 {
+            tree.sample("sample3.mp3", &format!("{}.mp3", i));
+        }
+        let mut handle = MediaWalker::new(tree.path())
+            .threads(2)
+            .memory_budget(1)
+            .start();
+        thread::sleep(Duration::from_millis(200));
+        // A result queued, and one waiting for room on each thread.
+        assert!(handle.stats().files <= 3, "{:?}", handle.stats());
+        assert_eq!(handle.by_ref().count(), 20);
+        assert_eq!(handle.stats().files, 20);
+    }
+
+    #[test]
+    fn it_walks_prioritized_dirs_first() {
+        let tree = TempTree::new("prioritize-dirs");
+        for i in 0..10 {
+            tree.sample("sample_640x426.jpg", &format!("dir{}/a.jpg", i));
+        }
+        tree.sample("sample_640x426.jpg", "DCIM/100APPLE/IMG_0001.jpg");
+        tree.sample("sample_640x426.jpg", "DCIM/100APPLE/IMG_0002.jpg");
+
+        let paths: Vec<_> = MediaWalker::new(tree.path())
+            .prioritize_dirs(&["**/DCIM"])
+            .start()
+            .map(|r| r.path)
+            .collect();
+        assert_eq!(paths.len(), 12);
+        assert!(
+            paths[..2].iter().all(|path| path.contains("DCIM")),
+            "{:?}",
+            paths
+        );
+
+        let found = MediaWalker::new(tree.path())
+            .prioritize_dirs(&["**/DCIM"])
+            .traversal_threads(3)
+            .start()
+            .count();
+        assert_eq!(found, 12);
+    }
+
+    #[test]
+    fn it_flags_suspicious_files() {
+        let tree = TempTree::new("suspicious");
+        tree.sample("sample_640x426.jpg", "photo.jpg");
+        let mut elf = b"\x7fELF\x02\x01\x01".to_vec();
+        elf.resize(128, 0);
+        tree.file("movie.mp4", &elf);
+        tree.file("tool", &elf);
+
+        assert_eq!(MediaWalker::new(tree.path()).start().count(), 1);
+        let mut handle = MediaWalker::new(tree.path()).flag_suspicious(true).start();
+        let suspicious: Vec<_> = handle.by_ref().filter(|r| r.suspicious).collect();
+        assert_eq!(suspicious.len(), 1);
+        assert!(suspicious[0].path.ends_with("movie.mp4"));
+        assert_eq!(suspicious[0].kind, Some(Kind::Application));
+        let stats = handle.stats();
+        assert_eq!((stats.matched, stats.suspicious), (1, 1));
+    }
+
+    #[test]
+    fn it_tells_the_results_of_walks_apart() {
+        let before = SystemTime::now();
+        let mut first = MediaWalker::new("resources/test").start();
+        let mut second = MediaWalker::new("resources/test").start();
+        assert_ne!(first.session_id(), second.session_id());
+        let session_id = first.session_id();
+        let results: Vec<_> = first.by_ref().collect();
+        assert_eq!(results.len(), 9);
+        assert!(results.iter().all(
+            |r| r.session_id == Some(session_id) && r.emitted_at.is_some_and(|at| at >= before)
+        ));
+        assert!(second.all(|r| r.session_id != Some(session_id)));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn it_reports_paths_that_are_not_utf8_lossily() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+        let tree = TempTree::new("lossy-paths");
+        tree.sample("sample_640x426.jpg", "photo.jpg");
+        let name = OsStr::from_bytes(b"caf\xe9.jpg");
+        std::fs::copy(tree.path().join("photo.jpg"), tree.path().join(name)).unwrap();
+
+        assert_eq!(MediaWalker::new(tree.path()).start().count(), 1);
+        let results: Vec<_> = MediaWalker::new(tree.path())
+            .lossy_paths(true)
+            .start()
+            .collect();
+        assert_eq!(results.len(), 2);
+        let lossy: Vec<_> = results.iter().filter(|r| r.lossy).collect();
+        assert_eq!(lossy.len(), 1);
+        assert!(lossy[0].path.ends_with("caf\u{fffd}.jpg"));
+        assert_eq!(lossy[0].kind, Some(Kind::Image));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn it_dedupes_paths_to_the_same_file() {
+        let tree = TempTree::new("dedupe");
+        tree.sample("sample_640x426.jpg", "photos/a.jpg");
+        std::os::unix::fs::symlink(tree.path().join("photos"), tree.path().join("Photos")).unwrap();
+        assert!(!paths::is_case_insensitive(tree.path()));
+
+        assert_eq!(MediaWalker::new(tree.path()).start().count(), 2);
+        let mut handle = MediaWalker::new(tree.path()).dedupe_paths(true).start();
+        let events: Vec<_> = handle.events().collect();
+        assert_eq!(
+            events
+                .iter()
+                .filter(|e| matches!(e, WalkEvent::Found(_)))
+                .count(),
+            1
+        );
+        assert!(events.iter().any(|e| matches!(
+            e,
+            WalkEvent::Skipped {
+                reason: SkipReason::Duplicate,
+                ..
+            }
+        )));
+    }
+
+    #[test]
+    fn it_finishes_kinds_in_stages_of_their_own() {
+        let root = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/test");
+        let results: Vec<_> = MediaWalker::new(root)
+            .stage_concurrency(Kind::Video, 2)
+            .map_result(|mut walk_result| {
+                // Tag each result with the thread that finished it.
+                walk_result.mime = intern::mime(match thread::current().name() {
+                    Some("mediawalker-video") => "staged",
+                    _ => "inline",
+                });
+                Some(walk_result)
+            })
+            .start()
+            .collect();
+        assert_eq!(results.len(), 9);
+        for walk_result in results {
+            let staged = walk_result.kind == Some(Kind::Video);
+            assert_eq!(&*walk_result.mime, if staged { "staged" } else { "inline" });
+        }
+    }
+
+    #[test]
+    fn it_hashes_files_after_sending_their_results() {
+        let root = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/test");
+        let mut handle = MediaWalker::new(root)
+            .ids(IdStrategy::Content)
+            .hash_concurrency(2)
+            .start();
+        let mut found = HashMap::new();
+        let mut hashed = 0;
+        for event in handle.events() {
+            match event {
+                WalkEvent::Found(walk_result) => {
+                    assert_eq!(walk_result.id, None);
+                    found.insert(walk_result.result_id, walk_result.path);
+                }
+                WalkEvent::Enriched { id, field } => {
+                    let path = &found[&id];
+                    let inline = IdStrategy::Content.id(Path::new(path), None).unwrap();
+                    assert_eq!(field, crate::Enrichment::Id(inline));
+                    hashed += 1;
+                }
+                _ => {}
+            }
+        }
+        assert_eq!((found.len(), hashed), (9, 9));
+
+        // Cheap IDs are not worth a stage.
+        let results = MediaWalker::new(root)
+            .ids(IdStrategy::Inode)
+            .hash_concurrency(2)
+            .start();
+        assert!(results
+            .into_iter()
+            .all(|walk_result| walk_result.id.is_some()));
+    }
+
+    #[test]
+    fn it_fills_in_results_progressively() {
+        let tree = TempTree::new("progressive");
+        let tiff = exif::test_tiff(&[(0x9003, "2023:07:14 18:30:05")], None);
+        tree.file("taken.jpg", &exif::test_jpeg(&tiff));
+        tree.sample("sample_640x426.jpg", "plain.jpg");
+        tree.sample("sample3.mp3", "song.mp3");
+        let walker = || {
+            MediaWalker::new(tree.path())
+                .ids(IdStrategy::Content)
+                .exif(true)
+        };
+        let mut inline: Vec<_> = walker().start().collect();
+        let mut handle = walker().progressive(true).start();
+        let mut progressive = HashMap::new();
+        for event in handle.events() {
+            match event {
+                WalkEvent::Found(walk_result) => {
+                    assert!(walk_result.id.is_none() && walk_result.exif.is_none());
+                    progressive.insert(walk_result.result_id, walk_result);
+                }
+                WalkEvent::Enriched { id, field } => {
+                    field.apply(progressive.get_mut(&id).expect("enriched before found"));
+                }
+                _ => {}
+            }
+        }
+        let mut progressive: Vec<_> = progressive.into_values().collect();
+        inline.sort_by(|a, b| a.path.cmp(&b.path));
+        progressive.sort_by(|a, b| a.path.cmp(&b.path));
+        assert_eq!(progressive.len(), inline.len());
+        assert!(inline.iter().any(|walk_result| walk_result.exif.is_some()));
+        for (progressive, inline) in progressive.iter().zip(&inline) {
+            assert_eq!(progressive.path, inline.path);
+            assert_eq!(progressive.id, inline.id);
+            assert_eq!(progressive.exif, inline.exif);
+        }
+    }
+
+    #[test]
+    fn it_reports_directories_with_media() {
+        let tree = TempTree::new("emit-dirs");
+        tree.sample("sample3.mp3", "music/artist/album/01.mp3");
+        tree.sample("sample3.mp3", "music/artist/album/02.mp3");
+        tree.sample("sample_640x426.jpg", "music/artist/cover.jpg");
+        tree.file("notes/readme.txt", b"no media here");
+
+        let mut handle = MediaWalker::new(tree.path())
+            .emit_dirs(DirEmit::WithMatches)
+            .start();
+        let mut seen = HashSet::new();
+        let mut dirs = vec![];
+        for event in handle.events() {
+            match event {
+                WalkEvent::Directory { path, .. } => {
+                    if let Some(parent) = path.parent().filter(|_| path != tree.path()) {
+                        assert!(seen.contains(parent), "{:?} before its parent", path);
+                    }
+                    seen.insert(path.clone());
+                    dirs.push(path);
+                }
+                WalkEvent::Found(walk_result) if matches!(walk_result.result, Ok(true)) => {
+                    let parent = Path::new(&walk_result.path).parent().unwrap();
+                    assert!(
+                        seen.contains(parent),
+                        "{} before its directory",
+                        walk_result.path
+                    );
+                }
+                _ => {}
+            }
+        }
+        dirs.sort();
+        let expected: Vec<_> = ["", "music", "music/artist", "music/artist/album"]
+            .iter()
+            .map(|dir| tree.path().join(dir).components().collect::<PathBuf>())
+            .collect();
+        assert_eq!(dirs, expected);
+
+        let mut handle = MediaWalker::new(tree.path()).start();
+        assert!(!handle
+            .events()
+            .any(|event| matches!(event, WalkEvent::Directory { .. })));
+    }
+
+    #[test]
+    fn it_walks_on_several_threads() {
+        let tree = TempTree::new("traversal-threads");
+        for i in 0..200 {
+            tree.sample("sample_640x426.jpg", &format!("big/{}.jpg", i));
+        }
+        for i in 0..20 {
+            tree.sample("sample3.mp3", &format!("small/{}/deeper/{}.mp3", i, i));
+        }
+        tree.sample("sample3.mp3", "skip/a.mp3");
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(tree.path(), tree.path().join("small/0/loop")).unwrap();
+
+        let found = |walker: MediaWalker| {
+            let mut paths: Vec<_> = walker
+                .exclude("/skip")
+                .start()
+                .filter(|r| r.result.is_ok())
+                .map(|r| r.path)
+                .collect();
+            paths.sort();
+            paths
+        };
+        let expected = found(MediaWalker::new(tree.path()));
+        assert_eq!(expected.len(), 220);
+        assert_eq!(
+            found(MediaWalker::new(tree.path()).traversal_threads(4)),
+            expected
+        );
+        assert_eq!(
+            found(
+                MediaWalker::new(tree.path())
+                    .traversal_threads(3)
+                    .threads(2)
+            ),
+            expected
+        );
+        #[cfg(unix)]
+        assert!(MediaWalker::new(tree.path())
+            .traversal_threads(4)
+            .start()
+            .any(|r| r.result.is_err_and(|err| err.to_string().contains("loop"))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn it_stops_at_links_between_siblings() {
+        let tree = TempTree::new("sibling-loops");
+        tree.sample("sample_640x426.jpg", "a/x.jpg");
+        tree.sample("sample_640x426.jpg", "b/y.jpg");
+        std::os::unix::fs::symlink("../b", tree.path().join("a/tob")).unwrap();
+        std::os::unix::fs::symlink("../a", tree.path().join("b/toa")).unwrap();
+
+        for threads in [1, 3] {
+            let results: Vec<_> = MediaWalker::new(tree.path())
+                .traversal_threads(threads)
+                .start()
+                .collect();
+            let mut found: Vec<_> = results
+                .iter()
+                .filter(|r| r.result.is_ok())
+                .map(|r| r.path.strip_prefix(tree.path().to_str().unwrap()).unwrap())
+                .collect();
+            found.sort();
+            assert_eq!(
+                found,
+                ["/a/tob/y.jpg", "/a/x.jpg", "/b/toa/x.jpg", "/b/y.jpg"]
+            );
+            let mut loops: Vec<_> = results
+                .iter()
+                .filter(|r| {
+                    r.result
+                        .as_ref()
+                        .is_err_and(|e| e.to_string().contains("loop"))
+                })
+                .map(|r| r.path.strip_prefix(tree.path().to_str().unwrap()).unwrap())
+                .collect();
+            loops.sort();
+            assert_eq!(loops, ["/a/tob/toa", "/b/toa/tob"]);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn it_reports_dangling_symlinks_when_asked() {
+        let tree = TempTree::new("dangling-symlinks");
+        tree.sample("sample_640x426.jpg", "kept.jpg");
+        std::os::unix::fs::symlink("moved/gone.jpg", tree.path().join("gone.jpg")).unwrap();
+        let results: Vec<_> = MediaWalker::new(tree.path()).start().collect();
+        assert_eq!(results.iter().filter(|r| r.result.is_err()).count(), 1);
+
+        let mut handle = MediaWalker::new(tree.path())
+            .report_dangling_symlinks(true)
+            .start();
+        let events: Vec<_> = handle.events().collect();
+        assert!(events.iter().all(|event| match event {
+            WalkEvent::Found(walk_result) => walk_result.result.is_ok(),
+            _ => true,
+        }));
+        let dangling: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                WalkEvent::DanglingSymlink { link, target } => Some((link, target)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            dangling,
+            [(
+                &tree.path().join("gone.jpg"),
+                &tree.path().join("moved/gone.jpg")
+            )]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn it_resolves_symlinked_files() {
+        let tree = TempTree::new("resolved-paths");
+        let real = tree.sample("sample_640x426.jpg", "real/a.jpg");
+        std::os::unix::fs::symlink("real", tree.path().join("linked")).unwrap();
+        std::os::unix::fs::symlink("real/a.jpg", tree.path().join("b.jpg")).unwrap();
+        let mut resolved: Vec<_> = MediaWalker::new(tree.path())
+            .start()
+            .map(|r| (r.path, r.resolved_path))
+            .collect();
+        resolved.sort();
+        let real = fs::canonicalize(real).unwrap();
+        let path = |rel: &str| tree.path().join(rel).to_string_lossy().into_owned();
+        assert_eq!(
+            resolved,
+            [
+                (path("b.jpg"), Some(real.clone())),
+                (path("linked/a.jpg"), Some(real)),
+                (path("real/a.jpg"), None),
+            ]
+        );
+    }
+
+    #[cfg(all(unix, feature = "acoustid"))]
+    #[test]
+    fn it_fingerprints_songs() {
+        use std::os::unix::fs::PermissionsExt;
+        let tree = TempTree::new("fingerprints");
+        tree.sample("sample3.mp3", "music/a.mp3");
+        tree.sample("sample_640x426.jpg", "music/cover.jpg");
+        let fpcalc = tree.file(
+            "fpcalc",
+            b"#!/bin/sh\necho DURATION=27\necho FINGERPRINT=1,2,3\n",
+        );
+        fs::set_permissions(&fpcalc, fs::Permissions::from_mode(0o755)).unwrap();
+        let mut walker = MediaWalker::new(tree.path().join("music")).fingerprints(true);
+        walker.fpcalc = fpcalc.into_os_string();
+        let results: Vec<_> = walker.start().collect();
+        for walk_result in results {
+            let expected = (walk_result.kind == Some(Kind::Audio)).then(|| crate::Fingerprint {
+                duration: 27,
+                points: vec![1, 2, 3],
+            });
+            assert_eq!(walk_result.fingerprint, expected);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn it_signs_videos() {
+        use std::os::unix::fs::PermissionsExt;
+        let tree = TempTree::new("video-signatures");
+        tree.sample("sample_640x360.mp4", "clips/a.mp4");
+        tree.sample("sample_640x426.jpg", "clips/still.jpg");
+        let ffprobe = tree.file("ffprobe", b"#!/bin/sh\necho 10.0\n");
+        let ffmpeg = tree.file("ffmpeg", b"#!/bin/sh\nhead -c 72 /dev/zero\n");
+        for tool in [&ffprobe, &ffmpeg] {
+            fs::set_permissions(tool, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        let mut walker = MediaWalker::new(tree.path().join("clips")).video_signatures(true);
+        walker.ffmpeg = Ffmpeg {
+            ffprobe: ffprobe.into_os_string(),
+            ffmpeg: ffmpeg.into_os_string(),
+        };
+        let results: Vec<_> = walker.start().collect();
+        assert_eq!(results.len(), 2);
+        for walk_result in results {
+            let expected = (walk_result.kind == Some(Kind::Video))
+                .then(|| crate::VideoSignature { frames: vec![0; 8] });
+            assert_eq!(walk_result.video_signature, expected);
+        }
+    }
+
+    #[test]
+    fn it_reads_metadata_once() {
+        let tree = TempTree::new("metadata-once");
+        tree.sample("sample_640x426.jpg", "a.jpg");
+        let (tx, _rx) = mpsc::sync_channel(QUEUE_CAPACITY);
+        let walk = Walk::new(MediaWalker::new(tree.path()), tx);
+        let entries = walk.list_dir(tree.path());
+        let entry = &entries[0];
+        let len = entry.metadata().unwrap().len();
+        // Read once, the metadata is shared from then on, even with the
+        // file gone.
+        fs::remove_file(&entry.path).unwrap();
+        assert_eq!(entry.metadata().map(Metadata::len), Some(len));
+        let job = walk.visit_file(entry, 1, false).unwrap();
+        assert_eq!(job.metadata.map(|metadata| metadata.len()), Some(len));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn it_types_entries_without_stat_where_it_can() {
+        let tree = TempTree::new("entry-types");
+        tree.sample("sample_640x426.jpg", "file.jpg");
+        tree.file("dir/.keep", b"");
+        std::os::unix::fs::symlink("dir", tree.path().join("dir-link")).unwrap();
+        std::os::unix::fs::symlink("file.jpg", tree.path().join("file-link")).unwrap();
+        std::os::unix::fs::symlink("gone", tree.path().join("dangling")).unwrap();
+
+        let (tx, rx) = mpsc::sync_channel(QUEUE_CAPACITY);
+        let walk = Walk::new(MediaWalker::new(tree.path()), tx);
+        let mut entries = walk.list_dir(tree.path());
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        let types: Vec<_> = entries
+            .iter()
+            .map(|entry| {
+                let name = entry.path.file_name().unwrap().to_str().unwrap();
+                (name, entry.entry_type, entry.is_symlink)
+            })
+            .collect();
+        // The dangling link is reported as a failure instead.
+        assert!(matches!(rx.try_recv(), Ok(WalkEvent::Found(r)) if r.path.ends_with("dangling")));
+        assert_eq!(
+            types,
+            [
+                ("dir", EntryType::Dir, false),
+                ("dir-link", EntryType::Dir, true),
+                ("file-link", EntryType::File, true),
+                ("file.jpg", EntryType::File, false),
+            ]
+        );
+        // Only the links had to be stat'ed, to tell what they point to. The
+        // filesystem of the temporary directory tells the types of the
+        // others, as ext4, xfs, btrfs and tmpfs do.
+        let stated: Vec<_> = entries
+            .iter()
+            .map(|entry| entry.metadata.get().is_some())
+            .collect();
+        assert_eq!(stated, [false, true, true, false]);
+    }
+
+    #[test]
+    fn it_reuses_header_buffers() {
+        let pool = BufferPool::new(1);
+        let mut buffer = pool.take();
+        buffer.extend(b"header");
+        let address = buffer.as_ptr();
+        pool.give(buffer);
+        pool.give(Vec::new());
+        let buffer = pool.take();
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.as_ptr(), address);
+        assert!(pool.take().capacity() >= HEADER_LEN);
+    }
+
+    #[test]
+    fn it_finds_the_same_files_through_memory_maps() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("resources/test");
+        let found = |walker: MediaWalker| {
+            let mut found: Vec<_> = walker
+                .start()
+                .map(|r| (r.path, r.mime, r.result.ok()))
+                .collect();
+            found.sort();
+            found
+        };
+        assert_eq!(
+            found(MediaWalker::new(&root).mmap(true)),
+            found(MediaWalker::new(&root))
+        );
+    }
+
+    #[cfg(all(feature = "io-uring", target_os = "linux"))]
+    #[test]
+    fn it_finds_the_same_files_through_io_uring() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("resources/test");
+        let found = |walker: MediaWalker| {
+            let mut found: Vec<_> = walker
+                .start()
+                .map(|r| (r.path, r.mime, r.result.ok()))
+                .collect();
+            found.sort();
+            found
+        };
+        let expected = found(MediaWalker::new(&root));
+        assert_eq!(found(MediaWalker::new(&root).io_uring(true)), expected);
+        assert_eq!(
+            found(MediaWalker::new(&root).io_uring(true).threads(2)),
+            expected
+        );
+    }
+
+    #[test]
+    fn it_accepts_a_file_as_the_root() {
+        let file = Path::new(env!("CARGO_MANIFEST_DIR")).join("resources/test/sample3.mp3");
+        let found: Vec<_> = MediaWalker::new(&file)
+            .extensions(&["jpg"])
+            .try_start()
+            .unwrap()
+            .map(|r| (r.path, r.kind))
+            .collect();
+        assert_eq!(
+            found,
+            vec![(file.to_str().unwrap().to_string(), Some(Kind::Audio))]
+        );
+    }
+
+    #[test]
+    fn it_fails_to_start_on_a_bad_root() {
+        let tree = TempTree::new("bad-roots");
+        tree.sample("sample3.mp3", "a.mp3");
+        assert_eq!(
+            MediaWalker::new(tree.path()).try_start().unwrap().count(),
+            1
+        );
+
+        let missing = tree.path().join("missing");
+        let err = MediaWalker::new(&missing).try_start().unwrap_err();
+        assert!(matches!(&err, MediaWalkError::RootNotFound(root) if *root == missing));
+        assert_eq!(
+            err.to_string(),
+            format!("{}: no such directory", missing.display())
+        );
+        // Without try_start, the failure is the only result.
+        let results: Vec<_> = MediaWalker::new(&missing).start().collect();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].result.is_err());
+
+        #[cfg(unix)]
+        {
+            let socket = tree.path().join("socket");
+            let _listener = std::os::unix::net::UnixListener::bind(&socket).unwrap();
+            let err = MediaWalker::new(&socket).try_start().unwrap_err();
+            assert!(matches!(&err, MediaWalkError::InvalidRoot(root) if *root == socket));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn it_stops_resolving_long_chains_of_symlinks() {
+        let tree = TempTree::new("symlink-depth");
+        tree.sample("sample_640x426.jpg", "photo.jpg");
+        let link = |target: &str, name: &str| {
+            std::os::unix::fs::symlink(target, tree.path().join(name)).unwrap()
+        };
+        link("photo.jpg", "one.jpg");
+        link("one.jpg", "two.jpg");
+        link("two.jpg", "three.jpg");
+
+        assert_eq!(MediaWalker::new(tree.path()).start().count(), 4);
+        let mut handle = MediaWalker::new(tree.path()).max_symlink_depth(2).start();
+        let mut found = vec![];
+        let mut skipped = vec![];
+        for event in handle.events() {
+            match event {
+                WalkEvent::Found(walk_result) => found.push(walk_result.path),
+                WalkEvent::Skipped { path, reason } => {
+                    assert_eq!(reason, SkipReason::SymlinkDepth);
+                    skipped.push(path);
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(found.len(), 3);
+        assert_eq!(skipped, vec![tree.path().join("three.jpg")]);
+    }
+
+    #[test]
+    fn it_skips_files_above_the_min_depth() {
+        let tree = TempTree::new("min-depth");
+        tree.sample("sample_640x426.jpg", "junk.jpg");
+        tree.sample("sample_640x426.jpg", "album/a.jpg");
+        tree.sample("sample_640x426.jpg", "album/raw/b.jpg");
+
+        let mut handle = MediaWalker::new(tree.path()).min_depth(1).start();
+        let mut paths: Vec<_> = handle.by_ref().map(|r| r.path).collect();
+        paths.sort();
+        assert_eq!(paths.len(), 2);
+        assert!(paths[0].ends_with("a.jpg"));
+        assert_eq!(handle.stats().files, 2);
+    }
+
+    #[test]
+    fn it_filters_directories() {
+        let tree = TempTree::new("filter-dirs");
+        tree.sample("sample_640x426.jpg", "new/a.jpg");
+        tree.sample("sample_640x426.jpg", "known/b.jpg");
+        tree.sample("sample_640x426.jpg", "new/deep/known/c.jpg");
+        tree.sample("sample_640x426.jpg", "new/deep/d.jpg");
+
+        let mut paths: Vec<_> = MediaWalker::new(tree.path())
+            .filter_dirs(|path, _| !path.ends_with("known"))
+            .filter_dirs(|_, depth| depth <= 2)
+            .start()
+            .map(|r| r.path)
+            .collect();
+        paths.sort();
+        assert_eq!(paths.len(), 2);
+        assert!(paths[0].ends_with("new/a.jpg"));
+        assert!(paths[1].ends_with("new/deep/d.jpg"));
+    }
+
+    #[test]
+    fn it_filters_files_before_opening_them() {
+        let tree = TempTree::new("filter-files");
+        tree.sample("sample_640x426.jpg", "a.jpg");
+        tree.sample("sample_640x426.jpg", "known.jpg");
+        tree.file("tiny.jpg", b"");
+
+        let paths: Vec<_> = MediaWalker::new(tree.path())
+            .filter_files(|entry| !entry.path.ends_with("known.jpg"))
+            .filter_files(|entry| entry.metadata.is_some_and(|m| m.len() > 0))
+            .start()
+            .map(|r| r.path)
+            .collect();
+        assert_eq!(paths.len(), 1);
+        assert!(paths[0].ends_with("a.jpg"));
+    }
+
+    #[test]
+    fn it_maps_results() {
+        let tree = TempTree::new("map-result");
+        tree.sample("sample_640x426.jpg", "a.jpg");
+        tree.sample("sample3.mp3", "b.mp3");
+
+        let mut handle = MediaWalker::new(tree.path())
+            .threads(2)
+            .map_result(|r| (r.kind == Some(Kind::Image)).then_some(r))
+            .map_result(|mut r| {
+                r.path = r.path.to_uppercase();
+                Some(r)
+            })
+            .start();
+        let paths: Vec<_> = handle.by_ref().map(|r| r.path).collect();
+        assert_eq!(paths.len(), 1);
+        assert!(paths[0].ends_with("A.JPG"));
+        assert_eq!(handle.stats().files, 1);
+    }
+
+    #[test]
+    fn it_walks_newest_files_first() {
+        let tree = TempTree::new("newest-first");
+        let now = std::time::SystemTime::now();
+        for (name, age) in [("b.jpg", 60), ("a.jpg", 0), ("c.jpg", 120)] {
+            let path = tree.sample("sample_640x426.jpg", name);
+            let file = File::options().write(true).open(path).unwrap();
+            file.set_modified(now - Duration::from_secs(age)).unwrap();
+        }
+
+        let paths: Vec<_> = MediaWalker::new(tree.path())
+            .prioritize(Priority::NewestFirst)
+            .start()
+            .map(|r| Path::new(&r.path).file_name().unwrap().to_owned())
+            .collect();
+        assert_eq!(paths, ["a.jpg", "b.jpg", "c.jpg"]);
+    }
+
+    #[test]
+    fn it_shuffles_results() {
+        let tree = TempTree::new("shuffle");
+        for i in 0..20 {
+            tree.sample("sample_640x426.jpg", &format!("{:02}.jpg", i));
+        }
+        let walk = |walker: MediaWalker| -> Vec<_> { walker.start().map(|r| r.path).collect() };
+        let mut walked = walk(MediaWalker::new(tree.path()));
+        let shuffled = walk(MediaWalker::new(tree.path()).shuffle(42));
+        assert_ne!(shuffled, walked);
+        assert_eq!(shuffled, walk(MediaWalker::new(tree.path()).shuffle(42)));
+        let mut sorted = shuffled.clone();
+        sorted.sort();
+        walked.sort();
+        assert_eq!(sorted, walked);
+    }
+
+    #[test]
+    fn it_bounds_how_long_results_are_held_back() {
+        let tree = TempTree::new("target-latency");
+        for i in 0..5 {
+            tree.sample("sample_640x426.jpg", &format!("{}.jpg", i));
+        }
+        let first = |walker: MediaWalker| {
+            let mut handle = walker
+                .shuffle(7)
+                .throttle(Duration::from_millis(50))
+                .start();
+            handle.next().unwrap();
+            let files = handle.stats().files;
+            assert_eq!(handle.count(), 4);
+            files
+        };
+        assert_eq!(first(MediaWalker::new(tree.path())), 5);
+        let walker = MediaWalker::new(tree.path()).target_latency(Duration::from_millis(1));
+        assert!(first(walker) < 5);
+    }
+
+    #[test]
+    fn it_applies_presets() {
+        let tree = TempTree::new("presets");
+        tree.sample("sample_640x426.jpg", "photos/a.JPG");
+        tree.sample("sample_640x426.jpg", "photos/.thumbnails/a.jpg");
+        tree.file("photos/icon.png", b"\x89PNG\r\n\x1a\n tiny");
+        tree.sample("sample3.mp3", "music/b.mp3");
+        tree.file("music/b.cue", b"FILE \"b.mp3\" MP3\n");
+
+        let paths = |preset| {
+            let mut paths: Vec<_> = MediaWalker::new(tree.path())
+                .preset(preset)
+                .start()
+                .filter(|r| matches!(r.result, Ok(true)))
+                .map(|r| r.path)
+                .collect();
+            paths.sort();
+            paths
+        };
+        let photos = paths(Preset::Photos);
+        assert_eq!(photos.len(), 1);
+        assert!(photos[0].ends_with("a.JPG"));
+        assert_eq!(paths(Preset::Music).len(), 2);
+        assert_eq!(paths(Preset::Everything).len(), 3);
+    }
+
+    #[test]
+    fn it_treats_bundles_as_files() {
+        let tree = TempTree::new("bundles");
+        tree.sample("sample_640x426.jpg", "Pictures/a.jpg");
+        tree.sample(
+            "sample_640x426.jpg",
+            "Pictures/Photos Library.photoslibrary/b.jpg",
+        );
+        tree.sample("sample3.mp3", "Applications/Player.app/Contents/c.mp3");
+
+        assert_eq!(MediaWalker::new(tree.path()).start().count(), 3);
+        assert_eq!(
+            MediaWalker::new(tree.path())
+                .treat_bundles_as_files(true)
+                .start()
+                .count(),
+            1
+        );
+
+        let mut handle = MediaWalker::new(tree.path()).report_bundles(true).start();
+        let mut bundles: Vec<_> = handle
+            .events()
+            .filter_map(|event| match event {
+                WalkEvent::Bundle { path } => Some(path.file_name().unwrap().to_owned()),
+                _ => None,
+            })
+            .collect();
+        bundles.sort();
+        assert_eq!(bundles, ["Photos Library.photoslibrary", "Player.app"]);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn it_skips_junctions() {
+        let tree = TempTree::new("junctions");
+        tree.sample("sample_640x426.jpg", "photos/a.jpg");
+        let status = std::process::Command::new("cmd")
+            .args(["/C", "mklink", "/J"])
+            .arg(tree.path().join("photos").join("loop"))
+            .arg(tree.path().join("photos"))
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let mut handle = MediaWalker::new(tree.path()).start();
+        let events: Vec<_> = handle.events().collect();
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().any(|event| matches!(
+            event,
+            WalkEvent::Skipped {
+                reason: SkipReason::ReparsePoint,
+                ..
+            }
+        )));
+
+        let found = MediaWalker::new(tree.path())
+            .follow_reparse_points(true)
+            .start()
+            .filter(|r| r.path.contains("loop"))
+            .count();
+        assert_eq!(found, 1);
+    }
+}
@@ -0,0 +1,839 @@
+//! Watching a tree for files coming and going on Linux, behind the
+//! `watch` feature, see [`MediaWalker::watch`]. A whole filesystem is
+//! watched with a single fanotify mark where the process has the
+//! capabilities for it, and each directory with an inotify watch
+//! otherwise.
+use crate::{MediaWalkResult, MediaWalker};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::{CString, OsStr};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::os::raw::{c_char, c_int, c_uint, c_ulong};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+use walkdir::WalkDir;
+
+extern "C" {
+    fn inotify_init1(flags: c_int) -> c_int;
+    fn inotify_add_watch(fd: c_int, path: *const c_char, mask: u32) -> c_int;
+    fn fanotify_init(flags: c_uint, event_f_flags: c_uint) -> c_int;
+    fn fanotify_mark(
+        fd: c_int,
+        flags: c_uint,
+        mask: u64,
+        dirfd: c_int,
+        path: *const c_char,
+    ) -> c_int;
+    fn open_by_handle_at(mount_fd: c_int, handle: *mut u32, flags: c_int) -> c_int;
+    fn poll(fds: *mut PollFd, count: c_ulong, timeout: c_int) -> c_int;
+}
+
+#[repr(C)]
+struct PollFd {
+    fd: c_int,
+    events: i16,
+    revents: i16,
+}
+
+const POLLIN: i16 = 0x1;
+const O_CLOEXEC: c_int = 0o2_000_000;
+const O_NONBLOCK: c_int = 0o4000;
+const O_PATH: c_int = 0o10_000_000;
+const AT_FDCWD: c_int = -100;
+
+const IN_CLOSE_WRITE: u32 = 0x8;
+const IN_MOVED_FROM: u32 = 0x40;
+const IN_MOVED_TO: u32 = 0x80;
+const IN_CREATE: u32 = 0x100;
+const IN_DELETE: u32 = 0x200;
+const IN_Q_OVERFLOW: u32 = 0x4000;
+const IN_IGNORED: u32 = 0x8000;
+const IN_ONLYDIR: u32 = 0x0100_0000;
+const IN_EXCL_UNLINK: u32 = 0x0400_0000;
+const IN_ISDIR: u32 = 0x4000_0000;
+
+const FAN_CLASS_NOTIF: c_uint = 0;
+const FAN_CLOEXEC: c_uint = 0x1;
+const FAN_NONBLOCK: c_uint = 0x2;
+const FAN_REPORT_DFID_NAME: c_uint = 0x400 | 0x800;
+const FAN_MARK_ADD: c_uint = 0x1;
+const FAN_MARK_FILESYSTEM: c_uint = 0x100;
+const FAN_CLOSE_WRITE: u64 = 0x8;
+const FAN_MOVED_FROM: u64 = 0x40;
+const FAN_MOVED_TO: u64 = 0x80;
+const FAN_CREATE: u64 = 0x100;
+const FAN_DELETE: u64 = 0x200;
+const FAN_Q_OVERFLOW: u64 = 0x4000;
+const FAN_ONDIR: u64 = 0x4000_0000;
+const FAN_EVENT_INFO_TYPE_DFID_NAME: u8 = 2;
+
+/// How long to wait for changes at a time, before looking whether the
+/// watch was stopped.
+const IDLE_POLL: Duration = Duration::from_millis(100);
+
+/// How many bytes of events are read at a time.
+const EVENT_BUFFER: usize = 64 * 1024;
+
+/// How many of the files it reported last a watch remembers, see
+/// [`Recent`].
+const RECENT_FILES: usize = 64 * 1024;
+
+/// Something that changed under a watched root, see [`Watcher`].
+#[derive(Debug)]
+// Nearly all events are found files, which are not worth boxing.
+#[allow(clippy::large_enum_variant)]
+pub enum WatchEvent {
+    /// A file found by the walk of the root before the watch, see
+    /// [`MediaWalker::scan_and_watch`].
+    Found(MediaWalkResult),
+    /// A file was created or moved under the root, with its result as a
+    /// walk would have it.
+    Added(MediaWalkResult),
+    /// A file or a directory was deleted or moved away. Whatever was
+    /// under a directory is gone with it. As there is no telling what a
+    /// file was once it is gone, any file may be reported, not only
+    /// media.
+    Removed(String),
+    /// A file was written to.
+    Modified(MediaWalkResult),
+    /// Changes were lost, as the kernel's queue of them overflowed: the
+    /// root should be walked again to catch up.
+    Overflowed,
+}
+
+/// How a [`Watcher`] is told of changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WatchBackend {
+    /// A single fanotify mark for the root's whole filesystem, which
+    /// needs the `CAP_SYS_ADMIN` and `CAP_DAC_READ_SEARCH` capabilities.
+    /// The changes outside of the root are looked at and left out.
+    Fanotify,
+    /// An inotify watch for each directory under the root, of which
+    /// there may be as many as `fs.inotify.max_user_watches` allows.
+    Inotify,
+}
+
+/// A watch of a tree, see [`MediaWalker::watch`]. Iterate over it to
+/// receive the changes as they happen. Dropping it stops the watch.
+pub struct Watcher {
+    rx: Receiver<WatchEvent>,
+    stopped: Arc<AtomicBool>,
+    backend: WatchBackend,
+}
+
+impl Watcher {
+    /// How the changes are watched for.
+    pub fn backend(&self) -> WatchBackend {
+        self.backend
+    }
+
+    /// Waits up to `timeout` for the next change. `None` if there was
+    /// none, or the watch has ended.
+    pub fn next_timeout(&self, timeout: Duration) -> Option<WatchEvent> {
+        self.rx.recv_timeout(timeout).ok()
+    }
+
+    /// Stops the watch. Changes already on their way may still be
+    /// received.
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+impl fmt::Debug for Watcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Watcher")
+            .field("backend", &self.backend)
+            .field("stopped", &self.stopped)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Iterator for Watcher {
+    type Item = WatchEvent;
+
+    fn next(&mut self) -> Option<WatchEvent> {
+        self.rx.recv().ok()
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Starts watching the root of a walker through the given backend, or
+/// through fanotify if it can be set up and inotify else, walking the
+/// root first if asked to.
+pub(crate) fn watch(
+    options: MediaWalker,
+    backend: Option<WatchBackend>,
+    scan: bool,
+) -> io::Result<Watcher> {
+    let root = options.root();
+    let source: Box<dyn Source> = match backend {
+        Some(WatchBackend::Fanotify) => Box::new(Fanotify::new(root)?),
+        Some(WatchBackend::Inotify) => Box::new(Inotify::new(&options)?),
+        None => match Fanotify::new(root) {
+            Ok(fanotify) => Box::new(fanotify),
+            Err(_) => Box::new(Inotify::new(&options)?),
+        },
+    };
+    let backend = source.backend();
+    let (tx, rx) = mpsc::channel();
+    let stopped = Arc::new(AtomicBool::new(false));
+    let watch = Watch {
+        options,
+        source,
+        tx,
+        stopped: Arc::clone(&stopped),
+        created: HashSet::new(),
+        reported: HashSet::new(),
+        recent: Recent::default(),
+    };
+    thread::spawn(move || watch.run(scan));
+    Ok(Watcher {
+        rx,
+        stopped,
+        backend,
+    })
+}
+
+/// A change as a backend saw it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Change {
+    path: PathBuf,
+    is_dir: bool,
+    what: What,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum What {
+    Created,
+    Written,
+    MovedIn,
+    Gone,
+}
+
+/// Where changes come from.
+trait Source: Send {
+    fn backend(&self) -> WatchBackend;
+
+    /// Waits up to `timeout` for changes. Returns `None` for changes that
+    /// were lost.
+    fn changes(&mut self, timeout: Duration) -> io::Result<Option<Vec<Change>>>;
+
+    /// Starts watching a directory that appeared under the root, and the
+    /// ones under it, for the backends that watch each.
+    fn add_dirs(&mut self, _options: &MediaWalker, _dir: &Path) {}
+}
+
+/// The state of a running watch.
+struct Watch {
+    options: MediaWalker,
+    source: Box<dyn Source>,
+    tx: Sender<WatchEvent>,
+    stopped: Arc<AtomicBool>,
+    /// Files created but not yet written and closed, to be reported as
+    /// added once they are.
+    created: HashSet<PathBuf>,
+    /// The files reported for the changes read last, reported once only.
+    /// The directories of fanotify changes are found as they are when
+    /// read, so a file made in a directory that was then moved in may be
+    /// seen created in it as well as moved in with it.
+    reported: HashSet<PathBuf>,
+    recent: Recent,
+}
+
+impl Watch {
+    fn run(mut self, scan: bool) {
+        if scan {
+            for walk_result in self.options.clone().start() {
+                if self.stopped.load(Ordering::Relaxed)
+                    || !self.report(walk_result, WatchEvent::Found)
+                {
+                    return;
+                }
+            }
+        }
+        while !self.stopped.load(Ordering::Relaxed) {
+            let changes = match self.source.changes(IDLE_POLL) {
+                Ok(Some(changes)) => changes,
+                Ok(None) => {
+                    self.created.clear();
+                    if self.tx.send(WatchEvent::Overflowed).is_err() {
+                        return;
+                    }
+                    continue;
+                }
+                Err(_) => return,
+            };
+            self.reported.clear();
+            for change in changes {
+                if !self.apply(change) {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Reports a change, returning `false` once no one is listening.
+    fn apply(&mut self, change: Change) -> bool {
+        let Change { path, is_dir, what } = change;
+        let Some(parent) = path.parent() else {
+            return true;
+        };
+        if !self.options.is_walked_dir(parent) {
+            return true;
+        }
+        if !is_dir && what != What::Gone && self.reported.contains(&path) {
+            return true;
+        }
+        match what {
+            What::Gone => {
+                self.created.remove(&path);
+                self.reported.remove(&path);
+                self.recent.forget(&path);
+                let path = path.to_string_lossy().into_owned();
+                self.send(WatchEvent::Removed(path))
+            }
+            What::Created | What::MovedIn if is_dir => {
+                if !self.options.is_walked_dir(&path) {
+                    return true;
+                }
+                self.source.add_dirs(&self.options, &path);
+                // The files moved in with the directory, or created in it
+                // before it was watched.
+                let files: Vec<_> = WalkDir::new(&path)
+                    .min_depth(1)
+                    .into_iter()
+                    .filter_entry(|entry| {
+                        !entry.file_type().is_dir() || self.options.is_walked_dir(entry.path())
+                    })
+                    .filter_map(Result::ok)
+                    .filter(|entry| !entry.file_type().is_dir())
+                    .map(|entry| entry.into_path())
+                    .filter(|file| !self.reported.contains(file))
+                    .collect();
+                files
+                    .into_iter()
+                    .all(|file| self.inspect(&file, WatchEvent::Added))
+            }
+            What::Created => {
+                self.created.insert(path);
+                true
+            }
+            What::Written if self.created.remove(&path) => self.inspect(&path, WatchEvent::Added),
+            What::Written => self.inspect(&path, WatchEvent::Modified),
+            What::MovedIn => self.inspect(&path, WatchEvent::Added),
+        }
+    }
+
+    /// Inspects a file and reports it as `event`, if a walk would.
+    fn inspect(&mut self, path: &Path, event: fn(MediaWalkResult) -> WatchEvent) -> bool {
+        self.reported.insert(path.to_path_buf());
+        match self.options.inspect_file(path) {
+            Some(walk_result) => self.report(walk_result, event),
+            None => true,
+        }
+    }
+
+    /// Reports a result as `event`, unless the file was found or added
+    /// already and has not changed since. Files written to are reported
+    /// whatever their size and modification time, which may not tell
+    /// the change.
+    fn report(
+        &mut self,
+        walk_result: MediaWalkResult,
+        event: fn(MediaWalkResult) -> WatchEvent,
+    ) -> bool {
+        let seen = walk_result.result.is_ok() && self.recent.see(Path::new(&walk_result.path));
+        let event = event(walk_result);
+        if seen && !matches!(event, WatchEvent::Modified(_)) {
+            return true;
+        }
+        self.send(event)
+    }
+
+    fn send(&self, event: WatchEvent) -> bool {
+        self.tx.send(event).is_ok()
+    }
+}
+
+/// The files a watch reported last, with their identities, sizes and
+/// modification times, to leave out those reported again unchanged: a
+/// file made while the root is walked before the watch is seen by both,
+/// and a file may be seen moved in and written to in different reads of
+/// the changes. The least recently seen are forgotten first.
+#[derive(Default)]
+struct Recent {
+    files: HashMap<PathBuf, Seen>,
+    /// The files by when they were last seen.
+    order: BTreeMap<u64, PathBuf>,
+    tick: u64,
+}
+
+#[derive(PartialEq, Eq)]
+struct Seen {
+    id: (u64, u64),
+    len: u64,
+    modified: Option<SystemTime>,
+    tick: u64,
+}
+
+impl Recent {
+    /// Remembers a file as reported, returning whether it was already,
+    /// unchanged.
+    fn see(&mut self, path: &Path) -> bool {
+        let Ok(metadata) = fs::metadata(path) else {
+            return false;
+        };
+        self.tick += 1;
+        let seen = Seen {
+            id: (metadata.dev(), metadata.ino()),
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+            tick: self.tick,
+        };
+        let unchanged = match self.files.insert(path.to_path_buf(), seen) {
+            Some(old) => {
+                self.order.remove(&old.tick);
+                let new = &self.files[path];
+                (old.id, old.len, old.modified) == (new.id, new.len, new.modified)
+            }
+            None => false,
+        };
+        self.order.insert(self.tick, path.to_path_buf());
+        if self.files.len() > RECENT_FILES {
+            if let Some((_, oldest)) = self.order.pop_first() {
+                self.files.remove(&oldest);
+            }
+        }
+        unchanged
+    }
+
+    /// Forgets a file or a directory that is gone, and the files under
+    /// it.
+    fn forget(&mut self, path: &Path) {
+        let order = &mut self.order;
+        self.files.retain(|file, seen| {
+            let gone = file.starts_with(path);
+            if gone {
+                order.remove(&seen.tick);
+            }
+            !gone
+        });
+    }
+}
+
+/// Waits up to `timeout` for a file descriptor to be readable.
+fn wait(file: &File, timeout: Duration) -> io::Result<bool> {
+    let mut fd = PollFd {
+        fd: file.as_raw_fd(),
+        events: POLLIN,
+        revents: 0,
+    };
+    // SAFETY: polls a single valid pollfd.
+    let ready = unsafe { poll(&mut fd, 1, timeout.as_millis() as c_int) };
+    match ready {
+        -1 => {
+            let err = io::Error::last_os_error();
+            match err.kind() {
+                io::ErrorKind::Interrupted => Ok(false),
+                _ => Err(err),
+            }
+        }
+        0 => Ok(false),
+        _ => Ok(true),
+    }
+}
+
+/// Reads what events there are into a buffer.
+fn read_events(file: &mut File, buf: &mut [u8]) -> io::Result<usize> {
+    match file.read(buf) {
+        Ok(len) => Ok(len),
+        Err(err) if err.kind() == io::ErrorKind::WouldBlock => Ok(0),
+        Err(err) => Err(err),
+    }
+}
+
+fn c_path(path: &Path) -> io::Result<CString> {
+    CString::new(path.as_os_str().as_bytes()).map_err(|_| io::ErrorKind::InvalidInput.into())
+}
+
+/// A name in an event, up to the NUL that ends or pads it.
+fn event_name(bytes: &[u8]) -> &OsStr {
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    OsStr::from_bytes(&bytes[..end])
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_ne_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// Each directory under the root, watched through inotify.
+struct Inotify {
+    file: File,
+    /// The watched directories, by watch descriptor.
+    dirs: HashMap<c_int, PathBuf>,
+    buf: Vec<u8>,
+}
+
+impl Inotify {
+    const MASK: u32 = IN_CREATE
+        | IN_CLOSE_WRITE
+        | IN_MOVED_FROM
+        | IN_MOVED_TO
+        | IN_DELETE
+        | IN_ONLYDIR
+        | IN_EXCL_UNLINK;
+
+    fn new(options: &MediaWalker) -> io::Result<Self> {
+        // SAFETY: a plain syscall; the result is checked before use.
+        let fd = unsafe { inotify_init1(O_CLOEXEC | O_NONBLOCK) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut inotify = Inotify {
+            // SAFETY: the descriptor was just opened, and is owned here.
+            file: unsafe { File::from_raw_fd(fd) },
+            dirs: HashMap::new(),
+            buf: vec![0; EVENT_BUFFER],
+        };
+        inotify.add_dir(options.root())?;
+        inotify.add_dirs(options, options.root());
+        Ok(inotify)
+    }
+
+    fn add_dir(&mut self, dir: &Path) -> io::Result<()> {
+        let path = c_path(dir)?;
+        // SAFETY: watches a NUL-terminated path on an inotify descriptor.
+        let wd = unsafe { inotify_add_watch(self.file.as_raw_fd(), path.as_ptr(), Self::MASK) };
+        if wd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // A directory moved within the root keeps its watch, under its
+        // new path.
+        self.dirs.insert(wd, dir.to_path_buf());
+        Ok(())
+    }
+}
+
+impl Source for Inotify {
+    fn backend(&self) -> WatchBackend {
+        WatchBackend::Inotify
+    }
+
+    fn changes(&mut self, timeout: Duration) -> io::Result<Option<Vec<Change>>> {
+        if !wait(&self.file, timeout)? {
+            return Ok(Some(vec![]));
+        }
+        let len = read_events(&mut self.file, &mut self.buf)?;
+        let events = &self.buf[..len];
+        let mut changes = vec![];
+        let mut at = 0;
+        // Each event is a descriptor, a mask, a cookie and the length of
+        // the name that follows.
+        while let (Some(wd), Some(mask), Some(name_len)) = (
+            u32_at(events, at),
+            u32_at(events, at + 4),
+            u32_at(events, at + 12),
+        ) {
+            let name = events.get(at + 16..at + 16 + name_len as usize);
+            at += 16 + name_len as usize;
+            let wd = wd as c_int;
+            if mask & IN_Q_OVERFLOW != 0 {
+                return Ok(None);
+            }
+            if mask & IN_IGNORED != 0 {
+                self.dirs.remove(&wd);
+                continue;
+            }
+            let (Some(dir), Some(name)) = (self.dirs.get(&wd), name) else {
+                continue;
+            };
+            let what = if mask & IN_CREATE != 0 {
+                What::Created
+            } else if mask & IN_CLOSE_WRITE != 0 {
+                What::Written
+            } else if mask & IN_MOVED_TO != 0 {
+                What::MovedIn
+            } else if mask & (IN_MOVED_FROM | IN_DELETE) != 0 {
+                What::Gone
+            } else {
+                continue;
+            };
+            changes.push(Change {
+                path: dir.join(event_name(name)),
+                is_dir: mask & IN_ISDIR != 0,
+                what,
+            });
+        }
+        Ok(Some(changes))
+    }
+
+    fn add_dirs(&mut self, options: &MediaWalker, dir: &Path) {
+        let dirs = WalkDir::new(dir)
+            .into_iter()
+            .filter_entry(|entry| entry.file_type().is_dir() && options.is_walked_dir(entry.path()))
+            .filter_map(Result::ok);
+        for entry in dirs {
+            // A directory gone already is reported as such.
+            let _ = self.add_dir(entry.path());
+        }
+    }
+}
+
+/// The filesystem of the root, watched through fanotify.
+struct Fanotify {
+    file: File,
+    /// The root, to open the directories of changes by their handles.
+    mount: File,
+    /// The root as it was given, and as it is on disk, which the
+    /// directories of changes are found as.
+    root: PathBuf,
+    canonical: PathBuf,
+    buf: Vec<u8>,
+}
+
+impl Fanotify {
+    const MASK: u64 =
+        FAN_CREATE | FAN_CLOSE_WRITE | FAN_MOVED_FROM | FAN_MOVED_TO | FAN_DELETE | FAN_ONDIR;
+
+    fn new(root: &Path) -> io::Result<Self> {
+        // SAFETY: a plain syscall; the result is checked before use.
+        let fd = unsafe {
+            fanotify_init(
+                FAN_CLASS_NOTIF | FAN_CLOEXEC | FAN_NONBLOCK | FAN_REPORT_DFID_NAME,
+                O_CLOEXEC as c_uint,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: the descriptor was just opened, and is owned here.
+        let file = unsafe { File::from_raw_fd(fd) };
+        let path = c_path(root)?;
+        let flags = FAN_MARK_ADD | FAN_MARK_FILESYSTEM;
+        // SAFETY: marks a NUL-terminated path on a fanotify descriptor.
+        if unsafe { fanotify_mark(fd, flags, Self::MASK, AT_FDCWD, path.as_ptr()) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Fanotify {
+            file,
+            mount: File::open(root)?,
+            root: root.to_path_buf(),
+            canonical: fs::canonicalize(root)?,
+            buf: vec![0; EVENT_BUFFER],
+        })
+    }
+
+    /// The path of a change, from the directory handle and the name of
+    /// an info record, if it is under the root.
+    fn path(&self, info: &[u8]) -> Option<PathBuf> {
+        // A header, the filesystem ID, then the handle of the directory
+        // and the name.
+        let handle_len = u32_at(info, 12)? as usize;
+        let handle = info.get(12..20 + handle_len)?;
+        let name = event_name(info.get(20 + handle_len..)?);
+        let mut aligned = vec![0u32; handle.len().div_ceil(4)];
+        for (i, byte) in handle.iter().enumerate() {
+            aligned[i / 4] |= u32::from(*byte) << (8 * (i % 4));
+        }
+        if cfg!(target_endian = "big") {
+            for word in &mut aligned {
+                *word = word.swap_bytes();
+            }
+        }
+        // SAFETY: `aligned` holds a file_handle as the kernel gave it.
+        let fd = unsafe {
+            open_by_handle_at(
+                self.mount.as_raw_fd(),
+                aligned.as_mut_ptr(),
+                O_PATH | O_CLOEXEC,
+            )
+        };
+        if fd < 0 {
+            return None;
+        }
+        // SAFETY: the descriptor was just opened, and is closed on drop.
+        let dir = unsafe { File::from_raw_fd(fd) };
+        let dir = fs::read_link(format!("/proc/self/fd/{}", dir.as_raw_fd())).ok()?;
+        let relative = dir.strip_prefix(&self.canonical).ok()?;
+        Some(self.root.join(relative).join(name))
+    }
+}
+
+impl Source for Fanotify {
+    fn backend(&self) -> WatchBackend {
+        WatchBackend::Fanotify
+    }
+
+    fn changes(&mut self, timeout: Duration) -> io::Result<Option<Vec<Change>>> {
+        if !wait(&self.file, timeout)? {
+            return Ok(Some(vec![]));
+        }
+        let len = read_events(&mut self.file, &mut self.buf)?;
+        let mut changes = vec![];
+        let mut at = 0;
+        // Each event is its length, a version, the length of the fixed
+        // part and a mask, then info records up to its length.
+        while let Some(event_len) = u32_at(&self.buf[..len], at) {
+            let event = &self.buf[at..len.min(at + event_len as usize)];
+            at += (event_len as usize).max(1);
+            let (Some(meta_len), Some(mask)) = (event.get(6..8), event.get(8..16)) else {
+                continue;
+            };
+            let meta_len = u16::from_ne_bytes([meta_len[0], meta_len[1]]) as usize;
+            let mask = u64::from_ne_bytes(mask.try_into().unwrap());
+            if mask & FAN_Q_OVERFLOW != 0 {
+                return Ok(None);
+            }
+            let mut info_at = meta_len;
+            let mut path = None;
+            while let Some(header) = event.get(info_at..info_at + 4) {
+                let info_len = u16::from_ne_bytes([header[2], header[3]]) as usize;
+                if header[0] == FAN_EVENT_INFO_TYPE_DFID_NAME {
+                    path = event
+                        .get(info_at..info_at + info_len)
+                        .and_then(|i| self.path(i));
+                }
+                info_at += info_len.max(4);
+            }
+            let Some(path) = path else {
+                continue;
+            };
+            let is_dir = mask & FAN_ONDIR != 0;
+            // Events on the same file may be merged, in the order they
+            // happen in.
+            let whats = [
+                (FAN_CREATE, What::Created),
+                (FAN_MOVED_TO, What::MovedIn),
+                (FAN_CLOSE_WRITE, What::Written),
+                (FAN_MOVED_FROM | FAN_DELETE, What::Gone),
+            ];
+            for (bits, what) in whats {
+                if mask & bits != 0 {
+                    changes.push(Change {
+                        path: path.clone(),
+                        is_dir,
+                        what,
+                    });
+                }
+            }
+        }
+        Ok(Some(changes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempTree;
+    use crate::Kind;
+
+    /// The changes a watcher reports within a second, by what they are
+    /// and the path relative to `root`.
+    fn changes(watcher: &Watcher, root: &Path, count: usize) -> Vec<(&'static str, String)> {
+        let relative = |path: &str| {
+            path.strip_prefix(root.to_str().unwrap())
+                .unwrap()
+                .to_string()
+        };
+        let mut changes = vec![];
+        while changes.len() < count {
+            let Some(event) = watcher.next_timeout(Duration::from_secs(1)) else {
+                break;
+            };
+            changes.push(match event {
+                WatchEvent::Found(r) => ("found", relative(&r.path)),
+                WatchEvent::Added(r) => ("added", relative(&r.path)),
+                WatchEvent::Modified(r) => ("modified", relative(&r.path)),
+                WatchEvent::Removed(path) => ("removed", relative(&path)),
+                WatchEvent::Overflowed => ("overflowed", String::new()),
+            });
+        }
+        changes
+    }
+
+    #[test]
+    fn it_watches_for_changes() {
+        let tree = TempTree::new("watch");
+        let outside = TempTree::new("watch-outside");
+        tree.sample("sample3.mp3", "old.mp3");
+        tree.file("skip/.keep", b"");
+        let mut backends = vec![WatchBackend::Inotify];
+        if Fanotify::new(tree.path()).is_ok() {
+            backends.push(WatchBackend::Fanotify);
+        }
+        for backend in backends {
+            let walker = MediaWalker::new(tree.path())
+                .kinds(&[Kind::Image, Kind::Audio])
+                .exclude("/skip");
+            let watcher = watch(walker, Some(backend), false).unwrap();
+            assert_eq!(watcher.backend(), backend);
+            tree.sample("sample_640x426.jpg", "new.jpg");
+            tree.sample("sample_640x426.jpg", "skip/new.jpg");
+            tree.sample("sample_640x360.mp4", "video.mp4");
+            tree.sample("sample3.mp3", "old.mp3");
+            fs::remove_file(tree.path().join("new.jpg")).unwrap();
+            fs::create_dir(tree.path().join("moved")).unwrap();
+            outside.sample("sample_640x426.jpg", "a/b.jpg");
+            fs::rename(outside.path().join("a"), tree.path().join("moved/a")).unwrap();
+            assert_eq!(
+                changes(&watcher, tree.path(), 4),
+                [
+                    ("added", "/new.jpg".into()),
+                    ("modified", "/old.mp3".into()),
+                    ("removed", "/new.jpg".into()),
+                    ("added", "/moved/a/b.jpg".into()),
+                ],
+                "{:?}",
+                backend
+            );
+            // Directories moved in are watched too.
+            tree.sample("sample_640x426.jpg", "moved/a/c.jpg");
+            assert_eq!(
+                changes(&watcher, tree.path(), 1),
+                [("added", "/moved/a/c.jpg".into())],
+                "{:?}",
+                backend
+            );
+            drop(watcher);
+            fs::remove_dir_all(tree.path().join("moved")).unwrap();
+        }
+    }
+
+    #[test]
+    fn it_reports_files_once_when_walking_then_watching() {
+        let tree = TempTree::new("scan-and-watch");
+        let old = tree.sample("sample_640x426.jpg", "old.jpg");
+        let watcher = MediaWalker::new(tree.path()).scan_and_watch().unwrap();
+        tree.sample("sample3.mp3", "new.mp3");
+        let changes = changes(&watcher, tree.path(), 3);
+        assert!(changes.contains(&("found", "/old.jpg".into())));
+        let new: Vec<_> = changes
+            .iter()
+            .filter(|(_, path)| path == "/new.mp3")
+            .collect();
+        assert_eq!(new.len(), 1, "{:?}", changes);
+
+        let mut recent = Recent::default();
+        assert!(!recent.see(&old));
+        assert!(recent.see(&old));
+        recent.forget(tree.path());
+        assert!(!recent.see(&old));
+        fs::write(&old, b"edited").unwrap();
+        assert!(!recent.see(&old));
+        assert_eq!((recent.files.len(), recent.order.len()), (1, 1));
+    }
+}
@@ -0,0 +1,211 @@
+//! Extended attributes: Finder tags and labels on macOS, freedesktop
+//! tags on Linux, and any other attributes asked for.
+use std::path::Path;
+
+/// The Finder tags, a binary property list of strings.
+const FINDER_TAGS: &str = "com.apple.metadata:_kMDItemUserTags";
+/// The Finder info, which has the color label.
+const FINDER_INFO: &str = "com.apple.FinderInfo";
+/// The freedesktop.org tags, a comma-separated list.
+const XDG_TAGS: &str = "user.xdg.tags";
+
+/// The extended attributes of a file, see
+/// [`MediaWalker::capture_xattrs`](crate::MediaWalker::capture_xattrs).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Xattrs {
+    /// The user's tags: Finder tags on macOS, `user.xdg.tags` on Linux.
+    pub tags: Vec<String>,
+    /// The Finder color label, from 1 to 7, if one is set.
+    pub label: Option<u8>,
+    /// The other attributes asked for, by name, with their raw values.
+    pub attributes: Vec<(String, Vec<u8>)>,
+}
+
+impl Xattrs {
+    /// Reads the tags, the label and the given attributes of a file.
+    /// Returns `None` if the file has none of them.
+    pub(crate) fn read(path: &Path, names: &[String]) -> Option<Xattrs> {
+        let mut xattrs = Xattrs::default();
+        if let Some(tags) = sys::get(path, FINDER_TAGS) {
+            xattrs.tags = parse_finder_tags(&tags).unwrap_or_default();
+        } else if let Some(tags) = sys::get(path, XDG_TAGS) {
+            xattrs.tags = String::from_utf8_lossy(&tags)
+                .split(',')
+                .map(str::trim)
+                .filter(|tag| !tag.is_empty())
+                .map(str::to_string)
+                .collect();
+        }
+        if let Some(info) = sys::get(path, FINDER_INFO) {
+            // The label is in bits 1–3 of the Finder flags' low byte.
+            xattrs.label = info
+                .get(9)
+                .map(|flags| (flags >> 1) & 0x07)
+                .filter(|l| *l != 0);
+        }
+        for name in names {
+            if let Some(value) = sys::get(path, name) {
+                xattrs.attributes.push((name.clone(), value));
+            }
+        }
+        (xattrs != Xattrs::default()).then_some(xattrs)
+    }
+}
+
+/// Parses the Finder tags: a binary property list with an array of
+/// strings like `"Red\n6"`, a name and a color number.
+fn parse_finder_tags(plist: &[u8]) -> Option<Vec<String>> {
+    if !plist.starts_with(b"bplist00") || plist.len() < 40 {
+        return None;
+    }
+    let trailer = &plist[plist.len() - 32..];
+    let offset_size = trailer[6] as usize;
+    let ref_size = trailer[7] as usize;
+    let objects = be_int(&trailer[8..16])? as usize;
+    let top = be_int(&trailer[16..24])? as usize;
+    let table = be_int(&trailer[24..32])? as usize;
+    let offset = |object: usize| -> Option<usize> {
+        if object >= objects {
+            return None;
+        }
+        let start = table.checked_add(object.checked_mul(offset_size)?)?;
+        be_int(plist.get(start..start + offset_size)?).map(|o| o as usize)
+    };
+
+    let array = offset(top)?;
+    let (marker, count, mut refs) = object_header(plist, array)?;
+    if marker != 0xA {
+        return None;
+    }
+    let mut tags = vec![];
+    for _ in 0..count {
+        let object = be_int(plist.get(refs..refs + ref_size)?)? as usize;
+        refs += ref_size;
+        let start = offset(object)?;
+        let (marker, len, data) = object_header(plist, start)?;
+        let tag = match marker {
+            0x5 => String::from_utf8_lossy(plist.get(data..data + len)?).into_owned(),
+            0x6 => {
+                let units: Vec<u16> = plist
+                    .get(data..data + 2 * len)?
+                    .chunks(2)
+                    .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+                    .collect();
+                String::from_utf16_lossy(&units)
+            }
+            _ => continue,
+        };
+        // Drop the color number.
+        let name = tag.split('\n').next().unwrap_or("").to_string();
+        tags.push(name);
+    }
+    Some(tags)
+}
+
+/// Reads the header of a property list object: the type marker, the
+/// length, and where the data starts.
+fn object_header(plist: &[u8], start: usize) -> Option<(u8, usize, usize)> {
+    let byte = *plist.get(start)?;
+    let (marker, len) = (byte >> 4, (byte & 0x0F) as usize);
+    if len != 0x0F {
+        return Some((marker, len, start + 1));
+    }
+    // A longer length follows as an integer object.
+    let int = *plist.get(start + 1)?;
+    if int >> 4 != 0x1 {
+        return None;
+    }
+    let size = 1usize << (int & 0x0F);
+    let len = be_int(plist.get(start + 2..start + 2 + size)?)? as usize;
+    Some((marker, len, start + 2 + size))
+}
+
+fn be_int(bytes: &[u8]) -> Option<u64> {
+    (bytes.len() <= 8).then(|| bytes.iter().fold(0, |acc, b| acc << 8 | *b as u64))
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+mod sys {
+    use std::ffi::CString;
+    use std::os::raw::{c_char, c_int, c_void};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    extern "C" {
+        #[cfg(target_os = "linux")]
+        fn getxattr(
+            path: *const c_char,
+            name: *const c_char,
+            value: *mut c_void,
+            size: usize,
+        ) -> isize;
+        #[cfg(target_os = "macos")]
+        fn getxattr(
+            path: *const c_char,
+            name: *const c_char,
+            value: *mut c_void,
+            size: usize,
+            position: u32,
+            options: c_int,
+        ) -> isize;
+    }
+
+    unsafe fn get_raw(path: &CString, name: &CString, value: *mut c_void, size: usize) -> isize {
+        #[cfg(target_os = "linux")]
+        return getxattr(path.as_ptr(), name.as_ptr(), value, size);
+        #[cfg(target_os = "macos")]
+        return getxattr(path.as_ptr(), name.as_ptr(), value, size, 0, 0);
+    }
+
+    /// Reads an extended attribute, `None` if the file does not have it.
+    pub(super) fn get(path: &Path, name: &str) -> Option<Vec<u8>> {
+        let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+        let name = CString::new(name).ok()?;
+        // SAFETY: both strings are NUL-terminated, and a null buffer asks
+        // for the size of the value.
+        let size = unsafe { get_raw(&path, &name, std::ptr::null_mut(), 0) };
+        if size < 0 {
+            return None;
+        }
+        let mut value = vec![0u8; size as usize];
+        // SAFETY: `value` has room for `size` bytes.
+        let read = unsafe { get_raw(&path, &name, value.as_mut_ptr().cast(), value.len()) };
+        if read < 0 {
+            return None;
+        }
+        value.truncate(read as usize);
+        Some(value)
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+mod sys {
+    use std::path::Path;
+
+    pub(super) fn get(_path: &Path, _name: &str) -> Option<Vec<u8>> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_finder_tags() {
+        let mut plist = b"bplist00".to_vec();
+        plist.extend([0xA2, 1, 2]);
+        plist.extend(b"\x55Red\n6");
+        plist.extend(b"\x54Work");
+        plist.extend([8, 11, 17]);
+        plist.extend([0, 0, 0, 0, 0, 0, 1, 1]);
+        plist.extend(3u64.to_be_bytes());
+        plist.extend(0u64.to_be_bytes());
+        plist.extend(22u64.to_be_bytes());
+        assert_eq!(
+            parse_finder_tags(&plist),
+            Some(vec!["Red".to_string(), "Work".to_string()])
+        );
+        assert_eq!(parse_finder_tags(b"not a plist"), None);
+    }
+}